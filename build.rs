@@ -8,6 +8,14 @@ use std::path::{Path, PathBuf};
 fn main() {
     r2r_common::print_cargo_watches();
 
+    // rcl differs between distros, see src/distro.rs.
+    println!("cargo:rerun-if-env-changed=ROS_DISTRO");
+    let distro = env::var("ROS_DISTRO").unwrap_or_else(|_| "unknown".to_owned());
+    if distro.chars().all(|c| c.is_ascii_alphanumeric()) {
+        println!("cargo:rustc-cfg=r2r__ros__distro__{}", distro);
+    }
+    println!("cargo:rustc-env=R2R_ROS_DISTRO={}", distro);
+
     let msg_list = if let Some(cmake_includes) = env::var("CMAKE_INCLUDE_DIRS").ok() {
         let packages = cmake_includes
             .split(":")
@@ -29,6 +37,15 @@ fn main() {
         let msgs = r2r_common::get_ros_msgs(&paths);
         r2r_common::parse_msgs(&msgs)
     };
+    // packages left out of the generated types, which can still be used
+    // with type support loaded at runtime (see src/typesupport_loader.rs).
+    println!("cargo:rerun-if-env-changed=R2R_SKIP_IDL_PACKAGES");
+    let skipped = env::var("R2R_SKIP_IDL_PACKAGES").unwrap_or_default();
+    let skipped = skipped.split(":").collect::<Vec<_>>();
+    let msg_list = msg_list
+        .into_iter()
+        .filter(|msg| !skipped.contains(&msg.module.as_str()))
+        .collect::<Vec<_>>();
     let msgs = r2r_common::as_map(&msg_list);
 
     let mut modules = String::new();
@@ -20,14 +20,25 @@ async fn run_goal(
     let mut feedback_msg = Fibonacci::Feedback {
         sequence: vec![0, 1],
     };
-    g.publish_feedback(feedback_msg.clone()).expect("fail");
+    if let Err(e) = g.publish_feedback(feedback_msg.clone()) {
+        // e.g. the client is gone, no need to continue. the goal still
+        // finishes, with what was computed so far.
+        println!("stopping goal: {}", e);
+        return Fibonacci::Result {
+            sequence: feedback_msg.sequence,
+        };
+    }
 
     let order = g.goal.order as usize;
     for i in 1..order {
         feedback_msg
             .sequence
             .push(feedback_msg.sequence[i] + feedback_msg.sequence[i - 1]);
-        g.publish_feedback(feedback_msg.clone()).expect("fail");
+        if let Err(e) = g.publish_feedback(feedback_msg.clone()) {
+            // e.g. the client is gone, no need to continue.
+            println!("stopping goal: {}", e);
+            break;
+        }
         println!("Sending feedback: {:?}", feedback_msg);
         timer.tick().await.unwrap();
     }
@@ -76,6 +87,8 @@ async fn fibonacci_server(
                         if let Some(request) = request {
                             println!("got cancel request: {}", request.uuid);
                             request.accept();
+                            g.canceled(Fibonacci::Result::default())
+                                .expect("could not send result");
                         }
                     }
                 };
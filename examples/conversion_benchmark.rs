@@ -0,0 +1,77 @@
+// Compares converting large messages in spin_once with converting them
+// on the conversion thread of the subscription, see
+// `SubscriptionOptions::offload_conversion`.
+//
+// For each mode, prints the longest time a single spin_once took, i.e.
+// how long other callbacks could have been held up, the latency from
+// publishing to the message arriving in the stream, and the throughput.
+
+use r2r;
+use r2r::sensor_msgs::msg::PointCloud2;
+use r2r::SubscriptionOptions;
+use std::time::{Duration, Instant};
+
+const MESSAGES: usize = 50;
+const CLOUD_BYTES: usize = 8 << 20;
+
+fn run(offload_conversion: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let name = if offload_conversion {
+        "conversion_benchmark_offloaded"
+    } else {
+        "conversion_benchmark_inline"
+    };
+    let mut node = r2r::Node::create(ctx, name, "")?;
+    let topic = format!("/{}", name);
+    let options = SubscriptionOptions {
+        offload_conversion,
+        ..Default::default()
+    };
+    let mut sub = node.subscribe_with_options::<PointCloud2>(&topic, options)?;
+    let publisher = node.create_publisher::<PointCloud2>(&topic)?;
+    for _ in 0..50 {
+        node.spin_once(Duration::from_millis(10));
+    }
+
+    let cloud = PointCloud2 {
+        data: vec![7; CLOUD_BYTES],
+        ..Default::default()
+    };
+    let mut longest_spin = Duration::from_secs(0);
+    let mut latency = Duration::from_secs(0);
+    let start = Instant::now();
+    for _ in 0..MESSAGES {
+        let published = Instant::now();
+        publisher.publish(&cloud)?;
+        loop {
+            // returns right away when there is nothing to do.
+            let spin_start = Instant::now();
+            node.spin_once(Duration::from_millis(0));
+            longest_spin = longest_spin.max(spin_start.elapsed());
+            if !sub.drain().is_empty() {
+                break;
+            }
+        }
+        latency += published.elapsed();
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{:>10}: longest spin {:>8.2?}, latency {:>8.2?}/msg, {:>6.1} msg/s",
+        if offload_conversion {
+            "offloaded"
+        } else {
+            "inline"
+        },
+        longest_spin,
+        latency / MESSAGES as u32,
+        MESSAGES as f64 / elapsed.as_secs_f64()
+    );
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    run(false)?;
+    run(true)?;
+    Ok(())
+}
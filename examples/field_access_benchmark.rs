@@ -0,0 +1,59 @@
+// Compares reading the stamp and the position of a native
+// PoseWithCovarianceStamped through a `FieldSelection`, as done by
+// `Node::subscribe_fields`, with converting the whole message.
+//
+// The message is small, but the 36 element covariance has to be copied
+// by a full conversion even when it is never looked at.
+
+use r2r;
+use r2r::geometry_msgs::msg::PoseWithCovarianceStamped;
+use r2r::{FieldSelection, NativeMsg, WrappedTypesupport};
+use std::time::{Duration, Instant};
+
+const ROUNDS: u32 = 100_000;
+
+fn time<F: FnMut() -> f64>(mut f: F) -> (Duration, f64) {
+    let mut checksum = 0.0;
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        checksum += f();
+    }
+    (start.elapsed() / ROUNDS, checksum)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut msg = PoseWithCovarianceStamped::default();
+    msg.header.stamp.sec = 1;
+    msg.header.frame_id = "map".into();
+    msg.pose.pose.position.x = 1.0;
+    msg.pose.pose.position.y = 2.0;
+    let native = NativeMsg::from(&msg);
+
+    let (full, a) = time(|| {
+        let msg = PoseWithCovarianceStamped::from_native(&native);
+        msg.header.stamp.sec as f64 + msg.pose.pose.position.x + msg.pose.pose.position.y
+    });
+
+    let selection = FieldSelection::<PoseWithCovarianceStamped>::new(&[
+        "header.stamp.sec",
+        "pose.pose.position.x",
+        "pose.pose.position.y",
+    ])?;
+    let (fields, b) = time(|| {
+        selection
+            .extract(&native)
+            .iter()
+            .map(|v| v.as_f64().or(v.as_i64().map(|i| i as f64)).unwrap_or(0.0))
+            .sum()
+    });
+
+    let selection =
+        FieldSelection::<PoseWithCovarianceStamped>::new(&["header.stamp", "pose.pose.position"])?;
+    let (messages, _) = time(|| selection.extract(&native).len() as f64);
+
+    assert_eq!(a, b);
+    println!("full conversion:         {:>10.2?}", full);
+    println!("three scalar fields:     {:>10.2?}", fields);
+    println!("stamp and position:      {:>10.2?}", messages);
+    Ok(())
+}
@@ -0,0 +1,75 @@
+// Compares relaying large messages with `Node::mirror_passthrough`,
+// which publishes the taken message as is, with `Node::mirror` and a
+// transformation that returns the message unchanged, which converts it
+// from and back to the native message.
+//
+// Prints the latency from publishing on the input topic to the message
+// arriving on the output topic and the throughput.
+
+use r2r;
+use r2r::sensor_msgs::msg::PointCloud2;
+use r2r::test_support::collect_n;
+use r2r::QosProfile;
+use std::time::{Duration, Instant};
+
+const MESSAGES: usize = 50;
+const CLOUD_BYTES: usize = 8 << 20;
+
+fn run(passthrough: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let name = if passthrough {
+        "mirror_benchmark_passthrough"
+    } else {
+        "mirror_benchmark_transform"
+    };
+    let mut node = r2r::Node::create(ctx, name, "")?;
+    let from = format!("/{}_in", name);
+    let to = format!("/{}_out", name);
+    let qos = QosProfile::default();
+    let mirror = if passthrough {
+        node.mirror_passthrough::<PointCloud2>(&from, &to, qos.clone(), qos)?
+    } else {
+        node.mirror::<PointCloud2, PointCloud2>(&from, &to, qos.clone(), qos, Some)?
+    };
+    // native, so that only the relay converts.
+    let mut sub = node.subscribe_native::<PointCloud2>(&to)?;
+    let publisher = node.create_publisher::<PointCloud2>(&from)?;
+    for _ in 0..50 {
+        node.spin_once(Duration::from_millis(10));
+    }
+
+    let cloud = PointCloud2 {
+        data: vec![7; CLOUD_BYTES],
+        ..Default::default()
+    };
+    let mut latency = Duration::from_secs(0);
+    let start = Instant::now();
+    for _ in 0..MESSAGES {
+        let published = Instant::now();
+        publisher.publish(&cloud)?;
+        if collect_n(&mut sub, 1, &mut node, Duration::from_secs(5)).is_empty() {
+            return Err("message not relayed".into());
+        }
+        latency += published.elapsed();
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{:>12}: latency {:>8.2?}/msg, {:>6.1} msg/s, {} relayed",
+        if passthrough {
+            "passthrough"
+        } else {
+            "transform"
+        },
+        latency / MESSAGES as u32,
+        MESSAGES as f64 / elapsed.as_secs_f64(),
+        mirror.sent().messages
+    );
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    run(true)?;
+    run(false)?;
+    Ok(())
+}
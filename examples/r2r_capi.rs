@@ -0,0 +1,9 @@
+//
+// The C API of r2r (see include/r2r.h) as a shared library, e.g. for
+// linking into a C or C++ application:
+//
+//   cargo build --features capi --example r2r_capi
+//
+// which gives target/debug/examples/libr2r_capi.so.
+//
+extern crate r2r;
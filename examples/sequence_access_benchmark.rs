@@ -0,0 +1,70 @@
+// Compares reading and building a native MarkerArray with 5000 markers
+// through the per element accessors, e.g. `markers_at` and
+// `markers_push`, with converting the whole message.
+//
+// Reading touches the id of every marker, like a visualization hot path
+// looking for the markers to update.
+
+use r2r;
+use r2r::visualization_msgs::msg::{Marker, MarkerArray};
+use r2r::{NativeMsg, WrappedTypesupport};
+use std::time::{Duration, Instant};
+
+const MARKERS: usize = 5000;
+const ROUNDS: u32 = 20;
+
+fn time<F: FnMut() -> i64>(mut f: F) -> (Duration, i64) {
+    let mut checksum = 0;
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        checksum += f();
+    }
+    (start.elapsed() / ROUNDS, checksum)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let markers = (0..MARKERS)
+        .map(|id| Marker {
+            id: id as i32,
+            ns: "benchmark".into(),
+            text: "some text that has to be copied".into(),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+    let msg = MarkerArray {
+        markers: markers.clone(),
+    };
+    let native = NativeMsg::from(&msg);
+
+    let (full, a) = time(|| {
+        let msg = MarkerArray::from_native(&native);
+        msg.markers.iter().map(|m| m.id as i64).sum()
+    });
+    let (lazy, b) = time(|| {
+        (0..native.markers_len())
+            .filter_map(|i| native.markers_at(i))
+            .map(|m| m.id as i64)
+            .sum()
+    });
+    assert_eq!(a, b);
+    println!("read ids,  full conversion: {:>10.2?}", full);
+    println!("read ids,  accessors:       {:>10.2?}", lazy);
+
+    let (full, _) = time(|| {
+        let mut msg = MarkerArray::default();
+        for m in &markers {
+            msg.markers.push(m.clone());
+        }
+        NativeMsg::from(&msg).markers_len() as i64
+    });
+    let (lazy, _) = time(|| {
+        let mut native = NativeMsg::<MarkerArray>::new();
+        for m in &markers {
+            native.markers_push(m);
+        }
+        native.markers_len() as i64
+    });
+    println!("build,     full conversion: {:>10.2?}", full);
+    println!("build,     accessors:       {:>10.2?}", lazy);
+    Ok(())
+}
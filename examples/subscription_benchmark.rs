@@ -0,0 +1,98 @@
+// Compares the ways of taking small messages from a subscription: as
+// a stream (the async path), with `Subscription::drain`, and from a
+// plain channel as subscriptions used before `Subscription`, to check
+// that the async path has not become slower.
+//
+// For each mode, prints the throughput of publishing batches of
+// messages and taking them all before the next batch.
+
+use futures::channel::mpsc;
+use futures::future::FutureExt;
+use futures::stream::StreamExt;
+use r2r;
+use r2r::std_msgs::msg::Int32;
+use r2r::{ChannelSink, SubscriptionOptions};
+use std::time::{Duration, Instant};
+
+const BATCHES: usize = 1000;
+const BATCH: usize = 10;
+
+#[derive(Debug, Clone, Copy)]
+enum Mode {
+    Stream,
+    Drain,
+    Channel,
+}
+
+fn run(mode: Mode) -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let name = format!("subscription_benchmark_{:?}", mode).to_lowercase();
+    let mut node = r2r::Node::create(ctx, &name, "")?;
+    let topic = format!("/{}", name);
+
+    // takes the messages that have arrived, returns how many.
+    let mut take: Box<dyn FnMut() -> usize> = match mode {
+        Mode::Stream => {
+            let mut sub = node.subscribe::<Int32>(&topic)?;
+            Box::new(move || {
+                let mut n = 0;
+                while let Some(Some(_)) = sub.next().now_or_never() {
+                    n += 1;
+                }
+                n
+            })
+        }
+        Mode::Drain => {
+            let mut sub = node.subscribe::<Int32>(&topic)?;
+            Box::new(move || sub.drain().len())
+        }
+        Mode::Channel => {
+            let (sender, mut receiver) = mpsc::channel(BATCH);
+            node.subscribe_with_sink::<Int32>(
+                &topic,
+                SubscriptionOptions::default(),
+                ChannelSink::new(sender),
+            )?;
+            Box::new(move || {
+                let mut n = 0;
+                while let Some(Some(_)) = receiver.next().now_or_never() {
+                    n += 1;
+                }
+                n
+            })
+        }
+    };
+    let publisher = node.create_publisher::<Int32>(&topic)?;
+    for _ in 0..50 {
+        node.spin_once(Duration::from_millis(10));
+    }
+
+    let start = Instant::now();
+    for batch in 0..BATCHES {
+        for i in 0..BATCH {
+            publisher.publish(&Int32 {
+                data: (batch * BATCH + i) as i32,
+            })?;
+        }
+        let mut taken = 0;
+        while taken < BATCH {
+            node.spin_once(Duration::from_millis(1));
+            taken += take();
+        }
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{:>8}: {:>9.0} msg/s",
+        format!("{:?}", mode).to_lowercase(),
+        (BATCHES * BATCH) as f64 / elapsed.as_secs_f64()
+    );
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    run(Mode::Channel)?;
+    run(Mode::Stream)?;
+    run(Mode::Drain)?;
+    Ok(())
+}
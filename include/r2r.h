@@ -0,0 +1,97 @@
+/*
+ * The C API of r2r, built with the `capi` feature (see the `r2r_capi`
+ * example for a shared library).
+ *
+ * Error codes: functions return R2R_OK on success. Otherwise they
+ * return the rcl return code of the error (RCL_RET_*, positive), or one
+ * of the negative R2R_ERR_* codes. r2r_last_error gives the message of
+ * the last error on the calling thread.
+ *
+ * Ownership:
+ * - Handles are created by the *_create functions and must be freed
+ *   with the matching *_destroy function. Destroying a node stops its
+ *   publishers, subscribers and clients, which must still be destroyed.
+ * - Strings and buffers passed to r2r are borrowed for the duration of
+ *   the call and copied if needed. The caller keeps ownership.
+ * - Buffers returned by r2r_subscriber_take and r2r_response_take are
+ *   owned by the caller and must be freed with r2r_buffer_free, passing
+ *   the length returned with them.
+ *
+ * A node and the handles created from it must only be used from one
+ * thread at a time.
+ */
+#ifndef R2R_H
+#define R2R_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define R2R_OK 0
+/* There is nothing to take yet. */
+#define R2R_ERR_NO_DATA -1
+/* An error that has no rcl return code, see r2r_last_error. */
+#define R2R_ERR_OTHER -2
+
+typedef struct R2rContext R2rContext;
+typedef struct R2rNode R2rNode;
+typedef struct R2rPublisher R2rPublisher;
+typedef struct R2rSubscriber R2rSubscriber;
+typedef struct R2rClient R2rClient;
+typedef struct R2rResponse R2rResponse;
+
+/* Copies the last error message into buf, truncated to len - 1 bytes
+ * and nul terminated. Returns the length of the whole message. */
+size_t r2r_last_error(char *buf, size_t len);
+
+/* Frees a buffer returned by r2r. */
+void r2r_buffer_free(uint8_t *data, size_t len);
+
+/* Creates a context from the command line of the process. Nodes keep
+ * their context alive. */
+int r2r_context_create(R2rContext **out);
+void r2r_context_destroy(R2rContext *context);
+
+int r2r_node_create(R2rContext *context, const char *name, const char *namespace_,
+                    R2rNode **out);
+void r2r_node_destroy(R2rNode *node);
+/* Waits at most timeout_ms for something to happen and handles it. */
+int r2r_node_spin_once(R2rNode *node, uint64_t timeout_ms);
+
+/* Messages are serialized by the middleware in use (e.g. CDR). Types
+ * are named like "std_msgs/msg/String". */
+int r2r_publisher_create(R2rNode *node, const char *topic, const char *type_name,
+                         R2rPublisher **out);
+int r2r_publisher_publish(R2rPublisher *publisher, const uint8_t *data, size_t len);
+void r2r_publisher_destroy(R2rPublisher *publisher);
+
+int r2r_subscriber_create(R2rNode *node, const char *topic, const char *type_name,
+                          R2rSubscriber **out);
+/* Takes the next message received while spinning the node, or returns
+ * R2R_ERR_NO_DATA. */
+int r2r_subscriber_take(R2rSubscriber *subscriber, uint8_t **out_data, size_t *out_len);
+void r2r_subscriber_destroy(R2rSubscriber *subscriber);
+
+/* Requests and responses are utf-8 json. Types are named like
+ * "example_interfaces/srv/AddTwoInts". */
+int r2r_client_create(R2rNode *node, const char *service, const char *type_name,
+                      R2rClient **out);
+/* Spins the node until the service is available, or returns
+ * RCL_RET_TIMEOUT (2) after timeout_ms. */
+int r2r_client_wait_for_service(R2rNode *node, R2rClient *client, uint64_t timeout_ms);
+int r2r_client_request(R2rClient *client, const uint8_t *json, size_t len, R2rResponse **out);
+void r2r_client_destroy(R2rClient *client);
+
+/* Takes the response once it has arrived while spinning the node, or
+ * returns R2R_ERR_NO_DATA. A response can be taken once. */
+int r2r_response_take(R2rResponse *response, uint8_t **out_data, size_t *out_len);
+void r2r_response_destroy(R2rResponse *response);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* R2R_H */
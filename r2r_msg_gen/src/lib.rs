@@ -24,7 +24,9 @@ fn field_type(t: u8) -> String {
     } else if t == (rosidl_typesupport_introspection_c__ROS_TYPE_BOOLEAN as u8) {
         "bool".to_owned()
     } else if t == (rosidl_typesupport_introspection_c__ROS_TYPE_CHAR as u8) {
-        "i8".to_owned()
+        // `char` is a legacy alias of uint8, even though it is a signed
+        // char in C. Converted with `as` to and from the C struct.
+        "u8".to_owned()
     } else if t == (rosidl_typesupport_introspection_c__ROS_TYPE_WCHAR as u8) {
         "u16".to_owned()
     } else if t == (rosidl_typesupport_introspection_c__ROS_TYPE_OCTET as u8) {
@@ -92,6 +94,39 @@ unsafe fn introspection<'a>(
     )
 }
 
+// The path of the rust type of a nested message.
+unsafe fn message_type_path(ptr: *const rosidl_message_type_support_t) -> String {
+    let (module, prefix, name, _, _) = introspection(ptr);
+    // hack here to rustify nested action type names
+    if prefix == "action" {
+        if let Some((n1, n2)) = name.rsplit_once("_") {
+            return format!(
+                "{module}::{prefix}::{srvname}::{msgname}",
+                module = module,
+                prefix = prefix,
+                srvname = n1,
+                msgname = n2
+            );
+        }
+    }
+    format!(
+        "{module}::{prefix}::{msgname}",
+        module = module,
+        prefix = prefix,
+        msgname = name
+    )
+}
+
+fn is_char(t: u8) -> bool {
+    t == (rosidl_typesupport_introspection_c__ROS_TYPE_CHAR as u8)
+}
+
+// C code may store any byte in a bool, which is not a valid rust bool
+// unless it is 0 or 1. Such fields are read as bytes.
+fn is_bool(t: u8) -> bool {
+    t == (rosidl_typesupport_introspection_c__ROS_TYPE_BOOLEAN as u8)
+}
+
 fn field_name(field_name: &str) -> String {
     // check for reserved words
     if field_name == "type" {
@@ -232,6 +267,7 @@ pub fn generate_rust_msg(module_: &str, prefix_: &str, name_: &str) -> String {
         }
 
         let mut fields = String::new();
+        let mut has_header = false;
 
         let is_empty_msg = members.len() == 1
             && field_name(CStr::from_ptr(members[0].name_).to_str().unwrap())
@@ -245,36 +281,16 @@ pub fn generate_rust_msg(module_: &str, prefix_: &str, name_: &str) -> String {
             }
             let rust_field_type = field_type(member.type_id_);
             let rust_field_type = if rust_field_type == "message" {
-                let (module, prefix, name, _, _) = introspection(member.members_);
-                // hack here to rustify nested action type names
-                if prefix == "action" {
-                    if let Some((n1, n2)) = name.rsplit_once("_") {
-                        format!(
-                            "{module}::{prefix}::{srvname}::{msgname}",
-                            module = module,
-                            prefix = prefix,
-                            srvname = n1,
-                            msgname = n2
-                        )
-                    } else {
-                        format!(
-                            "{module}::{prefix}::{msgname}",
-                            module = module,
-                            prefix = prefix,
-                            msgname = name
-                        )
-                    }
-                } else {
-                    format!(
-                        "{module}::{prefix}::{msgname}",
-                        module = module,
-                        prefix = prefix,
-                        msgname = name
-                    )
-                }
+                message_type_path(member.members_)
             } else {
                 rust_field_type
             };
+            if field_name == "header"
+                && !member.is_array_
+                && rust_field_type == "std_msgs::msg::Header"
+            {
+                has_header = true;
+            }
             let s = if member.is_array_ {
                 // if member.array_size_ > 0 {
                 // fixed size array
@@ -332,6 +348,17 @@ pub fn generate_rust_msg(module_: &str, prefix_: &str, name_: &str) -> String {
                         "{field_name}: msg.{field_name}.iter().map(|s|s.to_str().to_owned()).collect(),\n",
                         field_name = field_name
                     ));
+                } else if is_char(member.type_id_) {
+                    from_native.push_str(&format!(
+                        "{field_name}: msg.{field_name}.iter().map(|c| *c as u8).collect(),\n",
+                        field_name = field_name
+                    ));
+                } else if is_bool(member.type_id_) {
+                    from_native.push_str(&format!(
+                        "{field_name}: unsafe {{ &*(std::ptr::addr_of!(msg.{field_name}) as *const [u8; {array_size}]) }}.iter().map(|b| *b != 0).collect(),\n",
+                        field_name = field_name,
+                        array_size = member.array_size_
+                    ));
                 } else {
                     from_native.push_str(&format!(
                         "{field_name}: msg.{field_name}.to_vec(),\n",
@@ -377,6 +404,16 @@ pub fn generate_rust_msg(module_: &str, prefix_: &str, name_: &str) -> String {
                 } else {
                     from_native.push_str(&format!("{field_name}: {module}::{prefix}::{msgname}::from_native(&msg.{field_name}),\n", field_name = field_name, module = module, prefix=prefix, msgname = name));
                 }
+            } else if is_char(member.type_id_) {
+                from_native.push_str(&format!(
+                    "{field_name}: msg.{field_name} as u8,\n",
+                    field_name = field_name
+                ));
+            } else if is_bool(member.type_id_) {
+                from_native.push_str(&format!(
+                    "{field_name}: unsafe {{ *(std::ptr::addr_of!(msg.{field_name}) as *const u8) != 0 }},\n",
+                    field_name = field_name
+                ));
             } else {
                 from_native.push_str(&format!(
                     "{field_name}: msg.{field_name},\n",
@@ -409,6 +446,8 @@ pub fn generate_rust_msg(module_: &str, prefix_: &str, name_: &str) -> String {
                     copy_to_native.push_str(&format!("for (t,s) in msg.{field_name}.iter_mut().zip(&self.{field_name}) {{ s.copy_to_native(t);}}\n", field_name=field_name));
                 } else if rust_field_type == "std::string::String" {
                     copy_to_native.push_str(&format!("for (t,s) in msg.{field_name}.iter_mut().zip(&self.{field_name}) {{ t.assign(&s);}}\n", field_name=field_name));
+                } else if is_char(member.type_id_) {
+                    copy_to_native.push_str(&format!("for (t,s) in msg.{field_name}.iter_mut().zip(&self.{field_name}) {{ *t = *s as i8;}}\n", field_name=field_name));
                 } else {
                     copy_to_native.push_str(&format!(
                         "msg.{field_name}.copy_from_slice(&self.{field_name}[..{array_size}]);\n",
@@ -448,6 +487,11 @@ pub fn generate_rust_msg(module_: &str, prefix_: &str, name_: &str) -> String {
                     "self.{field_name}.copy_to_native(&mut msg.{field_name});\n",
                     field_name = field_name
                 ));
+            } else if is_char(member.type_id_) {
+                copy_to_native.push_str(&format!(
+                    "msg.{field_name} = self.{field_name} as i8;\n",
+                    field_name = field_name
+                ));
             } else {
                 copy_to_native.push_str(&format!(
                     "msg.{field_name} = self.{field_name};\n",
@@ -457,12 +501,104 @@ pub fn generate_rust_msg(module_: &str, prefix_: &str, name_: &str) -> String {
         }
         copy_to_native.push_str("}\n");
 
+        // lets primitive sequences be filled in place in the native message,
+        // and sequences of messages be read and built one element at a time.
+        let mut native_accessors = String::new();
+        for member in members {
+            let field_name = field_name(CStr::from_ptr(member.name_).to_str().unwrap());
+            let rust_field_type = field_type(member.type_id_);
+            let is_sequence =
+                member.is_array_ && (member.array_size_ == 0 || member.is_upper_bound_);
+            if !is_sequence || rust_field_type == "std::string::String" {
+                continue;
+            }
+            if rust_field_type == "message" {
+                let (_, _, _, elem_c_struct, _) = introspection(member.members_);
+                let bound_check = if member.is_upper_bound_ {
+                    format!("assert!(self.{field_name}.size < {array_size}, \"Field {{}} is upper bounded by {{}}!\", \"{field_name}\", {array_size});\n", field_name = field_name, array_size = member.array_size_)
+                } else {
+                    String::new()
+                };
+                native_accessors.push_str(&format!(
+                    "/// The number of elements in `{field_name}`.
+                    pub fn {field_name}_len(&self) -> usize {{
+                        self.{field_name}.size
+                    }}\n
+                    /// A view of element `index` of `{field_name}`, without
+                    /// converting the other elements.
+                    pub fn {field_name}_at(&self, index: usize) -> Option<NativeMsgView<'_, {elem_type}>> {{
+                        if index >= self.{field_name}.size {{
+                            return None;
+                        }}
+                        Some(NativeMsgView::new(unsafe {{ &*self.{field_name}.data.add(index) }}))
+                    }}\n
+                    /// Appends `elem` to `{field_name}`, without converting the
+                    /// elements already there.
+                    pub fn {field_name}_push(&mut self, elem: &{elem_type}) {{
+                        {bound_check}
+                        let seq = &mut self.{field_name};
+                        if seq.size == seq.capacity {{
+                            // elements past the size stay initialized, as the
+                            // sequence functions of rosidl expect.
+                            let mut grown: {elem_c_struct}__Sequence = unsafe {{ std::mem::zeroed() }};
+                            unsafe {{ {elem_c_struct}__Sequence__init(&mut grown, (seq.capacity * 2).max(4)) }};
+                            for i in 0..seq.size {{
+                                unsafe {{ std::ptr::swap(seq.data.add(i), grown.data.add(i)) }};
+                            }}
+                            grown.size = seq.size;
+                            std::mem::swap(seq, &mut grown);
+                            unsafe {{ {elem_c_struct}__Sequence__fini(&mut grown) }};
+                        }}
+                        elem.copy_to_native(unsafe {{ &mut *seq.data.add(seq.size) }});
+                        seq.size += 1;
+                    }}\n",
+                    field_name = field_name,
+                    elem_type = message_type_path(member.members_),
+                    elem_c_struct = elem_c_struct,
+                    bound_check = bound_check
+                ));
+                continue;
+            }
+            let bound_check = if member.is_upper_bound_ {
+                format!("assert!(len <= {array_size}, \"Field {{}} is upper bounded by {{}}!\", \"{field_name}\", {array_size});\n", field_name = field_name, array_size = member.array_size_)
+            } else {
+                String::new()
+            };
+            native_accessors.push_str(&format!(
+                "/// Resizes `{field_name}` to `len` zeroed elements.
+                pub fn resize_{field_name}(&mut self, len: usize) {{
+                    {bound_check}
+                    self.{field_name}.resize(len);
+                }}\n
+                /// The elements of `{field_name}`, for filling them in place
+                /// after `resize_{field_name}`.
+                pub fn {field_name}_mut(&mut self) -> &mut [{rust_field_type}] {{
+                    self.{field_name}.as_mut_slice()
+                }}\n",
+                field_name = field_name,
+                bound_check = bound_check,
+                rust_field_type = rust_field_type
+            ));
+        }
+        let native_accessors = if native_accessors.is_empty() {
+            native_accessors
+        } else {
+            format!(
+                "impl WrappedNativeMsg<{msgname}> {{\n{accessors}}}\n",
+                msgname = name,
+                accessors = native_accessors
+            )
+        };
+
         let typesupport = format!(
             "impl WrappedTypesupport for {msgname} {{ \n
             type CStruct = {c_struct}; \n\n
             fn get_ts() -> &'static rosidl_message_type_support_t {{ \n
                 unsafe {{ &*rosidl_typesupport_c__get_message_type_support_handle__{c_struct}() }}
             }}\n
+            fn get_introspection_ts() -> &'static rosidl_message_type_support_t {{ \n
+                unsafe {{ &*rosidl_typesupport_introspection_c__get_message_type_support_handle__{c_struct}() }}
+            }}\n
             fn create_msg() -> *mut {c_struct} {{\n
                 unsafe {{ {c_struct}__create() }}\n
             }}\n
@@ -478,6 +614,22 @@ pub fn generate_rust_msg(module_: &str, prefix_: &str, name_: &str) -> String {
             copy_to_native = copy_to_native
         );
 
+        let impl_has_header = if has_header {
+            format!(
+                "impl HasHeader for {msgname} {{
+                    fn stamp(&self) -> &builtin_interfaces::msg::Time {{
+                        &self.header.stamp
+                    }}
+                    fn frame_id(&self) -> &str {{
+                        &self.header.frame_id
+                    }}
+                }}\n",
+                msgname = name
+            )
+        } else {
+            String::new()
+        };
+
         let impl_default = format!(
             "
                           impl Default for {msgname} {{
@@ -498,11 +650,15 @@ pub fn generate_rust_msg(module_: &str, prefix_: &str, name_: &str) -> String {
                           }}\n
                           {typesupport}\n
                           {default}\n\n
+                          {has_header}\n
+                          {native_accessors}\n
                     ",
             msgname = name,
             fields = fields,
             typesupport = typesupport,
-            default = impl_default
+            default = impl_default,
+            has_header = impl_has_header,
+            native_accessors = native_accessors
         );
 
         module_str
@@ -132,11 +132,129 @@ macro_rules! primitive_sequence {
                     unsafe { std::ptr::copy(self.data, target.as_mut_ptr(), self.size); }
                     target
                 }
+
+                /// Replaces the elements with `len` zeroed ones.
+                pub fn resize(&mut self, len: usize) {
+                    unsafe { [<$ctype __Sequence__fini>] (self as *mut _); }
+                    unsafe { [<$ctype __Sequence__init>] (self as *mut _, len); }
+                }
+
+                pub fn as_slice(&self) -> &[$element_type] {
+                    if self.data.is_null() {
+                        return &[];
+                    }
+                    unsafe { std::slice::from_raw_parts(self.data, self.size) }
+                }
+
+                /// Lets the elements be written in place, e.g. by
+                /// foreign code.
+                pub fn as_mut_slice(&mut self) -> &mut [$element_type] {
+                    if self.data.is_null() {
+                        return &mut [];
+                    }
+                    unsafe { std::slice::from_raw_parts_mut(self.data, self.size) }
+                }
             }
         }
     };
 }
 
+// `char` sequences hold `signed char` in C, but the elements are
+// exposed as u8, like `byte` and `uint8`. The layouts are the same so
+// the data is used as is.
+macro_rules! cast_sequence {
+    ($ctype:ident, $element_type:ident) => {
+        paste::item! {
+            impl [<$ctype __Sequence>] {
+                pub fn update(&mut self, values: &[$element_type]) {
+                    unsafe { [<$ctype __Sequence__fini>] (self as *mut _); }
+                    unsafe { [<$ctype __Sequence__init>] (self as *mut _, values.len()); }
+                    unsafe { std::ptr::copy(values.as_ptr(), self.data as *mut $element_type, values.len()); }
+                }
+
+                pub fn to_vec(&self) -> Vec<$element_type> {
+                    self.as_slice().to_vec()
+                }
+
+                /// Replaces the elements with `len` zeroed ones.
+                pub fn resize(&mut self, len: usize) {
+                    unsafe { [<$ctype __Sequence__fini>] (self as *mut _); }
+                    unsafe { [<$ctype __Sequence__init>] (self as *mut _, len); }
+                }
+
+                pub fn as_slice(&self) -> &[$element_type] {
+                    if self.data.is_null() {
+                        return &[];
+                    }
+                    unsafe { std::slice::from_raw_parts(self.data as *const $element_type, self.size) }
+                }
+
+                /// Lets the elements be written in place, e.g. by
+                /// foreign code.
+                pub fn as_mut_slice(&mut self) -> &mut [$element_type] {
+                    if self.data.is_null() {
+                        return &mut [];
+                    }
+                    unsafe { std::slice::from_raw_parts_mut(self.data as *mut $element_type, self.size) }
+                }
+            }
+        }
+    };
+}
+
+// The C booleans take a byte each, as in a Vec<bool>, but nothing stops
+// C code or the middleware from storing other values than 0 and 1,
+// which are not valid rust bools. So the elements are read as bytes,
+// where anything but 0 is true.
+impl rosidl_runtime_c__boolean__Sequence {
+    pub fn update(&mut self, values: &[bool]) {
+        unsafe {
+            rosidl_runtime_c__boolean__Sequence__fini(self as *mut _);
+        }
+        unsafe {
+            rosidl_runtime_c__boolean__Sequence__init(self as *mut _, values.len());
+        }
+        unsafe {
+            std::ptr::copy(values.as_ptr(), self.data, values.len());
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<bool> {
+        self.as_bytes().iter().map(|b| *b != 0).collect()
+    }
+
+    /// Replaces the elements with `len` false ones.
+    pub fn resize(&mut self, len: usize) {
+        unsafe {
+            rosidl_runtime_c__boolean__Sequence__fini(self as *mut _);
+        }
+        unsafe {
+            rosidl_runtime_c__boolean__Sequence__init(self as *mut _, len);
+        }
+    }
+
+    /// The elements as stored, anything but 0 means true.
+    pub fn as_bytes(&self) -> &[u8] {
+        if self.data.is_null() {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.data as *const u8, self.size) }
+    }
+
+    /// Lets the elements be written in place, e.g. by foreign code.
+    /// Elements that are neither 0 nor 1 are set to 1 first.
+    pub fn as_mut_slice(&mut self) -> &mut [bool] {
+        if self.data.is_null() {
+            return &mut [];
+        }
+        let bytes = unsafe { std::slice::from_raw_parts_mut(self.data as *mut u8, self.size) };
+        for b in bytes.iter_mut() {
+            *b = (*b != 0) as u8;
+        }
+        unsafe { std::slice::from_raw_parts_mut(self.data, self.size) }
+    }
+}
+
 primitive_sequence!(rosidl_runtime_c__float32, f32);
 primitive_sequence!(rosidl_runtime_c__float64, f64);
 
@@ -146,9 +264,8 @@ primitive_sequence!(rosidl_runtime_c__long_double, f64);
 #[cfg(not(all(target_os = "macos", target_arch = "aarch64")))]
 primitive_sequence!(rosidl_runtime_c__long_double, u128);
 
-primitive_sequence!(rosidl_runtime_c__char, i8);
+cast_sequence!(rosidl_runtime_c__char, u8);
 primitive_sequence!(rosidl_runtime_c__wchar, u16);
-primitive_sequence!(rosidl_runtime_c__boolean, bool);
 primitive_sequence!(rosidl_runtime_c__octet, u8);
 primitive_sequence!(rosidl_runtime_c__uint8, u8);
 primitive_sequence!(rosidl_runtime_c__int8, i8);
@@ -4,9 +4,17 @@
 // query the network
 #include <rcl/graph.h>
 
+// command line arguments and name resolution
+#include <rcl/arguments.h>
+#include <rcl/expand_topic_name.h>
+#include <rcl/remap.h>
+
 // logging
 #include <rcl/logging.h>
 
+// gids of the rmw entities
+#include <rmw/rmw.h>
+
 // errors
 #include <rcutils/error_handling.h>
 
@@ -0,0 +1,874 @@
+//! Commands that are acknowledged by their subscriber.
+//!
+//! An `AckedPublisher` numbers its commands and sends them on a topic
+//! until an `AckedSubscriber` acknowledges them on `<topic>/ack`,
+//! waiting longer after every attempt and giving up after a number of
+//! attempts. The subscriber hands the commands of each publisher to the
+//! application in order and exactly once, acknowledging duplicates
+//! again without delivering them.
+//!
+//! Like `TopicRpcClient`, both topics carry `std_msgs/msg/String`
+//! messages holding json, so that no special message type is needed.
+//! A command carries the UUID of its publisher, its sequence number and
+//! the lowest sequence number the publisher still waits for, so that a
+//! subscriber that joins late, or skips commands the publisher gave up
+//! on, knows where to start:
+//!
+//! ```json
+//! {"publisher": "5b4f...", "seq": 3, "low": 2, "message": {"speed": 1.0}}
+//! {"publisher": "5b4f...", "seq": 3, "subscriber": "0c1d...", "status": "delivered"}
+//! ```
+//!
+//! A subscriber only remembers what it has delivered while it lives: a
+//! restarted subscriber delivers a command again if the acknowledgment
+//! of the earlier delivery was lost. Any subscriber's acknowledgment
+//! counts, so a command topic should have one subscriber.
+//!
+//! Both sides are driven by spinning their node and measure time on
+//! the ROS clock.
+
+use futures::channel::{mpsc, oneshot};
+use futures::future::FutureExt;
+use futures::stream::{Stream, StreamExt};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use crate::clocks::{Clock, ClockType};
+use crate::error::*;
+use crate::error_events::{EntityErrors, SpinOperation};
+use crate::executor::EntityKind;
+use crate::msg_types::WrappedTypesupport;
+use crate::nodes::{Node, Timer};
+use crate::publishers::PublisherUntyped;
+
+const ACKED_MSG_TYPE: &str = "std_msgs/msg/String";
+
+fn ack_topic(topic: &str) -> String {
+    format!("{}/ack", topic)
+}
+
+/// Retries of an `AckedPublisher` and deduplication of an
+/// `AckedSubscriber`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AckedOptions {
+    /// How long to wait for the acknowledgment of the first attempt.
+    pub retry_timeout: Duration,
+    /// The wait grows by this factor with every attempt.
+    pub backoff: f64,
+    /// The longest wait between attempts.
+    pub max_retry_timeout: Duration,
+    /// How many times a command is sent before it fails with
+    /// `Error::CommandNotAcknowledged`.
+    pub max_attempts: u32,
+    /// How long a subscriber remembers a publisher it no longer hears
+    /// from. Should be longer than the publisher retries a command.
+    pub dedup_window: Duration,
+}
+
+impl Default for AckedOptions {
+    fn default() -> Self {
+        AckedOptions {
+            retry_timeout: Duration::from_millis(100),
+            backoff: 2.0,
+            max_retry_timeout: Duration::from_secs(2),
+            max_attempts: 5,
+            dedup_window: Duration::from_secs(60),
+        }
+    }
+}
+
+impl AckedOptions {
+    fn validate(&self) -> Result<()> {
+        let zero = Duration::from_secs(0);
+        if self.retry_timeout == zero
+            || self.max_retry_timeout < self.retry_timeout
+            || self.backoff.is_nan()
+            || self.backoff < 1.0
+            || self.max_attempts == 0
+            || self.dedup_window == zero
+        {
+            return Err(Error::RCL_RET_INVALID_ARGUMENT);
+        }
+        Ok(())
+    }
+
+    // How long to wait after the given attempt, counted from 1.
+    fn retry_after(&self, attempt: u32) -> Duration {
+        let factor = self.backoff.powi(attempt as i32 - 1);
+        let wait = self.retry_timeout.as_secs_f64() * factor;
+        if wait >= self.max_retry_timeout.as_secs_f64() {
+            self.max_retry_timeout
+        } else {
+            Duration::from_secs_f64(wait)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AckStatus {
+    Delivered,
+    Duplicate,
+    // the message could not be converted to the type of the subscriber.
+    Rejected,
+}
+
+impl AckStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AckStatus::Delivered => "delivered",
+            AckStatus::Duplicate => "duplicate",
+            AckStatus::Rejected => "rejected",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "delivered" => Some(AckStatus::Delivered),
+            "duplicate" => Some(AckStatus::Duplicate),
+            "rejected" => Some(AckStatus::Rejected),
+            _ => None,
+        }
+    }
+}
+
+struct Command {
+    publisher: uuid::Uuid,
+    seq: u64,
+    low: u64,
+    message: serde_json::Value,
+}
+
+struct Ack {
+    publisher: uuid::Uuid,
+    seq: u64,
+    status: AckStatus,
+}
+
+fn wrap(json: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({ "data": json.to_string() })
+}
+
+fn unwrap(msg: &serde_json::Value) -> Result<serde_json::Value> {
+    let data = msg
+        .get("data")
+        .and_then(|d| d.as_str())
+        .ok_or_else(|| Error::SerdeError {
+            err: format!("acked message without data: {}", msg),
+        })?;
+    serde_json::from_str(data).map_err(|e| Error::SerdeError { err: e.to_string() })
+}
+
+fn field<'a>(json: &'a serde_json::Value, name: &str) -> Result<&'a serde_json::Value> {
+    json.get(name).ok_or_else(|| Error::SerdeError {
+        err: format!("acked message without a valid {}: {}", name, json),
+    })
+}
+
+fn uuid_field(json: &serde_json::Value, name: &str) -> Result<uuid::Uuid> {
+    field(json, name)?
+        .as_str()
+        .and_then(|id| uuid::Uuid::parse_str(id).ok())
+        .ok_or_else(|| Error::SerdeError {
+            err: format!("acked message without a valid {}: {}", name, json),
+        })
+}
+
+fn u64_field(json: &serde_json::Value, name: &str) -> Result<u64> {
+    field(json, name)?
+        .as_u64()
+        .ok_or_else(|| Error::SerdeError {
+            err: format!("acked message without a valid {}: {}", name, json),
+        })
+}
+
+fn parse_command(msg: &serde_json::Value) -> Result<Command> {
+    let mut json = unwrap(msg)?;
+    let publisher = uuid_field(&json, "publisher")?;
+    let seq = u64_field(&json, "seq")?;
+    let low = u64_field(&json, "low")?;
+    field(&json, "message")?;
+    Ok(Command {
+        publisher,
+        seq,
+        low,
+        message: json["message"].take(),
+    })
+}
+
+fn parse_ack(msg: &serde_json::Value) -> Result<Ack> {
+    let json = unwrap(msg)?;
+    let status = field(&json, "status")?
+        .as_str()
+        .and_then(AckStatus::parse)
+        .ok_or_else(|| Error::SerdeError {
+            err: format!("acked message without a valid status: {}", json),
+        })?;
+    Ok(Ack {
+        publisher: uuid_field(&json, "publisher")?,
+        seq: u64_field(&json, "seq")?,
+        status,
+    })
+}
+
+/// Publishes commands on a topic until they are acknowledged by an
+/// `AckedSubscriber`.
+pub struct AckedPublisher<T> {
+    shared: Arc<Mutex<PublisherShared>>,
+    publisher: PublisherUntyped,
+    id: uuid::Uuid,
+    message_type: PhantomData<fn(T)>,
+}
+
+impl<T> AckedPublisher<T>
+where
+    T: WrappedTypesupport,
+{
+    pub fn create(node: &mut Node, topic: &str) -> Result<Self> {
+        Self::create_with_options(node, topic, AckedOptions::default())
+    }
+
+    pub fn create_with_options(
+        node: &mut Node,
+        topic: &str,
+        options: AckedOptions,
+    ) -> Result<Self> {
+        options.validate()?;
+        let publisher = node.create_publisher_untyped(topic, ACKED_MSG_TYPE)?;
+        let acks = node.subscribe_untyped(&ack_topic(topic), ACKED_MSG_TYPE)?;
+        // wake up often enough to retry in time.
+        let timer =
+            node.create_wall_timer((options.retry_timeout / 4).max(Duration::from_millis(1)))?;
+        let mut clock = Clock::create(ClockType::RosTime)?;
+        let now = clock.get_now()?;
+        let id = uuid::Uuid::new_v4();
+        let shared = Arc::new(Mutex::new(PublisherShared {
+            tracker: AckTracker::new(options, now),
+            commands: HashMap::new(),
+        }));
+        node.add_acked_publisher(AckedPublisher_ {
+            shared: shared.clone(),
+            id,
+            publisher: publisher.clone(),
+            acks: Box::new(acks),
+            timer,
+            clock,
+            errors: node.entity_errors(EntityKind::Publisher, topic),
+        });
+        Ok(AckedPublisher {
+            shared,
+            publisher,
+            id,
+            message_type: PhantomData,
+        })
+    }
+
+    /// The UUID the commands of this publisher carry.
+    pub fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    /// Number of subscribers of the command topic.
+    pub fn subscriber_count(&self) -> Result<usize> {
+        self.publisher.get_inter_process_subscription_count()
+    }
+
+    /// Number of commands waiting for their acknowledgment.
+    pub fn pending(&self) -> usize {
+        self.shared.lock().unwrap().commands.len()
+    }
+
+    /// Send a command. The returned future completes when the command
+    /// is acknowledged, fails with `Error::CommandNotAcknowledged` if
+    /// it is not after the last attempt, and with
+    /// `Error::CommandRejected` if the subscriber cannot convert it.
+    pub fn send(&self, msg: &T) -> Result<impl Future<Output = Result<()>>> {
+        let message =
+            serde_json::to_value(msg).map_err(|e| Error::SerdeError { err: e.to_string() })?;
+        let (sender, receiver) = oneshot::channel();
+        // held while publishing, the acknowledgment may arrive right
+        // away.
+        let mut shared = self.shared.lock().unwrap();
+        let now = shared.tracker.now;
+        let seq = shared.tracker.add(now);
+        let command = wrap(serde_json::json!({
+            "publisher": self.id.to_string(),
+            "seq": seq,
+            "low": shared.tracker.low(),
+            "message": message.clone(),
+        }));
+        if let Err(e) = self.publisher.publish(command) {
+            shared.tracker.ack(seq);
+            return Err(e);
+        }
+        shared.commands.insert(seq, (message, sender));
+        Ok(receiver.map(|result| match result {
+            Ok(result) => result,
+            Err(oneshot::Canceled) => Err(Error::RCL_RET_PUBLISHER_INVALID),
+        }))
+    }
+}
+
+struct PublisherShared {
+    tracker: AckTracker,
+    // the messages of the pending commands and who waits for them.
+    commands: HashMap<u64, (serde_json::Value, oneshot::Sender<Result<()>>)>,
+}
+
+pub(crate) struct AckedPublisher_ {
+    shared: Arc<Mutex<PublisherShared>>,
+    id: uuid::Uuid,
+    publisher: PublisherUntyped,
+    acks: Box<dyn Stream<Item = Result<serde_json::Value>> + Unpin>,
+    timer: Timer,
+    clock: Clock,
+    errors: EntityErrors,
+}
+
+impl AckedPublisher_ {
+    /// Handles acknowledgments and sends the commands that are due
+    /// again. Returns false once the `AckedPublisher` has been dropped.
+    pub(crate) fn poll(&mut self) -> bool {
+        // the publisher holds the only other reference.
+        if Arc::strong_count(&self.shared) == 1 {
+            return false;
+        }
+        // the timer only wakes up the spin.
+        while let Some(Ok(_)) = self.timer.tick().now_or_never() {}
+        let now = match self.clock.get_now() {
+            Ok(now) => now,
+            Err(e) => {
+                self.errors.report(SpinOperation::Update, e);
+                return true;
+            }
+        };
+        let mut shared = self.shared.lock().unwrap();
+        while let Some(Some(msg)) = self.acks.next().now_or_never() {
+            match msg.and_then(|m| parse_ack(&m)) {
+                // acknowledgments for other publishers on the topic.
+                Ok(ack) if ack.publisher != self.id => (),
+                Ok(ack) => {
+                    if !shared.tracker.ack(ack.seq) {
+                        // acknowledged before, e.g. a duplicate.
+                        continue;
+                    }
+                    if let Some((_, sender)) = shared.commands.remove(&ack.seq) {
+                        let result = match ack.status {
+                            AckStatus::Delivered | AckStatus::Duplicate => Ok(()),
+                            AckStatus::Rejected => Err(Error::CommandRejected { seq: ack.seq }),
+                        };
+                        let _ = sender.send(result);
+                    }
+                }
+                Err(e) => self.errors.report(SpinOperation::Convert, e),
+            }
+        }
+        let (resend, failed) = shared.tracker.poll(now);
+        for seq in failed {
+            if let Some((_, sender)) = shared.commands.remove(&seq) {
+                let _ = sender.send(Err(Error::CommandNotAcknowledged {
+                    seq,
+                    attempts: shared.tracker.options.max_attempts,
+                }));
+            }
+        }
+        let low = shared.tracker.low();
+        for seq in resend {
+            let message = match shared.commands.get(&seq) {
+                Some((message, _)) => message.clone(),
+                None => continue,
+            };
+            let command = wrap(serde_json::json!({
+                "publisher": self.id.to_string(),
+                "seq": seq,
+                "low": low,
+                "message": message,
+            }));
+            if let Err(e) = self.publisher.publish(command) {
+                self.errors.report(SpinOperation::Send, e);
+            }
+        }
+        true
+    }
+}
+
+/// A `Stream` of the commands sent by `AckedPublisher`s, in order and
+/// without duplicates.
+///
+/// Commands are acknowledged when they are put in the stream. Commands
+/// that cannot be converted are rejected, and reported as errors of
+/// the node, see `Node::error_events`.
+pub struct AckedSubscriber<T> {
+    commands: mpsc::UnboundedReceiver<T>,
+}
+
+impl<T> AckedSubscriber<T>
+where
+    T: WrappedTypesupport + 'static,
+{
+    pub fn create(node: &mut Node, topic: &str) -> Result<Self> {
+        Self::create_with_options(node, topic, AckedOptions::default())
+    }
+
+    pub fn create_with_options(
+        node: &mut Node,
+        topic: &str,
+        options: AckedOptions,
+    ) -> Result<Self> {
+        options.validate()?;
+        let commands = node.subscribe_untyped(topic, ACKED_MSG_TYPE)?;
+        let publisher = node.create_publisher_untyped(&ack_topic(topic), ACKED_MSG_TYPE)?;
+        let mut clock = Clock::create(ClockType::RosTime)?;
+        let now = clock.get_now()?;
+        let (sender, receiver) = mpsc::unbounded();
+        let closed = sender.clone();
+        node.add_acked_subscriber(AckedSubscriber_ {
+            tracker: DedupTracker::new(options.dedup_window, now),
+            id: uuid::Uuid::new_v4(),
+            commands: Box::new(commands),
+            publisher,
+            clock,
+            deliver: Box::new(move |message| {
+                let msg = serde_json::from_value::<T>(message)
+                    .map_err(|e| Error::SerdeError { err: e.to_string() })?;
+                Ok(sender.unbounded_send(msg).is_ok())
+            }),
+            is_closed: Box::new(move || closed.is_closed()),
+            errors: node.entity_errors(EntityKind::Subscription, topic),
+        });
+        Ok(AckedSubscriber { commands: receiver })
+    }
+}
+
+impl<T> Stream for AckedSubscriber<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.commands).poll_next(cx)
+    }
+}
+
+pub(crate) struct AckedSubscriber_ {
+    tracker: DedupTracker<serde_json::Value>,
+    id: uuid::Uuid,
+    commands: Box<dyn Stream<Item = Result<serde_json::Value>> + Unpin>,
+    publisher: PublisherUntyped,
+    clock: Clock,
+    // converts a command and hands it to the stream. False once the
+    // stream has been dropped.
+    deliver: Box<dyn FnMut(serde_json::Value) -> Result<bool>>,
+    is_closed: Box<dyn Fn() -> bool>,
+    errors: EntityErrors,
+}
+
+impl AckedSubscriber_ {
+    /// Delivers and acknowledges the received commands. Returns false
+    /// once the `AckedSubscriber` has been dropped.
+    pub(crate) fn poll(&mut self) -> bool {
+        if (self.is_closed)() {
+            return false;
+        }
+        let now = match self.clock.get_now() {
+            Ok(now) => now,
+            Err(e) => {
+                self.errors.report(SpinOperation::Update, e);
+                return true;
+            }
+        };
+        self.tracker.expire(now);
+        while let Some(Some(msg)) = self.commands.next().now_or_never() {
+            let command = match msg.and_then(|m| parse_command(&m)) {
+                Ok(command) => command,
+                Err(e) => {
+                    self.errors.report(SpinOperation::Convert, e);
+                    continue;
+                }
+            };
+            let received = self.tracker.receive(
+                command.publisher,
+                command.seq,
+                command.low,
+                command.message,
+                now,
+            );
+            for seq in received.duplicates {
+                self.ack(command.publisher, seq, AckStatus::Duplicate);
+            }
+            for (seq, message) in received.deliver {
+                let status = match (self.deliver)(message) {
+                    Ok(true) => AckStatus::Delivered,
+                    // not acknowledged, the publisher keeps trying.
+                    Ok(false) => return false,
+                    Err(e) => {
+                        self.errors.report(SpinOperation::Convert, e);
+                        AckStatus::Rejected
+                    }
+                };
+                self.ack(command.publisher, seq, status);
+            }
+        }
+        true
+    }
+
+    fn ack(&mut self, publisher: uuid::Uuid, seq: u64, status: AckStatus) {
+        let ack = wrap(serde_json::json!({
+            "publisher": publisher.to_string(),
+            "seq": seq,
+            "subscriber": self.id.to_string(),
+            "status": status.as_str(),
+        }));
+        if let Err(e) = self.publisher.publish(ack) {
+            self.errors.report(SpinOperation::Send, e);
+        }
+    }
+}
+
+// The commands a publisher waits for, with time given as ROS time.
+struct AckTracker {
+    options: AckedOptions,
+    next_seq: u64,
+    // the number of attempts of each pending command and when to try
+    // again.
+    pending: BTreeMap<u64, (u32, Duration)>,
+    // the time of the last poll, for sending from outside the spin.
+    now: Duration,
+}
+
+impl AckTracker {
+    fn new(options: AckedOptions, now: Duration) -> Self {
+        AckTracker {
+            options,
+            next_seq: 0,
+            pending: BTreeMap::new(),
+            now,
+        }
+    }
+
+    // A new command, sent for the first time at `now`.
+    fn add(&mut self, now: Duration) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.pending
+            .insert(seq, (1, now + self.options.retry_after(1)));
+        seq
+    }
+
+    // Returns false if the command was not pending.
+    fn ack(&mut self, seq: u64) -> bool {
+        self.pending.remove(&seq).is_some()
+    }
+
+    // The lowest sequence number that is still pending.
+    fn low(&self) -> u64 {
+        self.pending.keys().next().copied().unwrap_or(self.next_seq)
+    }
+
+    // The commands to send again and those that failed, in order.
+    fn poll(&mut self, now: Duration) -> (Vec<u64>, Vec<u64>) {
+        if now < self.now {
+            // time jumped backwards, e.g. a simulation was restarted.
+            let last = self.now;
+            for (_, next) in self.pending.values_mut() {
+                *next = now + next.saturating_sub(last);
+            }
+        }
+        self.now = now;
+        let mut resend = vec![];
+        let mut failed = vec![];
+        for (seq, (attempts, next)) in self.pending.iter_mut() {
+            if now < *next {
+                continue;
+            }
+            if *attempts >= self.options.max_attempts {
+                failed.push(*seq);
+            } else {
+                *attempts += 1;
+                *next = now + self.options.retry_after(*attempts);
+                resend.push(*seq);
+            }
+        }
+        for seq in &failed {
+            self.pending.remove(seq);
+        }
+        (resend, failed)
+    }
+}
+
+struct Received<V> {
+    // the commands to hand to the application, in order.
+    deliver: Vec<(u64, V)>,
+    // delivered before, to be acknowledged again.
+    duplicates: Vec<u64>,
+}
+
+struct Peer<V> {
+    next_seq: u64,
+    // commands that arrived before the ones preceding them.
+    early: BTreeMap<u64, V>,
+    last_heard: Duration,
+}
+
+// What a subscriber has delivered of each publisher.
+struct DedupTracker<V> {
+    window: Duration,
+    peers: HashMap<uuid::Uuid, Peer<V>>,
+    now: Duration,
+}
+
+impl<V> DedupTracker<V> {
+    fn new(window: Duration, now: Duration) -> Self {
+        DedupTracker {
+            window,
+            peers: HashMap::new(),
+            now,
+        }
+    }
+
+    fn receive(
+        &mut self,
+        publisher: uuid::Uuid,
+        seq: u64,
+        low: u64,
+        message: V,
+        now: Duration,
+    ) -> Received<V> {
+        self.now = now;
+        let peer = self.peers.entry(publisher).or_insert_with(|| Peer {
+            // a publisher we have not heard from, or forgot.
+            next_seq: low,
+            early: BTreeMap::new(),
+            last_heard: now,
+        });
+        peer.last_heard = now;
+        let mut received = Received {
+            deliver: vec![],
+            duplicates: vec![],
+        };
+        if low > peer.next_seq {
+            // the publisher gave up on the commands in between.
+            peer.next_seq = low;
+            peer.early = peer.early.split_off(&low);
+        }
+        if seq < peer.next_seq {
+            received.duplicates.push(seq);
+        } else {
+            // a command that arrived early again is still waiting.
+            peer.early.entry(seq).or_insert(message);
+        }
+        while let Some(message) = peer.early.remove(&peer.next_seq) {
+            received.deliver.push((peer.next_seq, message));
+            peer.next_seq += 1;
+        }
+        received
+    }
+
+    // Forget the publishers not heard from within the window.
+    fn expire(&mut self, now: Duration) {
+        if now < self.now {
+            // time jumped backwards, e.g. a simulation was restarted.
+            let last = self.now;
+            for peer in self.peers.values_mut() {
+                peer.last_heard = now.saturating_sub(last.saturating_sub(peer.last_heard));
+            }
+        }
+        self.now = now;
+        let window = self.window;
+        self.peers.retain(|_, peer| now < peer.last_heard + window);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn options() -> AckedOptions {
+        AckedOptions {
+            retry_timeout: ms(100),
+            backoff: 2.0,
+            max_retry_timeout: ms(300),
+            max_attempts: 4,
+            dedup_window: ms(1000),
+        }
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        let o = options();
+        assert_eq!(o.retry_after(1), ms(100));
+        assert_eq!(o.retry_after(2), ms(200));
+        assert_eq!(o.retry_after(3), ms(300));
+        assert_eq!(o.retry_after(10), ms(300));
+        assert!(o.validate().is_ok());
+        let invalid = AckedOptions {
+            backoff: 0.5,
+            ..options()
+        };
+        assert!(invalid.validate().is_err());
+        let invalid = AckedOptions {
+            max_attempts: 0,
+            ..options()
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_acked_in_time() {
+        let mut t = AckTracker::new(options(), ms(0));
+        assert_eq!(t.add(ms(0)), 0);
+        assert_eq!(t.add(ms(10)), 1);
+        assert_eq!(t.low(), 0);
+        assert_eq!(t.poll(ms(99)), (vec![], vec![]));
+        assert!(t.ack(0));
+        // a duplicate acknowledgment.
+        assert!(!t.ack(0));
+        assert_eq!(t.low(), 1);
+        assert!(t.ack(1));
+        assert_eq!(t.low(), 2);
+        assert_eq!(t.poll(ms(5000)), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_retries_until_failed() {
+        let mut t = AckTracker::new(options(), ms(0));
+        t.add(ms(0));
+        // attempts at 0, 100, 300 and 600 ms, then it fails 300 ms later.
+        assert_eq!(t.poll(ms(100)), (vec![0], vec![]));
+        assert_eq!(t.poll(ms(299)), (vec![], vec![]));
+        assert_eq!(t.poll(ms(300)), (vec![0], vec![]));
+        assert_eq!(t.poll(ms(600)), (vec![0], vec![]));
+        assert_eq!(t.poll(ms(899)), (vec![], vec![]));
+        assert_eq!(t.poll(ms(900)), (vec![], vec![0]));
+        assert_eq!(t.low(), 1);
+        // too late.
+        assert!(!t.ack(0));
+    }
+
+    #[test]
+    fn test_acked_paused_and_reset_time() {
+        let mut t = AckTracker::new(options(), ms(5000));
+        t.add(ms(5000));
+        // a paused clock does not advance, so nothing is sent again.
+        for _ in 0..10 {
+            assert_eq!(t.poll(ms(5000)), (vec![], vec![]));
+        }
+        // a restarted simulation keeps the remaining time.
+        assert_eq!(t.poll(ms(10)), (vec![], vec![]));
+        assert_eq!(t.poll(ms(109)), (vec![], vec![]));
+        assert_eq!(t.poll(ms(110)), (vec![0], vec![]));
+    }
+
+    #[test]
+    fn test_dedup_in_order() {
+        let p = uuid::Uuid::new_v4();
+        let mut t = DedupTracker::new(ms(1000), ms(0));
+        let r = t.receive(p, 0, 0, "a", ms(0));
+        assert_eq!(r.deliver, vec![(0, "a")]);
+        // the acknowledgment was lost and the command sent again.
+        let r = t.receive(p, 0, 0, "a", ms(100));
+        assert!(r.deliver.is_empty());
+        assert_eq!(r.duplicates, vec![0]);
+        let r = t.receive(p, 1, 1, "b", ms(100));
+        assert_eq!(r.deliver, vec![(1, "b")]);
+    }
+
+    #[test]
+    fn test_dedup_reorders() {
+        let p = uuid::Uuid::new_v4();
+        let mut t = DedupTracker::new(ms(1000), ms(0));
+        // the first command was lost, the second says it is missing.
+        let r = t.receive(p, 1, 0, "b", ms(0));
+        assert!(r.deliver.is_empty() && r.duplicates.is_empty());
+        // sent again while waiting.
+        let r = t.receive(p, 1, 0, "b", ms(10));
+        assert!(r.deliver.is_empty() && r.duplicates.is_empty());
+        let r = t.receive(p, 0, 0, "a", ms(100));
+        assert_eq!(r.deliver, vec![(0, "a"), (1, "b")]);
+    }
+
+    #[test]
+    fn test_dedup_skips_failed() {
+        let p = uuid::Uuid::new_v4();
+        let mut t = DedupTracker::new(ms(1000), ms(0));
+        t.receive(p, 0, 0, "a", ms(0));
+        let r = t.receive(p, 2, 1, "c", ms(0));
+        assert!(r.deliver.is_empty());
+        // the publisher gave up on 1.
+        let r = t.receive(p, 3, 2, "d", ms(10));
+        assert_eq!(r.deliver, vec![(2, "c"), (3, "d")]);
+        // and it shows up late.
+        let r = t.receive(p, 1, 1, "b", ms(20));
+        assert!(r.deliver.is_empty());
+        assert_eq!(r.duplicates, vec![1]);
+    }
+
+    #[test]
+    fn test_dedup_publishers_apart() {
+        let (p, q) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        let mut t = DedupTracker::new(ms(1000), ms(0));
+        assert_eq!(t.receive(p, 0, 0, "a", ms(0)).deliver, vec![(0, "a")]);
+        // a restarted publisher has a new id and counts from 0 again.
+        assert_eq!(t.receive(q, 0, 0, "x", ms(0)).deliver, vec![(0, "x")]);
+        // a subscriber that joins late starts where the publisher is.
+        let mut late = DedupTracker::new(ms(1000), ms(0));
+        assert_eq!(late.receive(p, 7, 7, "h", ms(0)).deliver, vec![(7, "h")]);
+    }
+
+    #[test]
+    fn test_dedup_window() {
+        let p = uuid::Uuid::new_v4();
+        let mut t = DedupTracker::new(ms(1000), ms(0));
+        t.receive(p, 0, 0, "a", ms(0));
+        t.expire(ms(999));
+        assert_eq!(t.receive(p, 0, 0, "a", ms(999)).duplicates, vec![0]);
+        t.expire(ms(1998));
+        assert_eq!(t.peers.len(), 1);
+        // forgotten after the window, a late duplicate is delivered
+        // again.
+        t.expire(ms(1999));
+        assert!(t.peers.is_empty());
+        assert_eq!(t.receive(p, 0, 0, "a", ms(2000)).deliver, vec![(0, "a")]);
+
+        // a restarted simulation keeps the remaining time.
+        let mut t = DedupTracker::new(ms(1000), ms(5000));
+        t.receive(p, 0, 0, "a", ms(5000));
+        t.expire(ms(5400));
+        t.expire(ms(1000));
+        t.expire(ms(1599));
+        assert_eq!(t.peers.len(), 1);
+        t.expire(ms(1600));
+        assert!(t.peers.is_empty());
+    }
+
+    #[test]
+    fn test_parse_command_and_ack() {
+        let p = uuid::Uuid::new_v4();
+        let command = parse_command(&wrap(serde_json::json!({
+            "publisher": p.to_string(), "seq": 3, "low": 2, "message": { "data": 1 },
+        })))
+        .unwrap();
+        assert_eq!((command.publisher, command.seq, command.low), (p, 3, 2));
+        assert_eq!(command.message, serde_json::json!({ "data": 1 }));
+        assert!(parse_command(&wrap(serde_json::json!({ "seq": 3 }))).is_err());
+
+        let ack = parse_ack(&wrap(serde_json::json!({
+            "publisher": p.to_string(), "seq": 3, "status": "duplicate",
+        })))
+        .unwrap();
+        assert_eq!(
+            (ack.publisher, ack.seq, ack.status),
+            (p, 3, AckStatus::Duplicate)
+        );
+        assert!(parse_ack(&wrap(serde_json::json!({
+            "publisher": p.to_string(), "seq": 3, "status": "maybe",
+        })))
+        .is_err());
+    }
+}
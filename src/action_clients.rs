@@ -1,15 +1,28 @@
 use futures::channel::{mpsc, oneshot};
-use futures::future::{FutureExt, TryFutureExt};
-use futures::stream::Stream;
-use std::collections::HashMap;
+use futures::future::{self, BoxFuture, Either, FutureExt, TryFutureExt};
+use futures::stream::{BoxStream, Stream, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::future::Future;
 use std::sync::{Mutex, Weak};
 use std::mem::MaybeUninit;
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
+use std::time::{Duration, Instant};
 
+use crate::arguments::resolve_topic_name;
 use crate::error::*;
 use crate::action_common::*;
+use crate::clients::{ResponseGuard, ServerCheck};
+use crate::error_events::*;
+use crate::log_handler::log_internal;
+use crate::utils::LogSeverity;
+use crate::executor::EntityKind;
+use crate::message_sinks::{sink_full, ChannelSink, DeliverResult, MessageSink};
 use crate::msg_types::*;
+use crate::nodes::{publishers_info_by_topic, Node};
+use crate::publishers::PublisherUntyped;
+use crate::qos::QosProfile;
+use crate::traits::Entity;
 use crate::msg_types::generated_msgs::{
     unique_identifier_msgs,
     action_msgs,
@@ -18,6 +31,133 @@ use crate::msg_types::generated_msgs::{
 use r2r_rcl::*;
 use r2r_actions::*;
 
+/// Options for creating action clients.
+#[derive(Debug, Clone)]
+pub struct ActionClientOptions {
+    /// Publish `GoalMetadata` for goals sent with
+    /// `GoalRequestBuilder::metadata`.
+    pub goal_metadata: bool,
+    /// Fail with `Error::MultipleServiceServers` when more than one
+    /// node serves the goal, cancel or result service of the action,
+    /// see `ClientOptions::expect_single_server`.
+    pub expect_single_server: bool,
+    /// When an accepted goal gets no status update within this time,
+    /// check the QoS of the status and feedback publishers of the
+    /// action and report incompatible ones as
+    /// `Error::ActionStatusQosMismatch` to the node error stream.
+    /// Five seconds by default.
+    pub status_qos_check: Option<Duration>,
+    /// QoS of the goal, cancel and result services and of the
+    /// feedback topic, unless `service_qos` or `feedback_qos` is set.
+    /// The status topic keeps its transient local profile.
+    pub qos: Option<QosProfile>,
+    /// QoS of the goal, cancel and result services. The rcl default
+    /// when neither this nor `qos` is set.
+    pub service_qos: Option<QosProfile>,
+    /// QoS of the feedback subscription, e.g. best effort with a depth
+    /// of one for large feedback where only the latest matters. The
+    /// rcl default when neither this nor `qos` is set.
+    pub feedback_qos: Option<QosProfile>,
+    /// QoS of the status subscription, transient local by default. It
+    /// has to be compatible with the status publisher of the server.
+    pub status_qos: Option<QosProfile>,
+    /// Fail goal requests with `Error::RequestTimedOut` when the server
+    /// has not answered within this time. A late answer is reported as
+    /// unmatched.
+    pub goal_response_timeout: Option<Duration>,
+    /// Fail result futures with `Error::RequestTimedOut` when the result
+    /// has not arrived within this time after it was requested.
+    pub result_timeout: Option<Duration>,
+    /// Fail cancel requests with `Error::RequestTimedOut` when the
+    /// server has not answered within this time.
+    pub cancel_response_timeout: Option<Duration>,
+    /// How many feedback messages of a goal are buffered, 10 by default.
+    pub feedback_capacity: usize,
+    /// Request the result of a goal as soon as it is accepted (the
+    /// default). Otherwise the result is requested when the result
+    /// future is first polled.
+    pub auto_request_result: bool,
+    /// Also track the status of goals sent by other clients, as seen
+    /// on the status topic, see `ActionClient::known_goals`.
+    pub track_all_goals: bool,
+    /// How long the status of a goal is kept after it reached a
+    /// terminal status. One minute by default.
+    pub terminal_status_horizon: Duration,
+    /// Report `Error::BookkeepingOversized` to the node error stream
+    /// when one of the internal maps of the client grows beyond this
+    /// many entries, see `ActionClient::bookkeeping_report`. 10000 by
+    /// default.
+    pub bookkeeping_warning_size: Option<usize>,
+    /// Publish the goals and the bookkeeping report of the client on
+    /// the hidden topic `~/_r2r/action_client_debug` this often, as a
+    /// `diagnostic_msgs/msg/DiagnosticArray`. Off by default.
+    pub debug_period: Option<Duration>,
+}
+
+impl Default for ActionClientOptions {
+    fn default() -> Self {
+        ActionClientOptions {
+            goal_metadata: false,
+            expect_single_server: false,
+            status_qos_check: Some(Duration::from_secs(5)),
+            qos: None,
+            service_qos: None,
+            feedback_qos: None,
+            status_qos: None,
+            goal_response_timeout: None,
+            result_timeout: None,
+            cancel_response_timeout: None,
+            feedback_capacity: 10,
+            auto_request_result: true,
+            track_all_goals: false,
+            terminal_status_horizon: Duration::from_secs(60),
+            bookkeeping_warning_size: Some(10_000),
+            debug_period: None,
+        }
+    }
+}
+
+/// Watches a goal for a server that stopped making progress, see
+/// `GoalRequestBuilder::watchdog`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GoalWatchdog {
+    /// Cancel the goal when neither feedback nor a status change has
+    /// been seen for this long after it was accepted.
+    pub inactivity: Duration,
+    /// Fail the result future with `Error::GoalStuck` when the goal
+    /// has not ended this long after it was canceled.
+    pub cancel_timeout: Duration,
+}
+
+impl GoalWatchdog {
+    pub fn new(inactivity: Duration, cancel_timeout: Duration) -> Self {
+        GoalWatchdog {
+            inactivity,
+            cancel_timeout,
+        }
+    }
+}
+
+/// The entries of one of the internal maps of an action client, see
+/// `ActionClient::bookkeeping_report`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookkeepingStats {
+    /// Name of the map, e.g. `goal_status`.
+    pub map: &'static str,
+    pub entries: usize,
+    /// Time since the oldest entry was inserted.
+    pub oldest: Option<Duration>,
+    /// Time since the newest entry was inserted.
+    pub newest: Option<Duration>,
+}
+
+// A watchdog is armed when its goal is accepted.
+pub(crate) struct GoalWatchdogState {
+    watchdog: GoalWatchdog,
+    deadline: Option<Instant>,
+    cancel_sent: bool,
+}
+
 unsafe impl<T> Send for ActionClient<T> where T: WrappedActionTypeSupport {}
 
 /// Action client
@@ -42,7 +182,9 @@ where
     T: WrappedActionTypeSupport,
 {
     client: Weak<Mutex<WrappedActionClient<T>>>,
-    pub uuid: uuid::Uuid,
+    pub uuid: GoalId,
+    /// When the server accepted the goal, from its goal response.
+    pub accepted_at: builtin_interfaces::msg::Time,
 }
 
 impl<T: 'static> ActionClientGoal<T>
@@ -50,6 +192,11 @@ where
     T: WrappedActionTypeSupport,
 {
     /// Get the current status of this goal.
+    ///
+    /// The status is `Accepted` until the server reports another one.
+    /// The client forgets a goal once it has been in a terminal status
+    /// for `ActionClientOptions::terminal_status_horizon`, after which
+    /// the status is `Unknown`.
     pub fn get_status(&self) -> Result<GoalStatus> {
         let client = self
             .client
@@ -60,14 +207,29 @@ where
         Ok(client.get_goal_status(&self.uuid))
     }
 
+    /// Get a stream of the status changes of this goal.
+    ///
+    /// The stream starts with the current status if one has been
+    /// received, yields each status only once and ends after a
+    /// terminal status (succeeded, canceled or aborted).
+    pub fn subscribe_status(&self) -> Result<impl Stream<Item = GoalStatus> + Unpin> {
+        let client = self
+            .client
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
+        let mut client = client.lock().unwrap();
+
+        Ok(client.subscribe_status(&self.uuid))
+    }
+
     /// Send a cancel request for this goal to the server.
     ///
-    /// If the server accepts and completes the request, the future completes without error.
-    /// Otherwise, one of these errors can be returned:
+    /// If the server accepts the request, the future resolves to its
+    /// answer. Otherwise, one of these errors can be returned:
     /// - `GoalCancelRejected`
     /// - `GoalCancelUnknownGoalID`
     /// - `GoalCancelAlreadyTerminated`
-    pub fn cancel(&self) -> Result<impl Future<Output = Result<()>>> {
+    pub fn cancel(&self) -> Result<impl Future<Output = Result<CancelResult>>> {
         // upgrade to actual ref. if still alive
         let client = self
             .client
@@ -79,16 +241,125 @@ where
     }
 }
 
+/// A goal sent with `ActionClient::send_goal`.
+///
+/// Bundles the goal id with its status, feedback, result and
+/// cancellation. Dropping the handle makes the client forget the
+/// feedback and result of the goal, unless they have been taken out
+/// with `feedback` and `result` and are still in use.
+pub struct ClientGoalHandle<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    goal: ActionClientGoal<T>,
+    feedback: Option<BoxStream<'static, T::Feedback>>,
+    result: Option<BoxFuture<'static, Result<(GoalStatus, T::Result)>>>,
+}
+
+impl<T: 'static> ClientGoalHandle<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    pub fn uuid(&self) -> GoalId {
+        self.goal.uuid
+    }
+
+    /// Get the current status of the goal.
+    pub fn status(&self) -> Result<GoalStatus> {
+        self.goal.get_status()
+    }
+
+    /// See `ActionClientGoal::subscribe_status`.
+    pub fn subscribe_status(&self) -> Result<impl Stream<Item = GoalStatus> + Unpin> {
+        self.goal.subscribe_status()
+    }
+
+    /// Take the stream of feedback messages of the goal. Returns `None`
+    /// if it has already been taken.
+    pub fn feedback(&mut self) -> Option<impl Stream<Item = T::Feedback> + Unpin> {
+        self.feedback.take()
+    }
+
+    /// Take the future for the result of the goal. Returns `None` if it
+    /// has already been taken.
+    pub fn result(&mut self) -> Option<impl Future<Output = Result<(GoalStatus, T::Result)>>> {
+        self.result.take()
+    }
+
+    /// See `ActionClientGoal::cancel`.
+    pub fn cancel(&self) -> Result<impl Future<Output = Result<CancelResult>>> {
+        self.goal.cancel()
+    }
+
+    /// The plain goal handle, which can be cloned.
+    pub fn goal(&self) -> &ActionClientGoal<T> {
+        &self.goal
+    }
+}
+
+impl<T> Drop for ClientGoalHandle<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    fn drop(&mut self) {
+        // drop our ends first, so only the ones still in use are kept.
+        self.feedback.take();
+        self.result.take();
+        if let Some(client) = self.goal.client.upgrade() {
+            client
+                .lock()
+                .unwrap()
+                .forget_dropped_receivers(self.goal.uuid.as_uuid());
+        }
+    }
+}
+
 impl<T: 'static> ActionClient<T>
 where
     T: WrappedActionTypeSupport,
 {
+    /// Whether the action server is available right now. Use
+    /// `Node::is_available` to wait until it is.
+    pub fn is_available(&self, node: &Node) -> Result<bool> {
+        let client = self
+            .client
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
+        let client = client.lock().unwrap();
+        node.action_server_available(client.handle())
+    }
+
+    /// Make a new goal request.
+    ///
+    /// If the server accepts the new goal, the future resolves to a
+    /// handle for its status, feedback, result and cancellation. If
+    /// not, it fails with `Error::GoalRejected`.
+    pub fn send_goal(
+        &self,
+        goal: T::Goal,
+    ) -> Result<impl Future<Output = Result<ClientGoalHandle<T>>>>
+    where
+        T: WrappedActionTypeSupport,
+    {
+        let future = self.goal_request_builder(goal).send()?;
+        Ok(future.map(|r| {
+            r.map(|(goal, result, feedback)| ClientGoalHandle {
+                goal,
+                feedback: Some(feedback.boxed()),
+                result: Some(result.boxed()),
+            })
+        }))
+    }
+
     /// Make a new goal request.
     ///
     /// If the server accepts the new goal, the future resolves to a triple of:
-    /// - A goal handle.
+    /// - A goal handle, with the time the goal was accepted.
     /// - A new future for the eventual result.
     /// - A stream of feedback messages.
+    ///
+    /// If the server rejects the goal, the future fails with
+    /// `Error::GoalRejected`.
     pub fn send_goal_request(
         &self,
         goal: T::Goal,
@@ -104,14 +375,242 @@ where
     where
         T: WrappedActionTypeSupport,
     {
-        // upgrade to actual ref. if still alive
+        self.goal_request_builder(goal).send()
+    }
+
+    /// Make a new goal request with options, e.g. a goal id, metadata
+    /// or a watchdog, which can be combined.
+    ///
+    /// ```ignore
+    /// let goal = client
+    ///     .goal_request_builder(Fibonacci::Goal { order: 5 })
+    ///     .goal_id(id)
+    ///     .watchdog(GoalWatchdog::new(inactivity, cancel_timeout))
+    ///     .send()?;
+    /// ```
+    pub fn goal_request_builder(&self, goal: T::Goal) -> GoalRequestBuilder<T> {
+        GoalRequestBuilder {
+            client: Weak::clone(&self.client),
+            goal,
+            goal_id: None,
+            metadata: None,
+            watchdog: None,
+            feedback_sink: None,
+        }
+    }
+
+    /// Cancel all goals of the action server, e.g. for an emergency
+    /// stop. The future resolves to the answer of the server, with the
+    /// goals that are being canceled.
+    pub fn cancel_all_goals(&self) -> Result<impl Future<Output = Result<CancelResult>>> {
+        let client = self
+            .client
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
+        let mut client = client.lock().unwrap();
+        client.send_cancel_all_requests()
+    }
+
+    /// Cancel all goals the action server accepted at or before
+    /// `stamp`. The future resolves to the answer of the server, with
+    /// the goals that are being canceled.
+    pub fn cancel_goals_before(
+        &self,
+        stamp: builtin_interfaces::msg::Time,
+    ) -> Result<impl Future<Output = Result<CancelResult>>> {
+        let client = self
+            .client
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
+        let mut client = client.lock().unwrap();
+        client.send_cancel_requests_before(stamp)
+    }
+
+    /// Number of responses on the goal, cancel and result services
+    /// that were dropped because they did not belong to a request
+    /// made by this client.
+    ///
+    /// See `ClientUntyped::stale_responses`.
+    pub fn stale_responses(&self) -> Result<usize> {
+        let client = self
+            .client
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
+        let client = client.lock().unwrap();
+        Ok(client.goal_response_guard.stale_responses()
+            + client.cancel_response_guard.stale_responses()
+            + client.result_response_guard.stale_responses())
+    }
+
+    /// The number and age of the entries in each of the internal maps
+    /// of the client, to audit that finished goals are forgotten.
+    ///
+    /// The age of an entry is the time since the client first tracked
+    /// its goal, or for cancel requests since the request was sent.
+    pub fn bookkeeping_report(&self) -> Result<Vec<BookkeepingStats>> {
+        let client = self
+            .client
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
+        let client = client.lock().unwrap();
+        Ok(client.bookkeeping_report(Instant::now()))
+    }
+
+    /// Forget the goals that reached a terminal status more than
+    /// `older_than` ago, before `ActionClientOptions::terminal_status_horizon`
+    /// would. Returns how many goals were forgotten.
+    pub fn purge_terminal(&self, older_than: Duration) -> Result<usize> {
+        let client = self
+            .client
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
+        let mut client = client.lock().unwrap();
+        Ok(client.purge_terminal(Instant::now(), older_than))
+    }
+
+    /// The goals this client knows the status of, i.e. its own goals
+    /// and, with `ActionClientOptions::track_all_goals`, all goals on
+    /// the status topic of the action. Goals are forgotten some time
+    /// after they reach a terminal status, see
+    /// `ActionClientOptions::terminal_status_horizon`.
+    pub fn known_goals(&self) -> Result<Vec<(GoalId, GoalStatus)>> {
         let client = self
             .client
             .upgrade()
             .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
+        let client = client.lock().unwrap();
+        Ok(client.known_goals())
+    }
+
+    /// `known_goals` with the ids as plain UUIDs.
+    #[deprecated(note = "use `known_goals`, which returns `GoalId`s")]
+    pub fn known_goal_uuids(&self) -> Result<Vec<(uuid::Uuid, GoalStatus)>> {
+        Ok(self
+            .known_goals()?
+            .into_iter()
+            .map(|(id, status)| (id.into(), status))
+            .collect())
+    }
+}
+
+/// A goal request with options, see `ActionClient::goal_request_builder`.
+pub struct GoalRequestBuilder<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    client: Weak<Mutex<WrappedActionClient<T>>>,
+    goal: T::Goal,
+    goal_id: Option<GoalId>,
+    metadata: Option<(Option<builtin_interfaces::msg::Time>, i32)>,
+    watchdog: Option<GoalWatchdog>,
+    feedback_sink: Option<Box<dyn MessageSink<T::Feedback>>>,
+}
+
+impl<T: 'static> GoalRequestBuilder<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    /// Use a goal id chosen by the caller, e.g. one derived from the
+    /// request, so that a retry after a restart uses the same id.
+    ///
+    /// `send` fails with `Error::GoalIdInUse` when this client already
+    /// has a goal with that id, pending or still known, see
+    /// `ActionClientOptions::terminal_status_horizon`.
+    pub fn goal_id(mut self, goal_id: impl Into<GoalId>) -> Self {
+        self.goal_id = Some(goal_id.into());
+        self
+    }
+
+    /// Publish `GoalMetadata` with a deadline and a priority for the
+    /// goal, which r2r action servers with `goal_metadata` enabled can
+    /// read from the goal handle. It is published once the goal
+    /// request has been sent, so it can arrive after the goal.
+    ///
+    /// The client must have been created with `goal_metadata` enabled,
+    /// otherwise `send` fails with `Error::GoalMetadataNotEnabled`.
+    pub fn metadata(
+        mut self,
+        deadline: Option<builtin_interfaces::msg::Time>,
+        priority: i32,
+    ) -> Self {
+        self.metadata = Some((deadline, priority));
+        self
+    }
+
+    /// Cancel the goal when the server stops making progress on it.
+    ///
+    /// Once the goal is accepted, it is canceled if no feedback or
+    /// status change arrives within `watchdog.inactivity`. If the goal
+    /// then does not end within `watchdog.cancel_timeout`, the result
+    /// future fails with `Error::GoalStuck` and the client forgets the
+    /// goal.
+    pub fn watchdog(mut self, watchdog: GoalWatchdog) -> Self {
+        self.watchdog = Some(watchdog);
+        self
+    }
+
+    /// Deliver the feedback to `sink` instead of the feedback stream,
+    /// for applications that want it in queues of their own. The
+    /// feedback stream then ends right away. Feedback the sink drops as
+    /// full is reported as `Error::DeliveryFailed`.
+    pub fn feedback_sink(mut self, sink: impl MessageSink<T::Feedback> + 'static) -> Self {
+        self.feedback_sink = Some(Box::new(sink));
+        self
+    }
+
+    /// Send the goal request, see `ActionClient::send_goal_request`.
+    pub fn send(
+        self,
+    ) -> Result<
+        impl Future<
+            Output = Result<(
+                ActionClientGoal<T>,
+                impl Future<Output = Result<(GoalStatus, T::Result)>>,
+                impl Stream<Item = T::Feedback> + Unpin,
+            )>,
+        >,
+    >
+    where
+        T: WrappedActionTypeSupport,
+    {
+        let GoalRequestBuilder {
+            client: weak_client,
+            goal,
+            goal_id,
+            metadata,
+            watchdog,
+            feedback_sink,
+        } = self;
+        // upgrade to actual ref. if still alive
+        let client = weak_client
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
         let mut client = client.lock().unwrap();
+        client.server_check.check()?;
 
-        let uuid = uuid::Uuid::new_v4();
+        let uuid = match goal_id {
+            Some(goal_id) if client.goal_in_use(&goal_id) => {
+                return Err(Error::GoalIdInUse {
+                    goal: goal_id.to_string(),
+                })
+            }
+            Some(goal_id) => *goal_id.as_uuid(),
+            None => uuid::Uuid::new_v4(),
+        };
+        let metadata = match metadata {
+            Some((deadline, priority)) => {
+                let metadata = GoalMetadata {
+                    goal_id: uuid.into(),
+                    deadline,
+                    priority,
+                };
+                if client.goal_metadata_publisher.is_none() {
+                    return Err(Error::GoalMetadataNotEnabled);
+                }
+                Some(metadata.to_msg_json()?)
+            }
+            None => None,
+        };
         let uuid_msg = unique_identifier_msgs::msg::UUID {
             uuid: uuid.as_bytes().to_vec(),
         };
@@ -120,56 +619,263 @@ where
             <<T as WrappedActionTypeSupport>::SendGoal as WrappedServiceTypeSupport>::Request,
         >::from(&request_msg);
         let mut seq_no = 0i64;
+        // The client lock is held from the send until the pending entries
+        // below are registered. The spin thread needs the same lock to take
+        // the goal response, so it can never see a response for a sequence
+        // number that we have not registered yet.
         let result = unsafe {
             rcl_action_send_goal_request(&client.rcl_handle, native_msg.void_ptr(), &mut seq_no)
         };
 
+        if result != RCL_RET_OK as i32 {
+            let err = Error::from_rcl_error(result);
+            client.errors.log(
+                LogSeverity::Warn,
+                &format!("could not send goal request: {}", err),
+            );
+            return Err(err);
+        }
+        if let (Some(metadata), Some(publisher)) = (metadata, &client.goal_metadata_publisher) {
+            // the goal is on its way, so a failure here only loses the metadata.
+            if let Err(err) = publisher.publish(metadata) {
+                client.errors.log(
+                    LogSeverity::Warn,
+                    &format!("could not publish goal metadata: {}", err),
+                );
+            }
+        }
+
         // set up channels
         let (goal_req_sender, goal_req_receiver) =
-            oneshot::channel::<(bool, builtin_interfaces::msg::Time)>();
-        let (feedback_sender, feedback_receiver) = mpsc::channel::<T::Feedback>(10);
-        client.feedback_senders.push((uuid, feedback_sender));
-        let (result_sender, result_receiver) = oneshot::channel::<(GoalStatus, T::Result)>();
-        client.result_senders.push((uuid, result_sender));
-
-        if result == RCL_RET_OK as i32 {
+            oneshot::channel::<Result<(bool, builtin_interfaces::msg::Time)>>();
+        let (feedback_sender, feedback_receiver) =
+            mpsc::channel::<T::Feedback>(client.options.feedback_capacity);
+        // with a sink of the user, the feedback stream ends right away.
+        let feedback_sink = match feedback_sink {
+            Some(sink) => sink,
+            None => Box::new(ChannelSink::new(feedback_sender)),
+        };
+        client.feedback_senders.insert(uuid, feedback_sink);
+        client.first_seen.insert(uuid, Instant::now());
+        let (result_sender, result_receiver) =
+            oneshot::channel::<Result<(GoalStatus, T::Result)>>();
+        client.result_senders.insert(uuid, result_sender);
+        client
+            .goal_response_channels
+            .insert(seq_no, (uuid, goal_req_sender));
+        if let Some(timeout) = client.options.goal_response_timeout {
             client
-                .goal_response_channels
-                .push((seq_no, goal_req_sender));
-            // instead of "canceled" we return invalid client.
-            let fut_client = Weak::clone(&self.client);
-            let future = goal_req_receiver
-                .map_err(|_| Error::RCL_RET_ACTION_CLIENT_INVALID)
-                .map(move |r| match r {
-                    Ok((accepted, _stamp)) => {
-                        if accepted {
-                            // on goal accept we immediately send the result request
-                            {
-                                let c = fut_client
-                                    .upgrade()
-                                    .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
-                                let mut c = c.lock().unwrap();
-                                c.send_result_request(uuid.clone());
+                .goal_response_deadlines
+                .insert(uuid, Instant::now() + timeout);
+        }
+        if let Some(watchdog) = watchdog {
+            client.watchdogs.insert(
+                uuid,
+                GoalWatchdogState {
+                    watchdog,
+                    deadline: None,
+                    cancel_sent: false,
+                },
+            );
+        }
+        let auto_request_result = client.options.auto_request_result;
+
+        // instead of "canceled" we return invalid client.
+        let fut_client = weak_client;
+        let future = goal_req_receiver
+            .map_err(|_| Error::RCL_RET_ACTION_CLIENT_INVALID)
+            .map(move |r| match r.and_then(|r| r) {
+                Ok((accepted, stamp)) => {
+                    if accepted {
+                        // unless disabled, the result request has already
+                        // been sent from the spin thread when the goal was
+                        // accepted, so we never need to lock the client here.
+                        let result_client = Weak::clone(&fut_client);
+                        let result = future::lazy(move |_| {
+                            if !auto_request_result {
+                                if let Some(client) = result_client.upgrade() {
+                                    client.lock().unwrap().send_result_request(uuid);
+                                }
                             }
+                        })
+                        .then(move |_| {
+                            result_receiver.map(|r| match r {
+                                Ok(r) => r,
+                                Err(_) => Err(Error::RCL_RET_ACTION_CLIENT_INVALID),
+                            })
+                        });
+                        Ok((
+                            ActionClientGoal {
+                                client: fut_client,
+                                uuid: uuid.into(),
+                                accepted_at: stamp,
+                            },
+                            result,
+                            feedback_receiver,
+                        ))
+                    } else {
+                        Err(Error::GoalRejected)
+                    }
+                }
+                Err(e) => Err(e),
+            });
+        Ok(future)
+    }
+}
 
-                            Ok((
-                                ActionClientGoal {
-                                    client: fut_client,
-                                    uuid,
-                                },
-                                result_receiver.map_err(|_| Error::RCL_RET_ACTION_CLIENT_INVALID),
-                                feedback_receiver,
-                            ))
-                        } else {
-                            Err(Error::RCL_RET_ACTION_GOAL_REJECTED)
-                        }
+/// Creates an action client with all of its options in one place.
+///
+/// ```ignore
+/// let client = node
+///     .action_client_builder::<Fibonacci::Action>("/fibonacci")
+///     .wait_for_server(Duration::from_secs(5))
+///     .goal_response_timeout(Duration::from_secs(1))
+///     .build();
+/// // spin the node, e.g. on another thread.
+/// let client = client.await?;
+/// ```
+///
+/// `build` returns a future that does not borrow the node, which must
+/// be spun for the server to be found.
+pub struct ActionClientBuilder<'a, T>
+where
+    T: WrappedActionTypeSupport,
+{
+    node: &'a mut Node,
+    action_name: String,
+    options: ActionClientOptions,
+    wait_for_server: Option<Duration>,
+}
+
+impl<'a, T: 'static> ActionClientBuilder<'a, T>
+where
+    T: WrappedActionTypeSupport,
+{
+    pub(crate) fn new(node: &'a mut Node, action_name: &str) -> Self {
+        ActionClientBuilder {
+            node,
+            action_name: action_name.to_owned(),
+            options: ActionClientOptions::default(),
+            wait_for_server: None,
+        }
+    }
+
+    /// See `ActionClientOptions::qos`.
+    pub fn qos(mut self, qos: QosProfile) -> Self {
+        self.options.qos = Some(qos);
+        self
+    }
+
+    /// See `ActionClientOptions::service_qos`.
+    pub fn service_qos(mut self, qos: QosProfile) -> Self {
+        self.options.service_qos = Some(qos);
+        self
+    }
+
+    /// See `ActionClientOptions::feedback_qos`.
+    pub fn feedback_qos(mut self, qos: QosProfile) -> Self {
+        self.options.feedback_qos = Some(qos);
+        self
+    }
+
+    /// See `ActionClientOptions::status_qos`.
+    pub fn status_qos(mut self, qos: QosProfile) -> Self {
+        self.options.status_qos = Some(qos);
+        self
+    }
+
+    /// Only complete `build` once the action server is available, or
+    /// fail with `Error::ActionServerUnavailable` after `timeout`.
+    pub fn wait_for_server(mut self, timeout: Duration) -> Self {
+        self.wait_for_server = Some(timeout);
+        self
+    }
+
+    /// See `ActionClientOptions::goal_response_timeout`.
+    pub fn goal_response_timeout(mut self, timeout: Duration) -> Self {
+        self.options.goal_response_timeout = Some(timeout);
+        self
+    }
+
+    /// See `ActionClientOptions::result_timeout`.
+    pub fn result_timeout(mut self, timeout: Duration) -> Self {
+        self.options.result_timeout = Some(timeout);
+        self
+    }
+
+    /// See `ActionClientOptions::cancel_response_timeout`.
+    pub fn cancel_response_timeout(mut self, timeout: Duration) -> Self {
+        self.options.cancel_response_timeout = Some(timeout);
+        self
+    }
+
+    /// See `ActionClientOptions::feedback_capacity`.
+    pub fn feedback_capacity(mut self, capacity: usize) -> Self {
+        self.options.feedback_capacity = capacity;
+        self
+    }
+
+    /// See `ActionClientOptions::auto_request_result`.
+    pub fn auto_request_result(mut self, enable: bool) -> Self {
+        self.options.auto_request_result = enable;
+        self
+    }
+
+    /// See `ActionClientOptions::track_all_goals`.
+    pub fn track_all_goals(mut self, enable: bool) -> Self {
+        self.options.track_all_goals = enable;
+        self
+    }
+
+    /// See `ActionClientOptions::terminal_status_horizon`.
+    pub fn terminal_status_horizon(mut self, horizon: Duration) -> Self {
+        self.options.terminal_status_horizon = horizon;
+        self
+    }
+
+    /// See `ActionClientOptions::goal_metadata`.
+    pub fn goal_metadata(mut self, enable: bool) -> Self {
+        self.options.goal_metadata = enable;
+        self
+    }
+
+    /// See `ActionClientOptions::debug_period`.
+    pub fn debug_period(mut self, period: Duration) -> Self {
+        self.options.debug_period = Some(period);
+        self
+    }
+
+    /// Create the action client. Errors from creating it are returned
+    /// by the future.
+    pub fn build(self) -> impl Future<Output = Result<ActionClient<T>>> {
+        let node = self.node;
+        let action_name = self.action_name;
+        let wait_for_server = self.wait_for_server;
+        let created = node
+            .create_action_client_with_options::<T>(&action_name, self.options)
+            .and_then(|client| match wait_for_server {
+                Some(timeout) => {
+                    let available = node.is_available(&client)?;
+                    let timer = node.create_wall_timer(timeout)?;
+                    Ok((client, Some((available, timer, timeout))))
+                }
+                None => Ok((client, None)),
+            });
+        async move {
+            let (client, wait) = created?;
+            if let Some((available, mut timer, timeout)) = wait {
+                let timed_out = Box::pin(async move { timer.tick().await });
+                match future::select(available, timed_out).await {
+                    Either::Left((available, _)) => available?,
+                    Either::Right(_) => {
+                        return Err(Error::ActionServerUnavailable {
+                            action_name,
+                            timeout,
+                        })
                     }
-                    Err(e) => Err(e),
-                });
-            Ok(future)
-        } else {
-            eprintln!("coult not send goal request {}", result);
-            Err(Error::from_rcl_error(result))
+                }
+            }
+            Ok(client)
         }
     }
 }
@@ -181,20 +887,56 @@ where
     ActionClient { client }
 }
 
+unsafe impl<T> Send for WrappedActionClient<T> where T: WrappedActionTypeSupport {}
+
 pub struct WrappedActionClient<T>
 where
     T: WrappedActionTypeSupport,
 {
     pub rcl_handle: rcl_action_client_t,
-    pub goal_response_channels: Vec<(i64, oneshot::Sender<(bool, builtin_interfaces::msg::Time)>)>,
+    // requests by sequence number and goals by id, so that finding
+    // them stays cheap with many goals in flight.
+    pub goal_response_channels: HashMap<
+        i64,
+        (
+            uuid::Uuid,
+            oneshot::Sender<Result<(bool, builtin_interfaces::msg::Time)>>,
+        ),
+    >,
     pub cancel_response_channels:
-        Vec<(i64, oneshot::Sender<action_msgs::srv::CancelGoal::Response>)>,
-    pub feedback_senders: Vec<(uuid::Uuid, mpsc::Sender<T::Feedback>)>,
-    pub result_requests: Vec<(i64, uuid::Uuid)>,
-    pub result_senders: Vec<(uuid::Uuid, oneshot::Sender<(GoalStatus, T::Result)>)>,
+        HashMap<i64, oneshot::Sender<Result<action_msgs::srv::CancelGoal::Response>>>,
+    pub feedback_senders: HashMap<uuid::Uuid, Box<dyn MessageSink<T::Feedback>>>,
+    pub result_requests: HashMap<i64, uuid::Uuid>,
+    pub result_senders: HashMap<uuid::Uuid, oneshot::Sender<Result<(GoalStatus, T::Result)>>>,
+    pub goal_response_deadlines: HashMap<uuid::Uuid, Instant>,
+    pub result_deadlines: HashMap<uuid::Uuid, Instant>,
+    pub cancel_response_deadlines: HashMap<i64, Instant>,
+    pub(crate) watchdogs: HashMap<uuid::Uuid, GoalWatchdogState>,
+    // cancel requests sent by watchdogs, nobody waits for the answer.
+    pub(crate) watchdog_cancels:
+        Vec<oneshot::Receiver<Result<action_msgs::srv::CancelGoal::Response>>>,
+    pub options: ActionClientOptions,
     pub goal_status: HashMap<uuid::Uuid, GoalStatus>,
+    // when goals reached a terminal status, to forget them later.
+    pub terminal_goals: HashMap<uuid::Uuid, Instant>,
+    // forgotten goals still on the status topic, not to be tracked again.
+    pub forgotten_goals: HashSet<uuid::Uuid>,
+    // when goals were first tracked and cancel requests sent, for the
+    // bookkeeping report. Pruned by audit_bookkeeping.
+    pub(crate) first_seen: HashMap<uuid::Uuid, Instant>,
+    pub(crate) cancels_sent: HashMap<i64, Instant>,
+    // maps above the warning size that have been reported.
+    pub(crate) oversized_maps: HashSet<&'static str>,
+    pub status_senders: Vec<(uuid::Uuid, mpsc::Sender<GoalStatus>)>,
+    pub goal_response_guard: ResponseGuard,
+    pub cancel_response_guard: ResponseGuard,
+    pub result_response_guard: ResponseGuard,
+    pub goal_metadata_publisher: Option<PublisherUntyped>,
 
-    pub poll_available_channels: Vec<oneshot::Sender<()>>,
+    pub poll_available_channels: Vec<oneshot::Sender<Result<()>>>,
+    pub server_check: ServerCheck,
+    pub status_qos_check: StatusQosCheck,
+    pub errors: EntityErrors,
 }
 
 pub trait ActionClient_ {
@@ -209,33 +951,455 @@ pub trait ActionClient_ {
 
     fn send_result_request(&mut self, uuid: uuid::Uuid) -> ();
 
-    fn register_poll_available(&mut self, s: oneshot::Sender<()>) -> ();
+    fn register_poll_available(&mut self, s: oneshot::Sender<Result<()>>) -> ();
     fn poll_available(&mut self, node: &mut rcl_node_t) -> ();
+    fn check_servers(&mut self, node: &rcl_node_t) -> ();
+    fn check_status_qos(&mut self, node: &rcl_node_t) -> ();
+    fn check_timeouts(&mut self) -> ();
+    fn cancel_pending_goals(&mut self) -> ();
 }
 
 impl<T> WrappedActionClient<T>
 where
     T: WrappedActionTypeSupport,
 {
-    pub fn get_goal_status(&self, uuid: &uuid::Uuid) -> GoalStatus {
-        *self.goal_status.get(uuid).unwrap_or(&GoalStatus::Unknown)
+    pub fn get_goal_status(&self, goal: &GoalId) -> GoalStatus {
+        *self
+            .goal_status
+            .get(goal.as_uuid())
+            .unwrap_or(&GoalStatus::Unknown)
+    }
+
+    // Whether a goal with this id has been sent and not forgotten.
+    fn goal_in_use(&self, uuid: &uuid::Uuid) -> bool {
+        self.goal_status.contains_key(uuid)
+            || self.result_senders.contains_key(uuid)
+            || self.feedback_senders.contains_key(uuid)
+            || self.goal_response_channels.values().any(|(u, _)| u == uuid)
+    }
+
+    pub fn known_goals(&self) -> Vec<(GoalId, GoalStatus)> {
+        self.goal_status
+            .iter()
+            .map(|(uuid, status)| ((*uuid).into(), *status))
+            .collect()
+    }
+
+    pub fn subscribe_status(&mut self, goal: &GoalId) -> mpsc::Receiver<GoalStatus> {
+        let (mut sender, receiver) = mpsc::channel::<GoalStatus>(10);
+        let current = self.get_goal_status(goal);
+        if current != GoalStatus::Unknown {
+            // cannot fail, the channel is empty.
+            let _ = sender.try_send(current);
+        }
+        if !current.is_terminal() {
+            self.status_senders.push((*goal.as_uuid(), sender));
+        }
+        receiver
+    }
+
+    // Stores the status of a goal and passes it on to its status
+    // streams if it changed.
+    fn set_goal_status(&mut self, uuid: uuid::Uuid, status: GoalStatus) {
+        if self.goal_status.insert(uuid, status) == Some(status) {
+            return;
+        }
+        self.first_seen.entry(uuid).or_insert_with(Instant::now);
+        if status.is_terminal() {
+            self.watchdogs.remove(&uuid);
+        } else {
+            self.goal_activity(&uuid);
+        }
+        for (_, sender) in self.status_senders.iter_mut().filter(|(s, _)| s == &uuid) {
+            match sender.try_send(status) {
+                Err(e) if e.is_full() => self.errors.report(
+                    SpinOperation::Deliver,
+                    Error::DeliveryFailed {
+                        reason: e.to_string(),
+                    },
+                ),
+                _ => (),
+            }
+        }
+        // dropping the senders ends the streams.
+        self.status_senders
+            .retain(|(s, sender)| !sender.is_closed() && (s != &uuid || !status.is_terminal()));
+        // the status of a finished goal is kept for a while, see
+        // forget_terminal_goals.
+        if status.is_terminal() {
+            self.feedback_senders.remove(&uuid);
+            self.terminal_goals.insert(uuid, Instant::now());
+        }
+    }
+
+    // Forgets the goals that reached a terminal status more than the
+    // horizon ago, so that clients seeing many goals do not grow.
+    fn forget_terminal_goals(&mut self, now: Instant) {
+        self.purge_terminal(now, self.options.terminal_status_horizon);
+    }
+
+    pub fn purge_terminal(&mut self, now: Instant, older_than: Duration) -> usize {
+        let goal_status = &mut self.goal_status;
+        let forgotten = &mut self.forgotten_goals;
+        let before = self.terminal_goals.len();
+        self.terminal_goals.retain(|uuid, since| {
+            let keep = now.saturating_duration_since(*since) < older_than;
+            if !keep {
+                goal_status.remove(uuid);
+                forgotten.insert(*uuid);
+            }
+            keep
+        });
+        before - self.terminal_goals.len()
+    }
+
+    // (name, ages of the entries) of each map.
+    fn bookkeeping_ages(&self, now: Instant) -> Vec<(&'static str, Vec<Duration>)> {
+        let goal_age = |uuid: &uuid::Uuid| {
+            self.first_seen
+                .get(uuid)
+                .map_or(Duration::ZERO, |t| now.saturating_duration_since(*t))
+        };
+        let cancel_age = |seq_no: &i64| {
+            self.cancels_sent
+                .get(seq_no)
+                .map_or(Duration::ZERO, |t| now.saturating_duration_since(*t))
+        };
+        vec![
+            (
+                "goal_response_channels",
+                self.goal_response_channels
+                    .values()
+                    .map(|(uuid, _)| goal_age(uuid))
+                    .collect(),
+            ),
+            (
+                "cancel_response_channels",
+                self.cancel_response_channels
+                    .keys()
+                    .map(cancel_age)
+                    .collect(),
+            ),
+            (
+                "feedback_senders",
+                self.feedback_senders.keys().map(goal_age).collect(),
+            ),
+            (
+                "result_requests",
+                self.result_requests.values().map(goal_age).collect(),
+            ),
+            (
+                "result_senders",
+                self.result_senders.keys().map(goal_age).collect(),
+            ),
+            (
+                "goal_response_deadlines",
+                self.goal_response_deadlines.keys().map(goal_age).collect(),
+            ),
+            (
+                "result_deadlines",
+                self.result_deadlines.keys().map(goal_age).collect(),
+            ),
+            (
+                "cancel_response_deadlines",
+                self.cancel_response_deadlines
+                    .keys()
+                    .map(cancel_age)
+                    .collect(),
+            ),
+            ("watchdogs", self.watchdogs.keys().map(goal_age).collect()),
+            (
+                "goal_status",
+                self.goal_status.keys().map(goal_age).collect(),
+            ),
+            (
+                "terminal_goals",
+                self.terminal_goals.keys().map(goal_age).collect(),
+            ),
+            (
+                "forgotten_goals",
+                self.forgotten_goals.iter().map(goal_age).collect(),
+            ),
+            (
+                "status_senders",
+                self.status_senders
+                    .iter()
+                    .map(|(uuid, _)| goal_age(uuid))
+                    .collect(),
+            ),
+        ]
+    }
+
+    pub fn bookkeeping_report(&self, now: Instant) -> Vec<BookkeepingStats> {
+        self.bookkeeping_ages(now)
+            .into_iter()
+            .map(|(map, ages)| BookkeepingStats {
+                map,
+                entries: ages.len(),
+                oldest: ages.iter().max().copied(),
+                newest: ages.iter().min().copied(),
+            })
+            .collect()
+    }
+
+    // Drops the timestamps of goals and cancel requests that are no
+    // longer in any map, and reports maps that grew too large.
+    fn audit_bookkeeping(&mut self) {
+        let cancels = &self.cancel_response_channels;
+        self.cancels_sent
+            .retain(|seq_no, _| cancels.contains_key(seq_no));
+        let tracked: HashSet<uuid::Uuid> = self
+            .goal_status
+            .keys()
+            .chain(self.feedback_senders.keys())
+            .chain(self.result_senders.keys())
+            .chain(self.result_requests.values())
+            .chain(self.goal_response_channels.values().map(|(uuid, _)| uuid))
+            .chain(self.watchdogs.keys())
+            .chain(self.terminal_goals.keys())
+            .chain(self.forgotten_goals.iter())
+            .chain(self.status_senders.iter().map(|(uuid, _)| uuid))
+            .copied()
+            .collect();
+        self.first_seen.retain(|uuid, _| tracked.contains(uuid));
+
+        let limit = match self.options.bookkeeping_warning_size {
+            Some(limit) => limit,
+            None => return,
+        };
+        let sizes = [
+            ("goal_response_channels", self.goal_response_channels.len()),
+            (
+                "cancel_response_channels",
+                self.cancel_response_channels.len(),
+            ),
+            ("feedback_senders", self.feedback_senders.len()),
+            ("result_requests", self.result_requests.len()),
+            ("result_senders", self.result_senders.len()),
+            ("watchdogs", self.watchdogs.len()),
+            ("goal_status", self.goal_status.len()),
+            ("terminal_goals", self.terminal_goals.len()),
+            ("forgotten_goals", self.forgotten_goals.len()),
+            ("status_senders", self.status_senders.len()),
+        ];
+        for (map, entries) in sizes.iter().copied() {
+            if entries <= limit {
+                self.oversized_maps.remove(map);
+            } else if self.oversized_maps.insert(map) {
+                // once until the map shrinks again.
+                self.errors.report(
+                    SpinOperation::Update,
+                    Error::BookkeepingOversized {
+                        map: map.into(),
+                        entries,
+                        limit,
+                    },
+                );
+            }
+        }
+    }
+
+    // Forgets the feedback and result senders of a goal whose
+    // receivers have been dropped.
+    fn forget_dropped_receivers(&mut self, uuid: &uuid::Uuid) {
+        if self
+            .feedback_senders
+            .get(uuid)
+            .map_or(false, |s| s.is_closed())
+        {
+            self.feedback_senders.remove(uuid);
+        }
+        if self
+            .result_senders
+            .get(uuid)
+            .map_or(false, |s| s.is_canceled())
+        {
+            self.result_senders.remove(uuid);
+        }
+    }
+
+    // Forgets the requests whose futures have been dropped, e.g. by a
+    // timeout around them, so that they do not pile up when a server
+    // never answers. Late answers are reported as unmatched.
+    fn forget_abandoned_requests(&mut self) {
+        // nobody can get hold of the feedback or the result of a goal
+        // whose goal response is not awaited.
+        let mut abandoned = vec![];
+        self.goal_response_channels.retain(|_, (uuid, sender)| {
+            let keep = !sender.is_canceled();
+            if !keep {
+                abandoned.push(*uuid);
+            }
+            keep
+        });
+        for uuid in &abandoned {
+            self.goal_response_deadlines.remove(uuid);
+            self.feedback_senders.remove(uuid);
+            self.result_senders.remove(uuid);
+        }
+
+        let abandoned: Vec<uuid::Uuid> = self
+            .result_senders
+            .iter()
+            .filter(|(_, sender)| sender.is_canceled())
+            .map(|(uuid, _)| *uuid)
+            .chain(abandoned)
+            .collect();
+        for uuid in &abandoned {
+            self.result_senders.remove(uuid);
+            self.result_deadlines.remove(uuid);
+            self.watchdogs.remove(uuid);
+        }
+        // also the results of dropped goal handles.
+        let senders = &self.result_senders;
+        self.result_requests
+            .retain(|_, uuid| senders.contains_key(uuid));
+        let deadlines = &mut self.cancel_response_deadlines;
+        self.cancel_response_channels.retain(|seq_no, sender| {
+            let keep = !sender.is_canceled();
+            if !keep {
+                deadlines.remove(seq_no);
+            }
+            keep
+        });
+    }
+
+    // Completes the futures still waiting on the client with
+    // `ClientDestroyed`, so that they can be told apart from a server
+    // that never answered. Feedback and status streams cannot carry an
+    // error and end instead.
+    fn fail_outstanding_requests(&mut self) {
+        for (_, (_, sender)) in self.goal_response_channels.drain() {
+            let _ = sender.send(Err(Error::ClientDestroyed));
+        }
+        for (_, sender) in self.cancel_response_channels.drain() {
+            let _ = sender.send(Err(Error::ClientDestroyed));
+        }
+        for (_, sender) in self.result_senders.drain() {
+            let _ = sender.send(Err(Error::ClientDestroyed));
+        }
+        for sender in self.poll_available_channels.drain(..) {
+            let _ = sender.send(Err(Error::ClientDestroyed));
+        }
+        self.feedback_senders.clear();
+        self.status_senders.clear();
+        self.result_requests.clear();
+        self.goal_response_deadlines.clear();
+        self.result_deadlines.clear();
+        self.cancel_response_deadlines.clear();
+        self.watchdogs.clear();
+    }
+
+    // Feedback or a status change puts off the watchdog of a goal,
+    // unless it has already canceled the goal.
+    fn goal_activity(&mut self, uuid: &uuid::Uuid) {
+        if let Some(state) = self.watchdogs.get_mut(uuid) {
+            if !state.cancel_sent {
+                state.deadline = Some(Instant::now() + state.watchdog.inactivity);
+            }
+        }
+    }
+
+    fn check_watchdogs(&mut self, now: Instant) {
+        self.watchdog_cancels
+            .retain_mut(|r| matches!(r.try_recv(), Ok(None)));
+
+        let expired: Vec<uuid::Uuid> = self
+            .watchdogs
+            .iter()
+            .filter(|(_, state)| state.deadline.map_or(false, |d| d <= now))
+            .map(|(uuid, _)| *uuid)
+            .collect();
+        for uuid in expired {
+            let state = self.watchdogs.get_mut(&uuid).expect("expired watchdog");
+            if !state.cancel_sent {
+                state.cancel_sent = true;
+                state.deadline = Some(now + state.watchdog.cancel_timeout);
+                let goal_info = action_msgs::msg::GoalInfo {
+                    goal_id: unique_identifier_msgs::msg::UUID {
+                        uuid: uuid.as_bytes().to_vec(),
+                    },
+                    ..action_msgs::msg::GoalInfo::default()
+                };
+                // a cancel that could not be sent times out like one
+                // that was never answered.
+                match self.send_cancel_request_msg(goal_info) {
+                    Ok(receiver) => self.watchdog_cancels.push(receiver),
+                    Err(e) => self.errors.report(SpinOperation::Send, e),
+                }
+                continue;
+            }
+
+            let state = self.watchdogs.remove(&uuid).expect("expired watchdog");
+            self.result_requests.retain(|_, suuid| suuid != &uuid);
+            self.result_deadlines.remove(&uuid);
+            if let Some(sender) = self.result_senders.remove(&uuid) {
+                let _ = sender.send(Err(Error::GoalStuck {
+                    inactivity: state.watchdog.inactivity,
+                }));
+            }
+            self.feedback_senders.remove(&uuid);
+            // dropping the senders ends the status streams.
+            self.status_senders.retain(|(suuid, _)| suuid != &uuid);
+            self.goal_status.remove(&uuid);
+            self.terminal_goals.remove(&uuid);
+        }
+    }
+
+    pub fn send_cancel_request(
+        &mut self,
+        goal: &uuid::Uuid,
+    ) -> Result<impl Future<Output = Result<CancelResult>>>
+    where
+        T: WrappedActionTypeSupport,
+    {
+        let goal_info = action_msgs::msg::GoalInfo {
+            goal_id: unique_identifier_msgs::msg::UUID {
+                uuid: goal.as_bytes().to_vec(),
+            },
+            ..action_msgs::msg::GoalInfo::default()
+        };
+        self.send_cancel(goal_info)
+    }
+
+    /// Cancel all goals of the server.
+    pub fn send_cancel_all_requests(
+        &mut self,
+    ) -> Result<impl Future<Output = Result<CancelResult>>> {
+        self.send_cancel_requests_before(builtin_interfaces::msg::Time::default())
+    }
+
+    /// Cancel all goals accepted at or before `stamp`.
+    pub fn send_cancel_requests_before(
+        &mut self,
+        stamp: builtin_interfaces::msg::Time,
+    ) -> Result<impl Future<Output = Result<CancelResult>>> {
+        // a zero goal id means all goals, a zero stamp ignores the stamp.
+        let goal_info = action_msgs::msg::GoalInfo {
+            goal_id: unique_identifier_msgs::msg::UUID { uuid: vec![0; 16] },
+            stamp,
+        };
+        self.send_cancel(goal_info)
+    }
+
+    fn send_cancel(
+        &mut self,
+        goal_info: action_msgs::msg::GoalInfo,
+    ) -> Result<impl Future<Output = Result<CancelResult>>> {
+        let cancel_req_receiver = self.send_cancel_request_msg(goal_info)?;
+        // instead of "canceled" we return invalid client.
+        let future = cancel_req_receiver.map(|r| {
+            r.unwrap_or(Err(Error::RCL_RET_CLIENT_INVALID))
+                .and_then(|r| CancelResult::from_msg(&r))
+        });
+        Ok(future)
     }
 
-    pub fn send_cancel_request(
+    fn send_cancel_request_msg(
         &mut self,
-        goal: &uuid::Uuid,
-    ) -> Result<impl Future<Output = Result<()>>>
-    where
-        T: WrappedActionTypeSupport,
-    {
-        let msg = action_msgs::srv::CancelGoal::Request {
-            goal_info: action_msgs::msg::GoalInfo {
-                goal_id: unique_identifier_msgs::msg::UUID {
-                    uuid: goal.as_bytes().to_vec(),
-                },
-                ..action_msgs::msg::GoalInfo::default()
-            },
-        };
+        goal_info: action_msgs::msg::GoalInfo,
+    ) -> Result<oneshot::Receiver<Result<action_msgs::srv::CancelGoal::Response>>> {
+        let msg = action_msgs::srv::CancelGoal::Request { goal_info };
         let native_msg = WrappedNativeMsg::<action_msgs::srv::CancelGoal::Request>::from(&msg);
         let mut seq_no = 0i64;
         let result = unsafe {
@@ -244,27 +1408,113 @@ where
 
         if result == RCL_RET_OK as i32 {
             let (cancel_req_sender, cancel_req_receiver) =
-                oneshot::channel::<action_msgs::srv::CancelGoal::Response>();
-
+                oneshot::channel::<Result<action_msgs::srv::CancelGoal::Response>>();
             self.cancel_response_channels
-                .push((seq_no, cancel_req_sender));
-            // instead of "canceled" we return invalid client.
-            let future = cancel_req_receiver
-                .map_err(|_| Error::RCL_RET_CLIENT_INVALID)
-                .map(|r| match r {
-                    Ok(r) => match r.return_code {
-                        0 => Ok(()),
-                        1 => Err(Error::GoalCancelRejected),
-                        2 => Err(Error::GoalCancelUnknownGoalID),
-                        3 => Err(Error::GoalCancelAlreadyTerminated),
-                        x => panic!("unknown error code return from action server: {}", x),
-                    },
-                    Err(e) => Err(e),
-                });
-            Ok(future)
+                .insert(seq_no, cancel_req_sender);
+            self.cancels_sent.insert(seq_no, Instant::now());
+            if let Some(timeout) = self.options.cancel_response_timeout {
+                self.cancel_response_deadlines
+                    .insert(seq_no, Instant::now() + timeout);
+            }
+            Ok(cancel_req_receiver)
         } else {
-            eprintln!("coult not send goal request {}", result);
-            Err(Error::from_rcl_error(result))
+            let err = Error::from_rcl_error(result);
+            self.errors.log(
+                LogSeverity::Warn,
+                &format!("could not send cancel request: {}", err),
+            );
+            Err(err)
+        }
+    }
+
+    fn deliver_feedback(
+        &mut self,
+        uuid: &unique_identifier_msgs::msg::UUID,
+        feedback: T::Feedback,
+    ) {
+        let msg_uuid = match uuid_msg_to_uuid(uuid) {
+            Ok(uuid) => uuid,
+            Err(e) => {
+                self.errors.report(SpinOperation::Convert, e);
+                return;
+            }
+        };
+        if let Some(sink) = self.feedback_senders.get(&msg_uuid) {
+            match sink.try_deliver(feedback) {
+                DeliverResult::Accepted => (),
+                DeliverResult::DroppedFull => {
+                    self.errors.report(SpinOperation::Deliver, sink_full())
+                }
+                DeliverResult::Closed => {
+                    self.feedback_senders.remove(&msg_uuid);
+                }
+            }
+        }
+        self.goal_activity(&msg_uuid);
+    }
+
+    fn update_goal_status(&mut self, arr: &action_msgs::msg::GoalStatusArray) {
+        // the server lists a goal until it expires, only then can it
+        // not come back.
+        let listed: HashSet<Vec<u8>> = arr
+            .status_list
+            .iter()
+            .map(|a| a.goal_info.goal_id.uuid.clone())
+            .collect();
+        self.forgotten_goals
+            .retain(|uuid| listed.contains(&uuid.as_bytes()[..]));
+        for a in &arr.status_list {
+            let uuid = match uuid_msg_to_uuid(&a.goal_info.goal_id) {
+                Ok(uuid) => uuid,
+                Err(e) => {
+                    self.errors.report(SpinOperation::Convert, e);
+                    continue;
+                }
+            };
+            self.status_qos_check.status_received(&uuid);
+            if self.forgotten_goals.contains(&uuid) {
+                continue;
+            }
+            if !self.options.track_all_goals
+                && !self.goal_status.contains_key(&uuid)
+                && !self.result_senders.contains_key(&uuid)
+                && !self.status_senders.iter().any(|(suuid, _)| suuid == &uuid)
+            {
+                continue;
+            }
+            let status = match GoalStatus::try_from(a.status) {
+                Ok(status) => status,
+                Err(e) => {
+                    self.errors.report(SpinOperation::Convert, e);
+                    continue;
+                }
+            };
+            self.set_goal_status(uuid, status);
+        }
+    }
+}
+
+impl<T: 'static> WrappedActionClient<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    // Takes and delivers one feedback message, returns false if there
+    // was none.
+    fn take_feedback(&mut self) -> bool {
+        let mut feedback_msg = WrappedNativeMsg::<T::FeedbackMessage>::new();
+        let ret =
+            unsafe { rcl_action_take_feedback(&self.rcl_handle, feedback_msg.void_ptr_mut()) };
+        if ret == RCL_RET_OK as i32 {
+            let msg = T::FeedbackMessage::from_native(&feedback_msg);
+            let (uuid, feedback) = T::destructure_feedback_msg(msg);
+            self.deliver_feedback(&uuid, feedback);
+            true
+        } else {
+            if ret != RCL_RET_ACTION_CLIENT_TAKE_FAILED as i32 {
+                self.errors
+                    .report(SpinOperation::Take, Error::from_rcl_error(ret));
+            }
+            false
         }
     }
 }
@@ -292,32 +1542,66 @@ where
         };
         if ret == RCL_RET_OK as i32 {
             let request_id = unsafe { request_id.assume_init() };
-            if let Some(idx) = self
+            if self
                 .goal_response_channels
-                .iter()
-                .position(|(id, _)| id == &request_id.sequence_number)
+                .contains_key(&request_id.sequence_number)
             {
-                let (_, sender) = self.goal_response_channels.swap_remove(idx);
+                if !self.goal_response_guard.accept(&request_id, &self.errors) {
+                    return;
+                }
+                let (uuid, sender) = self
+                    .goal_response_channels
+                    .remove(&request_id.sequence_number)
+                    .expect("goal response channel");
+                self.goal_response_deadlines.remove(&uuid);
                 let response = <<T as WrappedActionTypeSupport>::SendGoal as WrappedServiceTypeSupport>::Response::from_native(&response_msg);
                 let (accept, stamp) = T::destructure_goal_response_msg(response);
-                match sender.send((accept, stamp)) {
+                if accept {
+                    self.status_qos_check.goal_accepted(uuid);
+                    // tracked from now on, also without a result request.
+                    self.goal_status.entry(uuid).or_insert(GoalStatus::Accepted);
+                    self.goal_activity(&uuid);
+                    // on goal accept we immediately send the result request
+                    if self.options.auto_request_result {
+                        self.send_result_request(uuid);
+                    }
+                } else {
+                    // no feedback or result will ever arrive for this goal.
+                    self.feedback_senders.remove(&uuid);
+                    self.result_senders.remove(&uuid);
+                    self.watchdogs.remove(&uuid);
+                }
+                match sender.send(Ok((accept, stamp))) {
                     Ok(()) => {}
-                    Err(e) => {
-                        println!("error sending to action client: {:?}", e);
+                    Err(_) => {
+                        self.errors.report(
+                            SpinOperation::Deliver,
+                            Error::DeliveryFailed {
+                                reason: "goal future dropped".into(),
+                            },
+                        );
                     }
                 }
             } else {
+                self.goal_response_guard.reject();
                 let we_have: String = self
                     .goal_response_channels
-                    .iter()
-                    .map(|(id, _)| id.to_string())
+                    .keys()
+                    .map(|id| id.to_string())
                     .collect::<Vec<_>>()
                     .join(",");
-                eprintln!(
-                    "no such req id: {}, we have [{}], ignoring",
-                    request_id.sequence_number, we_have
+                self.errors.report(
+                    SpinOperation::Match,
+                    Error::UnmatchedResponse {
+                        reason: format!(
+                            "no such req id: {}, we have [{}], ignoring",
+                            request_id.sequence_number, we_have
+                        ),
+                    },
                 );
             }
+        } else if ret != RCL_RET_ACTION_CLIENT_TAKE_FAILED as i32 {
+            self.errors.report(SpinOperation::Take, Error::from_rcl_error(ret));
         }
     }
 
@@ -334,51 +1618,54 @@ where
         };
         if ret == RCL_RET_OK as i32 {
             let request_id = unsafe { request_id.assume_init() };
-            if let Some(idx) = self
+            if self
                 .cancel_response_channels
-                .iter()
-                .position(|(id, _)| id == &request_id.sequence_number)
+                .contains_key(&request_id.sequence_number)
             {
-                let (_, sender) = self.cancel_response_channels.swap_remove(idx);
+                if !self.cancel_response_guard.accept(&request_id, &self.errors) {
+                    return;
+                }
+                let sender = self
+                    .cancel_response_channels
+                    .remove(&request_id.sequence_number)
+                    .expect("cancel response channel");
+                self.cancel_response_deadlines
+                    .remove(&request_id.sequence_number);
                 let response = action_msgs::srv::CancelGoal::Response::from_native(&response_msg);
-                match sender.send(response) {
-                    Err(e) => eprintln!("warning: could not send cancel response msg ({:?})", e),
+                match sender.send(Ok(response)) {
+                    Err(_) => self.errors.report(
+                        SpinOperation::Deliver,
+                        Error::DeliveryFailed {
+                            reason: "cancel future dropped".into(),
+                        },
+                    ),
                     _ => (),
                 }
             } else {
+                self.cancel_response_guard.reject();
                 let we_have: String = self
-                    .goal_response_channels
-                    .iter()
-                    .map(|(id, _)| id.to_string())
+                    .cancel_response_channels
+                    .keys()
+                    .map(|id| id.to_string())
                     .collect::<Vec<_>>()
                     .join(",");
-                eprintln!(
-                    "no such req id: {}, we have [{}], ignoring",
-                    request_id.sequence_number, we_have
+                self.errors.report(
+                    SpinOperation::Match,
+                    Error::UnmatchedResponse {
+                        reason: format!(
+                            "no such req id: {}, we have [{}], ignoring",
+                            request_id.sequence_number, we_have
+                        ),
+                    },
                 );
             }
+        } else if ret != RCL_RET_ACTION_CLIENT_TAKE_FAILED as i32 {
+            self.errors.report(SpinOperation::Take, Error::from_rcl_error(ret));
         }
     }
 
     fn handle_feedback_msg(&mut self) -> () {
-        let mut feedback_msg = WrappedNativeMsg::<T::FeedbackMessage>::new();
-        let ret =
-            unsafe { rcl_action_take_feedback(&self.rcl_handle, feedback_msg.void_ptr_mut()) };
-        if ret == RCL_RET_OK as i32 {
-            let msg = T::FeedbackMessage::from_native(&feedback_msg);
-            let (uuid, feedback) = T::destructure_feedback_msg(msg);
-            let msg_uuid = uuid_msg_to_uuid(&uuid);
-            if let Some((_, sender)) = self
-                .feedback_senders
-                .iter_mut()
-                .find(|(uuid, _)| uuid == &msg_uuid)
-            {
-                match sender.try_send(feedback) {
-                    Err(e) => eprintln!("warning: could not send feedback msg ({})", e),
-                    _ => (),
-                }
-            }
-        }
+        self.take_feedback();
     }
 
     fn handle_status_msg(&mut self) -> () {
@@ -386,14 +1673,12 @@ where
         let ret = unsafe { rcl_action_take_status(&self.rcl_handle, status_array.void_ptr_mut()) };
         if ret == RCL_RET_OK as i32 {
             let arr = action_msgs::msg::GoalStatusArray::from_native(&status_array);
-            for a in &arr.status_list {
-                let uuid = uuid_msg_to_uuid(&a.goal_info.goal_id);
-                if !self.result_senders.iter().any(|(suuid, _)| suuid == &uuid) {
-                    continue;
-                }
-                let status = GoalStatus::from_rcl(a.status);
-                *self.goal_status.entry(uuid).or_insert(GoalStatus::Unknown) = status;
-            }
+            // feedback that is waiting is older than the status, and is
+            // delivered before a terminal status ends the stream.
+            while self.take_feedback() {}
+            self.update_goal_status(&arr);
+        } else if ret != RCL_RET_ACTION_CLIENT_TAKE_FAILED as i32 {
+            self.errors.report(SpinOperation::Take, Error::from_rcl_error(ret));
         }
     }
 
@@ -413,40 +1698,64 @@ where
 
         if ret == RCL_RET_OK as i32 {
             let request_id = unsafe { request_id.assume_init() };
-            if let Some(idx) = self
+            if self
                 .result_requests
-                .iter()
-                .position(|(id, _)| id == &request_id.sequence_number)
+                .contains_key(&request_id.sequence_number)
             {
-                let (_, uuid) = self.result_requests.swap_remove(idx);
-                if let Some(idx) = self
-                    .result_senders
-                    .iter()
-                    .position(|(suuid, _)| suuid == &uuid)
-                {
-                    let (_, sender) = self.result_senders.swap_remove(idx);
+                if !self.result_response_guard.accept(&request_id, &self.errors) {
+                    return;
+                }
+                let uuid = self
+                    .result_requests
+                    .remove(&request_id.sequence_number)
+                    .expect("result request");
+                self.result_deadlines.remove(&uuid);
+                if let Some(sender) = self.result_senders.remove(&uuid) {
+                    self.watchdogs.remove(&uuid);
                     let response = <<T as WrappedActionTypeSupport>::GetResult as WrappedServiceTypeSupport>::Response::from_native(&response_msg);
                     let (status, result) = T::destructure_result_response_msg(response);
-                    let status = GoalStatus::from_rcl(status);
-                    match sender.send((status, result)) {
+                    // the result is delivered anyway, with an unknown status.
+                    let status = GoalStatus::try_from(status).unwrap_or_else(|e| {
+                        self.errors.report(SpinOperation::Convert, e);
+                        GoalStatus::Unknown
+                    });
+                    // in case the status message has not arrived yet.
+                    if status.is_terminal() {
+                        while self.take_feedback() {}
+                        self.set_goal_status(uuid, status);
+                    }
+                    match sender.send(Ok((status, result))) {
                         Ok(()) => {}
-                        Err(e) => {
-                            println!("error sending result to action client: {:?}", e);
+                        Err(_) => {
+                            self.errors.report(
+                                SpinOperation::Deliver,
+                                Error::DeliveryFailed {
+                                    reason: "result future dropped".into(),
+                                },
+                            );
                         }
                     }
                 }
             } else {
+                self.result_response_guard.reject();
                 let we_have: String = self
                     .result_requests
-                    .iter()
-                    .map(|(id, _)| id.to_string())
+                    .keys()
+                    .map(|id| id.to_string())
                     .collect::<Vec<_>>()
                     .join(",");
-                eprintln!(
-                    "no such req id: {}, we have [{}], ignoring",
-                    request_id.sequence_number, we_have
+                self.errors.report(
+                    SpinOperation::Match,
+                    Error::UnmatchedResponse {
+                        reason: format!(
+                            "no such req id: {}, we have [{}], ignoring",
+                            request_id.sequence_number, we_have
+                        ),
+                    },
                 );
             }
+        } else if ret != RCL_RET_ACTION_CLIENT_TAKE_FAILED as i32 {
+            self.errors.report(SpinOperation::Take, Error::from_rcl_error(ret));
         }
     }
 
@@ -464,13 +1773,17 @@ where
         };
 
         if result == RCL_RET_OK as i32 {
-            self.result_requests.push((seq_no, uuid));
+            self.result_requests.insert(seq_no, uuid);
+            if let Some(timeout) = self.options.result_timeout {
+                self.result_deadlines.insert(uuid, Instant::now() + timeout);
+            }
         } else {
-            eprintln!("coult not send request {}", result);
+            self.errors
+                .report(SpinOperation::Send, Error::from_rcl_error(result));
         }
     }
 
-    fn register_poll_available(&mut self, s: oneshot::Sender<()>) {
+    fn register_poll_available(&mut self, s: oneshot::Sender<Result<()>>) {
         self.poll_available_channels.push(s);
     }
 
@@ -481,9 +1794,10 @@ where
         let available = action_server_available_helper(node, self.handle());
         match available {
             Ok(true) => {
-                // send ok and close channels
+                self.check_servers(node);
+                // send the outcome and close channels
                 while let Some(sender) = self.poll_available_channels.pop() {
-                    let _res = sender.send(()); // we ignore if receiver dropped.
+                    let _res = sender.send(self.server_check.check()); // we ignore if receiver dropped.
                 }
             }
             Ok(false) => {
@@ -496,24 +1810,285 @@ where
         }
     }
 
+    fn check_servers(&mut self, node: &rcl_node_t) {
+        if !self.server_check.enabled() {
+            return;
+        }
+        match action_service_names(&self.rcl_handle) {
+            Ok(names) => self.server_check.update(node, &names, &self.errors),
+            Err(e) => self.errors.report(SpinOperation::Update, e),
+        }
+    }
+
+    fn check_status_qos(&mut self, node: &rcl_node_t) {
+        self.status_qos_check.update(node, &self.rcl_handle, &self.errors);
+    }
+
+    fn check_timeouts(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<uuid::Uuid> = self
+            .goal_response_deadlines
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(uuid, _)| *uuid)
+            .collect();
+        for uuid in expired {
+            self.goal_response_deadlines.remove(&uuid);
+            let seq_no = self
+                .goal_response_channels
+                .iter()
+                .find(|(_, (suuid, _))| suuid == &uuid)
+                .map(|(seq_no, _)| *seq_no);
+            let channel = seq_no.and_then(|s| self.goal_response_channels.remove(&s));
+            if let Some((_, sender)) = channel {
+                let _ = sender.send(Err(Error::RequestTimedOut {
+                    timeout: self.options.goal_response_timeout.unwrap_or_default(),
+                }));
+            }
+            self.feedback_senders.remove(&uuid);
+            self.result_senders.remove(&uuid);
+            self.watchdogs.remove(&uuid);
+        }
+
+        let expired: Vec<uuid::Uuid> = self
+            .result_deadlines
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(uuid, _)| *uuid)
+            .collect();
+        for uuid in expired {
+            self.result_deadlines.remove(&uuid);
+            self.result_requests.retain(|_, suuid| suuid != &uuid);
+            if let Some(sender) = self.result_senders.remove(&uuid) {
+                let _ = sender.send(Err(Error::RequestTimedOut {
+                    timeout: self.options.result_timeout.unwrap_or_default(),
+                }));
+            }
+            self.feedback_senders.remove(&uuid);
+            self.watchdogs.remove(&uuid);
+        }
+
+        let expired: Vec<i64> = self
+            .cancel_response_deadlines
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(seq_no, _)| *seq_no)
+            .collect();
+        for seq_no in expired {
+            self.cancel_response_deadlines.remove(&seq_no);
+            if let Some(sender) = self.cancel_response_channels.remove(&seq_no) {
+                let _ = sender.send(Err(Error::RequestTimedOut {
+                    timeout: self.options.cancel_response_timeout.unwrap_or_default(),
+                }));
+            }
+        }
+
+        self.check_watchdogs(now);
+        self.forget_abandoned_requests();
+        self.forget_terminal_goals(now);
+        self.audit_bookkeeping();
+    }
+
+    fn cancel_pending_goals(&mut self) {
+        let goals: Vec<uuid::Uuid> = self.result_senders.keys().copied().collect();
+        for uuid in goals {
+            // nobody waits for the answer.
+            if let Err(e) = self.send_cancel_request(&uuid) {
+                self.errors.report(SpinOperation::Send, e);
+            }
+        }
+    }
+
     fn destroy(&mut self, node: &mut rcl_node_t) {
+        self.fail_outstanding_requests();
         unsafe {
             rcl_action_client_fini(&mut self.rcl_handle, node);
         }
     }
 }
 
+pub fn action_client_name(rcl_handle: &rcl_action_client_t) -> Result<String> {
+    let cstr = unsafe { rcl_action_client_get_action_name(rcl_handle) };
+    if cstr == std::ptr::null() {
+        return Err(Error::RCL_RET_ACTION_CLIENT_INVALID);
+    }
+    let s = unsafe { CStr::from_ptr(cstr) };
+    Ok(s.to_str().unwrap_or("").to_owned())
+}
+
+/// The names of the services behind an action.
+pub fn action_service_names(rcl_handle: &rcl_action_client_t) -> Result<Vec<String>> {
+    let action_name = action_client_name(rcl_handle)?;
+    Ok(["send_goal", "cancel_goal", "get_result"]
+        .iter()
+        .map(|s| format!("{}/_action/{}", action_name, s))
+        .collect())
+}
+
+/// Guards for the responses of the goal, cancel and result services
+/// behind an action client.
+pub fn action_response_guards(
+    rcl_handle: &rcl_action_client_t,
+) -> (ResponseGuard, ResponseGuard, ResponseGuard) {
+    // rcl_action has no accessors for its service clients, but its
+    // implementation struct starts with the goal, cancel and result
+    // clients in all distributions.
+    let clients = action_handle_impl(rcl_handle) as *const [rcl_client_t; 3];
+    if clients.is_null() {
+        return Default::default();
+    }
+    let [goal, cancel, result] = unsafe { &*clients };
+    (
+        ResponseGuard::new(goal),
+        ResponseGuard::new(cancel),
+        ResponseGuard::new(result),
+    )
+}
+
+/// Looks for the reason when accepted goals get no status updates.
+///
+/// The usual one is a server whose status publisher uses a QoS profile
+/// our subscription cannot receive from, e.g. a volatile publisher
+/// while we subscribe transient local. Each publisher is only reported
+/// once.
+pub struct StatusQosCheck {
+    window: Option<Duration>,
+    // accepted goals that have not been in a status message yet
+    waiting: HashMap<uuid::Uuid, Instant>,
+    reported: HashSet<(String, String)>,
+}
+
+impl StatusQosCheck {
+    pub fn new(window: Option<Duration>) -> Self {
+        StatusQosCheck {
+            window,
+            waiting: HashMap::new(),
+            reported: HashSet::new(),
+        }
+    }
+
+    pub fn goal_accepted(&mut self, uuid: uuid::Uuid) {
+        if self.window.is_some() {
+            self.waiting.insert(uuid, Instant::now());
+        }
+    }
+
+    pub fn status_received(&mut self, uuid: &uuid::Uuid) {
+        self.waiting.remove(uuid);
+    }
+
+    // Forgets goals that have waited too long, returns true if there
+    // were any.
+    fn take_overdue(&mut self, now: Instant) -> bool {
+        let window = match self.window {
+            Some(window) => window,
+            None => return false,
+        };
+        let before = self.waiting.len();
+        self.waiting
+            .retain(|_, accepted| now.duration_since(*accepted) <= window);
+        self.waiting.len() != before
+    }
+
+    pub fn update(
+        &mut self,
+        node: &rcl_node_t,
+        client: &rcl_action_client_t,
+        errors: &EntityErrors,
+    ) {
+        if !self.take_overdue(Instant::now()) {
+            return;
+        }
+        if let Err(e) = self.check(node, client, errors) {
+            errors.report(SpinOperation::Update, e);
+        }
+    }
+
+    fn check(
+        &mut self,
+        node: &rcl_node_t,
+        client: &rcl_action_client_t,
+        errors: &EntityErrors,
+    ) -> Result<()> {
+        let (action_name, options) = unsafe {
+            let name = rcl_action_client_get_action_name(client);
+            let options = rcl_action_client_get_options(client);
+            if name.is_null() || options.is_null() {
+                return Err(Error::RCL_RET_ACTION_CLIENT_INVALID);
+            }
+            (CStr::from_ptr(name).to_str().unwrap_or(""), &*options)
+        };
+        let topics = [
+            ("status", &options.status_topic_qos),
+            ("feedback", &options.feedback_topic_qos),
+        ];
+        for (topic, qos) in topics.iter() {
+            let subscription_qos = QosProfile::from(*qos);
+            if subscription_qos.has_unknown_policy() {
+                continue;
+            }
+            let topic = resolve_topic_name(node, &format!("{}/_action/{}", action_name, topic))?;
+            for p in publishers_info_by_topic(node, &topic)? {
+                let publisher_qos = match p.qos {
+                    Some(qos) => qos,
+                    None => continue,
+                };
+                let reasons = subscription_qos.incompatibilities_with(&publisher_qos);
+                if reasons.is_empty() {
+                    continue;
+                }
+                let publisher =
+                    format!("{}/{}", p.node_namespace.trim_end_matches('/'), p.node_name);
+                if !self.reported.insert((topic.clone(), publisher.clone())) {
+                    continue;
+                }
+                errors.report(
+                    SpinOperation::Update,
+                    Error::ActionStatusQosMismatch {
+                        topic: topic.clone(),
+                        publisher,
+                        publisher_qos,
+                        subscription_qos: subscription_qos.clone(),
+                        reasons,
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The rcl options for the QoS profiles in `options`, the rcl defaults
+/// for those that are not set.
+pub fn rcl_action_client_options(options: &ActionClientOptions) -> rcl_action_client_options_t {
+    let mut client_options = unsafe { rcl_action_client_get_default_options() };
+    if let Some(qos) = options.service_qos.as_ref().or(options.qos.as_ref()) {
+        let qos = rmw_qos_profile_t::from(qos);
+        client_options.goal_service_qos = qos;
+        client_options.cancel_service_qos = qos;
+        client_options.result_service_qos = qos;
+    }
+    if let Some(qos) = options.feedback_qos.as_ref().or(options.qos.as_ref()) {
+        client_options.feedback_topic_qos = rmw_qos_profile_t::from(qos);
+    }
+    if let Some(qos) = &options.status_qos {
+        client_options.status_topic_qos = rmw_qos_profile_t::from(qos);
+    }
+    client_options
+}
+
 pub fn create_action_client_helper(
     node: &mut rcl_node_t,
     action_name: &str,
     action_ts: *const rosidl_action_type_support_t,
+    options: &ActionClientOptions,
 ) -> Result<rcl_action_client_t> {
     let mut client_handle = unsafe { rcl_action_get_zero_initialized_client() };
     let action_name_c_string =
         CString::new(action_name).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
 
-    let result = unsafe {
-        let client_options = rcl_action_client_get_default_options();
+    rcl_call(|| unsafe {
+        let client_options = rcl_action_client_options(options);
         rcl_action_client_init(
             &mut client_handle,
             node,
@@ -521,12 +2096,8 @@ pub fn create_action_client_helper(
             action_name_c_string.as_ptr(),
             &client_options,
         )
-    };
-    if result == RCL_RET_OK as i32 {
-        Ok(client_handle)
-    } else {
-        Err(Error::from_rcl_error(result))
-    }
+    })?;
+    Ok(client_handle)
 }
 
 pub fn action_client_get_num_waits(
@@ -560,7 +2131,7 @@ impl<T: 'static> IsAvailablePollable for ActionClient<T>
 where
     T: WrappedActionTypeSupport,
 {
-    fn register_poll_available(&self, sender: oneshot::Sender<()>) -> Result<()> {
+    fn register_poll_available(&self, sender: oneshot::Sender<Result<()>>) -> Result<()> {
         let client = self
             .client
             .upgrade()
@@ -571,6 +2142,24 @@ where
     }
 }
 
+impl<T> Entity for ActionClient<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    fn name(&self) -> Result<String> {
+        let client = self
+            .client
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
+        let client = client.lock().unwrap();
+        action_client_name(&client.rcl_handle)
+    }
+
+    fn kind(&self) -> EntityKind {
+        EntityKind::ActionClient
+    }
+}
+
 pub fn action_server_available_helper(
     node: &rcl_node_t,
     client: &rcl_action_client_t,
@@ -581,7 +2170,536 @@ pub fn action_server_available_helper(
     if result == RCL_RET_OK as i32 {
         Ok(avail)
     } else {
-        eprintln!("coult not check if action server is available {}", result);
-        Err(Error::from_rcl_error(result))
+        let err = Error::from_rcl_error(result);
+        log_internal(
+            LogSeverity::Warn,
+            &format!("could not check if action server is available: {}", err),
+        );
+        Err(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(r2r__example_interfaces__action__Fibonacci)]
+    use crate::executor::EntityKind;
+    #[cfg(r2r__example_interfaces__action__Fibonacci)]
+    use crate::msg_types::generated_msgs::example_interfaces::action::Fibonacci;
+    #[cfg(r2r__example_interfaces__action__Fibonacci)]
+    use std::sync::Arc;
+
+    #[cfg(r2r__example_interfaces__action__Fibonacci)]
+    fn test_client(errors: &ErrorSink) -> WrappedActionClient<Fibonacci> {
+        WrappedActionClient {
+            rcl_handle: unsafe { rcl_action_get_zero_initialized_client() },
+            goal_response_channels: HashMap::new(),
+            cancel_response_channels: HashMap::new(),
+            feedback_senders: HashMap::new(),
+            result_requests: HashMap::new(),
+            result_senders: HashMap::new(),
+            goal_response_deadlines: HashMap::new(),
+            result_deadlines: HashMap::new(),
+            cancel_response_deadlines: HashMap::new(),
+            watchdogs: HashMap::new(),
+            watchdog_cancels: Vec::new(),
+            options: ActionClientOptions::default(),
+            goal_status: HashMap::new(),
+            terminal_goals: HashMap::new(),
+            forgotten_goals: HashSet::new(),
+            first_seen: HashMap::new(),
+            cancels_sent: HashMap::new(),
+            oversized_maps: HashSet::new(),
+            status_senders: Vec::new(),
+            goal_response_guard: ResponseGuard::default(),
+            cancel_response_guard: ResponseGuard::default(),
+            result_response_guard: ResponseGuard::default(),
+            goal_metadata_publisher: None,
+            poll_available_channels: Vec::new(),
+            server_check: ServerCheck::new(false),
+            status_qos_check: StatusQosCheck::new(None),
+            errors: errors.entity(EntityKind::ActionClient, "/fibonacci"),
+        }
+    }
+
+    #[cfg(r2r__example_interfaces__action__Fibonacci)]
+    fn malformed_uuids() -> Vec<unique_identifier_msgs::msg::UUID> {
+        [0, 15, 17]
+            .iter()
+            .map(|len| unique_identifier_msgs::msg::UUID {
+                uuid: vec![1; *len],
+            })
+            .collect()
+    }
+
+    #[cfg(r2r__example_interfaces__action__Fibonacci)]
+    #[test]
+    fn test_status_stream() {
+        let mut client = test_client(&ErrorSink::new());
+        let goal = uuid::Uuid::new_v4();
+        let mut stream = client.subscribe_status(&goal.into());
+        let time = builtin_interfaces::msg::Time::default();
+        let array = |statuses: &[GoalStatus]| action_msgs::msg::GoalStatusArray {
+            status_list: statuses
+                .iter()
+                .map(|s| s.to_msg(goal.into(), time.clone()))
+                .collect(),
+        };
+        client.update_goal_status(&array(&[GoalStatus::Accepted]));
+        client.update_goal_status(&array(&[GoalStatus::Accepted]));
+        client.update_goal_status(&array(&[GoalStatus::Executing, GoalStatus::Executing]));
+        client.update_goal_status(&array(&[GoalStatus::Canceling]));
+        let mut late = client.subscribe_status(&goal.into());
+        client.update_goal_status(&array(&[GoalStatus::Canceled]));
+        client.update_goal_status(&array(&[GoalStatus::Canceled]));
+
+        let mut received = Vec::new();
+        while let Ok(Some(status)) = stream.try_next() {
+            received.push(status);
+        }
+        assert_eq!(
+            received,
+            vec![
+                GoalStatus::Accepted,
+                GoalStatus::Executing,
+                GoalStatus::Canceling,
+                GoalStatus::Canceled
+            ]
+        );
+        // ended after the terminal status.
+        assert_eq!(stream.try_next().ok(), Some(None));
+        assert!(client.status_senders.is_empty());
+
+        // the current status comes first.
+        assert_eq!(late.try_next().ok(), Some(Some(GoalStatus::Canceling)));
+        assert_eq!(late.try_next().ok(), Some(Some(GoalStatus::Canceled)));
+        assert_eq!(late.try_next().ok(), Some(None));
+        // the finished goal is forgotten after the horizon, and does
+        // not come back while the server still lists it.
+        assert_eq!(client.get_goal_status(&goal.into()), GoalStatus::Canceled);
+        let horizon = client.options.terminal_status_horizon;
+        client.forget_terminal_goals(Instant::now() + horizon);
+        assert_eq!(client.get_goal_status(&goal.into()), GoalStatus::Unknown);
+        client.update_goal_status(&array(&[GoalStatus::Canceled]));
+        assert!(client.goal_status.is_empty());
+        assert!(client.terminal_goals.is_empty());
+        // until it is not listed anymore.
+        client.update_goal_status(&array(&[]));
+        assert!(client.forgotten_goals.is_empty());
+    }
+
+    #[cfg(r2r__example_interfaces__action__Fibonacci)]
+    #[test]
+    fn test_goal_in_use() {
+        let mut client = test_client(&ErrorSink::new());
+        let (pending, accepted, known) = (
+            uuid::Uuid::new_v4(),
+            uuid::Uuid::new_v4(),
+            uuid::Uuid::new_v4(),
+        );
+        let (sender, _receiver) = oneshot::channel();
+        client.goal_response_channels.insert(1, (pending, sender));
+        let (sender, _receiver) = oneshot::channel();
+        client.result_senders.insert(accepted, sender);
+        client.goal_status.insert(known, GoalStatus::Succeeded);
+        for uuid in &[pending, accepted, known] {
+            assert!(client.goal_in_use(uuid));
+        }
+        assert!(!client.goal_in_use(&uuid::Uuid::new_v4()));
+    }
+
+    #[cfg(r2r__example_interfaces__action__Fibonacci)]
+    #[test]
+    fn test_track_all_goals() {
+        let mut client = test_client(&ErrorSink::new());
+        let (own, other) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        // accepted, but the result has not been requested.
+        client.goal_status.insert(own, GoalStatus::Accepted);
+        let time = builtin_interfaces::msg::Time::default();
+        let array = |statuses: &[(uuid::Uuid, GoalStatus)]| action_msgs::msg::GoalStatusArray {
+            status_list: statuses
+                .iter()
+                .map(|(uuid, s)| s.to_msg((*uuid).into(), time.clone()))
+                .collect(),
+        };
+        client.update_goal_status(&array(&[
+            (own, GoalStatus::Executing),
+            (other, GoalStatus::Executing),
+        ]));
+        assert_eq!(
+            client.known_goals(),
+            vec![(GoalId::from(own), GoalStatus::Executing)]
+        );
+
+        client.options.track_all_goals = true;
+        client.update_goal_status(&array(&[
+            (own, GoalStatus::Succeeded),
+            (other, GoalStatus::Executing),
+        ]));
+        let mut known = client.known_goals();
+        known.sort_by_key(|(uuid, _)| *uuid == other);
+        assert_eq!(
+            known,
+            vec![
+                (GoalId::from(own), GoalStatus::Succeeded),
+                (GoalId::from(other), GoalStatus::Executing)
+            ]
+        );
+
+        // only terminal goals are evicted.
+        let horizon = client.options.terminal_status_horizon;
+        client.forget_terminal_goals(Instant::now() + horizon);
+        assert_eq!(
+            client.known_goals(),
+            vec![(GoalId::from(other), GoalStatus::Executing)]
+        );
+    }
+
+    #[cfg(r2r__example_interfaces__action__Fibonacci)]
+    #[test]
+    fn test_terminal_status_ends_feedback() {
+        let mut client = test_client(&ErrorSink::new());
+        let (goal, other) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        let goal_msg = unique_identifier_msgs::msg::UUID {
+            uuid: goal.as_bytes().to_vec(),
+        };
+        let mut streams = Vec::new();
+        for uuid in &[goal, other] {
+            let (feedback_sender, feedback) = mpsc::channel(10);
+            client
+                .feedback_senders
+                .insert(*uuid, Box::new(ChannelSink::new(feedback_sender)));
+            let (result_sender, result) = oneshot::channel();
+            client.result_senders.insert(*uuid, result_sender);
+            streams.push((feedback, result));
+        }
+        let time = builtin_interfaces::msg::Time::default();
+        client.update_goal_status(&action_msgs::msg::GoalStatusArray {
+            status_list: vec![
+                GoalStatus::Executing.to_msg(goal.into(), time.clone()),
+                GoalStatus::Executing.to_msg(other.into(), time.clone()),
+            ],
+        });
+        client.deliver_feedback(&goal_msg, Fibonacci::Feedback { sequence: vec![1] });
+        client.update_goal_status(&action_msgs::msg::GoalStatusArray {
+            status_list: vec![GoalStatus::Succeeded.to_msg(goal.into(), time)],
+        });
+
+        // feedback delivered before the terminal status is kept.
+        let (feedback, _result) = &mut streams[0];
+        assert_eq!(
+            feedback.try_next().unwrap(),
+            Some(Fibonacci::Feedback { sequence: vec![1] })
+        );
+        assert_eq!(feedback.try_next().ok(), Some(None));
+        assert_eq!(client.feedback_senders.len(), 1);
+        assert!(client.feedback_senders.contains_key(&other));
+        assert_eq!(client.get_goal_status(&goal.into()), GoalStatus::Succeeded);
+        assert_eq!(client.get_goal_status(&other.into()), GoalStatus::Executing);
+    }
+
+    #[cfg(r2r__example_interfaces__action__Fibonacci)]
+    #[test]
+    fn test_bookkeeping() {
+        let errors = ErrorSink::new();
+        errors.set_log(false);
+        let mut reported = errors.subscribe(10);
+        let mut client = test_client(&errors);
+        client.options.bookkeeping_warning_size = Some(1);
+        let (done, running) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        client.set_goal_status(done, GoalStatus::Executing);
+        client.set_goal_status(running, GoalStatus::Executing);
+
+        // reported once while too large.
+        client.audit_bookkeeping();
+        client.audit_bookkeeping();
+        let e = reported.try_next().unwrap().unwrap();
+        assert!(matches!(
+            e.error,
+            Error::BookkeepingOversized { ref map, entries: 2, limit: 1 } if map == "goal_status"
+        ));
+        assert!(reported.try_next().is_err());
+
+        let later = Instant::now() + Duration::from_secs(5);
+        let report = client.bookkeeping_report(later);
+        let goal_status = report.iter().find(|s| s.map == "goal_status").unwrap();
+        assert_eq!(goal_status.entries, 2);
+        assert!(goal_status.newest.unwrap() >= Duration::from_secs(5));
+
+        client.set_goal_status(done, GoalStatus::Succeeded);
+        assert_eq!(client.purge_terminal(later, Duration::from_secs(10)), 0);
+        assert_eq!(client.purge_terminal(later, Duration::from_secs(1)), 1);
+        assert_eq!(
+            client.known_goals(),
+            vec![(GoalId::from(running), GoalStatus::Executing)]
+        );
+        // forgotten once the server stops listing it.
+        client.update_goal_status(&action_msgs::msg::GoalStatusArray::default());
+        client.audit_bookkeeping();
+        assert_eq!(client.first_seen.keys().collect::<Vec<_>>(), vec![&running]);
+        assert!(client.oversized_maps.is_empty());
+    }
+
+    #[cfg(r2r__example_interfaces__action__Fibonacci)]
+    #[test]
+    fn test_malformed_goal_ids_are_skipped() {
+        let errors = ErrorSink::new();
+        errors.set_log(false);
+        let mut reported = errors.subscribe(10);
+        let mut client = test_client(&errors);
+        let goal = uuid::Uuid::new_v4();
+        let goal_msg = unique_identifier_msgs::msg::UUID {
+            uuid: goal.as_bytes().to_vec(),
+        };
+        let (feedback_sender, mut feedback) = mpsc::channel(10);
+        client
+            .feedback_senders
+            .insert(goal, Box::new(ChannelSink::new(feedback_sender)));
+        let (result_sender, _result) = oneshot::channel();
+        client.result_senders.insert(goal, result_sender);
+
+        for uuid in malformed_uuids() {
+            client.deliver_feedback(&uuid, Fibonacci::Feedback { sequence: vec![1] });
+        }
+        client.deliver_feedback(&goal_msg, Fibonacci::Feedback { sequence: vec![2] });
+        let time = builtin_interfaces::msg::Time::default();
+        let mut status_list: Vec<action_msgs::msg::GoalStatus> = malformed_uuids()
+            .into_iter()
+            .map(|uuid| {
+                let mut status = GoalStatus::Aborted.to_msg(GoalId::new_random(), time.clone());
+                status.goal_info.goal_id = uuid;
+                status
+            })
+            .collect();
+        status_list.push(GoalStatus::Executing.to_msg(goal.into(), time));
+        client.update_goal_status(&action_msgs::msg::GoalStatusArray { status_list });
+
+        assert_eq!(
+            feedback.try_next().unwrap(),
+            Some(Fibonacci::Feedback { sequence: vec![2] })
+        );
+        assert!(feedback.try_next().is_err());
+        assert_eq!(client.get_goal_status(&goal.into()), GoalStatus::Executing);
+        for _ in 0..6 {
+            let e = reported.try_next().unwrap().unwrap();
+            assert_eq!(e.operation, SpinOperation::Convert);
+            assert!(matches!(e.error, Error::InvalidGoalId { .. }));
+        }
+        assert!(reported.try_next().is_err());
+    }
+
+    #[cfg(r2r__example_interfaces__action__Fibonacci)]
+    #[test]
+    fn test_watchdog_fails_stuck_goal() {
+        let errors = ErrorSink::new();
+        errors.set_log(false);
+        let mut client = test_client(&errors);
+        let watchdog = GoalWatchdog::new(Duration::from_secs(1), Duration::from_secs(2));
+        let (stuck, done) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        let (feedback_sender, mut feedback) = mpsc::channel(10);
+        client
+            .feedback_senders
+            .insert(stuck, Box::new(ChannelSink::new(feedback_sender)));
+        let (result_sender, mut result) = oneshot::channel();
+        client.result_senders.insert(stuck, result_sender);
+        let mut status = client.subscribe_status(&stuck.into());
+        for uuid in &[stuck, done] {
+            client.watchdogs.insert(
+                *uuid,
+                GoalWatchdogState {
+                    watchdog,
+                    deadline: None,
+                    cancel_sent: false,
+                },
+            );
+            client.goal_activity(uuid);
+        }
+        client.set_goal_status(done, GoalStatus::Succeeded);
+        assert_eq!(client.watchdogs.len(), 1);
+
+        // the cancel cannot be sent from the test client, which only
+        // leaves the goal to time out.
+        let now = Instant::now();
+        client.check_watchdogs(now + Duration::from_millis(500));
+        assert!(!client.watchdogs[&stuck].cancel_sent);
+        client.check_watchdogs(now + Duration::from_millis(1500));
+        assert!(client.watchdogs[&stuck].cancel_sent);
+        // activity after the cancel does not put off the timeout.
+        client.goal_activity(&stuck);
+        client.check_watchdogs(now + Duration::from_millis(3000));
+        assert!(matches!(result.try_recv(), Ok(None)));
+        client.check_watchdogs(now + Duration::from_millis(4000));
+
+        assert!(matches!(
+            result.try_recv(),
+            Ok(Some(Err(Error::GoalStuck { .. })))
+        ));
+        assert_eq!(feedback.try_next().ok(), Some(None));
+        assert_eq!(status.try_next().ok(), Some(None));
+        assert_eq!(client.get_goal_status(&stuck.into()), GoalStatus::Unknown);
+        assert!(client.watchdogs.is_empty());
+    }
+
+    #[cfg(r2r__example_interfaces__action__Fibonacci)]
+    #[test]
+    fn test_goal_handle_drop_forgets_goal() {
+        let client = Arc::new(Mutex::new(test_client(&ErrorSink::new())));
+        let handle = |uuid: uuid::Uuid| {
+            let (feedback_sender, feedback) = mpsc::channel::<Fibonacci::Feedback>(10);
+            let (result_sender, result) = oneshot::channel();
+            let mut c = client.lock().unwrap();
+            c.feedback_senders
+                .insert(uuid, Box::new(ChannelSink::new(feedback_sender)));
+            c.result_senders.insert(uuid, result_sender);
+            ClientGoalHandle {
+                goal: ActionClientGoal {
+                    client: Arc::downgrade(&client),
+                    uuid: uuid.into(),
+                    accepted_at: Default::default(),
+                },
+                feedback: Some(feedback.boxed()),
+                result: Some(
+                    result
+                        .map(|r| r.unwrap_or(Err(Error::RCL_RET_ACTION_CLIENT_INVALID)))
+                        .boxed(),
+                ),
+            }
+        };
+        let (dropped, taken) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        drop(handle(dropped));
+        let mut goal = handle(taken);
+        let result = goal.result().unwrap();
+        assert!(goal.result().is_none());
+        drop(goal);
+
+        let c = client.lock().unwrap();
+        assert!(c.feedback_senders.is_empty());
+        assert_eq!(c.result_senders.len(), 1);
+        assert!(c.result_senders.contains_key(&taken));
+        drop(result);
+    }
+
+    #[cfg(r2r__example_interfaces__action__Fibonacci)]
+    #[test]
+    fn test_abandoned_requests_are_forgotten() {
+        let mut client = test_client(&ErrorSink::new());
+        client.options.cancel_response_timeout = Some(Duration::from_secs(1));
+        let (unanswered, dropped, kept) = (
+            uuid::Uuid::new_v4(),
+            uuid::Uuid::new_v4(),
+            uuid::Uuid::new_v4(),
+        );
+        // the goal response of `unanswered` is not awaited anymore.
+        let (goal_sender, goal_receiver) = oneshot::channel();
+        client
+            .goal_response_channels
+            .insert(1, (unanswered, goal_sender));
+        let later = Instant::now() + Duration::from_secs(10);
+        client.goal_response_deadlines.insert(unanswered, later);
+        let mut receivers = Vec::new();
+        for (seq_no, uuid) in &[(2, unanswered), (3, dropped), (4, kept)] {
+            let (feedback_sender, feedback) = mpsc::channel::<Fibonacci::Feedback>(10);
+            client
+                .feedback_senders
+                .insert(*uuid, Box::new(ChannelSink::new(feedback_sender)));
+            let (result_sender, result) = oneshot::channel();
+            client.result_senders.insert(*uuid, result_sender);
+            client.result_requests.insert(*seq_no, *uuid);
+            receivers.push((feedback, result));
+        }
+        drop(goal_receiver);
+        // the result future of `dropped` is dropped, its feedback kept.
+        let (_, result) = receivers.remove(1);
+        drop(result);
+        let (cancel_sender, cancel_receiver) = oneshot::channel();
+        client.cancel_response_channels.insert(5, cancel_sender);
+        client.cancel_response_deadlines.insert(5, later);
+        drop(cancel_receiver);
+        let (cancel_sender, mut timed_out) = oneshot::channel();
+        client.cancel_response_channels.insert(6, cancel_sender);
+        client
+            .cancel_response_deadlines
+            .insert(6, Instant::now() - Duration::from_millis(1));
+
+        client.check_timeouts();
+
+        assert!(client.goal_response_channels.is_empty());
+        assert!(client.goal_response_deadlines.is_empty());
+        assert_eq!(client.feedback_senders.len(), 2);
+        assert!(!client.feedback_senders.contains_key(&unanswered));
+        assert_eq!(client.result_senders.len(), 1);
+        assert_eq!(
+            client.result_requests.iter().collect::<Vec<_>>(),
+            vec![(&4, &kept)]
+        );
+        assert!(client.cancel_response_channels.is_empty());
+        assert!(client.cancel_response_deadlines.is_empty());
+        assert!(matches!(
+            timed_out.try_recv(),
+            Ok(Some(Err(Error::RequestTimedOut { .. })))
+        ));
+    }
+
+    #[test]
+    fn test_status_qos_check_overdue() {
+        let mut check = StatusQosCheck::new(Some(Duration::from_millis(100)));
+        let (a, b) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        check.goal_accepted(a);
+        check.goal_accepted(b);
+        check.status_received(&a);
+        let now = Instant::now();
+        assert!(!check.take_overdue(now));
+        assert!(check.take_overdue(now + Duration::from_millis(200)));
+        // b is only reported once.
+        assert!(!check.take_overdue(now + Duration::from_millis(300)));
+
+        let mut disabled = StatusQosCheck::new(None);
+        disabled.goal_accepted(a);
+        assert!(!disabled.take_overdue(now + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_rcl_action_client_options() {
+        let defaults = rcl_action_client_options(&ActionClientOptions::default());
+        let rcl_defaults = unsafe { rcl_action_client_get_default_options() };
+        let profiles = |o: &rcl_action_client_options_t| {
+            [
+                &o.goal_service_qos,
+                &o.cancel_service_qos,
+                &o.result_service_qos,
+                &o.feedback_topic_qos,
+                &o.status_topic_qos,
+            ]
+            .iter()
+            .map(|qos| QosProfile::from(*qos))
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(profiles(&defaults), profiles(&rcl_defaults));
+
+        let reliable = QosProfile::default().keep_last(20);
+        let feedback = QosProfile::default().best_effort().keep_last(1);
+        let options = ActionClientOptions {
+            qos: Some(reliable.clone()),
+            feedback_qos: Some(feedback.clone()),
+            ..Default::default()
+        };
+        let mapped = profiles(&rcl_action_client_options(&options));
+        assert_eq!(mapped[0..3], vec![Some(reliable.clone()); 3][..]);
+        assert_eq!(mapped[3], Some(feedback));
+        assert_eq!(mapped[4], profiles(&rcl_defaults)[4]);
+
+        let status = QosProfile::default().keep_last(5).transient_local();
+        let options = ActionClientOptions {
+            service_qos: Some(QosProfile::default()),
+            status_qos: Some(status.clone()),
+            ..options
+        };
+        let mapped = profiles(&rcl_action_client_options(&options));
+        assert_eq!(mapped[0], Some(QosProfile::default()));
+        assert_eq!(
+            mapped[3],
+            Some(QosProfile::default().best_effort().keep_last(1))
+        );
+        assert_eq!(mapped[4], Some(status));
     }
 }
@@ -1,15 +1,23 @@
 use futures::channel::{mpsc, oneshot};
 use futures::future::{FutureExt, TryFutureExt};
 use futures::stream::Stream;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::future::Future;
 use std::sync::{Mutex, Weak};
 use std::mem::MaybeUninit;
+use std::time::Instant;
 
 use crate::error::*;
 use crate::action_common::*;
+use crate::clients::{ResponseGuard, ServerCheck};
+use crate::error_events::*;
+use crate::utils::LogSeverity;
+use crate::executor::EntityKind;
 use crate::msg_types::*;
 use crate::action_clients::*;
+use crate::nodes::Node;
+use crate::traits::Entity;
 use crate::msg_types::generated_msgs::{
     unique_identifier_msgs,
     action_msgs,
@@ -40,11 +48,18 @@ unsafe impl Send for ActionClientGoalUntyped {}
 #[derive(Clone)]
 pub struct ActionClientGoalUntyped {
     client: Weak<Mutex<WrappedActionClientUntyped>>,
-    pub uuid: uuid::Uuid,
+    pub uuid: GoalId,
+    /// When the server accepted the goal, from its goal response.
+    pub accepted_at: builtin_interfaces::msg::Time,
 }
 
 impl ActionClientGoalUntyped {
     /// Get the current status of this goal.
+    ///
+    /// The status is `Accepted` until the server reports another one.
+    /// The client forgets a goal once it has been in a terminal status
+    /// for `ActionClientOptions::terminal_status_horizon`, after which
+    /// the status is `Unknown`.
     pub fn get_status(&self) -> Result<GoalStatus> {
         let client = self
             .client
@@ -55,14 +70,29 @@ impl ActionClientGoalUntyped {
         Ok(client.get_goal_status(&self.uuid))
     }
 
+    /// Get a stream of the status changes of this goal.
+    ///
+    /// The stream starts with the current status if one has been
+    /// received, yields each status only once and ends after a
+    /// terminal status (succeeded, canceled or aborted).
+    pub fn subscribe_status(&self) -> Result<impl Stream<Item = GoalStatus> + Unpin> {
+        let client = self
+            .client
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
+        let mut client = client.lock().unwrap();
+
+        Ok(client.subscribe_status(&self.uuid))
+    }
+
     /// Send a cancel request for this goal to the server.
     ///
-    /// If the server accepts and completes the request, the future completes without error.
-    /// Otherwise, one of these errors can be returned:
+    /// If the server accepts the request, the future resolves to its
+    /// answer. Otherwise, one of these errors can be returned:
     /// - `GoalCancelRejected`
     /// - `GoalCancelUnknownGoalID`
     /// - `GoalCancelAlreadyTerminated`
-    pub fn cancel(&self) -> Result<impl Future<Output = Result<()>>> {
+    pub fn cancel(&self) -> Result<impl Future<Output = Result<CancelResult>>> {
         // upgrade to actual ref. if still alive
         let client = self
             .client
@@ -75,12 +105,80 @@ impl ActionClientGoalUntyped {
 }
 
 impl ActionClientUntyped {
+    /// Whether the action server is available right now. Use
+    /// `Node::is_available` to wait until it is.
+    pub fn is_available(&self, node: &Node) -> Result<bool> {
+        let client = self
+            .client
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
+        let client = client.lock().unwrap();
+        node.action_server_available(client.handle())
+    }
+
+    /// Cancel all goals of the action server, e.g. for an emergency
+    /// stop. The future resolves to the answer of the server, with the
+    /// goals that are being canceled.
+    pub fn cancel_all_goals(&self) -> Result<impl Future<Output = Result<CancelResult>>> {
+        let client = self
+            .client
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
+        let mut client = client.lock().unwrap();
+        client.send_cancel_all_requests()
+    }
+
+    /// Cancel all goals the action server accepted at or before
+    /// `stamp`. The future resolves to the answer of the server, with
+    /// the goals that are being canceled.
+    pub fn cancel_goals_before(
+        &self,
+        stamp: builtin_interfaces::msg::Time,
+    ) -> Result<impl Future<Output = Result<CancelResult>>> {
+        let client = self
+            .client
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
+        let mut client = client.lock().unwrap();
+        client.send_cancel_requests_before(stamp)
+    }
+
+    /// The goals this client knows the status of, see
+    /// `ActionClient::known_goals`.
+    pub fn known_goals(&self) -> Result<Vec<(GoalId, GoalStatus)>> {
+        let client = self
+            .client
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
+        let client = client.lock().unwrap();
+        Ok(client.known_goals())
+    }
+
+    /// Number of responses on the goal, cancel and result services
+    /// that were dropped because they did not belong to a request
+    /// made by this client.
+    ///
+    /// See `ClientUntyped::stale_responses`.
+    pub fn stale_responses(&self) -> Result<usize> {
+        let client = self
+            .client
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
+        let client = client.lock().unwrap();
+        Ok(client.goal_response_guard.stale_responses()
+            + client.cancel_response_guard.stale_responses()
+            + client.result_response_guard.stale_responses())
+    }
+
     /// Make a new goal request.
     ///
     /// If the server accepts the new goal, the future resolves to a triple of:
-    /// - A goal handle.
+    /// - A goal handle, with the time the goal was accepted.
     /// - A new future for the eventual result. (as `serde_json::Value`)
     /// - A stream of feedback messages. (as `serde_json::Value`)
+    ///
+    /// If the server rejects the goal, the future fails with
+    /// `Error::GoalRejected`.
     pub fn send_goal_request(
         &self,
         goal: serde_json::Value, // T::Goal
@@ -99,67 +197,80 @@ impl ActionClientUntyped {
             .upgrade()
             .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
         let mut client = client.lock().unwrap();
+        client.server_check.check()?;
 
         let uuid = uuid::Uuid::new_v4();
         let uuid_msg = unique_identifier_msgs::msg::UUID {
             uuid: uuid.as_bytes().to_vec(),
         };
 
-        let native_msg = (client.action_type_support.make_goal_request_msg)(uuid_msg, goal);
+        let native_msg = (client.action_type_support.make_goal_request_msg)(uuid_msg, goal)?;
 
         let mut seq_no = 0i64;
+        // The client lock is held from the send until the pending entries
+        // below are registered. The spin thread needs the same lock to take
+        // the goal response, so it can never see a response for a sequence
+        // number that we have not registered yet.
         let result = unsafe {
             rcl_action_send_goal_request(&client.rcl_handle, native_msg.void_ptr(), &mut seq_no)
         };
 
+        if result != RCL_RET_OK as i32 {
+            let err = Error::from_rcl_error(result);
+            client.errors.log(
+                LogSeverity::Warn,
+                &format!("could not send goal request: {}", err),
+            );
+            return Err(err);
+        }
+
         // set up channels
         let (goal_req_sender, goal_req_receiver) =
-            oneshot::channel::<(bool, builtin_interfaces::msg::Time)>();
-        let (feedback_sender, feedback_receiver) = mpsc::channel::<Result<serde_json::Value>>(10);
-        client.feedback_senders.push((uuid, feedback_sender));
+            oneshot::channel::<Result<(bool, builtin_interfaces::msg::Time)>>();
+        let (feedback_sender, feedback_receiver) =
+            mpsc::channel::<Result<serde_json::Value>>(client.options.feedback_capacity);
+        client.feedback_senders.insert(uuid, feedback_sender);
         let (result_sender, result_receiver) =
-            oneshot::channel::<(GoalStatus, Result<serde_json::Value>)>();
-        client.result_senders.push((uuid, result_sender));
-
-        if result == RCL_RET_OK as i32 {
+            oneshot::channel::<Result<(GoalStatus, Result<serde_json::Value>)>>();
+        client.result_senders.insert(uuid, result_sender);
+        client
+            .goal_response_channels
+            .insert(seq_no, (uuid, goal_req_sender));
+        if let Some(timeout) = client.options.goal_response_timeout {
             client
-                .goal_response_channels
-                .push((seq_no, goal_req_sender));
-            // instead of "canceled" we return invalid client.
-            let fut_client = Weak::clone(&self.client);
-            let future = goal_req_receiver
-                .map_err(|_| Error::RCL_RET_ACTION_CLIENT_INVALID)
-                .map(move |r| match r {
-                    Ok((accepted, _stamp)) => {
-                        if accepted {
-                            // on goal accept we immediately send the result request
-                            {
-                                let c = fut_client
-                                    .upgrade()
-                                    .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
-                                let mut c = c.lock().unwrap();
-                                c.send_result_request(uuid.clone());
-                            }
-
-                            Ok((
-                                ActionClientGoalUntyped {
-                                    client: fut_client,
-                                    uuid,
-                                },
-                                result_receiver.map_err(|_| Error::RCL_RET_ACTION_CLIENT_INVALID),
-                                feedback_receiver,
-                            ))
-                        } else {
-                            Err(Error::RCL_RET_ACTION_GOAL_REJECTED)
-                        }
-                    }
-                    Err(e) => Err(e),
-                });
-            Ok(future)
-        } else {
-            eprintln!("coult not send goal request {}", result);
-            Err(Error::from_rcl_error(result))
+                .goal_response_deadlines
+                .insert(uuid, Instant::now() + timeout);
         }
+
+        // instead of "canceled" we return invalid client.
+        let fut_client = Weak::clone(&self.client);
+        let future = goal_req_receiver
+            .map_err(|_| Error::RCL_RET_ACTION_CLIENT_INVALID)
+            .map(move |r| match r.and_then(|r| r) {
+                Ok((accepted, stamp)) => {
+                    if accepted {
+                        // the result request has already been sent from
+                        // the spin thread when the goal was accepted, so
+                        // we never need to lock the client here.
+                        Ok((
+                            ActionClientGoalUntyped {
+                                client: fut_client,
+                                uuid: uuid.into(),
+                                accepted_at: stamp,
+                            },
+                            result_receiver.map(|r| match r {
+                                Ok(r) => r,
+                                Err(_) => Err(Error::RCL_RET_ACTION_CLIENT_INVALID),
+                            }),
+                            feedback_receiver,
+                        ))
+                    } else {
+                        Err(Error::GoalRejected)
+                    }
+                }
+                Err(e) => Err(e),
+            });
+        Ok(future)
     }
 }
 
@@ -169,21 +280,44 @@ pub fn make_action_client_untyped(
     ActionClientUntyped { client }
 }
 
+unsafe impl Send for WrappedActionClientUntyped {}
+
 pub struct WrappedActionClientUntyped {
     pub action_type_support: UntypedActionSupport,
     pub rcl_handle: rcl_action_client_t,
-    pub goal_response_channels: Vec<(i64, oneshot::Sender<(bool, builtin_interfaces::msg::Time)>)>,
+    // requests by sequence number and goals by id, as in
+    // `WrappedActionClient`.
+    pub goal_response_channels: HashMap<
+        i64,
+        (
+            uuid::Uuid,
+            oneshot::Sender<Result<(bool, builtin_interfaces::msg::Time)>>,
+        ),
+    >,
     pub cancel_response_channels:
-        Vec<(i64, oneshot::Sender<action_msgs::srv::CancelGoal::Response>)>,
-    pub feedback_senders: Vec<(uuid::Uuid, mpsc::Sender<Result<serde_json::Value>>)>,
-    pub result_requests: Vec<(i64, uuid::Uuid)>,
-    pub result_senders: Vec<(
-        uuid::Uuid,
-        oneshot::Sender<(GoalStatus, Result<serde_json::Value>)>,
-    )>,
+        HashMap<i64, oneshot::Sender<Result<action_msgs::srv::CancelGoal::Response>>>,
+    pub feedback_senders: HashMap<uuid::Uuid, mpsc::Sender<Result<serde_json::Value>>>,
+    pub result_requests: HashMap<i64, uuid::Uuid>,
+    pub result_senders:
+        HashMap<uuid::Uuid, oneshot::Sender<Result<(GoalStatus, Result<serde_json::Value>)>>>,
+    pub goal_response_deadlines: HashMap<uuid::Uuid, Instant>,
+    pub result_deadlines: HashMap<uuid::Uuid, Instant>,
+    pub cancel_response_deadlines: HashMap<i64, Instant>,
+    pub options: ActionClientOptions,
     pub goal_status: HashMap<uuid::Uuid, GoalStatus>,
-
-    pub poll_available_channels: Vec<oneshot::Sender<()>>,
+    // when goals reached a terminal status, to forget them later.
+    pub terminal_goals: HashMap<uuid::Uuid, Instant>,
+    // forgotten goals still on the status topic, not to be tracked again.
+    pub forgotten_goals: HashSet<uuid::Uuid>,
+    pub status_senders: Vec<(uuid::Uuid, mpsc::Sender<GoalStatus>)>,
+    pub goal_response_guard: ResponseGuard,
+    pub cancel_response_guard: ResponseGuard,
+    pub result_response_guard: ResponseGuard,
+
+    pub poll_available_channels: Vec<oneshot::Sender<Result<()>>>,
+    pub server_check: ServerCheck,
+    pub status_qos_check: StatusQosCheck,
+    pub errors: EntityErrors,
 }
 
 impl WrappedActionClientUntyped {
@@ -191,18 +325,195 @@ impl WrappedActionClientUntyped {
         *self.goal_status.get(uuid).unwrap_or(&GoalStatus::Unknown)
     }
 
+    pub fn known_goals(&self) -> Vec<(GoalId, GoalStatus)> {
+        self.goal_status
+            .iter()
+            .map(|(uuid, status)| ((*uuid).into(), *status))
+            .collect()
+    }
+
+    pub fn subscribe_status(&mut self, uuid: &uuid::Uuid) -> mpsc::Receiver<GoalStatus> {
+        let (mut sender, receiver) = mpsc::channel::<GoalStatus>(10);
+        let current = self.get_goal_status(uuid);
+        if current != GoalStatus::Unknown {
+            // cannot fail, the channel is empty.
+            let _ = sender.try_send(current);
+        }
+        if !current.is_terminal() {
+            self.status_senders.push((*uuid, sender));
+        }
+        receiver
+    }
+
+    // Stores the status of a goal and passes it on to its status
+    // streams if it changed.
+    fn set_goal_status(&mut self, uuid: uuid::Uuid, status: GoalStatus) {
+        if self.goal_status.insert(uuid, status) == Some(status) {
+            return;
+        }
+        for (_, sender) in self.status_senders.iter_mut().filter(|(s, _)| s == &uuid) {
+            match sender.try_send(status) {
+                Err(e) if e.is_full() => self.errors.report(
+                    SpinOperation::Deliver,
+                    Error::DeliveryFailed {
+                        reason: e.to_string(),
+                    },
+                ),
+                _ => (),
+            }
+        }
+        // dropping the senders ends the streams.
+        self.status_senders
+            .retain(|(s, sender)| !sender.is_closed() && (s != &uuid || !status.is_terminal()));
+        // the status of a finished goal is kept for a while, see
+        // forget_terminal_goals.
+        if status.is_terminal() {
+            self.feedback_senders.remove(&uuid);
+            self.terminal_goals.insert(uuid, Instant::now());
+        }
+    }
+
+    // Forgets the goals that reached a terminal status more than the
+    // horizon ago, so that clients seeing many goals do not grow.
+    fn forget_terminal_goals(&mut self, now: Instant) {
+        let horizon = self.options.terminal_status_horizon;
+        let goal_status = &mut self.goal_status;
+        let forgotten = &mut self.forgotten_goals;
+        self.terminal_goals.retain(|uuid, since| {
+            let keep = now.saturating_duration_since(*since) < horizon;
+            if !keep {
+                goal_status.remove(uuid);
+                forgotten.insert(*uuid);
+            }
+            keep
+        });
+    }
+
+    // Completes the futures and feedback streams still waiting on the
+    // client with `ClientDestroyed`, as in `WrappedActionClient`. Status
+    // streams cannot carry an error and end instead.
+    fn fail_outstanding_requests(&mut self) {
+        for (_, (_, sender)) in self.goal_response_channels.drain() {
+            let _ = sender.send(Err(Error::ClientDestroyed));
+        }
+        for (_, sender) in self.cancel_response_channels.drain() {
+            let _ = sender.send(Err(Error::ClientDestroyed));
+        }
+        for (_, sender) in self.result_senders.drain() {
+            let _ = sender.send(Err(Error::ClientDestroyed));
+        }
+        for (_, mut sender) in self.feedback_senders.drain() {
+            let _ = sender.try_send(Err(Error::ClientDestroyed));
+        }
+        for sender in self.poll_available_channels.drain(..) {
+            let _ = sender.send(Err(Error::ClientDestroyed));
+        }
+        self.result_requests.clear();
+        self.goal_response_deadlines.clear();
+        self.result_deadlines.clear();
+        self.cancel_response_deadlines.clear();
+        self.status_senders.clear();
+    }
+
+    // Forgets the requests whose futures have been dropped, e.g. by a
+    // timeout around them, so that they do not pile up when a server
+    // never answers. Late answers are reported as unmatched.
+    fn forget_abandoned_requests(&mut self) {
+        // nobody can get hold of the feedback or the result of a goal
+        // whose goal response is not awaited.
+        let mut abandoned = vec![];
+        self.goal_response_channels.retain(|_, (uuid, sender)| {
+            let keep = !sender.is_canceled();
+            if !keep {
+                abandoned.push(*uuid);
+            }
+            keep
+        });
+        for uuid in &abandoned {
+            self.goal_response_deadlines.remove(uuid);
+            self.feedback_senders.remove(uuid);
+            self.result_senders.remove(uuid);
+        }
+        let deadlines = &mut self.result_deadlines;
+        self.result_senders.retain(|uuid, sender| {
+            let keep = !sender.is_canceled();
+            if !keep {
+                deadlines.remove(uuid);
+            }
+            keep
+        });
+        // also the results of dropped goal handles.
+        let senders = &self.result_senders;
+        self.result_requests
+            .retain(|_, uuid| senders.contains_key(uuid));
+        let deadlines = &mut self.cancel_response_deadlines;
+        self.cancel_response_channels.retain(|seq_no, sender| {
+            let keep = !sender.is_canceled();
+            if !keep {
+                deadlines.remove(seq_no);
+            }
+            keep
+        });
+    }
+
+    // Takes and delivers one feedback message, returns false if there
+    // was none.
+    fn take_feedback(&mut self) -> bool {
+        let mut feedback_msg = (self.action_type_support.make_feedback_msg)();
+        let ret =
+            unsafe { rcl_action_take_feedback(&self.rcl_handle, feedback_msg.void_ptr_mut()) };
+        if ret == RCL_RET_OK as i32 {
+            let (uuid, feedback) =
+                (self.action_type_support.destructure_feedback_msg)(feedback_msg);
+            self.deliver_feedback(&uuid, feedback);
+            true
+        } else {
+            if ret != RCL_RET_ACTION_CLIENT_TAKE_FAILED as i32 {
+                self.errors
+                    .report(SpinOperation::Take, Error::from_rcl_error(ret));
+            }
+            false
+        }
+    }
+
     pub fn send_cancel_request(
         &mut self,
         goal: &uuid::Uuid,
-    ) -> Result<impl Future<Output = Result<()>>> {
-        let msg = action_msgs::srv::CancelGoal::Request {
-            goal_info: action_msgs::msg::GoalInfo {
-                goal_id: unique_identifier_msgs::msg::UUID {
-                    uuid: goal.as_bytes().to_vec(),
-                },
-                ..action_msgs::msg::GoalInfo::default()
+    ) -> Result<impl Future<Output = Result<CancelResult>>> {
+        let goal_info = action_msgs::msg::GoalInfo {
+            goal_id: unique_identifier_msgs::msg::UUID {
+                uuid: goal.as_bytes().to_vec(),
             },
+            ..action_msgs::msg::GoalInfo::default()
         };
+        self.send_cancel(goal_info)
+    }
+
+    /// Cancel all goals of the server.
+    pub fn send_cancel_all_requests(
+        &mut self,
+    ) -> Result<impl Future<Output = Result<CancelResult>>> {
+        self.send_cancel_requests_before(builtin_interfaces::msg::Time::default())
+    }
+
+    /// Cancel all goals accepted at or before `stamp`.
+    pub fn send_cancel_requests_before(
+        &mut self,
+        stamp: builtin_interfaces::msg::Time,
+    ) -> Result<impl Future<Output = Result<CancelResult>>> {
+        // a zero goal id means all goals, a zero stamp ignores the stamp.
+        let goal_info = action_msgs::msg::GoalInfo {
+            goal_id: unique_identifier_msgs::msg::UUID { uuid: vec![0; 16] },
+            stamp,
+        };
+        self.send_cancel(goal_info)
+    }
+
+    fn send_cancel(
+        &mut self,
+        goal_info: action_msgs::msg::GoalInfo,
+    ) -> Result<impl Future<Output = Result<CancelResult>>> {
+        let msg = action_msgs::srv::CancelGoal::Request { goal_info };
         let native_msg = WrappedNativeMsg::<action_msgs::srv::CancelGoal::Request>::from(&msg);
         let mut seq_no = 0i64;
         let result = unsafe {
@@ -211,27 +522,92 @@ impl WrappedActionClientUntyped {
 
         if result == RCL_RET_OK as i32 {
             let (cancel_req_sender, cancel_req_receiver) =
-                oneshot::channel::<action_msgs::srv::CancelGoal::Response>();
+                oneshot::channel::<Result<action_msgs::srv::CancelGoal::Response>>();
 
             self.cancel_response_channels
-                .push((seq_no, cancel_req_sender));
+                .insert(seq_no, cancel_req_sender);
+            if let Some(timeout) = self.options.cancel_response_timeout {
+                self.cancel_response_deadlines
+                    .insert(seq_no, Instant::now() + timeout);
+            }
             // instead of "canceled" we return invalid client.
-            let future = cancel_req_receiver
-                .map_err(|_| Error::RCL_RET_CLIENT_INVALID)
-                .map(|r| match r {
-                    Ok(r) => match r.return_code {
-                        0 => Ok(()),
-                        1 => Err(Error::GoalCancelRejected),
-                        2 => Err(Error::GoalCancelUnknownGoalID),
-                        3 => Err(Error::GoalCancelAlreadyTerminated),
-                        x => panic!("unknown error code return from action server: {}", x),
-                    },
-                    Err(e) => Err(e),
-                });
+            let future = cancel_req_receiver.map(|r| {
+                r.unwrap_or(Err(Error::RCL_RET_CLIENT_INVALID))
+                    .and_then(|r| CancelResult::from_msg(&r))
+            });
             Ok(future)
         } else {
-            eprintln!("coult not send goal request {}", result);
-            Err(Error::from_rcl_error(result))
+            let err = Error::from_rcl_error(result);
+            self.errors.log(
+                LogSeverity::Warn,
+                &format!("could not send cancel request: {}", err),
+            );
+            Err(err)
+        }
+    }
+
+    fn deliver_feedback(
+        &mut self,
+        uuid: &unique_identifier_msgs::msg::UUID,
+        feedback: Result<serde_json::Value>,
+    ) {
+        let msg_uuid = match uuid_msg_to_uuid(uuid) {
+            Ok(uuid) => uuid,
+            Err(e) => {
+                self.errors.report(SpinOperation::Convert, e);
+                return;
+            }
+        };
+        if let Some(sender) = self.feedback_senders.get_mut(&msg_uuid) {
+            match sender.try_send(feedback) {
+                Err(e) => self.errors.report(
+                    SpinOperation::Deliver,
+                    Error::DeliveryFailed {
+                        reason: e.to_string(),
+                    },
+                ),
+                _ => (),
+            }
+        }
+    }
+
+    fn update_goal_status(&mut self, arr: &action_msgs::msg::GoalStatusArray) {
+        // the server lists a goal until it expires, only then can it
+        // not come back.
+        let listed: HashSet<Vec<u8>> = arr
+            .status_list
+            .iter()
+            .map(|a| a.goal_info.goal_id.uuid.clone())
+            .collect();
+        self.forgotten_goals
+            .retain(|uuid| listed.contains(&uuid.as_bytes()[..]));
+        for a in &arr.status_list {
+            let uuid = match uuid_msg_to_uuid(&a.goal_info.goal_id) {
+                Ok(uuid) => uuid,
+                Err(e) => {
+                    self.errors.report(SpinOperation::Convert, e);
+                    continue;
+                }
+            };
+            self.status_qos_check.status_received(&uuid);
+            if self.forgotten_goals.contains(&uuid) {
+                continue;
+            }
+            if !self.options.track_all_goals
+                && !self.goal_status.contains_key(&uuid)
+                && !self.result_senders.contains_key(&uuid)
+                && !self.status_senders.iter().any(|(suuid, _)| suuid == &uuid)
+            {
+                continue;
+            }
+            let status = match GoalStatus::try_from(a.status) {
+                Ok(status) => status,
+                Err(e) => {
+                    self.errors.report(SpinOperation::Convert, e);
+                    continue;
+                }
+            };
+            self.set_goal_status(uuid, status);
         }
     }
 }
@@ -254,32 +630,62 @@ impl ActionClient_ for WrappedActionClientUntyped {
         };
         if ret == RCL_RET_OK as i32 {
             let request_id = unsafe { request_id.assume_init() };
-            if let Some(idx) = self
+            if self
                 .goal_response_channels
-                .iter()
-                .position(|(id, _)| id == &request_id.sequence_number)
+                .contains_key(&request_id.sequence_number)
             {
-                let (_, sender) = self.goal_response_channels.swap_remove(idx);
+                if !self.goal_response_guard.accept(&request_id, &self.errors) {
+                    return;
+                }
+                let (uuid, sender) = self
+                    .goal_response_channels
+                    .remove(&request_id.sequence_number)
+                    .expect("goal response channel");
+                self.goal_response_deadlines.remove(&uuid);
                 let (accept, stamp) =
                     (self.action_type_support.destructure_goal_response_msg)(response_msg);
-                match sender.send((accept, stamp)) {
+                if accept {
+                    self.status_qos_check.goal_accepted(uuid);
+                    // tracked from now on, also without a result request.
+                    self.goal_status.entry(uuid).or_insert(GoalStatus::Accepted);
+                    // on goal accept we immediately send the result request
+                    self.send_result_request(uuid);
+                } else {
+                    // no feedback or result will ever arrive for this goal.
+                    self.feedback_senders.remove(&uuid);
+                    self.result_senders.remove(&uuid);
+                }
+                match sender.send(Ok((accept, stamp))) {
                     Ok(()) => {}
-                    Err(e) => {
-                        println!("error sending to action client: {:?}", e);
+                    Err(_) => {
+                        self.errors.report(
+                            SpinOperation::Deliver,
+                            Error::DeliveryFailed {
+                                reason: "goal future dropped".into(),
+                            },
+                        );
                     }
                 }
             } else {
+                self.goal_response_guard.reject();
                 let we_have: String = self
                     .goal_response_channels
-                    .iter()
-                    .map(|(id, _)| id.to_string())
+                    .keys()
+                    .map(|id| id.to_string())
                     .collect::<Vec<_>>()
                     .join(",");
-                eprintln!(
-                    "no such req id: {}, we have [{}], ignoring",
-                    request_id.sequence_number, we_have
+                self.errors.report(
+                    SpinOperation::Match,
+                    Error::UnmatchedResponse {
+                        reason: format!(
+                            "no such req id: {}, we have [{}], ignoring",
+                            request_id.sequence_number, we_have
+                        ),
+                    },
                 );
             }
+        } else if ret != RCL_RET_ACTION_CLIENT_TAKE_FAILED as i32 {
+            self.errors.report(SpinOperation::Take, Error::from_rcl_error(ret));
         }
     }
 
@@ -296,51 +702,54 @@ impl ActionClient_ for WrappedActionClientUntyped {
         };
         if ret == RCL_RET_OK as i32 {
             let request_id = unsafe { request_id.assume_init() };
-            if let Some(idx) = self
+            if self
                 .cancel_response_channels
-                .iter()
-                .position(|(id, _)| id == &request_id.sequence_number)
+                .contains_key(&request_id.sequence_number)
             {
-                let (_, sender) = self.cancel_response_channels.swap_remove(idx);
+                if !self.cancel_response_guard.accept(&request_id, &self.errors) {
+                    return;
+                }
+                let sender = self
+                    .cancel_response_channels
+                    .remove(&request_id.sequence_number)
+                    .expect("cancel response channel");
+                self.cancel_response_deadlines
+                    .remove(&request_id.sequence_number);
                 let response = action_msgs::srv::CancelGoal::Response::from_native(&response_msg);
-                match sender.send(response) {
-                    Err(e) => eprintln!("warning: could not send cancel response msg ({:?})", e),
+                match sender.send(Ok(response)) {
+                    Err(_) => self.errors.report(
+                        SpinOperation::Deliver,
+                        Error::DeliveryFailed {
+                            reason: "cancel future dropped".into(),
+                        },
+                    ),
                     _ => (),
                 }
             } else {
+                self.cancel_response_guard.reject();
                 let we_have: String = self
-                    .goal_response_channels
-                    .iter()
-                    .map(|(id, _)| id.to_string())
+                    .cancel_response_channels
+                    .keys()
+                    .map(|id| id.to_string())
                     .collect::<Vec<_>>()
                     .join(",");
-                eprintln!(
-                    "no such req id: {}, we have [{}], ignoring",
-                    request_id.sequence_number, we_have
+                self.errors.report(
+                    SpinOperation::Match,
+                    Error::UnmatchedResponse {
+                        reason: format!(
+                            "no such req id: {}, we have [{}], ignoring",
+                            request_id.sequence_number, we_have
+                        ),
+                    },
                 );
             }
+        } else if ret != RCL_RET_ACTION_CLIENT_TAKE_FAILED as i32 {
+            self.errors.report(SpinOperation::Take, Error::from_rcl_error(ret));
         }
     }
 
     fn handle_feedback_msg(&mut self) -> () {
-        let mut feedback_msg = (self.action_type_support.make_feedback_msg)();
-        let ret =
-            unsafe { rcl_action_take_feedback(&self.rcl_handle, feedback_msg.void_ptr_mut()) };
-        if ret == RCL_RET_OK as i32 {
-            let (uuid, feedback) =
-                (self.action_type_support.destructure_feedback_msg)(feedback_msg);
-            let msg_uuid = uuid_msg_to_uuid(&uuid);
-            if let Some((_, sender)) = self
-                .feedback_senders
-                .iter_mut()
-                .find(|(uuid, _)| uuid == &msg_uuid)
-            {
-                match sender.try_send(feedback) {
-                    Err(e) => eprintln!("warning: could not send feedback msg ({})", e),
-                    _ => (),
-                }
-            }
-        }
+        self.take_feedback();
     }
 
     fn handle_status_msg(&mut self) -> () {
@@ -348,14 +757,12 @@ impl ActionClient_ for WrappedActionClientUntyped {
         let ret = unsafe { rcl_action_take_status(&self.rcl_handle, status_array.void_ptr_mut()) };
         if ret == RCL_RET_OK as i32 {
             let arr = action_msgs::msg::GoalStatusArray::from_native(&status_array);
-            for a in &arr.status_list {
-                let uuid = uuid_msg_to_uuid(&a.goal_info.goal_id);
-                if !self.result_senders.iter().any(|(suuid, _)| suuid == &uuid) {
-                    continue;
-                }
-                let status = GoalStatus::from_rcl(a.status);
-                *self.goal_status.entry(uuid).or_insert(GoalStatus::Unknown) = status;
-            }
+            // feedback that is waiting is older than the status, and is
+            // delivered before a terminal status ends the stream.
+            while self.take_feedback() {}
+            self.update_goal_status(&arr);
+        } else if ret != RCL_RET_ACTION_CLIENT_TAKE_FAILED as i32 {
+            self.errors.report(SpinOperation::Take, Error::from_rcl_error(ret));
         }
     }
 
@@ -372,40 +779,63 @@ impl ActionClient_ for WrappedActionClientUntyped {
 
         if ret == RCL_RET_OK as i32 {
             let request_id = unsafe { request_id.assume_init() };
-            if let Some(idx) = self
+            if self
                 .result_requests
-                .iter()
-                .position(|(id, _)| id == &request_id.sequence_number)
+                .contains_key(&request_id.sequence_number)
             {
-                let (_, uuid) = self.result_requests.swap_remove(idx);
-                if let Some(idx) = self
-                    .result_senders
-                    .iter()
-                    .position(|(suuid, _)| suuid == &uuid)
-                {
-                    let (_, sender) = self.result_senders.swap_remove(idx);
+                if !self.result_response_guard.accept(&request_id, &self.errors) {
+                    return;
+                }
+                let uuid = self
+                    .result_requests
+                    .remove(&request_id.sequence_number)
+                    .expect("result request");
+                self.result_deadlines.remove(&uuid);
+                if let Some(sender) = self.result_senders.remove(&uuid) {
                     let (status, result) =
                         (self.action_type_support.destructure_result_response_msg)(response_msg);
-                    let status = GoalStatus::from_rcl(status);
-                    match sender.send((status, result)) {
+                    // the result is delivered anyway, with an unknown status.
+                    let status = GoalStatus::try_from(status).unwrap_or_else(|e| {
+                        self.errors.report(SpinOperation::Convert, e);
+                        GoalStatus::Unknown
+                    });
+                    // in case the status message has not arrived yet.
+                    if status.is_terminal() {
+                        while self.take_feedback() {}
+                        self.set_goal_status(uuid, status);
+                    }
+                    match sender.send(Ok((status, result))) {
                         Ok(()) => {}
-                        Err(e) => {
-                            println!("error sending result to action client: {:?}", e);
+                        Err(_) => {
+                            self.errors.report(
+                                SpinOperation::Deliver,
+                                Error::DeliveryFailed {
+                                    reason: "result future dropped".into(),
+                                },
+                            );
                         }
                     }
                 }
             } else {
+                self.result_response_guard.reject();
                 let we_have: String = self
                     .result_requests
-                    .iter()
-                    .map(|(id, _)| id.to_string())
+                    .keys()
+                    .map(|id| id.to_string())
                     .collect::<Vec<_>>()
                     .join(",");
-                eprintln!(
-                    "no such req id: {}, we have [{}], ignoring",
-                    request_id.sequence_number, we_have
+                self.errors.report(
+                    SpinOperation::Match,
+                    Error::UnmatchedResponse {
+                        reason: format!(
+                            "no such req id: {}, we have [{}], ignoring",
+                            request_id.sequence_number, we_have
+                        ),
+                    },
                 );
             }
+        } else if ret != RCL_RET_ACTION_CLIENT_TAKE_FAILED as i32 {
+            self.errors.report(SpinOperation::Take, Error::from_rcl_error(ret));
         }
     }
 
@@ -420,13 +850,17 @@ impl ActionClient_ for WrappedActionClientUntyped {
         };
 
         if result == RCL_RET_OK as i32 {
-            self.result_requests.push((seq_no, uuid));
+            self.result_requests.insert(seq_no, uuid);
+            if let Some(timeout) = self.options.result_timeout {
+                self.result_deadlines.insert(uuid, Instant::now() + timeout);
+            }
         } else {
-            eprintln!("coult not send request {}", result);
+            self.errors
+                .report(SpinOperation::Send, Error::from_rcl_error(result));
         }
     }
 
-    fn register_poll_available(&mut self, s: oneshot::Sender<()>) {
+    fn register_poll_available(&mut self, s: oneshot::Sender<Result<()>>) {
         self.poll_available_channels.push(s);
     }
 
@@ -437,9 +871,10 @@ impl ActionClient_ for WrappedActionClientUntyped {
         let available = action_server_available_helper(node, self.handle());
         match available {
             Ok(true) => {
-                // send ok and close channels
+                self.check_servers(node);
+                // send the outcome and close channels
                 while let Some(sender) = self.poll_available_channels.pop() {
-                    let _res = sender.send(()); // we ignore if receiver dropped.
+                    let _res = sender.send(self.server_check.check()); // we ignore if receiver dropped.
                 }
             }
             Ok(false) => {
@@ -452,7 +887,93 @@ impl ActionClient_ for WrappedActionClientUntyped {
         }
     }
 
+    fn check_servers(&mut self, node: &rcl_node_t) {
+        if !self.server_check.enabled() {
+            return;
+        }
+        match action_service_names(&self.rcl_handle) {
+            Ok(names) => self.server_check.update(node, &names, &self.errors),
+            Err(e) => self.errors.report(SpinOperation::Update, e),
+        }
+    }
+
+    fn check_status_qos(&mut self, node: &rcl_node_t) {
+        self.status_qos_check.update(node, &self.rcl_handle, &self.errors);
+    }
+
+    fn check_timeouts(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<uuid::Uuid> = self
+            .goal_response_deadlines
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(uuid, _)| *uuid)
+            .collect();
+        for uuid in expired {
+            self.goal_response_deadlines.remove(&uuid);
+            let seq_no = self
+                .goal_response_channels
+                .iter()
+                .find(|(_, (suuid, _))| suuid == &uuid)
+                .map(|(seq_no, _)| *seq_no);
+            let channel = seq_no.and_then(|s| self.goal_response_channels.remove(&s));
+            if let Some((_, sender)) = channel {
+                let _ = sender.send(Err(Error::RequestTimedOut {
+                    timeout: self.options.goal_response_timeout.unwrap_or_default(),
+                }));
+            }
+            self.feedback_senders.remove(&uuid);
+            self.result_senders.remove(&uuid);
+        }
+
+        let expired: Vec<uuid::Uuid> = self
+            .result_deadlines
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(uuid, _)| *uuid)
+            .collect();
+        for uuid in expired {
+            self.result_deadlines.remove(&uuid);
+            self.result_requests.retain(|_, suuid| suuid != &uuid);
+            if let Some(sender) = self.result_senders.remove(&uuid) {
+                let _ = sender.send(Err(Error::RequestTimedOut {
+                    timeout: self.options.result_timeout.unwrap_or_default(),
+                }));
+            }
+            self.feedback_senders.remove(&uuid);
+        }
+
+        let expired: Vec<i64> = self
+            .cancel_response_deadlines
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(seq_no, _)| *seq_no)
+            .collect();
+        for seq_no in expired {
+            self.cancel_response_deadlines.remove(&seq_no);
+            if let Some(sender) = self.cancel_response_channels.remove(&seq_no) {
+                let _ = sender.send(Err(Error::RequestTimedOut {
+                    timeout: self.options.cancel_response_timeout.unwrap_or_default(),
+                }));
+            }
+        }
+
+        self.forget_abandoned_requests();
+        self.forget_terminal_goals(now);
+    }
+
+    fn cancel_pending_goals(&mut self) {
+        let goals: Vec<uuid::Uuid> = self.result_senders.keys().copied().collect();
+        for uuid in goals {
+            // nobody waits for the answer.
+            if let Err(e) = self.send_cancel_request(&uuid) {
+                self.errors.report(SpinOperation::Send, e);
+            }
+        }
+    }
+
     fn destroy(&mut self, node: &mut rcl_node_t) {
+        self.fail_outstanding_requests();
         unsafe {
             rcl_action_client_fini(&mut self.rcl_handle, node);
         }
@@ -462,7 +983,7 @@ impl ActionClient_ for WrappedActionClientUntyped {
 use crate::nodes::IsAvailablePollable;
 
 impl IsAvailablePollable for ActionClientUntyped {
-    fn register_poll_available(&self, sender: oneshot::Sender<()>) -> Result<()> {
+    fn register_poll_available(&self, sender: oneshot::Sender<Result<()>>) -> Result<()> {
         let client = self
             .client
             .upgrade()
@@ -472,3 +993,18 @@ impl IsAvailablePollable for ActionClientUntyped {
         Ok(())
     }
 }
+
+impl Entity for ActionClientUntyped {
+    fn name(&self) -> Result<String> {
+        let client = self
+            .client
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
+        let client = client.lock().unwrap();
+        action_client_name(&client.rcl_handle)
+    }
+
+    fn kind(&self) -> EntityKind {
+        EntityKind::ActionClient
+    }
+}
@@ -1,3 +1,104 @@
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+
+use crate::error::*;
+use crate::msg_types::uuid_msg_to_uuid;
+use crate::msg_types::generated_msgs::{action_msgs, builtin_interfaces, unique_identifier_msgs};
+
+/// The id of an action goal.
+///
+/// Prints and parses as a hyphenated UUID, e.g.
+/// "67e55044-10b1-426f-9247-bb680e5fe0c8", which is the form used by
+/// rclpy, rclcpp and the ros2 command line tools.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct GoalId(uuid::Uuid);
+
+impl GoalId {
+    /// A new random (v4) goal id.
+    pub fn new_random() -> Self {
+        GoalId(uuid::Uuid::new_v4())
+    }
+
+    pub fn as_uuid(&self) -> &uuid::Uuid {
+        &self.0
+    }
+
+    pub fn to_msg(&self) -> unique_identifier_msgs::msg::UUID {
+        unique_identifier_msgs::msg::UUID {
+            uuid: self.0.as_bytes().to_vec(),
+        }
+    }
+
+    /// Fails unless the message contains exactly 16 bytes.
+    pub fn from_msg(msg: &unique_identifier_msgs::msg::UUID) -> Result<Self> {
+        uuid_msg_to_uuid(msg).map(GoalId)
+    }
+}
+
+impl fmt::Display for GoalId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.to_hyphenated_ref())
+    }
+}
+
+impl FromStr for GoalId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        uuid::Uuid::parse_str(s)
+            .map(GoalId)
+            .map_err(|e| Error::InvalidGoalId {
+                reason: e.to_string(),
+            })
+    }
+}
+
+impl From<uuid::Uuid> for GoalId {
+    fn from(uuid: uuid::Uuid) -> Self {
+        GoalId(uuid)
+    }
+}
+
+impl From<GoalId> for uuid::Uuid {
+    fn from(goal_id: GoalId) -> Self {
+        goal_id.0
+    }
+}
+
+impl From<GoalId> for unique_identifier_msgs::msg::UUID {
+    fn from(goal_id: GoalId) -> Self {
+        goal_id.to_msg()
+    }
+}
+
+impl std::convert::TryFrom<&unique_identifier_msgs::msg::UUID> for GoalId {
+    type Error = Error;
+
+    fn try_from(msg: &unique_identifier_msgs::msg::UUID) -> Result<Self> {
+        GoalId::from_msg(msg)
+    }
+}
+
+// Goal ids used to be plain `uuid::Uuid`s, these keep most code
+// written against that working.
+impl Deref for GoalId {
+    type Target = uuid::Uuid;
+
+    fn deref(&self) -> &uuid::Uuid {
+        &self.0
+    }
+}
+
+impl PartialEq<uuid::Uuid> for GoalId {
+    fn eq(&self, other: &uuid::Uuid) -> bool {
+        &self.0 == other
+    }
+}
+
 /// The status of a goal.
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum GoalStatus {
@@ -24,20 +125,92 @@ impl GoalStatus {
         }
     }
 
+    /// Status codes unknown to r2r are returned as `Unknown`, use
+    /// `GoalStatus::try_from` to tell them apart.
     pub fn from_rcl(s: i8) -> Self {
-        match s {
-            0 => GoalStatus::Unknown,
-            1 => GoalStatus::Accepted,
-            2 => GoalStatus::Executing,
-            3 => GoalStatus::Canceling,
-            4 => GoalStatus::Succeeded,
-            5 => GoalStatus::Canceled,
-            6 => GoalStatus::Aborted,
-            _ => panic!("unknown action status: {}", s),
+        GoalStatus::try_from(s).unwrap_or(GoalStatus::Unknown)
+    }
+
+    /// Succeeded, canceled or aborted. The goal will not change state
+    /// again.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            GoalStatus::Succeeded | GoalStatus::Canceled | GoalStatus::Aborted
+        )
+    }
+
+    /// Accepted, executing or canceling.
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self,
+            GoalStatus::Accepted | GoalStatus::Executing | GoalStatus::Canceling
+        )
+    }
+
+    /// The status message of the goal `goal_id`, accepted at `stamp`.
+    pub fn to_msg(
+        &self,
+        goal_id: GoalId,
+        stamp: builtin_interfaces::msg::Time,
+    ) -> action_msgs::msg::GoalStatus {
+        action_msgs::msg::GoalStatus {
+            goal_info: action_msgs::msg::GoalInfo {
+                goal_id: goal_id.to_msg(),
+                stamp,
+            },
+            status: self.to_rcl(),
+        }
+    }
+}
+
+impl From<GoalStatus> for i8 {
+    fn from(status: GoalStatus) -> Self {
+        status.to_rcl()
+    }
+}
+
+impl TryFrom<i8> for GoalStatus {
+    type Error = Error;
+
+    fn try_from(code: i8) -> Result<Self> {
+        match code {
+            0 => Ok(GoalStatus::Unknown),
+            1 => Ok(GoalStatus::Accepted),
+            2 => Ok(GoalStatus::Executing),
+            3 => Ok(GoalStatus::Canceling),
+            4 => Ok(GoalStatus::Succeeded),
+            5 => Ok(GoalStatus::Canceled),
+            6 => Ok(GoalStatus::Aborted),
+            _ => Err(Error::InvalidGoalStatus { code }),
         }
     }
 }
 
+impl TryFrom<&action_msgs::msg::GoalStatus> for GoalStatus {
+    type Error = Error;
+
+    fn try_from(msg: &action_msgs::msg::GoalStatus) -> Result<Self> {
+        GoalStatus::try_from(msg.status)
+    }
+}
+
+/// The goals in a status message, e.g. from the hidden
+/// `<action>/_action/status` topic.
+///
+/// Goals with a malformed id are left out. Status codes unknown to r2r
+/// are returned as `GoalStatus::Unknown`.
+pub fn parse_status_array(msg: &action_msgs::msg::GoalStatusArray) -> Vec<(GoalId, GoalStatus)> {
+    msg.status_list
+        .iter()
+        .filter_map(|s| {
+            let goal_id = GoalId::from_msg(&s.goal_info.goal_id).ok()?;
+            let status = GoalStatus::try_from(s).unwrap_or(GoalStatus::Unknown);
+            Some((goal_id, status))
+        })
+        .collect()
+}
+
 impl std::fmt::Display for GoalStatus {
     fn fmt(&self, fmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
@@ -53,3 +226,269 @@ impl std::fmt::Display for GoalStatus {
         write!(fmtr, "{}", s)
     }
 }
+
+/// The return code of a cancel request.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CancelReturnCode {
+    /// The request was accepted, which is also the case when no goal
+    /// was canceled by a request for all goals.
+    None,
+    Rejected,
+    UnknownGoalId,
+    GoalTerminated,
+}
+
+impl CancelReturnCode {
+    pub fn to_rcl(&self) -> i8 {
+        match self {
+            CancelReturnCode::None => 0,
+            CancelReturnCode::Rejected => 1,
+            CancelReturnCode::UnknownGoalId => 2,
+            CancelReturnCode::GoalTerminated => 3,
+        }
+    }
+}
+
+impl From<CancelReturnCode> for i8 {
+    fn from(code: CancelReturnCode) -> Self {
+        code.to_rcl()
+    }
+}
+
+impl TryFrom<i8> for CancelReturnCode {
+    type Error = Error;
+
+    fn try_from(code: i8) -> Result<Self> {
+        match code {
+            0 => Ok(CancelReturnCode::None),
+            1 => Ok(CancelReturnCode::Rejected),
+            2 => Ok(CancelReturnCode::UnknownGoalId),
+            3 => Ok(CancelReturnCode::GoalTerminated),
+            _ => Err(Error::InvalidCancelReturnCode { code }),
+        }
+    }
+}
+
+/// The answer of an action server to a cancel request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CancelResult {
+    pub return_code: CancelReturnCode,
+    /// The goals that are being canceled, with the time they were
+    /// accepted.
+    pub goals_canceling: Vec<(GoalId, builtin_interfaces::msg::Time)>,
+}
+
+impl CancelResult {
+    /// Converts a cancel response, returning the rejections as
+    /// `GoalCancelRejected`, `GoalCancelUnknownGoalID` and
+    /// `GoalCancelAlreadyTerminated` errors. Goals with a malformed id
+    /// are left out.
+    pub fn from_msg(msg: &action_msgs::srv::CancelGoal::Response) -> Result<Self> {
+        let return_code = match CancelReturnCode::try_from(msg.return_code)? {
+            CancelReturnCode::None => CancelReturnCode::None,
+            CancelReturnCode::Rejected => return Err(Error::GoalCancelRejected),
+            CancelReturnCode::UnknownGoalId => return Err(Error::GoalCancelUnknownGoalID),
+            CancelReturnCode::GoalTerminated => return Err(Error::GoalCancelAlreadyTerminated),
+        };
+        let goals_canceling = msg
+            .goals_canceling
+            .iter()
+            .filter_map(|g| Some((GoalId::from_msg(&g.goal_id).ok()?, g.stamp.clone())))
+            .collect();
+        Ok(CancelResult {
+            return_code,
+            goals_canceling,
+        })
+    }
+
+    /// The ids of the goals that are being canceled.
+    pub fn goal_ids(&self) -> Vec<GoalId> {
+        self.goals_canceling.iter().map(|(id, _)| *id).collect()
+    }
+}
+
+/// Extra information about a goal.
+///
+/// r2r action clients created with `goal_metadata` enabled publish
+/// this on the `<action>/_goal_metadata` topic when sending a goal,
+/// and r2r action servers with `goal_metadata` enabled make it
+/// available on the goal handle. Other ROS clients do not send it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GoalMetadata {
+    pub goal_id: GoalId,
+    /// The time by which the goal should be finished.
+    pub deadline: Option<builtin_interfaces::msg::Time>,
+    pub priority: i32,
+}
+
+// The metadata is sent as json in a string message, so that no
+// special message type is needed.
+pub(crate) const GOAL_METADATA_MSG_TYPE: &str = "std_msgs/msg/String";
+
+pub(crate) fn goal_metadata_topic(action_name: &str) -> String {
+    format!("{}/_goal_metadata", action_name)
+}
+
+impl GoalMetadata {
+    pub(crate) fn to_msg_json(&self) -> Result<serde_json::Value> {
+        let data = serde_json::to_string(self).map_err(|e| Error::SerdeError {
+            err: e.to_string(),
+        })?;
+        Ok(serde_json::json!({ "data": data }))
+    }
+
+    pub(crate) fn from_msg_json(json: &serde_json::Value) -> Result<Self> {
+        let data = json
+            .get("data")
+            .and_then(|d| d.as_str())
+            .ok_or(Error::SerdeError {
+                err: "goal metadata message has no data".into(),
+            })?;
+        serde_json::from_str(data).map_err(|e| Error::SerdeError {
+            err: e.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_goal_metadata_json() -> () {
+        let metadata = GoalMetadata {
+            goal_id: GoalId::new_random(),
+            deadline: Some(builtin_interfaces::msg::Time {
+                sec: 10,
+                nanosec: 500,
+            }),
+            priority: -3,
+        };
+        let json = metadata.to_msg_json().unwrap();
+        assert_eq!(GoalMetadata::from_msg_json(&json).unwrap(), metadata);
+        assert!(GoalMetadata::from_msg_json(&serde_json::json!({ "data": "nope" })).is_err());
+    }
+
+    #[test]
+    fn test_goal_status_conversions() {
+        let all = [
+            (GoalStatus::Unknown, false, false),
+            (GoalStatus::Accepted, false, true),
+            (GoalStatus::Executing, false, true),
+            (GoalStatus::Canceling, false, true),
+            (GoalStatus::Succeeded, true, false),
+            (GoalStatus::Canceled, true, false),
+            (GoalStatus::Aborted, true, false),
+        ];
+        for (status, terminal, active) in all.iter() {
+            let code: i8 = (*status).into();
+            assert_eq!(GoalStatus::try_from(code).unwrap(), *status);
+            assert_eq!(GoalStatus::from_rcl(status.to_rcl()), *status);
+            assert_eq!(status.is_terminal(), *terminal);
+            assert_eq!(status.is_active(), *active);
+            let msg = status.to_msg(
+                GoalId::new_random(),
+                builtin_interfaces::msg::Time::default(),
+            );
+            assert_eq!(GoalStatus::try_from(&msg).unwrap(), *status);
+        }
+        assert_eq!(GoalStatus::Canceling.to_string(), "canceling");
+        assert!(matches!(
+            GoalStatus::try_from(7),
+            Err(Error::InvalidGoalStatus { code: 7 })
+        ));
+        for code in [-128, -1, 7, 42, 127].iter() {
+            assert!(GoalStatus::try_from(*code).is_err());
+            assert_eq!(GoalStatus::from_rcl(*code), GoalStatus::Unknown);
+        }
+    }
+
+    #[test]
+    fn test_cancel_result_from_msg() {
+        let goal_id = GoalId::new_random();
+        let stamp = builtin_interfaces::msg::Time { sec: 3, nanosec: 0 };
+        let mut msg = action_msgs::srv::CancelGoal::Response {
+            return_code: CancelReturnCode::None.into(),
+            goals_canceling: vec![
+                action_msgs::msg::GoalInfo {
+                    goal_id: goal_id.to_msg(),
+                    stamp: stamp.clone(),
+                },
+                action_msgs::msg::GoalInfo {
+                    goal_id: unique_identifier_msgs::msg::UUID { uuid: vec![1; 3] },
+                    stamp: stamp.clone(),
+                },
+            ],
+        };
+        let result = CancelResult::from_msg(&msg).unwrap();
+        assert_eq!(result.return_code, CancelReturnCode::None);
+        assert_eq!(result.goals_canceling, vec![(goal_id, stamp)]);
+        assert_eq!(result.goal_ids(), vec![goal_id]);
+
+        msg.return_code = CancelReturnCode::Rejected.into();
+        assert!(matches!(
+            CancelResult::from_msg(&msg),
+            Err(Error::GoalCancelRejected)
+        ));
+        msg.return_code = CancelReturnCode::UnknownGoalId.into();
+        assert!(matches!(
+            CancelResult::from_msg(&msg),
+            Err(Error::GoalCancelUnknownGoalID)
+        ));
+        msg.return_code = CancelReturnCode::GoalTerminated.into();
+        assert!(matches!(
+            CancelResult::from_msg(&msg),
+            Err(Error::GoalCancelAlreadyTerminated)
+        ));
+        msg.return_code = 4;
+        assert!(matches!(
+            CancelResult::from_msg(&msg),
+            Err(Error::InvalidCancelReturnCode { code: 4 })
+        ));
+    }
+
+    #[test]
+    fn test_parse_status_array() {
+        let goal_id = GoalId::new_random();
+        let time = builtin_interfaces::msg::Time::default();
+        let mut future = GoalStatus::Executing.to_msg(GoalId::new_random(), time.clone());
+        future.status = 42;
+        let mut malformed = GoalStatus::Executing.to_msg(goal_id, time.clone());
+        malformed.goal_info.goal_id.uuid.pop();
+        let msg = action_msgs::msg::GoalStatusArray {
+            status_list: vec![
+                GoalStatus::Succeeded.to_msg(goal_id, time.clone()),
+                future,
+                malformed,
+            ],
+        };
+        let parsed = parse_status_array(&msg);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0], (goal_id, GoalStatus::Succeeded));
+        assert_eq!(parsed[1].1, GoalStatus::Unknown);
+    }
+
+    #[test]
+    fn test_goal_id_conversions() {
+        let s = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        let goal_id: GoalId = s.parse().unwrap();
+        assert_eq!(goal_id.to_string(), s);
+        assert_eq!(GoalId::from_msg(&goal_id.to_msg()).unwrap(), goal_id);
+        assert!("not a goal id".parse::<GoalId>().is_err());
+        let short = unique_identifier_msgs::msg::UUID { uuid: vec![1; 15] };
+        assert!(GoalId::from_msg(&short).is_err());
+        // serialized the same way as a plain uuid.
+        assert_eq!(
+            serde_json::to_string(&goal_id).unwrap(),
+            serde_json::to_string(goal_id.as_uuid()).unwrap()
+        );
+    }
+}
+
+/// The implementation pointer of an rcl_action client or server.
+///
+/// The bindings keep these handles opaque, but like the other rcl
+/// handles they only hold this pointer, which finalizing sets to null.
+pub(crate) fn action_handle_impl<T>(handle: &T) -> *const std::os::raw::c_void {
+    unsafe { *(handle as *const T as *const *const std::os::raw::c_void) }
+}
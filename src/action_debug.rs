@@ -0,0 +1,246 @@
+//! What action clients and servers think is happening, published for
+//! debugging.
+//!
+//! With `ActionClientOptions::debug_period` or
+//! `ActionServerOptions::debug_period` set, the client publishes its
+//! goals and the sizes of its internal maps (see
+//! `ActionClient::bookkeeping_report`) on the hidden topic
+//! `~/_r2r/action_client_debug`, and the server its active goals on
+//! `~/_r2r/action_server_debug`, so that `ros2 topic echo
+//! --include-hidden-topics` shows them.
+//!
+//! The messages are `diagnostic_msgs/msg/DiagnosticArray`s with one
+//! `DiagnosticStatus` per client or server, named after the node and
+//! the action, with a key-value pair per goal and per map, and one
+//! with the hash of the action type (Iron and newer) to tell apart
+//! clients and servers built against different definitions.
+
+use std::sync::{Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use crate::action_clients::{BookkeepingStats, WrappedActionClient};
+use crate::action_common::{GoalId, GoalStatus};
+use crate::action_servers::ActionServer_;
+use crate::diagnostics::{diagnostic_array, DiagnosticStatus, DIAGNOSTICS_MSG_TYPE};
+use crate::distro;
+use crate::error::*;
+use crate::msg_types::generated_msgs::builtin_interfaces;
+use crate::msg_types::WrappedActionTypeSupport;
+use crate::nodes::Node;
+use crate::periodic::{PeriodicMessage, PeriodicPublisher_};
+
+const CLIENT_DEBUG_TOPIC: &str = "~/_r2r/action_client_debug";
+const SERVER_DEBUG_TOPIC: &str = "~/_r2r/action_server_debug";
+
+/// Publishes the state of an action client every `period` until the
+/// client is destroyed.
+pub(crate) fn publish_client_debug<T: 'static>(
+    node: &mut Node,
+    action_name: &str,
+    client: Weak<Mutex<WrappedActionClient<T>>>,
+    period: Duration,
+) -> Result<()>
+where
+    T: WrappedActionTypeSupport,
+{
+    let source = ClientDebug {
+        name: format!("{}: {}", node.fully_qualified_name()?, action_name),
+        type_hash: type_hash_value::<T>(),
+        client,
+    };
+    PeriodicPublisher_::start(
+        node,
+        CLIENT_DEBUG_TOPIC,
+        DIAGNOSTICS_MSG_TYPE,
+        period,
+        Box::new(source),
+    )
+}
+
+/// Publishes the active goals of an action server every `period` until
+/// the server is destroyed.
+pub(crate) fn publish_server_debug<T>(
+    node: &mut Node,
+    action_name: &str,
+    server: Weak<Mutex<dyn ActionServer_>>,
+    period: Duration,
+) -> Result<()>
+where
+    T: WrappedActionTypeSupport,
+{
+    let source = ServerDebug {
+        name: format!("{}: {}", node.fully_qualified_name()?, action_name),
+        type_hash: type_hash_value::<T>(),
+        server,
+    };
+    PeriodicPublisher_::start(
+        node,
+        SERVER_DEBUG_TOPIC,
+        DIAGNOSTICS_MSG_TYPE,
+        period,
+        Box::new(source),
+    )
+}
+
+struct ClientDebug<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    name: String,
+    type_hash: (String, String),
+    client: Weak<Mutex<WrappedActionClient<T>>>,
+}
+
+// only used from the spin, like the client itself.
+unsafe impl<T> Send for ClientDebug<T> where T: WrappedActionTypeSupport {}
+
+impl<T> PeriodicMessage for ClientDebug<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    fn is_dropped(&self) -> bool {
+        self.client.strong_count() == 0
+    }
+
+    fn message(&mut self, stamp: &builtin_interfaces::msg::Time) -> Result<serde_json::Value> {
+        let client = self
+            .client
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_CLIENT_INVALID)?;
+        let client = client.lock().unwrap();
+        let mut goals = client.known_goals();
+        goals.sort_by_key(|(id, _)| *id);
+        let mut values = vec![self.type_hash.clone()];
+        values.extend(goal_values(goals.into_iter()));
+        values.extend(
+            client
+                .bookkeeping_report(Instant::now())
+                .iter()
+                .map(bookkeeping_value),
+        );
+        Ok(debug_array(&self.name, values, stamp))
+    }
+}
+
+struct ServerDebug {
+    name: String,
+    type_hash: (String, String),
+    server: Weak<Mutex<dyn ActionServer_>>,
+}
+
+// only used from the spin, like the server itself.
+unsafe impl Send for ServerDebug {}
+
+impl PeriodicMessage for ServerDebug {
+    fn is_dropped(&self) -> bool {
+        self.server.strong_count() == 0
+    }
+
+    fn message(&mut self, stamp: &builtin_interfaces::msg::Time) -> Result<serde_json::Value> {
+        let server = self
+            .server
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_SERVER_INVALID)?;
+        let goals = server.lock().unwrap().active_goals();
+        let mut values = vec![self.type_hash.clone()];
+        values.extend(goal_values(goals.into_iter()));
+        Ok(debug_array(&self.name, values, stamp))
+    }
+}
+
+fn type_hash_value<T>() -> (String, String)
+where
+    T: WrappedActionTypeSupport,
+{
+    let hash = distro::action_type_hash(T::get_ts()).unwrap_or_else(|_| "-".to_owned());
+    ("type_hash".to_owned(), hash)
+}
+
+fn goal_values(goals: impl Iterator<Item = (GoalId, GoalStatus)>) -> Vec<(String, String)> {
+    goals
+        .map(|(id, status)| (format!("goal {}", id), status.to_string()))
+        .collect()
+}
+
+fn bookkeeping_value(stats: &BookkeepingStats) -> (String, String) {
+    let age = |age: Option<Duration>| match age {
+        Some(age) => format!("{:.3}s", age.as_secs_f64()),
+        None => "-".to_owned(),
+    };
+    (
+        stats.map.to_owned(),
+        format!(
+            "entries: {}, oldest: {}, newest: {}",
+            stats.entries,
+            age(stats.oldest),
+            age(stats.newest)
+        ),
+    )
+}
+
+fn debug_array(
+    name: &str,
+    values: Vec<(String, String)>,
+    stamp: &builtin_interfaces::msg::Time,
+) -> serde_json::Value {
+    let status = DiagnosticStatus {
+        name: name.to_owned(),
+        values,
+        ..Default::default()
+    };
+    diagnostic_array(&[status], stamp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_values() {
+        let id = GoalId::new_random();
+        let values = goal_values(vec![(id, GoalStatus::Executing)].into_iter());
+        assert_eq!(
+            values,
+            vec![(format!("goal {}", id), "executing".to_owned())]
+        );
+
+        let stats = BookkeepingStats {
+            map: "goal_status",
+            entries: 2,
+            oldest: Some(Duration::from_millis(1500)),
+            newest: None,
+        };
+        assert_eq!(
+            bookkeeping_value(&stats),
+            (
+                "goal_status".to_owned(),
+                "entries: 2, oldest: 1.500s, newest: -".to_owned()
+            )
+        );
+
+        let stamp = builtin_interfaces::msg::Time { sec: 1, nanosec: 2 };
+        let msg = debug_array("/node: /fibonacci", values, &stamp);
+        let status = &msg["status"][0];
+        assert_eq!(status["name"], "/node: /fibonacci");
+        assert_eq!(status["values"][0]["value"], "executing");
+        assert_eq!(msg["header"]["stamp"]["sec"], 1);
+    }
+
+    #[cfg(r2r__example_interfaces__action__Fibonacci)]
+    #[test]
+    fn test_type_hash_value() {
+        use crate::msg_types::generated_msgs::example_interfaces::action::Fibonacci;
+
+        let (key, value) = type_hash_value::<Fibonacci::Action>();
+        assert_eq!(key, "type_hash");
+        if cfg!(any(
+            r2r__ros__distro__foxy,
+            r2r__ros__distro__galactic,
+            r2r__ros__distro__humble
+        )) {
+            assert_eq!(value, "-");
+        } else {
+            assert!(value.starts_with("RIHS01_"));
+        }
+    }
+}
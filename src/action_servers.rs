@@ -1,16 +1,27 @@
 use futures::channel::{mpsc, oneshot};
-use futures::future::FutureExt;
-use futures::future::{join_all, JoinAll};
-use futures::stream::Stream;
+use futures::future::{BoxFuture, Future, FutureExt};
+use futures::stream::{Stream, StreamExt};
 use retain_mut::RetainMut;
-use std::collections::HashMap;
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::ffi::CString;
+use std::fmt;
+use std::marker::PhantomData;
 use std::mem::MaybeUninit;
 use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
 
 use crate::error::*;
 use crate::action_common::*;
+use crate::arguments::resolve_topic_name;
+use crate::error_events::*;
+use crate::log_handler::log_internal;
+use crate::utils::LogSeverity;
 use crate::msg_types::*;
+use crate::nodes::Node;
+use crate::qos::QosProfile;
+use crate::subscribers::{recreate_subscription_helper, Subscriber_};
 use crate::msg_types::generated_msgs::{
     unique_identifier_msgs,
     action_msgs,
@@ -33,53 +44,290 @@ pub trait ActionServer_ {
         uuid: &uuid::Uuid,
         new_state: rcl_action_goal_event_t,
     ) -> Result<()>;
-    fn add_result(&mut self, uuid: uuid::Uuid, msg: Box<dyn VoidPtr>) -> ();
+    /// Stores the result of a finished goal, `result` is a `T::Result`
+    /// of the action of the server.
+    fn add_result(&mut self, uuid: uuid::Uuid, status: GoalStatus, result: Box<dyn Any>) -> ();
     fn cancel_goal(&mut self, uuid: &uuid::Uuid);
     fn is_cancelling(&self, uuid: &uuid::Uuid) -> Result<bool>;
+    fn goal_metadata(&self, uuid: &uuid::Uuid) -> Option<GoalMetadata>;
     fn add_goal_handle(
         &mut self,
         uuid: uuid::Uuid,
         goal_handle: *mut rcl_action_goal_handle_t,
     ) -> ();
+    /// Moves a goal that was accepted into the queue to executing.
+    fn start_queued_goal(&mut self, uuid: &uuid::Uuid) -> Result<()>;
+    /// Hands queued goals to the goal request stream as slots free up.
+    fn promote_queued_goals(&mut self, server: Arc<Mutex<dyn ActionServer_>>) -> ();
+    fn release_goal_slot(&mut self, uuid: &uuid::Uuid) -> ();
+    fn action_name(&self) -> &str;
+    /// Returns true when the user has dropped the goal request stream.
+    fn is_dropped(&self) -> bool;
+    fn goal_status(&self, uuid: &uuid::Uuid) -> Option<GoalStatus>;
+    /// Aborts a goal that has not reached a terminal state, see
+    /// `Node::abort_goal`.
+    fn abort_goal(&mut self, uuid: &uuid::Uuid) -> Result<()>;
+    fn active_goals(&self) -> Vec<(GoalId, GoalStatus)>;
+    /// False if sending something to the client of the goal failed, or
+    /// if no action client seems to be left.
+    fn client_reachable(&self, uuid: &uuid::Uuid) -> bool;
+    fn mark_unreachable(&mut self, uuid: &uuid::Uuid);
+    /// Looks in the ROS graph for clients of the action.
+    fn check_clients(&mut self, node: &rcl_node_t);
     fn destroy(&mut self, node: &mut rcl_node_t);
 }
 
+/// Options for creating action servers.
+#[derive(Debug, Clone, Default)]
+pub struct ActionServerOptions {
+    /// Receive `GoalMetadata` from r2r action clients, available
+    /// through `ActionServerGoal::metadata`.
+    pub goal_metadata: bool,
+    /// Limit the number of goals executing at the same time, see `GoalQueue`.
+    pub goal_queue: Option<GoalQueue>,
+    /// Decide on cancel requests with a callback instead of through
+    /// the cancel request streams of the goals, see `CancelCallback`.
+    pub cancel_callback: Option<CancelCallback>,
+    /// QoS of the goal, cancel and result services and of the
+    /// feedback topic. The status topic keeps its transient local
+    /// profile.
+    pub qos: Option<QosProfile>,
+    /// How long results of finished goals are kept for clients that
+    /// have not asked for them yet (15 minutes by default). After that
+    /// the goal expires, it is dropped from the status and result
+    /// requests for it are answered with `GoalStatus::Unknown`.
+    pub result_timeout: Option<Duration>,
+    /// When the server is destroyed, e.g. because the node is dropped,
+    /// its unfinished goals are aborted. For up to this long (200 ms by
+    /// default), the server then waits for result requests of those
+    /// goals to answer them with the abort.
+    pub shutdown_timeout: Option<Duration>,
+    /// Publish the active goals of the server on the hidden topic
+    /// `~/_r2r/action_server_debug` this often, as a
+    /// `diagnostic_msgs/msg/DiagnosticArray`. Off by default.
+    pub debug_period: Option<Duration>,
+}
+
+pub(crate) const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// The answer to a request to cancel a goal.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CancelDecision {
+    Accept,
+    Reject,
+}
+
+/// An async callback that decides whether a goal may be canceled.
+///
+/// It is called once for each goal a cancel request targets, and the
+/// response to the request is sent when all of the returned futures
+/// have resolved. The futures are polled while spinning the node. A
+/// decision that takes longer than the timeout (10 seconds unless set
+/// with `timeout`) counts as `Accept`.
+///
+/// When a server has a cancel callback, the cancel request streams of
+/// its goals receive nothing. Use `ActionServerGoal::is_cancelling` to
+/// find out that a goal should stop.
+#[derive(Clone)]
+pub struct CancelCallback {
+    callback: Arc<dyn Fn(GoalId) -> BoxFuture<'static, CancelDecision> + Send + Sync>,
+    timeout: Duration,
+}
+
+impl CancelCallback {
+    pub fn new<F, Fut>(callback: F) -> Self
+    where
+        F: Fn(GoalId) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = CancelDecision> + Send + 'static,
+    {
+        CancelCallback {
+            callback: Arc::new(move |goal| callback(goal).boxed()),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// A callback that decides right away, e.g. to refuse canceling
+    /// goals that are past a point of no return.
+    ///
+    /// ```ignore
+    /// let callback = CancelCallback::immediate(move |goal| {
+    ///     if committed.lock().unwrap().contains(goal) {
+    ///         CancelDecision::Reject
+    ///     } else {
+    ///         CancelDecision::Accept
+    ///     }
+    /// });
+    /// ```
+    pub fn immediate<F>(callback: F) -> Self
+    where
+        F: FnMut(&GoalId) -> CancelDecision + Send + 'static,
+    {
+        let callback = Mutex::new(callback);
+        CancelCallback::new(move |goal| {
+            let decision = (callback.lock().unwrap())(&goal);
+            futures::future::ready(decision)
+        })
+    }
+
+    /// Accept the cancellation of goals whose decision takes longer
+    /// than `timeout`.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        CancelCallback { timeout, ..self }
+    }
+}
+
+impl fmt::Debug for CancelCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancelCallback")
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+/// Admission control for an action server.
+///
+/// At most `max_executing` goal requests are handed to the goal
+/// request stream at a time. Goals arriving while all of them are
+/// busy are accepted on behalf of the server and wait in the
+/// `Accepted` state, in order of arrival, until a goal reaches a
+/// terminal state (or is rejected). Goals arriving when
+/// `max_queued` goals are already waiting are rejected.
+///
+/// A goal from the queue has already been accepted, so calling
+/// `accept` moves it to `Executing` and calling `reject` aborts it.
+/// Canceling a queued goal removes it from the queue, and it is never
+/// handed to the goal request stream.
+#[derive(Debug, Clone)]
+pub struct GoalQueue {
+    pub max_executing: usize,
+    pub max_queued: usize,
+}
+
+pub struct QueuedGoal<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    uuid: uuid::Uuid,
+    goal: T::Goal,
+    cancel_requests: mpsc::Receiver<ActionServerCancelRequest>,
+}
+
 /// Request to cancel an active goal.
 pub struct ActionServerCancelRequest {
-    pub uuid: uuid::Uuid,
+    pub uuid: GoalId,
     response_sender: oneshot::Sender<(uuid::Uuid, bool)>,
 }
 
 impl ActionServerCancelRequest {
     /// Accepts the cancel request. The action server should now cancel the corresponding goal.
     pub fn accept(self) {
-        match self.response_sender.send((self.uuid, true)) {
-            Err(_) => eprintln!("warning: could not send goal canellation accept msg"),
+        match self.response_sender.send((self.uuid.into(), true)) {
+            Err(_) => log_internal(
+                LogSeverity::Warn,
+                "could not send goal cancellation accept msg",
+            ),
             _ => (),
         }
     }
     /// Rejects the cancel request.
     pub fn reject(self) {
-        match self.response_sender.send((self.uuid, false)) {
-            Err(_) => eprintln!("warning: could not send goal cancellation rejection"),
+        match self.response_sender.send((self.uuid.into(), false)) {
+            Err(_) => log_internal(
+                LogSeverity::Warn,
+                "could not send goal cancellation rejection",
+            ),
             _ => (),
         }
     }
 }
 
 /// Request to the action server to accept a new `Goal`.
-pub struct ActionServerGoalRequest<T>
+///
+/// The client gets its response when the request is accepted or
+/// rejected, which can be done later, e.g. after asking another node
+/// whether the goal can be handled. A request that is dropped before
+/// that, e.g. because the future deciding on it was canceled, is
+/// rejected.
+pub struct ActionServerGoalRequest<T: 'static>
 where
     T: WrappedActionTypeSupport,
 {
-    pub uuid: uuid::Uuid,
+    pub uuid: GoalId,
     pub goal: T::Goal,
     cancel_requests: mpsc::Receiver<ActionServerCancelRequest>,
+    response: PendingGoalResponse<T>,
+}
+
+// The response to a goal request until it has been decided on.
+struct PendingGoalResponse<T: 'static>
+where
+    T: WrappedActionTypeSupport,
+{
+    uuid: uuid::Uuid,
     server: Weak<Mutex<dyn ActionServer_>>,
-    request_id: rmw_request_id_t,
+    // None for goals from the queue, which have already been accepted.
+    request_id: Option<rmw_request_id_t>,
+    decided: bool,
+    phantom: PhantomData<T>,
+}
+
+impl<T: 'static> PendingGoalResponse<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    fn new(
+        uuid: uuid::Uuid,
+        server: Weak<Mutex<dyn ActionServer_>>,
+        request_id: Option<rmw_request_id_t>,
+    ) -> Self {
+        PendingGoalResponse {
+            uuid,
+            server,
+            request_id,
+            decided: false,
+            phantom: PhantomData,
+        }
+    }
+
+    fn reject(&mut self, server: &mut dyn ActionServer_) -> Result<()> {
+        self.decided = true;
+        let mut request_id = match self.request_id {
+            Some(request_id) => request_id,
+            None => {
+                server.start_queued_goal(&self.uuid)?;
+                return ActionServerGoal::<T>::finish(
+                    server,
+                    &self.uuid,
+                    rcl_action_goal_event_t::GOAL_EVENT_ABORT,
+                    GoalStatus::Aborted,
+                    T::Result::default(),
+                );
+            }
+        };
+        let time = builtin_interfaces::msg::Time::default();
+        send_goal_response::<T>(server.handle_mut(), &mut request_id, false, time)?;
+        server.release_goal_slot(&self.uuid);
+        Ok(())
+    }
+}
+
+impl<T: 'static> Drop for PendingGoalResponse<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    fn drop(&mut self) {
+        if self.decided {
+            return;
+        }
+        // nobody to respond to when the server is gone.
+        if let Some(server) = self.server.upgrade() {
+            let mut server = server.lock().unwrap();
+            let _ = self.reject(&mut *server);
+        }
+    }
 }
 
-unsafe impl<T> Send for ActionServerGoalRequest<T> where T: WrappedActionTypeSupport {}
+unsafe impl<T: 'static> Send for ActionServerGoalRequest<T> where T: WrappedActionTypeSupport {}
 
 impl<T: 'static> ActionServerGoalRequest<T>
 where
@@ -88,14 +336,31 @@ where
     /// Accept the goal request and become a ServerGoal.
     /// Returns a handle to the goal and a stream on which cancel requests can be received.
     pub fn accept(
-        mut self,
+        self,
     ) -> Result<(
         ActionServerGoal<T>,
         impl Stream<Item = ActionServerCancelRequest> + Unpin,
     )> {
-        let uuid_msg = unique_identifier_msgs::msg::UUID {
-            uuid: self.uuid.as_bytes().to_vec(),
+        let mut response = self.response;
+        response.decided = true;
+        let mut request_id = match response.request_id {
+            Some(request_id) => request_id,
+            None => {
+                let server = response
+                    .server
+                    .upgrade()
+                    .ok_or(Error::RCL_RET_ACTION_SERVER_INVALID)?;
+                server.lock().unwrap().start_queued_goal(&self.uuid)?;
+                let g = ActionServerGoal {
+                    uuid: self.uuid,
+                    goal: self.goal,
+                    server: response.server.clone(),
+                    feedback: Default::default(),
+                };
+                return Ok((g, self.cancel_requests));
+            }
         };
+        let uuid_msg = self.uuid.to_msg();
         let time = builtin_interfaces::msg::Time::default();
         let goal_info = action_msgs::msg::GoalInfo {
             goal_id: uuid_msg,
@@ -103,28 +368,16 @@ where
         };
         let native_goal_info = WrappedNativeMsg::<action_msgs::msg::GoalInfo>::from(&goal_info);
 
-        let server = self.server.upgrade().unwrap(); // todo fixme
+        let server = response
+            .server
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_SERVER_INVALID)?;
         let mut server = server.lock().unwrap();
 
         let goal_handle: *mut rcl_action_goal_handle_t =
             unsafe { rcl_action_accept_new_goal(server.handle_mut(), &*native_goal_info) };
 
-        // send response
-        let response_msg = T::make_goal_response_msg(true, time);
-        let mut response_msg = WrappedNativeMsg::<
-            <<T as WrappedActionTypeSupport>::SendGoal as WrappedServiceTypeSupport>::Response,
-        >::from(&response_msg);
-
-        let ret = unsafe {
-            rcl_action_send_goal_response(
-                server.handle_mut(),
-                &mut self.request_id,
-                response_msg.void_ptr_mut(),
-            )
-        };
-        if ret != RCL_RET_OK as i32 {
-            return Err(Error::from_rcl_error(ret));
-        }
+        send_goal_response::<T>(server.handle_mut(), &mut request_id, true, time)?;
 
         unsafe {
             rcl_action_update_goal_state(goal_handle, rcl_action_goal_event_t::GOAL_EVENT_EXECUTE);
@@ -135,38 +388,29 @@ where
         let g = ActionServerGoal {
             uuid: self.uuid.clone(),
             goal: self.goal,
-            server: self.server,
+            server: response.server.clone(),
+            feedback: Default::default(),
         };
 
         // server.goals.insert(g.uuid.clone(), goal_handle);
-        server.add_goal_handle(g.uuid.clone(), goal_handle);
+        server.add_goal_handle(g.uuid.into(), goal_handle);
 
         return Ok((g, self.cancel_requests));
     }
 
     /// reject the goal request and be consumed in the process
-    pub fn reject(mut self) -> Result<()> {
-        let time = builtin_interfaces::msg::Time::default();
-        let server = self.server.upgrade().unwrap(); // todo fixme
+    ///
+    /// Goals from a `GoalQueue` have already been accepted and are
+    /// aborted instead.
+    pub fn reject(self) -> Result<()> {
+        let mut response = self.response;
+        response.decided = true;
+        let server = response
+            .server
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_SERVER_INVALID)?;
         let mut server = server.lock().unwrap();
-
-        let response_msg = T::make_goal_response_msg(false, time);
-        let mut response_msg = WrappedNativeMsg::<
-            <<T as WrappedActionTypeSupport>::SendGoal as WrappedServiceTypeSupport>::Response,
-        >::from(&response_msg);
-
-        let ret = unsafe {
-            rcl_action_send_goal_response(
-                server.handle_mut(),
-                &mut self.request_id,
-                response_msg.void_ptr_mut(),
-            )
-        };
-        if ret != RCL_RET_OK as i32 {
-            return Err(Error::from_rcl_error(ret));
-        }
-
-        Ok(())
+        response.reject(&mut *server)
     }
 }
 
@@ -178,14 +422,299 @@ where
     pub clock_handle: Box<rcl_clock_t>,
     pub goal_request_sender: mpsc::Sender<ActionServerGoalRequest<T>>,
     pub cancel_senders: HashMap<uuid::Uuid, mpsc::Sender<ActionServerCancelRequest>>,
-    pub active_cancel_requests: Vec<(
-        rmw_request_id_t,
-        action_msgs::srv::CancelGoal::Response,
-        JoinAll<oneshot::Receiver<(uuid::Uuid, bool)>>,
-    )>,
+    pub cancel_callback: Option<CancelCallback>,
+    pub active_cancel_requests: Vec<PendingCancelRequest>,
     pub goals: HashMap<uuid::Uuid, *mut rcl_action_goal_handle_t>,
-    pub result_msgs: HashMap<uuid::Uuid, Box<dyn VoidPtr>>,
+    // results are stored together with the terminal status they were
+    // produced by, which the result responses carry.
+    pub result_msgs: HashMap<uuid::Uuid, (GoalStatus, T::Result)>,
     pub result_requests: HashMap<uuid::Uuid, Vec<rmw_request_id_t>>,
+    pub goal_metadata: Option<Arc<Mutex<HashMap<uuid::Uuid, GoalMetadata>>>>,
+    pub errors: EntityErrors,
+    pub action_name: String,
+    pub goal_queue: Option<GoalQueue>,
+    // goals handed to the goal request stream that are not yet done.
+    pub running_goals: HashSet<uuid::Uuid>,
+    pub queued_goals: VecDeque<QueuedGoal<T>>,
+    // queued goals whose cancellation is being answered.
+    pub canceled_queued_goals: Vec<uuid::Uuid>,
+    // goals whose client could not be sent something.
+    pub unreachable_goals: HashSet<uuid::Uuid>,
+    // goals whose last status could not be published.
+    pub status_publish_failed: RefCell<HashSet<uuid::Uuid>>,
+    // number of client checks in a row that found no client.
+    pub missing_client_checks: usize,
+    pub shutdown_timeout: Duration,
+}
+
+// Clients count as gone after this many checks without any of them.
+const MISSING_CLIENT_CHECKS: usize = 2;
+
+// A cancel request whose response waits for the decisions on its goals.
+pub struct PendingCancelRequest {
+    request_id: rmw_request_id_t,
+    response: action_msgs::srv::CancelGoal::Response,
+    decisions: Vec<PendingCancelDecision>,
+}
+
+struct PendingCancelDecision {
+    uuid: uuid::Uuid,
+    decision: Option<bool>,
+    future: BoxFuture<'static, std::result::Result<bool, oneshot::Canceled>>,
+    // decisions of a cancel callback count as accepted after this.
+    deadline: Option<Instant>,
+}
+
+impl PendingCancelDecision {
+    fn decided(uuid: uuid::Uuid, accept: bool) -> Self {
+        PendingCancelDecision {
+            uuid,
+            decision: Some(accept),
+            future: futures::future::ready(Ok(accept)).boxed(),
+            deadline: None,
+        }
+    }
+}
+
+fn send_goal_response<T>(
+    handle: &mut rcl_action_server_t,
+    request_id: &mut rmw_request_id_t,
+    accepted: bool,
+    stamp: builtin_interfaces::msg::Time,
+) -> Result<()>
+where
+    T: WrappedActionTypeSupport,
+{
+    let response_msg = T::make_goal_response_msg(accepted, stamp);
+    let mut response_msg = WrappedNativeMsg::<
+        <<T as WrappedActionTypeSupport>::SendGoal as WrappedServiceTypeSupport>::Response,
+    >::from(&response_msg);
+
+    let ret = unsafe {
+        rcl_action_send_goal_response(handle, request_id, response_msg.void_ptr_mut())
+    };
+    if ret != RCL_RET_OK as i32 {
+        return Err(Error::from_rcl_error(ret));
+    }
+    Ok(())
+}
+
+impl<T: 'static> WrappedActionServer<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    // Accepts a goal on behalf of the user, it waits in the queue
+    // until a slot frees up.
+    fn enqueue_goal(
+        &mut self,
+        mut request_id: rmw_request_id_t,
+        uuid: uuid::Uuid,
+        goal: T::Goal,
+    ) -> Result<()> {
+        let time = builtin_interfaces::msg::Time::default();
+        let goal_info = action_msgs::msg::GoalInfo {
+            goal_id: GoalId::from(uuid).to_msg(),
+            stamp: time.clone(),
+        };
+        let native_goal_info = WrappedNativeMsg::<action_msgs::msg::GoalInfo>::from(&goal_info);
+        let goal_handle =
+            unsafe { rcl_action_accept_new_goal(&mut self.rcl_handle, &*native_goal_info) };
+        if goal_handle.is_null() {
+            return Err(Error::RCL_RET_ACTION_GOAL_HANDLE_INVALID);
+        }
+        send_goal_response::<T>(&mut self.rcl_handle, &mut request_id, true, time)?;
+        self.goals.insert(uuid, goal_handle);
+
+        let (cancel_sender, cancel_receiver) = mpsc::channel::<ActionServerCancelRequest>(10);
+        self.cancel_senders.insert(uuid, cancel_sender);
+        self.queued_goals.push_back(QueuedGoal {
+            uuid,
+            goal,
+            cancel_requests: cancel_receiver,
+        });
+        self.publish_status();
+        Ok(())
+    }
+
+    // The request is rejected here, as dropping it would lock the server
+    // that is locked already.
+    fn undeliverable_goal_request(&mut self, e: mpsc::TrySendError<ActionServerGoalRequest<T>>) {
+        self.errors.report(
+            SpinOperation::Deliver,
+            Error::DeliveryFailed {
+                reason: e.to_string(),
+            },
+        );
+        let mut response = e.into_inner().response;
+        if let Err(e) = response.reject(self) {
+            self.errors.report(SpinOperation::Send, e);
+        }
+    }
+
+    fn finish_canceled_queued_goal(&mut self, uuid: &uuid::Uuid) -> Result<()> {
+        self.set_goal_state(uuid, rcl_action_goal_event_t::GOAL_EVENT_CANCELED)?;
+        self.add_result(*uuid, GoalStatus::Canceled, Box::new(T::Result::default()));
+        Ok(())
+    }
+
+    fn result_response(
+        status: GoalStatus,
+        result: T::Result,
+    ) -> WrappedNativeMsg<
+        <<T as WrappedActionTypeSupport>::GetResult as WrappedServiceTypeSupport>::Response,
+    > {
+        let msg = T::make_result_response_msg(status.to_rcl(), result);
+        WrappedNativeMsg::from(&msg)
+    }
+
+    fn goal_exists(&self, uuid: &uuid::Uuid) -> bool {
+        let goal_info = action_msgs::msg::GoalInfo {
+            goal_id: GoalId::from(*uuid).to_msg(),
+            ..action_msgs::msg::GoalInfo::default()
+        };
+        let goal_info = WrappedNativeMsg::<action_msgs::msg::GoalInfo>::from(&goal_info);
+        unsafe { rcl_action_server_goal_exists(&self.rcl_handle, &*goal_info) }
+    }
+
+    // Takes one result request and answers it if the result is known,
+    // returns the goal of an answered request.
+    fn serve_result_request(&mut self) -> Option<uuid::Uuid> {
+        let mut request_id = MaybeUninit::<rmw_request_id_t>::uninit();
+        let mut request_msg = WrappedNativeMsg::<
+            <<T as WrappedActionTypeSupport>::GetResult as WrappedServiceTypeSupport>::Request,
+        >::new();
+        let ret = unsafe {
+            rcl_action_take_result_request(
+                &self.rcl_handle,
+                request_id.as_mut_ptr(),
+                request_msg.void_ptr_mut(),
+            )
+        };
+
+        if ret != RCL_RET_OK as i32 {
+            // this seems normal if client dies.
+            if ret != RCL_RET_ACTION_SERVER_TAKE_FAILED as i32 {
+                self.errors
+                    .report(SpinOperation::Take, Error::from_rcl_error(ret));
+            }
+            return None;
+        }
+
+        let msg = <<<T as WrappedActionTypeSupport>::GetResult as WrappedServiceTypeSupport>::Request>::from_native(&request_msg);
+        // checked before converting back, which asserts the length.
+        let uuid = match uuid_msg_to_uuid(&T::destructure_result_request_msg(msg)) {
+            Ok(uuid) => uuid,
+            Err(e) => {
+                self.errors.report(SpinOperation::Convert, e);
+                return None;
+            }
+        };
+
+        let response_msg = if !self.goal_exists(&uuid) {
+            // never accepted, or expired after `result_timeout`.
+            self.errors.report(
+                SpinOperation::Match,
+                Error::UnmatchedResponse {
+                    reason: format!("result requested for unknown goal {}", uuid),
+                },
+            );
+            Some(Self::result_response(
+                GoalStatus::Unknown,
+                T::Result::default(),
+            ))
+        } else {
+            self.result_msgs
+                .get(&uuid)
+                .map(|(status, result)| Self::result_response(*status, result.clone()))
+        };
+
+        let mut request_id = unsafe { request_id.assume_init() };
+        if let Some(mut response_msg) = response_msg {
+            let ret = unsafe {
+                rcl_action_send_result_response(
+                    &self.rcl_handle,
+                    &mut request_id,
+                    response_msg.void_ptr_mut(),
+                )
+            };
+
+            if ret != RCL_RET_OK as i32 {
+                self.errors
+                    .report(SpinOperation::Send, Error::from_rcl_error(ret));
+                return None;
+            }
+            Some(uuid)
+        } else {
+            // keep request for later when result comes in
+            // todo: add logic that replies to the requests
+            self.result_requests
+                .entry(uuid)
+                .or_insert(vec![])
+                .push(request_id);
+            None
+        }
+    }
+
+    // Aborts the goals that have not reached a terminal state and
+    // publishes their status. Returns the aborted goals whose result
+    // has not been requested yet.
+    fn abort_unfinished_goals(&mut self) -> HashSet<uuid::Uuid> {
+        let unfinished: Vec<(uuid::Uuid, GoalStatus)> = self
+            .goals
+            .keys()
+            .filter_map(|uuid| self.goal_status(uuid).map(|s| (*uuid, s)))
+            .filter(|(_, status)| {
+                matches!(
+                    status,
+                    GoalStatus::Accepted | GoalStatus::Executing | GoalStatus::Canceling
+                )
+            })
+            .collect();
+        if unfinished.is_empty() {
+            return HashSet::new();
+        }
+
+        let mut waiting = HashSet::new();
+        for (uuid, status) in unfinished {
+            let no_result_request = !self.result_requests.contains_key(&uuid);
+            match self.abort_goal_handle(&uuid, status) {
+                Ok(()) if no_result_request => {
+                    waiting.insert(uuid);
+                }
+                Ok(()) => (),
+                Err(e) => self.errors.report(SpinOperation::Update, e),
+            }
+        }
+        unsafe {
+            rcl_action_notify_goal_done(&self.rcl_handle);
+        }
+        self.publish_status();
+        waiting
+    }
+
+    // Moves an unfinished goal to aborted and stores an empty result
+    // for it. The caller notifies rcl and publishes the status.
+    fn abort_goal_handle(&mut self, uuid: &uuid::Uuid, status: GoalStatus) -> Result<()> {
+        let handle = *self
+            .goals
+            .get(uuid)
+            .ok_or(Error::RCL_RET_ACTION_GOAL_HANDLE_INVALID)?;
+        let mut events = vec![];
+        // accepted goals cannot be aborted before they execute.
+        if status == GoalStatus::Accepted {
+            events.push(rcl_action_goal_event_t::GOAL_EVENT_EXECUTE);
+        }
+        events.push(rcl_action_goal_event_t::GOAL_EVENT_ABORT);
+        for event in events {
+            let ret = unsafe { rcl_action_update_goal_state(handle, event) };
+            if ret != RCL_RET_OK as i32 {
+                return Err(Error::from_rcl_error(ret));
+            }
+        }
+
+        self.add_result(*uuid, GoalStatus::Aborted, Box::new(T::Result::default()));
+        Ok(())
+    }
 }
 
 impl<T: 'static> ActionServer_ for WrappedActionServer<T>
@@ -206,28 +735,37 @@ where
             let ret = unsafe { rcl_action_goal_handle_get_status(*handle, &mut state) };
 
             if ret != RCL_RET_OK as i32 {
-                println!("action server: Failed to get goal handle state: {}", ret);
-                return Err(Error::from_rcl_error(ret));
+                let err = Error::from_rcl_error(ret);
+                self.errors.log(
+                    LogSeverity::Warn,
+                    &format!("could not get goal handle state: {}", err),
+                );
+                return Err(err);
             }
             return Ok(state == 3u8); // TODO: int8 STATUS_CANCELING
         }
         Err(Error::RCL_RET_ACTION_GOAL_HANDLE_INVALID)
     }
 
+    fn goal_metadata(&self, uuid: &uuid::Uuid) -> Option<GoalMetadata> {
+        self.goal_metadata
+            .as_ref()
+            .and_then(|m| m.lock().unwrap().get(uuid).cloned())
+    }
+
     fn cancel_goal(&mut self, uuid: &uuid::Uuid) {
-        if let Some(handle) = self.goals.remove(uuid) {
+        // keep the handle, the goal still needs to reach a terminal state.
+        if let Some(handle) = self.goals.get(uuid) {
             let ret = unsafe {
                 rcl_action_update_goal_state(
-                    handle,
+                    *handle,
                     rcl_action_goal_event_t::GOAL_EVENT_CANCEL_GOAL,
                 )
             };
 
             if ret != RCL_RET_OK as i32 {
-                println!(
-                    "action server: could not cancel goal: {}",
-                    Error::from_rcl_error(ret)
-                );
+                self.errors
+                    .report(SpinOperation::Update, Error::from_rcl_error(ret));
             }
         }
     }
@@ -250,14 +788,17 @@ where
             unsafe { rcl_action_server_goal_exists(self.handle(), &*goal_info_native) };
 
         if !goal_exists {
-            println!("tried to publish result without a goal");
+            self.errors
+                .log(LogSeverity::Warn, "tried to publish result without a goal");
             return Err(Error::RCL_RET_ACTION_GOAL_HANDLE_INVALID);
         }
 
         if let Some(handle) = self.goals.get(uuid) {
-            // todo: error handling
-            unsafe {
-                rcl_action_update_goal_state(*handle, new_state);
+            // fails e.g. for goals that have been aborted with
+            // `Node::abort_goal` in the meantime.
+            let ret = unsafe { rcl_action_update_goal_state(*handle, new_state) };
+            if ret != RCL_RET_OK as i32 {
+                return Err(Error::from_rcl_error(ret));
             }
 
             // todo: error handling
@@ -277,50 +818,72 @@ where
     fn send_completed_cancel_requests(&mut self) {
         let mut canceled = vec![];
         let mut responses = vec![];
-        self.active_cancel_requests
-            .retain_mut(|(request_id, msg, fut)| {
-                let boxed = fut.boxed();
-                if let Some(results) = boxed.now_or_never() {
-                    let mut response_msg = msg.clone();
-                    let requested_cancels = response_msg.goals_canceling.len();
-                    for r in results {
-                        match r {
-                            Ok((uuid, do_cancel)) => {
-                                // cancel goal and filter response msg.
-                                if do_cancel {
-                                    canceled.push(uuid.clone());
-                                }
-
-                                response_msg.goals_canceling.retain(|goal_info| {
-                                    let msg_uuid = uuid_msg_to_uuid(&goal_info.goal_id);
-                                    do_cancel && msg_uuid == uuid
-                                });
-                            }
-                            Err(oneshot::Canceled) => {
-                                eprintln!("Warning, cancel request not handled!");
-                                return false; // skip this request.
-                            }
-                        }
+        let now = Instant::now();
+        let errors = &self.errors;
+        self.active_cancel_requests.retain_mut(|request| {
+            for d in request
+                .decisions
+                .iter_mut()
+                .filter(|d| d.decision.is_none())
+            {
+                match d.future.as_mut().now_or_never() {
+                    Some(Ok(accept)) => d.decision = Some(accept),
+                    Some(Err(oneshot::Canceled)) => {
+                        errors.report(
+                            SpinOperation::Deliver,
+                            Error::DeliveryFailed {
+                                reason: "cancel request dropped without a reply".into(),
+                            },
+                        );
+                        return false; // skip this request.
                     }
-
-                    // check if all cancels were rejected.
-                    if requested_cancels >= 1 && response_msg.goals_canceling.is_empty() {
-                        response_msg.return_code = 1; // TODO: auto generate these (int8 ERROR_REJECTED=1)
+                    None if d.deadline.map(|t| now >= t).unwrap_or(false) => {
+                        // no decision in time, cancel anyway.
+                        d.decision = Some(true);
                     }
-
-                    responses.push((*request_id, response_msg));
-
-                    false
-                } else {
-                    true
+                    None => (),
                 }
+            }
+            if request.decisions.iter().any(|d| d.decision.is_none()) {
+                return true;
+            }
+
+            // only report the goals that will be canceled.
+            let accepted: Vec<uuid::Uuid> = request
+                .decisions
+                .iter()
+                .filter(|d| d.decision == Some(true))
+                .map(|d| d.uuid)
+                .collect();
+            let mut response_msg = request.response.clone();
+            let requested_cancels = response_msg.goals_canceling.len();
+            response_msg.goals_canceling.retain(|goal_info| {
+                uuid_msg_to_uuid(&goal_info.goal_id).map_or(false, |uuid| accepted.contains(&uuid))
             });
 
+            // check if all cancels were rejected.
+            if requested_cancels >= 1 && response_msg.goals_canceling.is_empty() {
+                response_msg.return_code = 1; // TODO: auto generate these (int8 ERROR_REJECTED=1)
+            }
+
+            canceled.extend(accepted);
+            responses.push((request.request_id, response_msg));
+            false
+        });
+
         canceled.iter().for_each(|uuid| self.cancel_goal(&uuid));
         if !canceled.is_empty() {
             // at least one goal state changed, publish a new status message
             self.publish_status();
         }
+        for uuid in &canceled {
+            if let Some(idx) = self.canceled_queued_goals.iter().position(|u| u == uuid) {
+                self.canceled_queued_goals.swap_remove(idx);
+                if let Err(e) = self.finish_canceled_queued_goal(uuid) {
+                    self.errors.report(SpinOperation::Update, e);
+                }
+            }
+        }
 
         // send out responses
         for (mut request_id, response_msg) in responses {
@@ -336,7 +899,8 @@ where
             };
 
             if ret != RCL_RET_OK as i32 {
-                println!("action server: could send cancel response. {}", ret);
+                self.errors
+                    .report(SpinOperation::Send, Error::from_rcl_error(ret));
             }
         }
     }
@@ -356,27 +920,56 @@ where
 
         if ret != RCL_RET_OK as i32 {
             // this seems normal if client dies.
+            if ret != RCL_RET_ACTION_SERVER_TAKE_FAILED as i32 {
+                self.errors
+                    .report(SpinOperation::Take, Error::from_rcl_error(ret));
+            }
             return;
         }
         let msg = <<<T as WrappedActionTypeSupport>::SendGoal as WrappedServiceTypeSupport>::Request>::from_native(&request_msg);
         let (uuid_msg, goal) = T::destructure_goal_request_msg(msg);
-        let uuid = uuid_msg_to_uuid(&uuid_msg);
+        let uuid = match uuid_msg_to_uuid(&uuid_msg) {
+            Ok(uuid) => uuid,
+            Err(e) => {
+                self.errors.report(SpinOperation::Convert, e);
+                return;
+            }
+        };
+        let mut request_id = unsafe { request_id.assume_init() };
+
+        if let Some(queue) = &self.goal_queue {
+            if self.running_goals.len() >= queue.max_executing.max(1) {
+                let result = if self.queued_goals.len() < queue.max_queued {
+                    self.enqueue_goal(request_id, uuid, goal)
+                } else {
+                    send_goal_response::<T>(
+                        &mut self.rcl_handle,
+                        &mut request_id,
+                        false,
+                        builtin_interfaces::msg::Time::default(),
+                    )
+                };
+                if let Err(e) = result {
+                    self.errors.report(SpinOperation::Send, e);
+                }
+                return;
+            }
+            self.running_goals.insert(uuid);
+        }
 
         let (cancel_sender, cancel_receiver) = mpsc::channel::<ActionServerCancelRequest>(10);
         self.cancel_senders.insert(uuid.clone(), cancel_sender);
 
         let gr: ActionServerGoalRequest<T> = ActionServerGoalRequest {
-            uuid,
+            uuid: uuid.into(),
             goal,
             cancel_requests: cancel_receiver,
-            server: Arc::downgrade(&server),
-            request_id: unsafe { request_id.assume_init() },
+            response: PendingGoalResponse::new(uuid, Arc::downgrade(&server), Some(request_id)),
         };
 
         // send out request.
-        match self.goal_request_sender.try_send(gr) {
-            Err(e) => eprintln!("warning: could not send service request ({})", e),
-            _ => (),
+        if let Err(e) = self.goal_request_sender.try_send(gr) {
+            self.undeliverable_goal_request(e);
         }
     }
 
@@ -395,6 +988,10 @@ where
 
         if ret != RCL_RET_OK as i32 {
             // this seems normal if client dies.
+            if ret != RCL_RET_ACTION_SERVER_TAKE_FAILED as i32 {
+                self.errors
+                    .report(SpinOperation::Take, Error::from_rcl_error(ret));
+            }
             return;
         }
 
@@ -404,62 +1001,111 @@ where
         };
 
         if ret != RCL_RET_OK as i32 {
-            println!("action server: could not process cancel request. {}", ret);
+            self.errors
+                .report(SpinOperation::Update, Error::from_rcl_error(ret));
             return;
         }
 
         let response_msg =
             action_msgs::srv::CancelGoal::Response::from_native(&cancel_response.msg);
 
-        let return_channels = response_msg
+        let decisions = response_msg
             .goals_canceling
             .iter()
             .flat_map(|goal_info| {
-                let uuid = uuid_msg_to_uuid(&goal_info.goal_id);
+                let uuid = match uuid_msg_to_uuid(&goal_info.goal_id) {
+                    Ok(uuid) => uuid,
+                    Err(e) => {
+                        self.errors.report(SpinOperation::Convert, e);
+                        return None;
+                    }
+                };
+                if let Some(idx) = self.queued_goals.iter().position(|q| q.uuid == uuid) {
+                    // never started, so it can always be canceled.
+                    self.queued_goals.remove(idx);
+                    self.canceled_queued_goals.push(uuid);
+                    return Some(PendingCancelDecision::decided(uuid, true));
+                }
+                if let Some(cb) = &self.cancel_callback {
+                    let decision = (cb.callback)(uuid.into());
+                    return Some(PendingCancelDecision {
+                        uuid,
+                        decision: None,
+                        future: decision.map(|d| Ok(d == CancelDecision::Accept)).boxed(),
+                        deadline: Some(Instant::now() + cb.timeout),
+                    });
+                }
                 self.cancel_senders
                     .get_mut(&uuid)
                     .and_then(|cancel_sender| {
                         let (s, r) = oneshot::channel::<(uuid::Uuid, bool)>();
                         let cr = ActionServerCancelRequest {
-                            uuid: uuid.clone(),
+                            uuid: uuid.into(),
                             response_sender: s,
                         };
                         match cancel_sender.try_send(cr) {
-                            Err(_) => {
-                                eprintln!("warning: could not send goal cancellation request");
+                            Err(e) => {
+                                self.errors.report(
+                                    SpinOperation::Deliver,
+                                    Error::DeliveryFailed {
+                                        reason: e.to_string(),
+                                    },
+                                );
                                 None
                             }
-                            _ => Some(r),
+                            _ => Some(PendingCancelDecision {
+                                uuid,
+                                decision: None,
+                                future: r.map(|r| r.map(|(_, accept)| accept)).boxed(),
+                                deadline: None,
+                            }),
                         }
                     })
             })
             .collect::<Vec<_>>();
 
-        // because we want to reply to the caller when all goals have been either accepted or rejected,
-        // join the channels into one future that we can poll during spin.
-        self.active_cancel_requests
-            .push((request_id, response_msg, join_all(return_channels)));
+        // we reply to the caller when all goals have been either
+        // accepted or rejected, the decisions are polled during spin.
+        self.active_cancel_requests.push(PendingCancelRequest {
+            request_id,
+            response: response_msg,
+            decisions,
+        });
     }
 
     fn handle_goal_expired(&mut self) {
-        let mut goal_info = WrappedNativeMsg::<action_msgs::msg::GoalInfo>::new();
-        let mut num_expired = 1;
-
-        while num_expired > 1 {
-            let ret = unsafe {
-                rcl_action_expire_goals(&self.rcl_handle, &mut *goal_info, 1, &mut num_expired)
-            };
-            if ret != RCL_RET_OK as i32 {
-                println!("action server: could not expire goal.");
-                return;
-            }
-            let gi = action_msgs::msg::GoalInfo::from_native(&goal_info);
-            let uuid = uuid_msg_to_uuid(&gi.goal_id);
-            println!("goal expired: {} - {}", uuid, num_expired);
-            // todo
-            // self.goals.remove(&uuid);
+        let mut num_expired = 0;
+        // all expired goals are expired at once, also those that do
+        // not fit in the array, so we find them ourselves below.
+        let ret = unsafe {
+            rcl_action_expire_goals(&self.rcl_handle, std::ptr::null_mut(), 0, &mut num_expired)
+        };
+        if ret != RCL_RET_OK as i32 {
+            self.errors
+                .report(SpinOperation::Update, Error::from_rcl_error(ret));
+            return;
+        }
+        if num_expired == 0 {
+            return;
+        }
+        // rcl has freed the handles of the expired goals.
+        let expired: Vec<uuid::Uuid> = self
+            .goals
+            .keys()
+            .filter(|uuid| !self.goal_exists(uuid))
+            .copied()
+            .collect();
+        for uuid in expired {
+            self.goals.remove(&uuid);
             self.result_msgs.remove(&uuid);
             self.result_requests.remove(&uuid);
+            self.unreachable_goals.remove(&uuid);
+            self.status_publish_failed.borrow_mut().remove(&uuid);
+            self.cancel_senders.remove(&uuid);
+            self.running_goals.remove(&uuid);
+            if let Some(m) = &self.goal_metadata {
+                m.lock().unwrap().remove(&uuid);
+            }
         }
     }
 
@@ -468,21 +1114,20 @@ where
             let mut status = rcl_action_get_zero_initialized_goal_status_array();
             let ret = rcl_action_get_goal_status_array(&self.rcl_handle, &mut status);
             if ret != RCL_RET_OK as i32 {
-                println!(
-                    "action server: failed to get goal status array: {}",
-                    Error::from_rcl_error(ret)
-                );
+                self.errors
+                    .report(SpinOperation::Update, Error::from_rcl_error(ret));
                 return;
             }
             let ret = rcl_action_publish_status(
                 &self.rcl_handle,
                 &status as *const _ as *const std::os::raw::c_void,
             );
+            let mut status_publish_failed = self.status_publish_failed.borrow_mut();
+            status_publish_failed.clear();
             if ret != RCL_RET_OK as i32 {
-                println!(
-                    "action server: failed to publish status: {}",
-                    Error::from_rcl_error(ret)
-                );
+                status_publish_failed.extend(self.goals.keys().cloned());
+                self.errors
+                    .report(SpinOperation::Send, Error::from_rcl_error(ret));
                 return;
             }
             rcl_action_goal_status_array_fini(&mut status);
@@ -498,94 +1143,175 @@ where
     }
 
     // bit of a hack...
-    fn add_result(&mut self, uuid: uuid::Uuid, mut msg: Box<dyn VoidPtr>) -> () {
+    fn add_result(&mut self, uuid: uuid::Uuid, status: GoalStatus, result: Box<dyn Any>) -> () {
+        let result = *result
+            .downcast::<T::Result>()
+            .expect("result of another action type");
         // if there are already requests for this goal, send the result immediately.
         if let Some(rr) = self.result_requests.remove(&uuid) {
+            let mut msg = Self::result_response(status, result.clone());
             for mut req in rr {
                 let ret = unsafe {
                     rcl_action_send_result_response(&self.rcl_handle, &mut req, msg.void_ptr_mut())
                 };
                 if ret != RCL_RET_OK as i32 {
-                    println!(
-                        "action server: could send result request response. {}",
-                        Error::from_rcl_error(ret)
-                    );
+                    self.unreachable_goals.insert(uuid);
+                    self.errors
+                        .report(SpinOperation::Send, Error::from_rcl_error(ret));
                 }
             }
         }
-        self.result_msgs.insert(uuid, msg);
+        if let Some(m) = &self.goal_metadata {
+            m.lock().unwrap().remove(&uuid);
+        }
+        self.release_goal_slot(&uuid);
+        self.result_msgs.insert(uuid, (status, result));
     }
 
-    fn handle_result_request(&mut self) -> () {
-        let mut request_id = MaybeUninit::<rmw_request_id_t>::uninit();
-        let mut request_msg = WrappedNativeMsg::<
-            <<T as WrappedActionTypeSupport>::GetResult as WrappedServiceTypeSupport>::Request,
-        >::new();
+    fn start_queued_goal(&mut self, uuid: &uuid::Uuid) -> Result<()> {
+        let handle = self
+            .goals
+            .get(uuid)
+            .ok_or(Error::RCL_RET_ACTION_GOAL_HANDLE_INVALID)?;
         let ret = unsafe {
-            rcl_action_take_result_request(
-                &self.rcl_handle,
-                request_id.as_mut_ptr(),
-                request_msg.void_ptr_mut(),
-            )
+            rcl_action_update_goal_state(*handle, rcl_action_goal_event_t::GOAL_EVENT_EXECUTE)
         };
-
         if ret != RCL_RET_OK as i32 {
-            // this seems normal if client dies.
-            return;
+            return Err(Error::from_rcl_error(ret));
         }
+        self.publish_status();
+        Ok(())
+    }
 
-        let msg = <<<T as WrappedActionTypeSupport>::GetResult as WrappedServiceTypeSupport>::Request>::from_native(&request_msg);
-        let goal_info = action_msgs::msg::GoalInfo {
-            goal_id: T::destructure_result_request_msg(msg),
-            ..action_msgs::msg::GoalInfo::default()
+    fn promote_queued_goals(&mut self, server: Arc<Mutex<dyn ActionServer_>>) {
+        let max_executing = match &self.goal_queue {
+            Some(queue) => queue.max_executing.max(1),
+            None => return,
         };
-        let goal_info_native = WrappedNativeMsg::<action_msgs::msg::GoalInfo>::from(&goal_info);
+        while self.running_goals.len() < max_executing {
+            let queued = match self.queued_goals.pop_front() {
+                Some(queued) => queued,
+                None => break,
+            };
+            self.running_goals.insert(queued.uuid);
+            let gr: ActionServerGoalRequest<T> = ActionServerGoalRequest {
+                uuid: queued.uuid.into(),
+                goal: queued.goal,
+                cancel_requests: queued.cancel_requests,
+                response: PendingGoalResponse::new(queued.uuid, Arc::downgrade(&server), None),
+            };
+            if let Err(e) = self.goal_request_sender.try_send(gr) {
+                self.undeliverable_goal_request(e);
+            }
+        }
+    }
 
-        // does this goal exist?
-        let goal_exists =
-            unsafe { rcl_action_server_goal_exists(&self.rcl_handle, &*goal_info_native) };
-
-        let uuid = uuid_msg_to_uuid(&goal_info.goal_id);
-
-        let response_msg = if !goal_exists {
-            // Goal does not exists
-            println!("goal does not exist :(");
-            let status = GoalStatus::Unknown;
-            let msg = T::make_result_response_msg(status.to_rcl(), T::Result::default());
-            let mut response_msg = WrappedNativeMsg::<
-                <<T as WrappedActionTypeSupport>::GetResult as WrappedServiceTypeSupport>::Response,
-            >::from(&msg);
-            Some(response_msg.void_ptr_mut())
-        } else {
-            self.result_msgs
-                .get_mut(&uuid)
-                .map(|msg| msg.void_ptr_mut())
-        };
+    fn release_goal_slot(&mut self, uuid: &uuid::Uuid) {
+        self.running_goals.remove(uuid);
+    }
 
-        let mut request_id = unsafe { request_id.assume_init() };
-        if let Some(response_msg) = response_msg {
-            let ret = unsafe {
-                rcl_action_send_result_response(&self.rcl_handle, &mut request_id, response_msg)
-            };
+    fn action_name(&self) -> &str {
+        &self.action_name
+    }
 
-            if ret != RCL_RET_OK as i32 {
-                println!(
-                    "action server: could send result request response. {}",
-                    Error::from_rcl_error(ret)
-                );
-                return;
-            }
-        } else {
-            // keep request for later when result comes in
-            // todo: add logic that replies to the requests
-            self.result_requests
-                .entry(uuid)
-                .or_insert(vec![])
-                .push(request_id);
+    fn is_dropped(&self) -> bool {
+        self.goal_request_sender.is_closed()
+    }
+
+    fn goal_status(&self, uuid: &uuid::Uuid) -> Option<GoalStatus> {
+        let handle = self.goals.get(uuid)?;
+        let mut state = 0u8;
+        let ret = unsafe { rcl_action_goal_handle_get_status(*handle, &mut state) };
+        if ret != RCL_RET_OK as i32 {
+            return None;
         }
+        Some(GoalStatus::from_rcl(state as i8))
+    }
+
+    fn abort_goal(&mut self, uuid: &uuid::Uuid) -> Result<()> {
+        let status = self
+            .goal_status(uuid)
+            .ok_or(Error::RCL_RET_ACTION_GOAL_HANDLE_INVALID)?;
+        if let Some(idx) = self.queued_goals.iter().position(|q| &q.uuid == uuid) {
+            // never handed out, so nobody else will finish it.
+            self.queued_goals.remove(idx);
+        }
+        self.abort_goal_handle(uuid, status)?;
+        unsafe {
+            rcl_action_notify_goal_done(&self.rcl_handle);
+        }
+        self.publish_status();
+        Ok(())
+    }
+
+    fn active_goals(&self) -> Vec<(GoalId, GoalStatus)> {
+        let queued: Vec<uuid::Uuid> = self.queued_goals.iter().map(|q| q.uuid).collect();
+        let mut active: Vec<(GoalId, GoalStatus)> = self
+            .goals
+            .keys()
+            .filter(|uuid| !queued.contains(uuid))
+            .filter_map(|uuid| self.goal_status(uuid).map(|s| (GoalId::from(*uuid), s)))
+            .filter(|(_, status)| {
+                matches!(
+                    status,
+                    GoalStatus::Accepted | GoalStatus::Executing | GoalStatus::Canceling
+                )
+            })
+            .collect();
+        active.extend(queued.iter().map(|uuid| (GoalId::from(*uuid), GoalStatus::Accepted)));
+        active
+    }
+
+    fn client_reachable(&self, uuid: &uuid::Uuid) -> bool {
+        !self.unreachable_goals.contains(uuid)
+            && !self.status_publish_failed.borrow().contains(uuid)
+            && self.missing_client_checks < MISSING_CLIENT_CHECKS
+    }
+
+    fn mark_unreachable(&mut self, uuid: &uuid::Uuid) {
+        self.unreachable_goals.insert(*uuid);
+    }
+
+    fn check_clients(&mut self, node: &rcl_node_t) {
+        if self.goals.is_empty() {
+            self.missing_client_checks = 0;
+            return;
+        }
+        // every action client subscribes to the feedback.
+        let count = resolve_topic_name(node, &format!("{}/_action/feedback", self.action_name))
+            .and_then(|topic| {
+                let topic = CString::new(topic).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
+                let mut count = 0;
+                let ret = unsafe { rcl_count_subscribers(node, topic.as_ptr(), &mut count) };
+                if ret == RCL_RET_OK as i32 {
+                    Ok(count)
+                } else {
+                    Err(Error::from_rcl_error(ret))
+                }
+            });
+        match count {
+            Ok(0) => self.missing_client_checks += 1,
+            Ok(_) => self.missing_client_checks = 0,
+            Err(e) => self.errors.report(SpinOperation::Update, e),
+        }
+    }
+
+    fn handle_result_request(&mut self) -> () {
+        self.serve_result_request();
     }
 
     fn destroy(&mut self, node: &mut rcl_node_t) {
+        // otherwise the clients of the unfinished goals wait forever.
+        let mut waiting = self.abort_unfinished_goals();
+        let deadline = Instant::now() + self.shutdown_timeout;
+        while !waiting.is_empty() && Instant::now() < deadline {
+            match self.serve_result_request() {
+                Some(uuid) => {
+                    waiting.remove(&uuid);
+                }
+                None => std::thread::sleep(Duration::from_millis(1)),
+            }
+        }
         unsafe {
             rcl_action_server_fini(&mut self.rcl_handle, node);
             rcl_ros_clock_fini(self.clock_handle.as_mut());
@@ -593,15 +1319,55 @@ where
     }
 }
 
+/// Throttling of the feedback of a goal, see
+/// `ActionServerGoal::publish_feedback_throttled`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeedbackThrottle {
+    /// Skip feedback published less than this long after the last
+    /// feedback that was sent.
+    pub interval: Duration,
+    /// Publish the last skipped feedback, if any, before the goal
+    /// reaches a terminal state, so that the client sees the final
+    /// progress.
+    pub publish_last: bool,
+}
+
+impl FeedbackThrottle {
+    pub fn new(interval: Duration) -> Self {
+        FeedbackThrottle {
+            interval,
+            publish_last: true,
+        }
+    }
+}
+
+// Shared by the clones of a goal handle.
+struct FeedbackState<F> {
+    throttle: Option<FeedbackThrottle>,
+    last_published: Option<Instant>,
+    skipped: Option<F>,
+}
+
+impl<F> Default for FeedbackState<F> {
+    fn default() -> Self {
+        FeedbackState {
+            throttle: None,
+            last_published: None,
+            skipped: None,
+        }
+    }
+}
+
 /// A handle to an active `Goal`
 #[derive(Clone)]
 pub struct ActionServerGoal<T>
 where
     T: WrappedActionTypeSupport,
 {
-    pub uuid: uuid::Uuid,
+    pub uuid: GoalId,
     pub goal: T::Goal,
     server: Weak<Mutex<dyn ActionServer_>>,
+    feedback: Arc<Mutex<FeedbackState<T::Feedback>>>,
 }
 
 unsafe impl<T> Send for ActionServerGoal<T> where T: WrappedActionTypeSupport {}
@@ -610,6 +1376,18 @@ impl<T: 'static> ActionServerGoal<T>
 where
     T: WrappedActionTypeSupport,
 {
+    /// Returns the metadata sent by the client for this goal, if any.
+    ///
+    /// This is only available if the server was created with
+    /// `goal_metadata` enabled, and the client is an r2r client that
+    /// sent metadata. The metadata is removed when the goal reaches
+    /// a terminal state.
+    pub fn metadata(&self) -> Option<GoalMetadata> {
+        let action_server = self.server.upgrade()?;
+        let action_server = action_server.lock().unwrap();
+        action_server.goal_metadata(&self.uuid)
+    }
+
     pub fn is_cancelling(&self) -> Result<bool> {
         let action_server = self
             .server
@@ -620,6 +1398,39 @@ where
         action_server.is_cancelling(&self.uuid)
     }
 
+    /// The current status of the goal, e.g. `Aborted` after
+    /// `Node::abort_goal`. `Unknown` once the goal has expired.
+    pub fn status(&self) -> Result<GoalStatus> {
+        let action_server = self
+            .server
+            .upgrade()
+            .ok_or(Error::RCL_RET_ACTION_SERVER_INVALID)?;
+
+        let action_server = action_server.lock().unwrap();
+        Ok(action_server
+            .goal_status(&self.uuid)
+            .unwrap_or(GoalStatus::Unknown))
+    }
+
+    /// Returns false if the client of this goal seems to be gone,
+    /// because sending it a response, status or feedback failed, or
+    /// because no action client has been seen in the ROS graph for a
+    /// while. The node has to be spinning for the latter.
+    ///
+    /// This is a heuristic: with several clients of the action, the
+    /// client of this goal can be gone while others are still around.
+    pub fn client_reachable(&self) -> bool {
+        match self.server.upgrade() {
+            Some(server) => server.lock().unwrap().client_reachable(&self.uuid),
+            None => false,
+        }
+    }
+
+    /// Publishes feedback for the goal.
+    ///
+    /// Returns `Error::GoalClientUnreachable` if the feedback was
+    /// published but the client seems to be gone (see
+    /// `client_reachable`), so that the goal can be stopped early.
     pub fn publish_feedback(&self, msg: T::Feedback) -> Result<()>
     where
         T: WrappedActionTypeSupport,
@@ -629,26 +1440,106 @@ where
             .server
             .upgrade()
             .ok_or(Error::RCL_RET_ACTION_SERVER_INVALID)?;
+        let mut action_server = action_server.lock().unwrap();
 
-        let uuid_msg = unique_identifier_msgs::msg::UUID {
-            uuid: self.uuid.as_bytes().to_vec(),
-        };
+        Self::send_feedback(&mut *action_server, &self.uuid, msg)?;
+        self.feedback.lock().unwrap().last_published = Some(Instant::now());
+        Self::check_reachable(&*action_server, &self.uuid)
+    }
+
+    /// Throttle the feedback published with `publish_feedback_throttled`.
+    /// The throttle is shared by the clones of this handle.
+    pub fn set_feedback_throttle(&self, throttle: Option<FeedbackThrottle>) {
+        let mut feedback = self.feedback.lock().unwrap();
+        feedback.throttle = throttle;
+        if throttle.map_or(true, |t| !t.publish_last) {
+            feedback.skipped = None;
+        }
+    }
+
+    /// Like `publish_feedback`, but skips `msg` when the last feedback
+    /// of the goal was published less than the interval set with
+    /// `set_feedback_throttle` ago. Returns whether `msg` was
+    /// published.
+    ///
+    /// ```ignore
+    /// goal.set_feedback_throttle(Some(FeedbackThrottle::new(Duration::from_millis(100))));
+    /// loop {
+    ///     // at 1 kHz, but the client gets feedback at 10 Hz.
+    ///     goal.publish_feedback_throttled(control_step())?;
+    /// }
+    /// ```
+    pub fn publish_feedback_throttled(&self, msg: T::Feedback) -> Result<bool> {
+        {
+            let mut feedback = self.feedback.lock().unwrap();
+            if let (Some(throttle), Some(last)) = (feedback.throttle, feedback.last_published) {
+                if last.elapsed() < throttle.interval {
+                    if throttle.publish_last {
+                        feedback.skipped = Some(msg);
+                    }
+                    return Ok(false);
+                }
+            }
+            feedback.skipped = None;
+        }
+        self.publish_feedback(msg)?;
+        Ok(true)
+    }
+
+    fn send_feedback(
+        action_server: &mut dyn ActionServer_,
+        uuid: &uuid::Uuid,
+        msg: T::Feedback,
+    ) -> Result<()> {
+        let uuid_msg = GoalId::from(*uuid).to_msg();
         let feedback_msg = T::make_feedback_msg(uuid_msg, msg);
         let mut native_msg = WrappedNativeMsg::<T::FeedbackMessage>::from(&feedback_msg);
         let ret = unsafe {
-            rcl_action_publish_feedback(
-                action_server.lock().unwrap().handle(),
-                native_msg.void_ptr_mut(),
-            )
+            rcl_action_publish_feedback(action_server.handle(), native_msg.void_ptr_mut())
         };
 
         if ret != RCL_RET_OK as i32 {
-            eprintln!("coult not publish {}", Error::from_rcl_error(ret));
+            action_server.mark_unreachable(uuid);
+            return Err(Error::from_rcl_error(ret));
+        }
+        Ok(())
+    }
+
+    // Publishes the feedback the throttle skipped last, before the
+    // goal reaches a terminal state.
+    fn flush_feedback(&self, action_server: &mut dyn ActionServer_) {
+        let skipped = self.feedback.lock().unwrap().skipped.take();
+        if let Some(msg) = skipped {
+            // the goal finishes regardless, unreachable clients show
+            // in client_reachable.
+            let _ = Self::send_feedback(action_server, &self.uuid, msg);
+        }
+    }
+
+    fn check_reachable(action_server: &dyn ActionServer_, uuid: &uuid::Uuid) -> Result<()> {
+        if action_server.client_reachable(uuid) {
+            Ok(())
+        } else {
+            Err(Error::GoalClientUnreachable {
+                goal: uuid.to_string(),
+            })
         }
-        Ok(()) // todo: error codes
     }
 
+    /// Deprecated alias of `canceled`.
+    #[deprecated(note = "use `canceled` instead")]
     pub fn cancel(&mut self, msg: T::Result) -> Result<()> {
+        self.canceled(msg)
+    }
+
+    /// Marks the goal as canceled and sends `msg` as its result.
+    ///
+    /// The result can contain whatever was completed before the goal
+    /// was canceled. Clients receive it together with the `Canceled`
+    /// status. If the goal is not already being canceled (i.e. a
+    /// cancel request was not accepted), it is moved to `Canceling`
+    /// first.
+    pub fn canceled(&mut self, msg: T::Result) -> Result<()> {
         // upgrade to actual ref. if still alive
         let action_server = self
             .server
@@ -656,42 +1547,21 @@ where
             .ok_or(Error::RCL_RET_ACTION_SERVER_INVALID)?;
         let mut action_server = action_server.lock().unwrap();
 
-        // todo: check that the goal exists
-        let goal_info = action_msgs::msg::GoalInfo {
-            goal_id: unique_identifier_msgs::msg::UUID {
-                uuid: self.uuid.as_bytes().to_vec(),
-            },
-            ..action_msgs::msg::GoalInfo::default()
-        };
-        let goal_info_native = WrappedNativeMsg::<action_msgs::msg::GoalInfo>::from(&goal_info);
-
-        // does this goal exist?
-        let goal_exists =
-            unsafe { rcl_action_server_goal_exists(action_server.handle(), &*goal_info_native) };
-
-        if !goal_exists {
-            println!("tried to publish result without a goal");
-            return Err(Error::RCL_RET_ACTION_GOAL_HANDLE_INVALID);
-        }
-
-        // todo: error handling
-        unsafe {
-            rcl_action_notify_goal_done(action_server.handle());
+        if !action_server.is_cancelling(&self.uuid)? {
+            action_server.cancel_goal(&self.uuid);
         }
 
-        // send out updated statues
-        action_server.publish_status();
-
-        // create result message
-        let result_msg = T::make_result_response_msg(5, msg); // todo: int8 STATUS_CANCELED  = 5
-        let native_msg = WrappedNativeMsg::<
-            <<T as WrappedActionTypeSupport>::GetResult as WrappedServiceTypeSupport>::Response,
-        >::from(&result_msg);
-        action_server.add_result(self.uuid.clone(), Box::new(native_msg));
-
-        Ok(())
+        self.flush_feedback(&mut *action_server);
+        Self::finish(
+            &mut *action_server,
+            &self.uuid,
+            rcl_action_goal_event_t::GOAL_EVENT_CANCELED,
+            GoalStatus::Canceled,
+            msg,
+        )
     }
 
+    /// Marks the goal as aborted and sends `msg` as its result.
     pub fn abort(&mut self, msg: T::Result) -> Result<()> {
         // upgrade to actual ref. if still alive
         let action_server = self
@@ -700,18 +1570,20 @@ where
             .ok_or(Error::RCL_RET_ACTION_SERVER_INVALID)?;
         let mut action_server = action_server.lock().unwrap();
 
-        action_server.set_goal_state(&self.uuid, rcl_action_goal_event_t::GOAL_EVENT_ABORT)?;
-
-        // create result message
-        let result_msg = T::make_result_response_msg(6, msg); // todo: int8 STATUS_ABORTED   = 6
-        let native_msg = WrappedNativeMsg::<
-            <<T as WrappedActionTypeSupport>::GetResult as WrappedServiceTypeSupport>::Response,
-        >::from(&result_msg);
-        action_server.add_result(self.uuid.clone(), Box::new(native_msg));
-
-        Ok(())
+        self.flush_feedback(&mut *action_server);
+        Self::finish(
+            &mut *action_server,
+            &self.uuid,
+            rcl_action_goal_event_t::GOAL_EVENT_ABORT,
+            GoalStatus::Aborted,
+            msg,
+        )
     }
 
+    /// Marks the goal as succeeded and sends `msg` as its result.
+    ///
+    /// The result is kept for the client also when it seems to be gone,
+    /// use `client_reachable` to tell whether it will probably see it.
     pub fn succeed(&mut self, msg: T::Result) -> Result<()>
     where
         T: WrappedActionTypeSupport,
@@ -723,31 +1595,299 @@ where
             .ok_or(Error::RCL_RET_ACTION_SERVER_INVALID)?;
         let mut action_server = action_server.lock().unwrap();
 
-        action_server.set_goal_state(&self.uuid, rcl_action_goal_event_t::GOAL_EVENT_SUCCEED)?;
-
-        // create result message
-        let result_msg = T::make_result_response_msg(4, msg); // todo: int8 STATUS_SUCCEEDED = 4
-        let native_msg = WrappedNativeMsg::<
-            <<T as WrappedActionTypeSupport>::GetResult as WrappedServiceTypeSupport>::Response,
-        >::from(&result_msg);
-        action_server.add_result(self.uuid.clone(), Box::new(native_msg));
+        self.flush_feedback(&mut *action_server);
+        Self::finish(
+            &mut *action_server,
+            &self.uuid,
+            rcl_action_goal_event_t::GOAL_EVENT_SUCCEED,
+            GoalStatus::Succeeded,
+            msg,
+        )
+    }
 
+    // Moves the goal to a terminal state and stores the result
+    // together with the status that produced it.
+    fn finish(
+        action_server: &mut dyn ActionServer_,
+        uuid: &uuid::Uuid,
+        event: rcl_action_goal_event_t,
+        status: GoalStatus,
+        msg: T::Result,
+    ) -> Result<()> {
+        action_server.set_goal_state(uuid, event)?;
+        action_server.add_result(uuid.clone(), status, Box::new(msg));
         Ok(())
     }
 }
 
+// Receives goal metadata for an action server.
+pub struct GoalMetadataSubscriber {
+    pub rcl_handle: rcl_subscription_t,
+    pub metadata: Weak<Mutex<HashMap<uuid::Uuid, GoalMetadata>>>,
+    pub errors: EntityErrors,
+}
+
+impl Subscriber_ for GoalMetadataSubscriber {
+    fn handle(&self) -> &rcl_subscription_t {
+        &self.rcl_handle
+    }
+
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    fn is_dropped(&self) -> bool {
+        // the action server is gone.
+        self.metadata.strong_count() == 0
+    }
+
+    fn handle_incoming(&mut self) -> bool {
+        let mut msg_info = rmw_message_info_t::default(); // we dont care for now
+        let mut msg = match WrappedNativeMsgUntyped::new_from(GOAL_METADATA_MSG_TYPE) {
+            Ok(msg) => msg,
+            Err(_) => return true,
+        };
+        let ret = unsafe {
+            rcl_take(
+                &self.rcl_handle,
+                msg.void_ptr_mut(),
+                &mut msg_info,
+                std::ptr::null_mut(),
+            )
+        };
+        if ret == RCL_RET_OK as i32 {
+            let metadata = match self.metadata.upgrade() {
+                Some(metadata) => metadata,
+                None => return true,
+            };
+            match msg.to_json().and_then(|json| GoalMetadata::from_msg_json(&json)) {
+                Ok(m) => {
+                    metadata.lock().unwrap().insert(m.goal_id.into(), m);
+                }
+                Err(e) => self.errors.report(SpinOperation::Convert, e),
+            }
+        }
+        false
+    }
+
+    fn recreate(&mut self, node: &mut rcl_node_t) -> Result<()> {
+        let msg = WrappedNativeMsgUntyped::new_from(GOAL_METADATA_MSG_TYPE)?;
+        recreate_subscription_helper(&mut self.rcl_handle, node, msg.ts)
+    }
+
+    fn destroy(&mut self, node: &mut rcl_node_t) {
+        unsafe {
+            rcl_subscription_fini(&mut self.rcl_handle, node);
+        }
+    }
+}
+
+/// What to do with a goal request, see `ActionServerBuilder::on_goal`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GoalDecision {
+    Accept,
+    Reject,
+}
+
+type GoalHandler<G> = Arc<dyn Fn(G) -> BoxFuture<'static, GoalDecision> + Send + Sync>;
+type ExecuteHandler<T, R> =
+    Arc<dyn Fn(ActionServerGoal<T>) -> BoxFuture<'static, Result<R>> + Send + Sync>;
+
+/// Creates an action server with all of its handlers in one place.
+///
+/// ```ignore
+/// let server = node
+///     .action_server_builder::<Fibonacci::Action>("/fibonacci")
+///     .on_goal(|goal| async move {
+///         if goal.order < 100 { GoalDecision::Accept } else { GoalDecision::Reject }
+///     })
+///     .on_execute(|goal| async move {
+///         Ok(Fibonacci::Result { sequence: vec![0, 1, 1] })
+///     })
+///     .build()?;
+/// spawner.spawn_local(server)?;
+/// ```
+///
+/// `build` returns a future that handles the goal requests and needs
+/// to be spawned on an executor, while the node is spun as usual.
+/// `on_execute` is required. Without `on_goal` all goals are accepted
+/// and without `on_cancel` all cancel requests are.
+pub struct ActionServerBuilder<'a, T>
+where
+    T: WrappedActionTypeSupport,
+{
+    node: &'a mut Node,
+    action_name: String,
+    options: ActionServerOptions,
+    on_goal: Option<GoalHandler<T::Goal>>,
+    on_execute: Option<ExecuteHandler<T, T::Result>>,
+}
+
+impl<'a, T: 'static> ActionServerBuilder<'a, T>
+where
+    T: WrappedActionTypeSupport,
+{
+    pub(crate) fn new(node: &'a mut Node, action_name: &str) -> Self {
+        ActionServerBuilder {
+            node,
+            action_name: action_name.to_owned(),
+            options: ActionServerOptions::default(),
+            on_goal: None,
+            on_execute: None,
+        }
+    }
+
+    /// Decide whether to accept a goal request. The client gets its
+    /// response when the returned future completes, so the decision
+    /// can wait for e.g. a service call. The goal is rejected if the
+    /// future is dropped before that.
+    pub fn on_goal<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(T::Goal) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = GoalDecision> + Send + 'static,
+    {
+        self.on_goal = Some(Arc::new(move |goal| handler(goal).boxed()));
+        self
+    }
+
+    /// Decide whether a goal may be canceled, see `CancelCallback`.
+    pub fn on_cancel<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(GoalId) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = CancelDecision> + Send + 'static,
+    {
+        self.options.cancel_callback = Some(CancelCallback::new(handler));
+        self
+    }
+
+    /// Execute an accepted goal. The handle can be used to publish
+    /// feedback and to see whether the goal is being canceled.
+    ///
+    /// When the handler returns `Ok(result)` the goal succeeds with
+    /// `result`, or is canceled with it if it is being canceled. When
+    /// it returns an error the goal is aborted. Nothing is done if the
+    /// handler has already finished the goal itself.
+    pub fn on_execute<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(ActionServerGoal<T>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<T::Result>> + Send + 'static,
+    {
+        self.on_execute = Some(Arc::new(move |goal| handler(goal).boxed()));
+        self
+    }
+
+    /// Limit the number of goals executing at the same time.
+    pub fn goal_policy(mut self, queue: GoalQueue) -> Self {
+        self.options.goal_queue = Some(queue);
+        self
+    }
+
+    /// See `ActionServerOptions::result_timeout`.
+    pub fn result_timeout(mut self, timeout: Duration) -> Self {
+        self.options.result_timeout = Some(timeout);
+        self
+    }
+
+    /// See `ActionServerOptions::shutdown_timeout`.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.options.shutdown_timeout = Some(timeout);
+        self
+    }
+
+    /// See `ActionServerOptions::debug_period`.
+    pub fn debug_period(mut self, period: Duration) -> Self {
+        self.options.debug_period = Some(period);
+        self
+    }
+
+    /// See `ActionServerOptions::qos`.
+    pub fn qos(mut self, qos: QosProfile) -> Self {
+        self.options.qos = Some(qos);
+        self
+    }
+
+    /// See `ActionServerOptions::goal_metadata`.
+    pub fn goal_metadata(mut self, enable: bool) -> Self {
+        self.options.goal_metadata = enable;
+        self
+    }
+
+    /// Create the action server. The returned future handles its goal
+    /// requests until the node is dropped.
+    pub fn build(self) -> Result<impl Future<Output = ()> + Send> {
+        let invalid = |reason: &str| Error::InvalidActionServer {
+            reason: format!("{}: {}", self.action_name, reason),
+        };
+        let on_execute = self
+            .on_execute
+            .clone()
+            .ok_or_else(|| invalid("no on_execute handler"))?;
+        if self.options.result_timeout == Some(Duration::from_secs(0)) {
+            return Err(invalid("the result timeout must be larger than zero"));
+        }
+        let on_goal = self.on_goal.clone();
+        let mut options = self.options;
+        if options.cancel_callback.is_none() {
+            options.cancel_callback = Some(CancelCallback::new(|_| {
+                futures::future::ready(CancelDecision::Accept)
+            }));
+        }
+        let requests = self
+            .node
+            .create_action_server_with_options::<T>(&self.action_name, options)?;
+        Ok(requests.for_each_concurrent(None, move |req| {
+            let on_goal = on_goal.clone();
+            let on_execute = on_execute.clone();
+            async move {
+                let decision = match &on_goal {
+                    Some(on_goal) => on_goal(req.goal.clone()).await,
+                    None => GoalDecision::Accept,
+                };
+                if decision == GoalDecision::Reject {
+                    // fails only when the node is gone.
+                    let _ = req.reject();
+                    return;
+                }
+                let (mut goal, _cancel_requests) = match req.accept() {
+                    Ok(accepted) => accepted,
+                    Err(_) => return,
+                };
+                let outcome = on_execute(goal.clone()).await;
+                // errors mean that the handler has finished the goal
+                // already or that the node is gone.
+                let _ = match outcome {
+                    Ok(result) if goal.is_cancelling().unwrap_or(false) => goal.canceled(result),
+                    Ok(result) => goal.succeed(result),
+                    Err(_) => goal.abort(T::Result::default()),
+                };
+            }
+        }))
+    }
+}
+
 pub fn create_action_server_helper(
     node: &mut rcl_node_t,
     action_name: &str,
     clock_handle: *mut rcl_clock_t,
     action_ts: *const rosidl_action_type_support_t,
+    options: &ActionServerOptions,
 ) -> Result<rcl_action_server_t> {
     let mut server_handle = unsafe { rcl_action_get_zero_initialized_server() };
     let action_name_c_string =
         CString::new(action_name).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
 
-    let result = unsafe {
-        let server_options = rcl_action_server_get_default_options();
+    rcl_call(|| unsafe {
+        let mut server_options = rcl_action_server_get_default_options();
+        if let Some(qos) = &options.qos {
+            let qos = rmw_qos_profile_t::from(qos);
+            server_options.goal_service_qos = qos;
+            server_options.cancel_service_qos = qos;
+            server_options.result_service_qos = qos;
+            server_options.feedback_topic_qos = qos;
+        }
+        if let Some(timeout) = options.result_timeout {
+            server_options.result_timeout.nanoseconds = timeout.as_nanos() as i64;
+        }
 
         rcl_action_server_init(
             &mut server_handle,
@@ -757,12 +1897,8 @@ pub fn create_action_server_helper(
             action_name_c_string.as_ptr(),
             &server_options,
         )
-    };
-    if result == RCL_RET_OK as i32 {
-        Ok(server_handle)
-    } else {
-        Err(Error::from_rcl_error(result))
-    }
+    })?;
+    Ok(server_handle)
 }
 
 pub fn action_server_get_num_waits(
@@ -0,0 +1,381 @@
+use std::ffi::{CStr, CString};
+use std::fmt;
+
+use crate::error::*;
+use r2r_rcl::*;
+
+/// A remap rule given on the command line, e.g. `-r talker:chatter:=news`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemapRule {
+    /// The node the rule is limited to, if any.
+    pub node: Option<String>,
+    /// The name to match, or `__node`/`__ns` when renaming the node.
+    pub from: String,
+    pub to: String,
+}
+
+impl RemapRule {
+    fn parse(rule: &str) -> Option<RemapRule> {
+        let (lhs, to) = rule.split_once(":=")?;
+        // a '/' or '~' can only start a name, so a ':' before them
+        // separates the node name.
+        let (node, from) = match lhs.split_once(':') {
+            Some((node, from)) if !node.contains('/') && !node.contains('~') => {
+                (Some(node.to_owned()), from)
+            }
+            _ => (None, lhs),
+        };
+        if from.is_empty() || to.is_empty() {
+            return None;
+        }
+        Some(RemapRule {
+            node,
+            from: from.to_owned(),
+            to: to.to_owned(),
+        })
+    }
+
+    /// Does the rule apply to the node with the given (unqualified) name.
+    pub fn applies_to_node(&self, name: &str) -> bool {
+        self.node.as_deref().map(|n| n == name).unwrap_or(true)
+    }
+
+    /// Does the rule rename the node or its namespace rather than a
+    /// topic or service.
+    pub fn is_node_rule(&self) -> bool {
+        self.from == "__node" || self.from == "__name" || self.from == "__ns"
+    }
+}
+
+impl fmt::Display for RemapRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(node) = &self.node {
+            write!(f, "{}:", node)?;
+        }
+        write!(f, "{}:={}", self.from, self.to)
+    }
+}
+
+/// The command line arguments of a context as parsed by rcl.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RosArguments {
+    /// All arguments given to rcl, including the program name.
+    pub args: Vec<String>,
+    /// Arguments that are not for ROS.
+    pub unparsed: Vec<String>,
+    /// Arguments inside `--ros-args` that rcl did not understand.
+    pub unparsed_ros: Vec<String>,
+    /// Remap rules in the order they are applied, including rules
+    /// that match nothing.
+    pub remap_rules: Vec<RemapRule>,
+    /// Parameter files given with `--params-file`.
+    pub param_files: Vec<String>,
+}
+
+/// How a name given to a node became the name used in the graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolutionTrace {
+    /// The name as given.
+    pub input: String,
+    /// The name after expanding `~` and `{node}`/`{ns}` substitutions
+    /// and prefixing relative names with the namespace.
+    pub expanded: String,
+    /// The first remap rule that matched the expanded name.
+    pub rule: Option<RemapRule>,
+    /// Whether rcl remapped the name, even if the rule that did it
+    /// could not be identified.
+    pub remapped: bool,
+    /// The name used in the graph.
+    pub resolved: String,
+}
+
+impl fmt::Display for ResolutionTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "input:    {}", self.input)?;
+        writeln!(f, "expanded: {}", self.expanded)?;
+        match (&self.rule, self.remapped) {
+            (Some(rule), _) => writeln!(f, "remapped: {} (rule {})", self.resolved, rule)?,
+            (None, true) => writeln!(f, "remapped: {}", self.resolved)?,
+            (None, false) => writeln!(f, "remapped: no rule matched")?,
+        }
+        write!(f, "resolved: {}", self.resolved)
+    }
+}
+
+// The arguments inside `--ros-args ... [--]` sections.
+fn ros_sections(args: &[String]) -> Vec<&str> {
+    let mut in_ros_args = false;
+    let mut ros_args = vec![];
+    for arg in args.iter().skip(1) {
+        match arg.as_str() {
+            "--ros-args" => in_ros_args = true,
+            "--" if in_ros_args => in_ros_args = false,
+            a if in_ros_args => ros_args.push(a),
+            _ => {}
+        }
+    }
+    ros_args
+}
+
+// Picks out the remap rules the same way rcl does.
+pub(crate) fn remap_rules(args: &[String]) -> Vec<RemapRule> {
+    let ros_args = ros_sections(args);
+    let mut rules = vec![];
+    let mut i = 0;
+    while i < ros_args.len() {
+        match ros_args[i] {
+            "-r" | "--remap" => {
+                if let Some(rule) = ros_args.get(i + 1).and_then(|r| RemapRule::parse(r)) {
+                    rules.push(rule);
+                }
+                i += 2;
+            }
+            // flags that take a value
+            "-p" | "--param" | "--params-file" | "-e" | "--enclave" | "--log-level"
+            | "--log-config-file" => i += 2,
+            _ => i += 1,
+        }
+    }
+    rules
+}
+
+pub(crate) fn global_arguments(
+    args: &[String],
+    rcl_args: &rcl_arguments_t,
+) -> Result<RosArguments> {
+    let unparsed = unsafe {
+        let count = rcl_arguments_get_count_unparsed(rcl_args);
+        let mut indices: *mut i32 = std::ptr::null_mut();
+        let ret =
+            rcl_arguments_get_unparsed(rcl_args, rcutils_get_default_allocator(), &mut indices);
+        args_at(args, ret, indices, count)?
+    };
+    let unparsed_ros = unsafe {
+        let count = rcl_arguments_get_count_unparsed_ros(rcl_args);
+        let mut indices: *mut i32 = std::ptr::null_mut();
+        let ret =
+            rcl_arguments_get_unparsed_ros(rcl_args, rcutils_get_default_allocator(), &mut indices);
+        args_at(args, ret, indices, count)?
+    };
+    let param_files = unsafe {
+        let count = rcl_arguments_get_param_files_count(rcl_args);
+        let mut files: *mut *mut std::os::raw::c_char = std::ptr::null_mut();
+        let allocator = rcutils_get_default_allocator();
+        let ret = rcl_arguments_get_param_files(rcl_args, allocator, &mut files);
+        if ret != RCL_RET_OK as i32 {
+            return Err(Error::from_rcl_error(ret));
+        }
+        let mut res = vec![];
+        if count > 0 && !files.is_null() {
+            for f in std::slice::from_raw_parts(files, count as usize) {
+                res.push(CStr::from_ptr(*f).to_str().unwrap_or("").to_owned());
+                deallocate(&allocator, *f as *mut _);
+            }
+            deallocate(&allocator, files as *mut _);
+        }
+        res
+    };
+    Ok(RosArguments {
+        args: args.to_vec(),
+        unparsed,
+        unparsed_ros,
+        remap_rules: remap_rules(args),
+        param_files,
+    })
+}
+
+unsafe fn args_at(args: &[String], ret: i32, indices: *mut i32, count: i32) -> Result<Vec<String>> {
+    if ret != RCL_RET_OK as i32 {
+        return Err(Error::from_rcl_error(ret));
+    }
+    if count <= 0 || indices.is_null() {
+        return Ok(vec![]);
+    }
+    let res = std::slice::from_raw_parts(indices, count as usize)
+        .iter()
+        .flat_map(|i| args.get(*i as usize).cloned())
+        .collect();
+    deallocate(&rcutils_get_default_allocator(), indices as *mut _);
+    Ok(res)
+}
+
+unsafe fn deallocate(allocator: &rcutils_allocator_t, p: *mut std::os::raw::c_void) {
+    if let Some(deallocate) = allocator.deallocate {
+        deallocate(p, allocator.state);
+    }
+}
+
+// Expands a name the same way rcl does before remapping it.
+fn expand_name(node_name: &str, node_ns: &str, name: &str) -> Result<String> {
+    let name_c = CString::new(name).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
+    let node_name_c = CString::new(node_name).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
+    let node_ns_c = CString::new(node_ns).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
+    unsafe {
+        let allocator = rcutils_get_default_allocator();
+        let mut substitutions = rcutils_get_zero_initialized_string_map();
+        let ret = rcutils_string_map_init(&mut substitutions, 0, allocator);
+        if ret != RCL_RET_OK as i32 {
+            return Err(Error::from_rcl_error(ret));
+        }
+        let mut ret = rcl_get_default_topic_name_substitutions(&mut substitutions);
+        let mut expanded: *mut std::os::raw::c_char = std::ptr::null_mut();
+        if ret == RCL_RET_OK as i32 {
+            ret = rcl_expand_topic_name(
+                name_c.as_ptr(),
+                node_name_c.as_ptr(),
+                node_ns_c.as_ptr(),
+                &substitutions,
+                allocator,
+                &mut expanded,
+            );
+        }
+        rcutils_string_map_fini(&mut substitutions);
+        if ret != RCL_RET_OK as i32 {
+            return Err(Error::from_rcl_error(ret));
+        }
+        let res = CStr::from_ptr(expanded).to_str().unwrap_or("").to_owned();
+        deallocate(&allocator, expanded as *mut _);
+        Ok(res)
+    }
+}
+
+/// Expands and remaps a topic name like the node does when creating
+/// a publisher or subscription.
+pub(crate) fn resolve_topic_name(node: &rcl_node_t, name: &str) -> Result<String> {
+    let name_c = CString::new(name).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
+    unsafe {
+        let allocator = rcutils_get_default_allocator();
+        let mut output: *mut std::os::raw::c_char = std::ptr::null_mut();
+        let ret =
+            rcl_node_resolve_name(node, name_c.as_ptr(), allocator, false, false, &mut output);
+        if ret != RCL_RET_OK as i32 {
+            return Err(Error::from_rcl_error(ret));
+        }
+        let res = CStr::from_ptr(output).to_str().unwrap_or("").to_owned();
+        deallocate(&allocator, output as *mut _);
+        Ok(res)
+    }
+}
+
+pub(crate) fn describe_resolution(
+    node: &rcl_node_t,
+    global_args: &rcl_arguments_t,
+    rules: &[RemapRule],
+    name: &str,
+) -> Result<ResolutionTrace> {
+    let (node_name, node_ns) = unsafe {
+        let n = rcl_node_get_name(node);
+        let ns = rcl_node_get_namespace(node);
+        if n.is_null() || ns.is_null() {
+            return Err(Error::RCL_RET_NODE_INVALID);
+        }
+        (
+            CStr::from_ptr(n).to_str().unwrap_or("").to_owned(),
+            CStr::from_ptr(ns).to_str().unwrap_or("").to_owned(),
+        )
+    };
+    let expanded = expand_name(&node_name, &node_ns, name)?;
+
+    let expanded_c =
+        CString::new(expanded.as_str()).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
+    let node_name_c =
+        CString::new(node_name.as_str()).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
+    let node_ns_c = CString::new(node_ns.as_str()).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
+    let remapped = unsafe {
+        let allocator = rcutils_get_default_allocator();
+        let options = rcl_node_get_options(node);
+        let local_args = if options.is_null() {
+            std::ptr::null()
+        } else {
+            &(*options).arguments as *const _
+        };
+        let mut output: *mut std::os::raw::c_char = std::ptr::null_mut();
+        let ret = rcl_remap_topic_name(
+            local_args,
+            global_args,
+            expanded_c.as_ptr(),
+            node_name_c.as_ptr(),
+            node_ns_c.as_ptr(),
+            allocator,
+            &mut output,
+        );
+        if ret != RCL_RET_OK as i32 {
+            return Err(Error::from_rcl_error(ret));
+        }
+        if output.is_null() {
+            None
+        } else {
+            let res = CStr::from_ptr(output).to_str().unwrap_or("").to_owned();
+            deallocate(&allocator, output as *mut _);
+            Some(res)
+        }
+    };
+
+    // rcl does not tell which rule it used, so look for the first
+    // one that matches like rcl would.
+    let rule = if remapped.is_some() {
+        rules
+            .iter()
+            .filter(|r| !r.is_node_rule() && r.applies_to_node(&node_name))
+            .find(|r| expand_name(&node_name, &node_ns, &r.from).ok().as_ref() == Some(&expanded))
+            .cloned()
+    } else {
+        None
+    };
+
+    Ok(ResolutionTrace {
+        input: name.to_owned(),
+        resolved: remapped.clone().unwrap_or_else(|| expanded.clone()),
+        expanded,
+        rule,
+        remapped: remapped.is_some(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(a: &[&str]) -> Vec<String> {
+        a.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_remap_rules() {
+        let a = args(&[
+            "prog",
+            "-r",
+            "not:=ros",
+            "--ros-args",
+            "-p",
+            "x:=1",
+            "-r",
+            "chatter:=news",
+            "--remap",
+            "talker:/a/b:=/c",
+            "--enable-rosout-logs",
+            "-r",
+            "__node:=other",
+            "--",
+            "-r",
+            "outside:=ros",
+            "--ros-args",
+            "-r",
+            "~/x:=y",
+        ]);
+        let rules = remap_rules(&a);
+        assert_eq!(
+            rules.iter().map(|r| r.to_string()).collect::<Vec<_>>(),
+            vec![
+                "chatter:=news",
+                "talker:/a/b:=/c",
+                "__node:=other",
+                "~/x:=y"
+            ]
+        );
+        assert!(rules[1].applies_to_node("talker"));
+        assert!(!rules[1].applies_to_node("listener"));
+        assert!(rules[2].is_node_rule());
+        assert_eq!(rules[3].node, None);
+    }
+}
@@ -0,0 +1,583 @@
+//! Bonds between two nodes, e.g. a supervisor and one of its children.
+//!
+//! Both sides of a bond publish `bond/msg/Status` messages with the
+//! same bond id on a shared topic, each with the UUID of its own
+//! incarnation in `instance_id`. A side considers the bond formed once
+//! it has heard from the other side and broken when the other side
+//! stops beating for longer than the heartbeat timeout, or says it is
+//! no longer active. This follows the `bond` package of ROS, so either
+//! side can be a `bond::Bond` of bondcpp or bondpy, given that the
+//! `bond` message package is installed.
+//!
+//! Like heartbeats, a bond is driven by spinning the node it was
+//! created on and measures time on the ROS clock.
+
+use futures::channel::mpsc;
+use futures::future::FutureExt;
+use futures::stream::{self, Stream, StreamExt};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::clocks::{Clock, ClockType};
+use crate::error::*;
+use crate::error_events::{EntityErrors, SpinOperation};
+use crate::executor::EntityKind;
+use crate::nodes::{Node, Timer};
+use crate::publishers::PublisherUntyped;
+
+const BOND_MSG_TYPE: &str = "bond/msg/Status";
+
+/// The timing of a bond. The defaults are those of the `bond` package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BondOptions {
+    /// How often the status is published.
+    pub heartbeat_period: Duration,
+    /// How long the other side may be silent before the bond breaks.
+    pub heartbeat_timeout: Duration,
+    /// How long to wait for the other side before giving up on forming
+    /// the bond.
+    pub connect_timeout: Duration,
+    /// After breaking the bond, how long to wait for the other side to
+    /// acknowledge it, and how long to keep telling it that the bond is
+    /// broken.
+    pub disconnect_timeout: Duration,
+}
+
+impl Default for BondOptions {
+    fn default() -> Self {
+        BondOptions {
+            heartbeat_period: Duration::from_secs(1),
+            heartbeat_timeout: Duration::from_secs(4),
+            connect_timeout: Duration::from_secs(10),
+            disconnect_timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Where a bond is in its life.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BondState {
+    /// Waiting for the first status of the other side.
+    Forming,
+    /// Both sides are beating.
+    Active,
+    /// The bond was broken by `Bond::break_bond` and the other side
+    /// has not acknowledged it yet.
+    AwaitingPeerBreak,
+    /// The bond is over for good.
+    Broken,
+}
+
+/// Why a bond broke.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BondBreak {
+    /// The other side did not show up within the connect timeout.
+    ConnectTimeout,
+    /// The other side stopped beating for longer than the heartbeat
+    /// timeout.
+    HeartbeatTimeout,
+    /// The other side broke the bond.
+    PeerBroke,
+    /// `Bond::break_bond` was called or the `Bond` was dropped.
+    Explicit,
+}
+
+/// The changes of a bond. Each bond is formed and broken at most once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BondEvent {
+    Formed,
+    Broken(BondBreak),
+}
+
+/// One side of a bond, created by `Node::create_bond`.
+///
+/// Both sides publish their status every heartbeat period. A side that
+/// has not heard from the other for the heartbeat timeout considers
+/// the bond broken, as does one that is told so by the other side.
+/// Dropping the `Bond` breaks the bond.
+pub struct Bond {
+    shared: Arc<Mutex<BondShared>>,
+    instance_id: uuid::Uuid,
+}
+
+impl Bond {
+    /// Start forming the bond `id` on `topic`. Fails if the type
+    /// support of `bond/msg/Status` cannot be found.
+    pub(crate) fn create(
+        node: &mut Node,
+        topic: &str,
+        id: &str,
+        options: BondOptions,
+    ) -> Result<Bond> {
+        let zero = Duration::from_secs(0);
+        if id.is_empty()
+            || options.heartbeat_period == zero
+            || options.heartbeat_timeout == zero
+            || options.connect_timeout == zero
+            || options.disconnect_timeout == zero
+        {
+            return Err(Error::RCL_RET_INVALID_ARGUMENT);
+        }
+        let publisher = node.create_publisher_untyped(topic, BOND_MSG_TYPE)?;
+        let statuses = node.subscribe_untyped(topic, BOND_MSG_TYPE)?;
+        // wake up often enough to beat and to notice timeouts in time.
+        let resolution = options
+            .heartbeat_period
+            .min(options.heartbeat_timeout)
+            .min(options.disconnect_timeout)
+            / 4;
+        let timer = node.create_wall_timer(resolution.max(Duration::from_millis(1)))?;
+        let mut clock = Clock::create(ClockType::RosTime)?;
+        let now = clock.get_now()?;
+        let instance_id = uuid::Uuid::new_v4();
+        let shared = Arc::new(Mutex::new(BondShared {
+            tracker: BondTracker::new(options, now),
+            history: vec![],
+            senders: vec![],
+        }));
+        node.add_bond(Bond_ {
+            shared: shared.clone(),
+            id: id.to_owned(),
+            instance_id: instance_id.to_string(),
+            options,
+            publisher,
+            statuses: Box::new(statuses),
+            timer,
+            clock,
+            errors: node.entity_errors(EntityKind::Publisher, topic),
+        });
+        Ok(Bond {
+            shared,
+            instance_id,
+        })
+    }
+
+    pub fn state(&self) -> BondState {
+        self.shared.lock().unwrap().tracker.state
+    }
+
+    /// The UUID this side publishes as its `instance_id`.
+    pub fn instance_id(&self) -> uuid::Uuid {
+        self.instance_id
+    }
+
+    /// A stream of the events of the bond, starting with those that
+    /// already happened. It ends once the `Bond` has been dropped.
+    pub fn events(&self) -> impl Stream<Item = BondEvent> + Unpin {
+        let mut shared = self.shared.lock().unwrap();
+        let (sender, receiver) = mpsc::channel(2);
+        shared.senders.push(sender);
+        stream::iter(shared.history.clone()).chain(receiver)
+    }
+
+    /// Break the bond. The other side is told by the next spin.
+    pub fn break_bond(&self) {
+        self.shared.lock().unwrap().break_bond();
+    }
+}
+
+struct BondShared {
+    tracker: BondTracker,
+    history: Vec<BondEvent>,
+    senders: Vec<mpsc::Sender<BondEvent>>,
+}
+
+impl BondShared {
+    fn emit(&mut self, event: Option<BondEvent>) {
+        if let Some(event) = event {
+            self.history.push(event);
+            // there are at most two events, so the channels never fill
+            // up.
+            self.senders.retain_mut(|s| s.try_send(event).is_ok());
+        }
+    }
+
+    fn break_bond(&mut self) {
+        let now = self.tracker.now;
+        let event = self.tracker.break_bond(now);
+        self.emit(event);
+    }
+}
+
+pub(crate) struct Bond_ {
+    shared: Arc<Mutex<BondShared>>,
+    id: String,
+    instance_id: String,
+    options: BondOptions,
+    publisher: PublisherUntyped,
+    statuses: Box<dyn Stream<Item = Result<serde_json::Value>> + Unpin>,
+    timer: Timer,
+    clock: Clock,
+    errors: EntityErrors,
+}
+
+impl Bond_ {
+    /// Handles received statuses, checks the timeouts and publishes
+    /// the own status when it is due. Returns false once the `Bond`
+    /// has been dropped.
+    pub(crate) fn poll(&mut self) -> bool {
+        // the bond holds the only other reference.
+        if Arc::strong_count(&self.shared) == 1 {
+            let shared = self.shared.clone();
+            let mut shared = shared.lock().unwrap();
+            if matches!(shared.tracker.state, BondState::Forming | BondState::Active) {
+                shared.break_bond();
+                // nobody spins us anymore, so tell the other side once.
+                if let Err(e) = self.publish(false) {
+                    self.errors.report(SpinOperation::Send, e);
+                }
+            }
+            return false;
+        }
+        // the timer only wakes up the spin.
+        while let Some(Ok(_)) = self.timer.tick().now_or_never() {}
+        let now = match self.clock.get_now() {
+            Ok(now) => now,
+            Err(e) => {
+                self.errors.report(SpinOperation::Update, e);
+                return true;
+            }
+        };
+        let shared = self.shared.clone();
+        let mut shared = shared.lock().unwrap();
+        shared.tracker.now = now;
+        while let Some(Some(msg)) = self.statuses.next().now_or_never() {
+            match msg.and_then(|m| parse_status(&m)) {
+                Ok(status) => {
+                    // our own status or that of another bond on the
+                    // same topic.
+                    if status.id != self.id || status.instance_id == self.instance_id {
+                        continue;
+                    }
+                    let event = shared
+                        .tracker
+                        .peer_status(&status.instance_id, status.active, now);
+                    shared.emit(event);
+                }
+                Err(e) => self.errors.report(SpinOperation::Convert, e),
+            }
+        }
+        let event = shared.tracker.check(now);
+        shared.emit(event);
+        if let Some(active) = shared.tracker.heartbeat(now) {
+            if let Err(e) = self.publish(active) {
+                self.errors.report(SpinOperation::Send, e);
+            }
+        }
+        true
+    }
+
+    fn publish(&mut self, active: bool) -> Result<()> {
+        let stamp = Clock::to_builtin_time(&self.clock.get_now()?);
+        self.publisher.publish(serde_json::json!({
+            "header": {
+                "stamp": { "sec": stamp.sec, "nanosec": stamp.nanosec },
+                "frame_id": "",
+            },
+            "id": self.id,
+            "instance_id": self.instance_id,
+            "active": active,
+            "heartbeat_timeout": self.options.heartbeat_timeout.as_secs_f32(),
+            "heartbeat_period": self.options.heartbeat_period.as_secs_f32(),
+        }))
+    }
+}
+
+struct Status {
+    id: String,
+    instance_id: String,
+    active: bool,
+}
+
+fn parse_status(msg: &serde_json::Value) -> Result<Status> {
+    let invalid = |name: &str| Error::SerdeError {
+        err: format!("bond status without a valid {}: {}", name, msg),
+    };
+    let string = |name: &str| {
+        msg.get(name)
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_owned())
+            .ok_or_else(|| invalid(name))
+    };
+    Ok(Status {
+        id: string("id")?,
+        instance_id: string("instance_id")?,
+        active: msg
+            .get("active")
+            .and_then(|v| v.as_bool())
+            .ok_or_else(|| invalid("active"))?,
+    })
+}
+
+// The state of one side of a bond, with time given as ROS time.
+struct BondTracker {
+    options: BondOptions,
+    state: BondState,
+    // the instance id of the other side, once heard from.
+    peer: Option<String>,
+    // when the current state times out.
+    deadline: Duration,
+    next_heartbeat: Duration,
+    // after the bond broke, until when to keep publishing that.
+    inactive_until: Duration,
+    // the time of the last check, for breaking from outside the spin.
+    now: Duration,
+}
+
+impl BondTracker {
+    fn new(options: BondOptions, now: Duration) -> Self {
+        BondTracker {
+            options,
+            state: BondState::Forming,
+            peer: None,
+            deadline: now + options.connect_timeout,
+            next_heartbeat: now,
+            inactive_until: now,
+            now,
+        }
+    }
+
+    fn peer_status(&mut self, instance_id: &str, active: bool, now: Duration) -> Option<BondEvent> {
+        match &self.peer {
+            // an earlier incarnation of the other side, or a third
+            // party using the same bond id.
+            Some(peer) if peer != instance_id => return None,
+            Some(_) => (),
+            None if self.state == BondState::Forming => self.peer = Some(instance_id.to_owned()),
+            None => return None,
+        }
+        match (self.state, active) {
+            (BondState::Forming, true) => {
+                self.state = BondState::Active;
+                self.deadline = now + self.options.heartbeat_timeout;
+                Some(BondEvent::Formed)
+            }
+            (BondState::Active, true) => {
+                self.deadline = now + self.options.heartbeat_timeout;
+                None
+            }
+            (BondState::Forming, false) | (BondState::Active, false) => {
+                self.broken(BondBreak::PeerBroke, now)
+            }
+            (BondState::AwaitingPeerBreak, false) => {
+                self.state = BondState::Broken;
+                None
+            }
+            (BondState::AwaitingPeerBreak, true) | (BondState::Broken, _) => None,
+        }
+    }
+
+    fn break_bond(&mut self, now: Duration) -> Option<BondEvent> {
+        match self.state {
+            BondState::Forming => self.broken(BondBreak::Explicit, now),
+            BondState::Active => {
+                // keep beating inactive until the other side agrees.
+                self.state = BondState::AwaitingPeerBreak;
+                self.deadline = now + self.options.disconnect_timeout;
+                self.next_heartbeat = now;
+                Some(BondEvent::Broken(BondBreak::Explicit))
+            }
+            BondState::AwaitingPeerBreak | BondState::Broken => None,
+        }
+    }
+
+    fn check(&mut self, now: Duration) -> Option<BondEvent> {
+        if now < self.now {
+            // time jumped backwards, e.g. a simulation was restarted.
+            let shift = |t: Duration| now + t.saturating_sub(self.now);
+            self.deadline = shift(self.deadline);
+            self.next_heartbeat = shift(self.next_heartbeat);
+            self.inactive_until = shift(self.inactive_until);
+        }
+        self.now = now;
+        if now < self.deadline {
+            return None;
+        }
+        match self.state {
+            BondState::Forming => self.broken(BondBreak::ConnectTimeout, now),
+            BondState::Active => self.broken(BondBreak::HeartbeatTimeout, now),
+            BondState::AwaitingPeerBreak => {
+                self.state = BondState::Broken;
+                None
+            }
+            BondState::Broken => None,
+        }
+    }
+
+    // Whether to publish a status now, and if the bond is active in it.
+    fn heartbeat(&mut self, now: Duration) -> Option<bool> {
+        if now < self.next_heartbeat {
+            return None;
+        }
+        let active = match self.state {
+            BondState::Forming | BondState::Active => true,
+            BondState::AwaitingPeerBreak => false,
+            BondState::Broken if now < self.inactive_until => false,
+            BondState::Broken => return None,
+        };
+        self.next_heartbeat = now + self.options.heartbeat_period;
+        Some(active)
+    }
+
+    fn broken(&mut self, reason: BondBreak, now: Duration) -> Option<BondEvent> {
+        self.state = BondState::Broken;
+        self.next_heartbeat = now;
+        self.inactive_until = now + self.options.disconnect_timeout;
+        Some(BondEvent::Broken(reason))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn options() -> BondOptions {
+        BondOptions {
+            heartbeat_period: ms(100),
+            heartbeat_timeout: ms(400),
+            connect_timeout: ms(1000),
+            disconnect_timeout: ms(200),
+        }
+    }
+
+    #[test]
+    fn test_forming_and_heartbeat_timeout() {
+        let mut t = BondTracker::new(options(), ms(0));
+        assert_eq!(t.heartbeat(ms(0)), Some(true));
+        assert_eq!(t.heartbeat(ms(50)), None);
+        assert_eq!(t.heartbeat(ms(100)), Some(true));
+        assert_eq!(t.check(ms(500)), None);
+        assert_eq!(t.peer_status("a", true, ms(500)), Some(BondEvent::Formed));
+        assert_eq!(t.state, BondState::Active);
+        // beats within the timeout keep the bond alive.
+        assert_eq!(t.peer_status("a", true, ms(800)), None);
+        assert_eq!(t.check(ms(1199)), None);
+        assert_eq!(
+            t.check(ms(1200)),
+            Some(BondEvent::Broken(BondBreak::HeartbeatTimeout))
+        );
+        assert_eq!(t.state, BondState::Broken);
+        // the other side is told for the disconnect timeout, and a
+        // bond that broke stays broken.
+        assert_eq!(t.heartbeat(ms(1200)), Some(false));
+        assert_eq!(t.heartbeat(ms(1300)), Some(false));
+        assert_eq!(t.heartbeat(ms(1400)), None);
+        assert_eq!(t.peer_status("a", true, ms(1400)), None);
+        assert_eq!(t.check(ms(5000)), None);
+        assert_eq!(t.state, BondState::Broken);
+    }
+
+    #[test]
+    fn test_connect_timeout() {
+        let mut t = BondTracker::new(options(), ms(0));
+        assert_eq!(t.check(ms(999)), None);
+        assert_eq!(
+            t.check(ms(1000)),
+            Some(BondEvent::Broken(BondBreak::ConnectTimeout))
+        );
+        assert_eq!(t.peer_status("a", true, ms(1001)), None);
+        assert_eq!(t.state, BondState::Broken);
+    }
+
+    #[test]
+    fn test_peer_breaks() {
+        let mut t = BondTracker::new(options(), ms(0));
+        t.peer_status("a", true, ms(10));
+        assert_eq!(
+            t.peer_status("a", false, ms(20)),
+            Some(BondEvent::Broken(BondBreak::PeerBroke))
+        );
+        assert_eq!(t.heartbeat(ms(20)), Some(false));
+
+        // a peer that is already broken when we first hear from it.
+        let mut t = BondTracker::new(options(), ms(0));
+        assert_eq!(
+            t.peer_status("a", false, ms(10)),
+            Some(BondEvent::Broken(BondBreak::PeerBroke))
+        );
+    }
+
+    #[test]
+    fn test_explicit_break() {
+        let mut t = BondTracker::new(options(), ms(0));
+        t.peer_status("a", true, ms(0));
+        t.heartbeat(ms(0));
+        assert_eq!(
+            t.break_bond(ms(50)),
+            Some(BondEvent::Broken(BondBreak::Explicit))
+        );
+        assert_eq!(t.state, BondState::AwaitingPeerBreak);
+        assert_eq!(t.break_bond(ms(60)), None);
+        // published right away, without waiting for the period.
+        assert_eq!(t.heartbeat(ms(50)), Some(false));
+        // still beating peers do not bring the bond back.
+        assert_eq!(t.peer_status("a", true, ms(100)), None);
+        assert_eq!(t.check(ms(100)), None);
+        assert_eq!(t.peer_status("a", false, ms(120)), None);
+        assert_eq!(t.state, BondState::Broken);
+
+        // without an acknowledgement, the bond breaks after the
+        // disconnect timeout.
+        let mut t = BondTracker::new(options(), ms(0));
+        t.peer_status("a", true, ms(0));
+        t.break_bond(ms(0));
+        assert_eq!(t.check(ms(199)), None);
+        assert_eq!(t.state, BondState::AwaitingPeerBreak);
+        assert_eq!(t.check(ms(200)), None);
+        assert_eq!(t.state, BondState::Broken);
+
+        // breaking a bond that is still forming.
+        let mut t = BondTracker::new(options(), ms(0));
+        assert_eq!(
+            t.break_bond(ms(0)),
+            Some(BondEvent::Broken(BondBreak::Explicit))
+        );
+        assert_eq!(t.state, BondState::Broken);
+    }
+
+    #[test]
+    fn test_other_instances_ignored() {
+        let mut t = BondTracker::new(options(), ms(0));
+        t.peer_status("a", true, ms(0));
+        // a restarted peer has a new instance id and cannot keep the
+        // old bond alive.
+        assert_eq!(t.peer_status("b", true, ms(300)), None);
+        assert_eq!(t.peer_status("b", false, ms(300)), None);
+        assert_eq!(
+            t.check(ms(400)),
+            Some(BondEvent::Broken(BondBreak::HeartbeatTimeout))
+        );
+    }
+
+    #[test]
+    fn test_paused_and_reset_time() {
+        let mut t = BondTracker::new(options(), ms(5000));
+        t.peer_status("a", true, ms(5000));
+        // a paused clock does not advance, so nothing times out.
+        for _ in 0..10 {
+            assert_eq!(t.check(ms(5000)), None);
+        }
+        // a restarted simulation keeps the remaining time.
+        assert_eq!(t.check(ms(10)), None);
+        assert_eq!(t.check(ms(409)), None);
+        assert_eq!(
+            t.check(ms(410)),
+            Some(BondEvent::Broken(BondBreak::HeartbeatTimeout))
+        );
+    }
+
+    #[test]
+    fn test_parse_status() {
+        let status = parse_status(&serde_json::json!({
+            "id": "child", "instance_id": "a", "active": true,
+        }))
+        .unwrap();
+        assert_eq!(status.id, "child");
+        assert!(status.active);
+        assert!(parse_status(&serde_json::json!({ "id": "child" })).is_err());
+    }
+}
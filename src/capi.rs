@@ -0,0 +1,452 @@
+//! A C API for hosts that cannot use r2r from Rust. `include/r2r.h`
+//! declares the functions and documents the ownership of buffers.
+//!
+//! Everything is behind opaque handles that are created and destroyed
+//! by the functions here. Functions return `R2R_OK` on success and
+//! otherwise an error code: the rcl return code for errors that have
+//! one, or one of the negative `R2R_ERR_*` codes. The message of the
+//! last error on a thread is available from `r2r_last_error`.
+//!
+//! A node and the handles created from it must only be used from one
+//! thread at a time.
+
+use futures::future::{Future, FutureExt};
+use futures::stream::{Stream, StreamExt};
+use r2r_actions::*;
+use r2r_rcl::*;
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+use crate::clients::ClientUntyped;
+use crate::context::Context;
+use crate::error::*;
+use crate::nodes::Node;
+use crate::publishers::PublisherSerialized;
+use crate::typesupport_loader::MessageTypeSupport;
+
+pub const R2R_OK: c_int = 0;
+/// There is nothing to take yet.
+pub const R2R_ERR_NO_DATA: c_int = -1;
+/// An error that has no rcl return code, see `r2r_last_error`.
+pub const R2R_ERR_OTHER: c_int = -2;
+
+thread_local! {
+    static LAST_ERROR: RefCell<String> = RefCell::new(String::new());
+}
+
+pub struct R2rContext {
+    context: Context,
+}
+
+pub struct R2rNode {
+    node: Node,
+}
+
+pub struct R2rPublisher {
+    publisher: PublisherSerialized,
+}
+
+pub struct R2rSubscriber {
+    stream: Box<dyn Stream<Item = Vec<u8>> + Unpin + Send>,
+}
+
+pub struct R2rClient {
+    client: ClientUntyped,
+}
+
+type ResponseFuture = Pin<Box<dyn Future<Output = Result<Result<serde_json::Value>>>>>;
+
+pub struct R2rResponse {
+    // `None` once the response has been taken.
+    response: Option<ResponseFuture>,
+}
+
+// The rcl return code of an error, if it has one.
+fn rcl_code(e: &Error) -> Option<u32> {
+    let code = match e.code() {
+        Error::RCL_RET_OK => RCL_RET_OK,
+        Error::RCL_RET_ERROR => RCL_RET_ERROR,
+        Error::RCL_RET_TIMEOUT => RCL_RET_TIMEOUT,
+        Error::RCL_RET_BAD_ALLOC => RCL_RET_BAD_ALLOC,
+        Error::RCL_RET_INVALID_ARGUMENT => RCL_RET_INVALID_ARGUMENT,
+        Error::RCL_RET_UNSUPPORTED => RCL_RET_UNSUPPORTED,
+        Error::RCL_RET_ALREADY_INIT => RCL_RET_ALREADY_INIT,
+        Error::RCL_RET_NOT_INIT => RCL_RET_NOT_INIT,
+        Error::RCL_RET_MISMATCHED_RMW_ID => RCL_RET_MISMATCHED_RMW_ID,
+        Error::RCL_RET_TOPIC_NAME_INVALID => RCL_RET_TOPIC_NAME_INVALID,
+        Error::RCL_RET_SERVICE_NAME_INVALID => RCL_RET_SERVICE_NAME_INVALID,
+        Error::RCL_RET_UNKNOWN_SUBSTITUTION => RCL_RET_UNKNOWN_SUBSTITUTION,
+        Error::RCL_RET_ALREADY_SHUTDOWN => RCL_RET_ALREADY_SHUTDOWN,
+        Error::RCL_RET_NODE_INVALID => RCL_RET_NODE_INVALID,
+        Error::RCL_RET_NODE_INVALID_NAME => RCL_RET_NODE_INVALID_NAME,
+        Error::RCL_RET_NODE_INVALID_NAMESPACE => RCL_RET_NODE_INVALID_NAMESPACE,
+        Error::RCL_RET_PUBLISHER_INVALID => RCL_RET_PUBLISHER_INVALID,
+        Error::RCL_RET_SUBSCRIPTION_INVALID => RCL_RET_SUBSCRIPTION_INVALID,
+        Error::RCL_RET_SUBSCRIPTION_TAKE_FAILED => RCL_RET_SUBSCRIPTION_TAKE_FAILED,
+        Error::RCL_RET_CLIENT_INVALID => RCL_RET_CLIENT_INVALID,
+        Error::RCL_RET_CLIENT_TAKE_FAILED => RCL_RET_CLIENT_TAKE_FAILED,
+        Error::RCL_RET_SERVICE_INVALID => RCL_RET_SERVICE_INVALID,
+        Error::RCL_RET_SERVICE_TAKE_FAILED => RCL_RET_SERVICE_TAKE_FAILED,
+        Error::RCL_RET_TIMER_INVALID => RCL_RET_TIMER_INVALID,
+        Error::RCL_RET_TIMER_CANCELED => RCL_RET_TIMER_CANCELED,
+        Error::RCL_RET_WAIT_SET_INVALID => RCL_RET_WAIT_SET_INVALID,
+        Error::RCL_RET_WAIT_SET_EMPTY => RCL_RET_WAIT_SET_EMPTY,
+        Error::RCL_RET_WAIT_SET_FULL => RCL_RET_WAIT_SET_FULL,
+        Error::RCL_RET_INVALID_REMAP_RULE => RCL_RET_INVALID_REMAP_RULE,
+        Error::RCL_RET_WRONG_LEXEME => RCL_RET_WRONG_LEXEME,
+        Error::RCL_RET_INVALID_PARAM_RULE => RCL_RET_INVALID_PARAM_RULE,
+        Error::RCL_RET_INVALID_LOG_LEVEL_RULE => RCL_RET_INVALID_LOG_LEVEL_RULE,
+        Error::RCL_RET_EVENT_INVALID => RCL_RET_EVENT_INVALID,
+        Error::RCL_RET_EVENT_TAKE_FAILED => RCL_RET_EVENT_TAKE_FAILED,
+        Error::RCL_RET_ACTION_NAME_INVALID => RCL_RET_ACTION_NAME_INVALID,
+        Error::RCL_RET_ACTION_GOAL_ACCEPTED => RCL_RET_ACTION_GOAL_ACCEPTED,
+        Error::RCL_RET_ACTION_GOAL_REJECTED => RCL_RET_ACTION_GOAL_REJECTED,
+        Error::RCL_RET_ACTION_CLIENT_INVALID => RCL_RET_ACTION_CLIENT_INVALID,
+        Error::RCL_RET_ACTION_CLIENT_TAKE_FAILED => RCL_RET_ACTION_CLIENT_TAKE_FAILED,
+        Error::RCL_RET_ACTION_SERVER_INVALID => RCL_RET_ACTION_SERVER_INVALID,
+        Error::RCL_RET_ACTION_SERVER_TAKE_FAILED => RCL_RET_ACTION_SERVER_TAKE_FAILED,
+        Error::RCL_RET_ACTION_GOAL_HANDLE_INVALID => RCL_RET_ACTION_GOAL_HANDLE_INVALID,
+        Error::RCL_RET_ACTION_GOAL_EVENT_INVALID => RCL_RET_ACTION_GOAL_EVENT_INVALID,
+        _ => return None,
+    };
+    Some(code)
+}
+
+fn error_code(e: &Error) -> c_int {
+    rcl_code(e).map(|c| c as c_int).unwrap_or(R2R_ERR_OTHER)
+}
+
+fn set_last_error(message: String) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+// Runs the body of an exported function, turning errors and panics
+// into error codes.
+fn run<F: FnOnce() -> Result<c_int>>(f: F) -> c_int {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(code)) => code,
+        Ok(Err(e)) => {
+            let code = error_code(&e);
+            set_last_error(e.to_string());
+            code
+        }
+        Err(_) => {
+            set_last_error("r2r panicked".into());
+            R2R_ERR_OTHER
+        }
+    }
+}
+
+unsafe fn str_arg<'a>(s: *const c_char) -> Result<&'a str> {
+    if s.is_null() {
+        return Err(Error::RCL_RET_INVALID_ARGUMENT);
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)
+}
+
+unsafe fn bytes_arg<'a>(data: *const u8, len: usize) -> Result<&'a [u8]> {
+    if len == 0 {
+        Ok(&[])
+    } else if data.is_null() {
+        Err(Error::RCL_RET_INVALID_ARGUMENT)
+    } else {
+        Ok(std::slice::from_raw_parts(data, len))
+    }
+}
+
+unsafe fn handle_arg<'a, T>(handle: *mut T) -> Result<&'a mut T> {
+    handle.as_mut().ok_or(Error::RCL_RET_INVALID_ARGUMENT)
+}
+
+unsafe fn give_handle<T>(out: *mut *mut T, value: T) -> Result<c_int> {
+    if out.is_null() {
+        return Err(Error::RCL_RET_INVALID_ARGUMENT);
+    }
+    *out = Box::into_raw(Box::new(value));
+    Ok(R2R_OK)
+}
+
+// Hands a buffer to the caller, who frees it with `r2r_buffer_free`.
+unsafe fn give_buffer(data: Vec<u8>, out_data: *mut *mut u8, out_len: *mut usize) -> Result<c_int> {
+    if out_data.is_null() || out_len.is_null() {
+        return Err(Error::RCL_RET_INVALID_ARGUMENT);
+    }
+    let data = data.into_boxed_slice();
+    *out_len = data.len();
+    *out_data = Box::into_raw(data) as *mut u8;
+    Ok(R2R_OK)
+}
+
+unsafe fn destroy<T>(handle: *mut T) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Copy the message of the last error on this thread into `buf`,
+/// truncated to `len - 1` bytes and nul terminated. Returns the length
+/// of the whole message.
+#[no_mangle]
+pub unsafe extern "C" fn r2r_last_error(buf: *mut c_char, len: usize) -> usize {
+    LAST_ERROR.with(|last| {
+        let last = last.borrow();
+        if !buf.is_null() && len > 0 {
+            let n = last.len().min(len - 1);
+            std::ptr::copy_nonoverlapping(last.as_ptr() as *const c_char, buf, n);
+            *buf.add(n) = 0;
+        }
+        last.len()
+    })
+}
+
+/// Free a buffer returned by `r2r_subscriber_take` or
+/// `r2r_response_take`. `len` is the length returned with it.
+#[no_mangle]
+pub unsafe extern "C" fn r2r_buffer_free(data: *mut u8, len: usize) {
+    if !data.is_null() {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(data, len)));
+    }
+}
+
+/// Create a context from the command line of the process.
+#[no_mangle]
+pub unsafe extern "C" fn r2r_context_create(out: *mut *mut R2rContext) -> c_int {
+    run(|| {
+        give_handle(
+            out,
+            R2rContext {
+                context: Context::create()?,
+            },
+        )
+    })
+}
+
+/// Nodes created from the context keep it alive.
+#[no_mangle]
+pub unsafe extern "C" fn r2r_context_destroy(context: *mut R2rContext) {
+    destroy(context)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn r2r_node_create(
+    context: *mut R2rContext,
+    name: *const c_char,
+    namespace: *const c_char,
+    out: *mut *mut R2rNode,
+) -> c_int {
+    run(|| {
+        let context = handle_arg(context)?.context.clone();
+        let node = Node::create(context, str_arg(name)?, str_arg(namespace)?)?;
+        give_handle(out, R2rNode { node })
+    })
+}
+
+/// The publishers, subscribers and clients of the node stop working,
+/// but still have to be destroyed.
+#[no_mangle]
+pub unsafe extern "C" fn r2r_node_destroy(node: *mut R2rNode) {
+    destroy(node)
+}
+
+/// Wait at most `timeout_ms` for something to happen and handle it.
+#[no_mangle]
+pub unsafe extern "C" fn r2r_node_spin_once(node: *mut R2rNode, timeout_ms: u64) -> c_int {
+    run(|| {
+        handle_arg(node)?
+            .node
+            .spin_once(Duration::from_millis(timeout_ms));
+        Ok(R2R_OK)
+    })
+}
+
+/// Create a publisher of serialized messages of the type named e.g.
+/// `std_msgs/msg/String`.
+#[no_mangle]
+pub unsafe extern "C" fn r2r_publisher_create(
+    node: *mut R2rNode,
+    topic: *const c_char,
+    type_name: *const c_char,
+    out: *mut *mut R2rPublisher,
+) -> c_int {
+    run(|| {
+        let node = &mut handle_arg(node)?.node;
+        let type_support = MessageTypeSupport::for_type_name(str_arg(type_name)?)?;
+        let publisher = node.create_publisher_serialized(str_arg(topic)?, &type_support)?;
+        give_handle(out, R2rPublisher { publisher })
+    })
+}
+
+/// Publish a message serialized by the middleware in use (e.g. CDR).
+/// The data is copied, the caller keeps ownership of it.
+#[no_mangle]
+pub unsafe extern "C" fn r2r_publisher_publish(
+    publisher: *mut R2rPublisher,
+    data: *const u8,
+    len: usize,
+) -> c_int {
+    run(|| {
+        handle_arg(publisher)?
+            .publisher
+            .publish(bytes_arg(data, len)?)?;
+        Ok(R2R_OK)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn r2r_publisher_destroy(publisher: *mut R2rPublisher) {
+    destroy(publisher)
+}
+
+/// Create a subscriber of serialized messages of the type named e.g.
+/// `std_msgs/msg/String`.
+#[no_mangle]
+pub unsafe extern "C" fn r2r_subscriber_create(
+    node: *mut R2rNode,
+    topic: *const c_char,
+    type_name: *const c_char,
+    out: *mut *mut R2rSubscriber,
+) -> c_int {
+    run(|| {
+        let node = &mut handle_arg(node)?.node;
+        let type_support = MessageTypeSupport::for_type_name(str_arg(type_name)?)?;
+        let stream = node.subscribe_serialized(str_arg(topic)?, &type_support)?;
+        give_handle(
+            out,
+            R2rSubscriber {
+                stream: Box::new(stream),
+            },
+        )
+    })
+}
+
+/// Take the next message received while spinning the node. Returns
+/// `R2R_ERR_NO_DATA` if there is none. On success the caller owns the
+/// buffer and frees it with `r2r_buffer_free`.
+#[no_mangle]
+pub unsafe extern "C" fn r2r_subscriber_take(
+    subscriber: *mut R2rSubscriber,
+    out_data: *mut *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    run(
+        || match handle_arg(subscriber)?.stream.next().now_or_never() {
+            Some(Some(data)) => give_buffer(data, out_data, out_len),
+            Some(None) => Err(Error::RCL_RET_SUBSCRIPTION_INVALID),
+            None => Ok(R2R_ERR_NO_DATA),
+        },
+    )
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn r2r_subscriber_destroy(subscriber: *mut R2rSubscriber) {
+    destroy(subscriber)
+}
+
+/// Create a client of the service type named e.g.
+/// `example_interfaces/srv/AddTwoInts`. Requests and responses are
+/// json.
+#[no_mangle]
+pub unsafe extern "C" fn r2r_client_create(
+    node: *mut R2rNode,
+    service: *const c_char,
+    type_name: *const c_char,
+    out: *mut *mut R2rClient,
+) -> c_int {
+    run(|| {
+        let node = &mut handle_arg(node)?.node;
+        let client = node.create_client_untyped(str_arg(service)?, str_arg(type_name)?)?;
+        give_handle(out, R2rClient { client })
+    })
+}
+
+/// Spin the node until the service is available, at most `timeout_ms`.
+/// Returns `RCL_RET_TIMEOUT` if it is not.
+#[no_mangle]
+pub unsafe extern "C" fn r2r_client_wait_for_service(
+    node: *mut R2rNode,
+    client: *mut R2rClient,
+    timeout_ms: u64,
+) -> c_int {
+    run(|| {
+        let node = &mut handle_arg(node)?.node;
+        let mut available = node.is_available(&handle_arg(client)?.client)?.boxed();
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        loop {
+            if let Some(result) = (&mut available).now_or_never() {
+                result?;
+                return Ok(R2R_OK);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::RCL_RET_TIMEOUT);
+            }
+            node.spin_once((deadline - now).min(Duration::from_millis(10)));
+        }
+    })
+}
+
+/// Send a request given as utf-8 json of `len` bytes. The data is
+/// copied, the caller keeps ownership of it. The response arrives
+/// while spinning the node, see `r2r_response_take`.
+#[no_mangle]
+pub unsafe extern "C" fn r2r_client_request(
+    client: *mut R2rClient,
+    json: *const u8,
+    len: usize,
+    out: *mut *mut R2rResponse,
+) -> c_int {
+    run(|| {
+        let request = serde_json::from_slice(bytes_arg(json, len)?)
+            .map_err(|e| Error::SerdeError { err: e.to_string() })?;
+        let response = handle_arg(client)?.client.request(request)?;
+        give_handle(
+            out,
+            R2rResponse {
+                response: Some(Box::pin(response)),
+            },
+        )
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn r2r_client_destroy(client: *mut R2rClient) {
+    destroy(client)
+}
+
+/// Take the response as utf-8 json. Returns `R2R_ERR_NO_DATA` if it
+/// has not arrived yet. On success the caller owns the buffer and
+/// frees it with `r2r_buffer_free`. A response can be taken once.
+#[no_mangle]
+pub unsafe extern "C" fn r2r_response_take(
+    response: *mut R2rResponse,
+    out_data: *mut *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    run(|| {
+        let response = handle_arg(response)?;
+        let pending = response
+            .response
+            .as_mut()
+            .ok_or(Error::RCL_RET_INVALID_ARGUMENT)?;
+        match pending.now_or_never() {
+            Some(result) => {
+                response.response = None;
+                let json = result??;
+                give_buffer(json.to_string().into_bytes(), out_data, out_len)
+            }
+            None => Ok(R2R_ERR_NO_DATA),
+        }
+    })
+}
+
+/// A response that has not arrived is dropped when it does.
+#[no_mangle]
+pub unsafe extern "C" fn r2r_response_destroy(response: *mut R2rResponse) {
+    destroy(response)
+}
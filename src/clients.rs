@@ -1,14 +1,36 @@
 use futures::channel::oneshot;
 use futures::TryFutureExt;
 use std::future::Future;
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use std::mem::MaybeUninit;
 use std::sync::{Mutex, Weak};
 
+use crate::error_events::*;
+use crate::utils::LogSeverity;
+use crate::executor::EntityKind;
 use crate::msg_types::*;
+use crate::distro::client_writer_guid;
 use crate::error::*;
+use crate::node_names::node_names;
+use crate::services::ServiceIntrospection;
+use crate::traits::Entity;
 use r2r_rcl::*;
 
+/// Options for creating service clients.
+#[derive(Debug, Clone, Default)]
+pub struct ClientOptions {
+    /// Fail with `Error::MultipleServiceServers` when more than one
+    /// node serves the service.
+    ///
+    /// The servers are counted when `Node::is_available` finds the
+    /// service and then periodically from `spin_once`. Requests fail
+    /// for as long as there are several servers.
+    pub expect_single_server: bool,
+    /// Publish service events, see `ServiceIntrospection`. Creating the
+    /// client fails with `Error::NotSupported` before Iron.
+    pub introspection: ServiceIntrospection,
+}
+
 /// ROS service client.
 ///
 /// This is a handle to a service client wrapped in a `Mutex` inside a
@@ -24,6 +46,16 @@ impl<T: 'static> Client<T>
 where
     T: WrappedServiceTypeSupport,
 {
+    /// Number of responses that were dropped because they did not
+    /// belong to a request made by this client.
+    ///
+    /// See `ClientUntyped::stale_responses`.
+    pub fn stale_responses(&self) -> Result<usize> {
+        let client = self.client.upgrade().ok_or(Error::RCL_RET_CLIENT_INVALID)?;
+        let client = client.lock().unwrap();
+        Ok(client.response_guard.stale_responses())
+    }
+
     /// Make a service request.
     ///
     /// Returns a `Future` of the `Response` type.
@@ -50,6 +82,18 @@ pub struct ClientUntyped {
 }
 
 impl ClientUntyped {
+    /// Number of responses that were dropped because they did not
+    /// belong to a request made by this client.
+    ///
+    /// These are typically responses to requests of an earlier
+    /// client on the same service, which rmw may hand to us with a
+    /// reused sequence number after that client was destroyed.
+    pub fn stale_responses(&self) -> Result<usize> {
+        let client = self.client.upgrade().ok_or(Error::RCL_RET_CLIENT_INVALID)?;
+        let client = client.lock().unwrap();
+        Ok(client.response_guard.stale_responses())
+    }
+
     /// Make an "untyped" service request.
     ///
     /// The request is a `serde_json::Value`. It is up to the user to
@@ -67,6 +111,33 @@ impl ClientUntyped {
     }
 }
 
+impl<T> Entity for Client<T>
+where
+    T: WrappedServiceTypeSupport,
+{
+    fn name(&self) -> Result<String> {
+        let client = self.client.upgrade().ok_or(Error::RCL_RET_CLIENT_INVALID)?;
+        let client = client.lock().unwrap();
+        client_service_name(&client.rcl_handle)
+    }
+
+    fn kind(&self) -> EntityKind {
+        EntityKind::Client
+    }
+}
+
+impl Entity for ClientUntyped {
+    fn name(&self) -> Result<String> {
+        let client = self.client.upgrade().ok_or(Error::RCL_RET_CLIENT_INVALID)?;
+        let client = client.lock().unwrap();
+        client_service_name(&client.rcl_handle)
+    }
+
+    fn kind(&self) -> EntityKind {
+        EntityKind::Client
+    }
+}
+
 pub fn make_client<T>(client: Weak<Mutex<TypedClient<T>>>) -> Client<T>
 where
     T: WrappedServiceTypeSupport,
@@ -88,6 +159,7 @@ where
     where
         T: WrappedServiceTypeSupport,
     {
+        self.server_check.check()?;
         let native_msg: WrappedNativeMsg<T::Request> = WrappedNativeMsg::<T::Request>::from(msg);
         let mut seq_no = 0i64;
         let result =
@@ -100,8 +172,12 @@ where
             // instead of "canceled" we return invalid client.
             Ok(receiver.map_err(|_| Error::RCL_RET_CLIENT_INVALID))
         } else {
-            eprintln!("coult not send request {}", result);
-            Err(Error::from_rcl_error(result))
+            let err = Error::from_rcl_error(result);
+            self.errors.log(
+                LogSeverity::Warn,
+                &format!("could not send request: {}", err),
+            );
+            Err(err)
         }
     }
 }
@@ -113,6 +189,7 @@ impl UntypedClient_ {
         &mut self,
         msg: serde_json::Value,
     ) -> Result<impl Future<Output = Result<Result<serde_json::Value>>>> {
+        self.server_check.check()?;
         let mut native_msg = (self.service_type.make_request_msg)();
         native_msg.from_json(msg)?;
 
@@ -127,8 +204,12 @@ impl UntypedClient_ {
             // instead of "canceled" we return invalid client.
             Ok(receiver.map_err(|_| Error::RCL_RET_CLIENT_INVALID))
         } else {
-            eprintln!("coult not send request {}", result);
-            Err(Error::from_rcl_error(result))
+            let err = Error::from_rcl_error(result);
+            self.errors.log(
+                LogSeverity::Warn,
+                &format!("could not send request: {}", err),
+            );
+            Err(err)
         }
     }
 }
@@ -136,8 +217,9 @@ impl UntypedClient_ {
 pub trait Client_ {
     fn handle(&self) -> &rcl_client_t;
     fn handle_response(&mut self) -> ();
-    fn register_poll_available(&mut self, s: oneshot::Sender<()>) -> ();
+    fn register_poll_available(&mut self, s: oneshot::Sender<Result<()>>) -> ();
     fn poll_available(&mut self, node: &mut rcl_node_t) -> ();
+    fn check_servers(&mut self, node: &rcl_node_t) -> ();
     fn destroy(&mut self, node: &mut rcl_node_t) -> ();
 }
 
@@ -147,7 +229,10 @@ where
 {
     pub rcl_handle: rcl_client_t,
     pub response_channels: Vec<(i64, oneshot::Sender<T::Response>)>,
-    pub poll_available_channels: Vec<oneshot::Sender<()>>,
+    pub response_guard: ResponseGuard,
+    pub poll_available_channels: Vec<oneshot::Sender<Result<()>>>,
+    pub server_check: ServerCheck,
+    pub errors: EntityErrors,
 }
 
 impl<T: 'static> Client_ for TypedClient<T>
@@ -176,30 +261,46 @@ where
                 .iter()
                 .position(|(id, _)| id == &request_id.sequence_number)
             {
+                if !self.response_guard.accept(&request_id, &self.errors) {
+                    return;
+                }
                 let (_, sender) = self.response_channels.swap_remove(idx);
                 let response = T::Response::from_native(&response_msg);
                 match sender.send(response) {
                     Ok(()) => {}
-                    Err(e) => {
-                        println!("error sending to client: {:?}", e);
+                    Err(_) => {
+                        self.errors.report(
+                            SpinOperation::Deliver,
+                            Error::DeliveryFailed {
+                                reason: "request future dropped".into(),
+                            },
+                        );
                     }
                 }
             } else {
+                self.response_guard.reject();
                 let we_have: String = self
                     .response_channels
                     .iter()
                     .map(|(id, _)| id.to_string())
                     .collect::<Vec<_>>()
                     .join(",");
-                eprintln!(
-                    "no such req id: {}, we have [{}], ignoring",
-                    request_id.sequence_number, we_have
+                self.errors.report(
+                    SpinOperation::Match,
+                    Error::UnmatchedResponse {
+                        reason: format!(
+                            "no such req id: {}, we have [{}], ignoring",
+                            request_id.sequence_number, we_have
+                        ),
+                    },
                 );
             }
-        } // TODO handle failure.
+        } else if ret != RCL_RET_CLIENT_TAKE_FAILED as i32 {
+            self.errors.report(SpinOperation::Take, Error::from_rcl_error(ret));
+        }
     }
 
-    fn register_poll_available(&mut self, s: oneshot::Sender<()>) {
+    fn register_poll_available(&mut self, s: oneshot::Sender<Result<()>>) {
         self.poll_available_channels.push(s);
     }
 
@@ -210,9 +311,10 @@ where
         let available = service_available_helper(node, self.handle());
         match available {
             Ok(true) => {
-                // send ok and close channels
+                self.check_servers(node);
+                // send the outcome and close channels
                 while let Some(sender) = self.poll_available_channels.pop() {
-                    let _res = sender.send(()); // we ignore if receiver dropped.
+                    let _res = sender.send(self.server_check.check()); // we ignore if receiver dropped.
                 }
             }
             Ok(false) => {
@@ -225,6 +327,16 @@ where
         }
     }
 
+    fn check_servers(&mut self, node: &rcl_node_t) {
+        if !self.server_check.enabled() {
+            return;
+        }
+        match client_service_name(&self.rcl_handle) {
+            Ok(name) => self.server_check.update(node, &[name], &self.errors),
+            Err(e) => self.errors.report(SpinOperation::Update, e),
+        }
+    }
+
     fn destroy(&mut self, node: &mut rcl_node_t) {
         unsafe {
             rcl_client_fini(&mut self.rcl_handle, node);
@@ -236,7 +348,10 @@ pub struct UntypedClient_ {
     pub service_type: UntypedServiceSupport,
     pub rcl_handle: rcl_client_t,
     pub response_channels: Vec<(i64, oneshot::Sender<Result<serde_json::Value>>)>,
-    pub poll_available_channels: Vec<oneshot::Sender<()>>,
+    pub response_guard: ResponseGuard,
+    pub poll_available_channels: Vec<oneshot::Sender<Result<()>>>,
+    pub server_check: ServerCheck,
+    pub errors: EntityErrors,
 }
 
 impl Client_ for UntypedClient_ {
@@ -262,30 +377,46 @@ impl Client_ for UntypedClient_ {
                 .iter()
                 .position(|(id, _)| id == &request_id.sequence_number)
             {
+                if !self.response_guard.accept(&request_id, &self.errors) {
+                    return;
+                }
                 let (_, sender) = self.response_channels.swap_remove(idx);
                 let response = response_msg.to_json();
                 match sender.send(response) {
                     Ok(()) => {}
-                    Err(e) => {
-                        println!("error sending to client: {:?}", e);
+                    Err(_) => {
+                        self.errors.report(
+                            SpinOperation::Deliver,
+                            Error::DeliveryFailed {
+                                reason: "request future dropped".into(),
+                            },
+                        );
                     }
                 }
             } else {
+                self.response_guard.reject();
                 let we_have: String = self
                     .response_channels
                     .iter()
                     .map(|(id, _)| id.to_string())
                     .collect::<Vec<_>>()
                     .join(",");
-                eprintln!(
-                    "no such req id: {}, we have [{}], ignoring",
-                    request_id.sequence_number, we_have
+                self.errors.report(
+                    SpinOperation::Match,
+                    Error::UnmatchedResponse {
+                        reason: format!(
+                            "no such req id: {}, we have [{}], ignoring",
+                            request_id.sequence_number, we_have
+                        ),
+                    },
                 );
             }
-        } // TODO handle failure.
+        } else if ret != RCL_RET_CLIENT_TAKE_FAILED as i32 {
+            self.errors.report(SpinOperation::Take, Error::from_rcl_error(ret));
+        }
     }
 
-    fn register_poll_available(&mut self, s: oneshot::Sender<()>) {
+    fn register_poll_available(&mut self, s: oneshot::Sender<Result<()>>) {
         self.poll_available_channels.push(s);
     }
 
@@ -296,9 +427,10 @@ impl Client_ for UntypedClient_ {
         let available = service_available_helper(node, self.handle());
         match available {
             Ok(true) => {
-                // send ok and close channels
+                self.check_servers(node);
+                // send the outcome and close channels
                 while let Some(sender) = self.poll_available_channels.pop() {
-                    let _res = sender.send(()); // we ignore if receiver dropped.
+                    let _res = sender.send(self.server_check.check()); // we ignore if receiver dropped.
                 }
             }
             Ok(false) => {
@@ -311,6 +443,16 @@ impl Client_ for UntypedClient_ {
         }
     }
 
+    fn check_servers(&mut self, node: &rcl_node_t) {
+        if !self.server_check.enabled() {
+            return;
+        }
+        match client_service_name(&self.rcl_handle) {
+            Ok(name) => self.server_check.update(node, &[name], &self.errors),
+            Err(e) => self.errors.report(SpinOperation::Update, e),
+        }
+    }
+
     fn destroy(&mut self, node: &mut rcl_node_t) {
         unsafe {
             rcl_client_fini(&mut self.rcl_handle, node);
@@ -318,6 +460,177 @@ impl Client_ for UntypedClient_ {
     }
 }
 
+/// Drops responses that were not meant for this client instance.
+///
+/// Sequence numbers start over for every new client, so after a
+/// client is destroyed and recreated, a late response to the old
+/// client can carry the sequence number of a pending request of the
+/// new one. The response header also carries the guid of the writer
+/// that sent the request, which differs between client instances, so
+/// responses with another guid than the one of our own writer are
+/// rejected. rmw only tells us that guid from iron on, on older
+/// distributions only the sequence numbers are matched.
+#[derive(Debug, Default)]
+pub struct ResponseGuard {
+    writer_guid: Option<[i8; 16]>,
+    stale_responses: usize,
+}
+
+impl ResponseGuard {
+    /// A guard for the responses to the requests `client` sends.
+    pub fn new(client: &rcl_client_t) -> Self {
+        ResponseGuard {
+            writer_guid: client_writer_guid(client).ok(),
+            stale_responses: 0,
+        }
+    }
+
+    /// Checks a response that matched a pending request.
+    pub fn accept(&mut self, request_id: &rmw_request_id_t, errors: &EntityErrors) -> bool {
+        match self.writer_guid {
+            Some(guid) if guid != request_id.writer_guid => {
+                errors.report(
+                    SpinOperation::Match,
+                    Error::UnmatchedResponse {
+                        reason: format!(
+                            "response for req id {} was sent to another client, ignoring",
+                            request_id.sequence_number
+                        ),
+                    },
+                );
+                self.reject();
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Counts a response that could not be matched to a request.
+    pub fn reject(&mut self) {
+        self.stale_responses += 1;
+    }
+
+    pub fn stale_responses(&self) -> usize {
+        self.stale_responses
+    }
+}
+
+/// Counts the servers of the services a client talks to, see
+/// `ClientOptions::expect_single_server`.
+#[derive(Debug, Default)]
+pub struct ServerCheck {
+    enabled: bool,
+    // the largest number of servers of one service and the nodes
+    // serving the services that have several.
+    multiple: Option<(usize, Vec<String>)>,
+}
+
+impl ServerCheck {
+    pub fn new(enabled: bool) -> Self {
+        ServerCheck {
+            enabled,
+            multiple: None,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Queries the graph for the servers of `services`. Reports when
+    /// several servers show up.
+    pub fn update(&mut self, node: &rcl_node_t, services: &[String], errors: &EntityErrors) {
+        if !self.enabled {
+            return;
+        }
+        let mut count = 0;
+        let mut nodes = vec![];
+        for service in services {
+            match service_server_nodes(node, service) {
+                Ok(servers) => {
+                    count = count.max(servers.len());
+                    if servers.len() > 1 {
+                        nodes.extend(servers);
+                    }
+                }
+                Err(e) => {
+                    errors.report(SpinOperation::Update, e);
+                    return;
+                }
+            }
+        }
+        nodes.sort();
+        nodes.dedup();
+        let multiple = if count > 1 { Some((count, nodes)) } else { None };
+        let changed = multiple.is_some() && multiple != self.multiple;
+        self.multiple = multiple;
+        if changed {
+            errors.report(SpinOperation::Update, self.check().unwrap_err());
+        }
+    }
+
+    pub fn check(&self) -> Result<()> {
+        match &self.multiple {
+            Some((count, nodes)) => Err(Error::MultipleServiceServers {
+                count: *count,
+                nodes: nodes.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the fully qualified names of the nodes that serve
+/// `service_name`.
+pub fn service_server_nodes(node: &rcl_node_t, service_name: &str) -> Result<Vec<String>> {
+    let mut servers = vec![];
+    for n in node_names(node)? {
+        let name = CString::new(n.name.as_str()).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
+        let namespace =
+            CString::new(n.namespace.as_str()).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
+        let mut snat = unsafe { rmw_get_zero_initialized_names_and_types() };
+        let ret = unsafe {
+            rcl_get_service_names_and_types_by_node(
+                node,
+                &mut rcutils_get_default_allocator(),
+                name.as_ptr(),
+                namespace.as_ptr(),
+                &mut snat,
+            )
+        };
+        if ret == RCL_RET_NODE_NAME_NON_EXISTENT as i32 {
+            continue; // the node went away.
+        }
+        if ret != RCL_RET_OK as i32 {
+            return Err(Error::from_rcl_error(ret));
+        }
+        let names = if snat.names.size == 0 {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(snat.names.data, snat.names.size) }
+        };
+        let serves = names
+            .iter()
+            .any(|s| unsafe { CStr::from_ptr(*s) }.to_str() == Ok(service_name));
+        unsafe {
+            rmw_names_and_types_fini(&mut snat);
+        } // TODO: check return value
+        if serves {
+            servers.push(n.fully_qualified_name());
+        }
+    }
+    Ok(servers)
+}
+
+pub fn client_service_name(rcl_handle: &rcl_client_t) -> Result<String> {
+    let cstr = unsafe { rcl_client_get_service_name(rcl_handle) };
+    if cstr == std::ptr::null() {
+        return Err(Error::RCL_RET_CLIENT_INVALID);
+    }
+    let s = unsafe { CStr::from_ptr(cstr) };
+    Ok(s.to_str().unwrap_or("").to_owned())
+}
+
 pub fn create_client_helper(
     node: *mut rcl_node_t,
     service_name: &str,
@@ -327,7 +640,7 @@ pub fn create_client_helper(
     let service_name_c_string =
         CString::new(service_name).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
 
-    let result = unsafe {
+    rcl_call(|| unsafe {
         let client_options = rcl_client_get_default_options();
         rcl_client_init(
             &mut client_handle,
@@ -336,12 +649,8 @@ pub fn create_client_helper(
             service_name_c_string.as_ptr(),
             &client_options,
         )
-    };
-    if result == RCL_RET_OK as i32 {
-        Ok(client_handle)
-    } else {
-        Err(Error::from_rcl_error(result))
-    }
+    })?;
+    Ok(client_handle)
 }
 
 pub fn service_available_helper(node: &mut rcl_node_t, client: &rcl_client_t) -> Result<bool> {
@@ -361,7 +670,7 @@ impl<T: 'static> IsAvailablePollable for Client<T>
 where
     T: WrappedServiceTypeSupport,
 {
-    fn register_poll_available(&self, sender: oneshot::Sender<()>) -> Result<()> {
+    fn register_poll_available(&self, sender: oneshot::Sender<Result<()>>) -> Result<()> {
         let client = self.client.upgrade().ok_or(Error::RCL_RET_CLIENT_INVALID)?;
         let mut client = client.lock().unwrap();
         client.register_poll_available(sender);
@@ -370,10 +679,60 @@ where
 }
 
 impl IsAvailablePollable for ClientUntyped {
-    fn register_poll_available(&self, sender: oneshot::Sender<()>) -> Result<()> {
+    fn register_poll_available(&self, sender: oneshot::Sender<Result<()>>) -> Result<()> {
         let client = self.client.upgrade().ok_or(Error::RCL_RET_CLIENT_INVALID)?;
         let mut client = client.lock().unwrap();
         client.register_poll_available(sender);
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_id(guid: i8, sequence_number: i64) -> rmw_request_id_t {
+        rmw_request_id_t {
+            writer_guid: [guid; 16],
+            sequence_number,
+        }
+    }
+
+    #[test]
+    fn test_response_guard() {
+        let errors = ErrorSink::new().entity(crate::executor::EntityKind::Client, "/test");
+        let mut guard = ResponseGuard {
+            writer_guid: Some([1; 16]),
+            stale_responses: 0,
+        };
+        // a stale response arriving first must not be trusted.
+        assert!(!guard.accept(&request_id(2, 1), &errors));
+        assert!(guard.accept(&request_id(1, 1), &errors));
+        assert!(guard.accept(&request_id(1, 2), &errors));
+        // same sequence number from another client instance.
+        assert!(!guard.accept(&request_id(2, 2), &errors));
+        guard.reject();
+        assert!(guard.accept(&request_id(1, 3), &errors));
+        assert_eq!(guard.stale_responses(), 3);
+
+        // without the guid of our writer only sequence numbers are matched.
+        let mut guard = ResponseGuard::default();
+        assert!(guard.accept(&request_id(2, 1), &errors));
+        assert!(guard.accept(&request_id(1, 2), &errors));
+        assert_eq!(guard.stale_responses(), 0);
+    }
+
+    #[test]
+    fn test_server_check() {
+        let mut check = ServerCheck::new(true);
+        assert!(check.check().is_ok());
+        check.multiple = Some((2, vec!["/a".into(), "/b".into()]));
+        match check.check() {
+            Err(Error::MultipleServiceServers { count, nodes }) => {
+                assert_eq!(count, 2);
+                assert_eq!(nodes, vec!["/a".to_owned(), "/b".to_owned()]);
+            }
+            r => panic!("expected MultipleServiceServers, got {:?}", r),
+        }
+    }
+}
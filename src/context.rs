@@ -3,62 +3,120 @@ use std::fmt::Debug;
 use std::ops::{Deref, DerefMut};
 use std::sync::{Arc, Mutex};
 
+use lazy_static::lazy_static;
+
+use crate::arguments::*;
 use crate::error::*;
 use crate::log_guard;
+use crate::log_handler::{LogHandler, LogHandlerSlot};
+use crate::threads::ThreadHooks;
 use r2r_rcl::*;
 
+lazy_static! {
+    // The number of live contexts. Logging is process global in rcl, it
+    // is configured by the first context and shut down with the last.
+    // Also serializes the creation and destruction of contexts.
+    static ref LIVE_CONTEXTS: Mutex<usize> = Mutex::new(0);
+}
+
+/// Options for `Context::create_with_options`.
+#[derive(Debug, Clone, Default)]
+pub struct ContextOptions {
+    /// The arguments given to rcl, in the form of `std::env::args`
+    /// (starting with the program name), e.g. `["app", "--ros-args",
+    /// "-r", "__ns:=/robot"]`. Defaults to the command line of the
+    /// process.
+    pub args: Option<Vec<String>>,
+}
+
 /// A ROS context. Needed to create nodes etc.
+///
+/// A process can have any number of contexts at the same time, each
+/// with its own arguments and nodes. Dropping one does not affect the
+/// others. Logging is shared by all of them and configured from the
+/// arguments of the first one.
 #[derive(Debug, Clone)]
 pub struct Context {
     pub(crate) context_handle: Arc<Mutex<ContextHandle>>,
+    // the arguments given to rcl_init
+    pub(crate) args: Arc<Vec<String>>,
+    thread_hooks: Arc<Mutex<ThreadHooks>>,
+    pub(crate) log_handler: LogHandlerSlot,
 }
 
 unsafe impl Send for Context {}
 
 impl Context {
-    /// Create a ROS context.
+    /// Create a ROS context from the command line of the process.
     pub fn create() -> Result<Context> {
+        Context::create_with_options(ContextOptions::default())
+    }
+
+    /// Create a ROS context, see `ContextOptions`.
+    pub fn create_with_options(options: ContextOptions) -> Result<Context> {
         let mut ctx: Box<rcl_context_t> = unsafe { Box::new(rcl_get_zero_initialized_context()) };
         // argc/v
-        let args = std::env::args()
-            .map(|arg| CString::new(arg).unwrap())
+        let args = options
+            .args
+            .unwrap_or_else(|| std::env::args().collect::<Vec<String>>());
+        let cstr_args = args
+            .iter()
+            .map(|arg| CString::new(arg.as_str()).unwrap())
             .collect::<Vec<CString>>();
-        let mut c_args = args
+        let mut c_args = cstr_args
             .iter()
             .map(|arg| arg.as_ptr())
             .collect::<Vec<*const ::std::os::raw::c_char>>();
         c_args.push(std::ptr::null());
 
-        let is_valid = unsafe {
-            let allocator = rcutils_get_default_allocator();
-            let mut init_options = rcl_get_zero_initialized_init_options();
-            rcl_init_options_init(&mut init_options, allocator);
+        let mut live_contexts = LIVE_CONTEXTS.lock().unwrap();
+        let mut init_options = unsafe { rcl_get_zero_initialized_init_options() };
+        let ret = unsafe { rcl_init_options_init(&mut init_options, rcutils_get_default_allocator()) };
+        if ret != RCL_RET_OK as i32 {
+            return Err(Error::from_rcl_error(ret));
+        }
+        let ret = rcl_call(|| unsafe {
             rcl_init(
                 (c_args.len() - 1) as ::std::os::raw::c_int,
                 c_args.as_ptr(),
                 &init_options,
                 ctx.as_mut(),
-            );
+            )
+        });
+        unsafe {
             rcl_init_options_fini(&mut init_options as *mut _);
-            rcl_context_is_valid(ctx.as_mut())
-        };
+        }
+        if let Err(err) = ret {
+            unsafe {
+                rcl_context_fini(ctx.as_mut());
+            }
+            return Err(err);
+        }
 
-        let logging_ok = unsafe {
-            let _guard = log_guard();
-            let ret = rcl_logging_configure(
-                &ctx.as_ref().global_arguments,
-                &rcutils_get_default_allocator(),
-            );
-            ret == RCL_RET_OK as i32
-        };
-
-        if is_valid && logging_ok {
-            Ok(Context {
-                context_handle: Arc::new(Mutex::new(ContextHandle(ctx))),
-            })
-        } else {
-            Err(Error::RCL_RET_ERROR) // TODO
+        if *live_contexts == 0 {
+            let ret = rcl_call(|| unsafe {
+                let _guard = log_guard();
+                rcl_logging_configure(
+                    &ctx.as_ref().global_arguments,
+                    &rcutils_get_default_allocator(),
+                )
+            });
+            if let Err(err) = ret {
+                unsafe {
+                    rcl_shutdown(ctx.as_mut());
+                    rcl_context_fini(ctx.as_mut());
+                }
+                return Err(err);
+            }
         }
+        *live_contexts += 1;
+
+        Ok(Context {
+            context_handle: Arc::new(Mutex::new(ContextHandle(ctx))),
+            args: Arc::new(args),
+            thread_hooks: Arc::new(Mutex::new(ThreadHooks::default())),
+            log_handler: LogHandlerSlot::default(),
+        })
     }
 
     /// Check if the ROS context is valid.
@@ -68,6 +126,30 @@ impl Context {
         let mut ctx = self.context_handle.lock().unwrap();
         unsafe { rcl_context_is_valid(ctx.as_mut()) }
     }
+
+    /// The command line arguments of the process as rcl parsed them.
+    pub fn global_arguments(&self) -> Result<RosArguments> {
+        let ctx = self.context_handle.lock().unwrap();
+        global_arguments(&self.args, &ctx.global_arguments)
+    }
+
+    /// Name the threads that r2r starts for nodes of this context, and
+    /// run `hooks` on them. Only applies to threads started afterwards.
+    pub fn set_thread_hooks(&self, hooks: ThreadHooks) {
+        *self.thread_hooks.lock().unwrap() = hooks;
+    }
+
+    /// See `set_thread_hooks`, e.g. for `MultiThreadedExecutor::with_thread_hooks`.
+    pub fn thread_hooks(&self) -> ThreadHooks {
+        self.thread_hooks.lock().unwrap().clone()
+    }
+
+    /// Handle the messages r2r logs itself for the nodes of this
+    /// context, e.g. errors while spinning. `None` falls back to the
+    /// handler of the process, see `set_log_handler`.
+    pub fn set_log_handler(&self, handler: Option<LogHandler>) {
+        self.log_handler.set(handler);
+    }
 }
 
 #[derive(Debug)]
@@ -90,10 +172,18 @@ impl DerefMut for ContextHandle {
 impl Drop for ContextHandle {
     fn drop(&mut self) {
         // TODO: error handling? atleast probably need rcl_reset_error
+        let mut live_contexts = LIVE_CONTEXTS.lock().unwrap();
         unsafe {
             rcl_shutdown(self.0.as_mut());
             rcl_context_fini(self.0.as_mut());
         }
+        *live_contexts -= 1;
+        if *live_contexts == 0 {
+            let _guard = log_guard();
+            unsafe {
+                rcl_logging_fini();
+            }
+        }
     }
 }
 
@@ -112,4 +202,25 @@ mod tests {
             assert!(ctx.is_valid());
         }
     }
+
+    #[test]
+    fn test_context_args() {
+        let ctx = Context::create_with_options(ContextOptions {
+            args: Some(vec![
+                "test".into(),
+                "--ros-args".into(),
+                "-r".into(),
+                "__ns:=/context_args".into(),
+                "--".into(),
+                "extra".into(),
+            ]),
+        })
+        .unwrap();
+        let other = Context::create().unwrap();
+        assert!(ctx.is_valid() && other.is_valid());
+        let args = ctx.global_arguments().unwrap();
+        assert_eq!(args.unparsed, vec!["test".to_owned(), "extra".to_owned()]);
+        drop(other);
+        assert!(ctx.is_valid());
+    }
 }
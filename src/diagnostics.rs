@@ -0,0 +1,254 @@
+//! Diagnostics of a node, published like the `diagnostic_updater`
+//! of rclcpp does.
+//!
+//! A `DiagnosticUpdater` runs its tasks on a timer of the node and
+//! publishes one `diagnostic_msgs/msg/DiagnosticStatus` per task in a
+//! `DiagnosticArray`, usually on "/diagnostics" where the
+//! `diagnostic_aggregator` and `rqt_robot_monitor` expect them.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::error::*;
+use crate::msg_types::generated_msgs::builtin_interfaces;
+use crate::nodes::Node;
+use crate::periodic::{PeriodicMessage, PeriodicPublisher_};
+
+pub(crate) const DIAGNOSTICS_MSG_TYPE: &str = "diagnostic_msgs/msg/DiagnosticArray";
+
+/// The level of a `DiagnosticStatus`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DiagnosticLevel {
+    Ok,
+    Warn,
+    Error,
+    Stale,
+}
+
+impl DiagnosticLevel {
+    fn as_byte(self) -> u8 {
+        match self {
+            DiagnosticLevel::Ok => 0,
+            DiagnosticLevel::Warn => 1,
+            DiagnosticLevel::Error => 2,
+            DiagnosticLevel::Stale => 3,
+        }
+    }
+}
+
+impl Default for DiagnosticLevel {
+    fn default() -> Self {
+        DiagnosticLevel::Ok
+    }
+}
+
+/// The status a diagnostic task reports, see `DiagnosticUpdater::add`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiagnosticStatus {
+    pub level: DiagnosticLevel,
+    pub name: String,
+    pub message: String,
+    pub hardware_id: String,
+    /// Key-value pairs, published in the order they were added.
+    pub values: Vec<(String, String)>,
+}
+
+impl DiagnosticStatus {
+    /// Set the level and the message.
+    pub fn summary(&mut self, level: DiagnosticLevel, message: &str) {
+        self.level = level;
+        self.message = message.to_owned();
+    }
+
+    /// Add a key-value pair.
+    pub fn add(&mut self, key: &str, value: impl ToString) {
+        self.values.push((key.to_owned(), value.to_string()));
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let values = self
+            .values
+            .iter()
+            .map(|(key, value)| serde_json::json!({ "key": key, "value": value }))
+            .collect::<Vec<_>>();
+        serde_json::json!({
+            "level": self.level.as_byte(),
+            "name": self.name,
+            "message": self.message,
+            "hardware_id": self.hardware_id,
+            "values": values,
+        })
+    }
+}
+
+/// A `diagnostic_msgs/msg/DiagnosticArray` with `statuses`, as json.
+pub(crate) fn diagnostic_array(
+    statuses: &[DiagnosticStatus],
+    stamp: &builtin_interfaces::msg::Time,
+) -> serde_json::Value {
+    serde_json::json!({
+        "header": {
+            "stamp": { "sec": stamp.sec, "nanosec": stamp.nanosec },
+            "frame_id": "",
+        },
+        "status": statuses.iter().map(DiagnosticStatus::to_json).collect::<Vec<_>>(),
+    })
+}
+
+type DiagnosticTask = Box<dyn FnMut(&mut DiagnosticStatus) -> Result<()> + Send>;
+
+struct Updater {
+    node_name: String,
+    hardware_id: String,
+    tasks: Vec<(String, DiagnosticTask)>,
+}
+
+impl Updater {
+    // A task that fails reports the error instead of its status.
+    fn statuses(&mut self) -> Vec<DiagnosticStatus> {
+        let node_name = &self.node_name;
+        let hardware_id = &self.hardware_id;
+        self.tasks
+            .iter_mut()
+            .map(|(name, task)| {
+                let mut status = DiagnosticStatus {
+                    name: format!("{}: {}", node_name, name),
+                    hardware_id: hardware_id.clone(),
+                    ..Default::default()
+                };
+                if let Err(e) = task(&mut status) {
+                    status.summary(DiagnosticLevel::Error, &e.to_string());
+                }
+                status
+            })
+            .collect()
+    }
+}
+
+/// Publishes the diagnostics of a node until dropped.
+///
+/// ```ignore
+/// let updater = DiagnosticUpdater::new(&mut node, Duration::from_secs(1))?;
+/// updater.add("battery", move |status| {
+///     let voltage = read_voltage()?;
+///     if voltage < 11.0 {
+///         status.summary(DiagnosticLevel::Warn, "low");
+///     }
+///     status.add("voltage", voltage);
+///     Ok(())
+/// });
+/// ```
+#[derive(Clone)]
+pub struct DiagnosticUpdater {
+    updater: Arc<Mutex<Updater>>,
+}
+
+impl DiagnosticUpdater {
+    /// Publish the statuses of the tasks on "/diagnostics" every
+    /// `period`.
+    pub fn new(node: &mut Node, period: Duration) -> Result<DiagnosticUpdater> {
+        Self::with_topic(node, "/diagnostics", period)
+    }
+
+    /// Like `new`, but publishes on `topic`.
+    pub fn with_topic(node: &mut Node, topic: &str, period: Duration) -> Result<DiagnosticUpdater> {
+        let node_name = node.fully_qualified_name()?;
+        let updater = Arc::new(Mutex::new(Updater {
+            hardware_id: node_name.clone(),
+            node_name,
+            tasks: Vec::new(),
+        }));
+        let source = UpdaterSource {
+            updater: updater.clone(),
+        };
+        PeriodicPublisher_::start(node, topic, DIAGNOSTICS_MSG_TYPE, period, Box::new(source))?;
+        Ok(DiagnosticUpdater { updater })
+    }
+
+    /// The hardware id of all statuses, the fully qualified name of the
+    /// node by default.
+    pub fn set_hardware_id(&self, hardware_id: &str) {
+        self.updater.lock().unwrap().hardware_id = hardware_id.to_owned();
+    }
+
+    /// Add a task that fills in a status on each update. The status is
+    /// named "<node>: <name>", and a task that returns an error reports
+    /// it with level `Error`.
+    pub fn add<F>(&self, name: &str, task: F)
+    where
+        F: FnMut(&mut DiagnosticStatus) -> Result<()> + Send + 'static,
+    {
+        self.updater
+            .lock()
+            .unwrap()
+            .tasks
+            .push((name.to_owned(), Box::new(task)));
+    }
+
+    /// Remove the tasks named `name`. Returns false if there were none.
+    pub fn remove(&self, name: &str) -> bool {
+        let tasks = &mut self.updater.lock().unwrap().tasks;
+        let before = tasks.len();
+        tasks.retain(|(n, _)| n != name);
+        tasks.len() != before
+    }
+}
+
+struct UpdaterSource {
+    updater: Arc<Mutex<Updater>>,
+}
+
+impl PeriodicMessage for UpdaterSource {
+    fn is_dropped(&self) -> bool {
+        Arc::strong_count(&self.updater) == 1
+    }
+
+    fn message(&mut self, stamp: &builtin_interfaces::msg::Time) -> Result<serde_json::Value> {
+        let statuses = self.updater.lock().unwrap().statuses();
+        Ok(diagnostic_array(&statuses, stamp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statuses() {
+        let mut updater = Updater {
+            node_name: "/ns/node".to_owned(),
+            hardware_id: "robot".to_owned(),
+            tasks: Vec::new(),
+        };
+        updater.tasks.push((
+            "battery".to_owned(),
+            Box::new(|status: &mut DiagnosticStatus| {
+                status.summary(DiagnosticLevel::Warn, "low");
+                status.add("voltage", 10.5);
+                Ok(())
+            }),
+        ));
+        updater.tasks.push((
+            "camera".to_owned(),
+            Box::new(|_: &mut DiagnosticStatus| Err(Error::RCL_RET_TIMEOUT)),
+        ));
+
+        let statuses = updater.statuses();
+        assert_eq!(statuses[0].name, "/ns/node: battery");
+        assert_eq!(statuses[0].hardware_id, "robot");
+        assert_eq!(statuses[0].level, DiagnosticLevel::Warn);
+        assert_eq!(
+            statuses[0].values,
+            vec![("voltage".to_owned(), "10.5".to_owned())]
+        );
+        assert_eq!(statuses[1].level, DiagnosticLevel::Error);
+        assert_eq!(statuses[1].message, Error::RCL_RET_TIMEOUT.to_string());
+
+        let stamp = builtin_interfaces::msg::Time { sec: 1, nanosec: 2 };
+        let msg = diagnostic_array(&statuses, &stamp);
+        assert_eq!(msg["status"][0]["level"], 1);
+        assert_eq!(msg["status"][0]["values"][0]["key"], "voltage");
+        assert_eq!(msg["status"][1]["level"], 2);
+        assert_eq!(msg["header"]["stamp"]["nanosec"], 2);
+    }
+}
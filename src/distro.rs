@@ -0,0 +1,628 @@
+//! Differences between the ROS distributions r2r can be built against.
+//!
+//! `build.rs` sets the cfg `r2r__ros__distro__<name>` from `ROS_DISTRO`.
+//! rcl functions and struct fields that only exist in some
+//! distributions are wrapped by the functions in this module, so that
+//! the rest of the crate compiles against all of them. Where a
+//! distribution lacks something, the wrapper returns
+//! `Error::NotSupported`. Distributions newer than the ones listed here
+//! are treated like the newest one, so supporting a new distribution
+//! should only require changes in this file.
+
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+use std::time::Duration;
+
+use r2r_rcl::*;
+
+use crate::error::*;
+use crate::message_filter::ContentFilter;
+use crate::publishers::FlushStatus;
+use crate::services::ServiceIntrospection;
+
+/// The `ROS_DISTRO` r2r was built for, e.g. "humble".
+pub fn ros_distro() -> &'static str {
+    env!("R2R_ROS_DISTRO")
+}
+
+fn not_supported(feature: &str) -> Error {
+    Error::NotSupported {
+        feature: feature.to_owned(),
+    }
+}
+
+#[cfg(r2r__ros__distro__foxy)]
+mod imp {
+    use super::*;
+
+    pub(super) const SHIM: &str = "foxy";
+
+    // the BEST_AVAILABLE QoS policies are new in iron.
+    pub(crate) const RELIABILITY_BEST_AVAILABLE: Option<rmw_qos_reliability_policy_t> = None;
+    pub(crate) const DURABILITY_BEST_AVAILABLE: Option<rmw_qos_durability_policy_t> = None;
+    pub(crate) const LIVELINESS_BEST_AVAILABLE: Option<rmw_qos_liveliness_policy_t> = None;
+
+    pub(crate) fn publisher_wait_for_all_acked(
+        _publisher: &rcl_publisher_t,
+        _timeout: Duration,
+    ) -> Result<FlushStatus> {
+        Err(not_supported("waiting for acknowledgments"))
+    }
+
+    pub(crate) fn endpoint_type_hash(_info: &rmw_topic_endpoint_info_t) -> Result<String> {
+        Err(not_supported("type hashes"))
+    }
+
+    pub(crate) fn message_type_hash(_ts: &rosidl_message_type_support_t) -> Result<String> {
+        Err(not_supported("type hashes"))
+    }
+
+    pub(crate) fn action_type_hash(_ts: &rosidl_action_type_support_t) -> Result<String> {
+        Err(not_supported("type hashes"))
+    }
+
+    pub(crate) fn configure_service_introspection(
+        _service: &mut rcl_service_t,
+        _node: &mut rcl_node_t,
+        _clock: &mut rcl_clock_t,
+        _ts: &rosidl_service_type_support_t,
+        _introspection: ServiceIntrospection,
+    ) -> Result<()> {
+        Err(not_supported("service introspection"))
+    }
+
+    pub(crate) fn configure_client_introspection(
+        _client: &mut rcl_client_t,
+        _node: &mut rcl_node_t,
+        _clock: &mut rcl_clock_t,
+        _ts: &rosidl_service_type_support_t,
+        _introspection: ServiceIntrospection,
+    ) -> Result<()> {
+        Err(not_supported("service introspection"))
+    }
+
+    pub(crate) fn publisher_matched_event_init(
+        _event: &mut rcl_event_t,
+        _publisher: &rcl_publisher_t,
+    ) -> Result<()> {
+        Err(not_supported("matched events"))
+    }
+
+    pub(crate) fn take_new_matches(_event: &rcl_event_t) -> Result<bool> {
+        Err(not_supported("matched events"))
+    }
+
+    pub(crate) fn set_content_filter(
+        _options: &mut rcl_subscription_options_t,
+        _filter: &ContentFilter,
+    ) -> Result<()> {
+        Err(not_supported("content filters"))
+    }
+
+    pub(crate) fn content_filter_enabled(_subscription: &rcl_subscription_t) -> bool {
+        false
+    }
+
+    pub(crate) fn publication_sequence_number(_info: &rmw_message_info_t) -> Option<u64> {
+        None
+    }
+
+    // rmw_get_gid_for_client is new in iron.
+    pub(crate) fn client_writer_guid(_client: &rcl_client_t) -> Result<[i8; 16]> {
+        Err(not_supported("client gids"))
+    }
+}
+
+#[cfg(r2r__ros__distro__galactic)]
+mod imp {
+    use super::*;
+
+    pub(super) const SHIM: &str = "galactic";
+
+    // the BEST_AVAILABLE QoS policies are new in iron.
+    pub(crate) const RELIABILITY_BEST_AVAILABLE: Option<rmw_qos_reliability_policy_t> = None;
+    pub(crate) const DURABILITY_BEST_AVAILABLE: Option<rmw_qos_durability_policy_t> = None;
+    pub(crate) const LIVELINESS_BEST_AVAILABLE: Option<rmw_qos_liveliness_policy_t> = None;
+
+    pub(crate) fn publisher_wait_for_all_acked(
+        publisher: &rcl_publisher_t,
+        timeout: Duration,
+    ) -> Result<FlushStatus> {
+        wait_for_all_acked(publisher, timeout)
+    }
+
+    pub(crate) fn endpoint_type_hash(_info: &rmw_topic_endpoint_info_t) -> Result<String> {
+        Err(not_supported("type hashes"))
+    }
+
+    pub(crate) fn message_type_hash(_ts: &rosidl_message_type_support_t) -> Result<String> {
+        Err(not_supported("type hashes"))
+    }
+
+    pub(crate) fn action_type_hash(_ts: &rosidl_action_type_support_t) -> Result<String> {
+        Err(not_supported("type hashes"))
+    }
+
+    pub(crate) fn configure_service_introspection(
+        _service: &mut rcl_service_t,
+        _node: &mut rcl_node_t,
+        _clock: &mut rcl_clock_t,
+        _ts: &rosidl_service_type_support_t,
+        _introspection: ServiceIntrospection,
+    ) -> Result<()> {
+        Err(not_supported("service introspection"))
+    }
+
+    pub(crate) fn configure_client_introspection(
+        _client: &mut rcl_client_t,
+        _node: &mut rcl_node_t,
+        _clock: &mut rcl_clock_t,
+        _ts: &rosidl_service_type_support_t,
+        _introspection: ServiceIntrospection,
+    ) -> Result<()> {
+        Err(not_supported("service introspection"))
+    }
+
+    pub(crate) fn publisher_matched_event_init(
+        _event: &mut rcl_event_t,
+        _publisher: &rcl_publisher_t,
+    ) -> Result<()> {
+        Err(not_supported("matched events"))
+    }
+
+    pub(crate) fn take_new_matches(_event: &rcl_event_t) -> Result<bool> {
+        Err(not_supported("matched events"))
+    }
+
+    pub(crate) fn set_content_filter(
+        _options: &mut rcl_subscription_options_t,
+        _filter: &ContentFilter,
+    ) -> Result<()> {
+        Err(not_supported("content filters"))
+    }
+
+    pub(crate) fn content_filter_enabled(_subscription: &rcl_subscription_t) -> bool {
+        false
+    }
+
+    pub(crate) fn publication_sequence_number(info: &rmw_message_info_t) -> Option<u64> {
+        sequence_number(info.publication_sequence_number)
+    }
+
+    // rmw_get_gid_for_client is new in iron.
+    pub(crate) fn client_writer_guid(_client: &rcl_client_t) -> Result<[i8; 16]> {
+        Err(not_supported("client gids"))
+    }
+}
+
+#[cfg(r2r__ros__distro__humble)]
+mod imp {
+    use super::*;
+
+    pub(super) const SHIM: &str = "humble";
+
+    // the BEST_AVAILABLE QoS policies are new in iron.
+    pub(crate) const RELIABILITY_BEST_AVAILABLE: Option<rmw_qos_reliability_policy_t> = None;
+    pub(crate) const DURABILITY_BEST_AVAILABLE: Option<rmw_qos_durability_policy_t> = None;
+    pub(crate) const LIVELINESS_BEST_AVAILABLE: Option<rmw_qos_liveliness_policy_t> = None;
+
+    pub(crate) fn publisher_wait_for_all_acked(
+        publisher: &rcl_publisher_t,
+        timeout: Duration,
+    ) -> Result<FlushStatus> {
+        wait_for_all_acked(publisher, timeout)
+    }
+
+    pub(crate) fn endpoint_type_hash(_info: &rmw_topic_endpoint_info_t) -> Result<String> {
+        Err(not_supported("type hashes"))
+    }
+
+    pub(crate) fn message_type_hash(_ts: &rosidl_message_type_support_t) -> Result<String> {
+        Err(not_supported("type hashes"))
+    }
+
+    pub(crate) fn action_type_hash(_ts: &rosidl_action_type_support_t) -> Result<String> {
+        Err(not_supported("type hashes"))
+    }
+
+    pub(crate) fn configure_service_introspection(
+        _service: &mut rcl_service_t,
+        _node: &mut rcl_node_t,
+        _clock: &mut rcl_clock_t,
+        _ts: &rosidl_service_type_support_t,
+        _introspection: ServiceIntrospection,
+    ) -> Result<()> {
+        Err(not_supported("service introspection"))
+    }
+
+    pub(crate) fn configure_client_introspection(
+        _client: &mut rcl_client_t,
+        _node: &mut rcl_node_t,
+        _clock: &mut rcl_clock_t,
+        _ts: &rosidl_service_type_support_t,
+        _introspection: ServiceIntrospection,
+    ) -> Result<()> {
+        Err(not_supported("service introspection"))
+    }
+
+    pub(crate) fn publisher_matched_event_init(
+        _event: &mut rcl_event_t,
+        _publisher: &rcl_publisher_t,
+    ) -> Result<()> {
+        Err(not_supported("matched events"))
+    }
+
+    pub(crate) fn take_new_matches(_event: &rcl_event_t) -> Result<bool> {
+        Err(not_supported("matched events"))
+    }
+
+    pub(crate) fn set_content_filter(
+        options: &mut rcl_subscription_options_t,
+        filter: &ContentFilter,
+    ) -> Result<()> {
+        set_subscription_content_filter(options, filter)
+    }
+
+    pub(crate) fn content_filter_enabled(subscription: &rcl_subscription_t) -> bool {
+        unsafe { rcl_subscription_is_cftopic_enabled(subscription) }
+    }
+
+    pub(crate) fn publication_sequence_number(info: &rmw_message_info_t) -> Option<u64> {
+        sequence_number(info.publication_sequence_number)
+    }
+
+    // rmw_get_gid_for_client is new in iron.
+    pub(crate) fn client_writer_guid(_client: &rcl_client_t) -> Result<[i8; 16]> {
+        Err(not_supported("client gids"))
+    }
+}
+
+// iron and newer
+#[cfg(not(any(
+    r2r__ros__distro__foxy,
+    r2r__ros__distro__galactic,
+    r2r__ros__distro__humble
+)))]
+mod imp {
+    use super::*;
+
+    pub(super) const SHIM: &str = "iron";
+
+    pub(crate) const RELIABILITY_BEST_AVAILABLE: Option<rmw_qos_reliability_policy_t> =
+        Some(rmw_qos_reliability_policy_t::RMW_QOS_POLICY_RELIABILITY_BEST_AVAILABLE);
+    pub(crate) const DURABILITY_BEST_AVAILABLE: Option<rmw_qos_durability_policy_t> =
+        Some(rmw_qos_durability_policy_t::RMW_QOS_POLICY_DURABILITY_BEST_AVAILABLE);
+    pub(crate) const LIVELINESS_BEST_AVAILABLE: Option<rmw_qos_liveliness_policy_t> =
+        Some(rmw_qos_liveliness_policy_t::RMW_QOS_POLICY_LIVELINESS_BEST_AVAILABLE);
+
+    pub(crate) fn publisher_wait_for_all_acked(
+        publisher: &rcl_publisher_t,
+        timeout: Duration,
+    ) -> Result<FlushStatus> {
+        wait_for_all_acked(publisher, timeout)
+    }
+
+    pub(crate) fn endpoint_type_hash(info: &rmw_topic_endpoint_info_t) -> Result<String> {
+        let hash = &info.topic_type_hash;
+        Ok(format_type_hash(hash.version, &hash.value))
+    }
+
+    pub(crate) fn message_type_hash(ts: &rosidl_message_type_support_t) -> Result<String> {
+        let get_type_hash = ts
+            .get_type_hash_func
+            .ok_or_else(|| not_supported("type hashes"))?;
+        read_type_hash(unsafe { get_type_hash(ts) })
+    }
+
+    pub(crate) fn action_type_hash(ts: &rosidl_action_type_support_t) -> Result<String> {
+        let get_type_hash = ts
+            .get_type_hash_func
+            .ok_or_else(|| not_supported("type hashes"))?;
+        read_type_hash(unsafe { get_type_hash(ts) })
+    }
+
+    pub(crate) fn configure_service_introspection(
+        service: &mut rcl_service_t,
+        node: &mut rcl_node_t,
+        clock: &mut rcl_clock_t,
+        ts: &rosidl_service_type_support_t,
+        introspection: ServiceIntrospection,
+    ) -> Result<()> {
+        rcl_call(|| unsafe {
+            rcl_service_configure_service_introspection(
+                service,
+                node,
+                clock,
+                ts,
+                rcl_publisher_get_default_options(),
+                introspection_state(introspection),
+            )
+        })
+    }
+
+    pub(crate) fn configure_client_introspection(
+        client: &mut rcl_client_t,
+        node: &mut rcl_node_t,
+        clock: &mut rcl_clock_t,
+        ts: &rosidl_service_type_support_t,
+        introspection: ServiceIntrospection,
+    ) -> Result<()> {
+        rcl_call(|| unsafe {
+            rcl_client_configure_service_introspection(
+                client,
+                node,
+                clock,
+                ts,
+                rcl_publisher_get_default_options(),
+                introspection_state(introspection),
+            )
+        })
+    }
+
+    pub(crate) fn publisher_matched_event_init(
+        event: &mut rcl_event_t,
+        publisher: &rcl_publisher_t,
+    ) -> Result<()> {
+        let ret = unsafe {
+            rcl_publisher_event_init(
+                event,
+                publisher,
+                rcl_publisher_event_type_t::RCL_PUBLISHER_MATCHED,
+            )
+        };
+        // not all rmw implementations have them.
+        if ret == RCL_RET_UNSUPPORTED as i32 {
+            return Err(not_supported("matched events"));
+        }
+        rcl_call(|| ret)
+    }
+
+    pub(crate) fn take_new_matches(event: &rcl_event_t) -> Result<bool> {
+        let mut status: rmw_matched_status_t = unsafe { std::mem::zeroed() };
+        let ret = unsafe { rcl_take_event(event, &mut status as *mut _ as *mut c_void) };
+        if ret == RCL_RET_EVENT_TAKE_FAILED as i32 {
+            return Ok(false);
+        }
+        rcl_call(|| ret)?;
+        Ok(status.total_count_change > 0)
+    }
+
+    fn introspection_state(
+        introspection: ServiceIntrospection,
+    ) -> rcl_service_introspection_state_t {
+        match introspection {
+            ServiceIntrospection::Off => {
+                rcl_service_introspection_state_t::RCL_SERVICE_INTROSPECTION_OFF
+            }
+            ServiceIntrospection::Metadata => {
+                rcl_service_introspection_state_t::RCL_SERVICE_INTROSPECTION_METADATA
+            }
+            ServiceIntrospection::Contents => {
+                rcl_service_introspection_state_t::RCL_SERVICE_INTROSPECTION_CONTENTS
+            }
+        }
+    }
+
+    pub(crate) fn set_content_filter(
+        options: &mut rcl_subscription_options_t,
+        filter: &ContentFilter,
+    ) -> Result<()> {
+        set_subscription_content_filter(options, filter)
+    }
+
+    pub(crate) fn content_filter_enabled(subscription: &rcl_subscription_t) -> bool {
+        unsafe { rcl_subscription_is_cftopic_enabled(subscription) }
+    }
+
+    pub(crate) fn publication_sequence_number(info: &rmw_message_info_t) -> Option<u64> {
+        sequence_number(info.publication_sequence_number)
+    }
+
+    pub(crate) fn client_writer_guid(client: &rcl_client_t) -> Result<[i8; 16]> {
+        let rmw_client = unsafe { rcl_client_get_rmw_handle(client) };
+        if rmw_client.is_null() {
+            return Err(Error::RCL_RET_CLIENT_INVALID);
+        }
+        let mut gid: rmw_gid_t = unsafe { std::mem::zeroed() };
+        let ret = unsafe { rmw_get_gid_for_client(rmw_client, &mut gid) };
+        if ret == RCL_RET_UNSUPPORTED as i32 {
+            return Err(not_supported("client gids"));
+        }
+        rcl_call(|| ret)?;
+        let mut guid = [0; 16];
+        for (g, d) in guid.iter_mut().zip(gid.data.iter()) {
+            *g = *d as i8;
+        }
+        Ok(guid)
+    }
+}
+
+pub(crate) use imp::*;
+
+// 0 is RMW_MESSAGE_INFO_SEQUENCE_NUMBER_UNSUPPORTED.
+#[cfg(not(r2r__ros__distro__foxy))]
+fn sequence_number(n: u64) -> Option<u64> {
+    if n == 0 {
+        None
+    } else {
+        Some(n)
+    }
+}
+
+#[cfg(not(r2r__ros__distro__foxy))]
+fn wait_for_all_acked(publisher: &rcl_publisher_t, timeout: Duration) -> Result<FlushStatus> {
+    let timeout = timeout.as_nanos().min(i64::MAX as u128) as i64;
+    let result = unsafe { rcl_publisher_wait_for_all_acked(publisher, timeout) };
+    if result == RCL_RET_OK as i32 {
+        Ok(FlushStatus::Flushed)
+    } else if result == RCL_RET_TIMEOUT as i32 {
+        Ok(FlushStatus::TimedOut)
+    } else if result == RCL_RET_UNSUPPORTED as i32 {
+        Ok(FlushStatus::Unknown)
+    } else {
+        Err(Error::from_rcl_error(result))
+    }
+}
+
+// The options keep copies of the strings and have to be finalized with
+// rcl_subscription_options_fini.
+#[cfg(not(any(r2r__ros__distro__foxy, r2r__ros__distro__galactic)))]
+fn set_subscription_content_filter(
+    options: &mut rcl_subscription_options_t,
+    filter: &ContentFilter,
+) -> Result<()> {
+    let expression =
+        CString::new(filter.expression.as_str()).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
+    let parameters = filter
+        .parameters
+        .iter()
+        .map(|p| CString::new(p.as_str()).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT))
+        .collect::<Result<Vec<_>>>()?;
+    let mut parameter_ptrs: Vec<*const c_char> = parameters.iter().map(|p| p.as_ptr()).collect();
+    let ret = unsafe {
+        rcl_subscription_options_set_content_filter_options(
+            expression.as_ptr(),
+            parameter_ptrs.len(),
+            parameter_ptrs.as_mut_ptr(),
+            options,
+        )
+    };
+    if ret == RCL_RET_OK as i32 {
+        Ok(())
+    } else {
+        Err(Error::from_rcl_error(ret))
+    }
+}
+
+/// Releases what `set_content_filter` allocated in `options`.
+pub(crate) fn subscription_options_fini(options: &mut rcl_subscription_options_t) {
+    if cfg!(any(r2r__ros__distro__foxy, r2r__ros__distro__galactic)) {
+        return;
+    }
+    #[cfg(not(any(r2r__ros__distro__foxy, r2r__ros__distro__galactic)))]
+    unsafe {
+        rcl_subscription_options_fini(options);
+    }
+}
+
+#[cfg(not(any(
+    r2r__ros__distro__foxy,
+    r2r__ros__distro__galactic,
+    r2r__ros__distro__humble
+)))]
+fn read_type_hash(hash: *const rosidl_type_hash_t) -> Result<String> {
+    if hash.is_null() {
+        return Err(not_supported("type hashes"));
+    }
+    let hash = unsafe { &*hash };
+    Ok(format_type_hash(hash.version, &hash.value))
+}
+
+// Same format as rosidl_stringify_type_hash, e.g. "RIHS01_1b2c...".
+#[allow(dead_code)]
+fn format_type_hash(version: u8, value: &[u8]) -> String {
+    let hex: String = value.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("RIHS{:02}_{}", version, hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shim_selection() {
+        let expected = if cfg!(r2r__ros__distro__foxy) {
+            "foxy"
+        } else if cfg!(r2r__ros__distro__galactic) {
+            "galactic"
+        } else if cfg!(r2r__ros__distro__humble) {
+            "humble"
+        } else {
+            "iron"
+        };
+        assert_eq!(imp::SHIM, expected);
+    }
+
+    #[test]
+    fn test_missing_features_are_not_supported() {
+        let info = unsafe { rmw_get_zero_initialized_topic_endpoint_info() };
+        let hash = endpoint_type_hash(&info);
+        if cfg!(any(
+            r2r__ros__distro__foxy,
+            r2r__ros__distro__galactic,
+            r2r__ros__distro__humble
+        )) {
+            assert!(matches!(hash, Err(Error::NotSupported { .. })));
+        } else {
+            assert_eq!(hash.unwrap(), format!("RIHS00_{}", "0".repeat(64)));
+        }
+
+        let publisher = unsafe { rcl_get_zero_initialized_publisher() };
+        let acked = publisher_wait_for_all_acked(&publisher, Duration::from_millis(1));
+        assert_eq!(
+            matches!(acked, Err(Error::NotSupported { .. })),
+            cfg!(r2r__ros__distro__foxy)
+        );
+
+        let mut options = unsafe { rcl_subscription_get_default_options() };
+        let filter = ContentFilter {
+            expression: "data = %0".to_owned(),
+            parameters: vec!["'a'".to_owned()],
+        };
+        let set = set_content_filter(&mut options, &filter);
+        if cfg!(any(r2r__ros__distro__foxy, r2r__ros__distro__galactic)) {
+            assert!(matches!(set, Err(Error::NotSupported { .. })));
+        } else {
+            assert!(set.is_ok());
+        }
+        subscription_options_fini(&mut options);
+
+        // an invalid publisher or service is an error, but a different
+        // one where the distribution has the feature.
+        let before_iron = cfg!(any(
+            r2r__ros__distro__foxy,
+            r2r__ros__distro__galactic,
+            r2r__ros__distro__humble
+        ));
+        let mut event = unsafe { rcl_get_zero_initialized_event() };
+        let matched = publisher_matched_event_init(&mut event, &publisher);
+        assert!(matched.is_err());
+        assert_eq!(
+            matches!(matched, Err(Error::NotSupported { .. })),
+            before_iron
+        );
+    }
+
+    #[cfg(r2r__example_interfaces__srv__AddTwoInts)]
+    #[test]
+    fn test_service_introspection_not_supported() {
+        use crate::msg_types::generated_msgs::example_interfaces::srv::AddTwoInts;
+        use crate::msg_types::WrappedServiceTypeSupport;
+
+        let before_iron = cfg!(any(
+            r2r__ros__distro__foxy,
+            r2r__ros__distro__galactic,
+            r2r__ros__distro__humble
+        ));
+        let mut service = unsafe { rcl_get_zero_initialized_service() };
+        let mut node = unsafe { rcl_get_zero_initialized_node() };
+        let mut clock: rcl_clock_t = unsafe { std::mem::zeroed() };
+        let introspection = configure_service_introspection(
+            &mut service,
+            &mut node,
+            &mut clock,
+            AddTwoInts::Service::get_ts(),
+            ServiceIntrospection::Contents,
+        );
+        assert!(introspection.is_err());
+        assert_eq!(
+            matches!(introspection, Err(Error::NotSupported { .. })),
+            before_iron
+        );
+    }
+
+    #[test]
+    fn test_format_type_hash() {
+        assert_eq!(format_type_hash(1, &[0x1b, 0x02, 0xff]), "RIHS01_1b02ff");
+    }
+}
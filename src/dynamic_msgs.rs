@@ -0,0 +1,422 @@
+//! Messages of interface packages r2r was not built with.
+//!
+//! Services and action clients created with type support loaded at
+//! runtime (see `InterfaceTypeSupport`) exchange json, like the untyped
+//! ones. Their messages are allocated and converted through the
+//! introspection type support of the package instead of generated code.
+//! Fields missing from the json keep their default values.
+
+use libloading::Library;
+use r2r_msg_gen::*;
+use r2r_rcl::{
+    rosidl_message_type_support_t, rosidl_runtime_c__String, rosidl_runtime_c__String__Sequence,
+    rosidl_runtime_c__String__Sequence__fini, rosidl_runtime_c__String__Sequence__init,
+    rosidl_runtime_c__String__assignn, rosidl_runtime_c__U16String,
+    rosidl_runtime_c__U16String__Sequence, rosidl_runtime_c__U16String__Sequence__fini,
+    rosidl_runtime_c__U16String__Sequence__init, rosidl_runtime_c__U16String__assignn,
+    rosidl_runtime_c__boolean__Sequence, rosidl_runtime_c__char__Sequence,
+    rosidl_runtime_c__float32__Sequence, rosidl_runtime_c__float64__Sequence,
+    rosidl_runtime_c__int16__Sequence, rosidl_runtime_c__int32__Sequence,
+    rosidl_runtime_c__int64__Sequence, rosidl_runtime_c__int8__Sequence,
+    rosidl_runtime_c__octet__Sequence, rosidl_runtime_c__uint16__Sequence,
+    rosidl_runtime_c__uint32__Sequence, rosidl_runtime_c__uint64__Sequence,
+    rosidl_runtime_c__uint8__Sequence, rosidl_runtime_c__wchar__Sequence,
+};
+use serde_json::Value;
+use std::alloc::Layout as AllocLayout;
+use std::convert::TryFrom;
+use std::os::raw::{c_char, c_void};
+use std::sync::Arc;
+
+use crate::error::*;
+use crate::field_access::{
+    member_name, member_step, read_message, DynamicValue, Element, Layout, Members, RawSequence,
+};
+
+/// A message type known only through its introspection type support.
+pub(crate) struct DynamicType {
+    pub(crate) ts: *const rosidl_message_type_support_t,
+    members: Members,
+    // the introspection library of the package, which `ts` points into.
+    _library: Arc<Library>,
+}
+
+// The introspection data is static and never written to.
+unsafe impl Send for DynamicType {}
+unsafe impl Sync for DynamicType {}
+
+impl std::fmt::Debug for DynamicType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("DynamicType")
+            .field(&self.members.name())
+            .finish()
+    }
+}
+
+impl DynamicType {
+    /// # Safety
+    ///
+    /// `ts` must be introspection type support from `library`.
+    pub(crate) unsafe fn new(
+        ts: *const rosidl_message_type_support_t,
+        library: Arc<Library>,
+    ) -> Self {
+        DynamicType {
+            ts,
+            members: Members::new(ts),
+            _library: library,
+        }
+    }
+
+    fn layout(&self) -> AllocLayout {
+        // no rosidl struct needs more than the alignment of a long double.
+        AllocLayout::from_size_align(self.members.size().max(1), 16).expect("a valid message size")
+    }
+
+    /// A message with its fields initialized to their defaults.
+    pub(crate) fn create_msg(&self) -> *mut c_void {
+        let layout = self.layout();
+        unsafe {
+            let msg = std::alloc::alloc_zeroed(layout);
+            if msg.is_null() {
+                std::alloc::handle_alloc_error(layout);
+            }
+            if let Some(init) = (*self.members.0).init_function {
+                init(
+                    msg as *mut c_void,
+                    rosidl_runtime_c__message_initialization::ROSIDL_RUNTIME_C_MSG_INIT_ALL,
+                );
+            }
+            msg as *mut c_void
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `msg` must come from `create_msg` of this type.
+    pub(crate) unsafe fn destroy_msg(&self, msg: *mut c_void) {
+        if let Some(fini) = (*self.members.0).fini_function {
+            fini(msg);
+        }
+        std::alloc::dealloc(msg as *mut u8, self.layout());
+    }
+
+    /// # Safety
+    ///
+    /// `msg` must come from `create_msg` of this type.
+    pub(crate) unsafe fn to_json(&self, msg: *const c_void) -> Value {
+        json_value(read_message(msg as *const u8, self.members))
+    }
+
+    /// Fails with `Error::SerdeError` if `json` does not fit the type,
+    /// possibly after having written some of the fields.
+    ///
+    /// # Safety
+    ///
+    /// `msg` must come from `create_msg` of this type.
+    pub(crate) unsafe fn from_json(&self, msg: *mut c_void, json: &Value) -> Result<()> {
+        write_message(msg as *mut u8, self.members, json)
+    }
+}
+
+fn json_value(value: DynamicValue) -> Value {
+    match value {
+        DynamicValue::Bool(v) => Value::Bool(v),
+        DynamicValue::Int(v) => Value::from(v),
+        DynamicValue::UInt(v) => Value::from(v),
+        // like serde_json, nan and infinity become null.
+        DynamicValue::Float(v) => Value::from(v),
+        DynamicValue::String(v) => Value::String(v),
+        DynamicValue::Array(values) => Value::Array(values.into_iter().map(json_value).collect()),
+        DynamicValue::Message(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(name, v)| (name, json_value(v)))
+                .collect(),
+        ),
+        DynamicValue::Missing => Value::Null,
+    }
+}
+
+fn mismatch(name: &str, expected: &str) -> Error {
+    Error::SerdeError {
+        err: format!("{}: expected {}", name, expected),
+    }
+}
+
+fn uint<T: TryFrom<u64>>(value: &Value, name: &str) -> Result<T> {
+    value
+        .as_u64()
+        .and_then(|v| T::try_from(v).ok())
+        .ok_or_else(|| mismatch(name, "an unsigned integer in range"))
+}
+
+fn int<T: TryFrom<i64>>(value: &Value, name: &str) -> Result<T> {
+    value
+        .as_i64()
+        .and_then(|v| T::try_from(v).ok())
+        .ok_or_else(|| mismatch(name, "an integer in range"))
+}
+
+fn float(value: &Value, name: &str) -> Result<f64> {
+    value.as_f64().ok_or_else(|| mismatch(name, "a number"))
+}
+
+fn string<'a>(value: &'a Value, name: &str) -> Result<&'a str> {
+    value.as_str().ok_or_else(|| mismatch(name, "a string"))
+}
+
+unsafe fn write_primitive(ptr: *mut u8, t: u8, value: &Value, name: &str) -> Result<()> {
+    match t {
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_BOOLEAN as u8 => {
+            *ptr = value.as_bool().ok_or_else(|| mismatch(name, "a bool"))? as u8
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_OCTET as u8
+            || t == rosidl_typesupport_introspection_c__ROS_TYPE_CHAR as u8
+            || t == rosidl_typesupport_introspection_c__ROS_TYPE_UINT8 as u8 =>
+        {
+            *ptr = uint(value, name)?
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_INT8 as u8 => {
+            *(ptr as *mut i8) = int(value, name)?
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_WCHAR as u8
+            || t == rosidl_typesupport_introspection_c__ROS_TYPE_UINT16 as u8 =>
+        {
+            *(ptr as *mut u16) = uint(value, name)?
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_INT16 as u8 => {
+            *(ptr as *mut i16) = int(value, name)?
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_UINT32 as u8 => {
+            *(ptr as *mut u32) = uint(value, name)?
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_INT32 as u8 => {
+            *(ptr as *mut i32) = int(value, name)?
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_UINT64 as u8 => {
+            *(ptr as *mut u64) = uint(value, name)?
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_INT64 as u8 => {
+            *(ptr as *mut i64) = int(value, name)?
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_FLOAT as u8 => {
+            *(ptr as *mut f32) = float(value, name)? as f32
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_DOUBLE as u8 => {
+            *(ptr as *mut f64) = float(value, name)?
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_STRING as u8 => {
+            let s = string(value, name)?;
+            let assigned = rosidl_runtime_c__String__assignn(
+                ptr as *mut rosidl_runtime_c__String,
+                s.as_ptr() as *const c_char,
+                s.len(),
+            );
+            if !assigned {
+                return Err(mismatch(name, "a string that fits in memory"));
+            }
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_WSTRING as u8 => {
+            let s = string(value, name)?.encode_utf16().collect::<Vec<_>>();
+            let assigned = rosidl_runtime_c__U16String__assignn(
+                ptr as *mut rosidl_runtime_c__U16String,
+                s.as_ptr(),
+                s.len(),
+            );
+            if !assigned {
+                return Err(mismatch(name, "a string that fits in memory"));
+            }
+        }
+        _ => unreachable!("checked by member_step"),
+    }
+    Ok(())
+}
+
+// Replaces the primitive sequence at `field` with `len` default
+// elements.
+unsafe fn resize_primitive_sequence(field: *mut u8, t: u8, len: usize) {
+    match t {
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_BOOLEAN as u8 => {
+            (*(field as *mut rosidl_runtime_c__boolean__Sequence)).resize(len)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_OCTET as u8 => {
+            (*(field as *mut rosidl_runtime_c__octet__Sequence)).resize(len)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_CHAR as u8 => {
+            (*(field as *mut rosidl_runtime_c__char__Sequence)).resize(len)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_UINT8 as u8 => {
+            (*(field as *mut rosidl_runtime_c__uint8__Sequence)).resize(len)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_INT8 as u8 => {
+            (*(field as *mut rosidl_runtime_c__int8__Sequence)).resize(len)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_WCHAR as u8 => {
+            (*(field as *mut rosidl_runtime_c__wchar__Sequence)).resize(len)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_UINT16 as u8 => {
+            (*(field as *mut rosidl_runtime_c__uint16__Sequence)).resize(len)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_INT16 as u8 => {
+            (*(field as *mut rosidl_runtime_c__int16__Sequence)).resize(len)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_UINT32 as u8 => {
+            (*(field as *mut rosidl_runtime_c__uint32__Sequence)).resize(len)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_INT32 as u8 => {
+            (*(field as *mut rosidl_runtime_c__int32__Sequence)).resize(len)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_UINT64 as u8 => {
+            (*(field as *mut rosidl_runtime_c__uint64__Sequence)).resize(len)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_INT64 as u8 => {
+            (*(field as *mut rosidl_runtime_c__int64__Sequence)).resize(len)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_FLOAT as u8 => {
+            (*(field as *mut rosidl_runtime_c__float32__Sequence)).resize(len)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_DOUBLE as u8 => {
+            (*(field as *mut rosidl_runtime_c__float64__Sequence)).resize(len)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_STRING as u8 => {
+            let seq = field as *mut rosidl_runtime_c__String__Sequence;
+            rosidl_runtime_c__String__Sequence__fini(seq);
+            rosidl_runtime_c__String__Sequence__init(seq, len);
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_WSTRING as u8 => {
+            let seq = field as *mut rosidl_runtime_c__U16String__Sequence;
+            rosidl_runtime_c__U16String__Sequence__fini(seq);
+            rosidl_runtime_c__U16String__Sequence__init(seq, len);
+        }
+        _ => unreachable!("checked by member_step"),
+    }
+}
+
+unsafe fn write_element(ptr: *mut u8, element: Element, value: &Value, name: &str) -> Result<()> {
+    match element {
+        Element::Primitive(t) => write_primitive(ptr, t, value, name),
+        Element::Message(m) => write_message(ptr, m, value),
+    }
+}
+
+unsafe fn write_elements(
+    data: *mut u8,
+    element: Element,
+    values: &[Value],
+    name: &str,
+) -> Result<()> {
+    let size = element.size();
+    for (i, value) in values.iter().enumerate() {
+        write_element(data.add(i * size), element, value, name)?;
+    }
+    Ok(())
+}
+
+unsafe fn write_message(ptr: *mut u8, message: Members, json: &Value) -> Result<()> {
+    let fields = json
+        .as_object()
+        .ok_or_else(|| mismatch(&message.name(), "an object"))?;
+    for member in message.members() {
+        let name = member_name(member);
+        let value = match fields.get(name) {
+            Some(value) => value,
+            None => continue,
+        };
+        let step = member_step(member).ok_or_else(|| mismatch(name, "a supported type"))?;
+        let field = ptr.add(step.offset);
+        match step.layout {
+            Layout::Single => write_element(field, step.element, value, name)?,
+            Layout::Fixed(size) => {
+                let values = value
+                    .as_array()
+                    .filter(|values| values.len() == size)
+                    .ok_or_else(|| mismatch(name, &format!("an array of {}", size)))?;
+                write_elements(field, step.element, values, name)?;
+            }
+            Layout::Sequence => {
+                let values = value.as_array().ok_or_else(|| mismatch(name, "an array"))?;
+                if member.is_upper_bound_ && values.len() > member.array_size_ {
+                    return Err(mismatch(
+                        name,
+                        &format!("at most {} elements", member.array_size_),
+                    ));
+                }
+                match step.element {
+                    Element::Primitive(t) => resize_primitive_sequence(field, t, values.len()),
+                    Element::Message(_) => {
+                        if let Some(resize) = member.resize_function {
+                            resize(field as *mut c_void, values.len());
+                        }
+                    }
+                }
+                let seq = &*(field as *const RawSequence);
+                if seq.size != values.len() {
+                    return Err(mismatch(name, "a sequence that fits in memory"));
+                }
+                write_elements(seq.data as *mut u8, step.element, values, name)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, r2r__visualization_msgs__msg__MarkerArray))]
+mod tests {
+    use super::*;
+    use crate::msg_types::generated_msgs::visualization_msgs::msg::{Marker, MarkerArray};
+    use crate::msg_types::{WrappedNativeMsg, WrappedTypesupport};
+    use crate::typesupport_loader::load_library;
+
+    #[test]
+    fn test_json_like_generated() {
+        let library =
+            load_library("visualization_msgs", "rosidl_typesupport_introspection_c").unwrap();
+        let marker_array =
+            unsafe { DynamicType::new(MarkerArray::get_introspection_ts(), library) };
+
+        let mut msg = MarkerArray::default();
+        for (i, ns) in ["a", "b"].iter().enumerate() {
+            let mut marker = Marker {
+                ns: ns.to_string(),
+                id: i as i32,
+                ..Default::default()
+            };
+            marker.header.frame_id = "map".into();
+            marker.pose.position.x = 1.5;
+            marker.text = "ünïcode".into();
+            marker.color.a = 0.5;
+            msg.markers.push(marker);
+        }
+        let json = serde_json::to_value(&msg).unwrap();
+
+        // reading gives what serde gives for the generated type.
+        let native = WrappedNativeMsg::from(&msg);
+        let read = unsafe { marker_array.to_json(native.msg as *const c_void) };
+        assert_eq!(read, json);
+
+        // and writing gives the message back.
+        let dynamic = marker_array.create_msg();
+        unsafe {
+            marker_array.from_json(dynamic, &json).unwrap();
+            let written = MarkerArray::from_native(
+                &*(dynamic as *const <MarkerArray as WrappedTypesupport>::CStruct),
+            );
+            assert_eq!(written.markers.len(), 2);
+            assert_eq!(written.markers[1].ns, "b");
+            assert_eq!(written.markers[1].id, 1);
+            assert_eq!(written.markers[1].header.frame_id, "map");
+            assert_eq!(written.markers[1].text, "ünïcode");
+            assert_eq!(written.markers[1].color.a, 0.5);
+            marker_array.destroy_msg(dynamic);
+        }
+
+        let dynamic = marker_array.create_msg();
+        let invalid = serde_json::json!({ "markers": [{ "id": "one" }] });
+        assert!(matches!(
+            unsafe { marker_array.from_json(dynamic, &invalid) },
+            Err(Error::SerdeError { .. })
+        ));
+        unsafe { marker_array.destroy_msg(dynamic) };
+    }
+}
@@ -0,0 +1,115 @@
+//! Bookkeeping of the entities of a node, see `Node::list_entities`.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Instant;
+
+use crate::executor::EntityKind;
+use crate::stats::StatsTracker;
+
+/// An entity of a node.
+#[derive(Debug, Clone)]
+pub struct EntityInfo {
+    pub kind: EntityKind,
+    /// Topic, service or action name. Empty for timers.
+    pub name: String,
+    pub created: Instant,
+    /// When a message was last published or received, for publishers
+    /// and subscriptions with statistics enabled.
+    pub last_activity: Option<Instant>,
+    /// Whether the handle returned when the entity was created, or a
+    /// clone of it, still exists.
+    pub handle_alive: bool,
+}
+
+struct EntityRecord {
+    kind: EntityKind,
+    name: String,
+    created: Instant,
+    stats: Option<Weak<Mutex<StatsTracker>>>,
+}
+
+/// What the node remembers about its entities from when they were
+/// created, by the address of the entity in the node.
+#[derive(Default)]
+pub(crate) struct EntityRegistry {
+    records: HashMap<usize, EntityRecord>,
+}
+
+impl EntityRegistry {
+    pub(crate) fn register(
+        &mut self,
+        address: usize,
+        kind: EntityKind,
+        name: &str,
+        stats: Option<&Arc<Mutex<StatsTracker>>>,
+    ) {
+        self.records.insert(
+            address,
+            EntityRecord {
+                kind,
+                name: name.to_owned(),
+                created: Instant::now(),
+                stats: stats.map(Arc::downgrade),
+            },
+        );
+    }
+
+    /// Forgets the entities that are not among `addresses` anymore,
+    /// e.g. subscriptions destroyed when their streams were dropped.
+    pub(crate) fn retain(&mut self, addresses: &HashSet<usize>) {
+        self.records.retain(|a, _| addresses.contains(a));
+    }
+
+    pub(crate) fn forget(&mut self, address: usize) {
+        self.records.remove(&address);
+    }
+
+    pub(crate) fn info(&self, address: usize, kind: EntityKind, handle_alive: bool) -> EntityInfo {
+        match self.records.get(&address) {
+            Some(record) => EntityInfo {
+                kind: record.kind,
+                name: record.name.clone(),
+                created: record.created,
+                last_activity: record
+                    .stats
+                    .as_ref()
+                    .and_then(|s| s.upgrade())
+                    .and_then(|s| s.lock().unwrap().last_message()),
+                handle_alive,
+            },
+            // created before it could be registered, should not happen.
+            None => EntityInfo {
+                kind,
+                name: String::new(),
+                created: Instant::now(),
+                last_activity: None,
+                handle_alive,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entity_registry() {
+        let mut registry = EntityRegistry::default();
+        let tracker = Arc::new(Mutex::new(StatsTracker::new("/chatter", true)));
+        registry.register(1, EntityKind::Publisher, "/chatter", Some(&tracker));
+        registry.register(2, EntityKind::Timer, "", None);
+
+        let info = registry.info(1, EntityKind::Publisher, false);
+        assert_eq!(info.kind, EntityKind::Publisher);
+        assert_eq!(info.name, "/chatter");
+        assert!(!info.handle_alive);
+        assert_eq!(info.last_activity, None);
+
+        registry.retain(&[1].iter().cloned().collect());
+        let info = registry.info(2, EntityKind::Timer, true);
+        assert!(info.name.is_empty());
+        assert!(info.created >= registry.info(1, EntityKind::Publisher, true).created);
+    }
+}
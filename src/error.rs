@@ -3,6 +3,10 @@ use r2r_actions::*;
 use r2r_rcl::*;
 use thiserror::Error;
 
+use std::time::Duration;
+
+use crate::qos::QosProfile;
+
 /// r2r Result type.
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -87,6 +91,98 @@ pub enum Error {
     InvalidMessageType { msgtype: String },
     #[error("Serde error: {}", err)]
     SerdeError { err: String },
+    #[error("Invalid parameter yaml: {}", err)]
+    InvalidParameterYaml { err: String },
+    #[error("Invalid QoS profile: {}", reason)]
+    InvalidQosProfile { reason: String },
+    #[error("No publishers of topic {} found", topic)]
+    TopicNotFound { topic: String },
+    #[error(
+        "Publisher {} of {} has type hash {}, expected {}",
+        publisher,
+        topic,
+        found,
+        expected
+    )]
+    TypeHashMismatch {
+        topic: String,
+        publisher: String,
+        expected: String,
+        found: String,
+    },
+    /// A publisher of the topic uses another message type than a
+    /// subscription with `SubscriptionOptions::strict_type_check`.
+    #[error("Topic {} has type {}, not {}", topic, found, expected)]
+    TopicTypeMismatch {
+        topic: String,
+        expected: String,
+        found: String,
+    },
+    #[error("Message failed validation: {}", reason)]
+    ValidationFailed { reason: String },
+    #[error("Could not find a QoS profile matching topic {}: {}", topic, reason)]
+    QosNotMatched { topic: String, reason: String },
+    #[error("Dependencies not ready: {}", missing.join(", "))]
+    DependenciesNotReady { missing: Vec<String> },
+    #[error("Could not deliver to the receiver: {}", reason)]
+    DeliveryFailed { reason: String },
+    #[error("Could not match response: {}", reason)]
+    UnmatchedResponse { reason: String },
+    #[error("Invalid goal id: {}", reason)]
+    InvalidGoalId { reason: String },
+    #[error("Unknown goal status code: {}", code)]
+    InvalidGoalStatus { code: i8 },
+    #[error("Unknown cancel return code: {}", code)]
+    InvalidCancelReturnCode { code: i8 },
+    #[error("A node named {} already exists", name)]
+    DuplicateNodeName { name: String },
+    #[error("Service served by {} servers: {}", count, nodes.join(", "))]
+    MultipleServiceServers { count: usize, nodes: Vec<String> },
+    #[error("Could not load type support: {}", reason)]
+    TypeSupportNotLoaded { reason: String },
+    #[error(
+        "QoS of {} does not match publisher {}: {}. Publisher: {:?}, subscription: {:?}",
+        topic, publisher, reasons.join(", "), publisher_qos, subscription_qos
+    )]
+    ActionStatusQosMismatch {
+        topic: String,
+        publisher: String,
+        publisher_qos: QosProfile,
+        subscription_qos: QosProfile,
+        reasons: Vec<String>,
+    },
+
+    #[error("Invalid message filter: {}", reason)]
+    InvalidFilter { reason: String },
+    #[error("Not supported: {} (ROS {})", feature, crate::distro::ros_distro())]
+    NotSupported { feature: String },
+    #[error("Recording error: {}", reason)]
+    RecordingError { reason: String },
+    #[error("Invalid action server: {}", reason)]
+    InvalidActionServer { reason: String },
+    #[error("{} messages from publisher {} were lost", missed, publisher)]
+    MessagesLost { publisher: String, missed: u64 },
+    #[error("Log sink error: {}", reason)]
+    LogSinkError { reason: String },
+    #[error("No response within {:?}", timeout)]
+    RequestTimedOut { timeout: Duration },
+    #[error("Command {} not acknowledged after {} attempts", seq, attempts)]
+    CommandNotAcknowledged { seq: u64, attempts: u32 },
+    #[error("Command {} rejected by the subscriber", seq)]
+    CommandRejected { seq: u64 },
+    #[error("Goal made no progress for {:?} and was not canceled", inactivity)]
+    GoalStuck { inactivity: Duration },
+    #[error("Action server {} not available within {:?}", action_name, timeout)]
+    ActionServerUnavailable {
+        action_name: String,
+        timeout: Duration,
+    },
+    #[error("Invalid parameter {}: {}", name, reason)]
+    InvalidParameter { name: String, reason: String },
+    #[error("Invalid field path {}: {}", path, reason)]
+    InvalidFieldPath { path: String, reason: String },
+    #[error("The client was destroyed before the request completed")]
+    ClientDestroyed,
 
     // action errors.
     #[error("RCL_RET_ACTION_NAME_INVALID")]
@@ -108,6 +204,16 @@ pub enum Error {
     #[error("RCL_RET_ACTION_GOAL_EVENT_INVALID")]
     RCL_RET_ACTION_GOAL_EVENT_INVALID,
 
+    /// One of the errors above together with the message rcl set for
+    /// it, see `Error::code`. Only returned when creating contexts,
+    /// nodes, publishers, subscriptions, clients, services and action
+    /// clients and servers; other rcl failures are the plain variants.
+    #[error("{}: {}", code, message)]
+    RclError { code: Box<Error>, message: String },
+
+    #[error("Goal rejected by server.")]
+    GoalRejected,
+
     #[error("Goal cancel request rejected by server.")]
     GoalCancelRejected,
 
@@ -116,10 +222,45 @@ pub enum Error {
 
     #[error("Goal already in a terminal state.")]
     GoalCancelAlreadyTerminated,
+
+    #[error("The client of goal {} is unreachable.", goal)]
+    GoalClientUnreachable { goal: String },
+
+    #[error("Goal metadata is not enabled for this action client.")]
+    GoalMetadataNotEnabled,
+
+    #[error("The client already has a goal with id {}.", goal)]
+    GoalIdInUse { goal: String },
+
+    #[error(
+        "Action client map {} has {} entries, more than {}.",
+        map,
+        entries,
+        limit
+    )]
+    BookkeepingOversized {
+        map: String,
+        entries: usize,
+        limit: usize,
+    },
 }
 
 impl Error {
+    /// The error for an rcl return code.
     pub fn from_rcl_error(e: i32) -> Self {
+        Error::from_rcl_code(e)
+    }
+
+    /// The error without the message of an `RclError`, e.g. for
+    /// matching on the rcl return code.
+    pub fn code(&self) -> &Error {
+        match self {
+            Error::RclError { code, .. } => code,
+            e => e,
+        }
+    }
+
+    fn from_rcl_code(e: i32) -> Self {
         let e = e as u32;
         match e {
             _ if e == RCL_RET_OK => Error::RCL_RET_OK,
@@ -171,3 +312,82 @@ impl Error {
         }
     }
 }
+
+/// Makes an rcl call, turning a failure into an `Error::RclError` with
+/// the message rcl set for it.
+///
+/// The rcl error state is reset right before the call, so that the
+/// message of an earlier failure on this thread, e.g. one that was
+/// returned without `Error::from_rcl_error`, is not attached.
+pub(crate) fn rcl_call(call: impl FnOnce() -> i32) -> Result<()> {
+    unsafe {
+        rcutils_reset_error();
+    }
+    let ret = call();
+    if ret == RCL_RET_OK as i32 {
+        return Ok(());
+    }
+    let code = Error::from_rcl_code(ret);
+    Err(match take_rcl_error_message() {
+        Some(message) => Error::RclError {
+            code: Box::new(code),
+            message,
+        },
+        None => code,
+    })
+}
+
+// The message of the last rcl error on this thread, which is then
+// reset so that it is not reported again.
+fn take_rcl_error_message() -> Option<String> {
+    unsafe {
+        if !rcutils_error_is_set() {
+            return None;
+        }
+        let error = rcutils_get_error_string();
+        let message = std::ffi::CStr::from_ptr(error.str.as_ptr())
+            .to_string_lossy()
+            .into_owned();
+        rcutils_reset_error();
+        Some(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn init_options_without_options() -> i32 {
+        unsafe { rcl_init_options_init(std::ptr::null_mut(), rcutils_get_default_allocator()) }
+    }
+
+    #[test]
+    fn test_rcl_error_message() {
+        let e = rcl_call(init_options_without_options).unwrap_err();
+        assert!(matches!(e.code(), Error::RCL_RET_INVALID_ARGUMENT));
+        assert!(matches!(e, Error::RclError { .. }));
+        assert!(e.to_string().starts_with("RCL_RET_INVALID_ARGUMENT: "));
+
+        // the message was taken.
+        let e = rcl_call(|| RCL_RET_TIMEOUT as i32).unwrap_err();
+        assert!(matches!(e, Error::RCL_RET_TIMEOUT));
+        assert!(matches!(e.code(), Error::RCL_RET_TIMEOUT));
+        assert!(rcl_call(|| RCL_RET_OK as i32).is_ok());
+    }
+
+    #[test]
+    fn test_stale_rcl_error_message() {
+        // a failure whose message nobody takes.
+        let ret = init_options_without_options();
+        assert!(matches!(
+            Error::from_rcl_error(ret),
+            Error::RCL_RET_INVALID_ARGUMENT
+        ));
+        assert!(unsafe { rcutils_error_is_set() });
+
+        // an unrelated failure does not get the message.
+        let e = rcl_call(|| RCL_RET_TIMEOUT as i32).unwrap_err();
+        assert!(matches!(e, Error::RCL_RET_TIMEOUT));
+        assert_eq!(e.to_string(), "RCL_RET_TIMEOUT");
+    }
+}
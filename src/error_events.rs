@@ -0,0 +1,202 @@
+use futures::channel::mpsc;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::error::*;
+use crate::executor::EntityKind;
+use crate::log_handler::LogHandlerSlot;
+use crate::utils::LogSeverity;
+
+/// What was being done when a `SpinError` occurred.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SpinOperation {
+    /// Taking a message, request or response from the middleware.
+    Take,
+    /// Converting a message, e.g. from json.
+    Convert,
+    /// Handing something over to a stream, future or channel of the user.
+    Deliver,
+    /// Matching a response to a request or goal.
+    Match,
+    /// Sending or publishing something from within spin.
+    Send,
+    /// Updating the state of the entity, e.g. of a goal.
+    Update,
+}
+
+impl SpinOperation {
+    // How severe an error during the operation is. A response that
+    // matches no request is usually just late.
+    fn log_severity(&self) -> LogSeverity {
+        match self {
+            SpinOperation::Match => LogSeverity::Debug,
+            SpinOperation::Send | SpinOperation::Deliver => LogSeverity::Warn,
+            SpinOperation::Take | SpinOperation::Convert | SpinOperation::Update => {
+                LogSeverity::Error
+            }
+        }
+    }
+}
+
+impl fmt::Display for SpinOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            SpinOperation::Take => "take",
+            SpinOperation::Convert => "convert",
+            SpinOperation::Deliver => "deliver",
+            SpinOperation::Match => "match",
+            SpinOperation::Send => "send",
+            SpinOperation::Update => "update",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// An error that occurred while spinning, see `Node::error_events`.
+#[derive(Debug)]
+pub struct SpinError {
+    pub kind: EntityKind,
+    /// Topic, service or action name. Empty for timers.
+    pub name: String,
+    pub operation: SpinOperation,
+    pub error: Error,
+}
+
+impl fmt::Display for SpinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} {}: {} failed: {}",
+            self.kind, self.name, self.operation, self.error
+        )
+    }
+}
+
+struct Sink {
+    sender: Option<mpsc::Sender<SpinError>>,
+    overflow: usize,
+    log: bool,
+    log_handler: LogHandlerSlot,
+}
+
+/// Where the entities of a node report their errors.
+#[derive(Clone)]
+pub struct ErrorSink(Arc<Mutex<Sink>>);
+
+impl ErrorSink {
+    pub fn new() -> Self {
+        ErrorSink::with_log_handler(LogHandlerSlot::default())
+    }
+
+    /// Errors are logged with `log_handler`, see `set_log`.
+    pub(crate) fn with_log_handler(log_handler: LogHandlerSlot) -> Self {
+        ErrorSink(Arc::new(Mutex::new(Sink {
+            sender: None,
+            overflow: 0,
+            log: true,
+            log_handler,
+        })))
+    }
+
+    pub fn entity(&self, kind: EntityKind, name: &str) -> EntityErrors {
+        EntityErrors {
+            sink: self.clone(),
+            kind,
+            name: name.to_owned(),
+        }
+    }
+
+    /// Replaces any earlier receiver.
+    pub fn subscribe(&self, capacity: usize) -> mpsc::Receiver<SpinError> {
+        let (sender, receiver) = mpsc::channel(capacity);
+        self.0.lock().unwrap().sender = Some(sender);
+        receiver
+    }
+
+    pub fn overflow(&self) -> usize {
+        self.0.lock().unwrap().overflow
+    }
+
+    pub fn set_log(&self, log: bool) {
+        self.0.lock().unwrap().log = log;
+    }
+
+    pub fn report(&self, error: SpinError) {
+        let mut sink = self.0.lock().unwrap();
+        // always log when nobody listens, so that errors are not lost.
+        let log = if sink.log || sink.sender.is_none() {
+            Some((
+                sink.log_handler.clone(),
+                error.operation.log_severity(),
+                error.to_string(),
+            ))
+        } else {
+            None
+        };
+        if let Some(sender) = &mut sink.sender {
+            match sender.try_send(error) {
+                Err(e) if e.is_full() => sink.overflow += 1,
+                Err(_) => sink.sender = None, // receiver dropped.
+                Ok(()) => {}
+            }
+        }
+        drop(sink);
+        if let Some((log_handler, severity, msg)) = log {
+            log_handler.log(severity, &msg);
+        }
+    }
+
+    /// Log a message with the log handler of the node.
+    pub fn log(&self, severity: LogSeverity, msg: &str) {
+        let log_handler = self.0.lock().unwrap().log_handler.clone();
+        log_handler.log(severity, msg);
+    }
+}
+
+/// An `ErrorSink` together with the entity that reports to it.
+#[derive(Clone)]
+pub struct EntityErrors {
+    sink: ErrorSink,
+    kind: EntityKind,
+    name: String,
+}
+
+impl EntityErrors {
+    pub fn report(&self, operation: SpinOperation, error: Error) {
+        self.sink.report(SpinError {
+            kind: self.kind,
+            name: self.name.clone(),
+            operation,
+            error,
+        });
+    }
+
+    /// Log a message with the log handler of the node.
+    pub fn log(&self, severity: LogSeverity, msg: &str) {
+        self.sink.log(severity, msg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream::StreamExt;
+
+    #[test]
+    fn test_error_sink_overflow() {
+        let sink = ErrorSink::new();
+        sink.set_log(false);
+        let mut receiver = sink.subscribe(0);
+        let errors = sink.entity(EntityKind::Client, "/service");
+        // a channel with capacity 0 still has room for one message per sender.
+        for _ in 0..3 {
+            errors.report(SpinOperation::Match, Error::RCL_RET_ERROR);
+        }
+        assert_eq!(sink.overflow(), 2);
+        let e = futures::executor::block_on(receiver.next()).unwrap();
+        assert_eq!(e.kind, EntityKind::Client);
+        assert_eq!(e.name, "/service");
+        assert_eq!(e.operation, SpinOperation::Match);
+        assert_eq!(e.to_string(), "Client /service: match failed: RCL_RET_ERROR");
+    }
+}
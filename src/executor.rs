@@ -0,0 +1,327 @@
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::action_common::action_handle_impl;
+use crate::action_clients::ActionClient_;
+use crate::action_servers::ActionServer_;
+use crate::clients::Client_;
+use crate::nodes::Node;
+use crate::services::Service_;
+use crate::threads::ThreadHooks;
+use r2r_rcl::*;
+
+/// Decides in which order, and on which threads, the ready entities
+/// of a node are handled.
+///
+/// This interface is experimental and may change between minor
+/// versions.
+///
+/// Implementations get the entities that were found ready by one wait
+/// and should hand each of them to `Node::execute` or, for entities
+/// that do not belong to the node itself, to
+/// `SharedReadyEntity::execute`, possibly on another thread.
+pub trait Executor {
+    /// Handle the entities that became ready in one wait.
+    ///
+    /// `ready` is in the default order: subscriptions and timers by
+    /// decreasing priority, then clients, services and actions.
+    fn execute(&mut self, node: &mut Node, ready: Vec<ReadyEntity>);
+
+    /// Spin the node once using this executor.
+    ///
+    /// Waits at most `timeout` for entities to become ready.
+    fn spin_once(&mut self, node: &mut Node, timeout: Duration) {
+        node.prepare_wait();
+        let ready = node.wait_ready(timeout);
+        self.execute(node, ready);
+    }
+}
+
+/// What kind of entity became ready.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EntityKind {
+    Subscription,
+    Timer,
+    Client,
+    Service,
+    ActionClient,
+    ActionServer,
+    /// Only used for `SpinError`s, publishers never become ready.
+    Publisher,
+    /// Only used for `SpinError`s about the node itself.
+    Node,
+}
+
+/// Identifies an entity of a node for as long as the entity lives.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct EntityId(usize);
+
+/// Readiness information about an entity.
+#[derive(Debug, Clone)]
+pub struct ReadyInfo {
+    pub id: EntityId,
+    pub kind: EntityKind,
+    /// Priority of subscriptions and timers, zero for everything else.
+    pub priority: i32,
+    /// When the wait that found the entity ready returned.
+    pub ready_since: Instant,
+}
+
+/// An entity that is ready to be handled.
+pub struct ReadyEntity {
+    pub(crate) handle: ReadyHandle,
+    info: ReadyInfo,
+}
+
+impl ReadyEntity {
+    pub(crate) fn new(handle: ReadyHandle, priority: i32, ready_since: Instant) -> Self {
+        let (id, kind) = match &handle {
+            ReadyHandle::Subscription(s) => (s.impl_ as usize, EntityKind::Subscription),
+            ReadyHandle::Timer(t) => (t.impl_ as usize, EntityKind::Timer),
+            ReadyHandle::Client(c) => (arc_address(c), EntityKind::Client),
+            ReadyHandle::Service(s) => (arc_address(s), EntityKind::Service),
+            ReadyHandle::ActionClient(c, _) => (arc_address(c), EntityKind::ActionClient),
+            ReadyHandle::ActionServer(s, _) => (arc_address(s), EntityKind::ActionServer),
+        };
+        ReadyEntity {
+            handle,
+            info: ReadyInfo {
+                id: EntityId(id),
+                kind,
+                priority,
+                ready_since,
+            },
+        }
+    }
+
+    pub fn info(&self) -> &ReadyInfo {
+        &self.info
+    }
+
+    /// Subscriptions and timers are owned by the node and must be
+    /// handled with `Node::execute`. All other entities can be turned
+    /// into a `SharedReadyEntity`, which can be handled on any thread.
+    pub fn into_shared(self) -> std::result::Result<SharedReadyEntity, ReadyEntity> {
+        match self.info.kind {
+            EntityKind::Subscription | EntityKind::Timer => Err(self),
+            _ => Ok(SharedReadyEntity(self)),
+        }
+    }
+}
+
+/// A ready entity that does not need the node to be handled.
+pub struct SharedReadyEntity(ReadyEntity);
+
+// The shared entities are only accessed through their mutexes.
+unsafe impl Send for SharedReadyEntity {}
+
+impl SharedReadyEntity {
+    pub fn info(&self) -> &ReadyInfo {
+        &self.0.info
+    }
+
+    /// Take the ready messages of the entity and pass them on.
+    pub fn execute(self) {
+        execute_shared(self.0.handle);
+    }
+}
+
+pub(crate) fn arc_address<T: ?Sized>(a: &Arc<Mutex<T>>) -> usize {
+    Arc::as_ptr(a) as *const () as usize
+}
+
+#[derive(Default)]
+pub(crate) struct ActionClientReady {
+    pub feedback: bool,
+    pub status: bool,
+    pub goal_response: bool,
+    pub cancel_response: bool,
+    pub result_response: bool,
+}
+
+#[derive(Default)]
+pub(crate) struct ActionServerReady {
+    pub goal_request: bool,
+    pub cancel_request: bool,
+    pub result_request: bool,
+    pub goal_expired: bool,
+}
+
+// Entities found ready by a wait, identified by their handles since
+// entities can be removed before they are handled.
+pub(crate) enum ReadyHandle {
+    Subscription(rcl_subscription_t),
+    Timer(rcl_timer_t),
+    Client(Arc<Mutex<dyn Client_>>),
+    Service(Arc<Mutex<dyn Service_>>),
+    ActionClient(Arc<Mutex<dyn ActionClient_>>, ActionClientReady),
+    ActionServer(Arc<Mutex<dyn ActionServer_>>, ActionServerReady),
+}
+
+// Handles the entities that are not owned by the node. Entities can be
+// destroyed between becoming ready and being handled here, e.g. a
+// service whose stream was dropped while it waited in the pending
+// entities of a budgeted spin. rcl clears the impl pointer of a
+// finalized handle, and it is checked under the same lock that
+// destroying takes, so finalized handles are skipped.
+pub(crate) fn execute_shared(handle: ReadyHandle) {
+    match handle {
+        ReadyHandle::Subscription(_) | ReadyHandle::Timer(_) => {
+            unreachable!("subscriptions and timers are owned by the node")
+        }
+        ReadyHandle::Client(c) => {
+            let mut c = c.lock().unwrap();
+            if c.handle().impl_.is_null() {
+                return;
+            }
+            c.handle_response();
+        }
+        ReadyHandle::Service(s) => {
+            let mut service = s.lock().unwrap();
+            if service.handle().impl_.is_null() {
+                return;
+            }
+            service.handle_request(s.clone());
+        }
+        ReadyHandle::ActionClient(ac, r) => {
+            let mut acs = ac.lock().unwrap();
+            if action_handle_impl(acs.handle()).is_null() {
+                return;
+            }
+            if r.feedback {
+                acs.handle_feedback_msg();
+            }
+            if r.status {
+                acs.handle_status_msg();
+            }
+            if r.goal_response {
+                acs.handle_goal_response();
+            }
+            if r.cancel_response {
+                acs.handle_cancel_response();
+            }
+            if r.result_response {
+                acs.handle_result_response();
+            }
+        }
+        ReadyHandle::ActionServer(s, r) => {
+            if action_handle_impl(s.lock().unwrap().handle()).is_null() {
+                return;
+            }
+            if r.goal_request {
+                let mut acs = s.lock().unwrap();
+                acs.handle_goal_request(s.clone());
+            }
+            let mut acs = s.lock().unwrap();
+            if action_handle_impl(acs.handle()).is_null() {
+                return;
+            }
+            if r.cancel_request {
+                acs.handle_cancel_request();
+            }
+            if r.result_request {
+                acs.handle_result_request();
+            }
+            if r.goal_expired {
+                acs.handle_goal_expired();
+            }
+        }
+    }
+}
+
+/// Handles all ready entities on the spinning thread, in the default
+/// order. This is what `Node::spin_once` does.
+#[derive(Debug, Default)]
+pub struct SingleThreadedExecutor;
+
+impl SingleThreadedExecutor {
+    pub fn new() -> Self {
+        SingleThreadedExecutor
+    }
+}
+
+impl Executor for SingleThreadedExecutor {
+    fn execute(&mut self, node: &mut Node, ready: Vec<ReadyEntity>) {
+        for entity in ready {
+            node.execute(entity);
+        }
+    }
+}
+
+/// Handles clients, services and actions on a pool of worker threads
+/// while the subscriptions and timers are handled on the spinning
+/// thread. Returns when all ready entities have been handled.
+pub struct MultiThreadedExecutor {
+    jobs: Option<mpsc::Sender<SharedReadyEntity>>,
+    done: mpsc::Receiver<()>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl MultiThreadedExecutor {
+    /// Create an executor with `num_threads` worker threads.
+    pub fn new(num_threads: usize) -> Self {
+        Self::with_thread_hooks(num_threads, ThreadHooks::default())
+    }
+
+    /// Like `new`, with the worker threads named and started by
+    /// `hooks`, e.g. those of `Context::thread_hooks`.
+    pub fn with_thread_hooks(num_threads: usize, hooks: ThreadHooks) -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<SharedReadyEntity>();
+        let (done_sender, done) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let workers = (0..num_threads.max(1))
+            .map(|i| {
+                let job_receiver = job_receiver.clone();
+                let done_sender = done_sender.clone();
+                hooks
+                    .spawn(&format!("executor-{}", i), move || loop {
+                        let job = job_receiver.lock().unwrap().recv();
+                        match job {
+                            Ok(job) => {
+                                job.execute();
+                                let _ = done_sender.send(());
+                            }
+                            Err(_) => break, // the executor was dropped.
+                        }
+                    })
+                    .expect("could not start worker thread")
+            })
+            .collect();
+        MultiThreadedExecutor {
+            jobs: Some(jobs),
+            done,
+            workers,
+        }
+    }
+}
+
+impl Executor for MultiThreadedExecutor {
+    fn execute(&mut self, node: &mut Node, ready: Vec<ReadyEntity>) {
+        let jobs = self.jobs.as_ref().expect("executor has no workers");
+        let mut outstanding = 0;
+        for entity in ready {
+            match entity.into_shared() {
+                Ok(shared) => {
+                    jobs.send(shared).expect("worker thread died");
+                    outstanding += 1;
+                }
+                Err(entity) => node.execute(entity),
+            }
+        }
+        for _ in 0..outstanding {
+            self.done.recv().expect("worker thread died");
+        }
+    }
+}
+
+impl Drop for MultiThreadedExecutor {
+    fn drop(&mut self) {
+        // closing the channel stops the workers.
+        self.jobs.take();
+        for w in self.workers.drain(..) {
+            let _ = w.join();
+        }
+    }
+}
@@ -0,0 +1,540 @@
+//! Reading selected fields of native messages, see
+//! `Node::subscribe_fields`.
+//!
+//! A `FieldSelection` resolves paths such as `header.stamp` or
+//! `transforms[0].child_frame_id` against the introspection type
+//! support of a message once. Extracting the values then reads the
+//! fields straight from the C struct, without converting the rest of
+//! the message.
+//!
+//! Path segments are separated by `.`. An array or sequence field can
+//! be indexed with `[i]`; without an index the rest of the path is read
+//! from every element, e.g. `transforms.child_frame_id` of a
+//! `TFMessage` gives the child frame ids of all transforms as a
+//! `DynamicValue::Array`.
+
+use r2r_msg_gen::*;
+use r2r_rcl::{
+    rosidl_message_type_support_t, rosidl_runtime_c__String, rosidl_runtime_c__U16String,
+};
+use std::ffi::CStr;
+use std::marker::PhantomData;
+
+use crate::error::*;
+use crate::msg_types::{WrappedNativeMsg, WrappedTypesupport};
+
+/// The value of a message field read through a `FieldSelection`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DynamicValue {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    String(String),
+    /// An array or sequence field, or a field read from every element
+    /// of one.
+    Array(Vec<DynamicValue>),
+    /// A nested message, as its field names and values in declaration
+    /// order.
+    Message(Vec<(String, DynamicValue)>),
+    /// An indexed element past the end of a sequence in this message.
+    Missing,
+}
+
+impl DynamicValue {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            DynamicValue::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Integers of either signedness that fit an `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            DynamicValue::Int(v) => Some(*v),
+            DynamicValue::UInt(v) if *v <= i64::MAX as u64 => Some(*v as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            DynamicValue::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            DynamicValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// A field of a nested message.
+    pub fn field(&self, name: &str) -> Option<&DynamicValue> {
+        match self {
+            DynamicValue::Message(fields) => fields.iter().find(|(n, _)| n == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+// The introspection data is static and never written to.
+#[derive(Clone, Copy)]
+pub(crate) struct Members(pub(crate) *const rosidl_typesupport_introspection_c__MessageMembers);
+
+unsafe impl Send for Members {}
+unsafe impl Sync for Members {}
+
+impl Members {
+    pub(crate) fn new(ts: *const rosidl_message_type_support_t) -> Self {
+        Members(unsafe { (*ts).data } as *const rosidl_typesupport_introspection_c__MessageMembers)
+    }
+
+    pub(crate) fn name(&self) -> String {
+        unsafe { CStr::from_ptr((*self.0).message_name_) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        unsafe { (*self.0).size_of_ }
+    }
+
+    pub(crate) fn members(&self) -> &'static [rosidl_typesupport_introspection_c__MessageMember] {
+        unsafe { std::slice::from_raw_parts((*self.0).members_, (*self.0).member_count_ as usize) }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Element {
+    Primitive(u8),
+    Message(Members),
+}
+
+impl Element {
+    pub(crate) fn size(&self) -> usize {
+        match self {
+            Element::Primitive(t) => primitive_size(*t).expect("checked when resolved"),
+            Element::Message(m) => m.size(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Layout {
+    Single,
+    Fixed(usize),
+    Sequence,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct Step {
+    pub(crate) offset: usize,
+    pub(crate) layout: Layout,
+    pub(crate) element: Element,
+    index: Option<usize>,
+}
+
+// All rosidl sequences (of primitives, strings and messages) share
+// this layout.
+#[repr(C)]
+pub(crate) struct RawSequence {
+    pub(crate) data: *const u8,
+    pub(crate) size: usize,
+    capacity: usize,
+}
+
+fn primitive_size(t: u8) -> Option<usize> {
+    match t {
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_BOOLEAN as u8
+            || t == rosidl_typesupport_introspection_c__ROS_TYPE_OCTET as u8
+            || t == rosidl_typesupport_introspection_c__ROS_TYPE_CHAR as u8
+            || t == rosidl_typesupport_introspection_c__ROS_TYPE_UINT8 as u8
+            || t == rosidl_typesupport_introspection_c__ROS_TYPE_INT8 as u8 =>
+        {
+            Some(1)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_WCHAR as u8
+            || t == rosidl_typesupport_introspection_c__ROS_TYPE_UINT16 as u8
+            || t == rosidl_typesupport_introspection_c__ROS_TYPE_INT16 as u8 =>
+        {
+            Some(2)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_FLOAT as u8
+            || t == rosidl_typesupport_introspection_c__ROS_TYPE_UINT32 as u8
+            || t == rosidl_typesupport_introspection_c__ROS_TYPE_INT32 as u8 =>
+        {
+            Some(4)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_DOUBLE as u8
+            || t == rosidl_typesupport_introspection_c__ROS_TYPE_UINT64 as u8
+            || t == rosidl_typesupport_introspection_c__ROS_TYPE_INT64 as u8 =>
+        {
+            Some(8)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_STRING as u8 => {
+            Some(std::mem::size_of::<rosidl_runtime_c__String>())
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_WSTRING as u8 => {
+            Some(std::mem::size_of::<rosidl_runtime_c__U16String>())
+        }
+        // long double has no rust counterpart.
+        _ => None,
+    }
+}
+
+fn is_message(t: u8) -> bool {
+    t == rosidl_typesupport_introspection_c__ROS_TYPE_MESSAGE as u8
+}
+
+pub(crate) fn member_name(member: &rosidl_typesupport_introspection_c__MessageMember) -> &'static str {
+    unsafe { CStr::from_ptr(member.name_) }
+        .to_str()
+        .unwrap_or("")
+}
+
+// A step reading the whole member.
+pub(crate) fn member_step(member: &rosidl_typesupport_introspection_c__MessageMember) -> Option<Step> {
+    let element = if is_message(member.type_id_) {
+        Element::Message(Members::new(member.members_))
+    } else {
+        primitive_size(member.type_id_)?;
+        Element::Primitive(member.type_id_)
+    };
+    let layout = if !member.is_array_ {
+        Layout::Single
+    } else if member.array_size_ > 0 && !member.is_upper_bound_ {
+        Layout::Fixed(member.array_size_)
+    } else {
+        Layout::Sequence
+    };
+    Some(Step {
+        offset: member.offset_ as usize,
+        layout,
+        element,
+        index: None,
+    })
+}
+
+// Splits `name[3]` into the name and the index.
+fn parse_segment(segment: &str) -> std::result::Result<(&str, Option<usize>), String> {
+    match segment.find('[') {
+        None => Ok((segment, None)),
+        Some(open) => {
+            let index = segment[open + 1..]
+                .strip_suffix(']')
+                .and_then(|i| i.parse().ok())
+                .ok_or_else(|| format!("bad index in {}", segment))?;
+            Ok((&segment[..open], Some(index)))
+        }
+    }
+}
+
+fn resolve(message: Members, path: &str) -> std::result::Result<Vec<Step>, String> {
+    let mut steps = vec![];
+    let mut current = Some(message);
+    for segment in path.split('.') {
+        let (name, index) = parse_segment(segment)?;
+        let members = current.ok_or_else(|| format!("{} is not a field of a message", name))?;
+        let member = members
+            .members()
+            .iter()
+            .find(|m| member_name(m) == name)
+            .ok_or_else(|| format!("{} has no field {}", members.name(), name))?;
+        let mut step = member_step(member)
+            .ok_or_else(|| format!("{} has a type that cannot be read", name))?;
+        match (step.layout, index) {
+            (Layout::Single, Some(_)) => return Err(format!("{} is not an array", name)),
+            (Layout::Fixed(size), Some(i)) if i >= size => {
+                return Err(format!("{} has only {} elements", name, size))
+            }
+            _ => step.index = index,
+        }
+        current = match step.element {
+            Element::Message(m) => Some(m),
+            Element::Primitive(_) => None,
+        };
+        steps.push(step);
+    }
+    Ok(steps)
+}
+
+unsafe fn read_primitive(ptr: *const u8, t: u8) -> DynamicValue {
+    match t {
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_BOOLEAN as u8 => {
+            DynamicValue::Bool(*ptr != 0)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_OCTET as u8
+            || t == rosidl_typesupport_introspection_c__ROS_TYPE_CHAR as u8
+            || t == rosidl_typesupport_introspection_c__ROS_TYPE_UINT8 as u8 =>
+        {
+            DynamicValue::UInt(*ptr as u64)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_INT8 as u8 => {
+            DynamicValue::Int(*(ptr as *const i8) as i64)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_WCHAR as u8
+            || t == rosidl_typesupport_introspection_c__ROS_TYPE_UINT16 as u8 =>
+        {
+            DynamicValue::UInt(*(ptr as *const u16) as u64)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_INT16 as u8 => {
+            DynamicValue::Int(*(ptr as *const i16) as i64)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_UINT32 as u8 => {
+            DynamicValue::UInt(*(ptr as *const u32) as u64)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_INT32 as u8 => {
+            DynamicValue::Int(*(ptr as *const i32) as i64)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_UINT64 as u8 => {
+            DynamicValue::UInt(*(ptr as *const u64))
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_INT64 as u8 => {
+            DynamicValue::Int(*(ptr as *const i64))
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_FLOAT as u8 => {
+            DynamicValue::Float(*(ptr as *const f32) as f64)
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_DOUBLE as u8 => {
+            DynamicValue::Float(*(ptr as *const f64))
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_STRING as u8 => {
+            let s = &*(ptr as *const rosidl_runtime_c__String);
+            if s.data.is_null() {
+                DynamicValue::String(String::new())
+            } else {
+                let bytes = std::slice::from_raw_parts(s.data as *const u8, s.size);
+                DynamicValue::String(String::from_utf8_lossy(bytes).into_owned())
+            }
+        }
+        _ if t == rosidl_typesupport_introspection_c__ROS_TYPE_WSTRING as u8 => {
+            let s = &*(ptr as *const rosidl_runtime_c__U16String);
+            if s.data.is_null() {
+                DynamicValue::String(String::new())
+            } else {
+                let chars = std::slice::from_raw_parts(s.data as *const u16, s.size);
+                DynamicValue::String(String::from_utf16_lossy(chars))
+            }
+        }
+        _ => unreachable!("checked when resolved"),
+    }
+}
+
+pub(crate) unsafe fn read_message(ptr: *const u8, message: Members) -> DynamicValue {
+    DynamicValue::Message(
+        message
+            .members()
+            .iter()
+            .filter_map(|member| {
+                let step = member_step(member)?;
+                Some((member_name(member).to_owned(), read(ptr, &[step])))
+            })
+            .collect(),
+    )
+}
+
+unsafe fn read_element(ptr: *const u8, element: Element, rest: &[Step]) -> DynamicValue {
+    if !rest.is_empty() {
+        return read(ptr, rest);
+    }
+    match element {
+        Element::Primitive(t) => read_primitive(ptr, t),
+        Element::Message(m) => read_message(ptr, m),
+    }
+}
+
+unsafe fn read(ptr: *const u8, steps: &[Step]) -> DynamicValue {
+    let step = &steps[0];
+    let rest = &steps[1..];
+    let field = ptr.add(step.offset);
+    let (data, len) = match step.layout {
+        Layout::Single => return read_element(field, step.element, rest),
+        Layout::Fixed(size) => (field, size),
+        Layout::Sequence => {
+            let seq = &*(field as *const RawSequence);
+            (seq.data, seq.size)
+        }
+    };
+    let size = step.element.size();
+    match step.index {
+        Some(i) if i < len => read_element(data.add(i * size), step.element, rest),
+        Some(_) => DynamicValue::Missing,
+        None => DynamicValue::Array(
+            (0..len)
+                .map(|i| read_element(data.add(i * size), step.element, rest))
+                .collect(),
+        ),
+    }
+}
+
+/// A set of field paths of message type `T`, resolved once.
+///
+/// ```no_run
+/// use r2r::geometry_msgs::msg::PoseWithCovarianceStamped;
+/// use r2r::{FieldSelection, NativeMsg};
+///
+/// # fn main() -> r2r::Result<()> {
+/// let selection = FieldSelection::<PoseWithCovarianceStamped>::new(&[
+///     "header.stamp.sec",
+///     "pose.pose.position",
+/// ])?;
+/// let msg = NativeMsg::<PoseWithCovarianceStamped>::new();
+/// let values = selection.extract(&msg);
+/// assert_eq!(values[0].as_i64(), Some(0));
+/// assert_eq!(values[1].field("x").and_then(|x| x.as_f64()), Some(0.0));
+/// # Ok(())
+/// # }
+/// ```
+pub struct FieldSelection<T> {
+    paths: Vec<String>,
+    steps: Vec<Vec<Step>>,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> FieldSelection<T>
+where
+    T: WrappedTypesupport,
+{
+    /// Fails with `Error::InvalidFieldPath` when a path does not name a
+    /// field of `T`.
+    pub fn new(paths: &[&str]) -> Result<Self> {
+        let message = Members::new(T::get_introspection_ts());
+        let steps = paths
+            .iter()
+            .map(|path| {
+                resolve(message, path).map_err(|reason| Error::InvalidFieldPath {
+                    path: path.to_string(),
+                    reason,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(FieldSelection {
+            paths: paths.iter().map(|p| p.to_string()).collect(),
+            steps,
+            phantom: PhantomData,
+        })
+    }
+
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    /// The values of the selected fields, in the order of the paths.
+    pub fn extract(&self, msg: &WrappedNativeMsg<T>) -> Vec<DynamicValue> {
+        let ptr = msg.msg as *const u8;
+        self.steps
+            .iter()
+            .map(|steps| unsafe { read(ptr, steps) })
+            .collect()
+    }
+}
+
+// the tests need at least one of the message types below.
+#[cfg(all(
+    test,
+    any(
+        r2r__geometry_msgs__msg__PoseWithCovarianceStamped,
+        r2r__visualization_msgs__msg__MarkerArray
+    )
+))]
+mod tests {
+    use super::*;
+
+    #[cfg(r2r__geometry_msgs__msg__PoseWithCovarianceStamped)]
+    #[test]
+    fn test_extract_fields() {
+        use crate::msg_types::generated_msgs::geometry_msgs;
+
+        let mut msg = geometry_msgs::msg::PoseWithCovarianceStamped::default();
+        msg.header.stamp.sec = 12;
+        msg.header.frame_id = "map".into();
+        msg.pose.pose.position.x = 1.5;
+        msg.pose.covariance[35] = 0.25;
+        let native = WrappedNativeMsg::from(&msg);
+
+        let selection = FieldSelection::<geometry_msgs::msg::PoseWithCovarianceStamped>::new(&[
+            "header.stamp",
+            "header.frame_id",
+            "pose.pose.position",
+            "pose.covariance[35]",
+        ])
+        .unwrap();
+        let values = selection.extract(&native);
+        assert_eq!(values.len(), 4);
+        assert_eq!(values[0].field("sec"), Some(&DynamicValue::Int(12)));
+        assert_eq!(values[0].field("nanosec"), Some(&DynamicValue::UInt(0)));
+        assert_eq!(values[1].as_str(), Some("map"));
+        assert_eq!(values[2].field("x").and_then(|x| x.as_f64()), Some(1.5));
+        assert_eq!(values[3].as_f64(), Some(0.25));
+    }
+
+    #[cfg(r2r__visualization_msgs__msg__MarkerArray)]
+    #[test]
+    fn test_extract_sequences() {
+        use crate::msg_types::generated_msgs::visualization_msgs;
+
+        let mut msg = visualization_msgs::msg::MarkerArray::default();
+        for ns in &["a", "b"] {
+            msg.markers.push(visualization_msgs::msg::Marker {
+                ns: ns.to_string(),
+                ..Default::default()
+            });
+        }
+        let native = WrappedNativeMsg::from(&msg);
+
+        let selection = FieldSelection::<visualization_msgs::msg::MarkerArray>::new(&[
+            "markers.ns",
+            "markers[1].ns",
+            "markers[2].ns",
+        ])
+        .unwrap();
+        let values = selection.extract(&native);
+        assert_eq!(
+            values[0],
+            DynamicValue::Array(vec![
+                DynamicValue::String("a".into()),
+                DynamicValue::String("b".into())
+            ])
+        );
+        assert_eq!(values[1].as_str(), Some("b"));
+        assert_eq!(values[2], DynamicValue::Missing);
+
+        // an empty sequence.
+        let native = WrappedNativeMsg::<visualization_msgs::msg::MarkerArray>::new();
+        let values = selection.extract(&native);
+        assert_eq!(values[0], DynamicValue::Array(vec![]));
+        assert_eq!(values[1], DynamicValue::Missing);
+    }
+
+    #[cfg(all(
+        r2r__geometry_msgs__msg__PoseWithCovarianceStamped,
+        r2r__std_msgs__msg__String
+    ))]
+    #[test]
+    fn test_invalid_paths() {
+        use crate::msg_types::generated_msgs::{geometry_msgs, std_msgs};
+
+        let invalid = [
+            "header.nope",
+            "data",
+            "header.frame_id.data",
+            "header[0]",
+            "pose.covariance[36]",
+            "pose.covariance[x]",
+        ];
+        for path in &invalid {
+            let selection =
+                FieldSelection::<geometry_msgs::msg::PoseWithCovarianceStamped>::new(&[*path]);
+            assert!(
+                matches!(selection, Err(Error::InvalidFieldPath { .. })),
+                "{} was accepted",
+                path
+            );
+        }
+        assert!(FieldSelection::<std_msgs::msg::String>::new(&["data"]).is_ok());
+    }
+}
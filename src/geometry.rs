@@ -0,0 +1,392 @@
+//! Helpers for geometry messages.
+//!
+//! Conversions to and from math library types are enabled with the
+//! `nalgebra` and `glam` features. Quaternions in
+//! messages are stored as (x, y, z, w). Quaternions that cannot be
+//! normalized (e.g. all zeros, which is what a default constructed
+//! message contains) are converted to the identity rotation.
+
+use crate::log_handler::log_internal;
+use crate::msg_types::generated_msgs::geometry_msgs::msg::{
+    Point, Pose, PoseWithCovariance, Quaternion, Transform, Twist, TwistWithCovariance, Vector3,
+};
+use crate::utils::LogSeverity;
+
+fn normalized_xyzw(q: &Quaternion) -> [f64; 4] {
+    let norm = (q.x * q.x + q.y * q.y + q.z * q.z + q.w * q.w).sqrt();
+    if !norm.is_normal() {
+        if cfg!(debug_assertions) {
+            log_internal(
+                LogSeverity::Warn,
+                &format!(
+                    "quaternion ({}, {}, {}, {}) cannot be normalized, using identity",
+                    q.x, q.y, q.z, q.w
+                ),
+            );
+        }
+        return [0.0, 0.0, 0.0, 1.0];
+    }
+    [q.x / norm, q.y / norm, q.z / norm, q.w / norm]
+}
+
+fn covariance_to_matrix(covariance: &[f64]) -> [[f64; 6]; 6] {
+    let mut m = [[0.0; 6]; 6];
+    for (i, v) in covariance.iter().take(36).enumerate() {
+        m[i / 6][i % 6] = *v;
+    }
+    m
+}
+
+fn matrix_to_covariance(m: &[[f64; 6]; 6]) -> Vec<f64> {
+    m.iter().flat_map(|row| row.iter().copied()).collect()
+}
+
+impl PoseWithCovariance {
+    /// The covariance as a row major 6x6 matrix over (x, y, z, rot x,
+    /// rot y, rot z).
+    pub fn covariance_matrix(&self) -> [[f64; 6]; 6] {
+        covariance_to_matrix(&self.covariance)
+    }
+
+    pub fn set_covariance_matrix(&mut self, m: &[[f64; 6]; 6]) {
+        self.covariance = matrix_to_covariance(m);
+    }
+}
+
+impl TwistWithCovariance {
+    /// The covariance as a row major 6x6 matrix over (x, y, z, rot x,
+    /// rot y, rot z).
+    pub fn covariance_matrix(&self) -> [[f64; 6]; 6] {
+        covariance_to_matrix(&self.covariance)
+    }
+
+    pub fn set_covariance_matrix(&mut self, m: &[[f64; 6]; 6]) {
+        self.covariance = matrix_to_covariance(m);
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+mod nalgebra_impls {
+    use super::*;
+    use nalgebra as na;
+
+    impl From<&Vector3> for na::Vector3<f64> {
+        fn from(v: &Vector3) -> Self {
+            na::Vector3::new(v.x, v.y, v.z)
+        }
+    }
+
+    impl From<na::Vector3<f64>> for Vector3 {
+        fn from(v: na::Vector3<f64>) -> Self {
+            Vector3 {
+                x: v.x,
+                y: v.y,
+                z: v.z,
+            }
+        }
+    }
+
+    impl From<&Point> for na::Point3<f64> {
+        fn from(p: &Point) -> Self {
+            na::Point3::new(p.x, p.y, p.z)
+        }
+    }
+
+    impl From<na::Point3<f64>> for Point {
+        fn from(p: na::Point3<f64>) -> Self {
+            Point {
+                x: p.x,
+                y: p.y,
+                z: p.z,
+            }
+        }
+    }
+
+    impl From<&Point> for na::Translation3<f64> {
+        fn from(p: &Point) -> Self {
+            na::Translation3::new(p.x, p.y, p.z)
+        }
+    }
+
+    impl From<na::Translation3<f64>> for Point {
+        fn from(t: na::Translation3<f64>) -> Self {
+            Point {
+                x: t.x,
+                y: t.y,
+                z: t.z,
+            }
+        }
+    }
+
+    impl From<&Vector3> for na::Translation3<f64> {
+        fn from(v: &Vector3) -> Self {
+            na::Translation3::new(v.x, v.y, v.z)
+        }
+    }
+
+    impl From<na::Translation3<f64>> for Vector3 {
+        fn from(t: na::Translation3<f64>) -> Self {
+            Vector3 {
+                x: t.x,
+                y: t.y,
+                z: t.z,
+            }
+        }
+    }
+
+    impl From<&Quaternion> for na::UnitQuaternion<f64> {
+        fn from(q: &Quaternion) -> Self {
+            let [x, y, z, w] = normalized_xyzw(q);
+            na::UnitQuaternion::new_unchecked(na::Quaternion::new(w, x, y, z))
+        }
+    }
+
+    impl From<na::UnitQuaternion<f64>> for Quaternion {
+        fn from(q: na::UnitQuaternion<f64>) -> Self {
+            Quaternion {
+                x: q.i,
+                y: q.j,
+                z: q.k,
+                w: q.w,
+            }
+        }
+    }
+
+    impl From<&Pose> for na::Isometry3<f64> {
+        fn from(p: &Pose) -> Self {
+            na::Isometry3::from_parts((&p.position).into(), (&p.orientation).into())
+        }
+    }
+
+    impl From<na::Isometry3<f64>> for Pose {
+        fn from(i: na::Isometry3<f64>) -> Self {
+            Pose {
+                position: i.translation.into(),
+                orientation: i.rotation.into(),
+            }
+        }
+    }
+
+    impl From<&Transform> for na::Isometry3<f64> {
+        fn from(t: &Transform) -> Self {
+            na::Isometry3::from_parts((&t.translation).into(), (&t.rotation).into())
+        }
+    }
+
+    impl From<na::Isometry3<f64>> for Transform {
+        fn from(i: na::Isometry3<f64>) -> Self {
+            Transform {
+                translation: i.translation.into(),
+                rotation: i.rotation.into(),
+            }
+        }
+    }
+
+    /// (linear, angular)
+    impl From<&Twist> for (na::Vector3<f64>, na::Vector3<f64>) {
+        fn from(t: &Twist) -> Self {
+            ((&t.linear).into(), (&t.angular).into())
+        }
+    }
+
+    impl From<(na::Vector3<f64>, na::Vector3<f64>)> for Twist {
+        fn from((linear, angular): (na::Vector3<f64>, na::Vector3<f64>)) -> Self {
+            Twist {
+                linear: linear.into(),
+                angular: angular.into(),
+            }
+        }
+    }
+
+    impl PoseWithCovariance {
+        pub fn covariance_matrix6(&self) -> na::Matrix6<f64> {
+            let m = self.covariance_matrix();
+            na::Matrix6::from_fn(|r, c| m[r][c])
+        }
+    }
+
+    impl TwistWithCovariance {
+        pub fn covariance_matrix6(&self) -> na::Matrix6<f64> {
+            let m = self.covariance_matrix();
+            na::Matrix6::from_fn(|r, c| m[r][c])
+        }
+    }
+}
+
+#[cfg(feature = "glam")]
+mod glam_impls {
+    use super::*;
+
+    impl From<&Vector3> for glam::Vec3 {
+        fn from(v: &Vector3) -> Self {
+            glam::Vec3::new(v.x as f32, v.y as f32, v.z as f32)
+        }
+    }
+
+    impl From<glam::Vec3> for Vector3 {
+        fn from(v: glam::Vec3) -> Self {
+            Vector3 {
+                x: v.x as f64,
+                y: v.y as f64,
+                z: v.z as f64,
+            }
+        }
+    }
+
+    impl From<&Point> for glam::Vec3 {
+        fn from(p: &Point) -> Self {
+            glam::Vec3::new(p.x as f32, p.y as f32, p.z as f32)
+        }
+    }
+
+    impl From<glam::Vec3> for Point {
+        fn from(v: glam::Vec3) -> Self {
+            Point {
+                x: v.x as f64,
+                y: v.y as f64,
+                z: v.z as f64,
+            }
+        }
+    }
+
+    impl From<&Quaternion> for glam::Quat {
+        fn from(q: &Quaternion) -> Self {
+            let [x, y, z, w] = normalized_xyzw(q);
+            glam::Quat::from_xyzw(x as f32, y as f32, z as f32, w as f32)
+        }
+    }
+
+    impl From<glam::Quat> for Quaternion {
+        fn from(q: glam::Quat) -> Self {
+            Quaternion {
+                x: q.x as f64,
+                y: q.y as f64,
+                z: q.z as f64,
+                w: q.w as f64,
+            }
+        }
+    }
+
+    impl From<&Pose> for glam::Affine3A {
+        fn from(p: &Pose) -> Self {
+            glam::Affine3A::from_rotation_translation(
+                (&p.orientation).into(),
+                (&p.position).into(),
+            )
+        }
+    }
+
+    /// Any scale in the transform is dropped.
+    impl From<glam::Affine3A> for Pose {
+        fn from(a: glam::Affine3A) -> Self {
+            let (_scale, rotation, translation) = a.to_scale_rotation_translation();
+            Pose {
+                position: translation.into(),
+                orientation: rotation.into(),
+            }
+        }
+    }
+
+    impl From<&Transform> for glam::Affine3A {
+        fn from(t: &Transform) -> Self {
+            glam::Affine3A::from_rotation_translation(
+                (&t.rotation).into(),
+                (&t.translation).into(),
+            )
+        }
+    }
+
+    /// Any scale in the transform is dropped.
+    impl From<glam::Affine3A> for Transform {
+        fn from(a: glam::Affine3A) -> Self {
+            let (_scale, rotation, translation) = a.to_scale_rotation_translation();
+            Transform {
+                translation: translation.into(),
+                rotation: rotation.into(),
+            }
+        }
+    }
+
+    /// (linear, angular)
+    impl From<&Twist> for (glam::Vec3, glam::Vec3) {
+        fn from(t: &Twist) -> Self {
+            ((&t.linear).into(), (&t.angular).into())
+        }
+    }
+
+    impl From<(glam::Vec3, glam::Vec3)> for Twist {
+        fn from((linear, angular): (glam::Vec3, glam::Vec3)) -> Self {
+            Twist {
+                linear: linear.into(),
+                angular: angular.into(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_quaternion_is_identity() {
+        let q = Quaternion {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            w: 0.0,
+        };
+        assert_eq!(normalized_xyzw(&q), [0.0, 0.0, 0.0, 1.0]);
+        let q = Quaternion {
+            x: 0.0,
+            y: 0.0,
+            z: 2.0,
+            w: 0.0,
+        };
+        assert_eq!(normalized_xyzw(&q), [0.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_covariance_matrix() {
+        let mut p = PoseWithCovariance::default();
+        let mut m = [[0.0; 6]; 6];
+        m[0][1] = 1.0;
+        m[5][4] = 2.0;
+        p.set_covariance_matrix(&m);
+        assert_eq!(p.covariance.len(), 36);
+        assert_eq!(p.covariance[1], 1.0);
+        assert_eq!(p.covariance[34], 2.0);
+        assert_eq!(p.covariance_matrix(), m);
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn test_nalgebra_pose_roundtrip() {
+        let iso = nalgebra::Isometry3::new(
+            nalgebra::Vector3::new(1.0, 2.0, 3.0),
+            nalgebra::Vector3::new(0.1, 0.2, 0.3),
+        );
+        let pose: Pose = iso.into();
+        let iso2: nalgebra::Isometry3<f64> = (&pose).into();
+        assert!((iso.translation.vector - iso2.translation.vector).norm() < 1e-12);
+        assert!(iso.rotation.angle_to(&iso2.rotation) < 1e-12);
+
+        let identity: nalgebra::UnitQuaternion<f64> = (&Quaternion::default()).into();
+        assert_eq!(identity, nalgebra::UnitQuaternion::identity());
+    }
+
+    #[cfg(feature = "glam")]
+    #[test]
+    fn test_glam_pose_roundtrip() {
+        let rotation = glam::Quat::from_rotation_z(0.5);
+        let translation = glam::Vec3::new(1.0, 2.0, 3.0);
+        let affine = glam::Affine3A::from_rotation_translation(rotation, translation);
+        let pose: Pose = affine.into();
+        assert!((pose.orientation.z - rotation.z as f64).abs() < 1e-6);
+        let affine2: glam::Affine3A = (&pose).into();
+        assert!(affine.abs_diff_eq(affine2, 1e-6));
+
+        let identity: glam::Quat = (&Quaternion::default()).into();
+        assert_eq!(identity, glam::Quat::IDENTITY);
+    }
+}
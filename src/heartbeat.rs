@@ -0,0 +1,317 @@
+//! Heartbeats for detecting that another node has stopped.
+//!
+//! A `Heartbeat` publishes `std_msgs/msg/Header` messages at a fixed
+//! rate, where `stamp` is the ROS time of the beat and `frame_id` holds
+//! a counter that increases by one for each beat. A `HeartbeatMonitor`
+//! subscribes to such a topic and turns the beats into
+//! `HeartbeatEvent`s.
+//!
+//! Both are driven by spinning the node they were created on. The
+//! monitor measures the time between beats on the ROS clock, so
+//! while simulated time is paused no beats are considered missed.
+
+use futures::channel::mpsc;
+use futures::future::FutureExt;
+use futures::stream::{Stream, StreamExt};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::clocks::{Clock, ClockType};
+use crate::error::*;
+use crate::error_events::{EntityErrors, SpinOperation};
+use crate::executor::EntityKind;
+use crate::msg_types::generated_msgs::builtin_interfaces;
+use crate::nodes::{Node, Timer};
+use crate::periodic::{PeriodicMessage, PeriodicPublisher_};
+
+const HEARTBEAT_MSG_TYPE: &str = "std_msgs/msg/Header";
+
+/// What a `HeartbeatMonitor` observed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeartbeatEvent {
+    /// The first beat was received, or the counter started over
+    /// because the publisher restarted.
+    Alive { counter: u64 },
+    /// Beats are missing. Either no beat has been received for longer
+    /// than the timeout, in which case `missed_beats` is zero and `gap`
+    /// is the time since the last beat, or the counter skipped
+    /// `missed_beats` beats since the previous beat `gap` ago.
+    Missed { gap: Duration, missed_beats: u64 },
+    /// Beats are received again after a timeout. `gap` is the time
+    /// between the last beat before the timeout and this one.
+    Recovered { gap: Duration, missed_beats: u64 },
+}
+
+/// Publishes heartbeats until dropped.
+pub struct Heartbeat {
+    counter: Arc<AtomicU64>,
+}
+
+impl Heartbeat {
+    /// Publish heartbeats on `topic` at `rate` Hz.
+    pub fn publish(node: &mut Node, topic: &str, rate: f64) -> Result<Heartbeat> {
+        if !(rate > 0.0 && rate.is_finite()) {
+            return Err(Error::RCL_RET_INVALID_ARGUMENT);
+        }
+        let counter = Arc::new(AtomicU64::new(0));
+        let beats = HeartbeatBeats {
+            counter: counter.clone(),
+        };
+        PeriodicPublisher_::start(
+            node,
+            topic,
+            HEARTBEAT_MSG_TYPE,
+            Duration::from_secs_f64(1.0 / rate),
+            Box::new(beats),
+        )?;
+        Ok(Heartbeat { counter })
+    }
+
+    /// The number of beats published so far.
+    pub fn count(&self) -> u64 {
+        self.counter.load(Ordering::Relaxed)
+    }
+}
+
+/// Watches the heartbeats of another node.
+pub struct HeartbeatMonitor;
+
+impl HeartbeatMonitor {
+    /// Subscribe to the heartbeats on `topic`. A `Missed` event is
+    /// emitted when no beat has been received for `timeout`.
+    ///
+    /// The monitor stops when the stream is dropped.
+    pub fn watch(
+        node: &mut Node,
+        topic: &str,
+        timeout: Duration,
+    ) -> Result<impl Stream<Item = HeartbeatEvent> + Unpin> {
+        if timeout == Duration::from_secs(0) {
+            return Err(Error::RCL_RET_INVALID_ARGUMENT);
+        }
+        let beats = node.subscribe_untyped(topic, HEARTBEAT_MSG_TYPE)?;
+        // wake up often enough to notice a timeout in time.
+        let timer = node.create_wall_timer(timeout / 4)?;
+        let (sender, receiver) = mpsc::channel(10);
+        let monitor = HeartbeatMonitor_ {
+            beats: Box::new(beats),
+            timer,
+            clock: Clock::create(ClockType::RosTime)?,
+            tracker: HeartbeatTracker::new(timeout),
+            sender,
+            errors: node.entity_errors(EntityKind::Subscription, topic),
+        };
+        node.add_heartbeat_monitor(monitor);
+        Ok(receiver)
+    }
+}
+
+struct HeartbeatBeats {
+    counter: Arc<AtomicU64>,
+}
+
+impl PeriodicMessage for HeartbeatBeats {
+    fn is_dropped(&self) -> bool {
+        Arc::strong_count(&self.counter) == 1
+    }
+
+    fn message(&mut self, stamp: &builtin_interfaces::msg::Time) -> Result<serde_json::Value> {
+        let counter = self.counter.load(Ordering::Relaxed) + 1;
+        Ok(serde_json::json!({
+            "stamp": { "sec": stamp.sec, "nanosec": stamp.nanosec },
+            "frame_id": counter.to_string(),
+        }))
+    }
+
+    fn published(&mut self) {
+        self.counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub(crate) struct HeartbeatMonitor_ {
+    beats: Box<dyn Stream<Item = Result<serde_json::Value>> + Unpin>,
+    timer: Timer,
+    clock: Clock,
+    tracker: HeartbeatTracker,
+    sender: mpsc::Sender<HeartbeatEvent>,
+    errors: EntityErrors,
+}
+
+impl HeartbeatMonitor_ {
+    /// Handles received beats and checks for a timeout. Returns false
+    /// once the event stream has been dropped.
+    pub(crate) fn poll(&mut self) -> bool {
+        if self.sender.is_closed() {
+            return false;
+        }
+        while let Some(Ok(_)) = self.timer.tick().now_or_never() {}
+        let now = match self.clock.get_now() {
+            Ok(now) => now,
+            Err(e) => {
+                self.errors.report(SpinOperation::Update, e);
+                return true;
+            }
+        };
+        while let Some(Some(msg)) = self.beats.next().now_or_never() {
+            match msg.and_then(|m| beat_counter(&m)) {
+                Ok(counter) => {
+                    for event in self.tracker.beat(counter, now) {
+                        self.send(event);
+                    }
+                }
+                Err(e) => self.errors.report(SpinOperation::Convert, e),
+            }
+        }
+        if let Some(event) = self.tracker.check(now) {
+            self.send(event);
+        }
+        true
+    }
+
+    fn send(&mut self, event: HeartbeatEvent) {
+        if let Err(e) = self.sender.try_send(event) {
+            if e.is_full() {
+                self.errors.report(
+                    SpinOperation::Deliver,
+                    Error::DeliveryFailed {
+                        reason: "heartbeat event not handled in time".into(),
+                    },
+                );
+            }
+        }
+    }
+}
+
+fn beat_counter(msg: &serde_json::Value) -> Result<u64> {
+    msg.get("frame_id")
+        .and_then(|f| f.as_str())
+        .and_then(|f| f.parse().ok())
+        .ok_or_else(|| Error::SerdeError {
+            err: format!("heartbeat without a counter: {}", msg),
+        })
+}
+
+// The state of a monitor, with time given as ROS time.
+struct HeartbeatTracker {
+    timeout: Duration,
+    // counter and time of the last beat
+    last: Option<(u64, Duration)>,
+    timed_out: bool,
+}
+
+impl HeartbeatTracker {
+    fn new(timeout: Duration) -> Self {
+        HeartbeatTracker {
+            timeout,
+            last: None,
+            timed_out: false,
+        }
+    }
+
+    fn beat(&mut self, counter: u64, now: Duration) -> Vec<HeartbeatEvent> {
+        let mut events = vec![];
+        match self.last {
+            Some((last_counter, last_time)) if counter > last_counter => {
+                let gap = now.checked_sub(last_time).unwrap_or_default();
+                let missed_beats = counter - last_counter - 1;
+                if self.timed_out {
+                    events.push(HeartbeatEvent::Recovered { gap, missed_beats });
+                } else if missed_beats > 0 {
+                    events.push(HeartbeatEvent::Missed { gap, missed_beats });
+                }
+            }
+            _ => {
+                // first beat or the publisher restarted.
+                events.push(HeartbeatEvent::Alive { counter });
+            }
+        }
+        self.last = Some((counter, now));
+        self.timed_out = false;
+        events
+    }
+
+    fn check(&mut self, now: Duration) -> Option<HeartbeatEvent> {
+        let (counter, last_time) = self.last?;
+        if now < last_time {
+            // time jumped backwards, e.g. a simulation was restarted.
+            self.last = Some((counter, now));
+            return None;
+        }
+        let gap = now - last_time;
+        if self.timed_out || gap <= self.timeout {
+            return None;
+        }
+        self.timed_out = true;
+        Some(HeartbeatEvent::Missed {
+            gap,
+            missed_beats: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_timeout_and_recovery() {
+        let mut t = HeartbeatTracker::new(ms(100));
+        assert_eq!(t.check(ms(1000)), None);
+        assert_eq!(t.beat(1, ms(0)), vec![HeartbeatEvent::Alive { counter: 1 }]);
+        assert_eq!(t.beat(2, ms(50)), vec![]);
+        assert_eq!(t.check(ms(150)), None);
+        assert_eq!(
+            t.check(ms(151)),
+            Some(HeartbeatEvent::Missed {
+                gap: ms(101),
+                missed_beats: 0
+            })
+        );
+        // only reported once per timeout.
+        assert_eq!(t.check(ms(300)), None);
+        assert_eq!(
+            t.beat(6, ms(300)),
+            vec![HeartbeatEvent::Recovered {
+                gap: ms(250),
+                missed_beats: 3
+            }]
+        );
+        assert_eq!(t.check(ms(350)), None);
+    }
+
+    #[test]
+    fn test_counter_gaps_and_restarts() {
+        let mut t = HeartbeatTracker::new(ms(100));
+        t.beat(1, ms(0));
+        assert_eq!(
+            t.beat(3, ms(20)),
+            vec![HeartbeatEvent::Missed {
+                gap: ms(20),
+                missed_beats: 1
+            }]
+        );
+        assert_eq!(
+            t.beat(1, ms(30)),
+            vec![HeartbeatEvent::Alive { counter: 1 }]
+        );
+    }
+
+    #[test]
+    fn test_paused_and_reset_time() {
+        let mut t = HeartbeatTracker::new(ms(100));
+        t.beat(1, ms(5000));
+        // a paused clock does not advance, so nothing is missed.
+        for _ in 0..10 {
+            assert_eq!(t.check(ms(5000)), None);
+        }
+        // a restarted simulation starts over from the new time.
+        assert_eq!(t.check(ms(10)), None);
+        assert_eq!(t.check(ms(100)), None);
+        assert!(t.check(ms(111)).is_some());
+    }
+}
@@ -76,44 +76,206 @@ pub extern crate uuid;
 mod error;
 pub use error::{Error, Result};
 
+mod error_events;
+pub use error_events::{SpinError, SpinOperation};
+
 mod msg_types;
 pub use msg_types::generated_msgs::*;
 pub use msg_types::WrappedNativeMsg as NativeMsg;
+pub use msg_types::{deserialize_message, serialize_message};
+pub use msg_types::{HasHeader, NativeMsgView, WrappedTypesupport};
+
+#[cfg(r2r__geometry_msgs__msg__Pose)]
+pub mod geometry;
 
 mod utils;
 pub use utils::*;
 
+mod log_sinks;
+pub use log_sinks::{
+    configure_log_sinks, log_ring_buffer, log_sink_config, FileSinkOptions, LogSinkConfig,
+    RingBufferSink, RotatingFileSink, LOG_SINK_PARAMETER_PREFIX,
+};
+
+mod log_handler;
+pub use log_handler::{set_log_handler, LogHandler};
+
+mod message_sinks;
+pub use message_sinks::{ChannelSink, DeliverResult, MessageSink};
+
+mod qos;
+pub use qos::{DurabilityPolicy, HistoryPolicy, LivelinessPolicy, QosProfile, ReliabilityPolicy};
+
+mod qos_overrides;
+pub use qos_overrides::{
+    QosOverridePolicy, QosOverridingOptions, QosPolicyKind, QosValidationCallback,
+};
+
 mod subscribers;
+pub use subscribers::{
+    MessageInfo, ResubscribeEvent, ResubscribeOptions, Subscription, SubscriptionOptions,
+};
 
 mod publishers;
-pub use publishers::{Publisher, PublisherUntyped};
+pub use publishers::{
+    FlushStatus, Publisher, PublisherOptions, PublisherSerialized, PublisherUntyped,
+    RetainedPublisher,
+};
+
+mod validation;
+pub use validation::ValidationPolicy;
+
+mod publish_gates;
+pub use publish_gates::{DropCounts, DropReason, PublishOutcome};
+
+mod stats;
+pub use stats::{NodeMetrics, TopicStats};
+
+mod typesupport_loader;
+pub use typesupport_loader::{InterfaceTypeSupport, MessageTypeSupport, TypeSupportLibrary};
+
+mod dynamic_msgs;
 
 mod services;
-pub use services::ServiceRequest;
+pub use services::{
+    RequestVerdict, ServiceIntrospection, ServiceOptions, ServiceRequest, ServiceRequestUntyped,
+};
 
 mod clients;
-pub use clients::{Client, ClientUntyped};
+pub use clients::{Client, ClientOptions, ClientUntyped};
 
 mod action_common;
-pub use action_common::GoalStatus;
+mod action_debug;
+pub use action_common::{
+    parse_status_array, CancelResult, CancelReturnCode, GoalId, GoalMetadata, GoalStatus,
+};
 
 mod action_clients;
-pub use action_clients::{ActionClient, ActionClientGoal};
+pub use action_clients::{
+    ActionClient, ActionClientBuilder, ActionClientGoal, ActionClientOptions, BookkeepingStats,
+    ClientGoalHandle, GoalRequestBuilder, GoalWatchdog,
+};
 
 mod action_clients_untyped;
 pub use action_clients_untyped::{ActionClientGoalUntyped, ActionClientUntyped};
 
 mod action_servers;
-pub use action_servers::{ActionServerCancelRequest, ActionServerGoal, ActionServerGoalRequest};
+pub use action_servers::{
+    ActionServerBuilder, ActionServerCancelRequest, ActionServerGoal, ActionServerGoalRequest,
+    ActionServerOptions, CancelCallback, CancelDecision, FeedbackThrottle, GoalDecision, GoalQueue,
+};
+
+mod message_filter;
+pub use message_filter::{
+    CompareOp, FilterExpr, FilterField, FilterMode, FilterValue, FilteredSubscription,
+    MessageFilter,
+};
+
+mod field_access;
+pub use field_access::{DynamicValue, FieldSelection};
+
+mod mirror;
+pub use mirror::Mirror;
+
+mod readiness;
+pub use readiness::Dependency;
+
+mod periodic;
+
+mod diagnostics;
+pub use diagnostics::{DiagnosticLevel, DiagnosticStatus, DiagnosticUpdater};
+
+mod heartbeat;
+pub use heartbeat::{Heartbeat, HeartbeatEvent, HeartbeatMonitor};
+
+mod bond;
+pub use bond::{Bond, BondBreak, BondEvent, BondOptions, BondState};
+
+mod topic_rpc;
+pub use topic_rpc::{TopicRpcClient, TopicRpcOptions, TopicRpcRequest, TopicRpcServer};
+
+mod acked;
+pub use acked::{AckedOptions, AckedPublisher, AckedSubscriber};
+
+mod playback;
+pub use playback::{
+    PlaybackClock, PlaybackItem, PlaybackOptions, PlaybackProgress, PlaybackScheduler, SkippedItems,
+};
+
+mod node_names;
+pub use node_names::{DuplicateNamePolicy, NodeName, NodeOptions};
+
+mod arguments;
+pub use arguments::{RemapRule, ResolutionTrace, RosArguments};
+
+mod shutdown;
+pub use shutdown::{ShutdownOptions, ShutdownReport};
+
+mod distro;
+pub use distro::ros_distro;
 
 mod context;
-pub use context::Context;
+pub use context::{Context, ContextOptions};
+
+mod threads;
+pub use threads::ThreadHooks;
 
 mod parameters;
 pub use parameters::ParameterValue;
 
+mod parameter_serde;
+pub use parameter_serde::{from_parameters, to_parameters};
+
 mod clocks;
 pub use clocks::{Clock, ClockType};
 
+mod entities;
+pub use entities::EntityInfo;
+
+mod executor;
+pub use executor::{
+    EntityId, EntityKind, Executor, MultiThreadedExecutor, ReadyEntity, ReadyInfo,
+    SharedReadyEntity, SingleThreadedExecutor,
+};
+
+mod traits;
+pub use traits::{Entity, MessageStream, Publish};
+
 mod nodes;
-pub use nodes::{Node, Timer};
+pub use nodes::{Node, SpinBudget, Timer, TimerOptions, TopicEndpointInfo};
+
+mod node_scope;
+pub use node_scope::NodeScope;
+
+#[cfg(feature = "spin-diagnostics")]
+mod spin_state;
+#[cfg(feature = "spin-diagnostics")]
+pub use spin_state::{DeadlockSuspect, DeadlockWatchdog, SpinMonitor, SpinState};
+
+#[cfg(feature = "process-info")]
+mod process_info;
+#[cfg(all(feature = "process-info", target_os = "linux"))]
+pub use process_info::ProcSampler;
+#[cfg(feature = "process-info")]
+pub use process_info::{default_sampler, ProcessInfo, ProcessSample, ProcessSampler};
+
+#[cfg(feature = "mcap")]
+mod mcap;
+#[cfg(feature = "mcap")]
+pub use self::mcap::{message_definition, McapReader, McapRecorder, McapReplay, RecordedMessage};
+
+#[cfg(feature = "replay")]
+mod replay;
+#[cfg(feature = "replay")]
+pub use replay::{
+    ActionRecording, ExactMatch, FieldSubsetMatch, RecordedExchange, RecordedGoal,
+    RecordingActionClient, RecordingClient, ReplayActionServer, ReplayServiceServer,
+    RequestMatcher, ServiceRecording,
+};
+
+#[cfg(feature = "capi")]
+mod capi;
+
+pub mod test_support;
+
+pub mod prelude;
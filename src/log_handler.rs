@@ -0,0 +1,95 @@
+//! Where r2r logs its own messages, e.g. errors that occur while
+//! spinning or requests that could not be sent.
+//!
+//! By default messages of severity `Info` and above are written to
+//! stderr. A handler can be installed for the whole process with
+//! `set_log_handler`, or for the nodes of one context with
+//! `Context::set_log_handler`, which takes precedence.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use lazy_static::lazy_static;
+
+use crate::utils::LogSeverity;
+
+/// Handles a message r2r logs itself.
+pub type LogHandler = Arc<dyn Fn(LogSeverity, &str) + Send + Sync>;
+
+lazy_static! {
+    static ref PROCESS_LOG_HANDLER: Mutex<Option<LogHandler>> = Mutex::new(None);
+}
+
+/// Handle the messages r2r logs itself, for all contexts that have no
+/// handler of their own. `None` restores the default of stderr.
+pub fn set_log_handler(handler: Option<LogHandler>) {
+    *PROCESS_LOG_HANDLER.lock().unwrap() = handler;
+}
+
+/// Log a message with the handler of the process.
+pub(crate) fn log_internal(severity: LogSeverity, msg: &str) {
+    // not called with the lock held, the handler may log again.
+    let handler = PROCESS_LOG_HANDLER.lock().unwrap().clone();
+    match handler {
+        Some(handler) => handler(severity, msg),
+        None => {
+            if !matches!(severity, LogSeverity::Unset | LogSeverity::Debug) {
+                eprintln!("{}", msg);
+            }
+        }
+    }
+}
+
+/// The log handler of a context, shared with its nodes.
+#[derive(Clone, Default)]
+pub(crate) struct LogHandlerSlot(Arc<Mutex<Option<LogHandler>>>);
+
+impl LogHandlerSlot {
+    pub(crate) fn set(&self, handler: Option<LogHandler>) {
+        *self.0.lock().unwrap() = handler;
+    }
+
+    /// Log a message with this handler, or with the handler of the
+    /// process if there is none.
+    pub(crate) fn log(&self, severity: LogSeverity, msg: &str) {
+        let handler = self.0.lock().unwrap().clone();
+        match handler {
+            Some(handler) => handler(severity, msg),
+            None => log_internal(severity, msg),
+        }
+    }
+}
+
+impl fmt::Debug for LogHandlerSlot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let set = self.0.lock().unwrap().is_some();
+        f.debug_tuple("LogHandlerSlot").field(&set).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_handler_slot() {
+        let logged = Arc::new(Mutex::new(vec![]));
+        let slot = LogHandlerSlot::default();
+        let l = logged.clone();
+        slot.set(Some(Arc::new(move |severity, msg: &str| {
+            l.lock().unwrap().push((severity, msg.to_owned()))
+        })));
+        slot.log(LogSeverity::Warn, "could not send");
+        slot.log(LogSeverity::Debug, "no such request");
+        assert_eq!(
+            *logged.lock().unwrap(),
+            vec![
+                (LogSeverity::Warn, "could not send".to_owned()),
+                (LogSeverity::Debug, "no such request".to_owned())
+            ]
+        );
+        slot.set(None);
+        slot.log(LogSeverity::Warn, "to the process handler");
+        assert_eq!(logged.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,431 @@
+//! Where log messages end up.
+//!
+//! Besides the rcutils logging (stdout and `/rosout`), log messages
+//! can be written to a rotating file and kept in an in-memory ring
+//! buffer. The sinks are shared by all nodes of the process and can be
+//! switched at runtime, either with `configure_log_sinks` or through
+//! the `log_sinks.*` parameters of a node with a parameter handler.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lazy_static::lazy_static;
+
+use crate::error::*;
+use crate::log_handler::log_internal;
+use crate::parameters::ParameterValue;
+use crate::utils::LogSeverity;
+
+lazy_static! {
+    static ref LOG_SINKS: Mutex<Sinks> = Mutex::new(Sinks::default());
+}
+
+/// Prefix of the parameters that configure the log sinks.
+pub const LOG_SINK_PARAMETER_PREFIX: &str = "log_sinks.";
+
+/// Where and how a rotating log file is written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileSinkOptions {
+    pub path: PathBuf,
+    /// The file is rotated when it would grow beyond this many bytes.
+    pub max_size: u64,
+    /// Number of files kept, including the one written to. Rotated
+    /// files get the suffixes `.1` (the newest) to `.<max_files - 1>`.
+    pub max_files: usize,
+}
+
+impl FileSinkOptions {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileSinkOptions {
+            path: path.into(),
+            max_size: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+/// Which log sinks are enabled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogSinkConfig {
+    /// Log through rcutils, i.e. to stdout and `/rosout`.
+    pub stdout: bool,
+    pub file: Option<FileSinkOptions>,
+    /// Number of lines kept in the ring buffer, if enabled.
+    pub ring_buffer: Option<usize>,
+}
+
+impl Default for LogSinkConfig {
+    fn default() -> Self {
+        LogSinkConfig {
+            stdout: true,
+            file: None,
+            ring_buffer: None,
+        }
+    }
+}
+
+impl LogSinkConfig {
+    /// Reads the configuration from the `log_sinks.*` parameters:
+    ///
+    /// - `log_sinks.stdout` (bool)
+    /// - `log_sinks.file.path` (string, empty to disable)
+    /// - `log_sinks.file.max_size` (integer, bytes)
+    /// - `log_sinks.file.max_files` (integer)
+    /// - `log_sinks.ring_buffer.capacity` (integer, 0 to disable)
+    ///
+    /// Parameters that are not set keep their default.
+    pub fn from_parameters(params: &HashMap<String, ParameterValue>) -> Result<Self> {
+        let get = |name: &str| {
+            params
+                .get(&format!("{}{}", LOG_SINK_PARAMETER_PREFIX, name))
+                .filter(|v| **v != ParameterValue::NotSet)
+        };
+        let integer = |name: &str| match get(name) {
+            None => Ok(None),
+            Some(ParameterValue::Integer(i)) if *i >= 0 => Ok(Some(*i as u64)),
+            Some(v) => Err(invalid_parameter(name, v)),
+        };
+
+        let mut config = LogSinkConfig::default();
+        match get("stdout") {
+            None => (),
+            Some(ParameterValue::Bool(b)) => config.stdout = *b,
+            Some(v) => return Err(invalid_parameter("stdout", v)),
+        }
+        match get("file.path") {
+            None => (),
+            Some(ParameterValue::String(path)) if path.is_empty() => (),
+            Some(ParameterValue::String(path)) => {
+                let mut file = FileSinkOptions::new(path);
+                if let Some(max_size) = integer("file.max_size")? {
+                    file.max_size = max_size;
+                }
+                if let Some(max_files) = integer("file.max_files")? {
+                    file.max_files = max_files as usize;
+                }
+                config.file = Some(file);
+            }
+            Some(v) => return Err(invalid_parameter("file.path", v)),
+        }
+        config.ring_buffer = integer("ring_buffer.capacity")?
+            .filter(|c| *c > 0)
+            .map(|c| c as usize);
+        Ok(config)
+    }
+}
+
+fn invalid_parameter(name: &str, value: &ParameterValue) -> Error {
+    Error::LogSinkError {
+        reason: format!(
+            "invalid value for {}{}: {:?}",
+            LOG_SINK_PARAMETER_PREFIX, name, value
+        ),
+    }
+}
+
+/// Keeps the last lines that were logged.
+#[derive(Debug, Clone)]
+pub struct RingBufferSink {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    capacity: usize,
+}
+
+impl RingBufferSink {
+    pub fn new(capacity: usize) -> Self {
+        RingBufferSink {
+            lines: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The lines in the buffer, oldest first.
+    pub fn dump(&self) -> Vec<String> {
+        self.lines.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn push(&self, line: &str) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line.to_owned());
+    }
+
+    // Keeps the newest lines that fit.
+    fn resized(&self, capacity: usize) -> Self {
+        let mut lines = self.lines.lock().unwrap().clone();
+        while lines.len() > capacity {
+            lines.pop_front();
+        }
+        RingBufferSink {
+            lines: Arc::new(Mutex::new(lines)),
+            capacity,
+        }
+    }
+}
+
+/// Writes lines to a file, and rotates it when it gets too large.
+#[derive(Debug)]
+pub struct RotatingFileSink {
+    options: FileSinkOptions,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFileSink {
+    /// Appends to the file if it exists.
+    pub fn open(options: FileSinkOptions) -> Result<Self> {
+        let file = open_append(&options.path)?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(RotatingFileSink {
+            options,
+            file,
+            size,
+        })
+    }
+
+    pub fn options(&self) -> &FileSinkOptions {
+        &self.options
+    }
+
+    pub fn write_line(&mut self, line: &str) -> Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.size > 0 && self.size + len > self.options.max_size {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line).map_err(sink_error)?;
+        self.size += len;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        let path = &self.options.path;
+        if self.options.max_files > 1 {
+            // the oldest file is overwritten by the rename.
+            for i in (1..self.options.max_files - 1).rev() {
+                let from = rotated_path(path, i);
+                if from.exists() {
+                    std::fs::rename(&from, rotated_path(path, i + 1)).map_err(sink_error)?;
+                }
+            }
+            std::fs::rename(path, rotated_path(path, 1)).map_err(sink_error)?;
+        } else {
+            std::fs::remove_file(path).map_err(sink_error)?;
+        }
+        self.file = open_append(path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path, i: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", i));
+    PathBuf::from(name)
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(sink_error)
+}
+
+fn sink_error(e: std::io::Error) -> Error {
+    Error::LogSinkError {
+        reason: e.to_string(),
+    }
+}
+
+#[derive(Default)]
+struct Sinks {
+    config: LogSinkConfig,
+    file: Option<RotatingFileSink>,
+    ring_buffer: Option<RingBufferSink>,
+}
+
+/// Switches the log sinks of the process.
+///
+/// An open log file is kept open if its options do not change, and the
+/// ring buffer keeps its newest lines when its capacity changes.
+pub fn configure_log_sinks(config: &LogSinkConfig) -> Result<()> {
+    let mut sinks = LOG_SINKS.lock().unwrap();
+    let file = match &config.file {
+        Some(options) if sinks.file.as_ref().map(|f| f.options()) == Some(options) => {
+            sinks.file.take()
+        }
+        Some(options) => Some(RotatingFileSink::open(options.clone())?),
+        None => None,
+    };
+    let ring_buffer = config.ring_buffer.map(|capacity| match &sinks.ring_buffer {
+        Some(ring) if ring.capacity() == capacity => ring.clone(),
+        Some(ring) => ring.resized(capacity),
+        None => RingBufferSink::new(capacity),
+    });
+    sinks.config = config.clone();
+    sinks.file = file;
+    sinks.ring_buffer = ring_buffer;
+    Ok(())
+}
+
+/// The current configuration of the log sinks.
+pub fn log_sink_config() -> LogSinkConfig {
+    LOG_SINKS.lock().unwrap().config.clone()
+}
+
+/// The ring buffer sink, if enabled.
+pub fn log_ring_buffer() -> Option<RingBufferSink> {
+    LOG_SINKS.lock().unwrap().ring_buffer.clone()
+}
+
+// Writes to the file and ring buffer sinks, and returns whether the
+// message should be logged through rcutils as well.
+pub(crate) fn write_to_sinks(msg: &str, logger_name: &str, severity: LogSeverity) -> bool {
+    let mut sinks = LOG_SINKS.lock().unwrap();
+    let mut file_error = None;
+    if sinks.file.is_some() || sinks.ring_buffer.is_some() {
+        let line = format_line(SystemTime::now(), msg, logger_name, severity);
+        if let Some(ring) = &sinks.ring_buffer {
+            ring.push(&line);
+        }
+        if let Some(file) = &mut sinks.file {
+            if let Err(e) = file.write_line(&line) {
+                file_error = Some(e);
+                sinks.file = None;
+                sinks.config.file = None;
+            }
+        }
+    }
+    let stdout = sinks.config.stdout;
+    // the handler may log again, which ends up here.
+    drop(sinks);
+    if let Some(e) = file_error {
+        log_internal(
+            LogSeverity::Error,
+            &format!("could not write to log file, disabling it: {}", e),
+        );
+    }
+    stdout
+}
+
+// Like the default format of rcutils.
+fn format_line(stamp: SystemTime, msg: &str, logger_name: &str, severity: LogSeverity) -> String {
+    let stamp = stamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let severity = match severity {
+        LogSeverity::Unset => "UNSET",
+        LogSeverity::Debug => "DEBUG",
+        LogSeverity::Info => "INFO",
+        LogSeverity::Warn => "WARN",
+        LogSeverity::Error => "ERROR",
+        LogSeverity::Fatal => "FATAL",
+    };
+    format!(
+        "[{}] [{}.{:09}] [{}]: {}",
+        severity,
+        stamp.as_secs(),
+        stamp.subsec_nanos(),
+        logger_name,
+        msg
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_ring_buffer() {
+        let ring = RingBufferSink::new(2);
+        ring.push("a");
+        ring.push("b");
+        ring.push("c");
+        assert_eq!(ring.dump(), vec!["b", "c"]);
+        assert_eq!(ring.resized(1).dump(), vec!["c"]);
+        assert_eq!(ring.resized(5).dump(), vec!["b", "c"]);
+    }
+
+    #[test]
+    fn test_rotating_file() {
+        let dir = std::env::temp_dir().join(format!("r2r_log_sinks_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("node.log");
+        let mut file = RotatingFileSink::open(FileSinkOptions {
+            path: path.clone(),
+            max_size: 8,
+            max_files: 3,
+        })
+        .unwrap();
+        for line in &["one", "two", "three", "four"] {
+            file.write_line(line).unwrap();
+        }
+        let read = |p: &Path| std::fs::read_to_string(p).unwrap();
+        assert_eq!(read(&path), "four\n");
+        assert_eq!(read(&rotated_path(&path, 1)), "three\n");
+        assert_eq!(read(&rotated_path(&path, 2)), "one\ntwo\n");
+        assert!(!rotated_path(&path, 3).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_config_from_parameters() {
+        let mut params = HashMap::new();
+        assert_eq!(
+            LogSinkConfig::from_parameters(&params).unwrap(),
+            LogSinkConfig::default()
+        );
+
+        params.insert("log_sinks.stdout".to_owned(), ParameterValue::Bool(false));
+        params.insert(
+            "log_sinks.file.path".to_owned(),
+            ParameterValue::String("/tmp/node.log".into()),
+        );
+        params.insert(
+            "log_sinks.file.max_files".to_owned(),
+            ParameterValue::Integer(2),
+        );
+        params.insert(
+            "log_sinks.ring_buffer.capacity".to_owned(),
+            ParameterValue::Integer(10000),
+        );
+        let config = LogSinkConfig::from_parameters(&params).unwrap();
+        assert!(!config.stdout);
+        let file = config.file.unwrap();
+        assert_eq!(file.path, PathBuf::from("/tmp/node.log"));
+        assert_eq!(file.max_files, 2);
+        assert_eq!(file.max_size, FileSinkOptions::new("").max_size);
+        assert_eq!(config.ring_buffer, Some(10000));
+
+        params.insert(
+            "log_sinks.ring_buffer.capacity".to_owned(),
+            ParameterValue::Integer(0),
+        );
+        assert_eq!(
+            LogSinkConfig::from_parameters(&params).unwrap().ring_buffer,
+            None
+        );
+        params.insert(
+            "log_sinks.ring_buffer.capacity".to_owned(),
+            ParameterValue::String("many".into()),
+        );
+        assert!(LogSinkConfig::from_parameters(&params).is_err());
+    }
+
+    #[test]
+    fn test_format_line() {
+        let stamp = UNIX_EPOCH + Duration::new(12, 34);
+        assert_eq!(
+            format_line(stamp, "hello", "node", LogSeverity::Warn),
+            "[WARN] [12.000000034] [node]: hello"
+        );
+    }
+}
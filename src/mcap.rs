@@ -0,0 +1,427 @@
+//! Recording to and playback from MCAP files.
+//!
+//! Only built with the `mcap` feature. Messages are stored CDR encoded
+//! as received from the middleware, with the `.msg` definitions of
+//! their types as `ros2msg` schemas, which is what rosbag2 and
+//! Foxglove expect. The definitions are read from the `share`
+//! directories of `AMENT_PREFIX_PATH`.
+
+use futures::future::FutureExt;
+use futures::stream::{Stream, StreamExt};
+use mcap_rs::records::MessageHeader;
+use mcap_rs::{Channel, Schema};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::*;
+use crate::msg_types::{deserialize_message, serialize_message, WrappedTypesupport};
+use crate::nodes::Node;
+use crate::publishers::PublisherSerialized;
+use crate::typesupport_loader::MessageTypeSupport;
+
+const SCHEMA_ENCODING: &str = "ros2msg";
+const MESSAGE_ENCODING: &str = "cdr";
+// separates the definitions of the dependencies in a ros2msg schema.
+const DEFINITION_SEPARATOR: &str =
+    "================================================================================";
+
+fn recording_error(e: impl std::fmt::Display) -> Error {
+    Error::RecordingError {
+        reason: e.to_string(),
+    }
+}
+
+/// Writes messages to an MCAP file.
+///
+/// Messages can be written directly with `write` and `write_typed`,
+/// or taken from subscriptions made with `record`. The file is
+/// complete once `finish` is called or the recorder is dropped.
+pub struct McapRecorder {
+    writer: mcap_rs::Writer<'static, BufWriter<File>>,
+    // topic -> channel id and type
+    channels: HashMap<String, (u16, String)>,
+    sequence: u32,
+    subscriptions: Vec<(String, Box<dyn Stream<Item = Vec<u8>> + Unpin + Send>)>,
+}
+
+impl McapRecorder {
+    /// Create (or truncate) the file at `path`.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::create(path).map_err(recording_error)?;
+        let writer = mcap_rs::Writer::new(BufWriter::new(file)).map_err(recording_error)?;
+        Ok(McapRecorder {
+            writer,
+            channels: HashMap::new(),
+            sequence: 0,
+            subscriptions: vec![],
+        })
+    }
+
+    /// Add a channel for `topic` with messages of `type_name`, e.g.
+    /// "std_msgs/msg/String". Done by `write` when needed, but fails
+    /// if the topic already has a channel of another type.
+    pub fn add_topic(&mut self, topic: &str, type_name: &str) -> Result<()> {
+        match self.channels.get(topic) {
+            Some((_, t)) if t == type_name => return Ok(()),
+            Some((_, t)) => {
+                return Err(recording_error(format!(
+                    "{} is recorded as {}, not {}",
+                    topic, t, type_name
+                )))
+            }
+            None => (),
+        }
+        let schema = Schema {
+            name: type_name.to_owned(),
+            encoding: SCHEMA_ENCODING.to_owned(),
+            data: Cow::Owned(message_definition(type_name)?.into_bytes()),
+        };
+        let channel = Channel {
+            topic: topic.to_owned(),
+            schema: Some(Arc::new(schema)),
+            message_encoding: MESSAGE_ENCODING.to_owned(),
+            metadata: BTreeMap::new(),
+        };
+        let id = self.writer.add_channel(&channel).map_err(recording_error)?;
+        self.channels
+            .insert(topic.to_owned(), (id, type_name.to_owned()));
+        Ok(())
+    }
+
+    /// Write a message that is already serialized, e.g. received with
+    /// `Node::subscribe_serialized`. `log_time` is the time since the
+    /// unix epoch.
+    pub fn write(
+        &mut self,
+        topic: &str,
+        type_name: &str,
+        data: &[u8],
+        log_time: Duration,
+    ) -> Result<()> {
+        self.add_topic(topic, type_name)?;
+        let (channel_id, _) = self.channels[topic];
+        self.sequence = self.sequence.wrapping_add(1);
+        let time = log_time.as_nanos() as u64;
+        let header = MessageHeader {
+            channel_id,
+            sequence: self.sequence,
+            log_time: time,
+            publish_time: time,
+        };
+        self.writer
+            .write_to_known_channel(&header, data)
+            .map_err(recording_error)
+    }
+
+    /// Serialize `msg` and write it.
+    pub fn write_typed<T>(
+        &mut self,
+        topic: &str,
+        type_name: &str,
+        msg: &T,
+        log_time: Duration,
+    ) -> Result<()>
+    where
+        T: WrappedTypesupport,
+    {
+        let data = serialize_message(msg)?;
+        self.write(topic, type_name, &data, log_time)
+    }
+
+    /// Subscribe to `topic` on `node` and record the messages received
+    /// on it. The messages are written by `write_pending`, with the
+    /// time they are written as log time.
+    pub fn record(&mut self, node: &mut Node, topic: &str, type_name: &str) -> Result<()> {
+        self.add_topic(topic, type_name)?;
+        let type_support = MessageTypeSupport::for_type_name(type_name)?;
+        let stream = node.subscribe_serialized(topic, &type_support)?;
+        self.subscriptions
+            .push((topic.to_owned(), Box::new(stream)));
+        Ok(())
+    }
+
+    /// Write the messages that the subscriptions made with `record`
+    /// have received. Call it after spinning the node. Returns the
+    /// number of messages written.
+    pub fn write_pending(&mut self) -> Result<usize> {
+        let mut pending = vec![];
+        for (topic, stream) in self.subscriptions.iter_mut() {
+            while let Some(Some(data)) = stream.next().now_or_never() {
+                pending.push((topic.clone(), data));
+            }
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let count = pending.len();
+        for (topic, data) in pending {
+            let type_name = self.channels[&topic].1.clone();
+            self.write(&topic, &type_name, &data, now)?;
+        }
+        Ok(count)
+    }
+
+    /// Write the pending messages and complete the file.
+    pub fn finish(mut self) -> Result<()> {
+        self.write_pending()?;
+        self.writer.finish().map_err(recording_error)
+    }
+}
+
+/// A message read from an MCAP file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedMessage {
+    pub topic: String,
+    /// The schema name, e.g. "std_msgs/msg/String".
+    pub type_name: String,
+    /// Time since the unix epoch.
+    pub log_time: Duration,
+    /// The message as serialized by the middleware (CDR).
+    pub data: Vec<u8>,
+}
+
+impl RecordedMessage {
+    /// Deserialize the message. `T` has to match `type_name`.
+    pub fn decode<T>(&self) -> Result<T>
+    where
+        T: WrappedTypesupport,
+    {
+        deserialize_message(&self.data)
+    }
+}
+
+/// Reads all messages of an MCAP file, in log time order.
+#[derive(Debug, Clone)]
+pub struct McapReader {
+    messages: Vec<RecordedMessage>,
+}
+
+impl McapReader {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path: PathBuf = path.as_ref().into();
+        let buf = std::fs::read(&path).map_err(recording_error)?;
+        let stream = mcap_rs::MessageStream::new(&buf).map_err(recording_error)?;
+        let mut messages = vec![];
+        for msg in stream {
+            let msg = msg.map_err(recording_error)?;
+            let schema = msg.channel.schema.as_ref();
+            if msg.channel.message_encoding != MESSAGE_ENCODING
+                || schema.map(|s| s.encoding.as_str()) != Some(SCHEMA_ENCODING)
+            {
+                return Err(recording_error(format!(
+                    "{}: {} is not a ROS 2 topic with CDR encoded messages",
+                    path.display(),
+                    msg.channel.topic
+                )));
+            }
+            messages.push(RecordedMessage {
+                topic: msg.channel.topic.clone(),
+                type_name: schema.map(|s| s.name.clone()).unwrap_or_default(),
+                log_time: Duration::from_nanos(msg.log_time),
+                data: msg.data.into_owned(),
+            });
+        }
+        // chunks do not have to be in order.
+        messages.sort_by_key(|m| m.log_time);
+        Ok(McapReader { messages })
+    }
+
+    pub fn messages(&self) -> &[RecordedMessage] {
+        &self.messages
+    }
+
+    /// The recorded topics and their types.
+    pub fn topics(&self) -> BTreeMap<String, String> {
+        self.messages
+            .iter()
+            .map(|m| (m.topic.clone(), m.type_name.clone()))
+            .collect()
+    }
+
+    /// The deserialized messages of `topic`.
+    pub fn typed<T>(&self, topic: &str) -> Result<Vec<T>>
+    where
+        T: WrappedTypesupport,
+    {
+        self.messages
+            .iter()
+            .filter(|m| m.topic == topic)
+            .map(|m| m.decode())
+            .collect()
+    }
+
+    /// Create a publisher on `node` for each recorded topic, for
+    /// publishing the messages again with `McapReplay::publish_all`.
+    pub fn replay(&self, node: &mut Node) -> Result<McapReplay> {
+        let mut publishers = HashMap::new();
+        for (topic, type_name) in self.topics() {
+            let type_support = MessageTypeSupport::for_type_name(&type_name)?;
+            let publisher = node.create_publisher_serialized(&topic, &type_support)?;
+            publishers.insert(topic, publisher);
+        }
+        Ok(McapReplay {
+            publishers,
+            messages: self.messages.clone(),
+        })
+    }
+}
+
+/// Publishers for the topics of an MCAP file, see `McapReader::replay`.
+pub struct McapReplay {
+    publishers: HashMap<String, PublisherSerialized>,
+    messages: Vec<RecordedMessage>,
+}
+
+impl McapReplay {
+    /// The publisher of `topic`, e.g. for waiting until subscribers
+    /// have matched before publishing.
+    pub fn publisher(&self, topic: &str) -> Option<&PublisherSerialized> {
+        self.publishers.get(topic)
+    }
+
+    /// Publish all messages in log time order, without waiting in
+    /// between. Returns the number of messages published.
+    pub fn publish_all(&self) -> Result<usize> {
+        for msg in &self.messages {
+            self.publishers[&msg.topic].publish(&msg.data)?;
+        }
+        Ok(self.messages.len())
+    }
+}
+
+/// The definition of the message `type_name` (e.g.
+/// "geometry_msgs/msg/Pose") in the `ros2msg` schema encoding: the
+/// `.msg` file of the type followed by those of all types it uses.
+pub fn message_definition(type_name: &str) -> Result<String> {
+    let root = normalize_type_name(type_name, "")?;
+    let mut definition = read_msg_file(&root)?;
+    let mut done = vec![root.clone()];
+    let mut todo: VecDeque<String> = dependencies(&root, &definition)?.into();
+    while let Some(dep) = todo.pop_front() {
+        if done.contains(&dep) {
+            continue;
+        }
+        let text = read_msg_file(&dep)?;
+        definition.push_str(&format!(
+            "\n{}\nMSG: {}\n{}",
+            DEFINITION_SEPARATOR, dep, text
+        ));
+        todo.extend(dependencies(&dep, &text)?);
+        done.push(dep);
+    }
+    Ok(definition)
+}
+
+// "pkg/msg/Name" from "pkg/msg/Name", "pkg/Name" or, for a type of
+// `package`, "Name".
+fn normalize_type_name(type_name: &str, package: &str) -> Result<String> {
+    let parts: Vec<&str> = type_name.split('/').collect();
+    match parts.as_slice() {
+        [name] if !package.is_empty() => Ok(format!("{}/msg/{}", package, name)),
+        [package, name] => Ok(format!("{}/msg/{}", package, name)),
+        [package, "msg", name] => Ok(format!("{}/msg/{}", package, name)),
+        _ => Err(Error::InvalidMessageType {
+            msgtype: type_name.to_owned(),
+        }),
+    }
+}
+
+// The message types used by the fields of the definition `text` of
+// `type_name`.
+fn dependencies(type_name: &str, text: &str) -> Result<Vec<String>> {
+    const BUILTIN: &[&str] = &[
+        "bool", "byte", "char", "float32", "float64", "int8", "uint8", "int16", "uint16", "int32",
+        "uint32", "int64", "uint64", "string", "wstring",
+    ];
+    let package = type_name.split('/').next().unwrap_or_default();
+    let mut deps = vec![];
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let field_type = match line.split_whitespace().next() {
+            Some(t) => t,
+            None => continue,
+        };
+        // strip arrays and string bounds, e.g. "int32[<=3]" or "string<=10".
+        let field_type = field_type.split('[').next().unwrap_or_default();
+        let field_type = field_type.split("<=").next().unwrap_or_default();
+        if BUILTIN.contains(&field_type) {
+            continue;
+        }
+        let dep = normalize_type_name(field_type, package)?;
+        if !deps.contains(&dep) {
+            deps.push(dep);
+        }
+    }
+    Ok(deps)
+}
+
+fn read_msg_file(type_name: &str) -> Result<String> {
+    let mut parts = type_name.split('/');
+    let (package, name) = match (parts.next(), parts.last()) {
+        (Some(package), Some(name)) => (package, name),
+        _ => {
+            return Err(Error::InvalidMessageType {
+                msgtype: type_name.to_owned(),
+            })
+        }
+    };
+    let prefixes = std::env::var("AMENT_PREFIX_PATH").unwrap_or_default();
+    for prefix in std::env::split_paths(&prefixes) {
+        let path = prefix
+            .join("share")
+            .join(package)
+            .join("msg")
+            .join(format!("{}.msg", name));
+        if let Ok(text) = std::fs::read_to_string(&path) {
+            return Ok(text.trim_end().to_owned());
+        }
+    }
+    Err(recording_error(format!(
+        "no definition of {} found in AMENT_PREFIX_PATH",
+        type_name
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_type_name() {
+        let n = |t: &str, p: &str| normalize_type_name(t, p).ok();
+        assert_eq!(
+            n("std_msgs/msg/Header", ""),
+            Some("std_msgs/msg/Header".into())
+        );
+        assert_eq!(
+            n("std_msgs/Header", "geometry_msgs"),
+            Some("std_msgs/msg/Header".into())
+        );
+        assert_eq!(
+            n("Point", "geometry_msgs"),
+            Some("geometry_msgs/msg/Point".into())
+        );
+        assert_eq!(n("Point", ""), None);
+        assert_eq!(n("a/b/c/d", ""), None);
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let text = "# a comment with std_msgs/Header in it\n\
+                    std_msgs/Header header\n\
+                    Point[] points  # trailing comment\n\
+                    Point[<=3] more\n\
+                    string<=10 name\n\
+                    int32 FOO=1\n\
+                    float64[9] covariance\n";
+        assert_eq!(
+            dependencies("geometry_msgs/msg/Polygon", text).unwrap(),
+            vec!["std_msgs/msg/Header", "geometry_msgs/msg/Point"]
+        );
+    }
+}
@@ -0,0 +1,523 @@
+//! Filtering subscriptions, see `Node::subscribe_filtered`.
+//!
+//! A `FilterExpr` compares scalar fields of a message with constants.
+//! Such expressions can be handed to the rmw as a DDS content filter,
+//! so that messages that do not match are dropped before they reach
+//! the node. Where that is not possible (older distributions, rmw
+//! implementations without content filters, expressions the rmw
+//! rejects) the same expression is evaluated in Rust on each received
+//! message instead.
+
+use futures::stream::Stream;
+use serde_json::Value;
+use std::fmt;
+use std::ops;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::error::*;
+use crate::msg_types::WrappedTypesupport;
+use crate::subscribers::Subscription;
+
+// DDS implementations only need to support this many parameters.
+const MAX_PARAMETERS: usize = 100;
+
+/// A constant to compare a field with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl From<bool> for FilterValue {
+    fn from(v: bool) -> Self {
+        FilterValue::Bool(v)
+    }
+}
+
+impl From<i64> for FilterValue {
+    fn from(v: i64) -> Self {
+        FilterValue::Int(v)
+    }
+}
+
+impl From<i32> for FilterValue {
+    fn from(v: i32) -> Self {
+        FilterValue::Int(v as i64)
+    }
+}
+
+impl From<u32> for FilterValue {
+    fn from(v: u32) -> Self {
+        FilterValue::Int(v as i64)
+    }
+}
+
+impl From<f64> for FilterValue {
+    fn from(v: f64) -> Self {
+        FilterValue::Float(v)
+    }
+}
+
+impl From<f32> for FilterValue {
+    fn from(v: f32) -> Self {
+        FilterValue::Float(v as f64)
+    }
+}
+
+impl From<&str> for FilterValue {
+    fn from(v: &str) -> Self {
+        FilterValue::String(v.to_owned())
+    }
+}
+
+impl From<String> for FilterValue {
+    fn from(v: String) -> Self {
+        FilterValue::String(v)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    fn dds(&self) -> &'static str {
+        match self {
+            CompareOp::Eq => "=",
+            CompareOp::Ne => "<>",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        }
+    }
+
+    fn holds(&self, ordering: std::cmp::Ordering) -> bool {
+        use std::cmp::Ordering::*;
+        match self {
+            CompareOp::Eq => ordering == Equal,
+            CompareOp::Ne => ordering != Equal,
+            CompareOp::Lt => ordering == Less,
+            CompareOp::Le => ordering != Greater,
+            CompareOp::Gt => ordering == Greater,
+            CompareOp::Ge => ordering != Less,
+        }
+    }
+}
+
+/// A condition on the fields of a message.
+///
+/// Build it with `FilterExpr::field` and combine the conditions with
+/// `and`, `or` and `!`:
+///
+/// ```
+/// use r2r::FilterExpr;
+/// let expr = FilterExpr::field("header.frame_id")
+///     .eq("base")
+///     .and(FilterExpr::field("range_max").gt(2.0));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterExpr {
+    Compare {
+        /// Field names separated by dots, e.g. "header.frame_id".
+        field: String,
+        op: CompareOp,
+        value: FilterValue,
+    },
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+    Not(Box<FilterExpr>),
+}
+
+/// A field of a message, see `FilterExpr::field`.
+#[derive(Debug, Clone)]
+pub struct FilterField(String);
+
+impl FilterField {
+    fn compare(self, op: CompareOp, value: impl Into<FilterValue>) -> FilterExpr {
+        FilterExpr::Compare {
+            field: self.0,
+            op,
+            value: value.into(),
+        }
+    }
+
+    pub fn eq(self, value: impl Into<FilterValue>) -> FilterExpr {
+        self.compare(CompareOp::Eq, value)
+    }
+
+    pub fn ne(self, value: impl Into<FilterValue>) -> FilterExpr {
+        self.compare(CompareOp::Ne, value)
+    }
+
+    pub fn lt(self, value: impl Into<FilterValue>) -> FilterExpr {
+        self.compare(CompareOp::Lt, value)
+    }
+
+    pub fn le(self, value: impl Into<FilterValue>) -> FilterExpr {
+        self.compare(CompareOp::Le, value)
+    }
+
+    pub fn gt(self, value: impl Into<FilterValue>) -> FilterExpr {
+        self.compare(CompareOp::Gt, value)
+    }
+
+    pub fn ge(self, value: impl Into<FilterValue>) -> FilterExpr {
+        self.compare(CompareOp::Ge, value)
+    }
+}
+
+impl FilterExpr {
+    /// A scalar field of the message, given as field names separated
+    /// by dots, e.g. "header.frame_id".
+    pub fn field(path: &str) -> FilterField {
+        FilterField(path.to_owned())
+    }
+
+    pub fn and(self, other: FilterExpr) -> FilterExpr {
+        FilterExpr::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: FilterExpr) -> FilterExpr {
+        FilterExpr::Or(Box::new(self), Box::new(other))
+    }
+
+    /// Checks that all field paths are well formed.
+    pub(crate) fn validate(&self) -> Result<()> {
+        match self {
+            FilterExpr::Compare { field, .. } => {
+                let valid = field.split('.').all(|name| {
+                    let mut chars = name.chars();
+                    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+                        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+                });
+                if valid {
+                    Ok(())
+                } else {
+                    Err(Error::InvalidFilter {
+                        reason: format!("invalid field name \"{}\"", field),
+                    })
+                }
+            }
+            FilterExpr::And(a, b) | FilterExpr::Or(a, b) => {
+                a.validate()?;
+                b.validate()
+            }
+            FilterExpr::Not(a) => a.validate(),
+        }
+    }
+
+    /// Translates the expression to a DDS content filter expression
+    /// and its parameters. Returns why not if the expression cannot be
+    /// expressed as a content filter.
+    pub(crate) fn to_content_filter(&self) -> std::result::Result<ContentFilter, String> {
+        let mut parameters = vec![];
+        let expression = self.write_dds(&mut parameters)?;
+        if parameters.len() > MAX_PARAMETERS {
+            return Err(format!(
+                "{} parameters, at most {} are supported",
+                parameters.len(),
+                MAX_PARAMETERS
+            ));
+        }
+        Ok(ContentFilter {
+            expression,
+            parameters,
+        })
+    }
+
+    fn write_dds(&self, parameters: &mut Vec<String>) -> std::result::Result<String, String> {
+        Ok(match self {
+            FilterExpr::Compare { field, op, value } => {
+                let parameter = match value {
+                    FilterValue::Bool(true) => "TRUE".to_owned(),
+                    FilterValue::Bool(false) => "FALSE".to_owned(),
+                    FilterValue::Int(v) => v.to_string(),
+                    FilterValue::Float(v) if v.is_finite() => format!("{:?}", v),
+                    FilterValue::Float(v) => return Err(format!("cannot compare with {}", v)),
+                    // the filter syntax has no way to escape quotes.
+                    FilterValue::String(s) if s.contains('\'') => {
+                        return Err(format!("string {:?} contains a quote", s))
+                    }
+                    FilterValue::String(s) => format!("'{}'", s),
+                };
+                parameters.push(parameter);
+                format!("{} {} %{}", field, op.dds(), parameters.len() - 1)
+            }
+            FilterExpr::And(a, b) => format!(
+                "({}) AND ({})",
+                a.write_dds(parameters)?,
+                b.write_dds(parameters)?
+            ),
+            FilterExpr::Or(a, b) => format!(
+                "({}) OR ({})",
+                a.write_dds(parameters)?,
+                b.write_dds(parameters)?
+            ),
+            FilterExpr::Not(a) => format!("NOT ({})", a.write_dds(parameters)?),
+        })
+    }
+
+    /// Evaluates the expression on a message converted to json. Fields
+    /// that do not exist or have another type do not match.
+    pub fn matches(&self, msg: &Value) -> bool {
+        match self {
+            FilterExpr::Compare { field, op, value } => {
+                let found = field.split('.').try_fold(msg, |v, name| v.get(name));
+                match found.and_then(|found| compare_values(found, value)) {
+                    Some(ordering) => op.holds(ordering),
+                    None => false,
+                }
+            }
+            FilterExpr::And(a, b) => a.matches(msg) && b.matches(msg),
+            FilterExpr::Or(a, b) => a.matches(msg) || b.matches(msg),
+            FilterExpr::Not(a) => !a.matches(msg),
+        }
+    }
+}
+
+impl ops::Not for FilterExpr {
+    type Output = FilterExpr;
+
+    fn not(self) -> FilterExpr {
+        FilterExpr::Not(Box::new(self))
+    }
+}
+
+fn compare_values(found: &Value, value: &FilterValue) -> Option<std::cmp::Ordering> {
+    match (found, value) {
+        (Value::Bool(a), FilterValue::Bool(b)) => Some(a.cmp(b)),
+        (Value::String(a), FilterValue::String(b)) => Some(a.as_str().cmp(b.as_str())),
+        (Value::Number(a), FilterValue::Int(b)) => match a.as_i64() {
+            Some(a) => Some(a.cmp(b)),
+            None => a.as_f64()?.partial_cmp(&(*b as f64)),
+        },
+        (Value::Number(a), FilterValue::Float(b)) => a.as_f64()?.partial_cmp(b),
+        _ => None,
+    }
+}
+
+/// A filter expression in the syntax of DDS content filtered topics.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ContentFilter {
+    pub expression: String,
+    pub parameters: Vec<String>,
+}
+
+/// What a `FilteredSubscription` filters on.
+pub struct MessageFilter<T> {
+    expr: Option<FilterExpr>,
+    predicate: Option<Box<dyn FnMut(&T) -> bool + Send>>,
+}
+
+impl<T> MessageFilter<T> {
+    /// Filter on an expression, with a content filter if possible.
+    pub fn expression(expr: FilterExpr) -> Self {
+        MessageFilter {
+            expr: Some(expr),
+            predicate: None,
+        }
+    }
+
+    /// Filter with a closure. Always done in Rust after the message
+    /// has been received.
+    pub fn predicate<F>(predicate: F) -> Self
+    where
+        F: FnMut(&T) -> bool + Send + 'static,
+    {
+        MessageFilter {
+            expr: None,
+            predicate: Some(Box::new(predicate)),
+        }
+    }
+
+    /// Additionally require `predicate` to hold, e.g. for conditions
+    /// that cannot be written as an expression.
+    pub fn with_predicate<F>(self, predicate: F) -> Self
+    where
+        F: FnMut(&T) -> bool + Send + 'static,
+    {
+        MessageFilter {
+            expr: self.expr,
+            predicate: Some(Box::new(predicate)),
+        }
+    }
+
+    pub(crate) fn expr(&self) -> Option<&FilterExpr> {
+        self.expr.as_ref()
+    }
+}
+
+impl<T> From<FilterExpr> for MessageFilter<T> {
+    fn from(expr: FilterExpr) -> Self {
+        MessageFilter::expression(expr)
+    }
+}
+
+/// Where the expression of a `FilteredSubscription` is evaluated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterMode {
+    /// By the rmw, with this content filter expression.
+    ContentFilter { expression: String },
+    /// In Rust after each message has been received, for `reason`.
+    Local { reason: String },
+}
+
+impl fmt::Display for FilterMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterMode::ContentFilter { expression } => {
+                write!(f, "content filter \"{}\"", expression)
+            }
+            FilterMode::Local { reason } => write!(f, "local filter ({})", reason),
+        }
+    }
+}
+
+/// A stream of the messages of a subscription that pass a
+/// `MessageFilter`.
+pub struct FilteredSubscription<T> {
+    subscription: Subscription<T>,
+    mode: FilterMode,
+    // evaluated in Rust, None when handled by the content filter.
+    local_expr: Option<FilterExpr>,
+    predicate: Option<Box<dyn FnMut(&T) -> bool + Send>>,
+}
+
+impl<T> FilteredSubscription<T>
+where
+    T: WrappedTypesupport,
+{
+    pub(crate) fn new(
+        subscription: Subscription<T>,
+        filter: MessageFilter<T>,
+        mode: FilterMode,
+    ) -> Self {
+        let local_expr = match mode {
+            FilterMode::ContentFilter { .. } => None,
+            FilterMode::Local { .. } => filter.expr,
+        };
+        FilteredSubscription {
+            subscription,
+            mode,
+            local_expr,
+            predicate: filter.predicate,
+        }
+    }
+
+    /// Where the filter expression is evaluated. A closure predicate is
+    /// always evaluated in Rust.
+    pub fn mode(&self) -> &FilterMode {
+        &self.mode
+    }
+
+    fn accept(&mut self, msg: &T) -> bool {
+        if let Some(expr) = &self.local_expr {
+            match serde_json::to_value(msg) {
+                Ok(value) if expr.matches(&value) => (),
+                _ => return false,
+            }
+        }
+        match &mut self.predicate {
+            Some(predicate) => predicate(msg),
+            None => true,
+        }
+    }
+}
+
+impl<T> Stream for FilteredSubscription<T>
+where
+    T: WrappedTypesupport,
+{
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        loop {
+            match Pin::new(&mut self.subscription).poll_next(cx) {
+                Poll::Ready(Some(msg)) => {
+                    if self.accept(&msg) {
+                        return Poll::Ready(Some(msg));
+                    }
+                }
+                other => return other,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn scan() -> Value {
+        json!({
+            "header": { "frame_id": "base", "stamp": { "sec": 3, "nanosec": 0 } },
+            "range_max": 10.5,
+            "ranges": [1.0, 2.0],
+            "valid": true,
+        })
+    }
+
+    #[test]
+    fn test_content_filter_translation() {
+        let expr = FilterExpr::field("header.frame_id").eq("base").and(
+            FilterExpr::field("range_max")
+                .gt(2.0)
+                .or(!FilterExpr::field("valid").eq(true)),
+        );
+        let filter = expr.to_content_filter().unwrap();
+        assert_eq!(
+            filter.expression,
+            "(header.frame_id = %0) AND ((range_max > %1) OR (NOT (valid = %2)))"
+        );
+        assert_eq!(filter.parameters, vec!["'base'", "2.0", "TRUE"]);
+
+        let quoted = FilterExpr::field("header.frame_id").eq("it's");
+        assert!(quoted.to_content_filter().is_err());
+        let nan = FilterExpr::field("range_max").lt(f64::NAN);
+        assert!(nan.to_content_filter().is_err());
+    }
+
+    #[test]
+    fn test_validate_field_names() {
+        assert!(FilterExpr::field("header.stamp.sec")
+            .ge(0)
+            .validate()
+            .is_ok());
+        assert!(FilterExpr::field("header..sec").ge(0).validate().is_err());
+        assert!(FilterExpr::field("x; DROP").eq(1).validate().is_err());
+        assert!(FilterExpr::field("1x").eq(1).validate().is_err());
+    }
+
+    #[test]
+    fn test_local_evaluation() {
+        let msg = scan();
+        assert!(FilterExpr::field("header.frame_id")
+            .eq("base")
+            .matches(&msg));
+        assert!(FilterExpr::field("header.frame_id").lt("c").matches(&msg));
+        assert!(FilterExpr::field("header.stamp.sec").eq(3).matches(&msg));
+        assert!(FilterExpr::field("header.stamp.sec").gt(2.5).matches(&msg));
+        assert!(FilterExpr::field("range_max").le(10.5).matches(&msg));
+        assert!(!FilterExpr::field("range_max").ne(10.5).matches(&msg));
+        assert!((!FilterExpr::field("valid").eq(false)).matches(&msg));
+        // missing fields and mismatching types never match.
+        assert!(!FilterExpr::field("missing").eq(1).matches(&msg));
+        assert!(!FilterExpr::field("header.frame_id").eq(1).matches(&msg));
+        assert!(!FilterExpr::field("ranges").eq(1.0).matches(&msg));
+        let either = FilterExpr::field("missing")
+            .eq(1)
+            .or(FilterExpr::field("valid").eq(true));
+        assert!(either.matches(&msg));
+    }
+}
@@ -0,0 +1,90 @@
+//! Where the spin delivers received messages, service requests and
+//! feedback.
+//!
+//! By default they are sent on a futures channel whose receiver is
+//! the stream handed out to the user. A `MessageSink` lets an
+//! application take them directly into queues of its own instead,
+//! e.g. to apply its own backpressure.
+
+use futures::channel::mpsc;
+use std::sync::Mutex;
+
+use crate::error::*;
+
+/// What happened to a delivered item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliverResult {
+    /// The item was taken.
+    Accepted,
+    /// The item was dropped because the sink is full. Reported as
+    /// `Error::DeliveryFailed`.
+    DroppedFull,
+    /// The sink does not take any more items. The entity delivering
+    /// to it stops, e.g. a subscription is destroyed.
+    Closed,
+}
+
+/// Takes the items delivered by the spin. Called from the spin
+/// thread, or from the conversion thread of a subscription with
+/// `SubscriptionOptions::offload_conversion`, so it should not block.
+pub trait MessageSink<T>: Send + Sync {
+    fn try_deliver(&self, item: T) -> DeliverResult;
+
+    /// True once the sink does not take any more items, which lets the
+    /// entity stop without waiting for the next delivery.
+    fn is_closed(&self) -> bool {
+        false
+    }
+}
+
+// What a `DroppedFull` is reported as.
+pub(crate) fn sink_full() -> Error {
+    Error::DeliveryFailed {
+        reason: "the receiver is full".into(),
+    }
+}
+
+/// The default sink, a futures channel.
+pub struct ChannelSink<T> {
+    sender: Mutex<mpsc::Sender<T>>,
+}
+
+impl<T> ChannelSink<T> {
+    pub fn new(sender: mpsc::Sender<T>) -> Self {
+        ChannelSink {
+            sender: Mutex::new(sender),
+        }
+    }
+}
+
+impl<T: Send> MessageSink<T> for ChannelSink<T> {
+    fn try_deliver(&self, item: T) -> DeliverResult {
+        match self.sender.lock().unwrap().try_send(item) {
+            Ok(()) => DeliverResult::Accepted,
+            Err(e) if e.is_disconnected() => DeliverResult::Closed,
+            Err(_) => DeliverResult::DroppedFull,
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.sender.lock().unwrap().is_closed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_sink() {
+        let (sender, mut receiver) = mpsc::channel(0);
+        let sink = ChannelSink::new(sender);
+        assert_eq!(sink.try_deliver(1), DeliverResult::Accepted);
+        assert_eq!(sink.try_deliver(2), DeliverResult::DroppedFull);
+        assert_eq!(receiver.try_next().unwrap(), Some(1));
+        assert!(!sink.is_closed());
+        drop(receiver);
+        assert!(sink.is_closed());
+        assert_eq!(sink.try_deliver(3), DeliverResult::Closed);
+    }
+}
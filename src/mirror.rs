@@ -0,0 +1,161 @@
+//! Relaying the messages of one topic to another, see `Node::mirror`.
+//!
+//! The relay runs in the spin of the node: each message is published
+//! right after it is taken, without passing through a stream.
+
+use std::sync::{Arc, Mutex};
+
+use crate::error::*;
+use crate::error_events::*;
+use crate::msg_types::*;
+use crate::publishers::Publisher;
+use crate::stats::*;
+use crate::subscribers::{
+    recreate_subscription_helper, report_take_failure, SequenceTracker, Subscriber_, TypeCheck,
+};
+use r2r_rcl::*;
+
+/// Relays messages from one topic to another until dropped.
+pub struct Mirror {
+    received: Arc<Mutex<StatsTracker>>,
+    sent: Arc<Mutex<StatsTracker>>,
+}
+
+impl Mirror {
+    /// Statistics of the messages taken from the input topic.
+    pub fn received(&self) -> TopicStats {
+        self.received.lock().unwrap().stats()
+    }
+
+    /// Statistics of the messages published on the output topic.
+    /// Messages the transformation returned `None` for are counted as
+    /// dropped.
+    pub fn sent(&self) -> TopicStats {
+        self.sent.lock().unwrap().stats()
+    }
+}
+
+pub(crate) enum Relay<In, Out>
+where
+    In: WrappedTypesupport,
+    Out: WrappedTypesupport,
+{
+    // the taken message is published as is, `In` and `Out` are the
+    // same type.
+    Passthrough(fn(WrappedNativeMsg<In>) -> WrappedNativeMsg<Out>),
+    Transform(Box<dyn FnMut(In) -> Option<Out> + Send>),
+}
+
+pub(crate) struct MirrorSubscriber<In, Out>
+where
+    In: WrappedTypesupport,
+    Out: WrappedTypesupport,
+{
+    pub rcl_handle: rcl_subscription_t,
+    pub relay: Relay<In, Out>,
+    pub publisher: Publisher<Out>,
+    pub received: Arc<Mutex<StatsTracker>>,
+    pub sent: Arc<Mutex<StatsTracker>>,
+    pub errors: EntityErrors,
+    pub sequence: SequenceTracker,
+    pub type_check: TypeCheck,
+}
+
+impl<In, Out> MirrorSubscriber<In, Out>
+where
+    In: WrappedTypesupport + 'static,
+    Out: WrappedTypesupport + 'static,
+{
+    /// The handle for the user, sharing the statistics.
+    pub(crate) fn handle(&self) -> Mirror {
+        Mirror {
+            received: self.received.clone(),
+            sent: self.sent.clone(),
+        }
+    }
+
+    fn relay(&mut self, msg: WrappedNativeMsg<In>) -> Result<()> {
+        let size = self.received.lock().unwrap().record(
+            In::get_ts(),
+            msg.void_ptr(),
+            std::mem::size_of::<In::CStruct>(),
+        );
+        match &mut self.relay {
+            Relay::Passthrough(same) => {
+                self.publisher.publish_native(&same(msg))?;
+                self.sent.lock().unwrap().record_bytes(size);
+            }
+            Relay::Transform(transform) => match transform(In::from_native(&msg)) {
+                Some(out) => {
+                    let out = WrappedNativeMsg::<Out>::from(&out);
+                    self.publisher.publish_native(&out)?;
+                    self.sent.lock().unwrap().record(
+                        Out::get_ts(),
+                        out.void_ptr(),
+                        std::mem::size_of::<Out::CStruct>(),
+                    );
+                }
+                None => self.sent.lock().unwrap().record_dropped(),
+            },
+        }
+        Ok(())
+    }
+}
+
+impl<In, Out> Subscriber_ for MirrorSubscriber<In, Out>
+where
+    In: WrappedTypesupport + 'static,
+    Out: WrappedTypesupport + 'static,
+{
+    fn handle(&self) -> &rcl_subscription_t {
+        &self.rcl_handle
+    }
+
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    fn is_dropped(&self) -> bool {
+        // the `Mirror` holds the only other reference.
+        Arc::strong_count(&self.received) == 1
+    }
+
+    fn handle_incoming(&mut self) -> bool {
+        if self.is_dropped() {
+            return true;
+        }
+        let mut msg_info = rmw_message_info_t::default();
+        let mut msg = WrappedNativeMsg::<In>::new();
+        let ret = unsafe {
+            rcl_take(
+                &self.rcl_handle,
+                msg.void_ptr_mut(),
+                &mut msg_info,
+                std::ptr::null_mut(),
+            )
+        };
+        if ret == RCL_RET_OK as i32 {
+            self.sequence.check(&msg_info, &self.errors);
+            if let Err(e) = self.relay(msg) {
+                self.errors.report(SpinOperation::Send, e);
+            }
+        } else {
+            report_take_failure(&self.errors, ret);
+        }
+        false
+    }
+
+    fn recreate(&mut self, node: &mut rcl_node_t) -> Result<()> {
+        recreate_subscription_helper(&mut self.rcl_handle, node, In::get_ts())
+    }
+
+    fn check_types(&mut self, node: &rcl_node_t) {
+        self.type_check.update(node, &self.rcl_handle, &self.errors);
+    }
+
+    fn destroy(&mut self, node: &mut rcl_node_t) {
+        unsafe {
+            rcl_subscription_fini(&mut self.rcl_handle, node);
+        }
+    }
+}
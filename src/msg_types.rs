@@ -1,12 +1,17 @@
+use crate::dynamic_msgs::DynamicType;
 use crate::error::*;
+use crate::typesupport_loader::InterfaceTypeSupport;
 use r2r_msg_gen::*;
 use r2r_rcl::{
-    rosidl_action_type_support_t, rosidl_message_type_support_t, rosidl_service_type_support_t,
+    rcutils_get_default_allocator, rcutils_get_zero_initialized_uint8_array,
+    rcutils_uint8_array_fini, rcutils_uint8_array_init, rmw_deserialize, rmw_serialize,
+    rmw_serialized_message_t, rosidl_action_type_support_t, rosidl_message_type_support_t,
+    rosidl_service_type_support_t, RCL_RET_OK, RMW_RET_OK,
 };
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 use std::ops::{Deref, DerefMut};
-use std::convert::TryInto;
+use std::sync::Arc;
 
 pub mod generated_msgs {
     use super::*;
@@ -24,30 +29,36 @@ pub mod generated_msgs {
 
 use generated_msgs::{builtin_interfaces, unique_identifier_msgs};
 
-fn vec_to_uuid_bytes<T>(v: Vec<T>) -> [T; 16] {
-    v.try_into().unwrap_or_else(|v: Vec<T>| {
-        panic!("Expected a Vec of length {} but it was {}", 16, v.len())
+/// Fails unless the message contains exactly 16 bytes, which it may
+/// not when it was received from another node.
+pub(crate) fn uuid_msg_to_uuid(msg: &unique_identifier_msgs::msg::UUID) -> Result<uuid::Uuid> {
+    uuid::Uuid::from_slice(&msg.uuid).map_err(|_| Error::InvalidGoalId {
+        reason: format!("expected 16 bytes, got {}", msg.uuid.len()),
     })
 }
 
-/// TODO: maybe expose this somewhere.
-pub(crate) fn uuid_msg_to_uuid(msg: &unique_identifier_msgs::msg::UUID) -> uuid::Uuid {
-    let bytes = vec_to_uuid_bytes(msg.uuid.clone());
-    uuid::Uuid::from_bytes(bytes)
-}
-
 pub trait WrappedTypesupport:
-    Serialize + serde::de::DeserializeOwned + Default + Debug + Clone
+    Serialize + serde::de::DeserializeOwned + Default + Debug + Clone + Send
 {
     type CStruct;
 
     fn get_ts() -> &'static rosidl_message_type_support_t;
+    /// The introspection type support, which describes the layout of
+    /// `CStruct`. Used to read single fields, see `FieldSelection`.
+    fn get_introspection_ts() -> &'static rosidl_message_type_support_t;
     fn create_msg() -> *mut Self::CStruct;
     fn destroy_msg(msg: *mut Self::CStruct);
     fn from_native(msg: &Self::CStruct) -> Self;
     fn copy_to_native(&self, msg: &mut Self::CStruct);
 }
 
+/// Messages with a `std_msgs/Header` named `header`, e.g. for
+/// `Publisher::drop_if_older_than`. Implemented by the generated types.
+pub trait HasHeader {
+    fn stamp(&self) -> &builtin_interfaces::msg::Time;
+    fn frame_id(&self) -> &str;
+}
+
 pub trait WrappedServiceTypeSupport: Debug + Clone {
     type Request: WrappedTypesupport;
     type Response: WrappedTypesupport;
@@ -114,6 +125,11 @@ where
     pub msg: *mut T::CStruct,
 }
 
+// The C struct owns its strings and sequences, which are allocated
+// with the default rcutils allocator and have no ties to the thread
+// that created them.
+unsafe impl<T> Send for WrappedNativeMsg<T> where T: WrappedTypesupport {}
+
 pub trait VoidPtr {
     fn void_ptr(&self) -> *const std::os::raw::c_void;
     fn void_ptr_mut(&mut self) -> *mut std::os::raw::c_void;
@@ -123,21 +139,33 @@ pub trait VoidPtr {
 pub struct WrappedNativeMsgUntyped {
     pub ts: &'static rosidl_message_type_support_t,
     msg: *mut std::os::raw::c_void,
-    destroy: fn(*mut std::os::raw::c_void),
-    msg_to_json: fn(
-        native: *const std::os::raw::c_void,
-    ) -> std::result::Result<serde_json::Value, serde_json::error::Error>,
-    msg_from_json: fn(
-        native: *mut std::os::raw::c_void,
-        json: serde_json::Value,
-    ) -> std::result::Result<(), serde_json::error::Error>,
+    conversion: Conversion,
+}
+
+// Generated code for the types r2r was built with, the introspection
+// type support for type support loaded at runtime.
+#[derive(Debug)]
+enum Conversion {
+    Generated {
+        destroy: fn(*mut std::os::raw::c_void),
+        msg_to_json:
+            fn(native: *const std::os::raw::c_void) -> serde_json::Result<serde_json::Value>,
+        msg_from_json: fn(
+            native: *mut std::os::raw::c_void,
+            json: serde_json::Value,
+        ) -> serde_json::Result<()>,
+    },
+    Dynamic(Arc<DynamicType>),
 }
 
 unsafe impl Send for UntypedServiceSupport {}
+unsafe impl Sync for UntypedServiceSupport {}
 pub struct UntypedServiceSupport {
     pub ts: &'static rosidl_service_type_support_t,
-    pub make_request_msg: fn() -> WrappedNativeMsgUntyped,
-    pub make_response_msg: fn() -> WrappedNativeMsgUntyped,
+    pub make_request_msg: Box<dyn Fn() -> WrappedNativeMsgUntyped + Send + Sync>,
+    pub make_response_msg: Box<dyn Fn() -> WrappedNativeMsgUntyped + Send + Sync>,
+    // keeps type support loaded at runtime loaded.
+    _type_support: Option<InterfaceTypeSupport<rosidl_service_type_support_t>>,
 }
 
 impl UntypedServiceSupport {
@@ -145,15 +173,32 @@ impl UntypedServiceSupport {
     where
         T: WrappedServiceTypeSupport,
     {
-        let make_request_msg = || WrappedNativeMsgUntyped::new::<T::Request>();
-        let make_response_msg = || WrappedNativeMsgUntyped::new::<T::Response>();
+        let make_request_msg = Box::new(|| WrappedNativeMsgUntyped::new::<T::Request>());
+        let make_response_msg = Box::new(|| WrappedNativeMsgUntyped::new::<T::Response>());
 
         UntypedServiceSupport {
             ts: T::get_ts(),
             make_request_msg,
             make_response_msg,
+            _type_support: None,
         }
     }
+
+    /// For type support loaded at runtime, converting the messages
+    /// through the introspection type support of the package.
+    pub(crate) fn from_type_support(
+        type_support: &InterfaceTypeSupport<rosidl_service_type_support_t>,
+    ) -> Result<Self> {
+        let types = type_support.message_types(&["Request", "Response"])?;
+        let (request, response) = (types[0].clone(), types[1].clone());
+        Ok(UntypedServiceSupport {
+            // valid for as long as `type_support` is kept below.
+            ts: unsafe { &*type_support.as_ptr() },
+            make_request_msg: Box::new(move || WrappedNativeMsgUntyped::new_dynamic(&request)),
+            make_response_msg: Box::new(move || WrappedNativeMsgUntyped::new_dynamic(&response)),
+            _type_support: Some(type_support.clone()),
+        })
+    }
 }
 
 // For now only the client side is implemented.
@@ -162,7 +207,10 @@ pub struct UntypedActionSupport {
     pub(crate) ts: &'static rosidl_action_type_support_t,
 
     pub(crate) make_goal_request_msg: Box<
-        dyn Fn(unique_identifier_msgs::msg::UUID, serde_json::Value) -> WrappedNativeMsgUntyped,
+        dyn Fn(
+            unique_identifier_msgs::msg::UUID,
+            serde_json::Value,
+        ) -> Result<WrappedNativeMsgUntyped>,
     >,
     pub(crate) make_goal_response_msg: Box<dyn Fn() -> WrappedNativeMsgUntyped>,
     pub(crate) destructure_goal_response_msg:
@@ -180,6 +228,9 @@ pub struct UntypedActionSupport {
     pub(crate) make_result_response_msg: Box<dyn Fn() -> WrappedNativeMsgUntyped>,
     pub(crate) destructure_result_response_msg:
         Box<dyn Fn(WrappedNativeMsgUntyped) -> (i8, Result<serde_json::Value>)>,
+
+    // keeps type support loaded at runtime loaded.
+    _type_support: Option<InterfaceTypeSupport<rosidl_action_type_support_t>>,
 }
 
 impl UntypedActionSupport {
@@ -190,9 +241,13 @@ impl UntypedActionSupport {
         // TODO: this is terrible. These closures perform json (de)serialization just to move the data.
         // FIX.
 
+        // a goal that does not fit the goal type is an error of the
+        // caller, the rest cannot fail.
         let make_goal_request_msg = Box::new(|goal_id, goal| {
             let goal_msg: T::Goal =
-                serde_json::from_value(goal).expect("TODO: move this error handling");
+                serde_json::from_value(goal).map_err(|e| Error::SerdeError {
+                    err: format!("invalid goal: {}", e),
+                })?;
             let request_msg = T::make_goal_request_msg(goal_id, goal_msg);
             let json =
                 serde_json::to_value(request_msg.clone()).expect("TODO: move this error handling");
@@ -202,7 +257,7 @@ impl UntypedActionSupport {
             native_untyped
                 .from_json(json)
                 .expect("TODO: move this error handling");
-            native_untyped
+            Ok(native_untyped)
         });
 
         let make_goal_response_msg = Box::new(|| {
@@ -279,11 +334,95 @@ impl UntypedActionSupport {
             make_result_request_msg,
             make_result_response_msg,
             destructure_result_response_msg,
-            // destructure_goal_response_msg,
-            // make_request_msg,
-            // make_response_msg,
+            _type_support: None,
         }
     }
+
+    /// For type support loaded at runtime, converting the messages
+    /// through the introspection type support of the package.
+    pub(crate) fn from_type_support(
+        type_support: &InterfaceTypeSupport<rosidl_action_type_support_t>,
+    ) -> Result<Self> {
+        let types = type_support.message_types(&[
+            "SendGoal_Request",
+            "SendGoal_Response",
+            "FeedbackMessage",
+            "GetResult_Request",
+            "GetResult_Response",
+        ])?;
+        let goal_request = types[0].clone();
+        let goal_response = types[1].clone();
+        let feedback = types[2].clone();
+        let result_request = types[3].clone();
+        let result_response = types[4].clone();
+
+        // the goal is the only part that comes from the caller.
+        let make_goal_request_msg = Box::new(
+            move |goal_id: unique_identifier_msgs::msg::UUID, goal: serde_json::Value| {
+                let mut msg = WrappedNativeMsgUntyped::new_dynamic(&goal_request);
+                let goal_id = serde_json::to_value(goal_id).expect("a uuid is valid json");
+                msg.from_json(serde_json::json!({ "goal_id": goal_id, "goal": goal }))
+                    .map_err(|e| Error::SerdeError {
+                        err: format!("invalid goal: {}", e),
+                    })?;
+                Ok(msg)
+            },
+        );
+
+        let make_goal_response_msg =
+            Box::new(move || WrappedNativeMsgUntyped::new_dynamic(&goal_response));
+
+        let destructure_goal_response_msg = Box::new(|msg: WrappedNativeMsgUntyped| {
+            let mut json = msg.to_json().unwrap_or_default();
+            let accepted = json["accepted"].as_bool().unwrap_or(false);
+            let stamp: builtin_interfaces::msg::Time =
+                serde_json::from_value(json["stamp"].take()).unwrap_or_default();
+            (accepted, stamp)
+        });
+
+        let make_feedback_msg = Box::new(move || WrappedNativeMsgUntyped::new_dynamic(&feedback));
+
+        let destructure_feedback_msg = Box::new(|msg: WrappedNativeMsgUntyped| {
+            let mut json = msg.to_json().unwrap_or_default();
+            let uuid: unique_identifier_msgs::msg::UUID =
+                serde_json::from_value(json["goal_id"].take()).unwrap_or_default();
+            let feedback: Result<serde_json::Value> = Ok(json["feedback"].take());
+            (uuid, feedback)
+        });
+
+        let make_result_request_msg =
+            Box::new(move |goal_id: unique_identifier_msgs::msg::UUID| {
+                let mut msg = WrappedNativeMsgUntyped::new_dynamic(&result_request);
+                let goal_id = serde_json::to_value(goal_id).expect("a uuid is valid json");
+                msg.from_json(serde_json::json!({ "goal_id": goal_id }))
+                    .expect("a uuid fits the result request");
+                msg
+            });
+
+        let make_result_response_msg =
+            Box::new(move || WrappedNativeMsgUntyped::new_dynamic(&result_response));
+
+        let destructure_result_response_msg = Box::new(|msg: WrappedNativeMsgUntyped| {
+            let mut json = msg.to_json().unwrap_or_default();
+            let status = json["status"].as_i64().unwrap_or_default() as i8;
+            let result: Result<serde_json::Value> = Ok(json["result"].take());
+            (status, result)
+        });
+
+        Ok(UntypedActionSupport {
+            // valid for as long as `type_support` is kept below.
+            ts: unsafe { &*type_support.as_ptr() },
+            make_goal_request_msg,
+            make_goal_response_msg,
+            destructure_goal_response_msg,
+            make_feedback_msg,
+            destructure_feedback_msg,
+            make_result_request_msg,
+            make_result_response_msg,
+            destructure_result_response_msg,
+            _type_support: Some(type_support.clone()),
+        })
+    }
 }
 
 impl WrappedNativeMsgUntyped {
@@ -310,23 +449,44 @@ impl WrappedNativeMsgUntyped {
         WrappedNativeMsgUntyped {
             ts: T::get_ts(),
             msg: T::create_msg() as *mut std::os::raw::c_void,
-            destroy,
-            msg_to_json,
-            msg_from_json,
+            conversion: Conversion::Generated {
+                destroy,
+                msg_to_json,
+                msg_from_json,
+            },
+        }
+    }
+
+    // The message keeps the introspection library loaded, which `ts`
+    // points into.
+    pub(crate) fn new_dynamic(message_type: &Arc<DynamicType>) -> Self {
+        WrappedNativeMsgUntyped {
+            ts: unsafe { &*message_type.ts },
+            msg: message_type.create_msg(),
+            conversion: Conversion::Dynamic(message_type.clone()),
         }
     }
 
     pub fn to_json(&self) -> Result<serde_json::Value> {
-        let json = (self.msg_to_json)(self.msg);
-        json.map_err(|serde_err| Error::SerdeError {
-            err: serde_err.to_string(),
-        })
+        match &self.conversion {
+            Conversion::Generated { msg_to_json, .. } => {
+                msg_to_json(self.msg).map_err(|serde_err| Error::SerdeError {
+                    err: serde_err.to_string(),
+                })
+            }
+            Conversion::Dynamic(message_type) => Ok(unsafe { message_type.to_json(self.msg) }),
+        }
     }
 
     pub fn from_json(&mut self, json: serde_json::Value) -> Result<()> {
-        (self.msg_from_json)(self.msg, json).map_err(|serde_err| Error::SerdeError {
-            err: serde_err.to_string(),
-        })
+        match &self.conversion {
+            Conversion::Generated { msg_from_json, .. } => {
+                msg_from_json(self.msg, json).map_err(|serde_err| Error::SerdeError {
+                    err: serde_err.to_string(),
+                })
+            }
+            Conversion::Dynamic(message_type) => unsafe { message_type.from_json(self.msg, &json) },
+        }
     }
 }
 
@@ -342,7 +502,10 @@ impl VoidPtr for WrappedNativeMsgUntyped {
 
 impl Drop for WrappedNativeMsgUntyped {
     fn drop(&mut self) {
-        (self.destroy)(self.msg);
+        match &self.conversion {
+            Conversion::Generated { destroy, .. } => destroy(self.msg),
+            Conversion::Dynamic(message_type) => unsafe { message_type.destroy_msg(self.msg) },
+        }
     }
 }
 
@@ -405,12 +568,130 @@ where
     }
 }
 
+/// The ROS name of a message type, e.g. "std_msgs/msg/String", or
+/// "example_interfaces/action/Fibonacci_Goal" for the messages of
+/// services and actions.
+pub(crate) fn ros_type_name<T>() -> String {
+    let path = std::any::type_name::<T>();
+    let path = path.rsplit("generated_msgs::").next().unwrap_or(path);
+    let parts = path.split("::").collect::<Vec<_>>();
+    if parts.len() < 3 {
+        return path.to_owned();
+    }
+    format!("{}/{}/{}", parts[0], parts[1], parts[2..].join("_"))
+}
+
+/// A borrowed element of a sequence of messages in a native message,
+/// e.g. from `WrappedNativeMsg::<MarkerArray>::markers_at`.
+///
+/// The fields of the C struct are read through `Deref`, only
+/// `to_msg` converts the whole element.
+#[derive(Debug)]
+pub struct NativeMsgView<'a, T>
+where
+    T: WrappedTypesupport,
+{
+    msg: &'a T::CStruct,
+}
+
+impl<'a, T> NativeMsgView<'a, T>
+where
+    T: WrappedTypesupport,
+{
+    pub(crate) fn new(msg: &'a T::CStruct) -> Self {
+        NativeMsgView { msg }
+    }
+
+    pub fn to_msg(&self) -> T {
+        T::from_native(self.msg)
+    }
+}
+
+impl<'a, T> Deref for NativeMsgView<'a, T>
+where
+    T: WrappedTypesupport,
+{
+    type Target = T::CStruct;
+
+    fn deref(&self) -> &Self::Target {
+        self.msg
+    }
+}
+
+/// Serializes `msg` like the middleware in use does (e.g. CDR), i.e.
+/// into what `PublisherSerialized::publish` takes and
+/// `Node::subscribe_serialized` returns.
+pub fn serialize_message<T>(msg: &T) -> Result<Vec<u8>>
+where
+    T: WrappedTypesupport,
+{
+    let native = WrappedNativeMsg::<T>::from(msg);
+    let mut serialized = unsafe { rcutils_get_zero_initialized_uint8_array() };
+    // rmw_serialize grows the buffer as needed.
+    let ret =
+        unsafe { rcutils_uint8_array_init(&mut serialized, 0, &rcutils_get_default_allocator()) };
+    if ret != RCL_RET_OK as i32 {
+        return Err(Error::from_rcl_error(ret));
+    }
+    let ret = unsafe { rmw_serialize(native.void_ptr(), T::get_ts(), &mut serialized) };
+    let result = if ret == RMW_RET_OK as i32 {
+        let bytes =
+            unsafe { std::slice::from_raw_parts(serialized.buffer, serialized.buffer_length) };
+        Ok(bytes.to_vec())
+    } else {
+        Err(Error::from_rcl_error(ret))
+    };
+    unsafe {
+        rcutils_uint8_array_fini(&mut serialized);
+    }
+    result
+}
+
+/// The reverse of `serialize_message`.
+pub fn deserialize_message<T>(data: &[u8]) -> Result<T>
+where
+    T: WrappedTypesupport,
+{
+    let mut native = WrappedNativeMsg::<T>::new();
+    // rmw only reads from the buffer.
+    let serialized = rmw_serialized_message_t {
+        buffer: data.as_ptr() as *mut u8,
+        buffer_length: data.len(),
+        buffer_capacity: data.len(),
+        allocator: unsafe { rcutils_get_default_allocator() },
+    };
+    let ret = unsafe { rmw_deserialize(&serialized, T::get_ts(), native.void_ptr_mut()) };
+    if ret == RMW_RET_OK as i32 {
+        Ok(T::from_native(&native))
+    } else {
+        Err(Error::from_rcl_error(ret))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::generated_msgs::*;
     use super::*;
     use r2r_rcl::*;
 
+    #[test]
+    fn test_uuid_msg_lengths() -> () {
+        let uuid = uuid::Uuid::new_v4();
+        let msg = unique_identifier_msgs::msg::UUID {
+            uuid: uuid.as_bytes().to_vec(),
+        };
+        assert_eq!(uuid_msg_to_uuid(&msg).unwrap(), uuid);
+        for len in &[0, 15, 17] {
+            let msg = unique_identifier_msgs::msg::UUID {
+                uuid: vec![1; *len],
+            };
+            assert!(matches!(
+                uuid_msg_to_uuid(&msg),
+                Err(Error::InvalidGoalId { .. })
+            ));
+        }
+    }
+
     #[test]
     fn test_ros_str() -> () {
         let hej = "hej hopp";
@@ -610,6 +891,77 @@ mod tests {
         assert_eq!(msg.wstring_value, msg2.wstring_value);
     }
 
+    #[cfg(r2r__test_msgs__msg__BasicTypes)]
+    #[test]
+    fn test_test_msgs_bool_char_byte() -> () {
+        let mut msg = test_msgs::msg::BasicTypes::default();
+        msg.bool_value = true;
+        msg.byte_value = 0xff;
+        msg.char_value = 0xe5;
+        let mut native = WrappedNativeMsg::<test_msgs::msg::BasicTypes>::from(&msg);
+        assert_eq!(test_msgs::msg::BasicTypes::from_native(&native), msg);
+        let cdr = serialize_message(&msg).unwrap();
+        assert_eq!(
+            deserialize_message::<test_msgs::msg::BasicTypes>(&cdr).unwrap(),
+            msg
+        );
+
+        // e.g. written by C code that treats bools as bytes.
+        unsafe { *(std::ptr::addr_of_mut!((*native).bool_value) as *mut u8) = 2 };
+        assert!(test_msgs::msg::BasicTypes::from_native(&native).bool_value);
+    }
+
+    #[cfg(r2r__test_msgs__msg__Arrays)]
+    #[test]
+    fn test_test_msgs_bool_char_byte_arrays() -> () {
+        let mut msg = test_msgs::msg::Arrays::default();
+        msg.bool_values = vec![true, false, true];
+        msg.byte_values = vec![0, 0x80, 0xff];
+        msg.char_values = vec![b'a', 0x80, 0xff];
+        let mut native = WrappedNativeMsg::<test_msgs::msg::Arrays>::from(&msg);
+        assert_eq!(test_msgs::msg::Arrays::from_native(&native), msg);
+        let cdr = serialize_message(&msg).unwrap();
+        assert_eq!(
+            deserialize_message::<test_msgs::msg::Arrays>(&cdr).unwrap(),
+            msg
+        );
+
+        unsafe { *(std::ptr::addr_of_mut!((*native).bool_values) as *mut u8).add(1) = 0x10 };
+        assert_eq!(
+            test_msgs::msg::Arrays::from_native(&native).bool_values,
+            vec![true, true, true]
+        );
+    }
+
+    #[cfg(r2r__test_msgs__msg__UnboundedSequences)]
+    #[test]
+    fn test_test_msgs_bool_char_byte_sequences() -> () {
+        let mut msg = test_msgs::msg::UnboundedSequences::default();
+        msg.bool_values = vec![true, false, false, true, true];
+        msg.byte_values = vec![0, 0x80, 0xff];
+        msg.char_values = vec![b'a', 0x80, 0xff];
+        let mut native = WrappedNativeMsg::<test_msgs::msg::UnboundedSequences>::from(&msg);
+        assert_eq!(
+            test_msgs::msg::UnboundedSequences::from_native(&native),
+            msg
+        );
+        let cdr = serialize_message(&msg).unwrap();
+        assert_eq!(
+            deserialize_message::<test_msgs::msg::UnboundedSequences>(&cdr).unwrap(),
+            msg
+        );
+
+        // the sequence is a byte per element, not packed bits.
+        assert_eq!(native.bool_values.as_bytes(), &[1, 0, 0, 1, 1]);
+        unsafe { *((*native).bool_values.data as *mut u8).add(1) = 0xff };
+        assert_eq!(
+            test_msgs::msg::UnboundedSequences::from_native(&native).bool_values,
+            vec![true, true, false, true, true]
+        );
+        assert_eq!(native.bool_values_mut(), &[true, true, false, true, true]);
+        assert_eq!(native.bool_values.as_bytes(), &[1, 1, 0, 1, 1]);
+    }
+
     #[cfg(r2r__example_interfaces__srv__AddTwoInts)]
     #[test]
     fn test_service_msgs() {
@@ -670,6 +1022,62 @@ mod tests {
         assert_eq!(fb1, fb2);
     }
 
+    #[cfg(r2r__sensor_msgs__msg__Image)]
+    #[test]
+    fn test_native_sequence_in_place() {
+        let mut native = WrappedNativeMsg::<sensor_msgs::msg::Image>::new();
+        assert!(native.data_mut().is_empty());
+        native.resize_data(6);
+        assert_eq!(native.data.as_slice(), &[0; 6]);
+        // e.g. a camera sdk writing straight into the message
+        native.data_mut().copy_from_slice(&[1, 2, 3, 4, 5, 6]);
+        let msg = sensor_msgs::msg::Image::from_native(&native);
+        assert_eq!(msg.data, vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[cfg(r2r__example_interfaces__action__Fibonacci)]
+    #[test]
+    fn test_ros_type_name() {
+        use example_interfaces::action::Fibonacci;
+        assert_eq!(
+            ros_type_name::<std_msgs::msg::String>(),
+            "std_msgs/msg/String"
+        );
+        assert_eq!(
+            ros_type_name::<Fibonacci::Goal>(),
+            "example_interfaces/action/Fibonacci_Goal"
+        );
+    }
+
+    #[cfg(r2r__visualization_msgs__msg__MarkerArray)]
+    #[test]
+    fn test_native_message_sequence() {
+        use visualization_msgs::msg::{Marker, MarkerArray};
+        let mut native = WrappedNativeMsg::<MarkerArray>::new();
+        assert_eq!(native.markers_len(), 0);
+        for id in 0..10 {
+            let marker = Marker {
+                id,
+                ns: "test".into(),
+                ..Default::default()
+            };
+            native.markers_push(&marker);
+        }
+        assert_eq!(native.markers_len(), 10);
+        let view = native.markers_at(7).expect("element 7");
+        assert_eq!(view.id, 7);
+        assert_eq!(view.ns.to_str(), "test");
+        assert_eq!(view.to_msg().id, 7);
+        assert!(native.markers_at(10).is_none());
+
+        let msg = MarkerArray::from_native(&native);
+        assert_eq!(msg.markers.len(), 10);
+        assert_eq!(msg.markers[9].id, 9);
+        // and back, replacing the grown sequence.
+        let native = WrappedNativeMsg::<MarkerArray>::from(&msg);
+        assert_eq!(native.markers_len(), 10);
+    }
+
     #[cfg(r2r__example_interfaces__srv__AddTwoInts)]
     #[test]
     fn test_untyped_service_support() {
@@ -689,10 +1097,16 @@ mod tests {
         let uuid = unique_identifier_msgs::msg::UUID::default();
         let goal = Fibonacci::Goal { order: 5 };
         let json_goal = serde_json::to_value(&goal).unwrap();
-        let json_request = (ts.make_goal_request_msg)(uuid, json_goal)
+        let json_request = (ts.make_goal_request_msg)(uuid.clone(), json_goal)
+            .unwrap()
             .to_json()
             .unwrap();
         // the message should contain something (default msg)
         assert!(!json_request.to_string().is_empty());
+        let invalid = serde_json::json!({ "order": "five" });
+        assert!(matches!(
+            (ts.make_goal_request_msg)(uuid, invalid),
+            Err(Error::SerdeError { .. })
+        ));
     }
 }
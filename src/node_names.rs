@@ -0,0 +1,258 @@
+use std::ffi::{CStr, CString};
+use std::time::{Duration, Instant};
+
+use crate::context::Context;
+use crate::error::*;
+use crate::utils::LogSeverity;
+use r2r_rcl::*;
+
+/// A node found in the ROS graph.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeName {
+    pub name: String,
+    pub namespace: String,
+    /// The security enclave the node was started in.
+    pub enclave: String,
+}
+
+impl NodeName {
+    pub fn fully_qualified_name(&self) -> String {
+        fully_qualified_name(&self.namespace, &self.name)
+    }
+}
+
+/// What to do when a node with the same fully qualified name is
+/// already in the ROS graph, see `NodeOptions`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateNamePolicy {
+    /// Fail node creation with `Error::DuplicateNodeName`.
+    Fail,
+    /// Print a warning and create the node anyway.
+    Warn,
+    /// Append "_2", "_3", ... to the name until it is unused.
+    Rename,
+}
+
+/// Options for creating nodes.
+///
+/// Besides what is set here, a node has no infrastructure until it is
+/// asked for, e.g. the parameter services come with
+/// `Node::make_parameter_handler`, and nothing subscribes to `/clock`.
+#[derive(Debug, Clone)]
+pub struct NodeOptions {
+    /// Check the graph for a node with the same name before creating
+    /// the node.
+    ///
+    /// Discovery is asynchronous, so the check waits up to
+    /// `duplicate_check_window` for the graph to change, and a node
+    /// that shows up later, or one that is started at the same time,
+    /// is not detected. Name remapping is not taken into account.
+    pub duplicate_name: Option<DuplicateNamePolicy>,
+    pub duplicate_check_window: Duration,
+    /// Publish the log messages of the node on `/rosout`.
+    pub enable_rosout: bool,
+}
+
+impl Default for NodeOptions {
+    fn default() -> Self {
+        NodeOptions {
+            duplicate_name: None,
+            duplicate_check_window: Duration::from_millis(500),
+            enable_rosout: true,
+        }
+    }
+}
+
+impl NodeOptions {
+    /// A node with only the publishers, subscriptions, services etc.
+    /// that are created explicitly, e.g. for short lived tools where
+    /// the rosout publisher only adds discovery traffic.
+    pub fn minimal() -> Self {
+        NodeOptions {
+            enable_rosout: false,
+            ..NodeOptions::default()
+        }
+    }
+}
+
+pub fn fully_qualified_name(namespace: &str, name: &str) -> String {
+    let namespace = namespace.trim_end_matches('/');
+    if namespace.starts_with('/') || namespace.is_empty() {
+        format!("{}/{}", namespace, name)
+    } else {
+        format!("/{}/{}", namespace, name)
+    }
+}
+
+/// Returns the nodes in the graph, sorted and without duplicate
+/// entries.
+pub fn node_names(node: &rcl_node_t) -> Result<Vec<NodeName>> {
+    let mut names = unsafe { rcutils_get_zero_initialized_string_array() };
+    let mut namespaces = unsafe { rcutils_get_zero_initialized_string_array() };
+    let mut enclaves = unsafe { rcutils_get_zero_initialized_string_array() };
+    let ret = unsafe {
+        rcl_get_node_names_with_enclaves(
+            node,
+            rcutils_get_default_allocator(),
+            &mut names,
+            &mut namespaces,
+            &mut enclaves,
+        )
+    };
+    if ret != RCL_RET_OK as i32 {
+        return Err(Error::from_rcl_error(ret));
+    }
+
+    let to_vec = |a: &rcutils_string_array_t| -> Vec<String> {
+        if a.size == 0 {
+            return vec![];
+        }
+        unsafe { std::slice::from_raw_parts(a.data, a.size) }
+            .iter()
+            .map(|s| unsafe { CStr::from_ptr(*s).to_str().unwrap_or("").to_owned() })
+            .collect()
+    };
+    let mut res: Vec<NodeName> = to_vec(&names)
+        .into_iter()
+        .zip(to_vec(&namespaces))
+        .zip(to_vec(&enclaves))
+        .map(|((name, namespace), enclave)| NodeName {
+            name,
+            namespace,
+            enclave,
+        })
+        .collect();
+    unsafe {
+        rcutils_string_array_fini(&mut names);
+        rcutils_string_array_fini(&mut namespaces);
+        rcutils_string_array_fini(&mut enclaves);
+    } // TODO: check return values
+
+    // some rmw implementations list a node once per participant.
+    res.sort();
+    res.dedup();
+    Ok(res)
+}
+
+/// Checks the graph for `name` and returns the name the node should
+/// be created with according to the policy.
+pub fn resolve_node_name(
+    ctx: &Context,
+    name: &str,
+    namespace: &str,
+    policy: DuplicateNamePolicy,
+    window: Duration,
+) -> Result<String> {
+    let taken = |names: &[NodeName], name: &str| {
+        let fqn = fully_qualified_name(namespace, name);
+        names.iter().any(|n| n.fully_qualified_name() == fqn)
+    };
+
+    // a node is needed to see the graph, so use a temporary one with
+    // a name that cannot collide.
+    let probe_name = format!("_r2r_name_check_{}", uuid::Uuid::new_v4().to_simple());
+    let mut probe = create_probe_node(ctx, &probe_name)?;
+
+    // graph events come in as the other participants are discovered.
+    let deadline = Instant::now() + window;
+    let mut names = node_names(&probe);
+    while let Ok(current) = &names {
+        if taken(current, name) {
+            break;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        wait_for_graph_change(ctx, &probe, deadline - now);
+        names = node_names(&probe);
+    }
+    unsafe {
+        rcl_node_fini(&mut probe);
+    }
+    let names = names?;
+
+    if !taken(&names, name) {
+        return Ok(name.to_owned());
+    }
+    let fqn = fully_qualified_name(namespace, name);
+    match policy {
+        DuplicateNamePolicy::Fail => Err(Error::DuplicateNodeName { name: fqn }),
+        DuplicateNamePolicy::Warn => {
+            ctx.log_handler.log(
+                LogSeverity::Warn,
+                &format!("a node named {} already exists", fqn),
+            );
+            Ok(name.to_owned())
+        }
+        DuplicateNamePolicy::Rename => Ok((2..)
+            .map(|i| format!("{}_{}", name, i))
+            .find(|n| !taken(&names, n))
+            .expect("ran out of names")),
+    }
+}
+
+fn create_probe_node(ctx: &Context, name: &str) -> Result<rcl_node_t> {
+    let mut ctx_handle = ctx.context_handle.lock().unwrap();
+    let c_name = CString::new(name).unwrap();
+    let c_ns = CString::new("").unwrap();
+    let mut node = unsafe { rcl_get_zero_initialized_node() };
+    rcl_call(|| unsafe {
+        let node_options = rcl_node_get_default_options();
+        rcl_node_init(
+            &mut node,
+            c_name.as_ptr(),
+            c_ns.as_ptr(),
+            ctx_handle.as_mut(),
+            &node_options as *const _,
+        )
+    })?;
+    Ok(node)
+}
+
+fn wait_for_graph_change(ctx: &Context, node: &rcl_node_t, timeout: Duration) {
+    let graph_guard = unsafe { rcl_node_get_graph_guard_condition(node) };
+    if graph_guard.is_null() {
+        std::thread::sleep(timeout);
+        return;
+    }
+    let mut ws = unsafe { rcl_get_zero_initialized_wait_set() };
+    let ret = {
+        let mut ctx_handle = ctx.context_handle.lock().unwrap();
+        unsafe {
+            rcl_wait_set_init(
+                &mut ws,
+                0,
+                1,
+                0,
+                0,
+                0,
+                0,
+                ctx_handle.as_mut(),
+                rcutils_get_default_allocator(),
+            )
+        }
+    };
+    if ret != RCL_RET_OK as i32 {
+        std::thread::sleep(timeout);
+        return;
+    }
+    unsafe {
+        rcl_wait_set_add_guard_condition(&mut ws, graph_guard, std::ptr::null_mut());
+        rcl_wait(&mut ws, timeout.as_nanos() as i64);
+        rcl_wait_set_fini(&mut ws);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fully_qualified_name() {
+        assert_eq!(fully_qualified_name("", "a"), "/a");
+        assert_eq!(fully_qualified_name("/", "a"), "/a");
+        assert_eq!(fully_qualified_name("/ns", "a"), "/ns/a");
+        assert_eq!(fully_qualified_name("ns/", "a"), "/ns/a");
+    }
+}
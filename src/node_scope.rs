@@ -0,0 +1,169 @@
+//! Entities that live as long as a scope, see `Node::scope`.
+//!
+//! Everything created through a `NodeScope` is destroyed when the
+//! scope is dropped, together with the operations still in flight on
+//! it: goals of its action clients are canceled, and the futures of
+//! pending service calls, goal requests and results fail. This makes
+//! it easy to tear down a part of a node, e.g. when unloading a
+//! plugin or at the end of a test, while the node itself lives on.
+
+use futures::stream::Stream;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use crate::action_clients::ActionClient;
+use crate::action_servers::ActionServerGoalRequest;
+use crate::clients::Client;
+use crate::error::*;
+use crate::msg_types::*;
+use crate::nodes::{Node, Timer};
+use crate::publishers::Publisher;
+use crate::services::ServiceRequest;
+use crate::subscribers::Subscription;
+
+const DEFAULT_DROP_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Creates entities on a node and destroys them when dropped.
+///
+/// ```ignore
+/// {
+///     let mut scope = node.scope();
+///     let client = scope.create_action_client::<Fibonacci::Action>("/fibonacci")?;
+///     let goal = client.send_goal_request(Fibonacci::Goal { order: 5 })?;
+///     scope.spin_once(Duration::from_millis(100));
+/// } // the goal is canceled and the client destroyed.
+/// ```
+///
+/// Entities created with other functions of the node are tracked by
+/// creating them through `track`. While the scope exists, the node is
+/// spun with `spin_once` or reached through `node`.
+///
+/// Cancel requests are sent on a best effort basis: the action client
+/// is destroyed right after sending them, so their answers are never
+/// seen. Entities that another thread still uses when the drop
+/// timeout passes, e.g. a publisher in the middle of publishing, are
+/// left to the node, which destroys them when it is dropped or in
+/// `Node::reap_orphaned_entities`.
+pub struct NodeScope<'a> {
+    node: &'a mut Node,
+    entities: HashSet<usize>,
+    drop_timeout: Duration,
+}
+
+impl<'a> NodeScope<'a> {
+    pub(crate) fn new(node: &'a mut Node) -> Self {
+        NodeScope {
+            node,
+            entities: HashSet::new(),
+            drop_timeout: DEFAULT_DROP_TIMEOUT,
+        }
+    }
+
+    /// How long dropping the scope may wait for entities that are in
+    /// use on other threads, 100 milliseconds by default.
+    pub fn drop_timeout(mut self, timeout: Duration) -> Self {
+        self.drop_timeout = timeout;
+        self
+    }
+
+    /// Call `create` with the node and destroy whatever entities it
+    /// created when the scope is dropped.
+    ///
+    /// ```ignore
+    /// let publisher = scope.track(|node| node.create_publisher_with_options(topic, options))?;
+    /// ```
+    pub fn track<F, R>(&mut self, create: F) -> Result<R>
+    where
+        F: FnOnce(&mut Node) -> Result<R>,
+    {
+        let before = self.node.entity_addresses();
+        let created = create(&mut *self.node);
+        self.entities
+            .extend(self.node.entity_addresses().difference(&before));
+        created
+    }
+
+    /// The node of the scope. Entities created directly on it are not
+    /// destroyed with the scope.
+    pub fn node(&mut self) -> &mut Node {
+        &mut *self.node
+    }
+
+    /// See `Node::spin_once`.
+    pub fn spin_once(&mut self, timeout: Duration) {
+        self.node.spin_once(timeout)
+    }
+
+    /// See `Node::create_publisher`.
+    pub fn create_publisher<T>(&mut self, topic: &str) -> Result<Publisher<T>>
+    where
+        T: WrappedTypesupport,
+    {
+        self.track(|node| node.create_publisher(topic))
+    }
+
+    /// See `Node::subscribe`.
+    pub fn subscribe<T: 'static>(&mut self, topic: &str) -> Result<Subscription<T>>
+    where
+        T: WrappedTypesupport,
+    {
+        self.track(|node| node.subscribe(topic))
+    }
+
+    /// See `Node::create_service`.
+    pub fn create_service<T: 'static>(
+        &mut self,
+        service_name: &str,
+    ) -> Result<impl Stream<Item = ServiceRequest<T>> + Unpin>
+    where
+        T: WrappedServiceTypeSupport,
+    {
+        self.track(|node| node.create_service(service_name))
+    }
+
+    /// See `Node::create_client`.
+    pub fn create_client<T: 'static>(&mut self, service_name: &str) -> Result<Client<T>>
+    where
+        T: WrappedServiceTypeSupport,
+    {
+        self.track(|node| node.create_client(service_name))
+    }
+
+    /// See `Node::create_action_client`.
+    pub fn create_action_client<T: 'static>(&mut self, action_name: &str) -> Result<ActionClient<T>>
+    where
+        T: WrappedActionTypeSupport,
+    {
+        self.track(|node| node.create_action_client(action_name))
+    }
+
+    /// See `Node::create_action_server`.
+    pub fn create_action_server<T: 'static>(
+        &mut self,
+        action_name: &str,
+    ) -> Result<impl Stream<Item = ActionServerGoalRequest<T>> + Unpin>
+    where
+        T: WrappedActionTypeSupport,
+    {
+        self.track(|node| node.create_action_server(action_name))
+    }
+
+    /// See `Node::create_wall_timer`.
+    pub fn create_wall_timer(&mut self, period: Duration) -> Result<Timer> {
+        self.track(|node| node.create_wall_timer(period))
+    }
+}
+
+impl Drop for NodeScope<'_> {
+    fn drop(&mut self) {
+        let deadline = Instant::now() + self.drop_timeout;
+        // some may be gone already, e.g. reaped as orphans.
+        let existing = self.node.entity_addresses();
+        self.entities.retain(|a| existing.contains(a));
+        if self.entities.is_empty() {
+            return;
+        }
+        self.node.cancel_pending_goals(&self.entities);
+        self.node.destroy_entities(&self.entities, Some(deadline));
+    }
+}
@@ -3,17 +3,20 @@ use futures::future::FutureExt;
 use futures::future::TryFutureExt;
 use futures::future::{self, join_all};
 use futures::stream::{Stream, StreamExt};
+use retain_mut::RetainMut;
 use std::future::Future;
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::ffi::{CStr, CString};
 use std::mem::MaybeUninit;
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError, Weak};
+use std::time::{Duration, Instant};
 
 use r2r_rcl::*;
 use r2r_actions::*;
 
 use crate::error::*;
+use crate::error_events::*;
 use crate::msg_types::*;
 use crate::msg_types::generated_msgs::rcl_interfaces;
 use crate::subscribers::*;
@@ -23,9 +26,40 @@ use crate::clients::*;
 use crate::action_clients::*;
 use crate::action_clients_untyped::*;
 use crate::action_servers::*;
+use crate::action_common::*;
 use crate::context::*;
+use crate::executor::*;
+use crate::entities::*;
 use crate::parameters::*;
+use crate::parameter_serde::*;
 use crate::clocks::*;
+use crate::qos::*;
+use crate::qos_overrides::*;
+use crate::readiness::*;
+use crate::node_names::*;
+use crate::node_scope::NodeScope;
+use crate::stats::*;
+use crate::typesupport_loader::*;
+use crate::arguments::*;
+use crate::distro;
+use crate::message_filter::*;
+use crate::field_access::{DynamicValue, FieldSelection};
+use crate::mirror::{Mirror, MirrorSubscriber, Relay};
+use crate::acked::{AckedPublisher_, AckedSubscriber_};
+use crate::action_debug::{publish_client_debug, publish_server_debug};
+use crate::bond::{Bond, BondOptions, Bond_};
+use crate::heartbeat::HeartbeatMonitor_;
+use crate::topic_rpc::TopicRpcClient_;
+use crate::log_sinks::*;
+use crate::message_sinks::{ChannelSink, MessageSink};
+use crate::periodic::PeriodicPublisher_;
+use crate::playback::Playback_;
+use crate::shutdown::{ShutdownHook, ShutdownOptions, ShutdownReport};
+#[cfg(feature = "spin-diagnostics")]
+use crate::spin_state::*;
+use crate::traits::Entity;
+use crate::log_handler::log_internal;
+use crate::utils::{LogSeverity, RosoutEntry};
 
 /// A ROS Node.
 ///
@@ -36,6 +70,8 @@ pub struct Node {
     context: Context,
     /// ROS parameter values.
     pub params: Arc<Mutex<HashMap<String, ParameterValue>>>,
+    // changed parameters, once make_parameter_handler has been called
+    parameter_events: Option<mpsc::Sender<(String, ParameterValue)>>,
     node_handle: Box<rcl_node_t>,
     // the node owns the subscribers
     subscribers: Vec<Box<dyn Subscriber_>>,
@@ -51,6 +87,49 @@ pub struct Node {
     timers: Vec<Timer_>,
     // and the publishers, whom we allow to be shared.. hmm.
     pubs: Vec<Arc<rcl_publisher_t>>,
+    // optional watchdog that recreates subscriptions when publishers come back
+    resubscribe: Option<ResubscribeWatchdog>,
+    // entities found ready but not yet handled
+    pending_ready: VecDeque<ReadyEntity>,
+    // publishers that republish their messages to new subscribers,
+    // with their matched events where the distribution has them
+    retained_publishers: Vec<(Weak<Mutex<dyn Retained_>>, Option<MatchedEvent>)>,
+    // graph entities the node needs before it is ready
+    dependencies: Vec<Dependency>,
+    readiness_waiters: Vec<ReadinessWaiter>,
+    readiness_publisher: Option<(PublisherUntyped, Option<bool>)>,
+    // the dependencies missing when the graph was last queried, None
+    // until it is queried again after a graph change
+    readiness_missing: Option<Vec<Dependency>>,
+    // how long flush_publishers sleeps when acks cannot be waited for
+    flush_grace_period: Duration,
+    // run by shutdown, or when the node is dropped
+    shutdown_hooks: Vec<ShutdownHook>,
+    // statistics of the entities that have them enabled
+    topic_stats: Vec<Weak<Mutex<StatsTracker>>>,
+    // where errors that happen while spinning end up
+    errors: ErrorSink,
+    // when clients last counted the servers of their services
+    last_server_check: Option<Instant>,
+    // runtime type support of publishers, the library must outlive them
+    publisher_type_support: Vec<MessageTypeSupport>,
+    // e.g. heartbeats, published on timers of the node
+    periodic_publishers: Vec<PeriodicPublisher_>,
+    playbacks: Vec<Playback_>,
+    heartbeat_monitors: Vec<HeartbeatMonitor_>,
+    bonds: Vec<Bond_>,
+    // match responses of request-response over topics
+    topic_rpc_clients: Vec<TopicRpcClient_>,
+    // retry and acknowledge commands
+    acked_publishers: Vec<AckedPublisher_>,
+    acked_subscribers: Vec<AckedSubscriber_>,
+    // names and creation times, see list_entities
+    entities: EntityRegistry,
+    // stamps the events of introspected services and clients
+    introspection_clock: Option<Clock>,
+    // what the spinning thread is doing
+    #[cfg(feature = "spin-diagnostics")]
+    spin_tracker: Arc<SpinTracker>,
 }
 
 unsafe impl Send for Node {}
@@ -87,37 +166,65 @@ impl Node {
     }
 
     fn load_params(&mut self) -> Result<()> {
+        let values = self.effective_parameter_overrides()?;
+        self.params.lock().unwrap().extend(values);
+        Ok(())
+    }
+
+    /// The parameter values given on the command line, with `-p` or in
+    /// parameter files, that apply to this node.
+    pub fn effective_parameter_overrides(&self) -> Result<Vec<(String, ParameterValue)>> {
         let ctx = self.context.context_handle.lock().unwrap();
         let mut params: Box<*mut rcl_params_t> = Box::new(std::ptr::null_mut());
 
         let ret =
             unsafe { rcl_arguments_get_param_overrides(&ctx.global_arguments, params.as_mut()) };
         if ret != RCL_RET_OK as i32 {
-            eprintln!("could not read parameters: {}", ret);
             return Err(Error::from_rcl_error(ret));
         }
 
         if *params == std::ptr::null_mut() {
-            return Ok(());
+            return Ok(vec![]);
         }
 
+        let values = self.params_from_rcl(*params);
+        unsafe { rcl_yaml_node_struct_fini(*params) };
+        values
+    }
+
+    /// The remap rules given on the command line that apply to this
+    /// node, in the order they are tried. Rules that match no name are
+    /// included.
+    pub fn effective_remap_rules(&self) -> Result<Vec<RemapRule>> {
+        let name = self.name()?;
+        Ok(remap_rules(&self.context.args)
+            .into_iter()
+            .filter(|r| r.applies_to_node(&name))
+            .collect())
+    }
+
+    /// Explains how `name` is expanded and remapped into the topic or
+    /// service name the node uses in the graph.
+    pub fn describe_resolution(&self, name: &str) -> Result<ResolutionTrace> {
+        let rules = self.effective_remap_rules()?;
+        let ctx = self.context.context_handle.lock().unwrap();
+        describe_resolution(self.node_handle.as_ref(), &ctx.global_arguments, &rules, name)
+    }
+
+    // Picks out the parameters that apply to this node.
+    fn params_from_rcl(&self, params: *const rcl_params_t) -> Result<Vec<(String, ParameterValue)>> {
         let node_names = unsafe {
-            std::slice::from_raw_parts(
-                (*(*params.as_ref())).node_names,
-                (*(*params.as_ref())).num_nodes,
-            )
+            std::slice::from_raw_parts((*params).node_names, (*params).num_nodes)
         };
 
         let node_params = unsafe {
-            std::slice::from_raw_parts(
-                (*(*params.as_ref())).params,
-                (*(*params.as_ref())).num_nodes,
-            )
+            std::slice::from_raw_parts((*params).params, (*params).num_nodes)
         };
 
         let qualified_name = self.fully_qualified_name()?;
         let name = self.name()?;
 
+        let mut values = vec![];
         for (nn, np) in node_names.iter().zip(node_params) {
             let node_name_cstr = unsafe { CStr::from_ptr(*nn) };
             let node_name = node_name_cstr.to_str().unwrap_or("");
@@ -140,21 +247,151 @@ impl Node {
             let param_values =
                 unsafe { std::slice::from_raw_parts(np.parameter_values, np.num_params) };
 
-            let mut params = self.params.lock().unwrap();
             for (s, v) in param_names.iter().zip(param_values) {
                 let s = unsafe { CStr::from_ptr(*s) };
                 let key = s.to_str().unwrap_or("");
                 let val = ParameterValue::from_rcl(&*v);
-                params.insert(key.to_owned(), val);
+                values.push((key.to_owned(), val));
             }
         }
+        Ok(values)
+    }
+
+    /// Dumps the parameters of the node as ROS 2 parameter yaml.
+    ///
+    /// The output can be given to a node with `--params-file` or to
+    /// `restore_parameters`. Parameters that are not set and empty
+    /// arrays are left out since the file format cannot type them.
+    pub fn dump_parameters(&self) -> Result<String> {
+        let name = self.fully_qualified_name()?;
+        let params = self.params.lock().unwrap();
+        Ok(parameters_to_yaml(&name, &params))
+    }
+
+    /// Restores parameters from ROS 2 parameter yaml.
+    ///
+    /// The yaml is parsed by the same parser that handles
+    /// `--params-file`, and only the sections matching this node are
+    /// used. The parameters are set like in a `set_parameters` request,
+    /// so invalid log sink parameters are rejected and changes show up
+    /// on the stream of `make_parameter_handler`. Either all parameters
+    /// are applied or, if parsing fails or one is rejected, none of
+    /// them. Since the file format has no byte arrays, integer arrays
+    /// are stored as byte arrays if the parameter currently holds a
+    /// byte array.
+    pub fn restore_parameters(&mut self, yaml: &str) -> Result<()> {
+        // the rcl parser only reads files, there is no string variant.
+        let path = std::env::temp_dir().join(format!("r2r_params_{}.yaml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, yaml).map_err(|e| Error::InvalidParameterYaml { err: e.to_string() })?;
+        let c_path = CString::new(path.to_string_lossy().as_ref())
+            .map_err(|e| Error::InvalidParameterYaml { err: e.to_string() })?;
+
+        let params = unsafe { rcl_yaml_node_struct_init(rcutils_get_default_allocator()) };
+        if params == std::ptr::null_mut() {
+            let _ = std::fs::remove_file(&path);
+            return Err(Error::RCL_RET_BAD_ALLOC);
+        }
+        let parsed = unsafe { rcl_parse_yaml_file(c_path.as_ptr(), params) };
+        let _ = std::fs::remove_file(&path);
+        if !parsed {
+            unsafe { rcl_yaml_node_struct_fini(params) };
+            return Err(Error::InvalidParameterYaml {
+                err: "could not parse parameters".into(),
+            });
+        }
 
-        unsafe { rcl_yaml_node_struct_fini(*params) };
+        let values = self.params_from_rcl(params);
+        unsafe { rcl_yaml_node_struct_fini(params) };
+
+        let values = values?;
+        let values = {
+            let params = self.params.lock().unwrap();
+            values
+                .into_iter()
+                .map(|(name, value)| {
+                    let value = restore_type(params.get(&name), value);
+                    (name, value)
+                })
+                .collect()
+        };
+        self.set_parameters(values)
+    }
+
+    // Sets parameters like a `set_parameters` request, but all or none
+    // of them, and sends the changes to the stream of
+    // `make_parameter_handler`.
+    fn set_parameters(&mut self, values: Vec<(String, ParameterValue)>) -> Result<()> {
+        let changed = set_parameters(&mut self.params.lock().unwrap(), values)?;
+        if let Some(events) = &mut self.parameter_events {
+            for event in changed {
+                if let Err(e) = events.try_send(event) {
+                    log_internal(
+                        LogSeverity::Warn,
+                        &format!("could not send parameter event ({}).", e),
+                    );
+                }
+            }
+        }
         Ok(())
     }
 
+    /// Assembles a struct from the parameters below `prefix`, e.g. the
+    /// field `p` from the parameter `controller.pid.p` for the prefix
+    /// `controller.pid`, see `from_parameters`.
+    pub fn get_parameter_struct<T>(&self, prefix: &str) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        from_parameters(&self.params.lock().unwrap(), prefix)
+    }
+
+    /// Sets the parameters below `prefix` from the fields of `value`,
+    /// the reverse of `get_parameter_struct`. Other parameters below
+    /// `prefix` are left alone. The fields are set like in
+    /// `restore_parameters`, all or none of them, and changes show up
+    /// on the stream of `make_parameter_handler`.
+    pub fn set_parameter_struct<T>(&mut self, prefix: &str, value: &T) -> Result<()>
+    where
+        T: serde::Serialize,
+    {
+        let values = to_parameters(value, prefix)?;
+        let values = {
+            let params = self.params.lock().unwrap();
+            values
+                .into_iter()
+                .map(|(name, value)| {
+                    let value = keep_type(params.get(&name), value);
+                    (name, value)
+                })
+                .collect()
+        };
+        self.set_parameters(values)
+    }
+
     /// Creates a ROS node.
     pub fn create(ctx: Context, name: &str, namespace: &str) -> Result<Node> {
+        Self::create_with_options(ctx, name, namespace, NodeOptions::default())
+    }
+
+    /// Creates a ROS node, e.g. checking that its name is not taken
+    /// first, see `NodeOptions`.
+    pub fn create_with_options(
+        ctx: Context,
+        name: &str,
+        namespace: &str,
+        options: NodeOptions,
+    ) -> Result<Node> {
+        let name = match options.duplicate_name {
+            Some(policy) => resolve_node_name(
+                &ctx,
+                name,
+                namespace,
+                policy,
+                options.duplicate_check_window,
+            )?,
+            None => name.to_owned(),
+        };
+        let name = name.as_str();
         let (res, node_handle) = {
             let mut ctx_handle = ctx.context_handle.lock().unwrap();
 
@@ -162,8 +399,9 @@ impl Node {
             let c_node_ns = CString::new(namespace).unwrap();
             let mut node_handle: Box<rcl_node_t> =
                 unsafe { Box::new(rcl_get_zero_initialized_node()) };
-            let res = unsafe {
-                let node_options = rcl_node_get_default_options();
+            let res = rcl_call(|| unsafe {
+                let mut node_options = rcl_node_get_default_options();
+                node_options.enable_rosout = options.enable_rosout;
                 rcl_node_init(
                     node_handle.as_mut(),
                     c_node_name.as_ptr(),
@@ -171,29 +409,51 @@ impl Node {
                     ctx_handle.as_mut(),
                     &node_options as *const _,
                 )
-            };
+            });
             (res, node_handle)
         };
 
-        if res == RCL_RET_OK as i32 {
-            let mut node = Node {
-                params: Arc::new(Mutex::new(HashMap::new())),
-                context: ctx,
-                node_handle,
-                subscribers: Vec::new(),
-                services: Vec::new(),
-                clients: Vec::new(),
-                action_clients: Vec::new(),
-                action_servers: Vec::new(),
-                timers: Vec::new(),
-                pubs: Vec::new(),
-            };
-            node.load_params()?;
-            Ok(node)
-        } else {
-            eprintln!("could not create node{}", res);
-            Err(Error::from_rcl_error(res))
-        }
+        res?;
+        let errors = ErrorSink::with_log_handler(ctx.log_handler.clone());
+        let mut node = Node {
+            params: Arc::new(Mutex::new(HashMap::new())),
+            parameter_events: None,
+            context: ctx,
+            node_handle,
+            subscribers: Vec::new(),
+            services: Vec::new(),
+            clients: Vec::new(),
+            action_clients: Vec::new(),
+            action_servers: Vec::new(),
+            timers: Vec::new(),
+            pubs: Vec::new(),
+            resubscribe: None,
+            pending_ready: VecDeque::new(),
+            retained_publishers: Vec::new(),
+            dependencies: Vec::new(),
+            readiness_waiters: Vec::new(),
+            readiness_publisher: None,
+            readiness_missing: None,
+            flush_grace_period: Duration::from_millis(100),
+            shutdown_hooks: Vec::new(),
+            topic_stats: Vec::new(),
+            errors,
+            last_server_check: None,
+            publisher_type_support: Vec::new(),
+            periodic_publishers: Vec::new(),
+            playbacks: Vec::new(),
+            heartbeat_monitors: Vec::new(),
+            bonds: Vec::new(),
+            topic_rpc_clients: Vec::new(),
+            acked_publishers: Vec::new(),
+            acked_subscribers: Vec::new(),
+            entities: EntityRegistry::default(),
+            introspection_clock: None,
+            #[cfg(feature = "spin-diagnostics")]
+            spin_tracker: Arc::new(SpinTracker::new()),
+        };
+        node.load_params()?;
+        Ok(node)
     }
 
     /// Creates parameter service handlers for the Node.
@@ -204,6 +464,11 @@ impl Node {
     /// external sources. The event elements of the event stream
     /// include the name of the parameter which was updated as well as
     /// its new value.
+    ///
+    /// The `log_sinks.*` parameters configure the log sinks of the
+    /// process, see `LogSinkConfig::from_parameters`. If `std_srvs` is
+    /// available, a `~/get_recent_logs` service (`std_srvs/srv/Trigger`)
+    /// returns the lines in the ring buffer sink.
     pub fn make_parameter_handler(
         &mut self,
     ) -> Result<(
@@ -212,6 +477,18 @@ impl Node {
     )> {
         let mut handlers: Vec<std::pin::Pin<Box<dyn Future<Output = ()>>>> = Vec::new();
         let (mut event_tx, event_rx) = mpsc::channel::<(String, ParameterValue)>(10);
+        self.parameter_events = Some(event_tx.clone());
+
+        // log sinks given on the command line.
+        {
+            let params = self.params.lock().unwrap();
+            if params
+                .keys()
+                .any(|k| k.starts_with(LOG_SINK_PARAMETER_PREFIX))
+            {
+                configure_log_sinks(&LogSinkConfig::from_parameters(&params)?)?;
+            }
+        }
 
         let node_name = self.name()?;
         let set_params_request_stream = self
@@ -226,13 +503,19 @@ impl Node {
                 let mut result = rcl_interfaces::srv::SetParameters::Response::default();
                 for p in &req.message.parameters {
                     let val = ParameterValue::from_parameter_value_msg(p.value.clone());
-                    let changed = params
-                        .lock()
-                        .unwrap()
-                        .get(&p.name)
-                        .and_then(|v| Some(v != &val))
-                        .unwrap_or(true); // changed=true if new
-                    params.lock().unwrap().insert(p.name.clone(), val.clone());
+                    let changed =
+                        match set_parameter(&mut params.lock().unwrap(), &p.name, val.clone()) {
+                            Ok(changed) => changed,
+                            Err(e) => {
+                                result
+                                    .results
+                                    .push(rcl_interfaces::msg::SetParametersResult {
+                                        successful: false,
+                                        reason: e.to_string(),
+                                    });
+                                continue;
+                            }
+                        };
                     let r = rcl_interfaces::msg::SetParametersResult {
                         successful: true,
                         reason: "".into(),
@@ -286,6 +569,30 @@ impl Node {
 
         handlers.push(Box::pin(get_params_future));
 
+        #[cfg(r2r__std_srvs__srv__Trigger)]
+        {
+            use crate::msg_types::generated_msgs::std_srvs::srv::Trigger;
+            let recent_logs_request_stream =
+                self.create_service::<Trigger::Service>(&format!("{}/get_recent_logs", node_name))?;
+            let recent_logs_future =
+                recent_logs_request_stream.for_each(|req: ServiceRequest<Trigger::Service>| {
+                    let result = match log_ring_buffer() {
+                        Some(ring) => Trigger::Response {
+                            success: true,
+                            message: ring.dump().join("\n"),
+                        },
+                        None => Trigger::Response {
+                            success: false,
+                            message: "the ring buffer log sink is not enabled".into(),
+                        },
+                    };
+                    req.respond(result)
+                        .expect("could not send reply to get recent logs request");
+                    future::ready(())
+                });
+            handlers.push(Box::pin(recent_logs_future));
+        }
+
         // we don't care about the result, the futures will not complete anyway.
         Ok((join_all(handlers).map(|_| ()), event_rx))
     }
@@ -293,20 +600,226 @@ impl Node {
     /// Subscribe to a ROS topic.
     ///
     /// This function returns a `Stream` of ros messages.
-    pub fn subscribe<T: 'static>(&mut self, topic: &str) -> Result<impl Stream<Item = T> + Unpin>
+    /// Drop the stream to unsubscribe, the subscription is then
+    /// removed on the next call to `spin_once`.
+    pub fn subscribe<T: 'static>(&mut self, topic: &str) -> Result<Subscription<T>>
+    where
+        T: WrappedTypesupport,
+    {
+        self.subscribe_with_options(topic, SubscriptionOptions::default())
+    }
+
+    /// Subscribe to a ROS topic with the given options.
+    ///
+    /// Like `subscribe`, but e.g. lets you set the QoS profile or the
+    /// priority in which the subscription is handled within a spin.
+    /// Any number of subscriptions can be made to the same topic, each
+    /// with its own stream and options.
+    pub fn subscribe_with_options<T: 'static>(
+        &mut self,
+        topic: &str,
+        options: SubscriptionOptions,
+    ) -> Result<Subscription<T>>
+    where
+        T: WrappedTypesupport,
+    {
+        let qos = self.subscription_rmw_qos(topic, &options)?;
+        let subscription_handle =
+            create_subscription_helper(self.node_handle.as_mut(), topic, T::get_ts(), qos)?;
+        self.add_typed_subscriber(subscription_handle, topic, options, None, None)
+    }
+
+    /// Subscribe to a ROS topic and deliver the messages to `sink`
+    /// instead of a stream.
+    ///
+    /// Like `subscribe_with_options`, for applications that want the
+    /// messages in queues of their own. Messages the sink drops as
+    /// full are reported as `Error::DeliveryFailed`. The subscription
+    /// is destroyed once the sink is closed.
+    pub fn subscribe_with_sink<T: 'static>(
+        &mut self,
+        topic: &str,
+        options: SubscriptionOptions,
+        sink: impl MessageSink<(T, MessageInfo)> + 'static,
+    ) -> Result<()>
     where
         T: WrappedTypesupport,
     {
+        let qos = self.subscription_rmw_qos(topic, &options)?;
         let subscription_handle =
-            create_subscription_helper(self.node_handle.as_mut(), topic, T::get_ts())?;
-        let (sender, receiver) = mpsc::channel::<T>(10);
+            create_subscription_helper(self.node_handle.as_mut(), topic, T::get_ts(), qos)?;
+        self.add_typed_subscriber::<T>(
+            subscription_handle,
+            topic,
+            options,
+            None,
+            Some(Arc::new(sink)),
+        )?;
+        Ok(())
+    }
+
+    /// Subscribe to a ROS topic and only receive the messages that pass
+    /// `filter`.
+    ///
+    /// The expression of the filter is handed to the rmw as a content
+    /// filter if the ROS distribution and the rmw support it and the
+    /// rmw accepts the expression. Otherwise the expression is
+    /// evaluated on each received message, which costs a conversion of
+    /// the message to json. `FilteredSubscription::mode` tells which
+    /// one is used.
+    pub fn subscribe_filtered<T: 'static>(
+        &mut self,
+        topic: &str,
+        options: SubscriptionOptions,
+        filter: impl Into<MessageFilter<T>>,
+    ) -> Result<FilteredSubscription<T>>
+    where
+        T: WrappedTypesupport,
+    {
+        let filter = filter.into();
+        let qos = self.subscription_rmw_qos(topic, &options)?;
+        let mut content_filter = None;
+        let mut mode = FilterMode::Local {
+            reason: "the filter is a closure".into(),
+        };
+        if let Some(expr) = filter.expr() {
+            expr.validate()?;
+            match expr.to_content_filter() {
+                Ok(cf) => content_filter = Some(cf),
+                Err(reason) => mode = FilterMode::Local { reason },
+            }
+        }
+
+        let mut subscription_handle = None;
+        if let Some(cf) = &content_filter {
+            match create_filtered_subscription_helper(
+                self.node_handle.as_mut(),
+                topic,
+                T::get_ts(),
+                qos,
+                cf,
+            ) {
+                Ok(handle) => {
+                    mode = if distro::content_filter_enabled(&handle) {
+                        FilterMode::ContentFilter {
+                            expression: cf.expression.clone(),
+                        }
+                    } else {
+                        FilterMode::Local {
+                            reason: "the rmw does not support content filters".into(),
+                        }
+                    };
+                    subscription_handle = Some(handle);
+                }
+                Err(e @ Error::NotSupported { .. }) => {
+                    mode = FilterMode::Local {
+                        reason: e.to_string(),
+                    }
+                }
+                Err(e) => {
+                    // try again without the filter below.
+                    mode = FilterMode::Local {
+                        reason: format!("the rmw rejected the content filter: {}", e),
+                    }
+                }
+            }
+        }
+        if let FilterMode::Local { .. } = mode {
+            content_filter = None;
+        }
+        let subscription_handle = match subscription_handle {
+            Some(handle) => handle,
+            None => create_subscription_helper(self.node_handle.as_mut(), topic, T::get_ts(), qos)?,
+        };
+        let subscription =
+            self.add_typed_subscriber(subscription_handle, topic, options, content_filter, None)?;
+        Ok(FilteredSubscription::new(subscription, filter, mode))
+    }
+
+    fn add_typed_subscriber<T: 'static>(
+        &mut self,
+        subscription_handle: rcl_subscription_t,
+        topic: &str,
+        options: SubscriptionOptions,
+        content_filter: Option<ContentFilter>,
+        sink: Option<Arc<dyn MessageSink<(T, MessageInfo)>>>,
+    ) -> Result<Subscription<T>>
+    where
+        T: WrappedTypesupport,
+    {
+        let stats = self.subscription_stats(&subscription_handle, &options)?;
+        let tracker = stats.clone();
+        let (sender, pending, subscription) = make_subscription::<T>(10, stats.clone());
+        // with a sink of the user, the stream is never used.
+        let sink: Arc<dyn MessageSink<(T, MessageInfo)>> = match sink {
+            Some(sink) => sink,
+            None => Arc::new(ChannelSink::new(sender)),
+        };
+        let errors = self.errors.entity(EntityKind::Subscription, topic);
+        let conversion = if options.offload_conversion {
+            Some(ConversionWorker::new(
+                sink.clone(),
+                pending.clone(),
+                errors.clone(),
+                10,
+                &self.context.thread_hooks(),
+            )?)
+        } else {
+            None
+        };
 
         let ws = TypedSubscriber {
             rcl_handle: subscription_handle,
-            sender,
+            priority: options.priority,
+            sink,
+            pending,
+            stats,
+            errors,
+            sequence: SequenceTracker::default(),
+            content_filter,
+            conversion,
+            type_check: TypeCheck::new::<T>(options.strict_type_check),
         };
         self.subscribers.push(Box::new(ws));
-        Ok(receiver)
+        self.register_last_subscriber(topic, tracker.as_ref());
+        Ok(subscription)
+    }
+
+    /// Subscribe to `/rosout`.
+    ///
+    /// This function returns a `Stream` of the log messages of all
+    /// nodes, e.g. for building an in-process log viewer.
+    pub fn subscribe_rosout(&mut self) -> Result<impl Stream<Item = RosoutEntry> + Unpin> {
+        let stream = self.subscribe::<rcl_interfaces::msg::Log>("/rosout")?;
+        Ok(stream.map(RosoutEntry::from))
+    }
+
+    // The QoS profile of a subscription with `options`, see
+    // `SubscriptionOptions::qos_overrides`.
+    fn subscription_rmw_qos(
+        &self,
+        topic: &str,
+        options: &SubscriptionOptions,
+    ) -> Result<rmw_qos_profile_t> {
+        self.rmw_qos_with_overrides(
+            topic,
+            QosEntityKind::Subscription,
+            options.qos.as_ref(),
+            &options.qos_overrides,
+            options.rmw_qos()?,
+        )
+    }
+
+    fn subscription_stats(
+        &mut self,
+        subscription_handle: &rcl_subscription_t,
+        options: &SubscriptionOptions,
+    ) -> Result<Option<Arc<Mutex<StatsTracker>>>> {
+        if !options.stats {
+            return Ok(None);
+        }
+        let topic = subscription_topic_name(subscription_handle)?;
+        Ok(Some(self.make_stats_tracker(&topic, false)))
     }
 
     /// Subscribe to a ROS topic.
@@ -319,16 +832,194 @@ impl Node {
     where
         T: WrappedTypesupport,
     {
+        self.subscribe_native_with_options(topic, SubscriptionOptions::default())
+    }
+
+    /// Subscribe to a ROS topic with the given options, see
+    /// `subscribe_native`.
+    ///
+    /// The messages are never converted, so `offload_conversion` has
+    /// no effect. Statistics show up in `metrics`.
+    pub fn subscribe_native_with_options<T: 'static>(
+        &mut self,
+        topic: &str,
+        options: SubscriptionOptions,
+    ) -> Result<impl Stream<Item = WrappedNativeMsg<T>> + Unpin>
+    where
+        T: WrappedTypesupport,
+    {
+        let (sender, receiver) = mpsc::channel::<WrappedNativeMsg<T>>(10);
+        self.subscribe_native_with_sink(topic, options, ChannelSink::new(sender))?;
+        Ok(receiver)
+    }
+
+    /// Subscribe to a ROS topic and deliver the messages to `sink`
+    /// instead of a stream, see `subscribe_with_sink`.
+    pub fn subscribe_native_with_sink<T: 'static>(
+        &mut self,
+        topic: &str,
+        options: SubscriptionOptions,
+        sink: impl MessageSink<WrappedNativeMsg<T>> + 'static,
+    ) -> Result<()>
+    where
+        T: WrappedTypesupport,
+    {
+        let qos = self.subscription_rmw_qos(topic, &options)?;
         let subscription_handle =
-            create_subscription_helper(self.node_handle.as_mut(), topic, T::get_ts())?;
+            create_subscription_helper(self.node_handle.as_mut(), topic, T::get_ts(), qos)?;
+        let stats = self.subscription_stats(&subscription_handle, &options)?;
+
+        let ws = NativeSubscriber {
+            rcl_handle: subscription_handle,
+            priority: options.priority,
+            sink: Box::new(sink),
+            stats: stats.clone(),
+            errors: self.errors.entity(EntityKind::Subscription, topic),
+            sequence: SequenceTracker::default(),
+            type_check: TypeCheck::new::<T>(options.strict_type_check),
+        };
+        self.subscribers.push(Box::new(ws));
+        self.register_last_subscriber(topic, stats.as_ref());
+        Ok(())
+    }
+
+    /// Subscribe to a ROS topic and only receive the fields at `paths`,
+    /// e.g. `&["header.stamp", "pose.pose.position"]`.
+    ///
+    /// This function returns a `Stream` with the values of the fields
+    /// of each message, in the order of the paths. The fields are read
+    /// from the native message, the rest of the message is never
+    /// converted. Fails with `Error::InvalidFieldPath` when a path does
+    /// not name a field of `T`, see `FieldSelection` for the syntax.
+    pub fn subscribe_fields<T: 'static>(
+        &mut self,
+        topic: &str,
+        paths: &[&str],
+        qos: QosProfile,
+    ) -> Result<impl Stream<Item = Vec<DynamicValue>> + Unpin>
+    where
+        T: WrappedTypesupport,
+    {
+        let selection = FieldSelection::<T>::new(paths)?;
+        let subscription_handle = create_subscription_helper(
+            self.node_handle.as_mut(),
+            topic,
+            T::get_ts(),
+            rmw_qos_profile_t::from(&qos),
+        )?;
         let (sender, receiver) = mpsc::channel::<WrappedNativeMsg<T>>(10);
 
         let ws = NativeSubscriber {
             rcl_handle: subscription_handle,
-            sender,
+            priority: 0,
+            sink: Box::new(ChannelSink::new(sender)),
+            stats: None,
+            errors: self.errors.entity(EntityKind::Subscription, topic),
+            sequence: SequenceTracker::default(),
+            type_check: TypeCheck::new::<T>(false),
         };
         self.subscribers.push(Box::new(ws));
-        Ok(receiver)
+        self.register_last_subscriber(topic, None);
+        Ok(receiver.map(move |msg| selection.extract(&msg)))
+    }
+
+    /// Relay the messages of topic `from` to topic `to`, as transformed
+    /// by `transform`. Messages it returns `None` for are dropped.
+    ///
+    /// The messages are relayed while the node spins, until the
+    /// returned `Mirror` is dropped. Its statistics count the messages
+    /// on both topics and appear in `metrics`.
+    pub fn mirror<In: 'static, Out: 'static>(
+        &mut self,
+        from: &str,
+        to: &str,
+        qos_in: QosProfile,
+        qos_out: QosProfile,
+        transform: impl FnMut(In) -> Option<Out> + Send + 'static,
+    ) -> Result<Mirror>
+    where
+        In: WrappedTypesupport,
+        Out: WrappedTypesupport,
+    {
+        self.add_mirror(
+            from,
+            to,
+            qos_in,
+            qos_out,
+            Relay::Transform(Box::new(transform)),
+        )
+    }
+
+    /// Relay the messages of topic `from` to topic `to` unchanged.
+    ///
+    /// Like `mirror`, but the messages are published as they were
+    /// taken, without converting them.
+    pub fn mirror_passthrough<T: 'static>(
+        &mut self,
+        from: &str,
+        to: &str,
+        qos_in: QosProfile,
+        qos_out: QosProfile,
+    ) -> Result<Mirror>
+    where
+        T: WrappedTypesupport,
+    {
+        self.add_mirror::<T, T>(from, to, qos_in, qos_out, Relay::Passthrough(|msg| msg))
+    }
+
+    fn add_mirror<In: 'static, Out: 'static>(
+        &mut self,
+        from: &str,
+        to: &str,
+        qos_in: QosProfile,
+        qos_out: QosProfile,
+        relay: Relay<In, Out>,
+    ) -> Result<Mirror>
+    where
+        In: WrappedTypesupport,
+        Out: WrappedTypesupport,
+    {
+        qos_in.validate()?;
+        let publisher = self.create_publisher_with_options::<Out>(
+            to,
+            PublisherOptions {
+                qos: Some(qos_out),
+                ..Default::default()
+            },
+        )?;
+        let subscription_handle = create_subscription_helper(
+            self.node_handle.as_mut(),
+            from,
+            In::get_ts(),
+            rmw_qos_profile_t::from(&qos_in),
+        )?;
+        let received =
+            self.make_stats_tracker(&subscription_topic_name(&subscription_handle)?, false);
+        let sent = self.make_stats_tracker(&publisher.name()?, true);
+        let ws = MirrorSubscriber {
+            rcl_handle: subscription_handle,
+            relay,
+            publisher,
+            received: received.clone(),
+            sent,
+            errors: self.errors.entity(EntityKind::Subscription, from),
+            sequence: SequenceTracker::default(),
+            type_check: TypeCheck::new::<In>(false),
+        };
+        let mirror = ws.handle();
+        self.subscribers.push(Box::new(ws));
+        self.register_last_subscriber(from, Some(&received));
+        Ok(mirror)
+    }
+
+    /// Form the bond `id` with another node on `topic`, e.g. between a
+    /// supervisor and one of its children, see `Bond`.
+    ///
+    /// The bond is kept while the node spins and breaks when the
+    /// returned `Bond` is dropped. Interoperates with the `bond`
+    /// package and requires its message type, `bond/msg/Status`.
+    pub fn create_bond(&mut self, topic: &str, id: &str, options: BondOptions) -> Result<Bond> {
+        Bond::create(self, topic, id, options)
     }
 
     /// Subscribe to a ROS topic.
@@ -340,20 +1031,139 @@ impl Node {
         topic: &str,
         topic_type: &str,
     ) -> Result<impl Stream<Item = Result<serde_json::Value>> + Unpin> {
+        self.subscribe_untyped_with_options(topic, topic_type, SubscriptionOptions::default())
+    }
+
+    /// Subscribe to a ROS topic with the given options, see
+    /// `subscribe_untyped`.
+    ///
+    /// `offload_conversion` and `strict_type_check` only apply to
+    /// typed subscriptions. Statistics show up in `metrics`.
+    pub fn subscribe_untyped_with_options(
+        &mut self,
+        topic: &str,
+        topic_type: &str,
+        options: SubscriptionOptions,
+    ) -> Result<impl Stream<Item = Result<serde_json::Value>> + Unpin> {
+        let (sender, receiver) = mpsc::channel::<Result<serde_json::Value>>(10);
+        self.subscribe_untyped_with_sink(topic, topic_type, options, ChannelSink::new(sender))?;
+        Ok(receiver)
+    }
+
+    /// Subscribe to a ROS topic and deliver the messages to `sink`
+    /// instead of a stream, see `subscribe_with_sink`.
+    pub fn subscribe_untyped_with_sink(
+        &mut self,
+        topic: &str,
+        topic_type: &str,
+        options: SubscriptionOptions,
+        sink: impl MessageSink<Result<serde_json::Value>> + 'static,
+    ) -> Result<()> {
         let msg = WrappedNativeMsgUntyped::new_from(topic_type)?;
+        let qos = self.subscription_rmw_qos(topic, &options)?;
         let subscription_handle =
-            create_subscription_helper(self.node_handle.as_mut(), topic, msg.ts)?;
-        let (sender, receiver) = mpsc::channel::<Result<serde_json::Value>>(10);
+            create_subscription_helper(self.node_handle.as_mut(), topic, msg.ts, qos)?;
+        let stats = self.subscription_stats(&subscription_handle, &options)?;
 
         let ws = UntypedSubscriber {
             rcl_handle: subscription_handle,
             topic_type: topic_type.to_string(),
-            sender,
+            priority: options.priority,
+            sink: Box::new(sink),
+            stats: stats.clone(),
+            errors: self.errors.entity(EntityKind::Subscription, topic),
+            sequence: SequenceTracker::default(),
         };
         self.subscribers.push(Box::new(ws));
+        self.register_last_subscriber(topic, stats.as_ref());
+        Ok(())
+    }
+
+    /// Subscribe to a ROS topic with type support given at runtime,
+    /// see `TypeSupportLibrary`.
+    ///
+    /// This function returns a `Stream` of messages serialized by the
+    /// middleware in use (e.g. CDR). The subscription keeps the type
+    /// support library loaded.
+    pub fn subscribe_serialized(
+        &mut self,
+        topic: &str,
+        type_support: &MessageTypeSupport,
+    ) -> Result<impl Stream<Item = Vec<u8>> + Unpin> {
+        self.subscribe_serialized_with_options(topic, type_support, SubscriptionOptions::default())
+    }
+
+    /// Subscribe to a ROS topic with the given options, see
+    /// `subscribe_serialized`.
+    ///
+    /// `offload_conversion` and `strict_type_check` only apply to
+    /// typed subscriptions. Statistics show up in `metrics`, with the
+    /// serialized size of the messages as taken.
+    pub fn subscribe_serialized_with_options(
+        &mut self,
+        topic: &str,
+        type_support: &MessageTypeSupport,
+        options: SubscriptionOptions,
+    ) -> Result<impl Stream<Item = Vec<u8>> + Unpin> {
+        let (sender, receiver) = mpsc::channel::<Vec<u8>>(10);
+        self.subscribe_serialized_with_sink(
+            topic,
+            type_support,
+            options,
+            ChannelSink::new(sender),
+        )?;
         Ok(receiver)
     }
 
+    /// Subscribe to a ROS topic and deliver the serialized messages to
+    /// `sink` instead of a stream, see `subscribe_with_sink`.
+    pub fn subscribe_serialized_with_sink(
+        &mut self,
+        topic: &str,
+        type_support: &MessageTypeSupport,
+        options: SubscriptionOptions,
+        sink: impl MessageSink<Vec<u8>> + 'static,
+    ) -> Result<()> {
+        let qos = self.subscription_rmw_qos(topic, &options)?;
+        let subscription_handle = create_subscription_helper(
+            self.node_handle.as_mut(),
+            topic,
+            type_support.as_ptr(),
+            qos,
+        )?;
+        let stats = self.subscription_stats(&subscription_handle, &options)?;
+
+        let mut ws = SerializedSubscriber::new(
+            subscription_handle,
+            type_support.clone(),
+            Box::new(sink),
+            self.errors.entity(EntityKind::Subscription, topic),
+        );
+        ws.priority = options.priority;
+        ws.stats = stats.clone();
+        self.subscribers.push(Box::new(ws));
+        self.register_last_subscriber(topic, stats.as_ref());
+        Ok(())
+    }
+
+    /// Enable the resubscribe watchdog for all subscriptions of this node.
+    ///
+    /// When all publishers of a subscribed topic disappear (e.g. after
+    /// the middleware was restarted) and later come back, the
+    /// subscriptions on that topic are recreated in place, which makes
+    /// transient local publishers deliver their retained samples
+    /// again. Existing subscription streams keep working. The returned
+    /// stream reports when publishers are lost and when a topic was
+    /// resubscribed, so that the application knows that a gap occurred.
+    pub fn enable_resubscribe(
+        &mut self,
+        options: ResubscribeOptions,
+    ) -> impl Stream<Item = ResubscribeEvent> + Unpin {
+        let (sender, receiver) = mpsc::channel::<ResubscribeEvent>(10);
+        self.resubscribe = Some(ResubscribeWatchdog::new(options, sender, self.errors.clone()));
+        receiver
+    }
+
     /// Create a ROS service.
     ///
     /// This function returns a `Stream` of `ServiceRequest`:s. Call
@@ -365,37 +1175,142 @@ impl Node {
     where
         T: WrappedServiceTypeSupport,
     {
-        let service_handle =
+        self.create_service_with_options(service_name, ServiceOptions::default())
+    }
+
+    /// Create a ROS service with the given options.
+    ///
+    /// Like `create_service`, but lets you refuse invalid requests
+    /// before they reach the service stream, or deliver the requests to
+    /// a sink of your own, see `ServiceOptions`.
+    pub fn create_service_with_options<T: 'static>(
+        &mut self,
+        service_name: &str,
+        mut options: ServiceOptions<T>,
+    ) -> Result<impl Stream<Item = ServiceRequest<T>> + Unpin>
+    where
+        T: WrappedServiceTypeSupport,
+    {
+        let mut service_handle =
             create_service_helper(self.node_handle.as_mut(), service_name, T::get_ts())?;
+        if options.introspection != ServiceIntrospection::Off {
+            let introspection = options.introspection;
+            let configured = self.configure_introspection(|node, clock| {
+                distro::configure_service_introspection(
+                    &mut service_handle,
+                    node,
+                    clock,
+                    T::get_ts(),
+                    introspection,
+                )
+            });
+            if let Err(e) = configured {
+                unsafe {
+                    rcl_service_fini(&mut service_handle, self.node_handle.as_mut());
+                }
+                return Err(e);
+            }
+        }
         let (sender, receiver) = mpsc::channel::<ServiceRequest<T>>(10);
+        let sink = match options.sink.take() {
+            Some(sink) => sink,
+            None => Box::new(ChannelSink::new(sender)),
+        };
 
         let ws = TypedService::<T> {
             rcl_handle: service_handle,
             outstanding_requests: vec![],
-            sender,
+            sink,
+            options,
+            refused_requests: 0,
+            errors: self.errors.entity(EntityKind::Service, service_name),
         };
 
-        self.services.push(Arc::new(Mutex::new(ws)));
+        let service_arc = Arc::new(Mutex::new(ws));
+        self.entities.register(
+            arc_address(&service_arc),
+            EntityKind::Service,
+            service_name,
+            None,
+        );
+        self.services.push(service_arc);
         Ok(receiver)
     }
 
-    /// Create a ROS service client.
+    /// Create a ROS service with type support loaded at runtime, see
+    /// `TypeSupportLibrary`.
     ///
-    /// A service client is used to make requests to a ROS service server.
-    pub fn create_client<T: 'static>(&mut self, service_name: &str) -> Result<Client<T>>
-    where
-        T: WrappedServiceTypeSupport,
-    {
-        let client_handle =
-            create_client_helper(self.node_handle.as_mut(), service_name, T::get_ts())?;
-        let ws = TypedClient::<T> {
+    /// Like `create_service`, but the requests and responses are
+    /// `serde_json::Value`s. The service keeps the type support
+    /// library loaded.
+    pub fn create_service_with_type_support(
+        &mut self,
+        service_name: &str,
+        type_support: &InterfaceTypeSupport<rosidl_service_type_support_t>,
+    ) -> Result<impl Stream<Item = ServiceRequestUntyped> + Unpin> {
+        let service_type = UntypedServiceSupport::from_type_support(type_support)?;
+        let service_handle =
+            create_service_helper(self.node_handle.as_mut(), service_name, service_type.ts)?;
+        let (sender, receiver) = mpsc::channel::<ServiceRequestUntyped>(10);
+
+        let ws = UntypedService {
+            rcl_handle: service_handle,
+            service_type: Arc::new(service_type),
+            sink: Box::new(ChannelSink::new(sender)),
+            errors: self.errors.entity(EntityKind::Service, service_name),
+        };
+
+        let service_arc = Arc::new(Mutex::new(ws));
+        self.entities.register(
+            arc_address(&service_arc),
+            EntityKind::Service,
+            service_name,
+            None,
+        );
+        self.services.push(service_arc);
+        Ok(receiver)
+    }
+
+    /// Create a ROS service client.
+    ///
+    /// A service client is used to make requests to a ROS service server.
+    pub fn create_client<T: 'static>(&mut self, service_name: &str) -> Result<Client<T>>
+    where
+        T: WrappedServiceTypeSupport,
+    {
+        self.create_client_with_options(service_name, ClientOptions::default())
+    }
+
+    /// Create a ROS service client with the given options.
+    pub fn create_client_with_options<T: 'static>(
+        &mut self,
+        service_name: &str,
+        options: ClientOptions,
+    ) -> Result<Client<T>>
+    where
+        T: WrappedServiceTypeSupport,
+    {
+        let mut client_handle =
+            create_client_helper(self.node_handle.as_mut(), service_name, T::get_ts())?;
+        self.configure_client_introspection(&mut client_handle, T::get_ts(), &options)?;
+        let response_guard = ResponseGuard::new(&client_handle);
+        let ws = TypedClient::<T> {
             rcl_handle: client_handle,
             response_channels: Vec::new(),
+            response_guard,
             poll_available_channels: Vec::new(),
+            server_check: ServerCheck::new(options.expect_single_server),
+            errors: self.errors.entity(EntityKind::Client, service_name),
         };
 
         let client_arc = Arc::new(Mutex::new(ws));
         let c = make_client(Arc::downgrade(&client_arc));
+        self.entities.register(
+            arc_address(&client_arc),
+            EntityKind::Client,
+            service_name,
+            None,
+        );
         self.clients.push(client_arc);
         Ok(c)
     }
@@ -410,26 +1325,110 @@ impl Node {
         &mut self,
         service_name: &str,
         service_type: &str,
+    ) -> Result<ClientUntyped> {
+        self.create_client_untyped_with_options(service_name, service_type, ClientOptions::default())
+    }
+
+    /// Create a ROS service client with the given options, see
+    /// `create_client_untyped`.
+    pub fn create_client_untyped_with_options(
+        &mut self,
+        service_name: &str,
+        service_type: &str,
+        options: ClientOptions,
     ) -> Result<ClientUntyped> {
         let service_type = UntypedServiceSupport::new_from(service_type)?;
-        let client_handle =
+        self.create_client_untyped_helper(service_name, service_type, options)
+    }
+
+    /// Create a ROS service client with type support loaded at
+    /// runtime, see `TypeSupportLibrary`.
+    ///
+    /// Like `create_client_untyped`, the requests and responses are
+    /// `serde_json::Value`s. The client keeps the type support library
+    /// loaded.
+    pub fn create_client_with_type_support(
+        &mut self,
+        service_name: &str,
+        type_support: &InterfaceTypeSupport<rosidl_service_type_support_t>,
+        options: ClientOptions,
+    ) -> Result<ClientUntyped> {
+        let service_type = UntypedServiceSupport::from_type_support(type_support)?;
+        self.create_client_untyped_helper(service_name, service_type, options)
+    }
+
+    fn create_client_untyped_helper(
+        &mut self,
+        service_name: &str,
+        service_type: UntypedServiceSupport,
+        options: ClientOptions,
+    ) -> Result<ClientUntyped> {
+        let mut client_handle =
             create_client_helper(self.node_handle.as_mut(), service_name, service_type.ts)?;
+        self.configure_client_introspection(&mut client_handle, service_type.ts, &options)?;
+        let response_guard = ResponseGuard::new(&client_handle);
         let client = UntypedClient_ {
             service_type,
             rcl_handle: client_handle,
             response_channels: Vec::new(),
+            response_guard,
             poll_available_channels: Vec::new(),
+            server_check: ServerCheck::new(options.expect_single_server),
+            errors: self.errors.entity(EntityKind::Client, service_name),
         };
 
         let client_arc = Arc::new(Mutex::new(client));
         let c = make_untyped_client(Arc::downgrade(&client_arc));
+        self.entities.register(
+            arc_address(&client_arc),
+            EntityKind::Client,
+            service_name,
+            None,
+        );
         self.clients.push(client_arc);
         Ok(c)
     }
 
+    // Runs `configure` with the clock that stamps the service events,
+    // created by the first service or client that needs it.
+    fn configure_introspection(
+        &mut self,
+        configure: impl FnOnce(&mut rcl_node_t, &mut rcl_clock_t) -> Result<()>,
+    ) -> Result<()> {
+        if self.introspection_clock.is_none() {
+            self.introspection_clock = Some(Clock::create(ClockType::RosTime)?);
+        }
+        let clock = self.introspection_clock.as_mut().expect("created above");
+        configure(self.node_handle.as_mut(), clock.clock_handle.as_mut())
+    }
+
+    // Destroys the client if its introspection cannot be configured.
+    fn configure_client_introspection(
+        &mut self,
+        client_handle: &mut rcl_client_t,
+        ts: &rosidl_service_type_support_t,
+        options: &ClientOptions,
+    ) -> Result<()> {
+        if options.introspection == ServiceIntrospection::Off {
+            return Ok(());
+        }
+        let introspection = options.introspection;
+        let configured = self.configure_introspection(|node, clock| {
+            distro::configure_client_introspection(client_handle, node, clock, ts, introspection)
+        });
+        if configured.is_err() {
+            unsafe {
+                rcl_client_fini(client_handle, self.node_handle.as_mut());
+            }
+        }
+        configured
+    }
+
     /// Register a client for wakeup when the service or action server is available to the node.
     ///
     /// Returns a `Future` that completes when the service/action server is available.
+    /// For clients created with `expect_single_server`, it completes
+    /// with `Error::MultipleServiceServers` if several servers are found.
     ///
     /// This function will register the client to be polled in
     /// `spin_once` until available, so spin_once must be called
@@ -440,7 +1439,196 @@ impl Node {
     ) -> Result<impl Future<Output = Result<()>>> {
         let (sender, receiver) = oneshot::channel();
         client.register_poll_available(sender)?;
-        Ok(receiver.map_err(|_| Error::RCL_RET_CLIENT_INVALID))
+        Ok(receiver
+            .map_err(|_| Error::RCL_RET_CLIENT_INVALID)
+            .map(|r| r.and_then(|r| r)))
+    }
+
+    pub(crate) fn action_server_available(&self, client: &rcl_action_client_t) -> Result<bool> {
+        action_server_available_helper(&self.node_handle, client)
+    }
+
+    /// Declare something this node needs to find in the ROS graph
+    /// before it is ready, see `wait_until_ready`.
+    pub fn declare_dependency(&mut self, dependency: Dependency) {
+        if !self.dependencies.contains(&dependency) {
+            self.dependencies.push(dependency);
+            self.readiness_missing = None;
+        }
+    }
+
+    /// Returns the declared dependencies that are not yet found in the
+    /// ROS graph.
+    pub fn missing_dependencies(&self) -> Result<Vec<Dependency>> {
+        missing_dependencies(self.node_handle.as_ref(), &self.dependencies)
+    }
+
+    /// Returns a `Future` that completes when all declared dependencies
+    /// have been found, or with `Error::DependenciesNotReady` after
+    /// `timeout`.
+    ///
+    /// The graph is checked from `spin_once`, so spin_once must be
+    /// called repeatedly in order to get the wakeup.
+    pub fn wait_until_ready(
+        &mut self,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<()>> + Unpin {
+        self.wait_until_ready_(timeout, None)
+    }
+
+    /// Like `wait_until_ready`, but calls `progress` with the
+    /// outstanding dependencies whenever they change.
+    pub fn wait_until_ready_with_progress<F>(
+        &mut self,
+        timeout: Duration,
+        progress: F,
+    ) -> impl Future<Output = Result<()>> + Unpin
+    where
+        F: FnMut(&[Dependency]) + Send + 'static,
+    {
+        self.wait_until_ready_(timeout, Some(Box::new(progress)))
+    }
+
+    fn wait_until_ready_(
+        &mut self,
+        timeout: Duration,
+        progress: Option<Box<dyn FnMut(&[Dependency]) + Send>>,
+    ) -> impl Future<Output = Result<()>> + Unpin {
+        let (sender, receiver) = oneshot::channel();
+        self.readiness_waiters.push(ReadinessWaiter {
+            deadline: Instant::now() + timeout,
+            sender,
+            progress,
+            last_missing: None,
+        });
+        // check on the next spin.
+        self.readiness_missing = None;
+        receiver.map_err(|_| Error::RCL_RET_NODE_INVALID).map(|r| r.and_then(|r| r))
+    }
+
+    /// Publish the readiness of the node (see `declare_dependency`) as
+    /// a transient local `std_msgs/msg/Bool` on `topic`, for
+    /// orchestration tools. The value is updated from `spin_once`.
+    pub fn publish_readiness(&mut self, topic: &str) -> Result<()> {
+        let dummy = WrappedNativeMsgUntyped::new_from("std_msgs/msg/Bool")?;
+        let qos = QosProfile::default().keep_last(1).transient_local();
+        let publisher_handle = create_publisher_helper(
+            self.node_handle.as_mut(),
+            topic,
+            dummy.ts,
+            rmw_qos_profile_t::from(&qos),
+        )?;
+        let arc = Arc::new(publisher_handle);
+        let p = make_publisher_untyped(Arc::downgrade(&arc), "std_msgs/msg/Bool".to_owned());
+        self.entities
+            .register(publisher_address(&arc), EntityKind::Publisher, topic, None);
+        self.pubs.push(arc);
+        self.readiness_publisher = Some((p, None));
+        self.readiness_missing = None;
+        Ok(())
+    }
+
+    pub(crate) fn entity_errors(&self, kind: EntityKind, name: &str) -> EntityErrors {
+        self.errors.entity(kind, name)
+    }
+
+    pub(crate) fn add_periodic_publisher(&mut self, publisher: PeriodicPublisher_) {
+        self.periodic_publishers.push(publisher);
+    }
+
+    pub(crate) fn add_playback(&mut self, playback: Playback_) {
+        self.playbacks.push(playback);
+    }
+
+    pub(crate) fn add_heartbeat_monitor(&mut self, monitor: HeartbeatMonitor_) {
+        self.heartbeat_monitors.push(monitor);
+    }
+
+    pub(crate) fn add_bond(&mut self, bond: Bond_) {
+        self.bonds.push(bond);
+    }
+
+    pub(crate) fn add_topic_rpc_client(&mut self, client: TopicRpcClient_) {
+        self.topic_rpc_clients.push(client);
+    }
+
+    pub(crate) fn add_acked_publisher(&mut self, publisher: AckedPublisher_) {
+        self.acked_publishers.push(publisher);
+    }
+
+    pub(crate) fn add_acked_subscriber(&mut self, subscriber: AckedSubscriber_) {
+        self.acked_subscribers.push(subscriber);
+    }
+
+    // Lets clients with `expect_single_server` recount the servers of
+    // their services, and action clients look for status publishers
+    // they cannot hear.
+    fn poll_servers(&mut self) {
+        // this queries every node in the graph, so keep it rare.
+        let now = Instant::now();
+        if let Some(last) = self.last_server_check {
+            if now.duration_since(last) < Duration::from_secs(1) {
+                return;
+            }
+        }
+        self.last_server_check = Some(now);
+
+        let node_handle = self.node_handle.as_ref();
+        for c in &self.clients {
+            c.lock().unwrap().check_servers(node_handle);
+        }
+        for c in &self.action_clients {
+            let mut c = c.lock().unwrap();
+            c.check_servers(node_handle);
+            c.check_status_qos(node_handle);
+        }
+        for s in &self.action_servers {
+            s.lock().unwrap().check_clients(node_handle);
+        }
+    }
+
+    fn tracks_readiness(&self) -> bool {
+        !self.readiness_waiters.is_empty() || self.readiness_publisher.is_some()
+    }
+
+    fn poll_readiness(&mut self) {
+        if !self.tracks_readiness() {
+            return;
+        }
+        // graph queries are not free, so only query again when the graph
+        // guard condition has been triggered.
+        let missing = match self.readiness_missing.take() {
+            Some(missing) => missing,
+            None => match self.missing_dependencies() {
+                Ok(missing) => missing,
+                Err(e) => {
+                    self.node_errors().report(SpinOperation::Update, e);
+                    return;
+                }
+            },
+        };
+        let now = Instant::now();
+
+        let node_errors = self.node_errors();
+        if let Some((publisher, published)) = &mut self.readiness_publisher {
+            let ready = missing.is_empty();
+            if *published != Some(ready) {
+                match publisher.publish(serde_json::json!({ "data": ready })) {
+                    Ok(()) => *published = Some(ready),
+                    Err(e) => node_errors.report(SpinOperation::Send, e),
+                }
+            }
+        }
+
+        let mut i = 0;
+        while i < self.readiness_waiters.len() {
+            if self.readiness_waiters[i].update(&missing, now) {
+                self.readiness_waiters.swap_remove(i).complete(&missing);
+            } else {
+                i += 1;
+            }
+        }
+        self.readiness_missing = Some(missing);
     }
 
     /// Create a ROS action client.
@@ -450,21 +1638,97 @@ impl Node {
     where
         T: WrappedActionTypeSupport,
     {
-        let client_handle =
-            create_action_client_helper(self.node_handle.as_mut(), action_name, T::get_ts())?;
+        self.create_action_client_with_options(action_name, ActionClientOptions::default())
+    }
+
+    /// Create a ROS action client that is configured with the returned
+    /// builder and, if asked to, waits for its server.
+    pub fn action_client_builder<T: 'static>(
+        &mut self,
+        action_name: &str,
+    ) -> ActionClientBuilder<'_, T>
+    where
+        T: WrappedActionTypeSupport,
+    {
+        ActionClientBuilder::new(self, action_name)
+    }
+
+    /// Create a ROS action client with the given options.
+    pub fn create_action_client_with_options<T: 'static>(
+        &mut self,
+        action_name: &str,
+        options: ActionClientOptions,
+    ) -> Result<ActionClient<T>>
+    where
+        T: WrappedActionTypeSupport,
+    {
+        let profiles = [
+            &options.qos,
+            &options.service_qos,
+            &options.feedback_qos,
+            &options.status_qos,
+        ];
+        for qos in profiles.iter().filter_map(|qos| qos.as_ref()) {
+            qos.validate()?;
+        }
+        let goal_metadata_publisher = if options.goal_metadata {
+            Some(self.create_publisher_untyped(
+                &goal_metadata_topic(action_name),
+                GOAL_METADATA_MSG_TYPE,
+            )?)
+        } else {
+            None
+        };
+        let client_handle = create_action_client_helper(
+            self.node_handle.as_mut(),
+            action_name,
+            T::get_ts(),
+            &options,
+        )?;
+        let (goal_response_guard, cancel_response_guard, result_response_guard) =
+            action_response_guards(&client_handle);
+        let debug_period = options.debug_period;
         let client = WrappedActionClient::<T> {
             rcl_handle: client_handle,
-            goal_response_channels: Vec::new(),
-            cancel_response_channels: Vec::new(),
-            feedback_senders: Vec::new(),
-            result_senders: Vec::new(),
-            result_requests: Vec::new(),
+            goal_response_channels: HashMap::new(),
+            cancel_response_channels: HashMap::new(),
+            feedback_senders: HashMap::new(),
+            result_senders: HashMap::new(),
+            result_requests: HashMap::new(),
+            goal_response_deadlines: HashMap::new(),
+            result_deadlines: HashMap::new(),
+            cancel_response_deadlines: HashMap::new(),
+            watchdogs: HashMap::new(),
+            watchdog_cancels: Vec::new(),
             goal_status: HashMap::new(),
+            terminal_goals: HashMap::new(),
+            forgotten_goals: HashSet::new(),
+            first_seen: HashMap::new(),
+            cancels_sent: HashMap::new(),
+            oversized_maps: HashSet::new(),
+            status_senders: Vec::new(),
+            goal_metadata_publisher,
+            goal_response_guard,
+            cancel_response_guard,
+            result_response_guard,
             poll_available_channels: Vec::new(),
+            server_check: ServerCheck::new(options.expect_single_server),
+            status_qos_check: StatusQosCheck::new(options.status_qos_check),
+            errors: self.errors.entity(EntityKind::ActionClient, action_name),
+            options,
         };
 
         let client_arc = Arc::new(Mutex::new(client));
+        self.entities.register(
+            arc_address(&client_arc),
+            EntityKind::ActionClient,
+            action_name,
+            None,
+        );
         self.action_clients.push(client_arc.clone());
+        if let Some(period) = debug_period {
+            publish_client_debug(self, action_name, Arc::downgrade(&client_arc), period)?;
+        }
         let c = make_action_client(Arc::downgrade(&client_arc));
         Ok(c)
     }
@@ -479,26 +1743,100 @@ impl Node {
         &mut self,
         action_name: &str,
         action_type: &str,
+    ) -> Result<ActionClientUntyped> {
+        self.create_action_client_untyped_with_options(
+            action_name,
+            action_type,
+            ActionClientOptions::default(),
+        )
+    }
+
+    /// Create a ROS action client with the given options, see
+    /// `create_action_client_untyped`.
+    ///
+    /// `goal_metadata`, `auto_request_result`,
+    /// `bookkeeping_warning_size` and `debug_period` only apply to
+    /// typed action clients.
+    pub fn create_action_client_untyped_with_options(
+        &mut self,
+        action_name: &str,
+        action_type: &str,
+        options: ActionClientOptions,
     ) -> Result<ActionClientUntyped> {
         let action_type_support = UntypedActionSupport::new_from(action_type)?;
+        self.create_action_client_untyped_helper(action_name, action_type_support, options)
+    }
+
+    /// Create a ROS action client with type support loaded at runtime,
+    /// see `TypeSupportLibrary` and `create_action_client_untyped`.
+    ///
+    /// The goals, feedback and results are `serde_json::Value`s. The
+    /// client keeps the type support library loaded.
+    pub fn create_action_client_with_type_support(
+        &mut self,
+        action_name: &str,
+        type_support: &InterfaceTypeSupport<rosidl_action_type_support_t>,
+        options: ActionClientOptions,
+    ) -> Result<ActionClientUntyped> {
+        let action_type_support = UntypedActionSupport::from_type_support(type_support)?;
+        self.create_action_client_untyped_helper(action_name, action_type_support, options)
+    }
+
+    fn create_action_client_untyped_helper(
+        &mut self,
+        action_name: &str,
+        action_type_support: UntypedActionSupport,
+        options: ActionClientOptions,
+    ) -> Result<ActionClientUntyped> {
+        let profiles = [
+            &options.qos,
+            &options.service_qos,
+            &options.feedback_qos,
+            &options.status_qos,
+        ];
+        for qos in profiles.iter().filter_map(|qos| qos.as_ref()) {
+            qos.validate()?;
+        }
         let client_handle = create_action_client_helper(
             self.node_handle.as_mut(),
             action_name,
             action_type_support.ts,
+            &options,
         )?;
+        let (goal_response_guard, cancel_response_guard, result_response_guard) =
+            action_response_guards(&client_handle);
         let client = WrappedActionClientUntyped {
             action_type_support,
             rcl_handle: client_handle,
-            goal_response_channels: Vec::new(),
-            cancel_response_channels: Vec::new(),
-            feedback_senders: Vec::new(),
-            result_senders: Vec::new(),
-            result_requests: Vec::new(),
+            goal_response_channels: HashMap::new(),
+            cancel_response_channels: HashMap::new(),
+            feedback_senders: HashMap::new(),
+            result_senders: HashMap::new(),
+            result_requests: HashMap::new(),
+            goal_response_deadlines: HashMap::new(),
+            result_deadlines: HashMap::new(),
+            cancel_response_deadlines: HashMap::new(),
             goal_status: HashMap::new(),
+            terminal_goals: HashMap::new(),
+            forgotten_goals: HashSet::new(),
+            status_senders: Vec::new(),
+            goal_response_guard,
+            cancel_response_guard,
+            result_response_guard,
             poll_available_channels: Vec::new(),
+            server_check: ServerCheck::new(options.expect_single_server),
+            status_qos_check: StatusQosCheck::new(options.status_qos_check),
+            errors: self.errors.entity(EntityKind::ActionClient, action_name),
+            options,
         };
 
         let client_arc = Arc::new(Mutex::new(client));
+        self.entities.register(
+            arc_address(&client_arc),
+            EntityKind::ActionClient,
+            action_name,
+            None,
+        );
         self.action_clients.push(client_arc.clone());
         let c = make_action_client_untyped(Arc::downgrade(&client_arc));
         Ok(c)
@@ -515,6 +1853,53 @@ impl Node {
     where
         T: WrappedActionTypeSupport,
     {
+        self.create_action_server_with_options(action_name, ActionServerOptions::default())
+    }
+
+    /// Create a ROS action server whose goal, cancel and execution
+    /// handlers are registered with the returned builder.
+    pub fn action_server_builder<T: 'static>(
+        &mut self,
+        action_name: &str,
+    ) -> ActionServerBuilder<'_, T>
+    where
+        T: WrappedActionTypeSupport,
+    {
+        ActionServerBuilder::new(self, action_name)
+    }
+
+    /// Create a ROS action server with the given options.
+    pub fn create_action_server_with_options<T: 'static>(
+        &mut self,
+        action_name: &str,
+        options: ActionServerOptions,
+    ) -> Result<impl Stream<Item = ActionServerGoalRequest<T>> + Unpin>
+    where
+        T: WrappedActionTypeSupport,
+    {
+        if let Some(qos) = &options.qos {
+            qos.validate()?;
+        }
+        let goal_metadata = if options.goal_metadata {
+            let metadata = Arc::new(Mutex::new(HashMap::new()));
+            let msg = WrappedNativeMsgUntyped::new_from(GOAL_METADATA_MSG_TYPE)?;
+            let subscription_handle = create_subscription_helper(
+                self.node_handle.as_mut(),
+                &goal_metadata_topic(action_name),
+                msg.ts,
+                rmw_qos_profile_t::default(),
+            )?;
+            self.subscribers.push(Box::new(GoalMetadataSubscriber {
+                rcl_handle: subscription_handle,
+                metadata: Arc::downgrade(&metadata),
+                errors: self.errors.entity(EntityKind::ActionServer, action_name),
+            }));
+            self.register_last_subscriber(&goal_metadata_topic(action_name), None);
+            Some(metadata)
+        } else {
+            None
+        };
+
         // for now automatically create a ros clock...
         let mut clock_handle = MaybeUninit::<rcl_clock_t>::uninit();
         let ret = unsafe {
@@ -524,7 +1909,6 @@ impl Node {
             )
         };
         if ret != RCL_RET_OK as i32 {
-            eprintln!("could not create steady clock: {}", ret);
             return Err(Error::from_rcl_error(ret));
         }
         let mut clock_handle = Box::new(unsafe { clock_handle.assume_init() });
@@ -537,6 +1921,7 @@ impl Node {
             action_name,
             clock_handle.as_mut(),
             T::get_ts(),
+            &options,
         )?;
         let server = WrappedActionServer::<T> {
             rcl_handle: server_handle,
@@ -544,41 +1929,563 @@ impl Node {
             goal_request_sender,
             active_cancel_requests: Vec::new(),
             cancel_senders: HashMap::new(),
+            cancel_callback: options.cancel_callback,
             goals: HashMap::new(),
             result_msgs: HashMap::new(),
             result_requests: HashMap::new(),
+            goal_metadata,
+            errors: self.errors.entity(EntityKind::ActionServer, action_name),
+            action_name: action_name.to_owned(),
+            goal_queue: options.goal_queue,
+            running_goals: HashSet::new(),
+            queued_goals: VecDeque::new(),
+            canceled_queued_goals: Vec::new(),
+            unreachable_goals: HashSet::new(),
+            status_publish_failed: RefCell::new(HashSet::new()),
+            missing_client_checks: 0,
+            shutdown_timeout: options.shutdown_timeout.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT),
         };
 
         let server_arc = Arc::new(Mutex::new(server));
-        self.action_servers.push(server_arc);
+        self.entities.register(
+            arc_address(&server_arc),
+            EntityKind::ActionServer,
+            action_name,
+            None,
+        );
+        self.action_servers.push(server_arc.clone());
+        if let Some(period) = options.debug_period {
+            let server_arc: Arc<Mutex<dyn ActionServer_>> = server_arc;
+            publish_server_debug::<T>(self, action_name, Arc::downgrade(&server_arc), period)?;
+        }
         Ok(goal_request_receiver)
     }
 
-    /// Create a ROS publisher.
-    pub fn create_publisher<T>(&mut self, topic: &str) -> Result<Publisher<T>>
-    where
-        T: WrappedTypesupport,
-    {
-        let publisher_handle =
-            create_publisher_helper(self.node_handle.as_mut(), topic, T::get_ts())?;
-        let arc = Arc::new(publisher_handle);
-        let p = make_publisher(Arc::downgrade(&arc));
-        self.pubs.push(arc);
-        Ok(p)
+    /// Goals of the action server that have not reached a terminal
+    /// state, goals waiting in its `GoalQueue` last in the order they
+    /// will be started.
+    ///
+    /// `action_name` is the name the server was created with.
+    pub fn active_goals(&self, action_name: &str) -> Result<Vec<(GoalId, GoalStatus)>> {
+        self.action_servers
+            .iter()
+            .map(|a| a.lock().unwrap())
+            .find(|a| a.action_name() == action_name)
+            .map(|a| a.active_goals())
+            .ok_or(Error::RCL_RET_ACTION_NAME_INVALID)
+    }
+
+    /// Abort a goal of the action server from within the process,
+    /// without going through a cancel request. Clients see the goal
+    /// as aborted with a default result, and the `ActionServerGoal`
+    /// of the goal reports the new status through `status`. Finishing
+    /// the goal with that handle afterwards fails.
+    ///
+    /// `action_name` is the name the server was created with.
+    pub fn abort_goal(&self, action_name: &str, goal: &GoalId) -> Result<()> {
+        self.action_servers
+            .iter()
+            .map(|a| a.lock().unwrap())
+            .find(|a| a.action_name() == action_name)
+            .ok_or(Error::RCL_RET_ACTION_NAME_INVALID)?
+            .abort_goal(goal)
+    }
+
+    /// Create a ROS publisher.
+    pub fn create_publisher<T>(&mut self, topic: &str) -> Result<Publisher<T>>
+    where
+        T: WrappedTypesupport,
+    {
+        self.create_publisher_with_options(topic, PublisherOptions::default())
+    }
+
+    /// Like `create_publisher`, but e.g. lets you set the QoS profile
+    /// or enable message statistics.
+    pub fn create_publisher_with_options<T>(
+        &mut self,
+        topic: &str,
+        options: PublisherOptions,
+    ) -> Result<Publisher<T>>
+    where
+        T: WrappedTypesupport,
+    {
+        let qos = self.rmw_qos_with_overrides(
+            topic,
+            QosEntityKind::Publisher,
+            options.qos.as_ref(),
+            &options.qos_overrides,
+            options.rmw_qos()?,
+        )?;
+        let publisher_handle =
+            create_publisher_helper(self.node_handle.as_mut(), topic, T::get_ts(), qos)?;
+        let stats = if options.stats {
+            let topic = publisher_topic_name(&publisher_handle)?;
+            Some(self.make_stats_tracker(&topic, true))
+        } else {
+            None
+        };
+        let arc = Arc::new(publisher_handle);
+        self.entities.register(
+            publisher_address(&arc),
+            EntityKind::Publisher,
+            topic,
+            stats.as_ref(),
+        );
+        let p = make_publisher_with_stats(Arc::downgrade(&arc), stats);
+        self.pubs.push(arc);
+        Ok(p)
+    }
+
+    // Declares the qos_overrides parameters of a publisher or
+    // subscription and applies their values. Without a profile in the
+    // options, the overrides apply to the rmw defaults.
+    fn rmw_qos_with_overrides(
+        &self,
+        topic: &str,
+        entity: QosEntityKind,
+        qos: Option<&QosProfile>,
+        overrides: &QosOverridePolicy,
+        rmw_qos: rmw_qos_profile_t,
+    ) -> Result<rmw_qos_profile_t> {
+        let options = match overrides {
+            QosOverridePolicy::Ignore => return Ok(rmw_qos),
+            QosOverridePolicy::Declare(options) => options,
+        };
+        let topic = resolve_topic_name(self.node_handle.as_ref(), topic)?;
+        let qos = qos.cloned().unwrap_or_else(QosProfile::system_default);
+        let mut params = self.params.lock().unwrap();
+        let qos = declare_qos_overrides(&mut params, &topic, entity, options, qos)?;
+        Ok(rmw_qos_profile_t::from(&qos))
+    }
+
+    fn make_stats_tracker(&mut self, topic: &str, published: bool) -> Arc<Mutex<StatsTracker>> {
+        let tracker = Arc::new(Mutex::new(StatsTracker::new(topic, published)));
+        self.topic_stats.retain(|t| t.strong_count() > 0);
+        self.topic_stats.push(Arc::downgrade(&tracker));
+        tracker
+    }
+
+    /// Message statistics of the publishers and subscriptions that
+    /// have them enabled, summed per topic, and the number of requests
+    /// each service refused.
+    pub fn metrics(&self) -> NodeMetrics {
+        let mut metrics = NodeMetrics::default();
+        for tracker in self.topic_stats.iter().filter_map(|t| t.upgrade()) {
+            add_to_metrics(&mut metrics, &mut tracker.lock().unwrap());
+        }
+        metrics.refused_requests = self
+            .services
+            .iter()
+            .map(|s| {
+                let s = s.lock().unwrap();
+                (service_name(s.handle()).unwrap_or_default(), s.refused_requests())
+            })
+            .collect();
+        metrics
+    }
+
+    /// Create a ROS publisher that republishes its last `depth`
+    /// messages when new subscribers match, see `RetainedPublisher`.
+    ///
+    /// The republishing is done by `spin_once`.
+    pub fn create_retained_publisher<T: 'static>(
+        &mut self,
+        topic: &str,
+        qos: QosProfile,
+        depth: usize,
+    ) -> Result<RetainedPublisher<T>>
+    where
+        T: WrappedTypesupport,
+    {
+        qos.validate()?;
+        let publisher_handle = create_publisher_helper(
+            self.node_handle.as_mut(),
+            topic,
+            T::get_ts(),
+            rmw_qos_profile_t::from(&qos),
+        )?;
+        let arc = Arc::new(publisher_handle);
+        let (p, retained) = make_retained_publisher::<T>(
+            Arc::downgrade(&arc),
+            depth,
+            self.errors.entity(EntityKind::Publisher, topic),
+        );
+        self.entities
+            .register(publisher_address(&arc), EntityKind::Publisher, topic, None);
+        self.pubs.push(arc);
+        // without matched events, new subscriptions are noticed by
+        // their number growing.
+        let matched = MatchedEvent::new(&arc).ok();
+        let retained: Arc<Mutex<dyn Retained_>> = retained;
+        self.retained_publishers
+            .push((Arc::downgrade(&retained), matched));
+        Ok(p)
+    }
+
+    /// Create a ROS publisher with a type given at runtime.
+    pub fn create_publisher_untyped(
+        &mut self,
+        topic: &str,
+        topic_type: &str,
+    ) -> Result<PublisherUntyped> {
+        let dummy = WrappedNativeMsgUntyped::new_from(topic_type)?;
+        let publisher_handle = create_publisher_helper(
+            self.node_handle.as_mut(),
+            topic,
+            dummy.ts,
+            rmw_qos_profile_t::default(),
+        )?;
+        let arc = Arc::new(publisher_handle);
+        let p = make_publisher_untyped(Arc::downgrade(&arc), topic_type.to_owned());
+        self.entities
+            .register(publisher_address(&arc), EntityKind::Publisher, topic, None);
+        self.pubs.push(arc);
+        Ok(p)
+    }
+
+    /// Create a ROS publisher with type support given at runtime, see
+    /// `TypeSupportLibrary`. Messages are published already serialized.
+    pub fn create_publisher_serialized(
+        &mut self,
+        topic: &str,
+        type_support: &MessageTypeSupport,
+    ) -> Result<PublisherSerialized> {
+        let publisher_handle = create_publisher_helper(
+            self.node_handle.as_mut(),
+            topic,
+            type_support.as_ptr(),
+            rmw_qos_profile_t::default(),
+        )?;
+        let arc = Arc::new(publisher_handle);
+        let p = make_publisher_serialized(Arc::downgrade(&arc), type_support.clone());
+        self.entities
+            .register(publisher_address(&arc), EntityKind::Publisher, topic, None);
+        self.pubs.push(arc);
+        self.publisher_type_support.push(type_support.clone());
+        Ok(p)
+    }
+
+    /// Wait until the messages sent by the reliable publishers of this
+    /// node have been acknowledged by their subscribers.
+    ///
+    /// Useful before shutting down, so that e.g. a final status
+    /// message is not lost when the process exits. All publishers
+    /// share the same deadline, `timeout` from now. Returns the topic
+    /// name and outcome for each reliable publisher. Best effort
+    /// publishers are skipped.
+    ///
+    /// If the rmw implementation or ROS distribution cannot wait for
+    /// acknowledgments, this sleeps for the grace period (see
+    /// `set_flush_grace_period`) instead and reports
+    /// `FlushStatus::Unknown` for the affected publishers.
+    pub fn flush_publishers(&self, timeout: Duration) -> Vec<(String, FlushStatus)> {
+        let deadline = Instant::now() + timeout;
+        let mut report = Vec::new();
+        for p in &self.pubs {
+            if !publisher_is_reliable(p) {
+                continue;
+            }
+            let topic = publisher_topic_name(p).unwrap_or_default();
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            report.push((topic, self.flush_publisher(p, remaining)));
+        }
+        if report.iter().any(|(_, s)| s == &FlushStatus::Unknown) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            std::thread::sleep(self.flush_grace_period.min(remaining));
+        }
+        report
+    }
+
+    /// Set how long `flush_publishers` sleeps when it cannot wait for
+    /// acknowledgments. Defaults to 100ms.
+    pub fn set_flush_grace_period(&mut self, grace_period: Duration) {
+        self.flush_grace_period = grace_period;
+    }
+
+    /// Register a hook that runs when the node shuts down, e.g. to
+    /// publish a last status message. Hooks run once, in the order they
+    /// were registered, either from `shutdown` or when the node is
+    /// dropped.
+    pub fn on_shutdown<F>(&mut self, hook: F)
+    where
+        F: FnOnce(&mut Node) -> Result<()> + Send + 'static,
+    {
+        self.shutdown_hooks.push(Box::new(hook));
+    }
+
+    /// Shut the node down in phases: run the hooks registered with
+    /// `on_shutdown`, then flush the reliable publishers if
+    /// `options.flush_timeout` is set, so that what the hooks published
+    /// is acknowledged before the process exits.
+    ///
+    /// The entities of the node are destroyed when it is dropped. Hooks
+    /// that fail do not stop the shutdown, their errors are in the
+    /// report.
+    pub fn shutdown(&mut self, options: ShutdownOptions) -> ShutdownReport {
+        let hook_errors = self.run_shutdown_hooks();
+        let flushed = match options.flush_timeout {
+            Some(timeout) => self.flush_publishers(timeout),
+            None => Vec::new(),
+        };
+        ShutdownReport {
+            hook_errors,
+            flushed,
+        }
+    }
+
+    fn run_shutdown_hooks(&mut self) -> Vec<Error> {
+        let hooks = std::mem::take(&mut self.shutdown_hooks);
+        hooks.into_iter().filter_map(|hook| hook(self).err()).collect()
+    }
+
+    fn flush_publisher(&self, publisher: &rcl_publisher_t, timeout: Duration) -> FlushStatus {
+        match distro::publisher_wait_for_all_acked(publisher, timeout) {
+            Ok(status) => status,
+            Err(Error::NotSupported { .. }) => FlushStatus::Unknown,
+            Err(e) => {
+                log_internal(
+                    LogSeverity::Warn,
+                    &format!("could not wait for publisher acks: {}", e),
+                );
+                FlushStatus::Unknown
+            }
+        }
+    }
+
+    /// List the publishers, subscriptions, services, clients, actions
+    /// and timers the node currently owns.
+    ///
+    /// Most entities are only finalized when the node is dropped, even
+    /// if the handle returned when they were created is long gone. Such
+    /// entities are listed with `handle_alive` set to false, see
+    /// `reap_orphaned_entities`.
+    pub fn list_entities(&mut self) -> Vec<EntityInfo> {
+        let states = self.entity_states();
+        let addresses = states.iter().map(|(a, _, _)| *a).collect();
+        self.entities.retain(&addresses);
+        states
+            .into_iter()
+            .map(|(a, kind, alive)| self.entities.info(a, kind, alive))
+            .collect()
+    }
+
+    /// Finalize the entities whose handles have been dropped, and
+    /// return what they were.
+    ///
+    /// Must not be called while ready entities of this node are being
+    /// handled on other threads, see `SharedReadyEntity`.
+    pub fn reap_orphaned_entities(&mut self) -> Vec<EntityInfo> {
+        let mut reaped = Vec::new();
+        let mut orphaned = HashSet::new();
+        for (a, kind, alive) in self.entity_states() {
+            if !alive {
+                reaped.push(self.entities.info(a, kind, alive));
+                orphaned.insert(a);
+            }
+        }
+        if !orphaned.is_empty() {
+            self.destroy_entities(&orphaned, None);
+        }
+        reaped
+    }
+
+    /// Create a scope whose entities are destroyed when it is dropped,
+    /// see `NodeScope`.
+    pub fn scope(&mut self) -> NodeScope<'_> {
+        NodeScope::new(self)
+    }
+
+    // The addresses of all entities, to tell which ones a call created.
+    pub(crate) fn entity_addresses(&self) -> HashSet<usize> {
+        self.entity_states()
+            .into_iter()
+            .map(|(a, _, _)| a)
+            .collect()
+    }
+
+    // Sends cancel requests for the goals of the action clients at
+    // `addresses` that have not finished yet.
+    pub(crate) fn cancel_pending_goals(&mut self, addresses: &HashSet<usize>) {
+        for c in &self.action_clients {
+            if addresses.contains(&arc_address(c)) {
+                c.lock().unwrap().cancel_pending_goals();
+            }
+        }
+    }
+
+    // Finalizes the entities at `addresses`, which fails the futures
+    // and ends the streams still waiting on them. Entities that are in
+    // use elsewhere until `deadline` are kept, and their addresses
+    // returned. Without a deadline, we wait for them.
+    pub(crate) fn destroy_entities(
+        &mut self,
+        addresses: &HashSet<usize>,
+        deadline: Option<Instant>,
+    ) -> HashSet<usize> {
+        let mut kept = HashSet::new();
+        // fini functions are not thread safe so lock the context.
+        let _ctx_handle = self.context.context_handle.lock().unwrap();
+        let node_handle = self.node_handle.as_mut();
+        self.subscribers.retain_mut(|s| {
+            if addresses.contains(&subscriber_address(s)) {
+                s.destroy(node_handle);
+                false
+            } else {
+                true
+            }
+        });
+        self.services.retain(|s| {
+            let a = arc_address(s);
+            if !addresses.contains(&a) {
+                return true;
+            }
+            match lock_until(s, deadline) {
+                Some(mut s) => {
+                    s.destroy(node_handle);
+                    false
+                }
+                None => {
+                    kept.insert(a);
+                    true
+                }
+            }
+        });
+        self.clients.retain(|c| {
+            let a = arc_address(c);
+            if !addresses.contains(&a) {
+                return true;
+            }
+            match lock_until(c, deadline) {
+                Some(mut c) => {
+                    c.destroy(node_handle);
+                    false
+                }
+                None => {
+                    kept.insert(a);
+                    true
+                }
+            }
+        });
+        self.action_clients.retain(|c| {
+            let a = arc_address(c);
+            if !addresses.contains(&a) {
+                return true;
+            }
+            match lock_until(c, deadline) {
+                Some(mut c) => {
+                    c.destroy(node_handle);
+                    false
+                }
+                None => {
+                    kept.insert(a);
+                    true
+                }
+            }
+        });
+        self.action_servers.retain(|s| {
+            let a = arc_address(s);
+            if !addresses.contains(&a) {
+                return true;
+            }
+            match lock_until(s, deadline) {
+                Some(mut s) => {
+                    s.destroy(node_handle);
+                    false
+                }
+                None => {
+                    kept.insert(a);
+                    true
+                }
+            }
+        });
+        let (dropped, mut pubs): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pubs)
+            .into_iter()
+            .partition(|p| addresses.contains(&publisher_address(p)));
+        for p in dropped {
+            let mut p = match deadline {
+                Some(deadline) => match unwrap_until(p, deadline) {
+                    Ok(p) => p,
+                    Err(p) => {
+                        kept.insert(publisher_address(&p));
+                        pubs.push(p);
+                        continue;
+                    }
+                },
+                None => wait_until_unwrapped(p),
+            };
+            let _ret = unsafe { rcl_publisher_fini(&mut p as *mut _, node_handle) };
+        }
+        self.pubs = pubs;
+        self.timers
+            .retain(|t| !addresses.contains(&(t.timer_handle.impl_ as usize)));
+        // and forget about the ones that were found ready before.
+        let destroyed = |a: &usize| addresses.contains(a) && !kept.contains(a);
+        self.pending_ready.retain(|e| match &e.handle {
+            ReadyHandle::Subscription(_) => true,
+            ReadyHandle::Timer(t) => !destroyed(&(t.impl_ as usize)),
+            ReadyHandle::Client(c) => !destroyed(&arc_address(c)),
+            ReadyHandle::Service(s) => !destroyed(&arc_address(s)),
+            ReadyHandle::ActionClient(c, _) => !destroyed(&arc_address(c)),
+            ReadyHandle::ActionServer(s, _) => !destroyed(&arc_address(s)),
+        });
+        for a in addresses.iter().filter(|a| destroyed(a)) {
+            self.entities.forget(*a);
+        }
+        kept
+    }
+
+    // The address of each entity in the node, its kind and whether
+    // its handle is still alive.
+    fn entity_states(&self) -> Vec<(usize, EntityKind, bool)> {
+        let mut states = Vec::new();
+        for s in &self.subscribers {
+            states.push((
+                subscriber_address(s),
+                EntityKind::Subscription,
+                !s.is_dropped(),
+            ));
+        }
+        for s in &self.services {
+            let alive = !s.lock().unwrap().is_dropped();
+            states.push((arc_address(s), EntityKind::Service, alive));
+        }
+        for c in &self.clients {
+            states.push((arc_address(c), EntityKind::Client, Arc::weak_count(c) > 0));
+        }
+        for c in &self.action_clients {
+            states.push((
+                arc_address(c),
+                EntityKind::ActionClient,
+                Arc::weak_count(c) > 0,
+            ));
+        }
+        for s in &self.action_servers {
+            // goal handles keep the server alive after the goal stream is gone.
+            let alive = !s.lock().unwrap().is_dropped() || Arc::weak_count(s) > 0;
+            states.push((arc_address(s), EntityKind::ActionServer, alive));
+        }
+        for p in &self.pubs {
+            states.push((
+                publisher_address(p),
+                EntityKind::Publisher,
+                Arc::weak_count(p) > 0,
+            ));
+        }
+        for t in &self.timers {
+            let alive = !t.sender.is_closed();
+            states.push((t.timer_handle.impl_ as usize, EntityKind::Timer, alive));
+        }
+        states
     }
 
-    /// Create a ROS publisher with a type given at runtime.
-    pub fn create_publisher_untyped(
-        &mut self,
-        topic: &str,
-        topic_type: &str,
-    ) -> Result<PublisherUntyped> {
-        let dummy = WrappedNativeMsgUntyped::new_from(topic_type)?;
-        let publisher_handle = create_publisher_helper(self.node_handle.as_mut(), topic, dummy.ts)?;
-        let arc = Arc::new(publisher_handle);
-        let p = make_publisher_untyped(Arc::downgrade(&arc), topic_type.to_owned());
-        self.pubs.push(arc);
-        Ok(p)
+    // Remembers the topic and creation time of the subscription that
+    // was pushed last, see list_entities.
+    fn register_last_subscriber(&mut self, topic: &str, stats: Option<&Arc<Mutex<StatsTracker>>>) {
+        if let Some(s) = self.subscribers.last() {
+            let address = subscriber_address(s);
+            self.entities
+                .register(address, EntityKind::Subscription, topic, stats);
+        }
     }
 
     /// Spin the ROS node.
@@ -591,9 +2498,48 @@ impl Node {
     /// `timeout` is a duration specifying how long the spin should
     /// block for if there are no pending events.
     pub fn spin_once(&mut self, timeout: Duration) {
+        self.spin_once_with_budget(timeout, SpinBudget::default());
+    }
+
+    /// Spin the ROS node, doing at most `budget` worth of work.
+    ///
+    /// Works like `spin_once`, but stops handling ready entities
+    /// (every taken message, timer tick, request, response, etc
+    /// counts) when the budget is used up. At least one entity is
+    /// handled per call. The remaining ready entities are remembered
+    /// and handled by the next call to `spin_once` or
+    /// `spin_once_with_budget`, which then does not wait. Returns
+    /// true if work remains.
+    pub fn spin_once_with_budget(&mut self, timeout: Duration, budget: SpinBudget) -> bool {
+        self.prepare_wait();
+
+        if self.pending_ready.is_empty() {
+            self.pending_ready = self.wait(timeout);
+        }
+
+        let start = Instant::now();
+        let mut handled = 0;
+        while let Some(entity) = self.pending_ready.pop_front() {
+            self.execute(entity);
+            handled += 1;
+            if handled >= budget.max_messages || start.elapsed() >= budget.max_duration {
+                break;
+            }
+        }
+
+        !self.pending_ready.is_empty()
+    }
+
+    /// Does the work that precedes waiting in a spin, such as polling
+    /// for service availability and removing entities whose streams
+    /// have been dropped.
+    ///
+    /// Only needed when implementing an `Executor`.
+    pub fn prepare_wait(&mut self) {
         // first handle any completed action cancellation responses
         for a in &mut self.action_servers {
             a.lock().unwrap().send_completed_cancel_requests();
+            a.lock().unwrap().promote_queued_goals(a.clone());
         }
 
         // as well as polling any services/action servers for availability
@@ -601,10 +2547,82 @@ impl Node {
             c.lock().unwrap().poll_available(self.node_handle.as_mut());
         }
 
+        // and failing action requests that took too long.
         for c in &mut self.action_clients {
-            c.lock().unwrap().poll_available(self.node_handle.as_mut());
+            let mut c = c.lock().unwrap();
+            c.poll_available(self.node_handle.as_mut());
+            c.check_timeouts();
+        }
+        self.poll_servers();
+
+        // destroy subscriptions whose streams have been dropped so
+        // that we stop waking up for and converting their messages.
+        let node_handle = self.node_handle.as_mut();
+        self.subscribers.retain_mut(|s| {
+            if s.is_dropped() {
+                s.destroy(node_handle);
+                false
+            } else {
+                true
+            }
+        });
+
+        // report publishers whose type differs from ours
+        for s in &mut self.subscribers {
+            s.check_types(self.node_handle.as_ref());
+        }
+
+        // same for services
+        let node_handle = self.node_handle.as_mut();
+        self.services.retain(|s| {
+            let mut s = s.lock().unwrap();
+            if s.is_dropped() {
+                s.destroy(node_handle);
+                false
+            } else {
+                true
+            }
+        });
+
+        // check if declared dependencies have shown up
+        self.poll_readiness();
+
+        // republish retained messages to new subscribers
+        self.retained_publishers
+            .retain_mut(|(retained, matched)| match retained.upgrade() {
+                Some(r) => {
+                    let new_matches = matched.as_mut().and_then(|m| m.new_matches().ok());
+                    r.lock().unwrap().republish_on_match(new_matches)
+                }
+                None => false,
+            });
+
+        // publish periodic messages and check heartbeats and bonds
+        self.periodic_publishers.retain_mut(|p| p.poll());
+        self.playbacks.retain_mut(|p| p.poll());
+        self.heartbeat_monitors.retain_mut(|m| m.poll());
+        self.bonds.retain_mut(|b| b.poll());
+        self.topic_rpc_clients.retain_mut(|c| c.poll());
+        self.acked_subscribers.retain_mut(|s| s.poll());
+        self.acked_publishers.retain_mut(|p| p.poll());
+
+        // and recreate subscriptions whose publishers have come back
+        if let Some(w) = &mut self.resubscribe {
+            w.run(self.node_handle.as_mut(), &mut self.subscribers);
         }
+    }
+
+    /// Waits at most `timeout` for entities to become ready and
+    /// returns them in the default order, see `Executor::execute`.
+    ///
+    /// Only needed when implementing an `Executor`.
+    pub fn wait_ready(&mut self, timeout: Duration) -> Vec<ReadyEntity> {
+        self.wait(timeout).into_iter().collect()
+    }
 
+    // Waits for entities to become ready and returns them in the
+    // order they should be handled.
+    fn wait(&mut self, timeout: Duration) -> VecDeque<ReadyEntity> {
         let timeout = timeout.as_nanos() as i64;
         let mut ws = unsafe { rcl_get_zero_initialized_wait_set() };
 
@@ -674,6 +2692,14 @@ impl Node {
             total_action_services += num_services;
         }
 
+        // wake up on graph changes while dependencies are tracked.
+        let graph_guard = if self.tracks_readiness() {
+            unsafe { rcl_node_get_graph_guard_condition(self.node_handle.as_ref()) }
+        } else {
+            std::ptr::null()
+        };
+        let num_guard_conditions = if graph_guard.is_null() { 0 } else { 1 };
+
         {
             let mut ctx = self.context.context_handle.lock().unwrap();
 
@@ -681,7 +2707,7 @@ impl Node {
                 rcl_wait_set_init(
                     &mut ws,
                     self.subscribers.len() + total_action_subs,
-                    0,
+                    num_guard_conditions,
                     self.timers.len() + total_action_timers,
                     self.clients.len() + total_action_clients,
                     self.services.len() + total_action_services,
@@ -701,6 +2727,12 @@ impl Node {
             }
         }
 
+        if !graph_guard.is_null() {
+            unsafe {
+                rcl_wait_set_add_guard_condition(&mut ws, graph_guard, std::ptr::null_mut());
+            }
+        }
+
         for s in &self.timers {
             unsafe {
                 rcl_wait_set_add_timer(&mut ws, &s.timer_handle, std::ptr::null_mut());
@@ -744,84 +2776,76 @@ impl Node {
             }
         }
 
+        #[cfg(feature = "spin-diagnostics")]
+        self.spin_tracker.set_waiting();
         let ret = unsafe { rcl_wait(&mut ws, timeout) };
+        #[cfg(feature = "spin-diagnostics")]
+        self.spin_tracker.set_idle();
 
         if ret == RCL_RET_TIMEOUT as i32 {
             unsafe {
                 rcl_wait_set_fini(&mut ws);
             }
-            return;
+            return VecDeque::new();
         }
+        let now = Instant::now();
 
-        let ws_subs =
-            unsafe { std::slice::from_raw_parts(ws.subscriptions, self.subscribers.len()) };
-        let mut subs_to_remove = vec![];
-        for (s, ws_s) in self.subscribers.iter_mut().zip(ws_subs) {
-            if ws_s != &std::ptr::null() {
-                let dropped = s.handle_incoming();
-                if dropped {
-                    s.destroy(&mut self.node_handle);
-                    subs_to_remove.push(*s.handle());
-                }
+        if num_guard_conditions > 0 {
+            let ws_guards = unsafe { std::slice::from_raw_parts(ws.guard_conditions, 1) };
+            if ws_guards[0] != std::ptr::null() {
+                // query the graph on the next update.
+                self.readiness_missing = None;
             }
         }
-        self.subscribers
-            .retain(|s| !subs_to_remove.contains(s.handle()));
 
+        // subscriptions and timers are handled in priority order.
+        let ws_subs =
+            unsafe { std::slice::from_raw_parts(ws.subscriptions, self.subscribers.len()) };
         let ws_timers = unsafe { std::slice::from_raw_parts(ws.timers, self.timers.len()) };
-        let mut timers_to_remove = vec![];
-        for (s, ws_s) in self.timers.iter_mut().zip(ws_timers) {
+        let mut prioritized = vec![];
+        for (s, ws_s) in self.subscribers.iter().zip(ws_subs) {
             if ws_s != &std::ptr::null() {
-                // TODO: move this to impl Timer
-                let dropped = s.handle_incoming();
-                if dropped {
-                    timers_to_remove.push(s.timer_handle);
-                }
+                prioritized.push((s.priority(), ReadyHandle::Subscription(*s.handle())));
             }
         }
-        // drop timers scheduled for deletion
-        self.timers
-            .retain(|t| !timers_to_remove.contains(&t.timer_handle));
+        for (t, ws_t) in self.timers.iter().zip(ws_timers) {
+            if ws_t != &std::ptr::null() {
+                prioritized.push((t.priority, ReadyHandle::Timer(t.timer_handle)));
+            }
+        }
+        let order = dispatch_order(&prioritized.iter().map(|(p, _)| *p).collect::<Vec<_>>());
+        let mut prioritized: Vec<Option<ReadyEntity>> = prioritized
+            .into_iter()
+            .map(|(p, h)| Some(ReadyEntity::new(h, p, now)))
+            .collect();
+        let mut ready: VecDeque<ReadyEntity> =
+            order.into_iter().flat_map(|i| prioritized[i].take()).collect();
 
         let ws_clients = unsafe { std::slice::from_raw_parts(ws.clients, self.clients.len()) };
-        for (s, ws_s) in self.clients.iter_mut().zip(ws_clients) {
-            if ws_s != &std::ptr::null() {
-                let mut s = s.lock().unwrap();
-                s.handle_response();
+        for (c, ws_c) in self.clients.iter().zip(ws_clients) {
+            if ws_c != &std::ptr::null() {
+                ready.push_back(ReadyEntity::new(ReadyHandle::Client(c.clone()), 0, now));
             }
         }
 
         let ws_services = unsafe { std::slice::from_raw_parts(ws.services, self.services.len()) };
-        let mut services_to_remove = vec![];
-        for (s, ws_s) in self.services.iter_mut().zip(ws_services) {
+        for (s, ws_s) in self.services.iter().zip(ws_services) {
             if ws_s != &std::ptr::null() {
-                let mut service = s.lock().unwrap();
-                let dropped = service.handle_request(s.clone());
-                if dropped {
-                    service.destroy(&mut self.node_handle);
-                    services_to_remove.push(*service.handle());
-                }
+                ready.push_back(ReadyEntity::new(ReadyHandle::Service(s.clone()), 0, now));
             }
         }
-        self.services
-            .retain(|s| !services_to_remove.contains(s.lock().unwrap().handle()));
 
         for ac in &self.action_clients {
-            let mut is_feedback_ready = false;
-            let mut is_status_ready = false;
-            let mut is_goal_response_ready = false;
-            let mut is_cancel_response_ready = false;
-            let mut is_result_response_ready = false;
-
+            let mut r = ActionClientReady::default();
             let ret = unsafe {
                 rcl_action_client_wait_set_get_entities_ready(
                     &ws,
                     ac.lock().unwrap().handle(),
-                    &mut is_feedback_ready,
-                    &mut is_status_ready,
-                    &mut is_goal_response_ready,
-                    &mut is_cancel_response_ready,
-                    &mut is_result_response_ready,
+                    &mut r.feedback,
+                    &mut r.status,
+                    &mut r.goal_response,
+                    &mut r.cancel_response,
+                    &mut r.result_response,
                 )
             };
 
@@ -829,46 +2853,25 @@ impl Node {
                 continue;
             }
 
-            if is_feedback_ready {
-                let mut acs = ac.lock().unwrap();
-                acs.handle_feedback_msg();
-            }
-
-            if is_status_ready {
-                let mut acs = ac.lock().unwrap();
-                acs.handle_status_msg();
-            }
-
-            if is_goal_response_ready {
-                let mut acs = ac.lock().unwrap();
-                acs.handle_goal_response();
-            }
-
-            if is_cancel_response_ready {
-                let mut acs = ac.lock().unwrap();
-                acs.handle_cancel_response();
-            }
-
-            if is_result_response_ready {
-                let mut acs = ac.lock().unwrap();
-                acs.handle_result_response();
+            if r.feedback || r.status || r.goal_response || r.cancel_response || r.result_response {
+                ready.push_back(ReadyEntity::new(
+                    ReadyHandle::ActionClient(ac.clone(), r),
+                    0,
+                    now,
+                ));
             }
         }
 
         for s in &self.action_servers {
-            let mut is_goal_request_ready = false;
-            let mut is_cancel_request_ready = false;
-            let mut is_result_request_ready = false;
-            let mut is_goal_expired = false;
-
+            let mut r = ActionServerReady::default();
             let ret = unsafe {
                 rcl_action_server_wait_set_get_entities_ready(
                     &ws,
                     s.lock().unwrap().handle(),
-                    &mut is_goal_request_ready,
-                    &mut is_cancel_request_ready,
-                    &mut is_result_request_ready,
-                    &mut is_goal_expired,
+                    &mut r.goal_request,
+                    &mut r.cancel_request,
+                    &mut r.result_request,
+                    &mut r.goal_expired,
                 )
             };
 
@@ -876,74 +2879,305 @@ impl Node {
                 continue;
             }
 
-            if is_goal_request_ready {
-                let mut acs = s.lock().unwrap();
-                acs.handle_goal_request(s.clone());
+            if r.goal_request || r.cancel_request || r.result_request || r.goal_expired {
+                ready.push_back(ReadyEntity::new(
+                    ReadyHandle::ActionServer(s.clone(), r),
+                    0,
+                    now,
+                ));
             }
+        }
 
-            if is_cancel_request_ready {
-                let mut acs = s.lock().unwrap();
-                acs.handle_cancel_request();
-            }
+        unsafe {
+            rcl_wait_set_fini(&mut ws);
+        }
 
-            if is_result_request_ready {
-                let mut acs = s.lock().unwrap();
-                acs.handle_result_request();
-            }
+        ready
+    }
 
-            if is_goal_expired {
-                let mut acs = s.lock().unwrap();
-                acs.handle_goal_expired();
+    /// Handles one ready entity. Entities that have been removed since
+    /// they became ready are skipped.
+    ///
+    /// Only needed when implementing an `Executor`.
+    pub fn execute(&mut self, entity: ReadyEntity) {
+        #[cfg(feature = "spin-diagnostics")]
+        self.spin_tracker.set_dispatching(entity.info().kind);
+        match entity.handle {
+            ReadyHandle::Subscription(handle) => {
+                if let Some(idx) = self.subscribers.iter().position(|s| s.handle() == &handle) {
+                    let dropped = self.subscribers[idx].handle_incoming();
+                    if dropped {
+                        let mut s = self.subscribers.remove(idx);
+                        s.destroy(&mut self.node_handle);
+                    }
+                }
+            }
+            ReadyHandle::Timer(handle) => {
+                if let Some(idx) = self.timers.iter().position(|t| t.timer_handle == handle) {
+                    // TODO: move this to impl Timer
+                    let dropped = self.timers[idx].handle_incoming();
+                    if dropped {
+                        // drop timer scheduled for deletion
+                        self.timers.remove(idx);
+                    }
+                }
             }
+            handle => execute_shared(handle),
         }
+        #[cfg(feature = "spin-diagnostics")]
+        self.spin_tracker.set_idle();
+    }
 
-        unsafe {
-            rcl_wait_set_fini(&mut ws);
-        }
+    /// What the thread spinning the node is doing. Entities handed to
+    /// `SharedReadyEntity::execute` are not tracked.
+    ///
+    /// Only available with the `spin-diagnostics` feature.
+    #[cfg(feature = "spin-diagnostics")]
+    pub fn spin_state(&self) -> SpinState {
+        self.spin_monitor().state()
+    }
+
+    /// A handle for observing the spin state without holding the node
+    /// lock, e.g. from another thread while the node is spinning.
+    ///
+    /// Only available with the `spin-diagnostics` feature.
+    #[cfg(feature = "spin-diagnostics")]
+    pub fn spin_monitor(&self) -> SpinMonitor {
+        SpinMonitor::new(self.spin_tracker.clone(), self.context.thread_hooks())
+    }
+
+    /// See `SpinMonitor::deadlock_watchdog`.
+    ///
+    /// Only available with the `spin-diagnostics` feature.
+    #[cfg(feature = "spin-diagnostics")]
+    pub fn deadlock_watchdog<F>(&self, threshold: Duration, callback: F) -> DeadlockWatchdog
+    where
+        F: FnMut(DeadlockSuspect) + Send + 'static,
+    {
+        self.spin_monitor().deadlock_watchdog(threshold, callback)
+    }
+
+    /// Returns a `Stream` of the errors that occur while spinning,
+    /// e.g. failures to take a message or responses that do not match
+    /// any request.
+    ///
+    /// At most 100 errors are buffered, errors that do not fit are
+    /// counted by `error_events_overflow`. Errors are also logged to
+    /// stderr unless disabled with `set_error_logging`. Calling this
+    /// again replaces the previous stream.
+    pub fn error_events(&mut self) -> impl Stream<Item = SpinError> + Unpin {
+        self.errors.subscribe(100)
+    }
+
+    /// Number of errors dropped because the `error_events` stream was full.
+    pub fn error_events_overflow(&self) -> usize {
+        self.errors.overflow()
+    }
+
+    /// Turns logging of spin errors to stderr on or off. Errors are
+    /// always logged while there is no `error_events` stream.
+    pub fn set_error_logging(&mut self, enabled: bool) {
+        self.errors.set_log(enabled);
+    }
+
+    fn node_errors(&self) -> EntityErrors {
+        let name = self.fully_qualified_name().unwrap_or_default();
+        self.errors.entity(EntityKind::Node, &name)
+    }
+
+    /// Returns the nodes in the ROS graph, including this one.
+    ///
+    /// Each node is listed once even if the rmw reports it several
+    /// times.
+    pub fn get_node_names(&self) -> Result<Vec<NodeName>> {
+        node_names(self.node_handle.as_ref())
     }
 
     /// Returns a map of topic names and type names of the publishers
-    /// visible to this node.
+    /// and subscriptions visible to this node, whether or not it has
+    /// any of its own.
     pub fn get_topic_names_and_types(&self) -> Result<HashMap<String, Vec<String>>> {
+        self.topic_names_and_types(false)
+    }
+
+    /// Like `get_topic_names_and_types`, but with the names and types
+    /// as the middleware sees them, e.g. `rt/chatter` and
+    /// `std_msgs::msg::dds_::String_` with DDS, and including topics
+    /// that are not ROS topics.
+    pub fn get_topic_names_and_types_no_demangle(&self) -> Result<HashMap<String, Vec<String>>> {
+        self.topic_names_and_types(true)
+    }
+
+    fn topic_names_and_types(&self, no_demangle: bool) -> Result<HashMap<String, Vec<String>>> {
         let mut tnat = unsafe { rmw_get_zero_initialized_names_and_types() };
         let ret = unsafe {
             rcl_get_topic_names_and_types(
+                self.node_handle.as_ref(),
+                &mut rcutils_get_default_allocator(),
+                no_demangle,
+                &mut tnat,
+            )
+        };
+        if ret != RCL_RET_OK as i32 {
+            return Err(Error::from_rcl_error(ret));
+        }
+        Ok(take_names_and_types(&mut tnat))
+    }
+
+    /// Returns a map of topic names and type names of the publishers
+    /// of the node `node_name` in `node_namespace`.
+    pub fn get_publisher_names_and_types_by_node(
+        &self,
+        node_name: &str,
+        node_namespace: &str,
+    ) -> Result<HashMap<String, Vec<String>>> {
+        let c_name = CString::new(node_name).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
+        let c_ns = CString::new(node_namespace).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
+        let mut tnat = unsafe { rmw_get_zero_initialized_names_and_types() };
+        let ret = unsafe {
+            rcl_get_publisher_names_and_types_by_node(
                 self.node_handle.as_ref(),
                 &mut rcutils_get_default_allocator(),
                 false,
+                c_name.as_ptr(),
+                c_ns.as_ptr(),
                 &mut tnat,
             )
         };
         if ret != RCL_RET_OK as i32 {
-            eprintln!("could not get topic names and types {}", ret);
             return Err(Error::from_rcl_error(ret));
         }
+        Ok(take_names_and_types(&mut tnat))
+    }
 
-        let names = unsafe { std::slice::from_raw_parts(tnat.names.data, tnat.names.size) };
-        let types = unsafe { std::slice::from_raw_parts(tnat.types, tnat.names.size) };
+    /// Returns a map of service names and type names of the services
+    /// of the node `node_name` in `node_namespace`.
+    pub fn get_service_names_and_types_by_node(
+        &self,
+        node_name: &str,
+        node_namespace: &str,
+    ) -> Result<HashMap<String, Vec<String>>> {
+        let c_name = CString::new(node_name).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
+        let c_ns = CString::new(node_namespace).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
+        let mut tnat = unsafe { rmw_get_zero_initialized_names_and_types() };
+        let ret = unsafe {
+            rcl_get_service_names_and_types_by_node(
+                self.node_handle.as_ref(),
+                &mut rcutils_get_default_allocator(),
+                c_name.as_ptr(),
+                c_ns.as_ptr(),
+                &mut tnat,
+            )
+        };
+        if ret != RCL_RET_OK as i32 {
+            return Err(Error::from_rcl_error(ret));
+        }
+        Ok(take_names_and_types(&mut tnat))
+    }
 
-        let mut res = HashMap::new();
-        for (n, t) in names.iter().zip(types) {
-            let topic_name = unsafe { CStr::from_ptr(*n).to_str().unwrap().to_owned() };
-            let topic_types = unsafe { std::slice::from_raw_parts(t, t.size) };
-            let topic_types: Vec<String> = unsafe {
-                topic_types
-                    .iter()
-                    .map(|t| CStr::from_ptr(*((*t).data)).to_str().unwrap().to_owned())
-                    .collect()
-            };
-            res.insert(topic_name, topic_types);
+    /// Returns information about all publishers of `topic`, including
+    /// their QoS profiles.
+    pub fn get_publishers_info_by_topic(&self, topic: &str) -> Result<Vec<TopicEndpointInfo>> {
+        publishers_info_by_topic(self.node_handle.as_ref(), topic)
+    }
+
+    /// Subscribe to a topic without knowing its type or QoS in advance.
+    ///
+    /// Waits up to `timeout` for a publisher of `topic` to show up,
+    /// then subscribes with the type the publishers use and a QoS
+    /// profile that is compatible with all of them (i.e. reliable
+    /// only if every publisher is reliable, transient local only if
+    /// every publisher is transient local). Returns the type name,
+    /// the chosen profile and a stream of the messages as
+    /// `serde_json::Value`:s, like `subscribe_untyped`.
+    ///
+    /// The message type must be among the types r2r was built with.
+    pub fn observe_topic(
+        &mut self,
+        topic: &str,
+        timeout: Duration,
+    ) -> Result<(
+        String,
+        QosProfile,
+        impl Stream<Item = Result<serde_json::Value>> + Unpin,
+    )> {
+        let start = Instant::now();
+        let topic_type = loop {
+            let topics = self.get_topic_names_and_types()?;
+            if let Some(t) = topics.get(topic).and_then(|types| types.first()) {
+                break t.clone();
+            }
+            if start.elapsed() > timeout {
+                return Err(Error::TopicNotFound {
+                    topic: topic.to_owned(),
+                });
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        let msg = WrappedNativeMsgUntyped::new_from(&topic_type)?;
+
+        let publishers = self.get_publishers_info_by_topic(topic)?;
+        let mut qos = QosProfile::default().keep_last(10);
+        let mut all_reliable = true;
+        let mut all_transient_local = !publishers.is_empty();
+        for p in &publishers {
+            let p_qos = p.qos.as_ref().ok_or_else(|| Error::QosNotMatched {
+                topic: topic.to_owned(),
+                reason: format!(
+                    "publisher {}/{} has an unknown QoS policy",
+                    p.node_namespace, p.node_name
+                ),
+            })?;
+            all_reliable &= p_qos.reliability == ReliabilityPolicy::Reliable;
+            all_transient_local &= p_qos.durability == DurabilityPolicy::TransientLocal;
         }
-        unsafe {
-            rmw_names_and_types_fini(&mut tnat);
-        } // TODO: check return value
-        Ok(res)
+        qos = if all_reliable {
+            qos.reliable()
+        } else {
+            qos.best_effort()
+        };
+        qos = if all_transient_local {
+            qos.transient_local()
+        } else {
+            qos.volatile()
+        };
+        qos = qos.liveliness(LivelinessPolicy::Automatic, Duration::from_secs(0));
+
+        let subscription_handle = create_subscription_helper(
+            self.node_handle.as_mut(),
+            topic,
+            msg.ts,
+            rmw_qos_profile_t::from(&qos),
+        )?;
+        let (sender, receiver) = mpsc::channel::<Result<serde_json::Value>>(10);
+
+        let ws = UntypedSubscriber {
+            rcl_handle: subscription_handle,
+            topic_type: topic_type.clone(),
+            priority: 0,
+            sink: Box::new(ChannelSink::new(sender)),
+            stats: None,
+            errors: self.errors.entity(EntityKind::Subscription, topic),
+            sequence: SequenceTracker::default(),
+        };
+        self.subscribers.push(Box::new(ws));
+        self.register_last_subscriber(topic, None);
+        Ok((topic_type, qos, receiver))
     }
 
     /// Create a ROS wall timer.
     ///
     /// Create a ROS timer that is woken up by spin every `period`.
     pub fn create_wall_timer(&mut self, period: Duration) -> Result<Timer> {
+        self.create_wall_timer_with_options(period, TimerOptions::default())
+    }
+
+    /// Create a ROS wall timer with the given options.
+    pub fn create_wall_timer_with_options(
+        &mut self,
+        period: Duration,
+        options: TimerOptions,
+    ) -> Result<Timer> {
         let mut clock = Clock::create(ClockType::SteadyTime)?;
 
         let mut timer_handle = unsafe { rcl_get_zero_initialized_timer() };
@@ -961,7 +3195,6 @@ impl Node {
         };
 
         if ret != RCL_RET_OK as i32 {
-            eprintln!("could not create timer: {}", ret);
             return Err(Error::from_rcl_error(ret));
         }
 
@@ -970,8 +3203,16 @@ impl Node {
         let timer = Timer_ {
             timer_handle,
             _clock: clock,
+            priority: options.priority,
             sender: tx,
+            errors: self.errors.entity(EntityKind::Timer, ""),
         };
+        self.entities.register(
+            timer.timer_handle.impl_ as usize,
+            EntityKind::Timer,
+            "",
+            None,
+        );
         self.timers.push(timer);
 
         let out_timer = Timer { receiver: rx };
@@ -980,6 +3221,10 @@ impl Node {
     }
 
     /// Get the ros logger name for this node.
+    ///
+    /// Messages logged with it, e.g. `r2r::log_info!(node.logger(), ..)`,
+    /// are also published on `/rosout` if `NodeOptions::enable_rosout`
+    /// is set.
     pub fn logger<'a>(&'a self) -> &'a str {
         let ptr = unsafe { rcl_node_get_logger_name(self.node_handle.as_ref()) };
         if ptr == std::ptr::null() {
@@ -993,7 +3238,133 @@ impl Node {
 struct Timer_ {
     timer_handle: rcl_timer_t,
     _clock: Clock, // just here to be dropped properly later.
+    priority: i32,
     sender: mpsc::Sender<Duration>,
+    errors: EntityErrors,
+}
+
+/// Options for creating timers.
+#[derive(Debug, Clone, Default)]
+pub struct TimerOptions {
+    /// Priority within one spin, see `SubscriptionOptions::priority`.
+    pub priority: i32,
+}
+
+/// Limits the amount of work done by `Node::spin_once_with_budget`.
+///
+/// The default budget is unlimited.
+#[derive(Debug, Clone)]
+pub struct SpinBudget {
+    /// Maximum number of ready entities to handle.
+    pub max_messages: usize,
+    /// Stop handling ready entities after this long.
+    pub max_duration: Duration,
+}
+
+impl Default for SpinBudget {
+    fn default() -> Self {
+        SpinBudget {
+            max_messages: usize::MAX,
+            max_duration: Duration::from_secs(u64::MAX),
+        }
+    }
+}
+
+// Converts and finalizes names and types filled in by rcl.
+fn take_names_and_types(tnat: &mut rmw_names_and_types_t) -> HashMap<String, Vec<String>> {
+    let mut res = HashMap::new();
+    if tnat.names.size > 0 {
+        let names = unsafe { std::slice::from_raw_parts(tnat.names.data, tnat.names.size) };
+        let types = unsafe { std::slice::from_raw_parts(tnat.types, tnat.names.size) };
+        for (n, t) in names.iter().zip(types) {
+            let topic_name = unsafe { CStr::from_ptr(*n).to_string_lossy().into_owned() };
+            // each name has an array of type names.
+            let topic_types = if t.size > 0 {
+                unsafe { std::slice::from_raw_parts(t.data, t.size) }
+            } else {
+                &[]
+            };
+            let topic_types: Vec<String> = topic_types
+                .iter()
+                .map(|t| unsafe { CStr::from_ptr(*t).to_string_lossy().into_owned() })
+                .collect();
+            res.insert(topic_name, topic_types);
+        }
+    }
+    unsafe {
+        rmw_names_and_types_fini(tnat);
+    } // TODO: check return value
+    res
+}
+
+pub(crate) fn publishers_info_by_topic(
+    node: &rcl_node_t,
+    topic: &str,
+) -> Result<Vec<TopicEndpointInfo>> {
+    let topic_c_string = CString::new(topic).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
+    let mut info_array = unsafe { rmw_get_zero_initialized_topic_endpoint_info_array() };
+    let mut allocator = unsafe { rcutils_get_default_allocator() };
+    let ret = unsafe {
+        rcl_get_publishers_info_by_topic(
+            node,
+            &mut allocator,
+            topic_c_string.as_ptr(),
+            false,
+            &mut info_array,
+        )
+    };
+    if ret != RCL_RET_OK as i32 {
+        return Err(Error::from_rcl_error(ret));
+    }
+
+    let infos = if info_array.size == 0 {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(info_array.info_array, info_array.size) }
+    };
+    let to_string = |s: *const std::os::raw::c_char| {
+        if s == std::ptr::null() {
+            String::new()
+        } else {
+            unsafe { CStr::from_ptr(s).to_str().unwrap_or("").to_owned() }
+        }
+    };
+    let res = infos
+        .iter()
+        .map(|i| TopicEndpointInfo {
+            node_name: to_string(i.node_name),
+            node_namespace: to_string(i.node_namespace),
+            topic_type: to_string(i.topic_type),
+            qos: Some(QosProfile::from(&i.qos_profile)).filter(|q| !q.has_unknown_policy()),
+            type_hash: distro::endpoint_type_hash(i).ok(),
+        })
+        .collect();
+    unsafe {
+        rmw_topic_endpoint_info_array_fini(&mut info_array, &mut allocator);
+    } // TODO: check return value
+    Ok(res)
+}
+
+/// Information about a publisher or subscription of a topic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopicEndpointInfo {
+    pub node_name: String,
+    pub node_namespace: String,
+    pub topic_type: String,
+    /// None if the endpoint uses QoS policies unknown to r2r.
+    pub qos: Option<QosProfile>,
+    /// The hash of the type definition, e.g. "RIHS01_1b2c...". None
+    /// before Iron.
+    pub type_hash: Option<String>,
+}
+
+// Indices of the ready entities in the order they should be handled:
+// highest priority first, keeping the original order for equal priorities.
+fn dispatch_order(priorities: &[i32]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..priorities.len()).collect();
+    // sort_by_key is stable.
+    order.sort_by_key(|i| std::cmp::Reverse(priorities[*i]));
+    order
 }
 
 impl Timer_ {
@@ -1016,7 +3387,12 @@ impl Timer_ {
                                     return true;
                                 }
                                 if e.is_full() {
-                                    println!("Warning: timer tick not handled in time - no wakeup will occur");
+                                    self.errors.report(
+                                        SpinOperation::Deliver,
+                                        Error::DeliveryFailed {
+                                            reason: "timer tick not handled in time - no wakeup will occur".into(),
+                                        },
+                                    );
                                 }
                             }
                             _ => {} // ok
@@ -1054,6 +3430,37 @@ impl Timer {
     }
 }
 
+impl Entity for Timer {
+    /// Timers have no name, this is always empty.
+    fn name(&self) -> Result<String> {
+        Ok(String::new())
+    }
+
+    fn kind(&self) -> EntityKind {
+        EntityKind::Timer
+    }
+}
+
+impl Entity for Node {
+    fn name(&self) -> Result<String> {
+        self.fully_qualified_name()
+    }
+
+    fn kind(&self) -> EntityKind {
+        EntityKind::Node
+    }
+}
+
+// Subscriptions are recreated in place by the resubscribe watchdog,
+// so they are told apart by where they live rather than by their handle.
+fn subscriber_address(s: &Box<dyn Subscriber_>) -> usize {
+    &**s as *const dyn Subscriber_ as *const () as usize
+}
+
+fn publisher_address(p: &Arc<rcl_publisher_t>) -> usize {
+    Arc::as_ptr(p) as usize
+}
+
 // Since publishers are temporarily upgraded to owners during the
 // actual publish but are not the ones that handle cleanup, we simply
 // wait until there are no other owners in the cleanup procedure. The
@@ -1068,8 +3475,41 @@ fn wait_until_unwrapped<T>(mut a: Arc<T>) -> T {
     }
 }
 
+// Like wait_until_unwrapped, but gives up at `deadline`.
+fn unwrap_until<T>(mut a: Arc<T>, deadline: Instant) -> std::result::Result<T, Arc<T>> {
+    loop {
+        match Arc::try_unwrap(a) {
+            Ok(b) => return Ok(b),
+            Err(t) if Instant::now() >= deadline => return Err(t),
+            Err(t) => a = t,
+        }
+        std::thread::yield_now();
+    }
+}
+
+// Locks `m`, giving up at `deadline`. Without a deadline, we wait.
+fn lock_until<T: ?Sized>(m: &Mutex<T>, deadline: Option<Instant>) -> Option<MutexGuard<'_, T>> {
+    let deadline = match deadline {
+        Some(deadline) => deadline,
+        None => return Some(m.lock().unwrap()),
+    };
+    loop {
+        match m.try_lock() {
+            Ok(guard) => return Some(guard),
+            Err(TryLockError::Poisoned(e)) => return Some(e.into_inner()),
+            Err(TryLockError::WouldBlock) if Instant::now() >= deadline => return None,
+            Err(TryLockError::WouldBlock) => std::thread::yield_now(),
+        }
+    }
+}
+
 impl Drop for Node {
     fn drop(&mut self) {
+        // hooks that shutdown has not run yet.
+        for e in self.run_shutdown_hooks() {
+            log_internal(LogSeverity::Warn, &format!("shutdown hook failed: {}", e));
+        }
+
         // fini functions are not thread safe so lock the context.
         let _ctx_handle = self.context.context_handle.lock().unwrap();
 
@@ -1085,6 +3525,8 @@ impl Drop for Node {
         for s in &mut self.action_servers {
             s.lock().unwrap().destroy(&mut self.node_handle);
         }
+        // the matched events go before their publishers.
+        self.retained_publishers.clear();
         while let Some(p) = self.pubs.pop() {
             let mut p = wait_until_unwrapped(p);
             let _ret = unsafe { rcl_publisher_fini(&mut p as *mut _, self.node_handle.as_mut()) };
@@ -1097,5 +3539,18 @@ impl Drop for Node {
 }
 
 pub trait IsAvailablePollable {
-    fn register_poll_available(&self, sender: oneshot::Sender<()>) -> Result<()>;
+    fn register_poll_available(&self, sender: oneshot::Sender<Result<()>>) -> Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dispatch_order() -> () {
+        assert_eq!(dispatch_order(&[]), Vec::<usize>::new());
+        assert_eq!(dispatch_order(&[0, 0, 0]), vec![0, 1, 2]);
+        // e.g. a /clock subscription registered last but with higher priority.
+        assert_eq!(dispatch_order(&[0, -1, 0, 10]), vec![3, 0, 2, 1]);
+    }
 }
@@ -0,0 +1,982 @@
+//! Structs assembled from and written to namespaced parameters.
+//!
+//! A struct deserialized from the prefix `controller.pid` takes its
+//! field `p` from the parameter `controller.pid.p`, nested structs
+//! (and maps) from deeper names, `Vec` fields from array parameters
+//! and `Option` fields are `None` when the parameter is missing or not
+//! set. Unit enum variants are read from and written as strings.
+
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use serde::ser::{self, Serialize};
+use std::collections::{btree_map, BTreeMap, HashMap};
+use std::fmt;
+
+use crate::error::*;
+use crate::parameters::{restore_type, ParameterValue};
+
+/// Assembles a `T` from the parameters below `prefix`. An empty
+/// prefix uses all parameters.
+pub fn from_parameters<T>(params: &HashMap<String, ParameterValue>, prefix: &str) -> Result<T>
+where
+    T: de::DeserializeOwned,
+{
+    let deserializer = ParamDeserializer {
+        tree: build_tree(params, prefix),
+        path: prefix.to_owned(),
+    };
+    T::deserialize(deserializer).map_err(|e| e.into_error(prefix))
+}
+
+/// The parameters `value` consists of, named below `prefix`. `None`
+/// fields become parameters that are not set.
+pub fn to_parameters<T>(value: &T, prefix: &str) -> Result<Vec<(String, ParameterValue)>>
+where
+    T: Serialize + ?Sized,
+{
+    let mut out = vec![];
+    value
+        .serialize(ParamSerializer {
+            out: &mut out,
+            path: prefix.to_owned(),
+        })
+        .map_err(|e| e.into_error(prefix))?;
+    Ok(out)
+}
+
+/// Keeps the type of the current value where the serialized value
+/// cannot tell, i.e. for byte arrays and empty arrays.
+pub(crate) fn keep_type(current: Option<&ParameterValue>, new: ParameterValue) -> ParameterValue {
+    let empty = match &new {
+        ParameterValue::IntegerArray(v) => v.is_empty(),
+        _ => false,
+    };
+    match current {
+        Some(ParameterValue::BoolArray(_)) if empty => ParameterValue::BoolArray(vec![]),
+        Some(ParameterValue::ByteArray(_)) if empty => ParameterValue::ByteArray(vec![]),
+        Some(ParameterValue::DoubleArray(_)) if empty => ParameterValue::DoubleArray(vec![]),
+        Some(ParameterValue::StringArray(_)) if empty => ParameterValue::StringArray(vec![]),
+        _ => restore_type(current, new),
+    }
+}
+
+fn join(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{}.{}", prefix, name)
+    }
+}
+
+#[derive(Debug)]
+struct ParamError {
+    // the full name of the parameter, once known.
+    path: Option<String>,
+    missing_field: Option<&'static str>,
+    msg: String,
+}
+
+impl ParamError {
+    fn new(msg: impl Into<String>) -> Self {
+        ParamError {
+            path: None,
+            missing_field: None,
+            msg: msg.into(),
+        }
+    }
+
+    // Errors are created without knowing where they happened, the
+    // innermost (de)serializer names the parameter.
+    fn at(mut self, path: &str) -> Self {
+        if self.path.is_none() {
+            self.path = Some(match self.missing_field {
+                Some(field) => join(path, field),
+                None => path.to_owned(),
+            });
+        }
+        self
+    }
+
+    fn into_error(self, prefix: &str) -> Error {
+        Error::InvalidParameter {
+            name: self.path.unwrap_or_else(|| prefix.to_owned()),
+            reason: self.msg,
+        }
+    }
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}: {}", path, self.msg),
+            None => write!(f, "{}", self.msg),
+        }
+    }
+}
+
+impl std::error::Error for ParamError {}
+
+impl de::Error for ParamError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ParamError::new(msg.to_string())
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        ParamError {
+            missing_field: Some(field),
+            ..ParamError::new("is not set")
+        }
+    }
+}
+
+impl ser::Error for ParamError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ParamError::new(msg.to_string())
+    }
+}
+
+type ParamResult<T> = std::result::Result<T, ParamError>;
+
+// The parameters below a prefix, nested by the dots in their names.
+#[derive(Debug, PartialEq)]
+enum Tree {
+    Value(ParameterValue),
+    Map(BTreeMap<String, Tree>),
+}
+
+// Collects the parameters before they are turned into a `Tree`.
+#[derive(Default)]
+struct TreeBuilder {
+    value: Option<ParameterValue>,
+    children: BTreeMap<String, TreeBuilder>,
+}
+
+impl TreeBuilder {
+    // A parameter with parameters below it is read as a map.
+    fn build(self) -> Tree {
+        if self.children.is_empty() {
+            Tree::Value(self.value.unwrap_or(ParameterValue::NotSet))
+        } else {
+            Tree::Map(
+                self.children
+                    .into_iter()
+                    .map(|(name, child)| (name, child.build()))
+                    .collect(),
+            )
+        }
+    }
+}
+
+fn build_tree(params: &HashMap<String, ParameterValue>, prefix: &str) -> Option<Tree> {
+    let mut root = TreeBuilder::default();
+    let mut found = false;
+    for (name, value) in params {
+        let relative = if prefix.is_empty() {
+            name.as_str()
+        } else if name == prefix {
+            ""
+        } else if name.starts_with(prefix) && name[prefix.len()..].starts_with('.') {
+            &name[prefix.len() + 1..]
+        } else {
+            continue;
+        };
+        let mut t = &mut root;
+        if !relative.is_empty() {
+            for part in relative.split('.') {
+                t = t.children.entry(part.to_owned()).or_default();
+            }
+        }
+        t.value = Some(value.clone());
+        found = true;
+    }
+    if found {
+        Some(root.build())
+    } else {
+        None
+    }
+}
+
+struct ParamDeserializer {
+    tree: Option<Tree>,
+    path: String,
+}
+
+fn visit_value<'de, V: Visitor<'de>>(
+    value: ParameterValue,
+    path: &str,
+    visitor: V,
+) -> ParamResult<V::Value> {
+    let array = |items: Vec<ParameterValue>| ArrayAccess {
+        items: items.into_iter(),
+        path: path.to_owned(),
+        index: 0,
+    };
+    match value {
+        ParameterValue::NotSet => Err(ParamError::new("is not set")),
+        ParameterValue::Bool(b) => visitor.visit_bool(b),
+        ParameterValue::Integer(i) => visitor.visit_i64(i),
+        ParameterValue::Double(d) => visitor.visit_f64(d),
+        ParameterValue::String(s) => visitor.visit_string(s),
+        ParameterValue::BoolArray(v) => {
+            visitor.visit_seq(array(v.into_iter().map(ParameterValue::Bool).collect()))
+        }
+        ParameterValue::ByteArray(v) => visitor.visit_seq(array(
+            v.into_iter()
+                .map(|b| ParameterValue::Integer(b as i64))
+                .collect(),
+        )),
+        ParameterValue::IntegerArray(v) => {
+            visitor.visit_seq(array(v.into_iter().map(ParameterValue::Integer).collect()))
+        }
+        ParameterValue::DoubleArray(v) => {
+            visitor.visit_seq(array(v.into_iter().map(ParameterValue::Double).collect()))
+        }
+        ParameterValue::StringArray(v) => {
+            visitor.visit_seq(array(v.into_iter().map(ParameterValue::String).collect()))
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for ParamDeserializer {
+    type Error = ParamError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> ParamResult<V::Value> {
+        let path = self.path;
+        let result = match self.tree {
+            None => Err(ParamError::new("is not set")),
+            Some(Tree::Value(value)) => visit_value(value, &path, visitor),
+            Some(Tree::Map(map)) => visitor.visit_map(MapAccess::new(map, &path)),
+        };
+        result.map_err(|e| e.at(&path))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> ParamResult<V::Value> {
+        match self.tree {
+            None | Some(Tree::Value(ParameterValue::NotSet)) => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> ParamResult<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> ParamResult<V::Value> {
+        match self.tree {
+            // all fields are missing, which is fine if they are optional.
+            None => {
+                let path = self.path;
+                visitor
+                    .visit_map(MapAccess::new(BTreeMap::new(), &path))
+                    .map_err(|e| e.at(&path))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> ParamResult<V::Value> {
+        match self.tree {
+            Some(Tree::Value(ParameterValue::String(variant))) => {
+                let path = self.path;
+                visitor
+                    .visit_enum(variant.into_deserializer())
+                    .map_err(|e: ParamError| e.at(&path))
+            }
+            _ => Err(ParamError::new("expected a string naming a variant").at(&self.path)),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> ParamResult<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> ParamResult<V::Value> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
+        byte_buf unit unit_struct seq tuple tuple_struct identifier
+    }
+}
+
+struct ArrayAccess {
+    items: std::vec::IntoIter<ParameterValue>,
+    path: String,
+    index: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for ArrayAccess {
+    type Error = ParamError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> ParamResult<Option<T::Value>> {
+        let item = match self.items.next() {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+        let path = format!("{}[{}]", self.path, self.index);
+        self.index += 1;
+        seed.deserialize(ParamDeserializer {
+            tree: Some(Tree::Value(item)),
+            path,
+        })
+        .map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct MapAccess {
+    entries: btree_map::IntoIter<String, Tree>,
+    value: Option<(String, Tree)>,
+    path: String,
+}
+
+impl MapAccess {
+    fn new(map: BTreeMap<String, Tree>, path: &str) -> Self {
+        MapAccess {
+            entries: map.into_iter(),
+            value: None,
+            path: path.to_owned(),
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess {
+    type Error = ParamError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> ParamResult<Option<K::Value>> {
+        let (key, value) = match self.entries.next() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let deserializer: de::value::StringDeserializer<ParamError> =
+            key.clone().into_deserializer();
+        self.value = Some((key, value));
+        seed.deserialize(deserializer).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> ParamResult<V::Value> {
+        let (key, value) = self
+            .value
+            .take()
+            .ok_or_else(|| ParamError::new("value requested before key"))?;
+        seed.deserialize(ParamDeserializer {
+            tree: Some(value),
+            path: join(&self.path, &key),
+        })
+    }
+}
+
+struct ParamSerializer<'a> {
+    out: &'a mut Vec<(String, ParameterValue)>,
+    path: String,
+}
+
+impl<'a> ParamSerializer<'a> {
+    fn push(self, value: ParameterValue) -> ParamResult<()> {
+        self.out.push((self.path, value));
+        Ok(())
+    }
+
+    fn unsupported(&self, what: &str) -> ParamError {
+        ParamError::new(format!("{} cannot be stored as parameters", what)).at(&self.path)
+    }
+}
+
+impl<'a> ser::Serializer for ParamSerializer<'a> {
+    type Ok = ();
+    type Error = ParamError;
+    type SerializeSeq = ArraySerializer<'a>;
+    type SerializeTuple = ArraySerializer<'a>;
+    type SerializeTupleStruct = ArraySerializer<'a>;
+    type SerializeTupleVariant = ser::Impossible<(), ParamError>;
+    type SerializeMap = MapSerializer<'a>;
+    type SerializeStruct = MapSerializer<'a>;
+    type SerializeStructVariant = ser::Impossible<(), ParamError>;
+
+    fn serialize_bool(self, v: bool) -> ParamResult<()> {
+        self.push(ParameterValue::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> ParamResult<()> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> ParamResult<()> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> ParamResult<()> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> ParamResult<()> {
+        self.push(ParameterValue::Integer(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> ParamResult<()> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u16(self, v: u16) -> ParamResult<()> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u32(self, v: u32) -> ParamResult<()> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u64(self, v: u64) -> ParamResult<()> {
+        if v > i64::MAX as u64 {
+            return Err(self.unsupported(&format!("{} (too large)", v)));
+        }
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_f32(self, v: f32) -> ParamResult<()> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> ParamResult<()> {
+        self.push(ParameterValue::Double(v))
+    }
+
+    fn serialize_char(self, v: char) -> ParamResult<()> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> ParamResult<()> {
+        self.push(ParameterValue::String(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> ParamResult<()> {
+        self.push(ParameterValue::ByteArray(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> ParamResult<()> {
+        self.push(ParameterValue::NotSet)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> ParamResult<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> ParamResult<()> {
+        self.push(ParameterValue::NotSet)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> ParamResult<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> ParamResult<()> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> ParamResult<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _value: &T,
+    ) -> ParamResult<()> {
+        Err(self.unsupported(&format!("variant {} with data", variant)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> ParamResult<ArraySerializer<'a>> {
+        Ok(ArraySerializer {
+            out: self.out,
+            path: self.path,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> ParamResult<ArraySerializer<'a>> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> ParamResult<ArraySerializer<'a>> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> ParamResult<Self::SerializeTupleVariant> {
+        Err(self.unsupported(&format!("variant {} with data", variant)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> ParamResult<MapSerializer<'a>> {
+        Ok(MapSerializer {
+            out: self.out,
+            path: self.path,
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> ParamResult<MapSerializer<'a>> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> ParamResult<Self::SerializeStructVariant> {
+        Err(self.unsupported(&format!("variant {} with data", variant)))
+    }
+}
+
+struct ArraySerializer<'a> {
+    out: &'a mut Vec<(String, ParameterValue)>,
+    path: String,
+    items: Vec<ParameterValue>,
+}
+
+impl<'a> ArraySerializer<'a> {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> ParamResult<()> {
+        let path = format!("{}[{}]", self.path, self.items.len());
+        let mut out = vec![];
+        value.serialize(ParamSerializer {
+            out: &mut out,
+            path: path.clone(),
+        })?;
+        match out.pop() {
+            Some((p, item)) if out.is_empty() && p == path && is_scalar(&item) => {
+                self.items.push(item);
+                Ok(())
+            }
+            _ => Err(ParamError::new("arrays can only hold bools, numbers or strings").at(&path)),
+        }
+    }
+
+    fn finish(self) -> ParamResult<()> {
+        let value = array_value(self.items).ok_or_else(|| {
+            ParamError::new("array elements must all have the same type").at(&self.path)
+        })?;
+        self.out.push((self.path, value));
+        Ok(())
+    }
+}
+
+fn is_scalar(value: &ParameterValue) -> bool {
+    matches!(
+        value,
+        ParameterValue::Bool(_)
+            | ParameterValue::Integer(_)
+            | ParameterValue::Double(_)
+            | ParameterValue::String(_)
+    )
+}
+
+// The array parameter holding the items, which may mix integers and
+// doubles. Empty arrays become integer arrays.
+fn array_value(items: Vec<ParameterValue>) -> Option<ParameterValue> {
+    let all = |f: fn(&ParameterValue) -> bool| items.iter().all(f);
+    if all(|v| matches!(v, ParameterValue::Integer(_))) {
+        Some(ParameterValue::IntegerArray(
+            items
+                .into_iter()
+                .filter_map(|v| match v {
+                    ParameterValue::Integer(i) => Some(i),
+                    _ => None,
+                })
+                .collect(),
+        ))
+    } else if all(|v| matches!(v, ParameterValue::Integer(_) | ParameterValue::Double(_))) {
+        Some(ParameterValue::DoubleArray(
+            items
+                .into_iter()
+                .filter_map(|v| match v {
+                    ParameterValue::Integer(i) => Some(i as f64),
+                    ParameterValue::Double(d) => Some(d),
+                    _ => None,
+                })
+                .collect(),
+        ))
+    } else if all(|v| matches!(v, ParameterValue::Bool(_))) {
+        Some(ParameterValue::BoolArray(
+            items
+                .into_iter()
+                .filter_map(|v| match v {
+                    ParameterValue::Bool(b) => Some(b),
+                    _ => None,
+                })
+                .collect(),
+        ))
+    } else if all(|v| matches!(v, ParameterValue::String(_))) {
+        Some(ParameterValue::StringArray(
+            items
+                .into_iter()
+                .filter_map(|v| match v {
+                    ParameterValue::String(s) => Some(s),
+                    _ => None,
+                })
+                .collect(),
+        ))
+    } else {
+        None
+    }
+}
+
+impl<'a> ser::SerializeSeq for ArraySerializer<'a> {
+    type Ok = ();
+    type Error = ParamError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> ParamResult<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> ParamResult<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTuple for ArraySerializer<'a> {
+    type Ok = ();
+    type Error = ParamError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> ParamResult<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> ParamResult<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for ArraySerializer<'a> {
+    type Ok = ();
+    type Error = ParamError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> ParamResult<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> ParamResult<()> {
+        self.finish()
+    }
+}
+
+struct MapSerializer<'a> {
+    out: &'a mut Vec<(String, ParameterValue)>,
+    path: String,
+    key: Option<String>,
+}
+
+impl<'a> MapSerializer<'a> {
+    fn field<T: Serialize + ?Sized>(&mut self, name: &str, value: &T) -> ParamResult<()> {
+        if name.is_empty() || name.contains('.') {
+            return Err(
+                ParamError::new(format!("invalid parameter name {:?}", name)).at(&self.path),
+            );
+        }
+        value.serialize(ParamSerializer {
+            out: self.out,
+            path: join(&self.path, name),
+        })
+    }
+}
+
+impl<'a> ser::SerializeMap for MapSerializer<'a> {
+    type Ok = ();
+    type Error = ParamError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> ParamResult<()> {
+        let key = match serde_json::to_value(key) {
+            Ok(serde_json::Value::String(key)) => key,
+            _ => return Err(ParamError::new("map keys must be strings").at(&self.path)),
+        };
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> ParamResult<()> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ParamError::new("value serialized before key"))?;
+        self.field(&key, value)
+    }
+
+    fn end(self) -> ParamResult<()> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeStruct for MapSerializer<'a> {
+    type Ok = ();
+    type Error = ParamError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        name: &'static str,
+        value: &T,
+    ) -> ParamResult<()> {
+        self.field(name, value)
+    }
+
+    fn end(self) -> ParamResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Pid {
+        p: f64,
+        i: f64,
+        d: f64,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    enum Mode {
+        Position,
+        Velocity,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Controller {
+        pid: Pid,
+        joints: Vec<String>,
+        limits: Option<Vec<f64>>,
+        rate: u32,
+        mode: Mode,
+        name: Option<String>,
+        gains: HashMap<String, f64>,
+    }
+
+    fn params(values: Vec<(&str, ParameterValue)>) -> HashMap<String, ParameterValue> {
+        values
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), value))
+            .collect()
+    }
+
+    fn controller_params() -> HashMap<String, ParameterValue> {
+        params(vec![
+            ("controller.pid.p", ParameterValue::Double(1.5)),
+            ("controller.pid.i", ParameterValue::Integer(0)),
+            ("controller.pid.d", ParameterValue::Double(0.1)),
+            (
+                "controller.joints",
+                ParameterValue::StringArray(vec!["a".into(), "b".into()]),
+            ),
+            ("controller.rate", ParameterValue::Integer(100)),
+            ("controller.mode", ParameterValue::String("velocity".into())),
+            ("controller.name", ParameterValue::NotSet),
+            ("controller.gains.x", ParameterValue::Double(2.0)),
+            ("controllers.other", ParameterValue::Bool(true)),
+        ])
+    }
+
+    fn invalid_parameter<T: std::fmt::Debug>(result: Result<T>) -> String {
+        match result {
+            Err(Error::InvalidParameter { name, .. }) => name,
+            r => panic!("expected an invalid parameter, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_struct_from_parameters() {
+        let controller: Controller = from_parameters(&controller_params(), "controller").unwrap();
+        assert_eq!(
+            controller.pid,
+            Pid {
+                p: 1.5,
+                i: 0.0,
+                d: 0.1
+            }
+        );
+        assert_eq!(controller.joints, vec!["a", "b"]);
+        assert_eq!(controller.limits, None);
+        assert_eq!(controller.rate, 100);
+        assert_eq!(controller.mode, Mode::Velocity);
+        assert_eq!(controller.name, None);
+        assert_eq!(controller.gains["x"], 2.0);
+
+        let pid: Pid = from_parameters(&controller_params(), "controller.pid").unwrap();
+        assert_eq!(pid, controller.pid);
+        let rate: u32 = from_parameters(&controller_params(), "controller.rate").unwrap();
+        assert_eq!(rate, 100);
+        let bytes: Vec<u8> = from_parameters(
+            &params(vec![("b", ParameterValue::ByteArray(vec![1, 2]))]),
+            "b",
+        )
+        .unwrap();
+        assert_eq!(bytes, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_errors_name_the_parameter() {
+        let mut wrong_type = controller_params();
+        wrong_type.insert(
+            "controller.pid.d".into(),
+            ParameterValue::String("x".into()),
+        );
+        let result = from_parameters::<Controller>(&wrong_type, "controller");
+        assert_eq!(invalid_parameter(result), "controller.pid.d");
+
+        let mut missing = controller_params();
+        missing.remove("controller.pid.i");
+        let result = from_parameters::<Controller>(&missing, "controller");
+        assert_eq!(invalid_parameter(result), "controller.pid.i");
+
+        let mut out_of_range = controller_params();
+        out_of_range.insert("controller.rate".into(), ParameterValue::Integer(-1));
+        let result = from_parameters::<Controller>(&out_of_range, "controller");
+        assert_eq!(invalid_parameter(result), "controller.rate");
+
+        let mut wrong_element = controller_params();
+        wrong_element.insert(
+            "controller.limits".into(),
+            ParameterValue::StringArray(vec!["1.0".into()]),
+        );
+        let result = from_parameters::<Controller>(&wrong_element, "controller");
+        assert_eq!(invalid_parameter(result), "controller.limits[0]");
+
+        let mut unknown_variant = controller_params();
+        unknown_variant.insert(
+            "controller.mode".into(),
+            ParameterValue::String("torque".into()),
+        );
+        let result = from_parameters::<Controller>(&unknown_variant, "controller");
+        assert_eq!(invalid_parameter(result), "controller.mode");
+
+        let result = from_parameters::<Pid>(&controller_params(), "nothing.here");
+        assert_eq!(invalid_parameter(result), "nothing.here.p");
+    }
+
+    #[test]
+    fn test_all_optional_fields_missing() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Optional {
+            a: Option<i64>,
+            b: Option<Vec<bool>>,
+        }
+        let optional: Optional = from_parameters(&HashMap::new(), "opt").unwrap();
+        assert_eq!(optional, Optional { a: None, b: None });
+    }
+
+    #[test]
+    fn test_struct_to_parameters_and_back() {
+        let controller: Controller = from_parameters(&controller_params(), "controller").unwrap();
+        let values = to_parameters(&controller, "ns.controller").unwrap();
+        let find = |name: &str| {
+            values
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.clone())
+                .unwrap()
+        };
+        assert_eq!(find("ns.controller.pid.p"), ParameterValue::Double(1.5));
+        assert_eq!(find("ns.controller.rate"), ParameterValue::Integer(100));
+        assert_eq!(
+            find("ns.controller.mode"),
+            ParameterValue::String("velocity".into())
+        );
+        assert_eq!(find("ns.controller.limits"), ParameterValue::NotSet);
+        assert_eq!(find("ns.controller.gains.x"), ParameterValue::Double(2.0));
+
+        let params = values.into_iter().collect::<HashMap<_, _>>();
+        let read: Controller = from_parameters(&params, "ns.controller").unwrap();
+        assert_eq!(read, controller);
+    }
+
+    #[test]
+    fn test_arrays_to_parameters() {
+        #[derive(Serialize)]
+        struct Arrays {
+            mixed: Vec<f64>,
+            ints: (i32, i32),
+            empty: Vec<String>,
+        }
+        let values = to_parameters(
+            &Arrays {
+                mixed: vec![1.0, 2.5],
+                ints: (1, 2),
+                empty: vec![],
+            },
+            "",
+        )
+        .unwrap();
+        assert_eq!(
+            values,
+            vec![
+                (
+                    "mixed".to_owned(),
+                    ParameterValue::DoubleArray(vec![1.0, 2.5])
+                ),
+                ("ints".to_owned(), ParameterValue::IntegerArray(vec![1, 2])),
+                ("empty".to_owned(), ParameterValue::IntegerArray(vec![])),
+            ]
+        );
+        let empty = ParameterValue::IntegerArray(vec![]);
+        let current = ParameterValue::StringArray(vec!["a".into()]);
+        assert_eq!(
+            keep_type(Some(&current), empty),
+            ParameterValue::StringArray(vec![])
+        );
+
+        #[derive(Serialize)]
+        struct Nested {
+            pids: Vec<Pid>,
+        }
+        let nested = Nested {
+            pids: vec![Pid {
+                p: 1.0,
+                i: 0.0,
+                d: 0.0,
+            }],
+        };
+        let result = to_parameters(&nested, "c");
+        assert_eq!(invalid_parameter(result), "c.pids[0]");
+    }
+}
@@ -1,5 +1,9 @@
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::CStr;
+use std::fmt::Write;
 
+use crate::error::*;
+use crate::log_sinks::{configure_log_sinks, LogSinkConfig, LOG_SINK_PARAMETER_PREFIX};
 use crate::msg_types::generated_msgs::rcl_interfaces;
 use r2r_rcl::*;
 
@@ -143,3 +147,293 @@ impl ParameterValue {
         ret
     }
 }
+
+#[derive(Default)]
+struct YamlTree<'a> {
+    value: Option<&'a ParameterValue>,
+    children: BTreeMap<&'a str, YamlTree<'a>>,
+}
+
+fn yaml_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn yaml_double(d: f64) -> String {
+    if d.is_nan() {
+        ".nan".to_owned()
+    } else if d == f64::INFINITY {
+        ".inf".to_owned()
+    } else if d == f64::NEG_INFINITY {
+        "-.inf".to_owned()
+    } else {
+        // debug formatting always includes a decimal point or an
+        // exponent, so the value is not read back as an integer.
+        format!("{:?}", d)
+    }
+}
+
+fn yaml_array<T, F: Fn(&T) -> String>(vals: &[T], f: F) -> String {
+    let items: Vec<String> = vals.iter().map(f).collect();
+    format!("[{}]", items.join(", "))
+}
+
+/// Returns None for values that cannot be written as typed yaml.
+fn yaml_value(v: &ParameterValue) -> Option<String> {
+    match v {
+        ParameterValue::NotSet => None,
+        ParameterValue::Bool(b) => Some(b.to_string()),
+        ParameterValue::Integer(i) => Some(i.to_string()),
+        ParameterValue::Double(d) => Some(yaml_double(*d)),
+        ParameterValue::String(s) => Some(yaml_string(s)),
+        // an empty sequence has no type.
+        ParameterValue::BoolArray(v) if v.is_empty() => None,
+        ParameterValue::ByteArray(v) if v.is_empty() => None,
+        ParameterValue::IntegerArray(v) if v.is_empty() => None,
+        ParameterValue::DoubleArray(v) if v.is_empty() => None,
+        ParameterValue::StringArray(v) if v.is_empty() => None,
+        ParameterValue::BoolArray(v) => Some(yaml_array(v, |b| b.to_string())),
+        ParameterValue::ByteArray(v) => Some(yaml_array(v, |b| b.to_string())),
+        ParameterValue::IntegerArray(v) => Some(yaml_array(v, |i| i.to_string())),
+        ParameterValue::DoubleArray(v) => Some(yaml_array(v, |d| yaml_double(*d))),
+        ParameterValue::StringArray(v) => Some(yaml_array(v, |s| yaml_string(s))),
+    }
+}
+
+fn write_yaml_level(out: &mut String, level: &BTreeMap<&str, YamlTree>, indent: usize, prefix: &str) {
+    for (k, t) in level {
+        let key = format!("{}{}", prefix, k);
+        match t.value.and_then(yaml_value) {
+            Some(v) => {
+                let _ = writeln!(out, "{:indent$}{}: {}", "", key, v, indent = indent);
+                // a parameter which is also a prefix of other
+                // parameters, write the others with dotted keys.
+                write_yaml_level(out, &t.children, indent, &format!("{}.", key));
+            }
+            None if !t.children.is_empty() => {
+                let _ = writeln!(out, "{:indent$}{}:", "", key, indent = indent);
+                write_yaml_level(out, &t.children, indent + 2, "");
+            }
+            None => {}
+        }
+    }
+}
+
+/// Writes parameters in the ROS 2 parameter file format.
+///
+/// Parameter names are nested by their dots. Parameters that are not
+/// set and empty arrays are skipped, as their type cannot be
+/// expressed. Byte arrays are written as integer arrays, which is
+/// what the parameter file parser reads them back as.
+pub(crate) fn parameters_to_yaml(
+    node_name: &str,
+    params: &HashMap<String, ParameterValue>,
+) -> String {
+    let mut root = YamlTree::default();
+    for (name, value) in params {
+        let mut t = &mut root;
+        for part in name.split('.') {
+            t = t.children.entry(part).or_default();
+        }
+        t.value = Some(value);
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{}:", node_name);
+    let _ = writeln!(out, "  ros__parameters:");
+    write_yaml_level(&mut out, &root.children, 4, "");
+    out
+}
+
+/// Sets a parameter the way a `set_parameters` request does, where
+/// log sink parameters are applied first and rejected if they are
+/// invalid. Returns whether the value changed.
+pub(crate) fn set_parameter(
+    params: &mut HashMap<String, ParameterValue>,
+    name: &str,
+    value: ParameterValue,
+) -> Result<bool> {
+    let changed = set_parameters(params, vec![(name.to_owned(), value)])?;
+    Ok(!changed.is_empty())
+}
+
+/// Sets a batch of parameters, either all of them or none. The whole
+/// batch is validated before anything is applied, so a rejected value
+/// leaves both the parameters and the log sinks as they were. Returns
+/// the parameters that changed, in the order they were given.
+pub(crate) fn set_parameters(
+    params: &mut HashMap<String, ParameterValue>,
+    values: Vec<(String, ParameterValue)>,
+) -> Result<Vec<(String, ParameterValue)>> {
+    let mut updated = params.clone();
+    let mut names: Vec<String> = Vec::new();
+    for (name, value) in values {
+        if !names.contains(&name) {
+            names.push(name.clone());
+        }
+        updated.insert(name, value);
+    }
+    let changed: Vec<_> = names
+        .into_iter()
+        .filter(|name| params.get(name) != updated.get(name))
+        .map(|name| {
+            let value = updated[&name].clone();
+            (name, value)
+        })
+        .collect();
+
+    // reconfiguring is all or nothing too, a file sink that cannot be
+    // opened rejects the batch.
+    if changed
+        .iter()
+        .any(|(name, _)| name.starts_with(LOG_SINK_PARAMETER_PREFIX))
+    {
+        configure_log_sinks(&LogSinkConfig::from_parameters(&updated)?)?;
+    }
+    *params = updated;
+    Ok(changed)
+}
+
+/// Makes values read back from a parameter file keep the type of the
+/// current value where the file format cannot express it.
+pub(crate) fn restore_type(current: Option<&ParameterValue>, new: ParameterValue) -> ParameterValue {
+    match (current, new) {
+        (Some(ParameterValue::ByteArray(_)), ParameterValue::IntegerArray(v))
+            if v.iter().all(|i| *i >= 0 && *i <= u8::MAX as i64) =>
+        {
+            ParameterValue::ByteArray(v.into_iter().map(|i| i as u8).collect())
+        }
+        (_, new) => new,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Context, Node};
+    use futures::{FutureExt, StreamExt};
+
+    fn all_values() -> HashMap<String, ParameterValue> {
+        let mut params = HashMap::new();
+        params.insert("bool".to_owned(), ParameterValue::Bool(true));
+        params.insert("nested.int".to_owned(), ParameterValue::Integer(-42));
+        params.insert("nested.deeper.double".to_owned(), ParameterValue::Double(3.0));
+        params.insert("nested.string".to_owned(), ParameterValue::String("say \"hi\"\n".into()));
+        params.insert("number_string".to_owned(), ParameterValue::String("12".into()));
+        params.insert("bools".to_owned(), ParameterValue::BoolArray(vec![true, false]));
+        params.insert("bytes".to_owned(), ParameterValue::ByteArray(vec![0, 127, 255]));
+        params.insert("ints".to_owned(), ParameterValue::IntegerArray(vec![1, -2, 3]));
+        params.insert(
+            "doubles".to_owned(),
+            ParameterValue::DoubleArray(vec![1.0, 2.5e-10, f64::INFINITY, f64::NEG_INFINITY]),
+        );
+        params.insert("nan".to_owned(), ParameterValue::Double(f64::NAN));
+        params.insert("strings".to_owned(), ParameterValue::StringArray(vec!["a".into(), "true".into()]));
+        params
+    }
+
+    #[test]
+    fn test_parameters_to_yaml() -> () {
+        let mut params = HashMap::new();
+        params.insert("a.b".to_owned(), ParameterValue::Integer(1));
+        params.insert("a.c".to_owned(), ParameterValue::Double(1.0));
+        params.insert("d".to_owned(), ParameterValue::String("x".into()));
+        params.insert("e".to_owned(), ParameterValue::NotSet);
+        let yaml = parameters_to_yaml("/ns/node", &params);
+        assert_eq!(
+            yaml,
+            "/ns/node:\n  ros__parameters:\n    a:\n      b: 1\n      c: 1.0\n    d: \"x\"\n"
+        );
+    }
+
+    #[test]
+    fn test_dump_restore_parameters() -> () {
+        let ctx = Context::create().unwrap();
+        let node = Node::create(ctx.clone(), "dump_params_node", "").unwrap();
+        node.params.lock().unwrap().extend(all_values());
+        let yaml = node.dump_parameters().unwrap();
+
+        let mut node2 = Node::create(ctx, "dump_params_node", "").unwrap();
+        // byte arrays are read back as integers unless the parameter exists.
+        node2
+            .params
+            .lock()
+            .unwrap()
+            .insert("bytes".to_owned(), ParameterValue::ByteArray(vec![]));
+        node2.restore_parameters(&yaml).unwrap();
+
+        let params = node2.params.lock().unwrap();
+        for (k, v) in all_values() {
+            match (params.get(&k), &v) {
+                // NaN is not equal to itself.
+                (Some(ParameterValue::Double(d)), ParameterValue::Double(e)) if e.is_nan() => {
+                    assert!(d.is_nan(), "parameter {}", k)
+                }
+                (p, v) => assert_eq!(p, Some(v), "parameter {}", k),
+            }
+        }
+    }
+
+    #[test]
+    fn test_yaml_double() -> () {
+        assert_eq!(yaml_double(f64::NAN), ".nan");
+        assert_eq!(yaml_double(f64::INFINITY), ".inf");
+        assert_eq!(yaml_double(f64::NEG_INFINITY), "-.inf");
+        assert_eq!(yaml_double(-2.0), "-2.0");
+    }
+
+    #[test]
+    fn test_restore_parameters_events() -> () {
+        let ctx = Context::create().unwrap();
+        let mut node = Node::create(ctx, "restore_params_node", "").unwrap();
+        node.params
+            .lock()
+            .unwrap()
+            .insert("same".to_owned(), ParameterValue::Integer(1));
+        let (_handler, events) = node.make_parameter_handler().unwrap();
+        let mut events = Box::pin(events);
+
+        let yaml = "restore_params_node:\n  ros__parameters:\n    same: 1\n    new: 2\n";
+        node.restore_parameters(yaml).unwrap();
+        // only the changed parameter is reported.
+        assert_eq!(
+            events.next().now_or_never(),
+            Some(Some(("new".to_owned(), ParameterValue::Integer(2))))
+        );
+        assert_eq!(events.next().now_or_never(), None);
+
+        // an invalid log sink parameter is rejected like in a
+        // set_parameters request, and nothing is applied.
+        let yaml =
+            "restore_params_node:\n  ros__parameters:\n    new: 3\n    log_sinks.stdout: 1\n";
+        assert!(node.restore_parameters(yaml).is_err());
+        assert_eq!(
+            node.params.lock().unwrap().get("new"),
+            Some(&ParameterValue::Integer(2))
+        );
+        assert_eq!(events.next().now_or_never(), None);
+
+        // neither are valid log sink parameters before the rejected one.
+        let sinks = crate::log_sinks::log_sink_config();
+        let yaml = "restore_params_node:\n  ros__parameters:\n    \
+                    log_sinks.ring_buffer.capacity: 17\n    log_sinks.stdout: 1\n";
+        assert!(node.restore_parameters(yaml).is_err());
+        assert_eq!(crate::log_sinks::log_sink_config(), sinks);
+        assert_eq!(events.next().now_or_never(), None);
+    }
+}
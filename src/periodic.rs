@@ -0,0 +1,80 @@
+//! Publishers that are driven by a timer of the node, e.g. for
+//! heartbeats and process information.
+
+use futures::future::FutureExt;
+use std::time::Duration;
+
+use crate::clocks::{Clock, ClockType};
+use crate::error::*;
+use crate::error_events::{EntityErrors, SpinOperation};
+use crate::executor::EntityKind;
+use crate::msg_types::generated_msgs::builtin_interfaces;
+use crate::nodes::{Node, Timer};
+use crate::publishers::PublisherUntyped;
+
+/// What a periodic publisher publishes.
+pub(crate) trait PeriodicMessage: Send {
+    /// True once the user has dropped their handle, which stops the
+    /// publisher.
+    fn is_dropped(&self) -> bool;
+    /// The message to publish, with `stamp` the current ROS time.
+    fn message(&mut self, stamp: &builtin_interfaces::msg::Time) -> Result<serde_json::Value>;
+    /// Called when the message from `message` has been published.
+    fn published(&mut self) {}
+}
+
+pub(crate) struct PeriodicPublisher_ {
+    publisher: PublisherUntyped,
+    timer: Timer,
+    clock: Clock,
+    source: Box<dyn PeriodicMessage>,
+    errors: EntityErrors,
+}
+
+impl PeriodicPublisher_ {
+    /// Publishes the messages of `source` on `topic` every `period`
+    /// while the node is spinning.
+    pub(crate) fn start(
+        node: &mut Node,
+        topic: &str,
+        msg_type: &str,
+        period: Duration,
+        source: Box<dyn PeriodicMessage>,
+    ) -> Result<()> {
+        let publisher = node.create_publisher_untyped(topic, msg_type)?;
+        let timer = node.create_wall_timer(period)?;
+        let periodic = PeriodicPublisher_ {
+            publisher,
+            timer,
+            clock: Clock::create(ClockType::RosTime)?,
+            source,
+            errors: node.entity_errors(EntityKind::Publisher, topic),
+        };
+        node.add_periodic_publisher(periodic);
+        Ok(())
+    }
+
+    /// Publishes a message if it is time for one. Returns false once
+    /// the source has been dropped.
+    pub(crate) fn poll(&mut self) -> bool {
+        if self.source.is_dropped() {
+            return false;
+        }
+        let mut due = false;
+        while let Some(Ok(_)) = self.timer.tick().now_or_never() {
+            due = true;
+        }
+        if !due {
+            return true;
+        }
+        let msg = self
+            .clock
+            .get_now()
+            .and_then(|now| self.source.message(&Clock::to_builtin_time(&now)));
+        match msg.and_then(|msg| self.publisher.publish(msg)) {
+            Ok(()) => self.source.published(),
+            Err(e) => self.errors.report(SpinOperation::Send, e),
+        }
+        true
+    }
+}
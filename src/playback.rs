@@ -0,0 +1,442 @@
+//! Publishing recorded messages again with their recorded timing, see
+//! `PlaybackScheduler`.
+//!
+//! The messages are published while the node spins, woken up by a
+//! wall timer of the node. Their timing is kept relative to the first
+//! message: position zero of the playback is the stamp of the first
+//! item.
+
+use futures::channel::mpsc;
+use futures::future::FutureExt;
+use futures::stream::Stream;
+use retain_mut::RetainMut;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::clocks::Clock;
+use crate::error::*;
+use crate::error_events::{EntityErrors, SpinOperation};
+use crate::executor::EntityKind;
+use crate::msg_types::{ros_type_name, serialize_message, WrappedTypesupport};
+use crate::nodes::{Node, Timer};
+use crate::publishers::{PublisherSerialized, PublisherUntyped};
+use crate::typesupport_loader::MessageTypeSupport;
+
+const CLOCK_TOPIC: &str = "/clock";
+const CLOCK_MSG_TYPE: &str = "rosgraph_msgs/msg/Clock";
+
+/// How the position of a playback advances.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlaybackClock {
+    /// With the wall clock, at the recorded speed.
+    Wall,
+    /// With the wall clock, this many times faster than recorded.
+    Scaled(f64),
+    /// Only with `PlaybackScheduler::advance`, e.g. by a simulation
+    /// that runs in steps.
+    Manual,
+}
+
+/// What to do with the items that a seek forward skips over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkippedItems {
+    /// Never publish them.
+    Drop,
+    /// Publish them all at once.
+    Burst,
+}
+
+/// Options of a `PlaybackScheduler`.
+#[derive(Debug, Clone)]
+pub struct PlaybackOptions {
+    pub clock: PlaybackClock,
+    pub skipped: SkippedItems,
+    /// Publish the recorded time on `/clock` as the playback advances,
+    /// for nodes that use simulated time.
+    pub publish_clock: bool,
+    /// Start paused, e.g. to wait for subscribers to match first.
+    pub start_paused: bool,
+    /// How often the node looks for items that are due. 1 ms by
+    /// default.
+    pub resolution: Duration,
+}
+
+impl Default for PlaybackOptions {
+    fn default() -> Self {
+        PlaybackOptions {
+            clock: PlaybackClock::Wall,
+            skipped: SkippedItems::Drop,
+            publish_clock: false,
+            start_paused: false,
+            resolution: Duration::from_millis(1),
+        }
+    }
+}
+
+/// A message to publish at a recorded time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaybackItem {
+    pub stamp: Duration,
+    pub topic: String,
+    /// E.g. "std_msgs/msg/String".
+    pub type_name: String,
+    /// The message as serialized by the middleware (CDR).
+    pub data: Vec<u8>,
+}
+
+impl PlaybackItem {
+    pub fn serialized(stamp: Duration, topic: &str, type_name: &str, data: Vec<u8>) -> Self {
+        PlaybackItem {
+            stamp,
+            topic: topic.to_owned(),
+            type_name: type_name.to_owned(),
+            data,
+        }
+    }
+
+    pub fn typed<T>(stamp: Duration, topic: &str, msg: &T) -> Result<Self>
+    where
+        T: WrappedTypesupport,
+    {
+        Ok(Self::serialized(
+            stamp,
+            topic,
+            &ros_type_name::<T>(),
+            serialize_message(msg)?,
+        ))
+    }
+}
+
+#[cfg(feature = "mcap")]
+impl From<crate::mcap::RecordedMessage> for PlaybackItem {
+    fn from(msg: crate::mcap::RecordedMessage) -> Self {
+        PlaybackItem {
+            stamp: msg.log_time,
+            topic: msg.topic,
+            type_name: msg.type_name,
+            data: msg.data,
+        }
+    }
+}
+
+/// Where a playback is, see `PlaybackScheduler::progress`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlaybackProgress {
+    /// Time since the first item.
+    pub position: Duration,
+    /// Time from the first to the last item.
+    pub duration: Duration,
+    pub published: usize,
+    /// Items skipped by seeking with `SkippedItems::Drop`.
+    pub dropped: usize,
+    pub paused: bool,
+    /// All items up to the end have been published or dropped.
+    pub finished: bool,
+}
+
+struct PlaybackState {
+    // sorted by stamp.
+    items: Vec<PlaybackItem>,
+    // the next item to publish.
+    next: usize,
+    clock: PlaybackClock,
+    skipped: SkippedItems,
+    // the position at `anchor`, which is set while playing.
+    position: Duration,
+    anchor: Option<Instant>,
+    paused: bool,
+    published: usize,
+    dropped: usize,
+    last_progress: Option<PlaybackProgress>,
+    progress_senders: Vec<mpsc::Sender<PlaybackProgress>>,
+}
+
+impl PlaybackState {
+    fn new(mut items: Vec<PlaybackItem>, options: &PlaybackOptions) -> Self {
+        items.sort_by_key(|item| item.stamp);
+        PlaybackState {
+            items,
+            next: 0,
+            clock: options.clock,
+            skipped: options.skipped,
+            position: Duration::ZERO,
+            anchor: None,
+            paused: options.start_paused,
+            published: 0,
+            dropped: 0,
+            last_progress: None,
+            progress_senders: Vec::new(),
+        }
+    }
+
+    fn start(&self) -> Duration {
+        self.items.first().map(|i| i.stamp).unwrap_or_default()
+    }
+
+    fn offset(&self, i: usize) -> Duration {
+        self.items[i].stamp - self.start()
+    }
+
+    fn duration(&self) -> Duration {
+        match self.items.len() {
+            0 => Duration::ZERO,
+            n => self.offset(n - 1),
+        }
+    }
+
+    fn position(&self, now: Instant) -> Duration {
+        let scale = match self.clock {
+            PlaybackClock::Wall => 1.0,
+            PlaybackClock::Scaled(scale) => scale,
+            PlaybackClock::Manual => 0.0,
+        };
+        match self.anchor {
+            Some(anchor) => self.position + now.saturating_duration_since(anchor).mul_f64(scale),
+            None => self.position,
+        }
+    }
+
+    // Moves the anchor to `now`, e.g. before the position is changed.
+    fn settle(&mut self, now: Instant) {
+        self.position = self.position(now);
+        if self.anchor.is_some() {
+            self.anchor = Some(now);
+        }
+    }
+
+    fn pause(&mut self, now: Instant) {
+        self.settle(now);
+        self.anchor = None;
+        self.paused = true;
+    }
+
+    fn resume(&mut self, now: Instant) {
+        if self.paused {
+            self.paused = false;
+            self.anchor = Some(now);
+        }
+    }
+
+    fn seek(&mut self, position: Duration, now: Instant) {
+        self.settle(now);
+        let start = self.start();
+        let target = self
+            .items
+            .partition_point(|item| item.stamp - start < position);
+        if target < self.next {
+            // back, to publish them again.
+            self.next = target;
+        } else if self.skipped == SkippedItems::Drop {
+            self.dropped += target - self.next;
+            self.next = target;
+        }
+        self.position = position;
+    }
+
+    // The items due at `now`, which count as published.
+    fn take_due(&mut self, now: Instant) -> std::ops::Range<usize> {
+        if self.paused {
+            return self.next..self.next;
+        }
+        if self.anchor.is_none() {
+            // the first spin starts the playback.
+            self.anchor = Some(now);
+        }
+        let position = self.position(now);
+        let start = self.next;
+        while self.next < self.items.len() && self.offset(self.next) <= position {
+            self.next += 1;
+        }
+        self.published += self.next - start;
+        start..self.next
+    }
+
+    fn progress(&self, now: Instant) -> PlaybackProgress {
+        PlaybackProgress {
+            position: self.position(now),
+            duration: self.duration(),
+            published: self.published,
+            dropped: self.dropped,
+            paused: self.paused,
+            finished: self.next == self.items.len(),
+        }
+    }
+
+    // Sends the progress to the streams if it changed.
+    fn report_progress(&mut self, now: Instant) {
+        let progress = self.progress(now);
+        if self.last_progress.as_ref() == Some(&progress) {
+            return;
+        }
+        self.last_progress = Some(progress);
+        // a full stream skips updates, a dropped one is forgotten.
+        self.progress_senders
+            .retain_mut(|s| match s.try_send(progress) {
+                Err(e) => !e.is_disconnected(),
+                Ok(()) => true,
+            });
+    }
+}
+
+/// Publishes messages at the times they were recorded, e.g. those of
+/// an `McapReader` or messages made up for a test.
+///
+/// ```ignore
+/// let items = vec![
+///     PlaybackItem::typed(Duration::from_secs(0), "/chatter", &msg_a)?,
+///     PlaybackItem::typed(Duration::from_secs(1), "/chatter", &msg_b)?,
+/// ];
+/// let playback = PlaybackScheduler::start(&mut node, items, PlaybackOptions::default())?;
+/// // msg_a is published by the next spin, msg_b a second later.
+/// ```
+///
+/// The items are published while the node spins, until the scheduler
+/// is dropped. A publisher is created for each topic of the items,
+/// with the type support looked up by the type name.
+pub struct PlaybackScheduler {
+    state: Arc<Mutex<PlaybackState>>,
+    publishers: HashMap<String, PublisherSerialized>,
+}
+
+impl PlaybackScheduler {
+    /// Start publishing `items` on `node`. The playback starts with
+    /// the next spin, unless `PlaybackOptions::start_paused` is set.
+    pub fn start(
+        node: &mut Node,
+        items: impl IntoIterator<Item = PlaybackItem>,
+        options: PlaybackOptions,
+    ) -> Result<PlaybackScheduler> {
+        if let PlaybackClock::Scaled(scale) = options.clock {
+            if !(scale > 0.0 && scale.is_finite()) {
+                return Err(Error::RCL_RET_INVALID_ARGUMENT);
+            }
+        }
+        let state = PlaybackState::new(items.into_iter().collect(), &options);
+        let mut publishers = HashMap::new();
+        for item in &state.items {
+            if !publishers.contains_key(&item.topic) {
+                let type_support = MessageTypeSupport::for_type_name(&item.type_name)?;
+                let publisher = node.create_publisher_serialized(&item.topic, &type_support)?;
+                let errors = node.entity_errors(EntityKind::Publisher, &item.topic);
+                publishers.insert(item.topic.clone(), (publisher, errors));
+            }
+        }
+        let clock_publisher = if options.publish_clock {
+            let publisher = node.create_publisher_untyped(CLOCK_TOPIC, CLOCK_MSG_TYPE)?;
+            let errors = node.entity_errors(EntityKind::Publisher, CLOCK_TOPIC);
+            Some((publisher, errors))
+        } else {
+            None
+        };
+        let timer = node.create_wall_timer(options.resolution)?;
+        let state = Arc::new(Mutex::new(state));
+        let scheduler = PlaybackScheduler {
+            state: state.clone(),
+            publishers: publishers
+                .iter()
+                .map(|(topic, (p, _))| (topic.clone(), p.clone()))
+                .collect(),
+        };
+        node.add_playback(Playback_ {
+            state,
+            publishers,
+            clock_publisher,
+            last_clock: None,
+            timer,
+        });
+        Ok(scheduler)
+    }
+
+    /// The publisher of `topic`, e.g. for waiting until subscribers
+    /// have matched before resuming.
+    pub fn publisher(&self, topic: &str) -> Option<&PublisherSerialized> {
+        self.publishers.get(topic)
+    }
+
+    pub fn pause(&self) {
+        self.state.lock().unwrap().pause(Instant::now());
+    }
+
+    pub fn resume(&self) {
+        self.state.lock().unwrap().resume(Instant::now());
+    }
+
+    /// Continue from `position`, the time since the first item.
+    ///
+    /// Seeking back publishes the items from there again. Seeking
+    /// forward handles the items in between as set by
+    /// `PlaybackOptions::skipped`.
+    pub fn seek(&self, position: Duration) {
+        self.state.lock().unwrap().seek(position, Instant::now());
+    }
+
+    /// Move the position forward by `step`. Items that become due are
+    /// published by the next spin. Only for `PlaybackClock::Manual`,
+    /// fails with `RCL_RET_INVALID_ARGUMENT` otherwise.
+    pub fn advance(&self, step: Duration) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.clock != PlaybackClock::Manual {
+            return Err(Error::RCL_RET_INVALID_ARGUMENT);
+        }
+        state.position += step;
+        Ok(())
+    }
+
+    /// Where the playback is now.
+    pub fn status(&self) -> PlaybackProgress {
+        self.state.lock().unwrap().progress(Instant::now())
+    }
+
+    /// A stream of the progress, updated by the spins that publish
+    /// something and after the playback was paused, resumed or moved.
+    pub fn progress(&self) -> impl Stream<Item = PlaybackProgress> + Unpin {
+        let (sender, receiver) = mpsc::channel(10);
+        self.state.lock().unwrap().progress_senders.push(sender);
+        receiver
+    }
+}
+
+pub(crate) struct Playback_ {
+    state: Arc<Mutex<PlaybackState>>,
+    publishers: HashMap<String, (PublisherSerialized, EntityErrors)>,
+    clock_publisher: Option<(PublisherUntyped, EntityErrors)>,
+    // the last time published on /clock.
+    last_clock: Option<Duration>,
+    timer: Timer,
+}
+
+impl Playback_ {
+    /// Publishes the items that are due. Returns false once the
+    /// scheduler has been dropped.
+    pub(crate) fn poll(&mut self) -> bool {
+        // the scheduler holds the only other reference.
+        if Arc::strong_count(&self.state) == 1 {
+            return false;
+        }
+        // the timer only wakes up the spin.
+        while let Some(Ok(_)) = self.timer.tick().now_or_never() {}
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let due = state.take_due(now);
+        if let Some((publisher, errors)) = &self.clock_publisher {
+            // before the items, so that they are not from the future.
+            let time = state.start() + state.position(now);
+            if !state.paused && self.last_clock != Some(time) {
+                let msg = serde_json::json!({ "clock": Clock::to_builtin_time(&time) });
+                if let Err(e) = publisher.publish(msg) {
+                    errors.report(SpinOperation::Send, e);
+                }
+                self.last_clock = Some(time);
+            }
+        }
+        for item in &state.items[due] {
+            let (publisher, errors) = &self.publishers[&item.topic];
+            if let Err(e) = publisher.publish(&item.data) {
+                errors.report(SpinOperation::Send, e);
+            }
+        }
+        state.report_progress(now);
+        true
+    }
+}
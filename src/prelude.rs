@@ -0,0 +1,9 @@
+//! The traits needed to use r2r generically, to be glob imported.
+//!
+//! ```ignore
+//! use r2r::prelude::*;
+//! ```
+
+pub use crate::msg_types::WrappedTypesupport;
+pub use crate::test_support::Spin;
+pub use crate::traits::{Entity, MessageStream, Publish};
@@ -0,0 +1,199 @@
+//! Resource usage of the process a node runs in.
+//!
+//! Only built with the `process-info` feature. A `ProcessSampler`
+//! measures the usage, which is reported by a task of a
+//! `DiagnosticUpdater`.
+
+use std::time::Duration;
+
+use crate::diagnostics::{DiagnosticStatus, DiagnosticUpdater};
+use crate::error::*;
+use crate::nodes::Node;
+
+/// Resource usage of a process at one point in time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProcessSample {
+    /// Resident set size in bytes.
+    pub rss_bytes: u64,
+    /// User and system CPU time used so far.
+    pub cpu_time: Duration,
+    pub threads: u64,
+    pub open_fds: u64,
+}
+
+/// Measures the resource usage of the current process.
+///
+/// Implement this for platforms other than Linux, and pass it to
+/// `ProcessInfo::publish_with_sampler`.
+pub trait ProcessSampler: Send {
+    fn sample(&mut self) -> Result<ProcessSample>;
+}
+
+/// Reads the usage from `/proc/self`.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Default)]
+pub struct ProcSampler;
+
+#[cfg(target_os = "linux")]
+impl ProcessSampler for ProcSampler {
+    fn sample(&mut self) -> Result<ProcessSample> {
+        let read = |path: &str| {
+            std::fs::read_to_string(path).map_err(|e| Error::NotSupported {
+                feature: format!("{} ({})", path, e),
+            })
+        };
+        let (rss_bytes, threads) = parse_status(&read("/proc/self/status")?)?;
+        let cpu_time = parse_stat_cpu_time(&read("/proc/self/stat")?)?;
+        let open_fds = std::fs::read_dir("/proc/self/fd")
+            .map(|entries| entries.count() as u64)
+            .unwrap_or(0);
+        Ok(ProcessSample {
+            rss_bytes,
+            cpu_time,
+            threads,
+            open_fds,
+        })
+    }
+}
+
+/// The sampler for the current platform.
+pub fn default_sampler() -> Result<Box<dyn ProcessSampler>> {
+    #[cfg(target_os = "linux")]
+    {
+        Ok(Box::new(ProcSampler))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Err(Error::NotSupported {
+            feature: "process sampling on this platform".into(),
+        })
+    }
+}
+
+// VmRSS (in kB) and Threads from /proc/self/status.
+fn parse_status(status: &str) -> Result<(u64, u64)> {
+    let mut rss = None;
+    let mut threads = None;
+    for line in status.lines() {
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some("VmRSS:") => rss = parts.next().and_then(|kb| kb.parse::<u64>().ok()),
+            Some("Threads:") => threads = parts.next().and_then(|t| t.parse().ok()),
+            _ => (),
+        }
+    }
+    match (rss, threads) {
+        (Some(rss), Some(threads)) => Ok((rss * 1024, threads)),
+        _ => Err(Error::SerdeError {
+            err: "VmRSS or Threads missing in /proc/self/status".into(),
+        }),
+    }
+}
+
+// utime + stime from /proc/self/stat. They are given in USER_HZ,
+// which is 100 on all platforms that matter.
+fn parse_stat_cpu_time(stat: &str) -> Result<Duration> {
+    const USER_HZ: u64 = 100;
+    // the command name may contain spaces, the fields after it do not.
+    let fields = stat
+        .rfind(')')
+        .map(|end| stat[end + 1..].split_whitespace().collect::<Vec<_>>())
+        .unwrap_or_default();
+    // utime and stime are fields 14 and 15, i.e. the 12th and 13th
+    // after the command name and state.
+    let ticks = |i: usize| fields.get(i).and_then(|t| t.parse::<u64>().ok());
+    match (ticks(11), ticks(12)) {
+        (Some(utime), Some(stime)) => {
+            let ticks = utime + stime;
+            Ok(Duration::from_millis(ticks * 1000 / USER_HZ))
+        }
+        _ => Err(Error::SerdeError {
+            err: "utime or stime missing in /proc/self/stat".into(),
+        }),
+    }
+}
+
+/// Publishes the resource usage of the process until dropped.
+pub struct ProcessInfo {
+    _updater: DiagnosticUpdater,
+}
+
+impl ProcessInfo {
+    /// Publish the resource usage on `topic` (usually "/diagnostics")
+    /// every `period`, as one `DiagnosticStatus` named after the fully
+    /// qualified name of the node.
+    pub fn publish(node: &mut Node, topic: &str, period: Duration) -> Result<ProcessInfo> {
+        Self::publish_with_sampler(node, topic, period, default_sampler()?)
+    }
+
+    /// Like `publish`, but measures the usage with `sampler`.
+    pub fn publish_with_sampler(
+        node: &mut Node,
+        topic: &str,
+        period: Duration,
+        sampler: Box<dyn ProcessSampler>,
+    ) -> Result<ProcessInfo> {
+        let updater = DiagnosticUpdater::with_topic(node, topic, period)?;
+        Self::add_task(&updater, sampler);
+        Ok(ProcessInfo { _updater: updater })
+    }
+
+    /// Report the resource usage measured by `sampler` with the other
+    /// tasks of `updater`, in a status named "<node>: process".
+    pub fn add_task(updater: &DiagnosticUpdater, mut sampler: Box<dyn ProcessSampler>) {
+        updater.add("process", move |status| {
+            add_sample(status, &sampler.sample()?);
+            Ok(())
+        });
+    }
+}
+
+fn add_sample(status: &mut DiagnosticStatus, sample: &ProcessSample) {
+    status.add("rss_bytes", sample.rss_bytes);
+    status.add("cpu_time", format!("{:.3}", sample.cpu_time.as_secs_f64()));
+    status.add("threads", sample.threads);
+    status.add("open_fds", sample.open_fds);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc() {
+        let status = "Name:\tcat\nVmRSS:\t    1024 kB\nThreads:\t3\n";
+        assert_eq!(parse_status(status).unwrap(), (1024 * 1024, 3));
+        assert!(parse_status("Name:\tcat\n").is_err());
+
+        let stat = "4242 (my (odd) node) S 1 2 3 4 5 6 7 8 9 10 150 50 0 0 20 0 3 0";
+        assert_eq!(parse_stat_cpu_time(stat).unwrap(), Duration::from_secs(2));
+        assert!(parse_stat_cpu_time("4242 (node) S 1").is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_proc_sampler() {
+        let sample = ProcSampler.sample().unwrap();
+        assert!(sample.rss_bytes > 0);
+        assert!(sample.threads >= 1);
+        assert!(sample.open_fds >= 3);
+    }
+
+    #[test]
+    fn test_add_sample() {
+        let sample = ProcessSample {
+            rss_bytes: 10,
+            cpu_time: Duration::from_millis(1500),
+            threads: 2,
+            open_fds: 5,
+        };
+        let mut status = DiagnosticStatus::default();
+        add_sample(&mut status, &sample);
+        assert_eq!(status.values[0], ("rss_bytes".to_owned(), "10".to_owned()));
+        assert_eq!(
+            status.values[1],
+            ("cpu_time".to_owned(), "1.500".to_owned())
+        );
+        assert_eq!(status.values.len(), 4);
+    }
+}
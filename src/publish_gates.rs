@@ -0,0 +1,223 @@
+//! Dropping messages before they are published, see
+//! `Publisher::rate_limited` and `Publisher::drop_if_older_than`.
+//!
+//! Both are judged by a ROS time clock. The clock may jump when sim
+//! time is used: after a jump back the rate limit starts over, and
+//! messages stamped after the current time are never too old.
+
+use std::time::Duration;
+
+use crate::clocks::{Clock, ClockType};
+use crate::error::*;
+use crate::msg_types::generated_msgs::builtin_interfaces;
+use crate::msg_types::*;
+use crate::validation::Candidate;
+
+/// What happened to a message given to `Publisher::try_publish`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublishOutcome {
+    Sent,
+    Dropped(DropReason),
+}
+
+/// Why a publisher dropped a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+    /// Published faster than `Publisher::rate_limited` allows.
+    RateLimited,
+    /// The stamp was older than `Publisher::drop_if_older_than` allows.
+    TooOld,
+}
+
+/// Number of messages a publisher dropped, by reason.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DropCounts {
+    pub rate_limited: u64,
+    pub too_old: u64,
+}
+
+impl DropCounts {
+    pub fn total(&self) -> u64 {
+        self.rate_limited + self.too_old
+    }
+}
+
+// A token bucket that holds a single token, refilled at `rate`.
+pub(crate) struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last: Option<Duration>,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(max_hz: f64) -> Self {
+        TokenBucket {
+            rate: max_hz,
+            tokens: 1.0,
+            last: None,
+        }
+    }
+
+    pub(crate) fn refill(&mut self, now: Duration) {
+        if let Some(last) = self.last {
+            // a jump back refills nothing, a jump forward at most the
+            // one token.
+            let elapsed = now.saturating_sub(last).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.rate).min(1.0);
+        }
+        self.last = Some(now);
+    }
+
+    pub(crate) fn has_token(&self) -> bool {
+        self.tokens >= 1.0
+    }
+
+    pub(crate) fn take(&mut self) {
+        self.tokens -= 1.0;
+    }
+}
+
+pub(crate) fn too_old(
+    stamp: &builtin_interfaces::msg::Time,
+    now: Duration,
+    max_age: Duration,
+) -> bool {
+    let stamp = Duration::new(stamp.sec.max(0) as u64, stamp.nanosec);
+    now.saturating_sub(stamp) > max_age
+}
+
+type Stamp<T> = fn(&T) -> &builtin_interfaces::msg::Time;
+
+/// The gates of a publisher, shared by its clones.
+pub(crate) struct PublishGates<T> {
+    // created with the first gate.
+    clock: Option<Clock>,
+    rate_limits: Vec<TokenBucket>,
+    max_ages: Vec<(Duration, Stamp<T>)>,
+    dropped: DropCounts,
+}
+
+impl<T> Default for PublishGates<T> {
+    fn default() -> Self {
+        PublishGates {
+            clock: None,
+            rate_limits: Vec::new(),
+            max_ages: Vec::new(),
+            dropped: DropCounts::default(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for PublishGates<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PublishGates")
+            .field("rate_limits", &self.rate_limits.len())
+            .field("max_ages", &self.max_ages.len())
+            .field("dropped", &self.dropped)
+            .finish()
+    }
+}
+
+impl<T> PublishGates<T>
+where
+    T: WrappedTypesupport,
+{
+    fn ensure_clock(&mut self) -> Result<()> {
+        if self.clock.is_none() {
+            self.clock = Some(Clock::create(ClockType::RosTime)?);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn add_rate_limit(&mut self, max_hz: f64) -> Result<()> {
+        if !(max_hz > 0.0 && max_hz.is_finite()) {
+            return Err(Error::RCL_RET_INVALID_ARGUMENT);
+        }
+        self.ensure_clock()?;
+        self.rate_limits.push(TokenBucket::new(max_hz));
+        Ok(())
+    }
+
+    pub(crate) fn add_max_age(&mut self, max_age: Duration, stamp: Stamp<T>) -> Result<()> {
+        self.ensure_clock()?;
+        self.max_ages.push((max_age, stamp));
+        Ok(())
+    }
+
+    pub(crate) fn dropped(&self) -> DropCounts {
+        self.dropped
+    }
+
+    /// Whether `candidate` may be published. The age is checked before
+    /// the rate, so that dropped messages do not use up the rate.
+    pub(crate) fn admit(&mut self, candidate: &mut Candidate<'_, T>) -> Result<Option<DropReason>> {
+        let clock = match self.clock.as_mut() {
+            Some(clock) => clock,
+            None => return Ok(None),
+        };
+        let now = clock.get_now()?;
+        for (max_age, stamp) in &self.max_ages {
+            if too_old(stamp(candidate.msg()), now, *max_age) {
+                self.dropped.too_old += 1;
+                return Ok(Some(DropReason::TooOld));
+            }
+        }
+        // a message takes a token from every limit, or from none.
+        for bucket in &mut self.rate_limits {
+            bucket.refill(now);
+        }
+        if !self.rate_limits.iter().all(|b| b.has_token()) {
+            self.dropped.rate_limited += 1;
+            return Ok(Some(DropReason::RateLimited));
+        }
+        for bucket in &mut self.rate_limits {
+            bucket.take();
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn admit(bucket: &mut TokenBucket, ms: u64) -> bool {
+        bucket.refill(Duration::from_millis(ms));
+        if bucket.has_token() {
+            bucket.take();
+            true
+        } else {
+            false
+        }
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let mut bucket = TokenBucket::new(10.0);
+        assert!(admit(&mut bucket, 1000));
+        assert!(!admit(&mut bucket, 1050));
+        assert!(admit(&mut bucket, 1120));
+        assert!(!admit(&mut bucket, 1170));
+
+        // no burst after a long pause, or a jump forward.
+        assert!(admit(&mut bucket, 60_000));
+        assert!(!admit(&mut bucket, 60_010));
+
+        // a jump back does not block publishing for the jumped time.
+        assert!(!admit(&mut bucket, 500));
+        assert!(admit(&mut bucket, 600));
+    }
+
+    #[test]
+    fn test_too_old() {
+        let stamp = builtin_interfaces::msg::Time {
+            sec: 10,
+            nanosec: 0,
+        };
+        let max_age = Duration::from_millis(100);
+        assert!(!too_old(&stamp, Duration::from_millis(10_050), max_age));
+        assert!(too_old(&stamp, Duration::from_millis(10_200), max_age));
+        // stamped after now, e.g. after a jump back.
+        assert!(!too_old(&stamp, Duration::from_secs(5), max_age));
+    }
+}
@@ -1,10 +1,24 @@
-use std::ffi::CString;
+use std::collections::VecDeque;
+use std::time::Duration;
+use std::ffi::{CStr, CString};
 use std::fmt::Debug;
-use std::sync::Weak;
+use std::sync::{Arc, Mutex, Weak};
 use std::marker::PhantomData;
 
 use crate::msg_types::*;
 use crate::error::*;
+use crate::error_events::*;
+use crate::log_handler::log_internal;
+use crate::utils::LogSeverity;
+use crate::distro;
+use crate::executor::EntityKind;
+use crate::publish_gates::*;
+use crate::qos::QosProfile;
+use crate::qos_overrides::QosOverridePolicy;
+use crate::stats::*;
+use crate::traits::{Entity, Publish};
+use crate::typesupport_loader::MessageTypeSupport;
+use crate::validation::*;
 use r2r_rcl::*;
 
 // The publish function is thread safe. ROS2 docs state:
@@ -45,6 +59,9 @@ where
 {
     handle: Weak<rcl_publisher_t>,
     type_: PhantomData<T>,
+    stats: Option<Arc<Mutex<StatsTracker>>>,
+    validators: Arc<Mutex<Validators<T>>>,
+    gates: Arc<Mutex<PublishGates<T>>>,
 }
 
 unsafe impl Send for PublisherUntyped {}
@@ -59,31 +76,223 @@ pub struct PublisherUntyped {
     type_: String,
 }
 
+unsafe impl Send for PublisherSerialized {}
+
+/// A ROS publisher of messages that are already serialized, for types
+/// that are only known at runtime, see `Node::create_publisher_serialized`.
+///
+/// This is a handle to a publisher that is owned by the node. It keeps
+/// the type support library loaded, but the publisher stops working
+/// when the node is dropped.
+#[derive(Debug, Clone)]
+pub struct PublisherSerialized {
+    handle: Weak<rcl_publisher_t>,
+    type_support: MessageTypeSupport,
+}
+
 pub fn make_publisher<T>(handle: Weak<rcl_publisher_t>) -> Publisher<T>
+where
+    T: WrappedTypesupport,
+{
+    make_publisher_with_stats(handle, None)
+}
+
+pub fn make_publisher_with_stats<T>(
+    handle: Weak<rcl_publisher_t>,
+    stats: Option<Arc<Mutex<StatsTracker>>>,
+) -> Publisher<T>
 where
     T: WrappedTypesupport,
 {
     Publisher {
         handle,
         type_: PhantomData,
+        stats,
+        validators: Arc::new(Mutex::new(Validators::default())),
+        gates: Arc::new(Mutex::new(PublishGates::default())),
     }
 }
 
+/// Options for creating publishers.
+#[derive(Debug, Clone, Default)]
+pub struct PublisherOptions {
+    /// Keep message statistics, see `Publisher::stats`. This costs an
+    /// extra serialization of each message.
+    pub stats: bool,
+    /// The QoS profile of the publisher. When `None` the defaults of
+    /// the rmw implementation are used.
+    pub qos: Option<QosProfile>,
+    /// Whether the profile can be overridden by the
+    /// `qos_overrides.<topic>.publisher.*` parameters.
+    pub qos_overrides: QosOverridePolicy,
+}
+
+impl PublisherOptions {
+    pub(crate) fn rmw_qos(&self) -> Result<rmw_qos_profile_t> {
+        match &self.qos {
+            Some(qos) => {
+                qos.validate()?;
+                Ok(rmw_qos_profile_t::from(qos))
+            }
+            None => Ok(rmw_qos_profile_t::default()),
+        }
+    }
+}
+
+unsafe impl<T> Send for RetainedPublisher<T> where T: WrappedTypesupport {}
+
+/// A ROS publisher that remembers the last published messages.
+///
+/// The remembered messages are published again when the number of
+/// subscriptions matched with the publisher grows, e.g. when a new
+/// subscriber shows up or when the subscribers come back after the
+/// middleware was restarted. This gives "latching" that does not
+/// depend on how the DDS vendor handles durability. Note that
+/// subscribers that were already matched receive the messages again.
+#[derive(Clone)]
+pub struct RetainedPublisher<T>
+where
+    T: WrappedTypesupport,
+{
+    publisher: Publisher<T>,
+    retained: Arc<Mutex<Retained<T>>>,
+}
+
+pub struct Retained<T>
+where
+    T: WrappedTypesupport,
+{
+    publisher: Publisher<T>,
+    messages: VecDeque<T>,
+    depth: usize,
+    subscription_count: usize,
+    errors: EntityErrors,
+}
+
+pub trait Retained_ {
+    /// Publish the retained messages again if new subscriptions have
+    /// matched since last time, as told by the matched event of the
+    /// publisher, or else by the number of subscriptions growing.
+    /// Returns false if the publisher is gone.
+    fn republish_on_match(&mut self, new_matches: Option<bool>) -> bool;
+}
+
+/// The matched event of a publisher. Unlike the number of
+/// subscriptions, it also tells about a subscription that replaced
+/// another between two checks.
+pub struct MatchedEvent {
+    event: rcl_event_t,
+}
+
+impl MatchedEvent {
+    /// Fails with `Error::NotSupported` before Iron, or if the rmw
+    /// implementation has no matched events.
+    pub fn new(publisher: &rcl_publisher_t) -> Result<Self> {
+        let mut event = unsafe { rcl_get_zero_initialized_event() };
+        distro::publisher_matched_event_init(&mut event, publisher)?;
+        Ok(MatchedEvent { event })
+    }
+
+    /// Whether subscriptions have matched since the last call.
+    pub fn new_matches(&mut self) -> Result<bool> {
+        distro::take_new_matches(&self.event)
+    }
+}
+
+impl Drop for MatchedEvent {
+    fn drop(&mut self) {
+        unsafe {
+            rcl_event_fini(&mut self.event);
+        }
+    }
+}
+
+pub fn make_retained_publisher<T>(
+    handle: Weak<rcl_publisher_t>,
+    depth: usize,
+    errors: EntityErrors,
+) -> (RetainedPublisher<T>, Arc<Mutex<Retained<T>>>)
+where
+    T: WrappedTypesupport,
+{
+    let retained = Arc::new(Mutex::new(Retained {
+        publisher: make_publisher(handle.clone()),
+        messages: VecDeque::new(),
+        depth,
+        subscription_count: 0,
+        errors,
+    }));
+    let p = RetainedPublisher {
+        publisher: make_publisher(handle),
+        retained: retained.clone(),
+    };
+    (p, retained)
+}
+
 pub fn make_publisher_untyped(handle: Weak<rcl_publisher_t>, type_: String) -> PublisherUntyped {
     PublisherUntyped { handle, type_ }
 }
 
+pub fn make_publisher_serialized(
+    handle: Weak<rcl_publisher_t>,
+    type_support: MessageTypeSupport,
+) -> PublisherSerialized {
+    PublisherSerialized {
+        handle,
+        type_support,
+    }
+}
+
+pub fn publisher_subscription_count(publisher: &rcl_publisher_t) -> Result<usize> {
+    let mut count = 0usize;
+    let result = unsafe { rcl_publisher_get_subscription_count(publisher, &mut count) };
+    if result == RCL_RET_OK as i32 {
+        Ok(count)
+    } else {
+        Err(Error::from_rcl_error(result))
+    }
+}
+
+/// Outcome of flushing a publisher, see `Node::flush_publishers`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FlushStatus {
+    /// All published messages were acknowledged by the subscribers.
+    Flushed,
+    /// Not all messages were acknowledged before the deadline.
+    TimedOut,
+    /// The rmw implementation (or ROS distribution) does not support
+    /// waiting for acknowledgments.
+    Unknown,
+}
+
+pub fn publisher_topic_name(publisher: &rcl_publisher_t) -> Result<String> {
+    let cstr = unsafe { rcl_publisher_get_topic_name(publisher) };
+    if cstr == std::ptr::null() {
+        return Err(Error::RCL_RET_PUBLISHER_INVALID);
+    }
+    let s = unsafe { CStr::from_ptr(cstr) };
+    Ok(s.to_str().unwrap_or("").to_owned())
+}
+
+pub fn publisher_is_reliable(publisher: &rcl_publisher_t) -> bool {
+    let qos = unsafe { rcl_publisher_get_actual_qos(publisher) };
+    qos != std::ptr::null()
+        && unsafe { (*qos).reliability }
+            == rmw_qos_reliability_policy_t::RMW_QOS_POLICY_RELIABILITY_RELIABLE
+}
+
 pub fn create_publisher_helper(
     node: &mut rcl_node_t,
     topic: &str,
     typesupport: *const rosidl_message_type_support_t,
+    qos_profile: rmw_qos_profile_t,
 ) -> Result<rcl_publisher_t> {
     let mut publisher_handle = unsafe { rcl_get_zero_initialized_publisher() };
     let topic_c_string = CString::new(topic).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
 
-    let result = unsafe {
+    rcl_call(|| unsafe {
         let mut publisher_options = rcl_publisher_get_default_options();
-        publisher_options.qos = rmw_qos_profile_t::default();
+        publisher_options.qos = qos_profile;
         rcl_publisher_init(
             &mut publisher_handle,
             node,
@@ -91,15 +300,145 @@ pub fn create_publisher_helper(
             topic_c_string.as_ptr(),
             &publisher_options,
         )
-    };
-    if result == RCL_RET_OK as i32 {
-        Ok(publisher_handle)
-    } else {
-        Err(Error::from_rcl_error(result))
+    })?;
+    Ok(publisher_handle)
+}
+
+impl<T: 'static> Retained_ for Retained<T>
+where
+    T: WrappedTypesupport,
+{
+    fn republish_on_match(&mut self, new_matches: Option<bool>) -> bool {
+        let count = match self.publisher.get_inter_process_subscription_count() {
+            Ok(count) => count,
+            Err(_) => return false,
+        };
+        if new_matches.unwrap_or(count > self.subscription_count) {
+            // validated when they were first published.
+            for msg in &self.messages {
+                if let Err(e) = self
+                    .publisher
+                    .publish_validated(&WrappedNativeMsg::from(msg))
+                {
+                    self.errors.report(SpinOperation::Send, e);
+                }
+            }
+        }
+        self.subscription_count = count;
+        true
     }
 }
 
+impl<T: 'static> RetainedPublisher<T>
+where
+    T: WrappedTypesupport,
+{
+    /// Gets the number of subscriptions currently matched with this publisher.
+    pub fn get_inter_process_subscription_count(&self) -> Result<usize> {
+        self.publisher.get_inter_process_subscription_count()
+    }
+
+    /// Add a check that every published message has to pass, see
+    /// `Publisher::add_validator`. Messages that fail are not
+    /// remembered either.
+    pub fn add_validator<F>(&self, validator: F)
+    where
+        F: Fn(&T) -> std::result::Result<(), String> + Send + 'static,
+    {
+        self.publisher.add_validator(validator);
+    }
+
+    /// Add a validator that may change the message, see
+    /// `Publisher::add_mutating_validator`. The changed message is the
+    /// one that is remembered.
+    pub fn add_mutating_validator<F>(&self, validator: F)
+    where
+        F: Fn(&mut T) -> std::result::Result<(), String> + Send + 'static,
+    {
+        self.publisher.add_mutating_validator(validator);
+    }
+
+    /// Add a validator that looks at the native message, see
+    /// `Publisher::add_native_validator`.
+    pub fn add_native_validator<F>(&self, validator: F)
+    where
+        F: Fn(NativeMsgView<'_, T>) -> std::result::Result<(), String> + Send + 'static,
+    {
+        self.publisher.add_native_validator(validator);
+    }
+
+    /// What to do with messages that fail a validator, rejecting them
+    /// by default.
+    pub fn set_validation_policy(&self, policy: ValidationPolicy) {
+        self.publisher.set_validation_policy(policy);
+    }
+
+    /// Publish a ROS message and remember it, forgetting the oldest
+    /// remembered message if there are already `depth` of them.
+    ///
+    /// Fails with `Error::ValidationFailed` if the message is rejected
+    /// by a validator.
+    pub fn publish(&self, msg: &T) -> Result<()> {
+        // hold the lock while publishing so that the spin thread
+        // cannot republish in between.
+        let mut retained = self.retained.lock().unwrap();
+        let mut candidate = Candidate::from_msg(msg);
+        self.publisher.validate(&mut candidate)?;
+        self.publisher.publish_validated(candidate.native())?;
+        if retained.depth > 0 {
+            if retained.messages.len() == retained.depth {
+                retained.messages.pop_front();
+            }
+            retained.messages.push_back(candidate.msg().clone());
+        }
+        Ok(())
+    }
+
+    /// Forget all remembered messages.
+    pub fn clear(&self) {
+        self.retained.lock().unwrap().messages.clear();
+    }
+}
+
+impl<T: 'static> Publish<T> for RetainedPublisher<T>
+where
+    T: WrappedTypesupport,
+{
+    fn publish(&self, msg: &T) -> Result<()> {
+        RetainedPublisher::publish(self, msg)
+    }
+}
+
+impl<T> Entity for RetainedPublisher<T>
+where
+    T: WrappedTypesupport,
+{
+    fn name(&self) -> Result<String> {
+        self.publisher.name()
+    }
+
+    fn kind(&self) -> EntityKind {
+        EntityKind::Publisher
+    }
+}
+
+/// The topic of a publisher that may have been destroyed.
+fn handle_topic_name(handle: &Weak<rcl_publisher_t>) -> Result<String> {
+    let publisher = handle.upgrade().ok_or(Error::RCL_RET_PUBLISHER_INVALID)?;
+    publisher_topic_name(publisher.as_ref())
+}
+
 impl PublisherUntyped {
+    /// Gets the number of subscriptions currently matched with this publisher.
+    pub fn get_inter_process_subscription_count(&self) -> Result<usize> {
+        // upgrade to actual ref. if still alive
+        let publisher = self
+            .handle
+            .upgrade()
+            .ok_or(Error::RCL_RET_PUBLISHER_INVALID)?;
+        publisher_subscription_count(publisher.as_ref())
+    }
+
     /// Publish an "untyped" ROS message represented by a `serde_json::Value`.
     ///
     /// It is up to the user to make sure the fields are correct.
@@ -124,18 +463,199 @@ impl PublisherUntyped {
         if result == RCL_RET_OK as i32 {
             Ok(())
         } else {
-            eprintln!("coult not publish {}", result);
+            let err = Error::from_rcl_error(result);
+            log_internal(LogSeverity::Warn, &format!("could not publish: {}", err));
+            Err(err)
+        }
+    }
+}
+
+impl Entity for PublisherUntyped {
+    fn name(&self) -> Result<String> {
+        handle_topic_name(&self.handle)
+    }
+
+    fn kind(&self) -> EntityKind {
+        EntityKind::Publisher
+    }
+}
+
+impl PublisherSerialized {
+    /// Gets the number of subscriptions currently matched with this publisher.
+    pub fn get_inter_process_subscription_count(&self) -> Result<usize> {
+        // upgrade to actual ref. if still alive
+        let publisher = self
+            .handle
+            .upgrade()
+            .ok_or(Error::RCL_RET_PUBLISHER_INVALID)?;
+        publisher_subscription_count(publisher.as_ref())
+    }
+
+    /// The type name the publisher was created with.
+    pub fn type_name(&self) -> &str {
+        self.type_support.type_name()
+    }
+
+    /// Publish a message serialized by the middleware in use (e.g. CDR).
+    pub fn publish(&self, data: &[u8]) -> Result<()> {
+        // upgrade to actual ref. if still alive
+        let publisher = self
+            .handle
+            .upgrade()
+            .ok_or(Error::RCL_RET_PUBLISHER_INVALID)?;
+
+        // rcl only reads from the buffer.
+        let msg = rmw_serialized_message_t {
+            buffer: data.as_ptr() as *mut u8,
+            buffer_length: data.len(),
+            buffer_capacity: data.len(),
+            allocator: unsafe { rcutils_get_default_allocator() },
+        };
+        let result = unsafe {
+            rcl_publish_serialized_message(publisher.as_ref(), &msg, std::ptr::null_mut())
+        };
+
+        if result == RCL_RET_OK as i32 {
+            Ok(())
+        } else {
             Err(Error::from_rcl_error(result))
         }
     }
 }
 
+impl Entity for PublisherSerialized {
+    fn name(&self) -> Result<String> {
+        handle_topic_name(&self.handle)
+    }
+
+    fn kind(&self) -> EntityKind {
+        EntityKind::Publisher
+    }
+}
+
 impl<T: 'static> Publisher<T>
 where
     T: WrappedTypesupport,
 {
+    /// Gets the number of subscriptions currently matched with this publisher.
+    pub fn get_inter_process_subscription_count(&self) -> Result<usize> {
+        // upgrade to actual ref. if still alive
+        let publisher = self
+            .handle
+            .upgrade()
+            .ok_or(Error::RCL_RET_PUBLISHER_INVALID)?;
+        publisher_subscription_count(publisher.as_ref())
+    }
+
+    /// Add a check that every message published through this publisher
+    /// or its clones has to pass, e.g. that a velocity is within its
+    /// limits. Validators run in the order they were added, and what
+    /// happens to a message that fails one is set with
+    /// `set_validation_policy`.
+    ///
+    /// ```ignore
+    /// publisher.add_validator(|msg: &Twist| {
+    ///     if msg.linear.x.abs() <= 1.0 {
+    ///         Ok(())
+    ///     } else {
+    ///         Err(format!("linear.x {} out of bounds", msg.linear.x))
+    ///     }
+    /// });
+    /// ```
+    pub fn add_validator<F>(&self, validator: F)
+    where
+        F: Fn(&T) -> std::result::Result<(), String> + Send + 'static,
+    {
+        self.validators
+            .lock()
+            .unwrap()
+            .add_check(Box::new(validator));
+    }
+
+    /// Add a validator that may change the message before it is
+    /// published, e.g. to clamp a velocity or normalize a quaternion.
+    /// An error is handled like the error of any other validator.
+    pub fn add_mutating_validator<F>(&self, validator: F)
+    where
+        F: Fn(&mut T) -> std::result::Result<(), String> + Send + 'static,
+    {
+        self.validators
+            .lock()
+            .unwrap()
+            .add_correction(Box::new(validator));
+    }
+
+    /// Add a validator that looks at the native message, which saves
+    /// converting messages published with `publish_native`.
+    pub fn add_native_validator<F>(&self, validator: F)
+    where
+        F: Fn(NativeMsgView<'_, T>) -> std::result::Result<(), String> + Send + 'static,
+    {
+        self.validators
+            .lock()
+            .unwrap()
+            .add_native_check(Box::new(validator));
+    }
+
+    /// What to do with messages that fail a validator, rejecting them
+    /// by default.
+    pub fn set_validation_policy(&self, policy: ValidationPolicy) {
+        self.validators.lock().unwrap().set_policy(policy);
+    }
+
+    /// Drop messages published faster than `max_hz`, by the ROS time
+    /// clock. Applies to this publisher and its clones, and can be
+    /// combined with `drop_if_older_than` and validators, which only
+    /// see the messages that are not dropped.
+    ///
+    /// `publish` returns `Ok` for dropped messages, `try_publish` tells
+    /// whether they were sent. The drops are counted by `dropped` and
+    /// in the `stats`.
+    ///
+    /// ```ignore
+    /// let publisher = node
+    ///     .create_publisher::<PoseStamped>("/pose")?
+    ///     .rate_limited(10.0)?
+    ///     .drop_if_older_than(Duration::from_millis(100))?;
+    /// ```
+    pub fn rate_limited(self, max_hz: f64) -> Result<Self> {
+        self.gates.lock().unwrap().add_rate_limit(max_hz)?;
+        Ok(self)
+    }
+
+    /// Drop messages whose header stamp is more than `max_age` before
+    /// the current ROS time, see `rate_limited`.
+    pub fn drop_if_older_than(self, max_age: Duration) -> Result<Self>
+    where
+        T: HasHeader,
+    {
+        self.gates
+            .lock()
+            .unwrap()
+            .add_max_age(max_age, <T as HasHeader>::stamp)?;
+        Ok(self)
+    }
+
+    /// Number of messages dropped by `rate_limited` and
+    /// `drop_if_older_than`.
+    pub fn dropped(&self) -> DropCounts {
+        self.gates.lock().unwrap().dropped()
+    }
+
     /// Publish a ROS message.
+    ///
+    /// Fails with `Error::ValidationFailed` if the message is rejected
+    /// by a validator.
     pub fn publish(&self, msg: &T) -> Result<()>
+    where
+        T: WrappedTypesupport,
+    {
+        self.try_publish(msg).map(|_| ())
+    }
+
+    /// Publish a ROS message, unless it is dropped by `rate_limited`
+    /// or `drop_if_older_than`.
+    pub fn try_publish(&self, msg: &T) -> Result<PublishOutcome>
     where
         T: WrappedTypesupport,
     {
@@ -144,7 +664,12 @@ where
             .handle
             .upgrade()
             .ok_or(Error::RCL_RET_PUBLISHER_INVALID)?;
-        let native_msg: WrappedNativeMsg<T> = WrappedNativeMsg::<T>::from(msg);
+        let mut candidate = Candidate::from_msg(msg);
+        if let Some(reason) = self.admit(&mut candidate)? {
+            return Ok(PublishOutcome::Dropped(reason));
+        }
+        self.validators.lock().unwrap().validate(&mut candidate)?;
+        let native_msg = candidate.native();
         let result = unsafe {
             rcl_publish(
                 publisher.as_ref(),
@@ -154,10 +679,12 @@ where
         };
 
         if result == RCL_RET_OK as i32 {
-            Ok(())
+            self.record_stats(native_msg);
+            Ok(PublishOutcome::Sent)
         } else {
-            eprintln!("coult not publish {}", result);
-            Err(Error::from_rcl_error(result))
+            let err = Error::from_rcl_error(result);
+            log_internal(LogSeverity::Warn, &format!("could not publish: {}", err));
+            Err(err)
         }
     }
 
@@ -165,6 +692,9 @@ where
     ///
     /// This function is useful if you want to bypass the generated
     /// rust types as it lets you work with the raw C struct.
+    ///
+    /// The message is validated like in `publish`. Validators other
+    /// than the native ones need it converted.
     pub fn publish_native(&self, msg: &WrappedNativeMsg<T>) -> Result<()>
     where
         T: WrappedTypesupport,
@@ -174,14 +704,93 @@ where
             .handle
             .upgrade()
             .ok_or(Error::RCL_RET_PUBLISHER_INVALID)?;
+        let mut candidate = Candidate::from_native(msg);
+        if self.admit(&mut candidate)?.is_some() {
+            return Ok(());
+        }
+        self.validators.lock().unwrap().validate(&mut candidate)?;
+        let msg = candidate.native();
 
         let result =
             unsafe { rcl_publish(publisher.as_ref(), msg.void_ptr(), std::ptr::null_mut()) };
         if result == RCL_RET_OK as i32 {
+            self.record_stats(msg);
             Ok(())
         } else {
-            eprintln!("could not publish native {}", result);
-            Err(Error::from_rcl_error(result))
+            let err = Error::from_rcl_error(result);
+            log_internal(
+                LogSeverity::Warn,
+                &format!("could not publish native: {}", err),
+            );
+            Err(err)
         }
     }
+
+    /// Message statistics of this publisher, if enabled in the
+    /// `PublisherOptions`.
+    pub fn stats(&self) -> Option<TopicStats> {
+        self.stats.as_ref().map(|s| s.lock().unwrap().stats())
+    }
+
+    pub(crate) fn validate(&self, candidate: &mut Candidate<'_, T>) -> Result<()> {
+        self.validators.lock().unwrap().validate(candidate)
+    }
+
+    /// Publish a message that has been through the validators already.
+    pub(crate) fn publish_validated(&self, msg: &WrappedNativeMsg<T>) -> Result<()> {
+        let publisher = self
+            .handle
+            .upgrade()
+            .ok_or(Error::RCL_RET_PUBLISHER_INVALID)?;
+        let result =
+            unsafe { rcl_publish(publisher.as_ref(), msg.void_ptr(), std::ptr::null_mut()) };
+        if result == RCL_RET_OK as i32 {
+            self.record_stats(msg);
+            Ok(())
+        } else {
+            let err = Error::from_rcl_error(result);
+            log_internal(LogSeverity::Warn, &format!("could not publish: {}", err));
+            Err(err)
+        }
+    }
+
+    fn admit(&self, candidate: &mut Candidate<'_, T>) -> Result<Option<DropReason>> {
+        let dropped = self.gates.lock().unwrap().admit(candidate)?;
+        if let (Some(_), Some(stats)) = (dropped, &self.stats) {
+            stats.lock().unwrap().record_dropped();
+        }
+        Ok(dropped)
+    }
+
+    fn record_stats(&self, msg: &WrappedNativeMsg<T>) {
+        if let Some(stats) = &self.stats {
+            stats.lock().unwrap().record(
+                T::get_ts(),
+                msg.void_ptr(),
+                std::mem::size_of::<T::CStruct>(),
+            );
+        }
+    }
+}
+
+impl<T: 'static> Publish<T> for Publisher<T>
+where
+    T: WrappedTypesupport,
+{
+    fn publish(&self, msg: &T) -> Result<()> {
+        Publisher::publish(self, msg)
+    }
+}
+
+impl<T> Entity for Publisher<T>
+where
+    T: WrappedTypesupport,
+{
+    fn name(&self) -> Result<String> {
+        handle_topic_name(&self.handle)
+    }
+
+    fn kind(&self) -> EntityKind {
+        EntityKind::Publisher
+    }
 }
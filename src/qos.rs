@@ -0,0 +1,683 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::time::Duration;
+
+use crate::distro;
+use crate::error::*;
+use r2r_rcl::*;
+
+/// QoS history policy.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryPolicy {
+    SystemDefault,
+    KeepLast,
+    KeepAll,
+    /// A policy r2r does not know, e.g. in a profile from the ROS graph.
+    Unknown,
+}
+
+/// QoS reliability policy.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReliabilityPolicy {
+    SystemDefault,
+    Reliable,
+    BestEffort,
+    /// Match the publishers, iron and newer.
+    BestAvailable,
+    Unknown,
+}
+
+/// QoS durability policy.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DurabilityPolicy {
+    SystemDefault,
+    TransientLocal,
+    Volatile,
+    /// Match the publishers, iron and newer.
+    BestAvailable,
+    Unknown,
+}
+
+/// QoS liveliness policy.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LivelinessPolicy {
+    SystemDefault,
+    Automatic,
+    ManualByNode,
+    ManualByTopic,
+    /// Match the publishers, iron and newer.
+    BestAvailable,
+    Unknown,
+}
+
+/// A ROS QoS profile.
+///
+/// The serialized form uses snake case strings for the policies and
+/// seconds (as a float) for durations. A zero duration means
+/// "infinite" (i.e. the rmw default) and is serialized as such, as
+/// are durations of `i32::MAX` seconds and more, like the infinite
+/// duration of rmw. Fields that are left out when deserializing take
+/// their values from `QosProfile::default()`.
+///
+/// Converting to and from `rmw_qos_profile_t` is lossless, policies
+/// unknown to r2r become `Unknown`. `BestAvailable` policies become
+/// unknown rmw policies before iron.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QosProfile {
+    pub history: HistoryPolicy,
+    /// Number of samples kept for `KeepLast`.
+    pub depth: usize,
+    pub reliability: ReliabilityPolicy,
+    pub durability: DurabilityPolicy,
+    #[serde(with = "duration_serde")]
+    pub deadline: Duration,
+    #[serde(with = "duration_serde")]
+    pub lifespan: Duration,
+    pub liveliness: LivelinessPolicy,
+    #[serde(with = "duration_serde")]
+    pub liveliness_lease_duration: Duration,
+    pub avoid_ros_namespace_conventions: bool,
+}
+
+impl Default for QosProfile {
+    /// The default profile for publishers and subscriptions.
+    fn default() -> Self {
+        QosProfile {
+            history: HistoryPolicy::KeepLast,
+            depth: 10,
+            reliability: ReliabilityPolicy::Reliable,
+            durability: DurabilityPolicy::Volatile,
+            deadline: Duration::from_secs(0),
+            lifespan: Duration::from_secs(0),
+            liveliness: LivelinessPolicy::SystemDefault,
+            liveliness_lease_duration: Duration::from_secs(0),
+            avoid_ros_namespace_conventions: false,
+        }
+    }
+}
+
+impl QosProfile {
+    /// Profile for sensor data, best effort with a small queue.
+    pub fn sensor_data() -> Self {
+        QosProfile {
+            depth: 5,
+            reliability: ReliabilityPolicy::BestEffort,
+            ..QosProfile::default()
+        }
+    }
+
+    /// Profile for parameter services.
+    pub fn parameters() -> Self {
+        QosProfile {
+            depth: 1000,
+            ..QosProfile::default()
+        }
+    }
+
+    /// Profile for services.
+    pub fn services_default() -> Self {
+        QosProfile::default()
+    }
+
+    /// Profile for parameter events.
+    pub fn parameter_events() -> Self {
+        QosProfile {
+            depth: 1000,
+            ..QosProfile::default()
+        }
+    }
+
+    /// Use the defaults of the rmw implementation for everything.
+    pub fn system_default() -> Self {
+        QosProfile {
+            history: HistoryPolicy::SystemDefault,
+            depth: 0,
+            reliability: ReliabilityPolicy::SystemDefault,
+            durability: DurabilityPolicy::SystemDefault,
+            ..QosProfile::default()
+        }
+    }
+
+    /// Keep the last `depth` samples.
+    pub fn keep_last(self, depth: usize) -> Self {
+        QosProfile {
+            history: HistoryPolicy::KeepLast,
+            depth,
+            ..self
+        }
+    }
+
+    /// Keep all samples.
+    pub fn keep_all(self) -> Self {
+        QosProfile {
+            history: HistoryPolicy::KeepAll,
+            depth: 0,
+            ..self
+        }
+    }
+
+    pub fn reliable(self) -> Self {
+        QosProfile {
+            reliability: ReliabilityPolicy::Reliable,
+            ..self
+        }
+    }
+
+    pub fn best_effort(self) -> Self {
+        QosProfile {
+            reliability: ReliabilityPolicy::BestEffort,
+            ..self
+        }
+    }
+
+    pub fn volatile(self) -> Self {
+        QosProfile {
+            durability: DurabilityPolicy::Volatile,
+            ..self
+        }
+    }
+
+    pub fn transient_local(self) -> Self {
+        QosProfile {
+            durability: DurabilityPolicy::TransientLocal,
+            ..self
+        }
+    }
+
+    pub fn deadline(self, deadline: Duration) -> Self {
+        QosProfile { deadline, ..self }
+    }
+
+    pub fn lifespan(self, lifespan: Duration) -> Self {
+        QosProfile { lifespan, ..self }
+    }
+
+    pub fn liveliness(self, liveliness: LivelinessPolicy, lease_duration: Duration) -> Self {
+        QosProfile {
+            liveliness,
+            liveliness_lease_duration: lease_duration,
+            ..self
+        }
+    }
+
+    /// Checks that the profile makes sense.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: &str| {
+            Err(Error::InvalidQosProfile {
+                reason: reason.to_owned(),
+            })
+        };
+        if self.has_unknown_policy() {
+            return invalid("unknown policies cannot be used");
+        }
+        match self.history {
+            HistoryPolicy::KeepAll if self.depth != 0 => {
+                invalid("depth cannot be set together with keep_all history")
+            }
+            HistoryPolicy::KeepLast if self.depth == 0 => {
+                invalid("keep_last history needs a depth larger than zero")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn has_unknown_policy(&self) -> bool {
+        self.history == HistoryPolicy::Unknown
+            || self.reliability == ReliabilityPolicy::Unknown
+            || self.durability == DurabilityPolicy::Unknown
+            || self.liveliness == LivelinessPolicy::Unknown
+    }
+
+    /// Parses and validates a profile from yaml, e.g.
+    ///
+    /// ```yaml
+    /// history: keep_last
+    /// depth: 1
+    /// reliability: reliable
+    /// durability: transient_local
+    /// deadline: 0.1
+    /// ```
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml_str(yaml: &str) -> Result<Self> {
+        let profile: QosProfile = serde_yaml::from_str(yaml).map_err(|e| Error::SerdeError {
+            err: e.to_string(),
+        })?;
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    /// Reasons why a subscription with this profile will not receive
+    /// messages from a publisher with the `publisher` profile, empty
+    /// if they are compatible. System default policies are assumed to
+    /// be compatible with anything.
+    pub fn incompatibilities_with(&self, publisher: &QosProfile) -> Vec<String> {
+        let mut reasons = vec![];
+        if self.reliability == ReliabilityPolicy::Reliable
+            && publisher.reliability == ReliabilityPolicy::BestEffort
+        {
+            reasons.push("publisher is best effort but the subscription is reliable".to_owned());
+        }
+        if self.durability == DurabilityPolicy::TransientLocal
+            && publisher.durability == DurabilityPolicy::Volatile
+        {
+            reasons
+                .push("publisher is volatile but the subscription is transient local".to_owned());
+        }
+        let longer = |p: Duration, s: Duration| !is_infinite(&s) && (is_infinite(&p) || p > s);
+        if longer(publisher.deadline, self.deadline) {
+            reasons.push("publisher deadline is longer than the subscription deadline".to_owned());
+        }
+        let strength = |l: LivelinessPolicy| match l {
+            LivelinessPolicy::SystemDefault
+            | LivelinessPolicy::BestAvailable
+            | LivelinessPolicy::Unknown => None,
+            LivelinessPolicy::Automatic => Some(0),
+            LivelinessPolicy::ManualByNode => Some(1),
+            LivelinessPolicy::ManualByTopic => Some(2),
+        };
+        if let (Some(p), Some(s)) = (strength(publisher.liveliness), strength(self.liveliness)) {
+            if p < s {
+                reasons.push(format!(
+                    "publisher liveliness {:?} is weaker than the subscription liveliness {:?}",
+                    publisher.liveliness, self.liveliness
+                ));
+            }
+        }
+        if longer(
+            publisher.liveliness_lease_duration,
+            self.liveliness_lease_duration,
+        ) {
+            reasons.push(
+                "publisher liveliness lease is longer than the subscription lease".to_owned(),
+            );
+        }
+        reasons
+    }
+}
+
+impl From<&QosProfile> for rmw_qos_profile_t {
+    fn from(qos: &QosProfile) -> Self {
+        use rmw_qos_durability_policy_t::*;
+        use rmw_qos_history_policy_t::*;
+        use rmw_qos_liveliness_policy_t::*;
+        use rmw_qos_reliability_policy_t::*;
+
+        let mut profile = rmw_qos_profile_t::default();
+        profile.history = match qos.history {
+            HistoryPolicy::SystemDefault => RMW_QOS_POLICY_HISTORY_SYSTEM_DEFAULT,
+            HistoryPolicy::KeepLast => RMW_QOS_POLICY_HISTORY_KEEP_LAST,
+            HistoryPolicy::KeepAll => RMW_QOS_POLICY_HISTORY_KEEP_ALL,
+            HistoryPolicy::Unknown => RMW_QOS_POLICY_HISTORY_UNKNOWN,
+        };
+        profile.depth = qos.depth;
+        profile.reliability = match qos.reliability {
+            ReliabilityPolicy::SystemDefault => RMW_QOS_POLICY_RELIABILITY_SYSTEM_DEFAULT,
+            ReliabilityPolicy::Reliable => RMW_QOS_POLICY_RELIABILITY_RELIABLE,
+            ReliabilityPolicy::BestEffort => RMW_QOS_POLICY_RELIABILITY_BEST_EFFORT,
+            ReliabilityPolicy::BestAvailable => {
+                distro::RELIABILITY_BEST_AVAILABLE.unwrap_or(RMW_QOS_POLICY_RELIABILITY_UNKNOWN)
+            }
+            ReliabilityPolicy::Unknown => RMW_QOS_POLICY_RELIABILITY_UNKNOWN,
+        };
+        profile.durability = match qos.durability {
+            DurabilityPolicy::SystemDefault => RMW_QOS_POLICY_DURABILITY_SYSTEM_DEFAULT,
+            DurabilityPolicy::TransientLocal => RMW_QOS_POLICY_DURABILITY_TRANSIENT_LOCAL,
+            DurabilityPolicy::Volatile => RMW_QOS_POLICY_DURABILITY_VOLATILE,
+            DurabilityPolicy::BestAvailable => {
+                distro::DURABILITY_BEST_AVAILABLE.unwrap_or(RMW_QOS_POLICY_DURABILITY_UNKNOWN)
+            }
+            DurabilityPolicy::Unknown => RMW_QOS_POLICY_DURABILITY_UNKNOWN,
+        };
+        profile.deadline = duration_to_rmw(&qos.deadline);
+        profile.lifespan = duration_to_rmw(&qos.lifespan);
+        profile.liveliness = match qos.liveliness {
+            LivelinessPolicy::SystemDefault => RMW_QOS_POLICY_LIVELINESS_SYSTEM_DEFAULT,
+            LivelinessPolicy::Automatic => RMW_QOS_POLICY_LIVELINESS_AUTOMATIC,
+            LivelinessPolicy::ManualByNode => RMW_QOS_POLICY_LIVELINESS_MANUAL_BY_NODE,
+            LivelinessPolicy::ManualByTopic => RMW_QOS_POLICY_LIVELINESS_MANUAL_BY_TOPIC,
+            LivelinessPolicy::BestAvailable => {
+                distro::LIVELINESS_BEST_AVAILABLE.unwrap_or(RMW_QOS_POLICY_LIVELINESS_UNKNOWN)
+            }
+            LivelinessPolicy::Unknown => RMW_QOS_POLICY_LIVELINESS_UNKNOWN,
+        };
+        profile.liveliness_lease_duration = duration_to_rmw(&qos.liveliness_lease_duration);
+        profile.avoid_ros_namespace_conventions = qos.avoid_ros_namespace_conventions;
+        profile
+    }
+}
+
+impl From<QosProfile> for rmw_qos_profile_t {
+    fn from(qos: QosProfile) -> Self {
+        rmw_qos_profile_t::from(&qos)
+    }
+}
+
+impl From<&rmw_qos_profile_t> for QosProfile {
+    fn from(profile: &rmw_qos_profile_t) -> Self {
+        use rmw_qos_durability_policy_t::*;
+        use rmw_qos_history_policy_t::*;
+        use rmw_qos_liveliness_policy_t::*;
+        use rmw_qos_reliability_policy_t::*;
+
+        let history = match profile.history {
+            RMW_QOS_POLICY_HISTORY_SYSTEM_DEFAULT => HistoryPolicy::SystemDefault,
+            RMW_QOS_POLICY_HISTORY_KEEP_LAST => HistoryPolicy::KeepLast,
+            RMW_QOS_POLICY_HISTORY_KEEP_ALL => HistoryPolicy::KeepAll,
+            _ => HistoryPolicy::Unknown,
+        };
+        let reliability = match profile.reliability {
+            RMW_QOS_POLICY_RELIABILITY_SYSTEM_DEFAULT => ReliabilityPolicy::SystemDefault,
+            RMW_QOS_POLICY_RELIABILITY_RELIABLE => ReliabilityPolicy::Reliable,
+            RMW_QOS_POLICY_RELIABILITY_BEST_EFFORT => ReliabilityPolicy::BestEffort,
+            p if Some(p) == distro::RELIABILITY_BEST_AVAILABLE => ReliabilityPolicy::BestAvailable,
+            _ => ReliabilityPolicy::Unknown,
+        };
+        let durability = match profile.durability {
+            RMW_QOS_POLICY_DURABILITY_SYSTEM_DEFAULT => DurabilityPolicy::SystemDefault,
+            RMW_QOS_POLICY_DURABILITY_TRANSIENT_LOCAL => DurabilityPolicy::TransientLocal,
+            RMW_QOS_POLICY_DURABILITY_VOLATILE => DurabilityPolicy::Volatile,
+            p if Some(p) == distro::DURABILITY_BEST_AVAILABLE => DurabilityPolicy::BestAvailable,
+            _ => DurabilityPolicy::Unknown,
+        };
+        let liveliness = match profile.liveliness {
+            RMW_QOS_POLICY_LIVELINESS_SYSTEM_DEFAULT => LivelinessPolicy::SystemDefault,
+            RMW_QOS_POLICY_LIVELINESS_AUTOMATIC => LivelinessPolicy::Automatic,
+            RMW_QOS_POLICY_LIVELINESS_MANUAL_BY_NODE => LivelinessPolicy::ManualByNode,
+            RMW_QOS_POLICY_LIVELINESS_MANUAL_BY_TOPIC => LivelinessPolicy::ManualByTopic,
+            p if Some(p) == distro::LIVELINESS_BEST_AVAILABLE => LivelinessPolicy::BestAvailable,
+            _ => LivelinessPolicy::Unknown,
+        };
+        QosProfile {
+            history,
+            depth: profile.depth,
+            reliability,
+            durability,
+            deadline: duration_from_rmw(&profile.deadline),
+            lifespan: duration_from_rmw(&profile.lifespan),
+            liveliness,
+            liveliness_lease_duration: duration_from_rmw(&profile.liveliness_lease_duration),
+            avoid_ros_namespace_conventions: profile.avoid_ros_namespace_conventions,
+        }
+    }
+}
+
+impl From<rmw_qos_profile_t> for QosProfile {
+    fn from(profile: rmw_qos_profile_t) -> Self {
+        QosProfile::from(&profile)
+    }
+}
+
+fn duration_to_rmw(d: &Duration) -> rmw_time_t {
+    rmw_time_t {
+        sec: d.as_secs(),
+        nsec: d.subsec_nanos() as u64,
+    }
+}
+
+// Keeps the infinite duration of rmw as it is, so that converting
+// back gives the same profile.
+fn duration_from_rmw(t: &rmw_time_t) -> Duration {
+    Duration::from_secs(t.sec)
+        .checked_add(Duration::from_nanos(t.nsec))
+        .unwrap_or(Duration::MAX)
+}
+
+// rmw uses zero and very large values for "infinite".
+fn is_infinite(d: &Duration) -> bool {
+    *d == Duration::from_secs(0) || d.as_secs() >= i32::MAX as u64
+}
+
+// Durations are written as seconds, with zero meaning "infinite".
+mod duration_serde {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum DurationRepr {
+        Seconds(f64),
+        Named(String),
+    }
+
+    pub fn serialize<S: Serializer>(d: &Duration, s: S) -> std::result::Result<S::Ok, S::Error> {
+        if is_infinite(d) {
+            DurationRepr::Named("infinite".to_owned()).serialize(s)
+        } else {
+            DurationRepr::Seconds(d.as_secs_f64()).serialize(s)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> std::result::Result<Duration, D::Error> {
+        match DurationRepr::deserialize(d)? {
+            DurationRepr::Seconds(s) if s.is_finite() && s >= 0.0 => Ok(Duration::from_secs_f64(s)),
+            DurationRepr::Seconds(s) => Err(serde::de::Error::custom(format!(
+                "invalid duration: {}",
+                s
+            ))),
+            DurationRepr::Named(n) if n == "infinite" => Ok(Duration::from_secs(0)),
+            DurationRepr::Named(n) => Err(serde::de::Error::custom(format!(
+                "invalid duration: {}, expected seconds or \"infinite\"",
+                n
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::seq::SliceRandom;
+    use rand::Rng;
+
+    #[test]
+    fn test_qos_serde_round_trip() -> () {
+        let profiles = vec![
+            QosProfile::default(),
+            QosProfile::sensor_data(),
+            QosProfile::parameters(),
+            QosProfile::services_default(),
+            QosProfile::parameter_events(),
+            QosProfile::system_default(),
+            QosProfile::default()
+                .keep_all()
+                .transient_local()
+                .deadline(Duration::from_millis(250))
+                .lifespan(Duration::from_secs(3))
+                .liveliness(LivelinessPolicy::ManualByTopic, Duration::from_millis(500)),
+        ];
+        for p in profiles {
+            #[cfg(feature = "yaml")]
+            {
+                let yaml = serde_yaml::to_string(&p).unwrap();
+                assert_eq!(QosProfile::from_yaml_str(&yaml).unwrap(), p);
+            }
+            let json = serde_json::to_string(&p).unwrap();
+            assert_eq!(serde_json::from_str::<QosProfile>(&json).unwrap(), p);
+        }
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_qos_from_yaml_str() -> () {
+        let p = QosProfile::from_yaml_str(
+            "reliability: best_effort\ndurability: transient_local\ndeadline: 0.5\nlifespan: infinite\n",
+        )
+        .unwrap();
+        assert_eq!(
+            p,
+            QosProfile::default()
+                .best_effort()
+                .transient_local()
+                .deadline(Duration::from_millis(500))
+        );
+        assert_eq!(
+            serde_json::to_value(&p).unwrap()["lifespan"],
+            serde_json::json!("infinite")
+        );
+
+        assert!(QosProfile::from_yaml_str("history: keep_all\ndepth: 10\n").is_err());
+        assert!(QosProfile::from_yaml_str("history: keep_last\ndepth: 0\n").is_err());
+        assert!(QosProfile::from_yaml_str("reliability: sometimes\n").is_err());
+        assert!(QosProfile::from_yaml_str("deadline: -1.0\n").is_err());
+    }
+
+    #[test]
+    fn test_qos_incompatibilities() -> () {
+        let sub = QosProfile::default().transient_local();
+        assert!(sub.incompatibilities_with(&sub).is_empty());
+        assert!(QosProfile::default()
+            .incompatibilities_with(&sub)
+            .is_empty());
+        assert_eq!(
+            sub.incompatibilities_with(&QosProfile::default().best_effort())
+                .len(),
+            2
+        );
+
+        let sub = QosProfile::default().deadline(Duration::from_millis(100));
+        assert!(sub
+            .incompatibilities_with(&sub.clone().deadline(Duration::from_millis(50)))
+            .is_empty());
+        assert_eq!(sub.incompatibilities_with(&QosProfile::default()).len(), 1);
+
+        let sub = QosProfile::default()
+            .liveliness(LivelinessPolicy::ManualByTopic, Duration::from_secs(0));
+        let publisher =
+            QosProfile::default().liveliness(LivelinessPolicy::Automatic, Duration::from_secs(0));
+        assert_eq!(sub.incompatibilities_with(&publisher).len(), 1);
+        assert!(publisher.incompatibilities_with(&sub).is_empty());
+    }
+
+    fn random_duration(rng: &mut impl Rng) -> Duration {
+        match rng.gen_range(0..4) {
+            0 => Duration::from_secs(0),
+            // RMW_DURATION_INFINITE
+            1 => Duration::new(9_223_372_036, 854_775_807),
+            _ => Duration::new(rng.gen_range(0..100_000), rng.gen_range(0..1_000_000_000)),
+        }
+    }
+
+    fn random_rmw_time(rng: &mut impl Rng) -> rmw_time_t {
+        let sec = [
+            0,
+            i32::MAX as u64,
+            i64::MAX as u64,
+            rng.gen_range(0..100_000),
+        ];
+        rmw_time_t {
+            sec: *sec.choose(rng).unwrap(),
+            nsec: rng.gen_range(0..1_000_000_000),
+        }
+    }
+
+    #[test]
+    fn test_qos_to_rmw_round_trip() -> () {
+        let mut reliability = vec![
+            ReliabilityPolicy::SystemDefault,
+            ReliabilityPolicy::Reliable,
+            ReliabilityPolicy::BestEffort,
+            ReliabilityPolicy::Unknown,
+        ];
+        let mut durability = vec![
+            DurabilityPolicy::SystemDefault,
+            DurabilityPolicy::TransientLocal,
+            DurabilityPolicy::Volatile,
+            DurabilityPolicy::Unknown,
+        ];
+        let mut liveliness = vec![
+            LivelinessPolicy::SystemDefault,
+            LivelinessPolicy::Automatic,
+            LivelinessPolicy::ManualByNode,
+            LivelinessPolicy::ManualByTopic,
+            LivelinessPolicy::Unknown,
+        ];
+        // rmw has no best available policies before iron.
+        if distro::RELIABILITY_BEST_AVAILABLE.is_some() {
+            reliability.push(ReliabilityPolicy::BestAvailable);
+            durability.push(DurabilityPolicy::BestAvailable);
+            liveliness.push(LivelinessPolicy::BestAvailable);
+        }
+        let history = [
+            HistoryPolicy::SystemDefault,
+            HistoryPolicy::KeepLast,
+            HistoryPolicy::KeepAll,
+            HistoryPolicy::Unknown,
+        ];
+        let mut rng = rand::thread_rng();
+        for _ in 0..1000 {
+            let p = QosProfile {
+                history: *history.choose(&mut rng).unwrap(),
+                depth: rng.gen_range(0..1000),
+                reliability: *reliability.choose(&mut rng).unwrap(),
+                durability: *durability.choose(&mut rng).unwrap(),
+                deadline: random_duration(&mut rng),
+                lifespan: random_duration(&mut rng),
+                liveliness: *liveliness.choose(&mut rng).unwrap(),
+                liveliness_lease_duration: random_duration(&mut rng),
+                avoid_ros_namespace_conventions: rng.gen(),
+            };
+            assert_eq!(QosProfile::from(rmw_qos_profile_t::from(&p)), p);
+        }
+    }
+
+    #[test]
+    fn test_rmw_to_qos_round_trip() -> () {
+        use rmw_qos_durability_policy_t::*;
+        use rmw_qos_history_policy_t::*;
+        use rmw_qos_liveliness_policy_t::*;
+        use rmw_qos_reliability_policy_t::*;
+
+        let mut reliability = vec![
+            RMW_QOS_POLICY_RELIABILITY_SYSTEM_DEFAULT,
+            RMW_QOS_POLICY_RELIABILITY_RELIABLE,
+            RMW_QOS_POLICY_RELIABILITY_BEST_EFFORT,
+            RMW_QOS_POLICY_RELIABILITY_UNKNOWN,
+        ];
+        reliability.extend(distro::RELIABILITY_BEST_AVAILABLE);
+        let mut durability = vec![
+            RMW_QOS_POLICY_DURABILITY_SYSTEM_DEFAULT,
+            RMW_QOS_POLICY_DURABILITY_TRANSIENT_LOCAL,
+            RMW_QOS_POLICY_DURABILITY_VOLATILE,
+            RMW_QOS_POLICY_DURABILITY_UNKNOWN,
+        ];
+        durability.extend(distro::DURABILITY_BEST_AVAILABLE);
+        let mut liveliness = vec![
+            RMW_QOS_POLICY_LIVELINESS_SYSTEM_DEFAULT,
+            RMW_QOS_POLICY_LIVELINESS_AUTOMATIC,
+            RMW_QOS_POLICY_LIVELINESS_MANUAL_BY_NODE,
+            RMW_QOS_POLICY_LIVELINESS_MANUAL_BY_TOPIC,
+            RMW_QOS_POLICY_LIVELINESS_UNKNOWN,
+        ];
+        liveliness.extend(distro::LIVELINESS_BEST_AVAILABLE);
+        let history = [
+            RMW_QOS_POLICY_HISTORY_SYSTEM_DEFAULT,
+            RMW_QOS_POLICY_HISTORY_KEEP_LAST,
+            RMW_QOS_POLICY_HISTORY_KEEP_ALL,
+            RMW_QOS_POLICY_HISTORY_UNKNOWN,
+        ];
+        let mut rng = rand::thread_rng();
+        for _ in 0..1000 {
+            let mut p = rmw_qos_profile_t::default();
+            p.history = *history.choose(&mut rng).unwrap();
+            p.depth = rng.gen_range(0..1000);
+            p.reliability = *reliability.choose(&mut rng).unwrap();
+            p.durability = *durability.choose(&mut rng).unwrap();
+            p.deadline = random_rmw_time(&mut rng);
+            p.lifespan = random_rmw_time(&mut rng);
+            p.liveliness = *liveliness.choose(&mut rng).unwrap();
+            p.liveliness_lease_duration = random_rmw_time(&mut rng);
+            p.avoid_ros_namespace_conventions = rng.gen();
+            assert_eq!(rmw_qos_profile_t::from(QosProfile::from(&p)), p);
+        }
+
+        // unknown policies are kept, but cannot be used.
+        let mut p = rmw_qos_profile_t::default();
+        p.history = RMW_QOS_POLICY_HISTORY_UNKNOWN;
+        let qos = QosProfile::from(p);
+        assert_eq!(qos.history, HistoryPolicy::Unknown);
+        assert!(qos.validate().is_err());
+    }
+}
@@ -0,0 +1,378 @@
+//! QoS overrides from parameters, following the rclcpp convention.
+//!
+//! A publisher created with `QosOverridePolicy::Declare` declares the
+//! parameters `qos_overrides.<topic>.publisher.<policy>` (subscriptions
+//! use `subscription` instead of `publisher`), where `<topic>` is the
+//! fully qualified topic name. Values given on the command line or in a
+//! parameter file replace the policies of the profile in code, so that
+//! the same launch files work for rclcpp and r2r nodes. Policies are
+//! given as in rclcpp: strings like `best_effort` or `transient_local`,
+//! the depth as an integer and durations in nanoseconds.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::*;
+use crate::parameters::ParameterValue;
+use crate::qos::*;
+
+/// A QoS policy that can be overridden by a parameter.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QosPolicyKind {
+    History,
+    Depth,
+    Reliability,
+    Durability,
+    Deadline,
+    Lifespan,
+    Liveliness,
+    LivelinessLeaseDuration,
+}
+
+impl QosPolicyKind {
+    /// The last part of the parameter name.
+    pub fn parameter_name(&self) -> &'static str {
+        match self {
+            QosPolicyKind::History => "history",
+            QosPolicyKind::Depth => "depth",
+            QosPolicyKind::Reliability => "reliability",
+            QosPolicyKind::Durability => "durability",
+            QosPolicyKind::Deadline => "deadline",
+            QosPolicyKind::Lifespan => "lifespan",
+            QosPolicyKind::Liveliness => "liveliness",
+            QosPolicyKind::LivelinessLeaseDuration => "liveliness_lease_duration",
+        }
+    }
+}
+
+/// Checks the profile after the overrides have been applied. An error
+/// with the reason makes creating the publisher or subscription fail.
+#[derive(Clone)]
+pub struct QosValidationCallback(
+    Arc<dyn Fn(&QosProfile) -> std::result::Result<(), String> + Send + Sync>,
+);
+
+impl QosValidationCallback {
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(&QosProfile) -> std::result::Result<(), String> + Send + Sync + 'static,
+    {
+        QosValidationCallback(Arc::new(callback))
+    }
+}
+
+impl fmt::Debug for QosValidationCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "QosValidationCallback")
+    }
+}
+
+/// Which policies can be overridden, see `QosOverridePolicy`.
+#[derive(Debug, Clone)]
+pub struct QosOverridingOptions {
+    pub policies: Vec<QosPolicyKind>,
+    /// Distinguishes several publishers (or subscriptions) of a node on
+    /// the same topic, the parameters are then named
+    /// `qos_overrides.<topic>.publisher_<id>.<policy>`.
+    pub id: Option<String>,
+    pub validation: Option<QosValidationCallback>,
+}
+
+impl Default for QosOverridingOptions {
+    /// History, depth, reliability and durability can be overridden.
+    fn default() -> Self {
+        QosOverridingOptions {
+            policies: vec![
+                QosPolicyKind::History,
+                QosPolicyKind::Depth,
+                QosPolicyKind::Reliability,
+                QosPolicyKind::Durability,
+            ],
+            id: None,
+            validation: None,
+        }
+    }
+}
+
+impl QosOverridingOptions {
+    pub fn with_validation<F>(self, callback: F) -> Self
+    where
+        F: Fn(&QosProfile) -> std::result::Result<(), String> + Send + Sync + 'static,
+    {
+        QosOverridingOptions {
+            validation: Some(QosValidationCallback::new(callback)),
+            ..self
+        }
+    }
+}
+
+/// Whether the QoS of a publisher or subscription can be overridden by
+/// parameters.
+#[derive(Debug, Clone)]
+pub enum QosOverridePolicy {
+    /// Use the profile given in code.
+    Ignore,
+    /// Declare the override parameters and apply their values.
+    Declare(QosOverridingOptions),
+}
+
+impl Default for QosOverridePolicy {
+    fn default() -> Self {
+        QosOverridePolicy::Ignore
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum QosEntityKind {
+    Publisher,
+    Subscription,
+}
+
+/// Declares the override parameters of a publisher or subscription on
+/// the fully qualified `topic`, and returns `qos` with the values of
+/// the parameters that were already set applied.
+pub(crate) fn declare_qos_overrides(
+    params: &mut HashMap<String, ParameterValue>,
+    topic: &str,
+    entity: QosEntityKind,
+    options: &QosOverridingOptions,
+    mut qos: QosProfile,
+) -> Result<QosProfile> {
+    let entity = match (entity, &options.id) {
+        (QosEntityKind::Publisher, None) => "publisher".to_owned(),
+        (QosEntityKind::Subscription, None) => "subscription".to_owned(),
+        (QosEntityKind::Publisher, Some(id)) => format!("publisher_{}", id),
+        (QosEntityKind::Subscription, Some(id)) => format!("subscription_{}", id),
+    };
+    for policy in &options.policies {
+        let name = format!(
+            "qos_overrides.{}.{}.{}",
+            topic,
+            entity,
+            policy.parameter_name()
+        );
+        match params.get(&name) {
+            None | Some(ParameterValue::NotSet) => {
+                params.insert(name, policy_value(&qos, *policy));
+            }
+            Some(value) => {
+                apply_policy(&mut qos, *policy, value).map_err(|reason| {
+                    Error::InvalidParameter {
+                        name: name.clone(),
+                        reason,
+                    }
+                })?;
+            }
+        }
+    }
+    // the depth is meaningless then, as in rclcpp.
+    if qos.history == HistoryPolicy::KeepAll {
+        qos.depth = 0;
+    }
+    qos.validate()?;
+    if let Some(validation) = &options.validation {
+        (validation.0)(&qos).map_err(|reason| Error::InvalidQosProfile { reason })?;
+    }
+    Ok(qos)
+}
+
+fn policy_value(qos: &QosProfile, policy: QosPolicyKind) -> ParameterValue {
+    let string = |s: &str| ParameterValue::String(s.to_owned());
+    let nanos = |d: &Duration| ParameterValue::Integer(d.as_nanos().min(i64::MAX as u128) as i64);
+    match policy {
+        QosPolicyKind::History => string(match qos.history {
+            HistoryPolicy::SystemDefault => "system_default",
+            HistoryPolicy::KeepLast => "keep_last",
+            HistoryPolicy::KeepAll => "keep_all",
+            HistoryPolicy::Unknown => "unknown",
+        }),
+        QosPolicyKind::Depth => ParameterValue::Integer(qos.depth as i64),
+        QosPolicyKind::Reliability => string(match qos.reliability {
+            ReliabilityPolicy::SystemDefault => "system_default",
+            ReliabilityPolicy::Reliable => "reliable",
+            ReliabilityPolicy::BestEffort => "best_effort",
+            ReliabilityPolicy::BestAvailable => "best_available",
+            ReliabilityPolicy::Unknown => "unknown",
+        }),
+        QosPolicyKind::Durability => string(match qos.durability {
+            DurabilityPolicy::SystemDefault => "system_default",
+            DurabilityPolicy::TransientLocal => "transient_local",
+            DurabilityPolicy::Volatile => "volatile",
+            DurabilityPolicy::BestAvailable => "best_available",
+            DurabilityPolicy::Unknown => "unknown",
+        }),
+        QosPolicyKind::Deadline => nanos(&qos.deadline),
+        QosPolicyKind::Lifespan => nanos(&qos.lifespan),
+        QosPolicyKind::Liveliness => string(match qos.liveliness {
+            LivelinessPolicy::SystemDefault => "system_default",
+            LivelinessPolicy::Automatic => "automatic",
+            LivelinessPolicy::ManualByNode => "manual_by_node",
+            LivelinessPolicy::ManualByTopic => "manual_by_topic",
+            LivelinessPolicy::BestAvailable => "best_available",
+            LivelinessPolicy::Unknown => "unknown",
+        }),
+        QosPolicyKind::LivelinessLeaseDuration => nanos(&qos.liveliness_lease_duration),
+    }
+}
+
+fn apply_policy(
+    qos: &mut QosProfile,
+    policy: QosPolicyKind,
+    value: &ParameterValue,
+) -> std::result::Result<(), String> {
+    let unknown = || format!("unknown {} {:?}", policy.parameter_name(), value);
+    let string = || match value {
+        ParameterValue::String(s) => Ok(s.as_str()),
+        _ => Err(format!("expected a string, got {:?}", value)),
+    };
+    let integer = || match value {
+        ParameterValue::Integer(i) if *i >= 0 => Ok(*i as u64),
+        _ => Err(format!("expected a non-negative integer, got {:?}", value)),
+    };
+    match policy {
+        QosPolicyKind::History => {
+            qos.history = match string()? {
+                "system_default" => HistoryPolicy::SystemDefault,
+                "keep_last" => HistoryPolicy::KeepLast,
+                "keep_all" => HistoryPolicy::KeepAll,
+                _ => return Err(unknown()),
+            }
+        }
+        QosPolicyKind::Depth => qos.depth = integer()? as usize,
+        QosPolicyKind::Reliability => {
+            qos.reliability = match string()? {
+                "system_default" => ReliabilityPolicy::SystemDefault,
+                "reliable" => ReliabilityPolicy::Reliable,
+                "best_effort" => ReliabilityPolicy::BestEffort,
+                "best_available" => ReliabilityPolicy::BestAvailable,
+                _ => return Err(unknown()),
+            }
+        }
+        QosPolicyKind::Durability => {
+            qos.durability = match string()? {
+                "system_default" => DurabilityPolicy::SystemDefault,
+                "transient_local" => DurabilityPolicy::TransientLocal,
+                "volatile" => DurabilityPolicy::Volatile,
+                "best_available" => DurabilityPolicy::BestAvailable,
+                _ => return Err(unknown()),
+            }
+        }
+        QosPolicyKind::Deadline => qos.deadline = Duration::from_nanos(integer()?),
+        QosPolicyKind::Lifespan => qos.lifespan = Duration::from_nanos(integer()?),
+        QosPolicyKind::Liveliness => {
+            qos.liveliness = match string()? {
+                "system_default" => LivelinessPolicy::SystemDefault,
+                "automatic" => LivelinessPolicy::Automatic,
+                "manual_by_node" => LivelinessPolicy::ManualByNode,
+                "manual_by_topic" => LivelinessPolicy::ManualByTopic,
+                "best_available" => LivelinessPolicy::BestAvailable,
+                _ => return Err(unknown()),
+            }
+        }
+        QosPolicyKind::LivelinessLeaseDuration => {
+            qos.liveliness_lease_duration = Duration::from_nanos(integer()?)
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RELIABILITY: &str = "qos_overrides./chatter.publisher.reliability";
+
+    #[test]
+    fn test_declare_qos_overrides() {
+        let mut params = HashMap::new();
+        params.insert(
+            RELIABILITY.to_owned(),
+            ParameterValue::String("best_effort".into()),
+        );
+        let qos = declare_qos_overrides(
+            &mut params,
+            "/chatter",
+            QosEntityKind::Publisher,
+            &QosOverridingOptions::default(),
+            QosProfile::default(),
+        )
+        .unwrap();
+        assert_eq!(qos.reliability, ReliabilityPolicy::BestEffort);
+        assert_eq!(qos.depth, 10);
+        // the others are declared with the values in code.
+        assert_eq!(
+            params["qos_overrides./chatter.publisher.depth"],
+            ParameterValue::Integer(10)
+        );
+        assert_eq!(
+            params["qos_overrides./chatter.publisher.durability"],
+            ParameterValue::String("volatile".into())
+        );
+        assert!(!params.contains_key("qos_overrides./chatter.publisher.deadline"));
+    }
+
+    #[test]
+    fn test_qos_override_ids_and_durations() {
+        let mut params = HashMap::new();
+        params.insert(
+            "qos_overrides./chatter.subscription_fast.deadline".to_owned(),
+            ParameterValue::Integer(100_000_000),
+        );
+        params.insert(
+            "qos_overrides./chatter.subscription_fast.history".to_owned(),
+            ParameterValue::String("keep_all".into()),
+        );
+        let options = QosOverridingOptions {
+            policies: vec![QosPolicyKind::History, QosPolicyKind::Deadline],
+            id: Some("fast".into()),
+            validation: None,
+        };
+        let qos = declare_qos_overrides(
+            &mut params,
+            "/chatter",
+            QosEntityKind::Subscription,
+            &options,
+            QosProfile::default(),
+        )
+        .unwrap();
+        assert_eq!(qos.deadline, Duration::from_millis(100));
+        assert_eq!(qos.history, HistoryPolicy::KeepAll);
+        assert_eq!(qos.depth, 0);
+    }
+
+    #[test]
+    fn test_invalid_qos_overrides() {
+        let declare = |value: ParameterValue, options: &QosOverridingOptions| {
+            let mut params = HashMap::new();
+            params.insert(RELIABILITY.to_owned(), value);
+            declare_qos_overrides(
+                &mut params,
+                "/chatter",
+                QosEntityKind::Publisher,
+                options,
+                QosProfile::default(),
+            )
+        };
+        let options = QosOverridingOptions::default();
+        match declare(ParameterValue::String("sometimes".into()), &options) {
+            Err(Error::InvalidParameter { name, .. }) => assert_eq!(name, RELIABILITY),
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(declare(ParameterValue::Integer(1), &options).is_err());
+
+        let options = options.with_validation(|qos| {
+            if qos.reliability == ReliabilityPolicy::BestEffort {
+                Err("must be reliable".into())
+            } else {
+                Ok(())
+            }
+        });
+        assert!(declare(ParameterValue::String("reliable".into()), &options).is_ok());
+        assert!(matches!(
+            declare(ParameterValue::String("best_effort".into()), &options),
+            Err(Error::InvalidQosProfile { .. })
+        ));
+    }
+}
@@ -0,0 +1,138 @@
+use futures::channel::oneshot;
+use std::collections::HashSet;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::time::Instant;
+
+use crate::error::*;
+use r2r_rcl::*;
+
+/// Something a node needs to find in the ROS graph before it is ready.
+///
+/// Names must be fully qualified, e.g. "/scan".
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Dependency {
+    /// A server for the service.
+    Service(String),
+    /// At least one publisher on the topic.
+    PublisherOn(String),
+    /// A server for the action.
+    ActionServer(String),
+}
+
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Dependency::Service(name) => write!(f, "service {}", name),
+            Dependency::PublisherOn(topic) => write!(f, "publisher on {}", topic),
+            Dependency::ActionServer(name) => write!(f, "action server {}", name),
+        }
+    }
+}
+
+pub struct ReadinessWaiter {
+    pub deadline: Instant,
+    pub sender: oneshot::Sender<Result<()>>,
+    pub progress: Option<Box<dyn FnMut(&[Dependency]) + Send>>,
+    pub last_missing: Option<Vec<Dependency>>,
+}
+
+impl ReadinessWaiter {
+    /// Completes the waiter if possible and otherwise reports
+    /// progress. Returns true when the waiter is done.
+    pub fn update(&mut self, missing: &[Dependency], now: Instant) -> bool {
+        if self.sender.is_canceled() {
+            return true;
+        }
+        if missing.is_empty() {
+            return true;
+        }
+        if now >= self.deadline {
+            return true;
+        }
+        if self.last_missing.as_deref() != Some(missing) {
+            if let Some(progress) = &mut self.progress {
+                progress(missing);
+            }
+            self.last_missing = Some(missing.to_vec());
+        }
+        false
+    }
+
+    /// Sends the outcome to the waiting future.
+    pub fn complete(self, missing: &[Dependency]) {
+        let result = if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::DependenciesNotReady {
+                missing: missing.iter().map(|d| d.to_string()).collect(),
+            })
+        };
+        let _ = self.sender.send(result); // we ignore if receiver dropped.
+    }
+}
+
+/// Returns the dependencies that cannot be found in the graph.
+pub fn missing_dependencies(
+    node: &rcl_node_t,
+    dependencies: &[Dependency],
+) -> Result<Vec<Dependency>> {
+    let needs_services = dependencies
+        .iter()
+        .any(|d| !matches!(d, Dependency::PublisherOn(_)));
+    let services = if needs_services {
+        service_names(node)?
+    } else {
+        HashSet::new()
+    };
+
+    let mut missing = vec![];
+    for d in dependencies {
+        let found = match d {
+            Dependency::Service(name) => services.contains(name),
+            // the send_goal service is the first thing an action client talks to.
+            Dependency::ActionServer(name) => {
+                services.contains(&format!("{}/_action/send_goal", name))
+            }
+            Dependency::PublisherOn(topic) => publisher_count(node, topic)? > 0,
+        };
+        if !found {
+            missing.push(d.clone());
+        }
+    }
+    Ok(missing)
+}
+
+pub(crate) fn publisher_count(node: &rcl_node_t, topic: &str) -> Result<usize> {
+    let topic_c_string = CString::new(topic).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
+    let mut count = 0usize;
+    let ret = unsafe { rcl_count_publishers(node, topic_c_string.as_ptr(), &mut count) };
+    if ret == RCL_RET_OK as i32 {
+        Ok(count)
+    } else {
+        Err(Error::from_rcl_error(ret))
+    }
+}
+
+fn service_names(node: &rcl_node_t) -> Result<HashSet<String>> {
+    let mut snat = unsafe { rmw_get_zero_initialized_names_and_types() };
+    let ret = unsafe {
+        rcl_get_service_names_and_types(node, &mut rcutils_get_default_allocator(), &mut snat)
+    };
+    if ret != RCL_RET_OK as i32 {
+        return Err(Error::from_rcl_error(ret));
+    }
+    let names = if snat.names.size == 0 {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(snat.names.data, snat.names.size) }
+    };
+    let res = names
+        .iter()
+        .map(|n| unsafe { CStr::from_ptr(*n).to_str().unwrap_or("").to_owned() })
+        .collect();
+    unsafe {
+        rmw_names_and_types_fini(&mut snat);
+    } // TODO: check return value
+    Ok(res)
+}
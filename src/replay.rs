@@ -0,0 +1,877 @@
+//! Recording service and action traffic, and replaying the server
+//! side of it in tests.
+//!
+//! Only built with the `replay` feature. Traffic is recorded on the
+//! client side with `RecordingClient` and `RecordingActionClient` and
+//! saved to a file, from which `ReplayServiceServer` and
+//! `ReplayActionServer` answer like the recorded server did.
+//!
+//! The file starts with the magic `R2RRPL01` and a JSON header of
+//! length prefixed metadata: the kind of recording, the service or
+//! action name and its type. The messages follow as entries of a tag
+//! byte, a goal status byte, the time since the goal was sent in
+//! nanoseconds (u64), the length of the data (u32) and the CDR encoded
+//! message. All integers are little endian.
+
+use futures::future::{Future, FutureExt};
+use futures::stream::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::action_clients::ActionClient;
+use crate::action_common::GoalStatus;
+use crate::action_servers::{ActionServerCancelRequest, ActionServerGoal, ActionServerGoalRequest};
+use crate::clients::Client;
+use crate::error::*;
+use crate::msg_types::*;
+use crate::nodes::{Node, Timer};
+use crate::services::ServiceRequest;
+
+const MAGIC: &[u8; 8] = b"R2RRPL01";
+// how often a replayed action server looks for feedback to send.
+const REPLAY_STEP: Duration = Duration::from_millis(10);
+
+const TAG_REQUEST: u8 = 1;
+const TAG_RESPONSE: u8 = 2;
+const TAG_GOAL: u8 = 3;
+const TAG_FEEDBACK: u8 = 4;
+const TAG_RESULT: u8 = 5;
+
+fn recording_error(e: impl std::fmt::Display) -> Error {
+    Error::RecordingError {
+        reason: e.to_string(),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    kind: String,
+    name: String,
+    #[serde(rename = "type")]
+    type_name: String,
+}
+
+struct Entry {
+    tag: u8,
+    status: i8,
+    offset: Duration,
+    data: Vec<u8>,
+}
+
+impl Entry {
+    fn new<M: WrappedTypesupport>(tag: u8, offset: Duration, msg: &M) -> Result<Self> {
+        Ok(Entry {
+            tag,
+            status: 0,
+            offset,
+            data: serialize_message(msg)?,
+        })
+    }
+
+    fn message<M: WrappedTypesupport>(&self, tag: u8) -> Result<M> {
+        if self.tag != tag {
+            return Err(recording_error(format!(
+                "expected entry {}, found {}",
+                tag, self.tag
+            )));
+        }
+        deserialize_message(&self.data)
+    }
+}
+
+fn write_file(path: &Path, header: &Header, entries: &[Entry]) -> Result<()> {
+    let header = serde_json::to_vec(header).map_err(recording_error)?;
+    let mut data = Vec::new();
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    data.extend_from_slice(&header);
+    for entry in entries {
+        data.push(entry.tag);
+        data.push(entry.status as u8);
+        data.extend_from_slice(&(entry.offset.as_nanos() as u64).to_le_bytes());
+        data.extend_from_slice(&(entry.data.len() as u32).to_le_bytes());
+        data.extend_from_slice(&entry.data);
+    }
+    std::fs::write(path, data).map_err(recording_error)
+}
+
+// Splits off the first `n` bytes of `data`.
+fn take<'a>(data: &mut &'a [u8], n: usize) -> Result<&'a [u8]> {
+    if data.len() < n {
+        return Err(recording_error("truncated file"));
+    }
+    let (taken, rest) = data.split_at(n);
+    *data = rest;
+    Ok(taken)
+}
+
+fn read_file(path: &Path, kind: &str, type_name: &str) -> Result<(Header, Vec<Entry>)> {
+    let data = std::fs::read(path).map_err(recording_error)?;
+    let mut data = &data[..];
+    if take(&mut data, MAGIC.len())? != MAGIC {
+        return Err(recording_error("not a recording"));
+    }
+    let len = u32::from_le_bytes(<[u8; 4]>::try_from(take(&mut data, 4)?).unwrap());
+    let header: Header =
+        serde_json::from_slice(take(&mut data, len as usize)?).map_err(recording_error)?;
+    if header.kind != kind || header.type_name != type_name {
+        return Err(recording_error(format!(
+            "expected a {} recording of {}, found a {} recording of {}",
+            kind, type_name, header.kind, header.type_name
+        )));
+    }
+    let mut entries = Vec::new();
+    while !data.is_empty() {
+        let tag = take(&mut data, 1)?[0];
+        let status = take(&mut data, 1)?[0] as i8;
+        let offset = u64::from_le_bytes(<[u8; 8]>::try_from(take(&mut data, 8)?).unwrap());
+        let len = u32::from_le_bytes(<[u8; 4]>::try_from(take(&mut data, 4)?).unwrap());
+        entries.push(Entry {
+            tag,
+            status,
+            offset: Duration::from_nanos(offset),
+            data: take(&mut data, len as usize)?.to_vec(),
+        });
+    }
+    Ok((header, entries))
+}
+
+/// Decides whether a request is answered like a recorded one.
+///
+/// Closures taking the recorded and the new request are matchers too.
+pub trait RequestMatcher<M>: Send {
+    fn matches(&self, recorded: &M, request: &M) -> bool;
+}
+
+impl<M, F> RequestMatcher<M> for F
+where
+    F: Fn(&M, &M) -> bool + Send,
+{
+    fn matches(&self, recorded: &M, request: &M) -> bool {
+        self(recorded, request)
+    }
+}
+
+/// Matches requests that serialize to the same CDR data.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExactMatch;
+
+impl<M> RequestMatcher<M> for ExactMatch
+where
+    M: WrappedTypesupport,
+{
+    fn matches(&self, recorded: &M, request: &M) -> bool {
+        match (serialize_message(recorded), serialize_message(request)) {
+            (Ok(recorded), Ok(request)) => recorded == request,
+            _ => false,
+        }
+    }
+}
+
+/// Matches requests that have the same values in some of their
+/// fields, e.g. `FieldSubsetMatch::new(&["target.x", "target.y"])`.
+/// Nested fields are separated by dots, and elements of sequences are
+/// given by their index.
+#[derive(Debug, Clone)]
+pub struct FieldSubsetMatch {
+    pointers: Vec<String>,
+}
+
+impl FieldSubsetMatch {
+    pub fn new(fields: &[&str]) -> Self {
+        FieldSubsetMatch {
+            pointers: fields
+                .iter()
+                .map(|f| format!("/{}", f.replace('.', "/")))
+                .collect(),
+        }
+    }
+}
+
+impl<M> RequestMatcher<M> for FieldSubsetMatch
+where
+    M: WrappedTypesupport,
+{
+    fn matches(&self, recorded: &M, request: &M) -> bool {
+        let (recorded, request) = match (
+            serde_json::to_value(recorded),
+            serde_json::to_value(request),
+        ) {
+            (Ok(recorded), Ok(request)) => (recorded, request),
+            _ => return false,
+        };
+        self.pointers.iter().all(|p| match recorded.pointer(p) {
+            Some(value) => request.pointer(p) == Some(value),
+            None => false,
+        })
+    }
+}
+
+// The first recording that matches and has not been replayed yet, or
+// else the last one that matches.
+fn find_match<M, R>(
+    matcher: &dyn RequestMatcher<M>,
+    recorded: &[R],
+    used: &[bool],
+    request: &M,
+    key: impl Fn(&R) -> &M,
+) -> Option<usize> {
+    let mut matching = recorded
+        .iter()
+        .enumerate()
+        .filter(|(_, r)| matcher.matches(key(r), request))
+        .map(|(i, _)| i)
+        .peekable();
+    let mut last = *matching.peek()?;
+    for i in matching {
+        if !used[i] {
+            return Some(i);
+        }
+        last = i;
+    }
+    Some(last)
+}
+
+/// A request to a service and the response of the server.
+#[derive(Debug, Clone)]
+pub struct RecordedExchange<T>
+where
+    T: WrappedServiceTypeSupport,
+{
+    pub request: T::Request,
+    pub response: T::Response,
+}
+
+/// The requests and responses of a service, see `RecordingClient`.
+#[derive(Debug, Clone)]
+pub struct ServiceRecording<T>
+where
+    T: WrappedServiceTypeSupport,
+{
+    name: String,
+    exchanges: Vec<RecordedExchange<T>>,
+}
+
+impl<T> ServiceRecording<T>
+where
+    T: WrappedServiceTypeSupport,
+{
+    /// An empty recording of the service `service_name`.
+    pub fn new(service_name: &str) -> Self {
+        ServiceRecording {
+            name: service_name.to_owned(),
+            exchanges: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn exchanges(&self) -> &[RecordedExchange<T>] {
+        &self.exchanges
+    }
+
+    pub fn record(&mut self, request: T::Request, response: T::Response) {
+        self.exchanges.push(RecordedExchange { request, response });
+    }
+
+    /// Write the recording to `path`, replacing what is there.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut entries = Vec::new();
+        for exchange in &self.exchanges {
+            entries.push(Entry::new(TAG_REQUEST, Duration::ZERO, &exchange.request)?);
+            entries.push(Entry::new(
+                TAG_RESPONSE,
+                Duration::ZERO,
+                &exchange.response,
+            )?);
+        }
+        write_file(path.as_ref(), &self.header(), &entries)
+    }
+
+    /// Read a recording saved with `save`. Fails with
+    /// `Error::RecordingError` if it is not a recording of this
+    /// service type.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let type_name = Self::new("").header().type_name;
+        let (header, entries) = read_file(path.as_ref(), "service", &type_name)?;
+        let mut recording = Self::new(&header.name);
+        for pair in entries.chunks(2) {
+            match pair {
+                [request, response] => recording.record(
+                    request.message(TAG_REQUEST)?,
+                    response.message(TAG_RESPONSE)?,
+                ),
+                _ => return Err(recording_error("request without a response")),
+            }
+        }
+        Ok(recording)
+    }
+
+    fn header(&self) -> Header {
+        Header {
+            kind: "service".into(),
+            name: self.name.clone(),
+            type_name: ros_type_name::<T::Request>(),
+        }
+    }
+}
+
+/// A client that records the requests it sends and the responses it
+/// receives.
+///
+/// ```ignore
+/// let client = RecordingClient::new(node.create_client::<AddTwoInts::Service>("/add")?, "/add");
+/// let sum = client.request(&AddTwoInts::Request { a: 1, b: 2 })?.await?;
+/// client.recording().save("add.rec")?;
+/// ```
+pub struct RecordingClient<T>
+where
+    T: WrappedServiceTypeSupport,
+{
+    client: Client<T>,
+    recording: Arc<Mutex<ServiceRecording<T>>>,
+}
+
+impl<T: 'static> RecordingClient<T>
+where
+    T: WrappedServiceTypeSupport,
+{
+    pub fn new(client: Client<T>, service_name: &str) -> Self {
+        RecordingClient {
+            client,
+            recording: Arc::new(Mutex::new(ServiceRecording::new(service_name))),
+        }
+    }
+
+    /// See `Client::request`. The request is recorded when its
+    /// response arrives.
+    pub fn request(&self, msg: &T::Request) -> Result<impl Future<Output = Result<T::Response>>> {
+        let response = self.client.request(msg)?;
+        let request = msg.clone();
+        let recording = self.recording.clone();
+        Ok(response.map(move |response| {
+            let response = response?;
+            recording.lock().unwrap().record(request, response.clone());
+            Ok(response)
+        }))
+    }
+
+    /// What has been recorded so far.
+    pub fn recording(&self) -> ServiceRecording<T> {
+        self.recording.lock().unwrap().clone()
+    }
+}
+
+/// Answers requests to a service with the responses of a recording.
+///
+/// Each request is answered with the response to the first recorded
+/// request that the matcher matches and that has not been answered
+/// yet, or else the last one it matches. Requests that match nothing
+/// are not answered and counted in `unmatched`.
+pub struct ReplayServiceServer<T>
+where
+    T: WrappedServiceTypeSupport,
+{
+    requests: Box<dyn Stream<Item = ServiceRequest<T>> + Unpin + Send>,
+    exchanges: Vec<RecordedExchange<T>>,
+    used: Vec<bool>,
+    matcher: Box<dyn RequestMatcher<T::Request>>,
+    unmatched: usize,
+}
+
+impl<T: 'static> ReplayServiceServer<T>
+where
+    T: WrappedServiceTypeSupport,
+{
+    /// Create the recorded service on `node`.
+    pub fn create<M>(node: &mut Node, recording: ServiceRecording<T>, matcher: M) -> Result<Self>
+    where
+        M: RequestMatcher<T::Request> + 'static,
+    {
+        let requests = node.create_service::<T>(&recording.name)?;
+        Ok(ReplayServiceServer {
+            requests: Box::new(requests),
+            used: vec![false; recording.exchanges.len()],
+            exchanges: recording.exchanges,
+            matcher: Box::new(matcher),
+            unmatched: 0,
+        })
+    }
+
+    /// Answers the requests that have arrived, for tests that spin
+    /// the node themselves. Returns how many were answered.
+    pub fn handle_pending(&mut self) -> Result<usize> {
+        let mut answered = 0;
+        while let Some(Some(request)) = self.requests.next().now_or_never() {
+            if self.answer(request)? {
+                answered += 1;
+            }
+        }
+        Ok(answered)
+    }
+
+    /// Answers requests until the service is destroyed.
+    pub async fn run(mut self) -> Result<()> {
+        while let Some(request) = self.requests.next().await {
+            self.answer(request)?;
+        }
+        Ok(())
+    }
+
+    /// How many requests matched no recorded request.
+    pub fn unmatched(&self) -> usize {
+        self.unmatched
+    }
+
+    fn answer(&mut self, request: ServiceRequest<T>) -> Result<bool> {
+        match find_match(
+            self.matcher.as_ref(),
+            &self.exchanges,
+            &self.used,
+            &request.message,
+            |e| &e.request,
+        ) {
+            Some(i) => {
+                self.used[i] = true;
+                request.respond(self.exchanges[i].response.clone())?;
+                Ok(true)
+            }
+            None => {
+                self.unmatched += 1;
+                Ok(false)
+            }
+        }
+    }
+}
+
+/// A goal, the feedback sent for it and its result.
+#[derive(Debug, Clone)]
+pub struct RecordedGoal<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    pub goal: T::Goal,
+    /// Each feedback with the time since the goal was sent.
+    pub feedback: Vec<(Duration, T::Feedback)>,
+    pub status: GoalStatus,
+    pub result: T::Result,
+    /// When the result arrived, since the goal was sent.
+    pub finished_after: Duration,
+}
+
+/// The goals sent to an action, see `RecordingActionClient`.
+#[derive(Debug, Clone)]
+pub struct ActionRecording<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    name: String,
+    goals: Vec<RecordedGoal<T>>,
+}
+
+impl<T> ActionRecording<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    /// An empty recording of the action `action_name`.
+    pub fn new(action_name: &str) -> Self {
+        ActionRecording {
+            name: action_name.to_owned(),
+            goals: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn goals(&self) -> &[RecordedGoal<T>] {
+        &self.goals
+    }
+
+    pub fn record(&mut self, goal: RecordedGoal<T>) {
+        self.goals.push(goal);
+    }
+
+    /// Write the recording to `path`, replacing what is there.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut entries = Vec::new();
+        for goal in &self.goals {
+            entries.push(Entry::new(TAG_GOAL, Duration::ZERO, &goal.goal)?);
+            for (offset, feedback) in &goal.feedback {
+                entries.push(Entry::new(TAG_FEEDBACK, *offset, feedback)?);
+            }
+            let mut result = Entry::new(TAG_RESULT, goal.finished_after, &goal.result)?;
+            result.status = goal.status.to_rcl();
+            entries.push(result);
+        }
+        write_file(path.as_ref(), &self.header(), &entries)
+    }
+
+    /// Read a recording saved with `save`. Fails with
+    /// `Error::RecordingError` if it is not a recording of this
+    /// action type.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let type_name = Self::new("").header().type_name;
+        let (header, entries) = read_file(path.as_ref(), "action", &type_name)?;
+        let mut recording = Self::new(&header.name);
+        let mut entries = entries.into_iter().peekable();
+        while let Some(goal) = entries.next() {
+            let goal = goal.message(TAG_GOAL)?;
+            let mut feedback = Vec::new();
+            while let Some(entry) = entries.next_if(|e| e.tag == TAG_FEEDBACK) {
+                feedback.push((entry.offset, entry.message(TAG_FEEDBACK)?));
+            }
+            let result = entries
+                .next()
+                .ok_or_else(|| recording_error("goal without a result"))?;
+            recording.record(RecordedGoal {
+                goal,
+                feedback,
+                status: GoalStatus::try_from(result.status)?,
+                result: result.message(TAG_RESULT)?,
+                finished_after: result.offset,
+            });
+        }
+        Ok(recording)
+    }
+
+    fn header(&self) -> Header {
+        Header {
+            kind: "action".into(),
+            name: self.name.clone(),
+            type_name: ros_type_name::<T::Goal>(),
+        }
+    }
+}
+
+/// An action client that records the goals it sends, with their
+/// feedback and results.
+pub struct RecordingActionClient<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    client: ActionClient<T>,
+    recording: Arc<Mutex<ActionRecording<T>>>,
+}
+
+impl<T: 'static> RecordingActionClient<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    pub fn new(client: ActionClient<T>, action_name: &str) -> Self {
+        RecordingActionClient {
+            client,
+            recording: Arc::new(Mutex::new(ActionRecording::new(action_name))),
+        }
+    }
+
+    /// Sends a goal and resolves to its result. The goal is recorded
+    /// when the result arrives. Goals that are rejected or fail are
+    /// not recorded.
+    pub fn send_goal(
+        &self,
+        goal: T::Goal,
+    ) -> Result<impl Future<Output = Result<(GoalStatus, T::Result)>>> {
+        let sent = Instant::now();
+        let handle = self.client.send_goal(goal.clone())?;
+        let recording = self.recording.clone();
+        Ok(async move {
+            let mut handle = handle.await?;
+            let feedback = handle
+                .feedback()
+                .expect("feedback of a new goal")
+                .map(|feedback| (sent.elapsed(), feedback))
+                .collect::<Vec<_>>();
+            let result = handle.result().expect("result of a new goal");
+            // the feedback stream ends with the goal.
+            let (feedback, result) = futures::join!(feedback, result);
+            let (status, result) = result?;
+            recording.lock().unwrap().record(RecordedGoal {
+                goal,
+                feedback,
+                status,
+                result: result.clone(),
+                finished_after: sent.elapsed(),
+            });
+            Ok((status, result))
+        })
+    }
+
+    /// What has been recorded so far.
+    pub fn recording(&self) -> ActionRecording<T> {
+        self.recording.lock().unwrap().clone()
+    }
+}
+
+struct ReplayedGoal<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    handle: ActionServerGoal<T>,
+    cancel_requests: Box<dyn Stream<Item = ActionServerCancelRequest> + Unpin + Send>,
+    recorded: usize,
+    started: Instant,
+    feedback_sent: usize,
+}
+
+/// Replays the feedback and results of a recording to the goals sent
+/// to an action.
+///
+/// Goals are matched like the requests of a `ReplayServiceServer`,
+/// and goals that match nothing are rejected. The recorded feedback
+/// is sent at the same times after the goal was accepted as it was
+/// received, and the goal finishes with the recorded status and
+/// result. Canceling a goal finishes it right away, as canceled with
+/// the recorded result.
+pub struct ReplayActionServer<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    requests: Box<dyn Stream<Item = ActionServerGoalRequest<T>> + Unpin + Send>,
+    timer: Timer,
+    goals: Vec<RecordedGoal<T>>,
+    used: Vec<bool>,
+    matcher: Box<dyn RequestMatcher<T::Goal>>,
+    active: Vec<ReplayedGoal<T>>,
+    unmatched: usize,
+}
+
+impl<T: 'static> ReplayActionServer<T>
+where
+    T: WrappedActionTypeSupport,
+{
+    /// Create the recorded action server on `node`.
+    pub fn create<M>(node: &mut Node, recording: ActionRecording<T>, matcher: M) -> Result<Self>
+    where
+        M: RequestMatcher<T::Goal> + 'static,
+    {
+        let requests = node.create_action_server::<T>(&recording.name)?;
+        let timer = node.create_wall_timer(REPLAY_STEP)?;
+        Ok(ReplayActionServer {
+            requests: Box::new(requests),
+            timer,
+            used: vec![false; recording.goals.len()],
+            goals: recording.goals,
+            matcher: Box::new(matcher),
+            active: Vec::new(),
+            unmatched: 0,
+        })
+    }
+
+    /// Accepts the goals that have arrived and sends the feedback and
+    /// results that are due, for tests that spin the node themselves.
+    pub fn handle_pending(&mut self) -> Result<()> {
+        // the timer only wakes up `run`.
+        while let Some(Ok(_)) = self.timer.tick().now_or_never() {}
+        while let Some(Some(request)) = self.requests.next().now_or_never() {
+            self.start(request)?;
+        }
+        let now = Instant::now();
+        let mut i = 0;
+        while i < self.active.len() {
+            if self.replay(i, now)? {
+                i += 1;
+            } else {
+                self.active.swap_remove(i);
+            }
+        }
+        Ok(())
+    }
+
+    /// Replays goals until the action server is destroyed.
+    pub async fn run(mut self) -> Result<()> {
+        while self.timer.tick().await.is_ok() {
+            self.handle_pending()?;
+        }
+        Ok(())
+    }
+
+    /// How many goals matched no recorded goal.
+    pub fn unmatched(&self) -> usize {
+        self.unmatched
+    }
+
+    fn start(&mut self, request: ActionServerGoalRequest<T>) -> Result<()> {
+        let recorded = match find_match(
+            self.matcher.as_ref(),
+            &self.goals,
+            &self.used,
+            &request.goal,
+            |g| &g.goal,
+        ) {
+            Some(i) => i,
+            None => {
+                self.unmatched += 1;
+                return request.reject();
+            }
+        };
+        self.used[recorded] = true;
+        let (handle, cancel_requests) = request.accept()?;
+        self.active.push(ReplayedGoal {
+            handle,
+            cancel_requests: Box::new(cancel_requests),
+            recorded,
+            started: Instant::now(),
+            feedback_sent: 0,
+        });
+        Ok(())
+    }
+
+    // Sends what is due for the active goal `i`, returns false once
+    // the goal has finished.
+    fn replay(&mut self, i: usize, now: Instant) -> Result<bool> {
+        let goal = &mut self.active[i];
+        let recorded = &self.goals[goal.recorded];
+        if let Some(Some(cancel)) = goal.cancel_requests.next().now_or_never() {
+            cancel.accept();
+            goal.handle.canceled(recorded.result.clone())?;
+            return Ok(false);
+        }
+        let elapsed = now - goal.started;
+        for (offset, feedback) in &recorded.feedback[goal.feedback_sent..] {
+            if *offset > elapsed {
+                return Ok(true);
+            }
+            goal.handle.publish_feedback(feedback.clone())?;
+            goal.feedback_sent += 1;
+        }
+        if recorded.finished_after > elapsed {
+            return Ok(true);
+        }
+        let result = recorded.result.clone();
+        match recorded.status {
+            GoalStatus::Canceled => goal.handle.canceled(result)?,
+            GoalStatus::Aborted => goal.handle.abort(result)?,
+            _ => goal.handle.succeed(result)?,
+        }
+        Ok(false)
+    }
+}
+
+// the tests need at least one of the example interfaces.
+#[cfg(all(
+    test,
+    any(
+        r2r__example_interfaces__srv__AddTwoInts,
+        r2r__example_interfaces__action__Fibonacci
+    )
+))]
+mod tests {
+    use super::*;
+    #[cfg(r2r__example_interfaces__action__Fibonacci)]
+    use crate::msg_types::generated_msgs::example_interfaces::action::Fibonacci;
+    #[cfg(r2r__example_interfaces__srv__AddTwoInts)]
+    use crate::msg_types::generated_msgs::example_interfaces::srv::AddTwoInts;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("r2r_replay_{}_{}", name, std::process::id()))
+    }
+
+    #[cfg(r2r__example_interfaces__srv__AddTwoInts)]
+    #[test]
+    fn test_service_recording_file() {
+        let mut recording = ServiceRecording::<AddTwoInts::Service>::new("/add");
+        recording.record(
+            AddTwoInts::Request { a: 1, b: 2 },
+            AddTwoInts::Response { sum: 3 },
+        );
+        recording.record(
+            AddTwoInts::Request { a: 2, b: 2 },
+            AddTwoInts::Response { sum: 5 },
+        );
+        let path = temp_path("service");
+        recording.save(&path).unwrap();
+        let loaded = ServiceRecording::<AddTwoInts::Service>::load(&path).unwrap();
+        assert_eq!(loaded.name(), "/add");
+        assert_eq!(loaded.exchanges().len(), 2);
+        assert_eq!(loaded.exchanges()[1].request.a, 2);
+        assert_eq!(loaded.exchanges()[1].response.sum, 5);
+
+        // not a recording of an action.
+        assert!(matches!(
+            ActionRecording::<Fibonacci::Action>::load(&path),
+            Err(Error::RecordingError { .. })
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(r2r__example_interfaces__action__Fibonacci)]
+    #[test]
+    fn test_action_recording_file() {
+        let mut recording = ActionRecording::<Fibonacci::Action>::new("/fibonacci");
+        recording.record(RecordedGoal {
+            goal: Fibonacci::Goal { order: 2 },
+            feedback: vec![
+                (
+                    Duration::from_millis(10),
+                    Fibonacci::Feedback { sequence: vec![0] },
+                ),
+                (
+                    Duration::from_millis(20),
+                    Fibonacci::Feedback {
+                        sequence: vec![0, 1],
+                    },
+                ),
+            ],
+            status: GoalStatus::Succeeded,
+            result: Fibonacci::Result {
+                sequence: vec![0, 1],
+            },
+            finished_after: Duration::from_millis(30),
+        });
+        recording.record(RecordedGoal {
+            goal: Fibonacci::Goal { order: -1 },
+            feedback: vec![],
+            status: GoalStatus::Aborted,
+            result: Fibonacci::Result::default(),
+            finished_after: Duration::from_millis(5),
+        });
+        let path = temp_path("action");
+        recording.save(&path).unwrap();
+        let loaded = ActionRecording::<Fibonacci::Action>::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.name(), "/fibonacci");
+        let goals = loaded.goals();
+        assert_eq!(goals.len(), 2);
+        assert_eq!(goals[0].feedback.len(), 2);
+        assert_eq!(goals[0].feedback[1].0, Duration::from_millis(20));
+        assert_eq!(goals[0].feedback[1].1.sequence, vec![0, 1]);
+        assert_eq!(goals[0].status, GoalStatus::Succeeded);
+        assert_eq!(goals[0].finished_after, Duration::from_millis(30));
+        assert_eq!(goals[1].goal.order, -1);
+        assert_eq!(goals[1].status, GoalStatus::Aborted);
+    }
+
+    #[cfg(r2r__example_interfaces__srv__AddTwoInts)]
+    #[test]
+    fn test_matchers() {
+        let a = AddTwoInts::Request { a: 1, b: 2 };
+        let b = AddTwoInts::Request { a: 1, b: 3 };
+        assert!(ExactMatch.matches(&a, &a.clone()));
+        assert!(!ExactMatch.matches(&a, &b));
+        assert!(FieldSubsetMatch::new(&["a"]).matches(&a, &b));
+        assert!(!FieldSubsetMatch::new(&["a", "b"]).matches(&a, &b));
+        // unknown fields never match.
+        assert!(!FieldSubsetMatch::new(&["c"]).matches(&a, &a));
+
+        let recorded = vec![a.clone(), b.clone(), a.clone()];
+        let matcher = ExactMatch;
+        let find = |used: &[bool]| find_match(&matcher, &recorded, used, &a, |r| r);
+        assert_eq!(find(&[false, false, false]), Some(0));
+        assert_eq!(find(&[true, false, false]), Some(2));
+        // all used, the last one is repeated.
+        assert_eq!(find(&[true, false, true]), Some(2));
+        let by_closure = |r: &AddTwoInts::Request, q: &AddTwoInts::Request| r.b == q.b;
+        assert_eq!(
+            find_match(&by_closure, &recorded, &[false; 3], &b, |r| r),
+            Some(1)
+        );
+    }
+}
@@ -1,10 +1,11 @@
-use futures::channel::{mpsc, oneshot};
-use std::ffi::CString;
+use futures::channel::oneshot;
+use std::ffi::{CStr, CString};
 use std::sync::{Arc, Mutex, Weak};
 use std::mem::MaybeUninit;
 
 use crate::msg_types::*;
 use crate::error::*;
+use crate::message_sinks::{sink_full, DeliverResult, MessageSink};
 use r2r_rcl::*;
 
 /// Encapsulates a service request.
@@ -42,9 +43,151 @@ where
     }
 }
 
+/// Encapsulates a service request of a service created with type
+/// support loaded at runtime, see
+/// `Node::create_service_with_type_support`.
+///
+/// The request and the response are json, as for `ClientUntyped`.
+pub struct ServiceRequestUntyped {
+    pub message: serde_json::Value,
+    request_id: rmw_request_id_t,
+    service_type: Arc<UntypedServiceSupport>,
+    service: Weak<Mutex<dyn Service_>>,
+}
+
+unsafe impl Send for ServiceRequestUntyped {}
+
+impl ServiceRequestUntyped {
+    /// Complete the service request, consuming the request in the
+    /// process. Fails with `Error::SerdeError` if `msg` does not fit
+    /// the response type.
+    pub fn respond(self, msg: serde_json::Value) -> Result<()> {
+        let service = self
+            .service
+            .upgrade()
+            .ok_or(Error::RCL_RET_SERVICE_INVALID)?;
+        let mut native_msg = (self.service_type.make_response_msg)();
+        native_msg.from_json(msg)?;
+        let mut service = service.lock().unwrap();
+        service.send_response(self.request_id, Box::new(native_msg))
+    }
+}
+
+/// What to do with an incoming service request.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RequestVerdict {
+    /// Pass the request on to the service stream.
+    Accept,
+    /// Respond with a default constructed response without passing
+    /// the request on.
+    Reject,
+    /// Drop the request without responding.
+    Drop,
+}
+
+/// Whether a service or client publishes the requests and responses it
+/// sends and receives on the `<service>/_service_event` topic, e.g. for
+/// `ros2 service echo`. Only Iron and newer have it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ServiceIntrospection {
+    /// Publish nothing. The default.
+    Off,
+    /// Publish who sent what when, without the contents.
+    Metadata,
+    /// Publish the requests and responses too.
+    Contents,
+}
+
+impl Default for ServiceIntrospection {
+    fn default() -> Self {
+        ServiceIntrospection::Off
+    }
+}
+
+/// Options for creating services.
+pub struct ServiceOptions<T>
+where
+    T: WrappedServiceTypeSupport,
+{
+    /// Requests containing a longer string are refused.
+    pub max_string_len: Option<usize>,
+    /// Requests containing a longer sequence are refused.
+    pub max_sequence_len: Option<usize>,
+    /// How requests that break the limits above are refused.
+    pub refuse_with: RequestVerdict,
+    /// Runs on the native request message before it is converted to
+    /// the rust type.
+    pub validator: Option<Box<dyn Fn(&WrappedNativeMsg<T::Request>) -> RequestVerdict + Send>>,
+    /// Deliver the accepted requests to this sink instead of the
+    /// service stream, which then ends right away. The service is
+    /// destroyed once the sink is closed.
+    pub sink: Option<Box<dyn MessageSink<ServiceRequest<T>>>>,
+    /// Publish service events, see `ServiceIntrospection`. Creating the
+    /// service fails with `Error::NotSupported` before Iron.
+    pub introspection: ServiceIntrospection,
+}
+
+impl<T> Default for ServiceOptions<T>
+where
+    T: WrappedServiceTypeSupport,
+{
+    fn default() -> Self {
+        ServiceOptions {
+            max_string_len: None,
+            max_sequence_len: None,
+            refuse_with: RequestVerdict::Reject,
+            validator: None,
+            sink: None,
+            introspection: ServiceIntrospection::Off,
+        }
+    }
+}
+
+impl<T> ServiceOptions<T>
+where
+    T: WrappedServiceTypeSupport,
+{
+    fn check_limits(&self, msg: &T::Request) -> RequestVerdict {
+        if self.max_string_len.is_none() && self.max_sequence_len.is_none() {
+            return RequestVerdict::Accept;
+        }
+        match serde_json::to_value(msg) {
+            Ok(value) if within_limits(&value, self.max_string_len, self.max_sequence_len) => {
+                RequestVerdict::Accept
+            }
+            _ => self.refuse_with,
+        }
+    }
+}
+
+fn within_limits(
+    value: &serde_json::Value,
+    max_string_len: Option<usize>,
+    max_sequence_len: Option<usize>,
+) -> bool {
+    use serde_json::Value;
+    match value {
+        Value::String(s) => max_string_len.map(|max| s.len() <= max).unwrap_or(true),
+        Value::Array(a) => {
+            max_sequence_len.map(|max| a.len() <= max).unwrap_or(true)
+                && a
+                    .iter()
+                    .all(|v| within_limits(v, max_string_len, max_sequence_len))
+        }
+        Value::Object(o) => o
+            .values()
+            .all(|v| within_limits(v, max_string_len, max_sequence_len)),
+        _ => true,
+    }
+}
+
 pub trait Service_ {
     fn handle(&self) -> &rcl_service_t;
+    /// Number of requests refused by validation.
+    fn refused_requests(&self) -> usize;
     fn send_response(&mut self, request_id: rmw_request_id_t, msg: Box<dyn VoidPtr>) -> Result<()>;
+    /// Returns true when the user has dropped the request stream.
+    fn is_dropped(&self) -> bool;
     /// Returns true if the service stream has been dropped.
     fn handle_request(&mut self, service: Arc<Mutex<dyn Service_>>) -> bool;
     fn destroy(&mut self, node: &mut rcl_node_t) -> ();
@@ -55,8 +198,26 @@ where
     T: WrappedServiceTypeSupport,
 {
     pub rcl_handle: rcl_service_t,
-    pub sender: mpsc::Sender<ServiceRequest<T>>,
+    pub sink: Box<dyn MessageSink<ServiceRequest<T>>>,
     pub outstanding_requests: Vec<oneshot::Receiver<(rmw_request_id_t, T::Response)>>,
+    pub options: ServiceOptions<T>,
+    pub refused_requests: usize,
+    pub errors: EntityErrors,
+}
+
+impl<T: 'static> TypedService<T>
+where
+    T: WrappedServiceTypeSupport,
+{
+    fn refuse(&mut self, request_id: rmw_request_id_t, verdict: RequestVerdict) {
+        self.refused_requests += 1;
+        if verdict == RequestVerdict::Reject {
+            let response = WrappedNativeMsg::<T::Response>::from(&T::Response::default());
+            if let Err(e) = self.send_response(request_id, Box::new(response)) {
+                self.errors.report(SpinOperation::Send, e);
+            }
+        }
+    }
 }
 
 impl<T: 'static> Service_ for TypedService<T>
@@ -81,6 +242,14 @@ where
         }
     }
 
+    fn refused_requests(&self) -> usize {
+        self.refused_requests
+    }
+
+    fn is_dropped(&self) -> bool {
+        self.sink.is_closed()
+    }
+
     fn handle_request(&mut self, service: Arc<Mutex<dyn Service_>>) -> bool {
         let mut request_id = MaybeUninit::<rmw_request_id_t>::uninit();
         let mut request_msg = WrappedNativeMsg::<T::Request>::new();
@@ -94,22 +263,35 @@ where
         };
         if ret == RCL_RET_OK as i32 {
             let request_id = unsafe { request_id.assume_init() };
+            let verdict = match &self.options.validator {
+                Some(validator) => validator(&request_msg),
+                None => RequestVerdict::Accept,
+            };
+            if verdict != RequestVerdict::Accept {
+                self.refuse(request_id, verdict);
+                return false;
+            }
             let request_msg = T::Request::from_native(&request_msg);
+            let verdict = self.options.check_limits(&request_msg);
+            if verdict != RequestVerdict::Accept {
+                self.refuse(request_id, verdict);
+                return false;
+            }
             let request = ServiceRequest::<T> {
                 message: request_msg,
                 request_id,
                 service: Arc::downgrade(&service),
             };
-            match self.sender.try_send(request) {
-                Err(e) => {
-                    if e.is_disconnected() {
-                        return true;
-                    }
-                    eprintln!("warning: could not send service request ({})", e)
+            match self.sink.try_deliver(request) {
+                DeliverResult::Accepted => (),
+                DeliverResult::DroppedFull => {
+                    self.errors.report(SpinOperation::Deliver, sink_full());
                 }
-                _ => (),
+                DeliverResult::Closed => return true,
             }
-        } // TODO handle failure.
+        } else if ret != RCL_RET_SERVICE_TAKE_FAILED as i32 {
+            self.errors.report(SpinOperation::Take, Error::from_rcl_error(ret));
+        }
         return false;
     }
 
@@ -120,6 +302,94 @@ where
     }
 }
 
+pub struct UntypedService {
+    pub rcl_handle: rcl_service_t,
+    pub service_type: Arc<UntypedServiceSupport>,
+    pub sink: Box<dyn MessageSink<ServiceRequestUntyped>>,
+    pub errors: EntityErrors,
+}
+
+impl Service_ for UntypedService {
+    fn handle(&self) -> &rcl_service_t {
+        &self.rcl_handle
+    }
+
+    fn send_response(
+        &mut self,
+        mut request_id: rmw_request_id_t,
+        mut msg: Box<dyn VoidPtr>,
+    ) -> Result<()> {
+        let res =
+            unsafe { rcl_send_response(&self.rcl_handle, &mut request_id, msg.void_ptr_mut()) };
+        if res == RCL_RET_OK as i32 {
+            Ok(())
+        } else {
+            Err(Error::from_rcl_error(res))
+        }
+    }
+
+    fn refused_requests(&self) -> usize {
+        0
+    }
+
+    fn is_dropped(&self) -> bool {
+        self.sink.is_closed()
+    }
+
+    fn handle_request(&mut self, service: Arc<Mutex<dyn Service_>>) -> bool {
+        let mut request_id = MaybeUninit::<rmw_request_id_t>::uninit();
+        let mut request_msg = (self.service_type.make_request_msg)();
+
+        let ret = unsafe {
+            rcl_take_request(
+                &self.rcl_handle,
+                request_id.as_mut_ptr(),
+                request_msg.void_ptr_mut(),
+            )
+        };
+        if ret == RCL_RET_OK as i32 {
+            let message = match request_msg.to_json() {
+                Ok(message) => message,
+                Err(e) => {
+                    self.errors.report(SpinOperation::Convert, e);
+                    return false;
+                }
+            };
+            let request = ServiceRequestUntyped {
+                message,
+                request_id: unsafe { request_id.assume_init() },
+                service_type: self.service_type.clone(),
+                service: Arc::downgrade(&service),
+            };
+            match self.sink.try_deliver(request) {
+                DeliverResult::Accepted => (),
+                DeliverResult::DroppedFull => {
+                    self.errors.report(SpinOperation::Deliver, sink_full());
+                }
+                DeliverResult::Closed => return true,
+            }
+        } else if ret != RCL_RET_SERVICE_TAKE_FAILED as i32 {
+            self.errors.report(SpinOperation::Take, Error::from_rcl_error(ret));
+        }
+        false
+    }
+
+    fn destroy(&mut self, node: &mut rcl_node_t) {
+        unsafe {
+            rcl_service_fini(&mut self.rcl_handle, node);
+        }
+    }
+}
+
+pub fn service_name(rcl_handle: &rcl_service_t) -> Result<String> {
+    let cstr = unsafe { rcl_service_get_service_name(rcl_handle) };
+    if cstr == std::ptr::null() {
+        return Err(Error::RCL_RET_SERVICE_INVALID);
+    }
+    let s = unsafe { CStr::from_ptr(cstr) };
+    Ok(s.to_str().unwrap_or("").to_owned())
+}
+
 pub fn create_service_helper(
     node: &mut rcl_node_t,
     service_name: &str,
@@ -129,7 +399,7 @@ pub fn create_service_helper(
     let service_name_c_string =
         CString::new(service_name).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
 
-    let result = unsafe {
+    rcl_call(|| unsafe {
         let service_options = rcl_service_get_default_options();
         rcl_service_init(
             &mut service_handle,
@@ -138,10 +408,22 @@ pub fn create_service_helper(
             service_name_c_string.as_ptr(),
             &service_options,
         )
-    };
-    if result == RCL_RET_OK as i32 {
-        Ok(service_handle)
-    } else {
-        Err(Error::from_rcl_error(result))
+    })?;
+    Ok(service_handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_within_limits() {
+        let v = json!({"name": "hello", "values": [1, 2, 3], "nested": {"tags": ["a", "bb"]}});
+        assert!(within_limits(&v, None, None));
+        assert!(within_limits(&v, Some(5), Some(3)));
+        assert!(!within_limits(&v, Some(4), None));
+        assert!(!within_limits(&v, None, Some(2)));
+        assert!(!within_limits(&json!({"nested": {"tags": ["abc"]}}), Some(2), None));
     }
 }
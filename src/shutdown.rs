@@ -0,0 +1,34 @@
+//! Orderly shutdown of a node, see `Node::shutdown`.
+//!
+//! Shutting down runs in phases: first the hooks registered with
+//! `Node::on_shutdown`, e.g. to publish a last status message, then
+//! optionally a flush of the reliable publishers so that what the hooks
+//! published reaches the subscribers. The entities of the node are
+//! destroyed when it is dropped.
+
+use std::time::Duration;
+
+use crate::error::*;
+use crate::nodes::Node;
+use crate::publishers::FlushStatus;
+
+pub(crate) type ShutdownHook = Box<dyn FnOnce(&mut Node) -> Result<()> + Send>;
+
+/// Options for `Node::shutdown`.
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownOptions {
+    /// Flush the reliable publishers after the hooks have run, waiting
+    /// at most this long, see `Node::flush_publishers`. `None` (the
+    /// default) skips the flush.
+    pub flush_timeout: Option<Duration>,
+}
+
+/// What happened in the phases of `Node::shutdown`.
+#[derive(Debug, Default)]
+pub struct ShutdownReport {
+    /// The errors of the hooks that failed, in the order they ran.
+    pub hook_errors: Vec<Error>,
+    /// The topic name and outcome for each reliable publisher, empty if
+    /// the flush was skipped.
+    pub flushed: Vec<(String, FlushStatus)>,
+}
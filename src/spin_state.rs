@@ -0,0 +1,267 @@
+//! What the spinning thread of a node is doing, for finding deadlocks.
+//!
+//! Only built with the `spin-diagnostics` feature.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::executor::EntityKind;
+use crate::nodes::Node;
+use crate::threads::ThreadHooks;
+
+/// What the spinning thread of a node is doing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SpinState {
+    Idle,
+    /// Inside `rcl_wait`.
+    Waiting {
+        since: Instant,
+    },
+    /// Handling a ready entity in `Node::execute`.
+    Dispatching {
+        entity: EntityKind,
+        since: Instant,
+    },
+}
+
+/// Something that looks like a deadlock to the `deadlock_watchdog`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DeadlockSuspect {
+    /// An entity has been handled for `duration`, e.g. a callback that
+    /// does not return.
+    LongDispatch {
+        entity: EntityKind,
+        duration: Duration,
+    },
+    /// A `SpinMonitor::lock` call has waited for the node for
+    /// `duration`.
+    BlockedLock { duration: Duration },
+}
+
+const IDLE: u8 = 0;
+const WAITING: u8 = 1;
+const DISPATCHING: u8 = 2;
+
+// Kept in atomics so that the spin loop only pays for a few stores.
+pub(crate) struct SpinTracker {
+    base: Instant,
+    phase: AtomicU8,
+    entity: AtomicU8,
+    // nanoseconds since base
+    since: AtomicU64,
+    // start of each SpinMonitor::lock call that has not got the lock yet
+    lock_waits: Mutex<Vec<(u64, Instant)>>,
+    next_lock_wait: AtomicU64,
+}
+
+impl SpinTracker {
+    pub(crate) fn new() -> Self {
+        SpinTracker {
+            base: Instant::now(),
+            phase: AtomicU8::new(IDLE),
+            entity: AtomicU8::new(0),
+            since: AtomicU64::new(0),
+            lock_waits: Mutex::new(vec![]),
+            next_lock_wait: AtomicU64::new(0),
+        }
+    }
+
+    fn set(&self, phase: u8) {
+        let since = self.base.elapsed().as_nanos() as u64;
+        self.since.store(since, Ordering::Relaxed);
+        self.phase.store(phase, Ordering::Release);
+    }
+
+    pub(crate) fn set_idle(&self) {
+        self.set(IDLE);
+    }
+
+    pub(crate) fn set_waiting(&self) {
+        self.set(WAITING);
+    }
+
+    pub(crate) fn set_dispatching(&self, entity: EntityKind) {
+        self.entity.store(entity_to_u8(entity), Ordering::Relaxed);
+        self.set(DISPATCHING);
+    }
+
+    fn state(&self) -> SpinState {
+        let phase = self.phase.load(Ordering::Acquire);
+        let since = self.base + Duration::from_nanos(self.since.load(Ordering::Relaxed));
+        match phase {
+            WAITING => SpinState::Waiting { since },
+            DISPATCHING => SpinState::Dispatching {
+                entity: entity_from_u8(self.entity.load(Ordering::Relaxed)),
+                since,
+            },
+            _ => SpinState::Idle,
+        }
+    }
+
+    fn longest_lock_wait(&self) -> Option<Duration> {
+        let waits = self.lock_waits.lock().unwrap();
+        waits.iter().map(|(_, start)| start.elapsed()).max()
+    }
+}
+
+fn entity_to_u8(entity: EntityKind) -> u8 {
+    match entity {
+        EntityKind::Subscription => 0,
+        EntityKind::Timer => 1,
+        EntityKind::Client => 2,
+        EntityKind::Service => 3,
+        EntityKind::ActionClient => 4,
+        EntityKind::ActionServer => 5,
+        EntityKind::Publisher => 6,
+        EntityKind::Node => 7,
+    }
+}
+
+fn entity_from_u8(entity: u8) -> EntityKind {
+    match entity {
+        0 => EntityKind::Subscription,
+        1 => EntityKind::Timer,
+        2 => EntityKind::Client,
+        3 => EntityKind::Service,
+        4 => EntityKind::ActionClient,
+        5 => EntityKind::ActionServer,
+        6 => EntityKind::Publisher,
+        _ => EntityKind::Node,
+    }
+}
+
+/// Observes the spin state of a node without locking it.
+#[derive(Clone)]
+pub struct SpinMonitor {
+    tracker: Arc<SpinTracker>,
+    hooks: ThreadHooks,
+}
+
+impl SpinMonitor {
+    pub(crate) fn new(tracker: Arc<SpinTracker>, hooks: ThreadHooks) -> Self {
+        SpinMonitor { tracker, hooks }
+    }
+
+    pub fn state(&self) -> SpinState {
+        self.tracker.state()
+    }
+
+    /// Locks a shared node like `node.lock().unwrap()`, but lets the
+    /// `deadlock_watchdog` see how long the caller has been blocked.
+    /// Use it where entities are created from callbacks.
+    pub fn lock<'a>(&self, node: &'a Mutex<Node>) -> MutexGuard<'a, Node> {
+        let id = self.tracker.next_lock_wait.fetch_add(1, Ordering::Relaxed);
+        self.tracker
+            .lock_waits
+            .lock()
+            .unwrap()
+            .push((id, Instant::now()));
+        let guard = node.lock().unwrap();
+        self.tracker
+            .lock_waits
+            .lock()
+            .unwrap()
+            .retain(|(i, _)| *i != id);
+        guard
+    }
+
+    /// Calls `callback` from a background thread when the spinning
+    /// thread has handled one entity for longer than `threshold`, or
+    /// when a `lock` call has been blocked for longer than
+    /// `threshold`. Each incident is reported once. The watchdog stops
+    /// when the returned handle is dropped.
+    pub fn deadlock_watchdog<F>(&self, threshold: Duration, mut callback: F) -> DeadlockWatchdog
+    where
+        F: FnMut(DeadlockSuspect) + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let tracker = self.tracker.clone();
+        let period = std::cmp::max(threshold / 4, Duration::from_millis(1));
+        self.hooks
+            .spawn("watchdog", move || {
+                let mut reported_dispatch = None;
+                let mut reported_lock = false;
+                while !thread_stop.load(Ordering::Relaxed) {
+                    match tracker.state() {
+                        SpinState::Dispatching { entity, since } => {
+                            let duration = since.elapsed();
+                            if duration > threshold && reported_dispatch != Some(since) {
+                                reported_dispatch = Some(since);
+                                callback(DeadlockSuspect::LongDispatch { entity, duration });
+                            }
+                        }
+                        _ => reported_dispatch = None,
+                    }
+                    match tracker.longest_lock_wait() {
+                        Some(duration) if duration > threshold => {
+                            if !reported_lock {
+                                reported_lock = true;
+                                callback(DeadlockSuspect::BlockedLock { duration });
+                            }
+                        }
+                        _ => reported_lock = false,
+                    }
+                    thread::sleep(period);
+                }
+            })
+            .expect("could not start the watchdog thread");
+        DeadlockWatchdog { stop }
+    }
+}
+
+/// Stops the watchdog thread when dropped.
+pub struct DeadlockWatchdog {
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for DeadlockWatchdog {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spin_state_transitions() {
+        let tracker = SpinTracker::new();
+        assert_eq!(tracker.state(), SpinState::Idle);
+        tracker.set_waiting();
+        assert!(matches!(tracker.state(), SpinState::Waiting { .. }));
+        tracker.set_dispatching(EntityKind::ActionServer);
+        match tracker.state() {
+            SpinState::Dispatching { entity, since } => {
+                assert_eq!(entity, EntityKind::ActionServer);
+                assert!(since <= Instant::now());
+            }
+            s => panic!("unexpected state {:?}", s),
+        }
+        tracker.set_idle();
+        assert_eq!(tracker.state(), SpinState::Idle);
+    }
+
+    #[test]
+    fn test_watchdog_reports_long_dispatch_once() {
+        let tracker = Arc::new(SpinTracker::new());
+        let monitor = SpinMonitor::new(tracker.clone(), ThreadHooks::default());
+        let (tx, rx) = std::sync::mpsc::channel();
+        let _watchdog = monitor.deadlock_watchdog(Duration::from_millis(20), move |s| {
+            let _ = tx.send(s);
+        });
+        tracker.set_dispatching(EntityKind::Timer);
+        match rx.recv_timeout(Duration::from_secs(2)).unwrap() {
+            DeadlockSuspect::LongDispatch { entity, duration } => {
+                assert_eq!(entity, EntityKind::Timer);
+                assert!(duration > Duration::from_millis(20));
+            }
+            s => panic!("unexpected suspect {:?}", s),
+        }
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+        tracker.set_idle();
+    }
+}
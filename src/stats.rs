@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::os::raw::c_void;
+use std::time::{Duration, Instant};
+
+use r2r_rcl::*;
+
+// the frequency is computed over the last second, in buckets so that
+// fast topics do not need to remember every message.
+const BUCKET: Duration = Duration::from_millis(100);
+const BUCKETS: usize = 10;
+
+/// Message statistics of a publisher or subscription.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TopicStats {
+    /// Number of messages since the entity was created.
+    pub messages: u64,
+    /// Serialized size of those messages in bytes. Estimated from the
+    /// size of the native message if it cannot be serialized.
+    pub bytes: u64,
+    /// Messages per second during the last second.
+    pub frequency: f64,
+    /// Messages a publisher dropped instead of publishing them, see
+    /// `Publisher::rate_limited` and `Node::mirror`.
+    pub dropped: u64,
+}
+
+impl TopicStats {
+    fn add(&mut self, other: &TopicStats) {
+        self.messages += other.messages;
+        self.bytes += other.bytes;
+        self.frequency += other.frequency;
+        self.dropped += other.dropped;
+    }
+}
+
+/// Statistics of all publishers and subscriptions of a node that have
+/// them enabled, summed per topic, and the counters of its services.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeMetrics {
+    pub published: HashMap<String, TopicStats>,
+    pub received: HashMap<String, TopicStats>,
+    /// Requests refused by the validation of each service, see
+    /// `ServiceOptions`.
+    pub refused_requests: HashMap<String, usize>,
+}
+
+pub struct StatsTracker {
+    pub topic: String,
+    pub published: bool,
+    // steady clock, so that sim time jumps do not matter.
+    created: Instant,
+    messages: u64,
+    bytes: u64,
+    dropped: u64,
+    last_message: Option<Instant>,
+    buckets: [u64; BUCKETS],
+    current_bucket: u64,
+    serialized: rmw_serialized_message_t,
+}
+
+unsafe impl Send for StatsTracker {}
+
+impl fmt::Debug for StatsTracker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StatsTracker")
+            .field("topic", &self.topic)
+            .field("published", &self.published)
+            .field("messages", &self.messages)
+            .finish()
+    }
+}
+
+impl StatsTracker {
+    pub fn new(topic: &str, published: bool) -> Self {
+        let mut serialized = unsafe { rcutils_get_zero_initialized_uint8_array() };
+        unsafe {
+            // rmw_serialize grows the buffer as needed.
+            rcutils_uint8_array_init(&mut serialized, 0, &rcutils_get_default_allocator());
+        }
+        StatsTracker {
+            topic: topic.to_owned(),
+            published,
+            created: Instant::now(),
+            messages: 0,
+            bytes: 0,
+            dropped: 0,
+            last_message: None,
+            buckets: [0; BUCKETS],
+            current_bucket: 0,
+            serialized,
+        }
+    }
+
+    /// Records one message. `msg` points to the native message of
+    /// type `ts` and `native_size` is used if it cannot be serialized.
+    /// Returns the size that was recorded.
+    pub fn record(
+        &mut self,
+        ts: *const rosidl_message_type_support_t,
+        msg: *const c_void,
+        native_size: usize,
+    ) -> usize {
+        let size = self.serialized_size(ts, msg).unwrap_or(native_size);
+        self.record_size(size, Instant::now());
+        size
+    }
+
+    /// Records one message of `size` bytes, e.g. one whose size is
+    /// known from recording it on another tracker.
+    pub fn record_bytes(&mut self, size: usize) {
+        self.record_size(size, Instant::now());
+    }
+
+    fn serialized_size(
+        &mut self,
+        ts: *const rosidl_message_type_support_t,
+        msg: *const c_void,
+    ) -> Option<usize> {
+        let ret = unsafe { rmw_serialize(msg, ts, &mut self.serialized) };
+        if ret == RMW_RET_OK as i32 {
+            Some(self.serialized.buffer_length)
+        } else {
+            None
+        }
+    }
+
+    fn record_size(&mut self, size: usize, now: Instant) {
+        self.advance(now);
+        self.buckets[self.current_bucket as usize % BUCKETS] += 1;
+        self.messages += 1;
+        self.bytes += size as u64;
+        self.last_message = Some(now);
+    }
+
+    /// Records a message that was dropped before it was published.
+    pub fn record_dropped(&mut self) {
+        self.dropped += 1;
+    }
+
+    /// When the last message was recorded.
+    pub fn last_message(&self) -> Option<Instant> {
+        self.last_message
+    }
+
+    fn advance(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.created);
+        let bucket = (elapsed.as_nanos() / BUCKET.as_nanos()) as u64;
+        if bucket <= self.current_bucket {
+            return;
+        }
+        let expired = (bucket - self.current_bucket).min(BUCKETS as u64);
+        for i in 1..=expired {
+            self.buckets[(self.current_bucket + i) as usize % BUCKETS] = 0;
+        }
+        self.current_bucket = bucket;
+    }
+
+    pub fn stats(&mut self) -> TopicStats {
+        self.stats_at(Instant::now())
+    }
+
+    fn stats_at(&mut self, now: Instant) -> TopicStats {
+        self.advance(now);
+        let elapsed = now.saturating_duration_since(self.created);
+        // the current bucket is only partly filled.
+        let current_start = BUCKET * self.current_bucket as u32;
+        let window = elapsed.saturating_sub(current_start) + BUCKET * (BUCKETS as u32 - 1);
+        let window = window.min(elapsed);
+        let count: u64 = self.buckets.iter().sum();
+        let frequency = if window.is_zero() {
+            0.0
+        } else {
+            count as f64 / window.as_secs_f64()
+        };
+        TopicStats {
+            messages: self.messages,
+            bytes: self.bytes,
+            frequency,
+            dropped: self.dropped,
+        }
+    }
+}
+
+impl Drop for StatsTracker {
+    fn drop(&mut self) {
+        unsafe {
+            rcutils_uint8_array_fini(&mut self.serialized);
+        }
+    }
+}
+
+pub fn add_to_metrics(metrics: &mut NodeMetrics, tracker: &mut StatsTracker) {
+    let map = if tracker.published {
+        &mut metrics.published
+    } else {
+        &mut metrics.received
+    };
+    map.entry(tracker.topic.clone())
+        .or_default()
+        .add(&tracker.stats());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_window() {
+        let mut tracker = StatsTracker::new("/test", true);
+        let start = tracker.created;
+        // 10 messages per second for two seconds.
+        for i in 0..20 {
+            tracker.record_size(4, start + Duration::from_millis(i * 100 + 50));
+        }
+        let stats = tracker.stats_at(start + Duration::from_millis(2000));
+        assert_eq!(stats.messages, 20);
+        assert_eq!(stats.bytes, 80);
+        assert!((stats.frequency - 10.0).abs() < 1.0);
+
+        // nothing in the last second.
+        let stats = tracker.stats_at(start + Duration::from_millis(3500));
+        assert_eq!(stats.messages, 20);
+        assert_eq!(stats.frequency, 0.0);
+    }
+}
@@ -1,15 +1,40 @@
 use futures::channel::mpsc;
-use std::ffi::CString;
+use futures::stream::Stream;
+use std::collections::{HashMap, HashSet};
+use std::ffi::{CStr, CString};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
+use crate::error_events::*;
+use crate::executor::EntityKind;
 use crate::msg_types::*;
+use crate::stats::*;
 use crate::error::*;
+use crate::message_sinks::{sink_full, DeliverResult, MessageSink};
+use crate::distro;
+use crate::nodes::publishers_info_by_topic;
+use crate::readiness::publisher_count;
+use crate::message_filter::ContentFilter;
+use crate::qos::QosProfile;
+use crate::qos_overrides::QosOverridePolicy;
+use crate::threads::ThreadHooks;
+use crate::typesupport_loader::MessageTypeSupport;
 use r2r_rcl::*;
 
 pub trait Subscriber_ {
     fn handle(&self) -> &rcl_subscription_t;
-    /// Returns true if the subscriber stream has been dropped.
+    fn priority(&self) -> i32;
+    /// Returns true when the user has dropped the receiving stream.
+    fn is_dropped(&self) -> bool;
     fn handle_incoming(&mut self) -> bool;
+    /// Replaces the rcl subscription with a new one on the same topic.
+    fn recreate(&mut self, node: &mut rcl_node_t) -> Result<()>;
     fn destroy(&mut self, node: &mut rcl_node_t) -> ();
+    /// Compares the types of newly matched publishers with ours.
+    fn check_types(&mut self, _node: &rcl_node_t) {}
 }
 
 pub struct TypedSubscriber<T>
@@ -17,7 +42,234 @@ where
     T: WrappedTypesupport,
 {
     pub rcl_handle: rcl_subscription_t,
-    pub sender: mpsc::Sender<T>,
+    pub priority: i32,
+    pub sink: Arc<dyn MessageSink<(T, MessageInfo)>>,
+    // number of messages sent but not yet received
+    pub pending: Arc<AtomicUsize>,
+    pub stats: Option<Arc<Mutex<StatsTracker>>>,
+    pub errors: EntityErrors,
+    pub sequence: SequenceTracker,
+    // set again when the subscription is recreated.
+    pub content_filter: Option<ContentFilter>,
+    pub conversion: Option<ConversionWorker<T>>,
+    pub type_check: TypeCheck,
+}
+
+/// Converts the messages of a subscription on a thread of its own, so
+/// that converting large messages does not hold up the spin thread.
+/// There is one thread per subscription, so the messages reach the
+/// stream in the order they were taken.
+pub struct ConversionWorker<T>
+where
+    T: WrappedTypesupport,
+{
+    sender: std::sync::mpsc::SyncSender<(WrappedNativeMsg<T>, MessageInfo)>,
+}
+
+impl<T: 'static> ConversionWorker<T>
+where
+    T: WrappedTypesupport,
+{
+    pub fn new(
+        sink: Arc<dyn MessageSink<(T, MessageInfo)>>,
+        pending: Arc<AtomicUsize>,
+        errors: EntityErrors,
+        capacity: usize,
+        hooks: &ThreadHooks,
+    ) -> Result<Self> {
+        let (native_sender, native_receiver) =
+            std::sync::mpsc::sync_channel::<(WrappedNativeMsg<T>, MessageInfo)>(capacity);
+        // ends when the subscription is destroyed, or the stream is dropped.
+        hooks
+            .spawn("conversion", move || {
+                for (native, info) in native_receiver {
+                    let msg = T::from_native(&native);
+                    drop(native);
+                    pending.fetch_add(1, Ordering::Relaxed);
+                    match sink.try_deliver((msg, info)) {
+                        DeliverResult::Accepted => (),
+                        DeliverResult::DroppedFull => {
+                            pending.fetch_sub(1, Ordering::Relaxed);
+                            errors.report(SpinOperation::Deliver, sink_full());
+                        }
+                        DeliverResult::Closed => {
+                            pending.fetch_sub(1, Ordering::Relaxed);
+                            break;
+                        }
+                    }
+                }
+            })
+            .map_err(|e| Error::DeliveryFailed {
+                reason: format!("could not start the conversion thread: {}", e),
+            })?;
+        Ok(ConversionWorker {
+            sender: native_sender,
+        })
+    }
+
+    fn convert(&self, msg: WrappedNativeMsg<T>, info: MessageInfo) -> Result<()> {
+        self.sender.try_send((msg, info)).map_err(|e| {
+            let reason = match e {
+                std::sync::mpsc::TrySendError::Full(_) => "conversion queue full",
+                std::sync::mpsc::TrySendError::Disconnected(_) => "conversion thread stopped",
+            };
+            Error::DeliveryFailed {
+                reason: reason.into(),
+            }
+        })
+    }
+}
+
+/// Information about a received message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageInfo {
+    /// When the message was published, in nanoseconds.
+    pub source_timestamp: i64,
+    /// When the message was received, in nanoseconds.
+    pub received_timestamp: i64,
+    pub publisher_gid: Vec<u8>,
+    pub from_intra_process: bool,
+    /// Sequence number of the message among the messages of its
+    /// publisher, if the rmw implementation provides them.
+    pub publication_sequence_number: Option<u64>,
+}
+
+impl From<&rmw_message_info_t> for MessageInfo {
+    fn from(info: &rmw_message_info_t) -> Self {
+        MessageInfo {
+            source_timestamp: info.source_timestamp,
+            received_timestamp: info.received_timestamp,
+            publisher_gid: info.publisher_gid.data.to_vec(),
+            from_intra_process: info.from_intra_process,
+            publication_sequence_number: distro::publication_sequence_number(info),
+        }
+    }
+}
+
+/// Remembers the last sequence number seen from each publisher of a
+/// subscription, to detect messages that were lost on the way.
+///
+/// Tracking stops for good at the first message without a sequence
+/// number, as the rmw implementation does not provide them.
+#[derive(Debug, Default)]
+pub struct SequenceTracker {
+    last: HashMap<Vec<u8>, u64>,
+    unsupported: bool,
+}
+
+impl SequenceTracker {
+    /// Records the message and returns how many messages from the same
+    /// publisher were skipped since the last one seen, if any.
+    pub fn record(&mut self, publisher_gid: &[u8], sequence_number: Option<u64>) -> Option<u64> {
+        if self.unsupported {
+            return None;
+        }
+        let sequence_number = match sequence_number {
+            Some(n) => n,
+            None => {
+                self.unsupported = true;
+                self.last.clear();
+                return None;
+            }
+        };
+        // a lower number than before means that the publisher started over.
+        let last = self.last.insert(publisher_gid.to_vec(), sequence_number)?;
+        if sequence_number > last + 1 {
+            Some(sequence_number - last - 1)
+        } else {
+            None
+        }
+    }
+
+    // Reports a gap, if any, as an error of the subscription.
+    fn check(&mut self, info: &rmw_message_info_t, errors: &EntityErrors) {
+        let gid = &info.publisher_gid.data[..];
+        if let Some(missed) = self.record(gid, distro::publication_sequence_number(info)) {
+            errors.report(
+                SpinOperation::Take,
+                Error::MessagesLost {
+                    publisher: format_gid(gid),
+                    missed,
+                },
+            );
+        }
+    }
+}
+
+fn format_gid(gid: &[u8]) -> String {
+    gid.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// A stream of messages from a subscription.
+///
+/// Besides awaiting the messages as a `Stream`, the messages that
+/// have arrived so far can be taken without blocking using `drain`,
+/// e.g. from a synchronous control loop.
+pub struct Subscription<T> {
+    receiver: mpsc::Receiver<(T, MessageInfo)>,
+    pending: Arc<AtomicUsize>,
+    stats: Option<Arc<Mutex<StatsTracker>>>,
+}
+
+impl<T> Subscription<T> {
+    /// Takes all messages that have arrived and not yet been taken.
+    pub fn drain(&mut self) -> Vec<T> {
+        self.drain_with_info().into_iter().map(|(msg, _)| msg).collect()
+    }
+
+    /// Like `drain`, but also returns information about each message.
+    pub fn drain_with_info(&mut self) -> Vec<(T, MessageInfo)> {
+        let mut msgs = Vec::with_capacity(self.len());
+        while let Ok(Some(msg)) = self.receiver.try_next() {
+            self.pending.fetch_sub(1, Ordering::Relaxed);
+            msgs.push(msg);
+        }
+        msgs
+    }
+
+    /// Number of messages that have arrived and not yet been taken.
+    pub fn len(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Message statistics of this subscription, if enabled in the
+    /// `SubscriptionOptions`.
+    pub fn stats(&self) -> Option<TopicStats> {
+        self.stats.as_ref().map(|s| s.lock().unwrap().stats())
+    }
+}
+
+impl<T> Stream for Subscription<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        match Pin::new(&mut self.receiver).poll_next(cx) {
+            Poll::Ready(Some((msg, _))) => {
+                self.pending.fetch_sub(1, Ordering::Relaxed);
+                Poll::Ready(Some(msg))
+            }
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+pub fn make_subscription<T>(
+    sender_capacity: usize,
+    stats: Option<Arc<Mutex<StatsTracker>>>,
+) -> (mpsc::Sender<(T, MessageInfo)>, Arc<AtomicUsize>, Subscription<T>) {
+    let (sender, receiver) = mpsc::channel(sender_capacity);
+    let pending = Arc::new(AtomicUsize::new(0));
+    let subscription = Subscription {
+        receiver,
+        pending: pending.clone(),
+        stats,
+    };
+    (sender, pending, subscription)
 }
 
 pub struct NativeSubscriber<T>
@@ -25,13 +277,59 @@ where
     T: WrappedTypesupport,
 {
     pub rcl_handle: rcl_subscription_t,
-    pub sender: mpsc::Sender<WrappedNativeMsg<T>>,
+    pub priority: i32,
+    pub sink: Box<dyn MessageSink<WrappedNativeMsg<T>>>,
+    pub stats: Option<Arc<Mutex<StatsTracker>>>,
+    pub errors: EntityErrors,
+    pub sequence: SequenceTracker,
+    pub type_check: TypeCheck,
 }
 
 pub struct UntypedSubscriber {
     pub rcl_handle: rcl_subscription_t,
     pub topic_type: String,
-    pub sender: mpsc::Sender<Result<serde_json::Value>>,
+    pub priority: i32,
+    pub sink: Box<dyn MessageSink<Result<serde_json::Value>>>,
+    pub stats: Option<Arc<Mutex<StatsTracker>>>,
+    pub errors: EntityErrors,
+    pub sequence: SequenceTracker,
+}
+
+// Takes messages as bytes, for types known only at runtime.
+pub struct SerializedSubscriber {
+    pub rcl_handle: rcl_subscription_t,
+    pub type_support: MessageTypeSupport,
+    pub priority: i32,
+    pub sink: Box<dyn MessageSink<Vec<u8>>>,
+    // reused between takes, rcl grows it as needed.
+    pub buffer: rmw_serialized_message_t,
+    pub stats: Option<Arc<Mutex<StatsTracker>>>,
+    pub errors: EntityErrors,
+    pub sequence: SequenceTracker,
+}
+
+impl SerializedSubscriber {
+    pub fn new(
+        rcl_handle: rcl_subscription_t,
+        type_support: MessageTypeSupport,
+        sink: Box<dyn MessageSink<Vec<u8>>>,
+        errors: EntityErrors,
+    ) -> Self {
+        let mut buffer = unsafe { rcutils_get_zero_initialized_uint8_array() };
+        unsafe {
+            rcutils_uint8_array_init(&mut buffer, 0, &rcutils_get_default_allocator());
+        }
+        SerializedSubscriber {
+            rcl_handle,
+            type_support,
+            priority: 0,
+            sink,
+            buffer,
+            stats: None,
+            errors,
+            sequence: SequenceTracker::default(),
+        }
+    }
 }
 
 impl<T: 'static> Subscriber_ for TypedSubscriber<T>
@@ -42,8 +340,20 @@ where
         &self.rcl_handle
     }
 
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn is_dropped(&self) -> bool {
+        self.sink.is_closed()
+    }
+
     fn handle_incoming(&mut self) -> bool {
-        let mut msg_info = rmw_message_info_t::default(); // we dont care for now
+        if self.is_dropped() {
+            // no need to take and convert the message.
+            return true;
+        }
+        let mut msg_info = rmw_message_info_t::default();
         let mut msg = WrappedNativeMsg::<T>::new();
         let ret = unsafe {
             rcl_take(
@@ -54,26 +364,60 @@ where
             )
         };
         if ret == RCL_RET_OK as i32 {
+            self.sequence.check(&msg_info, &self.errors);
+            if let Some(stats) = &self.stats {
+                stats.lock().unwrap().record(
+                    T::get_ts(),
+                    msg.void_ptr(),
+                    std::mem::size_of::<T::CStruct>(),
+                );
+            }
+            if let Some(conversion) = &self.conversion {
+                if let Err(e) = conversion.convert(msg, MessageInfo::from(&msg_info)) {
+                    self.errors.report(SpinOperation::Deliver, e);
+                }
+                return false;
+            }
             let msg = T::from_native(&msg);
-            match self.sender.try_send(msg) {
-                Err(e) => {
-                    if e.is_disconnected() {
-                        // user dropped the handle to the stream, signal removal.
-                        return true;
-                    }
-                    println!("error {:?}", e)
+            // count before sending so that the receiver never sees a
+            // message that has not been counted.
+            self.pending.fetch_add(1, Ordering::Relaxed);
+            match self.sink.try_deliver((msg, MessageInfo::from(&msg_info))) {
+                DeliverResult::Accepted => (),
+                DeliverResult::DroppedFull => {
+                    self.pending.fetch_sub(1, Ordering::Relaxed);
+                    self.errors.report(SpinOperation::Deliver, sink_full());
+                }
+                DeliverResult::Closed => {
+                    self.pending.fetch_sub(1, Ordering::Relaxed);
+                    // user dropped the handle to the stream, signal removal.
+                    return true;
                 }
-                _ => (),
             }
+        } else {
+            report_take_failure(&self.errors, ret);
         }
         return false;
     }
 
+    fn recreate(&mut self, node: &mut rcl_node_t) -> Result<()> {
+        recreate_filtered_subscription_helper(
+            &mut self.rcl_handle,
+            node,
+            T::get_ts(),
+            self.content_filter.as_ref(),
+        )
+    }
+
     fn destroy(&mut self, node: &mut rcl_node_t) {
         unsafe {
             rcl_subscription_fini(&mut self.rcl_handle, node);
         }
     }
+
+    fn check_types(&mut self, node: &rcl_node_t) {
+        self.type_check.update(node, &self.rcl_handle, &self.errors);
+    }
 }
 
 impl<T: 'static> Subscriber_ for NativeSubscriber<T>
@@ -84,8 +428,20 @@ where
         &self.rcl_handle
     }
 
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn is_dropped(&self) -> bool {
+        self.sink.is_closed()
+    }
+
     fn handle_incoming(&mut self) -> bool {
-        let mut msg_info = rmw_message_info_t::default(); // we dont care for now
+        if self.is_dropped() {
+            // no need to take and convert the message.
+            return true;
+        }
+        let mut msg_info = rmw_message_info_t::default();
         let mut msg = WrappedNativeMsg::<T>::new();
         let ret = unsafe {
             rcl_take(
@@ -96,20 +452,38 @@ where
             )
         };
         if ret == RCL_RET_OK as i32 {
-            match self.sender.try_send(msg) {
-                Err(e) => {
-                    if e.is_disconnected() {
-                        // user dropped the handle to the stream, signal removal.
-                        return true;
-                    }
-                    println!("error {:?}", e)
+            self.sequence.check(&msg_info, &self.errors);
+            if let Some(stats) = &self.stats {
+                stats.lock().unwrap().record(
+                    T::get_ts(),
+                    msg.void_ptr(),
+                    std::mem::size_of::<T::CStruct>(),
+                );
+            }
+            match self.sink.try_deliver(msg) {
+                DeliverResult::Accepted => (),
+                DeliverResult::DroppedFull => {
+                    self.errors.report(SpinOperation::Deliver, sink_full())
+                }
+                DeliverResult::Closed => {
+                    // user dropped the handle to the stream, signal removal.
+                    return true;
                 }
-                _ => (),
             }
+        } else {
+            report_take_failure(&self.errors, ret);
         }
         return false;
     }
 
+    fn recreate(&mut self, node: &mut rcl_node_t) -> Result<()> {
+        recreate_subscription_helper(&mut self.rcl_handle, node, T::get_ts())
+    }
+
+    fn check_types(&mut self, node: &rcl_node_t) {
+        self.type_check.update(node, &self.rcl_handle, &self.errors);
+    }
+
     fn destroy(&mut self, node: &mut rcl_node_t) {
         unsafe {
             rcl_subscription_fini(&mut self.rcl_handle, node);
@@ -122,8 +496,20 @@ impl Subscriber_ for UntypedSubscriber {
         &self.rcl_handle
     }
 
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn is_dropped(&self) -> bool {
+        self.sink.is_closed()
+    }
+
     fn handle_incoming(&mut self) -> bool {
-        let mut msg_info = rmw_message_info_t::default(); // we dont care for now
+        if self.is_dropped() {
+            // no need to take and convert the message.
+            return true;
+        }
+        let mut msg_info = rmw_message_info_t::default();
         let mut msg = WrappedNativeMsgUntyped::new_from(&self.topic_type)
             .expect(&format!("no typesupport for {}", self.topic_type));
         let ret = unsafe {
@@ -135,21 +521,33 @@ impl Subscriber_ for UntypedSubscriber {
             )
         };
         if ret == RCL_RET_OK as i32 {
+            self.sequence.check(&msg_info, &self.errors);
+            if let Some(stats) = &self.stats {
+                // the size of the native message is not known here.
+                stats.lock().unwrap().record(msg.ts, msg.void_ptr(), 0);
+            }
             let json = msg.to_json();
-            match self.sender.try_send(json) {
-                Err(e) => {
-                    if e.is_disconnected() {
-                        // user dropped the handle to the stream, signal removal.
-                        return true;
-                    }
-                    println!("error {:?}", e)
+            match self.sink.try_deliver(json) {
+                DeliverResult::Accepted => (),
+                DeliverResult::DroppedFull => {
+                    self.errors.report(SpinOperation::Deliver, sink_full())
+                }
+                DeliverResult::Closed => {
+                    // user dropped the handle to the stream, signal removal.
+                    return true;
                 }
-                _ => (),
             }
+        } else {
+            report_take_failure(&self.errors, ret);
         }
         return false;
     }
 
+    fn recreate(&mut self, node: &mut rcl_node_t) -> Result<()> {
+        let msg = WrappedNativeMsgUntyped::new_from(&self.topic_type)?;
+        recreate_subscription_helper(&mut self.rcl_handle, node, msg.ts)
+    }
+
     fn destroy(&mut self, node: &mut rcl_node_t) {
         unsafe {
             rcl_subscription_fini(&mut self.rcl_handle, node);
@@ -157,17 +555,92 @@ impl Subscriber_ for UntypedSubscriber {
     }
 }
 
+impl Subscriber_ for SerializedSubscriber {
+    fn handle(&self) -> &rcl_subscription_t {
+        &self.rcl_handle
+    }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn is_dropped(&self) -> bool {
+        self.sink.is_closed()
+    }
+
+    fn handle_incoming(&mut self) -> bool {
+        if self.is_dropped() {
+            return true;
+        }
+        let mut msg_info = rmw_message_info_t::default();
+        let ret = unsafe {
+            rcl_take_serialized_message(
+                &self.rcl_handle,
+                &mut self.buffer,
+                &mut msg_info,
+                std::ptr::null_mut(),
+            )
+        };
+        if ret == RCL_RET_OK as i32 {
+            self.sequence.check(&msg_info, &self.errors);
+            let data = if self.buffer.buffer_length == 0 {
+                vec![]
+            } else {
+                unsafe {
+                    std::slice::from_raw_parts(self.buffer.buffer, self.buffer.buffer_length)
+                }
+                .to_vec()
+            };
+            if let Some(stats) = &self.stats {
+                stats.lock().unwrap().record_bytes(data.len());
+            }
+            match self.sink.try_deliver(data) {
+                DeliverResult::Accepted => (),
+                DeliverResult::DroppedFull => {
+                    self.errors.report(SpinOperation::Deliver, sink_full())
+                }
+                DeliverResult::Closed => {
+                    // user dropped the handle to the stream, signal removal.
+                    return true;
+                }
+            }
+        } else {
+            report_take_failure(&self.errors, ret);
+        }
+        return false;
+    }
+
+    fn recreate(&mut self, node: &mut rcl_node_t) -> Result<()> {
+        recreate_subscription_helper(&mut self.rcl_handle, node, self.type_support.as_ptr())
+    }
+
+    fn destroy(&mut self, node: &mut rcl_node_t) {
+        unsafe {
+            rcl_subscription_fini(&mut self.rcl_handle, node);
+            rcutils_uint8_array_fini(&mut self.buffer);
+        }
+    }
+}
+
+pub(crate) fn report_take_failure(errors: &EntityErrors, ret: i32) {
+    // failing to take after a wakeup is expected now and then.
+    if ret != RCL_RET_SUBSCRIPTION_TAKE_FAILED as i32 {
+        errors.report(SpinOperation::Take, Error::from_rcl_error(ret));
+    }
+}
+
 pub fn create_subscription_helper(
     node: &mut rcl_node_t,
     topic: &str,
     ts: *const rosidl_message_type_support_t,
+    qos_profile: rmw_qos_profile_t,
 ) -> Result<rcl_subscription_t> {
     let mut subscription_handle = unsafe { rcl_get_zero_initialized_subscription() };
     let topic_c_string = CString::new(topic).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
 
-    let result = unsafe {
+    rcl_call(|| unsafe {
         let mut subscription_options = rcl_subscription_get_default_options();
-        subscription_options.qos = rmw_qos_profile_t::default();
+        subscription_options.qos = qos_profile;
         rcl_subscription_init(
             &mut subscription_handle,
             node,
@@ -175,10 +648,490 @@ pub fn create_subscription_helper(
             topic_c_string.as_ptr(),
             &subscription_options,
         )
+    })?;
+    Ok(subscription_handle)
+}
+
+/// Like `create_subscription_helper`, but with a content filter.
+/// Returns `Error::NotSupported` if the distribution does not have
+/// content filters.
+pub fn create_filtered_subscription_helper(
+    node: &mut rcl_node_t,
+    topic: &str,
+    ts: *const rosidl_message_type_support_t,
+    qos_profile: rmw_qos_profile_t,
+    content_filter: &ContentFilter,
+) -> Result<rcl_subscription_t> {
+    let mut subscription_handle = unsafe { rcl_get_zero_initialized_subscription() };
+    let topic_c_string = CString::new(topic).map_err(|_| Error::RCL_RET_INVALID_ARGUMENT)?;
+
+    let mut subscription_options = unsafe { rcl_subscription_get_default_options() };
+    subscription_options.qos = qos_profile;
+    distro::set_content_filter(&mut subscription_options, content_filter)?;
+    let result = rcl_call(|| unsafe {
+        rcl_subscription_init(
+            &mut subscription_handle,
+            node,
+            ts,
+            topic_c_string.as_ptr(),
+            &subscription_options,
+        )
+    });
+    distro::subscription_options_fini(&mut subscription_options);
+    result?;
+    Ok(subscription_handle)
+}
+
+pub fn recreate_subscription_helper(
+    rcl_handle: &mut rcl_subscription_t,
+    node: &mut rcl_node_t,
+    ts: *const rosidl_message_type_support_t,
+) -> Result<()> {
+    recreate_filtered_subscription_helper(rcl_handle, node, ts, None)
+}
+
+pub fn recreate_filtered_subscription_helper(
+    rcl_handle: &mut rcl_subscription_t,
+    node: &mut rcl_node_t,
+    ts: *const rosidl_message_type_support_t,
+    content_filter: Option<&ContentFilter>,
+) -> Result<()> {
+    let topic = subscription_topic_name(rcl_handle)?;
+    let options = unsafe { rcl_subscription_get_options(rcl_handle) };
+    if options == std::ptr::null() {
+        return Err(Error::RCL_RET_SUBSCRIPTION_INVALID);
+    }
+    let qos_profile = unsafe { (*options).qos };
+    // create the new subscription first so that we keep the old one on failure.
+    let new_handle = match content_filter {
+        Some(cf) => create_filtered_subscription_helper(node, &topic, ts, qos_profile, cf)?,
+        None => create_subscription_helper(node, &topic, ts, qos_profile)?,
     };
-    if result == RCL_RET_OK as i32 {
-        Ok(subscription_handle)
+    unsafe {
+        rcl_subscription_fini(rcl_handle, node);
+    }
+    *rcl_handle = new_handle;
+    Ok(())
+}
+
+pub fn subscription_topic_name(rcl_handle: &rcl_subscription_t) -> Result<String> {
+    let cstr = unsafe { rcl_subscription_get_topic_name(rcl_handle) };
+    if cstr == std::ptr::null() {
+        return Err(Error::RCL_RET_SUBSCRIPTION_INVALID);
+    }
+    let s = unsafe { CStr::from_ptr(cstr) };
+    Ok(s.to_str().unwrap_or("").to_owned())
+}
+
+pub fn subscription_publisher_count(rcl_handle: &rcl_subscription_t) -> Result<usize> {
+    let mut count = 0usize;
+    let ret = unsafe { rcl_subscription_get_publisher_count(rcl_handle, &mut count) };
+    if ret == RCL_RET_OK as i32 {
+        Ok(count)
     } else {
-        Err(Error::from_rcl_error(result))
+        Err(Error::from_rcl_error(ret))
+    }
+}
+
+/// Compares the types of the publishers of the topic of a typed
+/// subscription to its own, each time the number of publishers in the
+/// graph changes, so that a peer built against a different definition
+/// of the message is reported instead of decoded wrongly.
+///
+/// For publishers with our type name, the type hashes are compared
+/// where both sides have them (Iron and newer). In strict mode,
+/// publishers with another type name are reported as well, for
+/// distributions without hashes. Each publisher is reported once.
+pub struct TypeCheck {
+    topic: Option<String>,
+    type_name: String,
+    type_hash: Option<String>,
+    strict: bool,
+    publisher_count: usize,
+    reported: HashSet<String>,
+}
+
+impl TypeCheck {
+    pub fn new<T>(strict: bool) -> Self
+    where
+        T: WrappedTypesupport,
+    {
+        TypeCheck {
+            topic: None,
+            type_name: ros_type_name::<T>(),
+            type_hash: distro::message_type_hash(T::get_ts()).ok(),
+            strict,
+            publisher_count: 0,
+            reported: HashSet::new(),
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        node: &rcl_node_t,
+        subscription: &rcl_subscription_t,
+        errors: &EntityErrors,
+    ) {
+        if self.type_hash.is_none() && !self.strict {
+            return;
+        }
+        if let Err(e) = self.check(node, subscription, errors) {
+            errors.report(SpinOperation::Update, e);
+        }
+    }
+
+    fn check(
+        &mut self,
+        node: &rcl_node_t,
+        subscription: &rcl_subscription_t,
+        errors: &EntityErrors,
+    ) -> Result<()> {
+        if self.topic.is_none() {
+            self.topic = Some(subscription_topic_name(subscription)?);
+        }
+        let topic = self.topic.as_deref().unwrap_or("");
+        // publishers of other types are counted too, unlike the
+        // publishers matched with the subscription.
+        let count = publisher_count(node, topic)?;
+        if count == self.publisher_count {
+            return Ok(());
+        }
+        self.publisher_count = count;
+        for p in publishers_info_by_topic(node, topic)? {
+            let publisher = format!("{}/{}", p.node_namespace.trim_end_matches('/'), p.node_name);
+            let error = if p.topic_type != self.type_name {
+                if !self.strict {
+                    continue;
+                }
+                Error::TopicTypeMismatch {
+                    topic: topic.to_owned(),
+                    expected: self.type_name.clone(),
+                    found: p.topic_type,
+                }
+            } else {
+                // "RIHS00_..." is an endpoint that did not send its hash.
+                let their_hash = p.type_hash.filter(|h| !h.starts_with("RIHS00_"));
+                match (&self.type_hash, their_hash) {
+                    (Some(ours), Some(theirs)) if ours != &theirs => Error::TypeHashMismatch {
+                        topic: topic.to_owned(),
+                        publisher: publisher.clone(),
+                        expected: ours.clone(),
+                        found: theirs,
+                    },
+                    _ => continue,
+                }
+            };
+            if self.reported.insert(publisher) {
+                errors.report(SpinOperation::Update, error);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Options for creating subscriptions.
+#[derive(Debug, Clone, Default)]
+pub struct SubscriptionOptions {
+    /// Within one call to `spin_once`, ready subscriptions and timers
+    /// are handled in order of decreasing priority. Entities with
+    /// equal priority are handled in the order they were created,
+    /// subscriptions before timers.
+    pub priority: i32,
+    /// Keep message statistics, see `Subscription::stats`. This costs
+    /// an extra serialization of each message. Native, untyped and
+    /// serialized subscriptions only report them in `Node::metrics`.
+    pub stats: bool,
+    /// The QoS profile of the subscription. When `None` the defaults
+    /// of the rmw implementation are used. Subscriptions on the same
+    /// topic can have different profiles.
+    pub qos: Option<QosProfile>,
+    /// Convert the messages from their C representation on a separate
+    /// thread instead of in `spin_once`, for large messages that would
+    /// hold up the handling of other subscriptions and timers.
+    pub offload_conversion: bool,
+    /// Whether the profile can be overridden by the
+    /// `qos_overrides.<topic>.subscription.*` parameters.
+    pub qos_overrides: QosOverridePolicy,
+    /// Also report publishers of the topic whose type name differs
+    /// from ours, with `Error::TopicTypeMismatch`, for distributions
+    /// without type hashes. Publishers with a different type hash are
+    /// always reported, with `Error::TypeHashMismatch`.
+    pub strict_type_check: bool,
+}
+
+impl SubscriptionOptions {
+    pub(crate) fn rmw_qos(&self) -> Result<rmw_qos_profile_t> {
+        match &self.qos {
+            Some(qos) => {
+                qos.validate()?;
+                Ok(rmw_qos_profile_t::from(qos))
+            }
+            None => Ok(rmw_qos_profile_t::default()),
+        }
+    }
+}
+
+/// Options for the resubscribe watchdog.
+#[derive(Debug, Clone)]
+pub struct ResubscribeOptions {
+    /// Minimum time between two attempts to recreate the subscriptions of a topic.
+    pub backoff: Duration,
+    /// Give up on a topic after this many failed attempts in a row.
+    pub max_attempts: usize,
+}
+
+impl Default for ResubscribeOptions {
+    fn default() -> Self {
+        ResubscribeOptions {
+            backoff: Duration::from_secs(1),
+            max_attempts: 10,
+        }
+    }
+}
+
+/// Events reported by the resubscribe watchdog.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResubscribeEvent {
+    /// All publishers of the topic disappeared.
+    PublishersLost { topic: String },
+    /// Publishers came back and the subscriptions on the topic that
+    /// had lost them were recreated. Messages published in between may
+    /// have been lost.
+    Resubscribed { topic: String, attempt: usize },
+    /// Recreating the subscriptions failed `max_attempts` times in a row.
+    GaveUp { topic: String },
+}
+
+impl ResubscribeEvent {
+    fn topic(&self) -> &str {
+        match self {
+            ResubscribeEvent::PublishersLost { topic } => topic,
+            ResubscribeEvent::Resubscribed { topic, .. } => topic,
+            ResubscribeEvent::GaveUp { topic } => topic,
+        }
+    }
+}
+
+// Subscriptions are told apart by their rcl handle, which changes
+// when they are recreated.
+fn watch_key(handle: &rcl_subscription_t) -> usize {
+    handle.impl_ as usize
+}
+
+// The state of one subscription.
+#[derive(Default)]
+struct SubscriptionWatch {
+    seen_publishers: bool,
+    lost: bool,
+    attempts: usize,
+    last_attempt: Option<Instant>,
+}
+
+// What to do about a subscription, see `SubscriptionWatch::update`.
+#[derive(Debug, PartialEq)]
+enum WatchAction {
+    Nothing,
+    PublishersLost,
+    Recreate,
+}
+
+impl SubscriptionWatch {
+    // Updates the state with the current number of publishers. Once
+    // the publishers were lost and have come back, recreating the
+    // subscription is attempted at most every `backoff` and at most
+    // `max_attempts` times.
+    fn update(
+        &mut self,
+        publishers: usize,
+        now: Instant,
+        options: &ResubscribeOptions,
+    ) -> WatchAction {
+        if publishers == 0 {
+            if self.seen_publishers && !self.lost {
+                self.lost = true;
+                return WatchAction::PublishersLost;
+            }
+            return WatchAction::Nothing;
+        }
+        self.seen_publishers = true;
+        if !self.lost || self.gave_up(options) {
+            return WatchAction::Nothing;
+        }
+        if let Some(last) = self.last_attempt {
+            if now.saturating_duration_since(last) < options.backoff {
+                return WatchAction::Nothing;
+            }
+        }
+        self.attempts += 1;
+        self.last_attempt = Some(now);
+        WatchAction::Recreate
+    }
+
+    fn gave_up(&self, options: &ResubscribeOptions) -> bool {
+        self.attempts >= options.max_attempts
+    }
+}
+
+pub struct ResubscribeWatchdog {
+    options: ResubscribeOptions,
+    sender: mpsc::Sender<ResubscribeEvent>,
+    subscriptions: HashMap<usize, SubscriptionWatch>,
+    errors: ErrorSink,
+}
+
+impl ResubscribeWatchdog {
+    pub fn new(
+        options: ResubscribeOptions,
+        sender: mpsc::Sender<ResubscribeEvent>,
+        errors: ErrorSink,
+    ) -> Self {
+        ResubscribeWatchdog {
+            options,
+            sender,
+            subscriptions: HashMap::new(),
+            errors,
+        }
+    }
+
+    /// Checks the publisher count of every subscription and recreates
+    /// the subscriptions whose publishers have come back. Each
+    /// subscription is watched on its own, so that e.g. a subscription
+    /// whose QoS does not match the publishers does not hide the loss
+    /// of the publishers of another subscription on the same topic.
+    pub fn run(&mut self, node: &mut rcl_node_t, subscribers: &mut [Box<dyn Subscriber_>]) {
+        // forget about subscriptions that are gone.
+        let alive: Vec<usize> = subscribers.iter().map(|s| watch_key(s.handle())).collect();
+        self.subscriptions.retain(|k, _| alive.contains(k));
+
+        let mut events = vec![];
+        for s in subscribers.iter_mut() {
+            let topic = match subscription_topic_name(s.handle()) {
+                Ok(topic) => topic,
+                Err(_) => continue,
+            };
+            let count = subscription_publisher_count(s.handle()).unwrap_or(0);
+            let key = watch_key(s.handle());
+            let watch = self.subscriptions.entry(key).or_default();
+            match watch.update(count, Instant::now(), &self.options) {
+                WatchAction::Nothing => continue,
+                WatchAction::PublishersLost => {
+                    events.push(ResubscribeEvent::PublishersLost { topic });
+                    continue;
+                }
+                WatchAction::Recreate => (),
+            }
+            match s.recreate(node) {
+                Ok(()) => {
+                    events.push(ResubscribeEvent::Resubscribed {
+                        topic,
+                        attempt: watch.attempts,
+                    });
+                    // the new rcl handle is watched from now on.
+                    self.subscriptions.remove(&key);
+                    self.subscriptions.insert(
+                        watch_key(s.handle()),
+                        SubscriptionWatch {
+                            seen_publishers: true,
+                            ..SubscriptionWatch::default()
+                        },
+                    );
+                }
+                Err(e) => {
+                    self.errors
+                        .entity(EntityKind::Subscription, &topic)
+                        .report(SpinOperation::Update, e);
+                    if watch.gave_up(&self.options) {
+                        events.push(ResubscribeEvent::GaveUp { topic });
+                    }
+                }
+            }
+        }
+        // several subscriptions on one topic report the same event once.
+        let mut reported = vec![];
+        events.retain(|e| {
+            let key = (std::mem::discriminant(e), e.topic().to_owned());
+            if reported.contains(&key) {
+                false
+            } else {
+                reported.push(key);
+                true
+            }
+        });
+
+        for e in events {
+            let topic = e.topic().to_owned();
+            if let Err(e) = self.sender.try_send(e) {
+                if !e.is_disconnected() {
+                    self.errors
+                        .entity(EntityKind::Subscription, &topic)
+                        .report(
+                            SpinOperation::Deliver,
+                            Error::DeliveryFailed {
+                                reason: e.to_string(),
+                            },
+                        );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resubscribe_backoff_and_give_up() {
+        let options = ResubscribeOptions {
+            backoff: Duration::from_secs(1),
+            max_attempts: 2,
+        };
+        let mut watch = SubscriptionWatch::default();
+        let now = Instant::now();
+        // no publishers have been seen yet.
+        assert_eq!(watch.update(0, now, &options), WatchAction::Nothing);
+        assert_eq!(watch.update(1, now, &options), WatchAction::Nothing);
+        assert_eq!(watch.update(0, now, &options), WatchAction::PublishersLost);
+        assert_eq!(watch.update(0, now, &options), WatchAction::Nothing);
+
+        // the publishers are back, the first attempt fails.
+        assert_eq!(watch.update(1, now, &options), WatchAction::Recreate);
+        assert_eq!(watch.attempts, 1);
+        assert!(!watch.gave_up(&options));
+        let half = now + Duration::from_millis(500);
+        assert_eq!(watch.update(1, half, &options), WatchAction::Nothing);
+        let later = now + Duration::from_secs(1);
+        assert_eq!(watch.update(1, later, &options), WatchAction::Recreate);
+        assert_eq!(watch.attempts, 2);
+
+        // the second one fails too.
+        assert!(watch.gave_up(&options));
+        let much_later = now + Duration::from_secs(60);
+        assert_eq!(watch.update(1, much_later, &options), WatchAction::Nothing);
+        assert_eq!(watch.attempts, 2);
+    }
+
+    #[test]
+    fn test_sequence_gaps() {
+        let mut tracker = SequenceTracker::default();
+        let a = [1u8; 4];
+        let b = [2u8; 4];
+        assert_eq!(tracker.record(&a, Some(1)), None);
+        assert_eq!(tracker.record(&a, Some(2)), None);
+        assert_eq!(tracker.record(&b, Some(7)), None);
+        assert_eq!(tracker.record(&a, Some(5)), Some(2));
+        assert_eq!(tracker.record(&b, Some(8)), None);
+        // the publisher started over.
+        assert_eq!(tracker.record(&a, Some(1)), None);
+        assert_eq!(tracker.record(&a, Some(3)), Some(1));
+    }
+
+    #[test]
+    fn test_sequence_numbers_unsupported() {
+        let mut tracker = SequenceTracker::default();
+        let a = [1u8; 4];
+        assert_eq!(tracker.record(&a, Some(1)), None);
+        assert_eq!(tracker.record(&a, None), None);
+        assert_eq!(tracker.record(&a, Some(5)), None);
     }
 }
@@ -0,0 +1,424 @@
+//! Helpers for tests that spin a node from the test itself.
+//!
+//! Each helper spins the node in short steps until its condition
+//! holds or the timeout passes, so tests need no background spin
+//! thread. Futures and streams are polled between the spins, which is
+//! where the spin delivers to them, so they do not need to be woken
+//! by anyone.
+//!
+//! The node can be spun directly, through an `Arc<Mutex<Node>>` that
+//! is shared with tasks of an async runtime, or with an `Executor` by
+//! passing a `(&mut Node, &mut executor)` tuple. Blocking helpers
+//! called from an async test keep the runtime thread busy, so other
+//! tasks need a multi threaded runtime.
+//!
+//! `RmwMatrix` runs a test once for each installed rmw implementation.
+
+use futures::future::Future;
+use futures::stream::Stream;
+use futures::task::{noop_waker_ref, Context, Poll};
+use std::io::Read;
+use std::path::Path;
+use std::pin::Pin;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::*;
+use crate::executor::Executor;
+use crate::log_handler::log_internal;
+use crate::nodes::Node;
+use crate::utils::LogSeverity;
+
+// how long each spin waits at most between checks.
+const SPIN_STEP: Duration = Duration::from_millis(10);
+
+/// Something that can spin a node.
+pub trait Spin {
+    fn spin_once(&mut self, timeout: Duration);
+}
+
+impl Spin for Node {
+    fn spin_once(&mut self, timeout: Duration) {
+        Node::spin_once(self, timeout)
+    }
+}
+
+impl Spin for Arc<Mutex<Node>> {
+    fn spin_once(&mut self, timeout: Duration) {
+        self.lock().unwrap().spin_once(timeout)
+    }
+}
+
+impl<E: Executor> Spin for (&mut Node, &mut E) {
+    fn spin_once(&mut self, timeout: Duration) {
+        self.1.spin_once(self.0, timeout)
+    }
+}
+
+// Spins until `done` returns true, checking before the first spin.
+fn spin_until<S>(node: &mut S, timeout: Duration, mut done: impl FnMut() -> bool) -> Result<()>
+where
+    S: Spin + ?Sized,
+{
+    let deadline = Instant::now() + timeout;
+    loop {
+        if done() {
+            return Ok(());
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(Error::RCL_RET_TIMEOUT);
+        }
+        node.spin_once(SPIN_STEP.min(deadline - now));
+    }
+}
+
+/// Spins the node for as long as `predicate` returns true.
+///
+/// Returns `Error::RCL_RET_TIMEOUT` if `predicate` still holds after
+/// `timeout`.
+pub fn spin_while<S>(
+    node: &mut S,
+    mut predicate: impl FnMut() -> bool,
+    timeout: Duration,
+) -> Result<()>
+where
+    S: Spin + ?Sized,
+{
+    spin_until(node, timeout, || !predicate())
+}
+
+/// Spins the node until `n` items have been taken from the stream.
+///
+/// Returns fewer items if the stream ends or `timeout` passes first.
+pub fn collect_n<S, St>(stream: &mut St, n: usize, node: &mut S, timeout: Duration) -> Vec<St::Item>
+where
+    S: Spin + ?Sized,
+    St: Stream + Unpin + ?Sized,
+{
+    let mut cx = Context::from_waker(noop_waker_ref());
+    let mut items = Vec::with_capacity(n);
+    let mut ended = false;
+    let _ = spin_until(node, timeout, || {
+        while !ended && items.len() < n {
+            match Pin::new(&mut *stream).poll_next(&mut cx) {
+                Poll::Ready(Some(item)) => items.push(item),
+                Poll::Ready(None) => ended = true,
+                Poll::Pending => break,
+            }
+        }
+        ended || items.len() >= n
+    });
+    items
+}
+
+/// Spins the node until one of the futures completes and returns
+/// its index together with its output.
+///
+/// Returns `Error::RCL_RET_TIMEOUT` if none completes within
+/// `timeout`. Futures that are not `Unpin` can be passed with
+/// `Box::pin`.
+pub fn first_of<S, F>(
+    futures: impl IntoIterator<Item = F>,
+    node: &mut S,
+    timeout: Duration,
+) -> Result<(usize, F::Output)>
+where
+    S: Spin + ?Sized,
+    F: Future + Unpin,
+{
+    let mut cx = Context::from_waker(noop_waker_ref());
+    let mut futures: Vec<F> = futures.into_iter().collect();
+    let mut output = None;
+    spin_until(node, timeout, || {
+        output =
+            futures
+                .iter_mut()
+                .enumerate()
+                .find_map(|(i, f)| match Pin::new(f).poll(&mut cx) {
+                    Poll::Ready(o) => Some((i, o)),
+                    Poll::Pending => None,
+                });
+        output.is_some()
+    })?;
+    Ok(output.expect("output is set on success"))
+}
+
+/// The rmw implementations an `RmwMatrix` runs on by default. The
+/// `R2R_TEST_RMW_IMPLEMENTATIONS` environment variable, a comma
+/// separated list, replaces them.
+pub const RMW_IMPLEMENTATIONS: &[&str] = &["rmw_cyclonedds_cpp", "rmw_fastrtps_cpp"];
+
+// set in the processes that run a single case.
+const RMW_CASE_VAR: &str = "R2R_TEST_RMW_CASE";
+// cases get domain ids of their own, counted from here.
+static NEXT_CASE: AtomicU32 = AtomicU32::new(0);
+
+/// The rmw implementation and domain a case of an `RmwMatrix` runs in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RmwCase {
+    pub rmw: String,
+    pub domain_id: u32,
+}
+
+/// How a case of an `RmwMatrix` went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RmwOutcome {
+    Passed,
+    /// The rmw implementation is not installed.
+    Skipped,
+    /// The case failed, with the output of its process.
+    Failed {
+        output: String,
+    },
+}
+
+/// Runs a test once for each rmw implementation.
+///
+/// ```ignore
+/// #[test]
+/// fn pub_sub() -> Result<(), Box<dyn std::error::Error>> {
+///     RmwMatrix::new().run("pub_sub", |case| {
+///         let ctx = r2r::Context::create()?;
+///         // ...
+///         Ok(())
+///     })
+/// }
+/// ```
+///
+/// The rmw implementation is loaded once per process, so each case
+/// runs in a process of its own: `run` starts the test binary again
+/// for only the test `test_name`, with `RMW_IMPLEMENTATION` set and a
+/// `ROS_DOMAIN_ID` of its own, so that cases running at the same time
+/// do not see each other. Discovery is limited to the local host. In
+/// that process `run` calls `case` instead. Implementations that are
+/// not installed are skipped.
+pub struct RmwMatrix {
+    implementations: Vec<String>,
+    env: Vec<(String, String)>,
+    timeout: Duration,
+}
+
+impl Default for RmwMatrix {
+    fn default() -> Self {
+        let implementations = match std::env::var("R2R_TEST_RMW_IMPLEMENTATIONS") {
+            Ok(list) => list
+                .split(',')
+                .map(|s| s.trim().to_owned())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            Err(_) => RMW_IMPLEMENTATIONS.iter().map(|s| s.to_string()).collect(),
+        };
+        RmwMatrix {
+            implementations,
+            env: vec![],
+            timeout: Duration::from_secs(120),
+        }
+    }
+}
+
+impl RmwMatrix {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run on these implementations instead.
+    pub fn implementations(mut self, implementations: &[&str]) -> Self {
+        self.implementations = implementations.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    /// Set an environment variable in the processes of the cases, e.g.
+    /// the configuration file of an rmw implementation.
+    pub fn env(mut self, key: &str, value: &str) -> Self {
+        self.env.push((key.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// How long a case may run before its process is killed and the
+    /// case failed.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run `case` for each implementation, see `RmwMatrix`.
+    /// `test_name` is the name of the calling test as given to the
+    /// test binary, e.g. `pub_sub` or `tests::pub_sub`.
+    ///
+    /// Fails if any case failed, with the outputs of the failed cases.
+    pub fn run<F>(
+        &self,
+        test_name: &str,
+        case: F,
+    ) -> std::result::Result<Vec<(RmwCase, RmwOutcome)>, Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&RmwCase) -> std::result::Result<(), Box<dyn std::error::Error>>,
+    {
+        if let Some(current) = RmwCase::current() {
+            case(&current)?;
+            return Ok(vec![(current, RmwOutcome::Passed)]);
+        }
+        let mut outcomes = vec![];
+        for rmw in &self.implementations {
+            let case = RmwCase {
+                rmw: rmw.clone(),
+                domain_id: next_domain_id(),
+            };
+            let outcome = if rmw_installed(rmw) {
+                self.run_case(test_name, &case)?
+            } else {
+                log_internal(
+                    LogSeverity::Warn,
+                    &format!("{}: skipped on {}, not installed", test_name, rmw),
+                );
+                RmwOutcome::Skipped
+            };
+            outcomes.push((case, outcome));
+        }
+        let failed: Vec<String> = outcomes
+            .iter()
+            .filter_map(|(case, outcome)| match outcome {
+                RmwOutcome::Failed { output } => Some(format!("{}:\n{}", case.rmw, output)),
+                _ => None,
+            })
+            .collect();
+        if !failed.is_empty() {
+            return Err(format!("{} failed on {}", test_name, failed.join("\n")).into());
+        }
+        Ok(outcomes)
+    }
+
+    fn run_case(&self, test_name: &str, case: &RmwCase) -> std::io::Result<RmwOutcome> {
+        let mut child = Command::new(std::env::current_exe()?)
+            .args(&[test_name, "--exact", "--nocapture", "--test-threads=1"])
+            .env("RMW_IMPLEMENTATION", &case.rmw)
+            .env("ROS_DOMAIN_ID", case.domain_id.to_string())
+            .env("ROS_LOCALHOST_ONLY", "1")
+            .env("ROS_AUTOMATIC_DISCOVERY_RANGE", "LOCALHOST")
+            .env(RMW_CASE_VAR, &case.rmw)
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        // read on threads of their own so that a full pipe does not
+        // block the child.
+        let read = |mut pipe: Box<dyn Read + Send>| {
+            std::thread::spawn(move || {
+                let mut output = String::new();
+                let _ = pipe.read_to_string(&mut output);
+                output
+            })
+        };
+        let stdout = read(Box::new(child.stdout.take().expect("piped")));
+        let stderr = read(Box::new(child.stderr.take().expect("piped")));
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break Some(status);
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                break None;
+            }
+            std::thread::sleep(SPIN_STEP);
+        };
+        let stdout = stdout.join().unwrap_or_default();
+        let stderr = stderr.join().unwrap_or_default();
+        let output = format!("{}{}", stdout, stderr);
+        Ok(match status {
+            // a name that matches no test also succeeds.
+            Some(status) if status.success() && stdout.contains("1 passed") => RmwOutcome::Passed,
+            Some(_) => RmwOutcome::Failed { output },
+            None => RmwOutcome::Failed {
+                output: format!("timed out after {:?}\n{}", self.timeout, output),
+            },
+        })
+    }
+}
+
+impl RmwCase {
+    /// The case this process runs, if started by an `RmwMatrix`.
+    pub fn current() -> Option<RmwCase> {
+        let rmw = std::env::var(RMW_CASE_VAR).ok()?;
+        let domain_id = std::env::var("ROS_DOMAIN_ID").ok()?.parse().ok()?;
+        Some(RmwCase { rmw, domain_id })
+    }
+}
+
+/// Whether the rmw implementation `rmw` is installed, according to
+/// the ament index of the sourced workspaces.
+pub fn rmw_installed(rmw: &str) -> bool {
+    let prefixes = std::env::var("AMENT_PREFIX_PATH").unwrap_or_default();
+    prefixes.split(':').filter(|p| !p.is_empty()).any(|prefix| {
+        Path::new(prefix)
+            .join("share/ament_index/resource_index/packages")
+            .join(rmw)
+            .exists()
+    })
+}
+
+// Domain ids 1 to 100 work with the default port ranges. Parallel test
+// binaries start from different ids.
+fn next_domain_id() -> u32 {
+    1 + std::process::id().wrapping_add(NEXT_CASE.fetch_add(1, Ordering::Relaxed)) % 100
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::{self, FutureExt};
+    use futures::stream;
+
+    struct CountingSpinner(usize);
+
+    impl Spin for CountingSpinner {
+        fn spin_once(&mut self, _timeout: Duration) {
+            self.0 += 1;
+        }
+    }
+
+    #[test]
+    fn test_collect_n() {
+        let mut spinner = CountingSpinner(0);
+        let mut s = stream::iter(0..10);
+        let items = collect_n(&mut s, 3, &mut spinner, Duration::from_secs(1));
+        assert_eq!(items, vec![0, 1, 2]);
+        assert_eq!(spinner.0, 0);
+
+        let mut s = stream::iter(0..2);
+        let items = collect_n(&mut s, 3, &mut spinner, Duration::from_secs(1));
+        assert_eq!(items, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_first_of_and_spin_while() {
+        let mut spinner = CountingSpinner(0);
+        let futures = vec![future::pending().boxed(), future::ready(5).boxed()];
+        let (i, v) = first_of(futures, &mut spinner, Duration::from_secs(1)).unwrap();
+        assert_eq!((i, v), (1, 5));
+
+        let r = first_of(
+            vec![future::pending::<()>()],
+            &mut spinner,
+            Duration::from_millis(20),
+        );
+        assert!(matches!(r, Err(Error::RCL_RET_TIMEOUT)));
+        assert!(spinner.0 > 0);
+
+        let mut n = 0;
+        spin_while(
+            &mut spinner,
+            || {
+                n += 1;
+                n < 3
+            },
+            Duration::from_secs(1),
+        )
+        .unwrap();
+        assert_eq!(n, 3);
+    }
+}
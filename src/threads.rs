@@ -0,0 +1,136 @@
+//! Hooks for the threads that r2r starts, see `Context::set_thread_hooks`.
+//!
+//! These are all the threads r2r starts itself, named after the prefix:
+//!
+//! - `conversion`, one per subscription created with
+//!   `SubscriptionOptions::offload_conversion`.
+//! - `executor-N`, the workers of a `MultiThreadedExecutor`.
+//! - `watchdog`, one per `deadlock_watchdog`, only with the
+//!   `spin-diagnostics` feature.
+//!
+//! Nodes are spun on the threads of the application, and the threads
+//! of the middleware are not started by r2r.
+
+use std::sync::Arc;
+use std::thread;
+
+type ThreadHook = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// How the threads r2r starts are named, and what runs on them before
+/// and after their work, e.g. to set the cpu affinity and priority of
+/// each thread on a real-time system.
+///
+/// ```ignore
+/// ctx.set_thread_hooks(
+///     ThreadHooks::default()
+///         .thread_name_prefix("nav-")
+///         .on_thread_start(|name| pin_to_cpu(name)),
+/// );
+/// ```
+#[derive(Clone)]
+pub struct ThreadHooks {
+    name_prefix: String,
+    on_start: Option<ThreadHook>,
+    on_stop: Option<ThreadHook>,
+}
+
+impl Default for ThreadHooks {
+    fn default() -> Self {
+        ThreadHooks {
+            name_prefix: "r2r-".into(),
+            on_start: None,
+            on_stop: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for ThreadHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThreadHooks")
+            .field("name_prefix", &self.name_prefix)
+            .field("on_start", &self.on_start.is_some())
+            .field("on_stop", &self.on_stop.is_some())
+            .finish()
+    }
+}
+
+impl ThreadHooks {
+    /// Put in front of the name of every thread, `r2r-` by default.
+    /// Linux only shows the first 15 bytes of a thread name.
+    pub fn thread_name_prefix(mut self, prefix: &str) -> Self {
+        self.name_prefix = prefix.to_owned();
+        self
+    }
+
+    /// Called with the full name of the thread, on the new thread,
+    /// before it does anything else.
+    pub fn on_thread_start<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.on_start = Some(Arc::new(callback));
+        self
+    }
+
+    /// Called with the full name of the thread, on the thread, when it
+    /// is done with its work.
+    pub fn on_thread_stop<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.on_stop = Some(Arc::new(callback));
+        self
+    }
+
+    pub(crate) fn spawn<F, T>(&self, name: &str, work: F) -> std::io::Result<thread::JoinHandle<T>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let name = format!("{}{}", self.name_prefix, name);
+        let hooks = self.clone();
+        thread::Builder::new().name(name.clone()).spawn(move || {
+            if let Some(on_start) = &hooks.on_start {
+                on_start(&name);
+            }
+            let result = work();
+            if let Some(on_stop) = &hooks.on_stop {
+                on_stop(&name);
+            }
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_thread_hooks() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let start_calls = calls.clone();
+        let stop_calls = calls.clone();
+        let hooks = ThreadHooks::default()
+            .thread_name_prefix("test-")
+            .on_thread_start(move |name| {
+                start_calls.lock().unwrap().push(format!("start {}", name))
+            })
+            .on_thread_stop(move |name| stop_calls.lock().unwrap().push(format!("stop {}", name)));
+        let work_calls = calls.clone();
+        let name = hooks
+            .spawn("worker", move || {
+                work_calls.lock().unwrap().push("work".into());
+                thread::current().name().map(|n| n.to_owned())
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(name.as_deref(), Some("test-worker"));
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec!["start test-worker", "work", "stop test-worker"]
+        );
+    }
+}
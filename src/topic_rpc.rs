@@ -0,0 +1,340 @@
+//! Requests and responses over a pair of topics.
+//!
+//! For peers that cannot host service servers reliably, e.g. on
+//! micro-ROS, a `TopicRpcServer` takes requests on `<name>/request` and
+//! publishes the responses on `<name>/response`. Both topics carry
+//! `std_msgs/msg/String` messages holding json with a correlation id
+//! and the message, so that no special message type is needed:
+//!
+//! ```json
+//! {"id": "5b4f...", "message": {"a": 1, "b": 2}}
+//! ```
+//!
+//! A `TopicRpcClient` matches the responses to its requests by id, in
+//! whatever order they arrive, and fails requests that are not
+//! answered in time. Responses to the requests of other clients on the
+//! same topics are ignored.
+
+use futures::channel::oneshot;
+use futures::future::FutureExt;
+use futures::stream::{Stream, StreamExt};
+use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use crate::error::*;
+use crate::error_events::{EntityErrors, SpinOperation};
+use crate::executor::EntityKind;
+use crate::msg_types::WrappedTypesupport;
+use crate::nodes::{Node, Timer};
+use crate::publishers::PublisherUntyped;
+
+const RPC_MSG_TYPE: &str = "std_msgs/msg/String";
+
+fn request_topic(name: &str) -> String {
+    format!("{}/request", name)
+}
+
+fn response_topic(name: &str) -> String {
+    format!("{}/response", name)
+}
+
+fn wrap_message<T: serde::Serialize>(id: &uuid::Uuid, msg: &T) -> Result<serde_json::Value> {
+    let message =
+        serde_json::to_value(msg).map_err(|e| Error::SerdeError { err: e.to_string() })?;
+    let data = serde_json::json!({ "id": id.to_string(), "message": message });
+    Ok(serde_json::json!({ "data": data.to_string() }))
+}
+
+fn unwrap_message(json: &serde_json::Value) -> Result<(uuid::Uuid, serde_json::Value)> {
+    let serde_error = |err: String| Error::SerdeError { err };
+    let data = json
+        .get("data")
+        .and_then(|d| d.as_str())
+        .ok_or_else(|| serde_error("rpc message has no data".into()))?;
+    let mut data: serde_json::Value =
+        serde_json::from_str(data).map_err(|e| serde_error(e.to_string()))?;
+    let id = data
+        .get("id")
+        .and_then(|id| id.as_str())
+        .ok_or_else(|| serde_error("rpc message has no id".into()))
+        .and_then(|id| uuid::Uuid::parse_str(id).map_err(|e| serde_error(e.to_string())))?;
+    let message = data
+        .get_mut("message")
+        .map(|m| m.take())
+        .ok_or_else(|| serde_error("rpc message has no message".into()))?;
+    Ok((id, message))
+}
+
+fn from_json<T: WrappedTypesupport>(json: serde_json::Value) -> Result<T> {
+    serde_json::from_value(json).map_err(|e| Error::SerdeError { err: e.to_string() })
+}
+
+/// Options for creating a `TopicRpcClient`.
+#[derive(Debug, Clone)]
+pub struct TopicRpcOptions {
+    /// How long to wait for a response, unless given per request.
+    pub timeout: Duration,
+}
+
+impl Default for TopicRpcOptions {
+    fn default() -> Self {
+        TopicRpcOptions {
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+struct PendingRequest {
+    sender: oneshot::Sender<Result<serde_json::Value>>,
+    deadline: Instant,
+    timeout: Duration,
+}
+
+type PendingRequests = HashMap<uuid::Uuid, PendingRequest>;
+
+/// Makes requests to a `TopicRpcServer`.
+///
+/// The responses are matched to the requests while the node spins,
+/// which is also when requests time out.
+pub struct TopicRpcClient<Req, Res> {
+    publisher: PublisherUntyped,
+    pending: Arc<Mutex<PendingRequests>>,
+    timeout: Duration,
+    types: PhantomData<fn(Req) -> Res>,
+}
+
+impl<Req, Res> TopicRpcClient<Req, Res>
+where
+    Req: WrappedTypesupport,
+    Res: WrappedTypesupport,
+{
+    pub fn create(node: &mut Node, name: &str) -> Result<Self> {
+        Self::create_with_options(node, name, TopicRpcOptions::default())
+    }
+
+    pub fn create_with_options(
+        node: &mut Node,
+        name: &str,
+        options: TopicRpcOptions,
+    ) -> Result<Self> {
+        if options.timeout == Duration::from_secs(0) {
+            return Err(Error::RCL_RET_INVALID_ARGUMENT);
+        }
+        let publisher = node.create_publisher_untyped(&request_topic(name), RPC_MSG_TYPE)?;
+        let responses = node.subscribe_untyped(&response_topic(name), RPC_MSG_TYPE)?;
+        // wake up often enough to notice a timeout in time.
+        let timer = node.create_wall_timer(options.timeout / 4)?;
+        let pending = Arc::new(Mutex::new(PendingRequests::new()));
+        node.add_topic_rpc_client(TopicRpcClient_ {
+            responses: Box::new(responses),
+            timer,
+            pending: Arc::downgrade(&pending),
+            errors: node.entity_errors(EntityKind::Subscription, &response_topic(name)),
+        });
+        Ok(TopicRpcClient {
+            publisher,
+            pending,
+            timeout: options.timeout,
+            types: PhantomData,
+        })
+    }
+
+    /// Number of servers listening for requests.
+    pub fn server_count(&self) -> Result<usize> {
+        self.publisher.get_inter_process_subscription_count()
+    }
+
+    /// Number of requests waiting for a response.
+    pub fn pending_requests(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    /// Make a request. The returned future fails with
+    /// `Error::RequestTimedOut` if no response arrives within the
+    /// timeout of the client.
+    pub fn request(&self, msg: &Req) -> Result<impl Future<Output = Result<Res>>> {
+        self.request_with_timeout(msg, self.timeout)
+    }
+
+    /// Like `request`, but with the given timeout.
+    pub fn request_with_timeout(
+        &self,
+        msg: &Req,
+        timeout: Duration,
+    ) -> Result<impl Future<Output = Result<Res>>> {
+        let id = uuid::Uuid::new_v4();
+        let wrapped = wrap_message(&id, msg)?;
+        let (sender, receiver) = oneshot::channel();
+        let request = PendingRequest {
+            sender,
+            deadline: Instant::now() + timeout,
+            timeout,
+        };
+        // before publishing, the response may arrive right away.
+        self.pending.lock().unwrap().insert(id, request);
+        if let Err(e) = self.publisher.publish(wrapped) {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(e);
+        }
+        Ok(receiver.map(|response| match response {
+            Ok(response) => response.and_then(from_json),
+            Err(oneshot::Canceled) => Err(Error::RCL_RET_CLIENT_INVALID),
+        }))
+    }
+}
+
+// Matches responses to the requests of a client, driven by spin.
+pub(crate) struct TopicRpcClient_ {
+    responses: Box<dyn Stream<Item = Result<serde_json::Value>> + Unpin>,
+    timer: Timer,
+    pending: Weak<Mutex<PendingRequests>>,
+    errors: EntityErrors,
+}
+
+impl TopicRpcClient_ {
+    /// Returns false once the client has been dropped.
+    pub(crate) fn poll(&mut self) -> bool {
+        let pending = match self.pending.upgrade() {
+            Some(pending) => pending,
+            None => return false,
+        };
+        while let Some(Ok(_)) = self.timer.tick().now_or_never() {}
+        let mut pending = pending.lock().unwrap();
+        while let Some(Some(msg)) = self.responses.next().now_or_never() {
+            match msg.and_then(|m| unwrap_message(&m)) {
+                Ok((id, message)) => {
+                    // unknown ids belong to other clients or to requests
+                    // that already timed out.
+                    if let Some(request) = pending.remove(&id) {
+                        let _ = request.sender.send(Ok(message));
+                    }
+                }
+                Err(e) => self.errors.report(SpinOperation::Convert, e),
+            }
+        }
+        let now = Instant::now();
+        let expired = pending
+            .iter()
+            .filter(|(_, r)| r.deadline <= now)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in expired {
+            if let Some(request) = pending.remove(&id) {
+                let _ = request.sender.send(Err(Error::RequestTimedOut {
+                    timeout: request.timeout,
+                }));
+            }
+        }
+        // nobody waits for these anymore.
+        pending.retain(|_, r| !r.sender.is_canceled());
+        true
+    }
+}
+
+/// A request taken by a `TopicRpcServer`.
+pub struct TopicRpcRequest<Req, Res> {
+    pub message: Req,
+    /// The correlation id the response is sent with.
+    pub id: uuid::Uuid,
+    publisher: PublisherUntyped,
+    response: PhantomData<fn(Res)>,
+}
+
+impl<Req, Res> TopicRpcRequest<Req, Res>
+where
+    Res: WrappedTypesupport,
+{
+    /// Publish the response to this request.
+    pub fn respond(self, msg: &Res) -> Result<()> {
+        self.publisher.publish(wrap_message(&self.id, msg)?)
+    }
+}
+
+/// A `Stream` of the requests made by `TopicRpcClient`s.
+///
+/// Requests that cannot be converted are reported as errors of the
+/// node, see `Node::error_events`.
+pub struct TopicRpcServer<Req, Res> {
+    requests: Box<dyn Stream<Item = Result<serde_json::Value>> + Unpin + Send>,
+    publisher: PublisherUntyped,
+    errors: EntityErrors,
+    types: PhantomData<fn(Req) -> Res>,
+}
+
+impl<Req, Res> TopicRpcServer<Req, Res>
+where
+    Req: WrappedTypesupport,
+    Res: WrappedTypesupport,
+{
+    pub fn create(node: &mut Node, name: &str) -> Result<Self> {
+        let requests = node.subscribe_untyped(&request_topic(name), RPC_MSG_TYPE)?;
+        let publisher = node.create_publisher_untyped(&response_topic(name), RPC_MSG_TYPE)?;
+        Ok(TopicRpcServer {
+            requests: Box::new(requests),
+            publisher,
+            errors: node.entity_errors(EntityKind::Subscription, &request_topic(name)),
+            types: PhantomData,
+        })
+    }
+
+    /// Number of clients listening for responses.
+    pub fn client_count(&self) -> Result<usize> {
+        self.publisher.get_inter_process_subscription_count()
+    }
+}
+
+impl<Req, Res> Stream for TopicRpcServer<Req, Res>
+where
+    Req: WrappedTypesupport,
+    Res: WrappedTypesupport,
+{
+    type Item = TopicRpcRequest<Req, Res>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let msg = match Pin::new(&mut self.requests).poll_next(cx) {
+                Poll::Ready(Some(msg)) => msg,
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+            let request = msg
+                .and_then(|m| unwrap_message(&m))
+                .and_then(|(id, message)| Ok((id, from_json::<Req>(message)?)));
+            match request {
+                Ok((id, message)) => {
+                    return Poll::Ready(Some(TopicRpcRequest {
+                        message,
+                        id,
+                        publisher: self.publisher.clone(),
+                        response: PhantomData,
+                    }))
+                }
+                Err(e) => self.errors.report(SpinOperation::Convert, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rpc_message_json() {
+        let id = uuid::Uuid::new_v4();
+        let json = wrap_message(&id, &serde_json::json!({ "a": 1 })).unwrap();
+        assert!(json["data"].is_string());
+        let (unwrapped_id, message) = unwrap_message(&json).unwrap();
+        assert_eq!(unwrapped_id, id);
+        assert_eq!(message, serde_json::json!({ "a": 1 }));
+
+        assert!(unwrap_message(&serde_json::json!({ "data": "{}" })).is_err());
+        let no_message = serde_json::json!({ "id": id.to_string() }).to_string();
+        assert!(unwrap_message(&serde_json::json!({ "data": no_message })).is_err());
+    }
+}
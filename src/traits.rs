@@ -0,0 +1,154 @@
+//! Traits shared by the handles of the different kinds of entities, to
+//! write code that works with any of them. They are all in
+//! `r2r::prelude`.
+
+use std::sync::Arc;
+
+use futures::stream::Stream;
+
+use crate::error::*;
+use crate::executor::EntityKind;
+
+/// A handle to an entity of a node.
+///
+/// ```no_run
+/// use r2r::prelude::*;
+///
+/// fn describe(entity: &impl Entity) -> String {
+///     format!("{:?} {}", entity.kind(), entity.name().unwrap_or_default())
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let ctx = r2r::Context::create()?;
+/// let mut node = r2r::Node::create(ctx, "node", "")?;
+/// let publisher = node.create_publisher::<r2r::std_msgs::msg::String>("/topic")?;
+/// let client = node.create_client::<r2r::example_interfaces::srv::AddTwoInts::Service>("/add")?;
+/// println!("{}", describe(&publisher));
+/// println!("{}", describe(&client));
+/// # Ok(())
+/// # }
+/// ```
+pub trait Entity {
+    /// The topic, service or action name, or the fully qualified name
+    /// of a node.
+    ///
+    /// Fails when the entity does not exist anymore, e.g. when the
+    /// node was dropped.
+    fn name(&self) -> Result<String>;
+
+    fn kind(&self) -> EntityKind;
+}
+
+impl<E: Entity + ?Sized> Entity for &E {
+    fn name(&self) -> Result<String> {
+        (**self).name()
+    }
+
+    fn kind(&self) -> EntityKind {
+        (**self).kind()
+    }
+}
+
+impl<E: Entity + ?Sized> Entity for Box<E> {
+    fn name(&self) -> Result<String> {
+        (**self).name()
+    }
+
+    fn kind(&self) -> EntityKind {
+        (**self).kind()
+    }
+}
+
+impl<E: Entity + ?Sized> Entity for Arc<E> {
+    fn name(&self) -> Result<String> {
+        (**self).name()
+    }
+
+    fn kind(&self) -> EntityKind {
+        (**self).kind()
+    }
+}
+
+/// Something that publishes messages of type `T`, e.g. a `Publisher`
+/// or a `RetainedPublisher`.
+///
+/// ```no_run
+/// use r2r::prelude::*;
+/// use r2r::std_msgs::msg::String as StringMsg;
+///
+/// fn greet(publisher: &impl Publish<StringMsg>) -> r2r::Result<()> {
+///     publisher.publish(&StringMsg { data: "hello".into() })
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let ctx = r2r::Context::create()?;
+/// let mut node = r2r::Node::create(ctx, "node", "")?;
+/// let publisher = node.create_publisher::<StringMsg>("/topic")?;
+/// let retained = node.create_retained_publisher::<StringMsg>(
+///     "/latched",
+///     r2r::QosProfile::default(),
+///     1,
+/// )?;
+/// greet(&publisher)?;
+/// greet(&retained)?;
+/// // or without knowing which one.
+/// let publishers: Vec<Box<dyn Publish<StringMsg>>> = vec![Box::new(publisher), Box::new(retained)];
+/// for publisher in &publishers {
+///     greet(publisher)?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub trait Publish<T> {
+    fn publish(&self, msg: &T) -> Result<()>;
+}
+
+impl<T, P: Publish<T> + ?Sized> Publish<T> for &P {
+    fn publish(&self, msg: &T) -> Result<()> {
+        (**self).publish(msg)
+    }
+}
+
+impl<T, P: Publish<T> + ?Sized> Publish<T> for Box<P> {
+    fn publish(&self, msg: &T) -> Result<()> {
+        (**self).publish(msg)
+    }
+}
+
+impl<T, P: Publish<T> + ?Sized> Publish<T> for Arc<P> {
+    fn publish(&self, msg: &T) -> Result<()> {
+        (**self).publish(msg)
+    }
+}
+
+/// A stream of messages of type `T`, e.g. a `Subscription`, a
+/// `FilteredSubscription` or one of the streams returned by the
+/// `subscribe_*` methods of `Node`.
+///
+/// Implemented for every `Unpin` stream, so that functions can take
+/// any of them without spelling out the `Stream` bounds.
+///
+/// ```no_run
+/// use futures::stream::StreamExt;
+/// use r2r::prelude::*;
+///
+/// async fn count(mut messages: impl MessageStream<r2r::std_msgs::msg::String>) -> usize {
+///     let mut count = 0;
+///     while let Some(_) = messages.next().await {
+///         count += 1;
+///     }
+///     count
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let ctx = r2r::Context::create()?;
+/// let mut node = r2r::Node::create(ctx, "node", "")?;
+/// let subscription = node.subscribe::<r2r::std_msgs::msg::String>("/topic")?;
+/// let counted = count(subscription);
+/// # drop(counted);
+/// # Ok(())
+/// # }
+/// ```
+pub trait MessageStream<T>: Stream<Item = T> + Unpin {}
+
+impl<T, S> MessageStream<T> for S where S: Stream<Item = T> + Unpin + ?Sized {}
@@ -0,0 +1,270 @@
+//! Type support loaded at runtime.
+//!
+//! Interface packages that were not available when r2r was built can
+//! still be used: on topics with messages as serialized bytes, and in
+//! services and action clients with json like the untyped ones. The
+//! type support comes either from `TypeSupportLibrary`, which loads
+//! the C type support library of a package, or from a pointer the
+//! caller obtained some other way, see `MessageTypeSupport::from_raw`.
+
+use libloading::Library;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::dynamic_msgs::DynamicType;
+use crate::error::*;
+use crate::msg_types::WrappedNativeMsgUntyped;
+use r2r_actions::*;
+use r2r_rcl::*;
+
+/// The C type support library of an interface package,
+/// `lib<package>__rosidl_typesupport_c.so`.
+#[derive(Debug, Clone)]
+pub struct TypeSupportLibrary {
+    package: String,
+    library: Arc<Library>,
+}
+
+/// Type support of a message, keeping the library it came from
+/// loaded.
+#[derive(Debug, Clone)]
+pub struct MessageTypeSupport {
+    ts: *const rosidl_message_type_support_t,
+    type_name: String,
+    _library: Option<Arc<Library>>,
+}
+
+unsafe impl Send for MessageTypeSupport {}
+unsafe impl Sync for MessageTypeSupport {}
+
+/// Type support of a service or an action, keeping the library it
+/// came from loaded.
+///
+/// See `Node::create_service_with_type_support`,
+/// `Node::create_client_with_type_support` and
+/// `Node::create_action_client_with_type_support`. Their messages are
+/// converted through the introspection type support of the package,
+/// which is loaded alongside.
+#[derive(Debug, Clone)]
+pub struct InterfaceTypeSupport<T> {
+    ts: *const T,
+    type_name: String,
+    _library: Option<Arc<Library>>,
+}
+
+unsafe impl<T> Send for InterfaceTypeSupport<T> {}
+unsafe impl<T> Sync for InterfaceTypeSupport<T> {}
+
+impl<T> InterfaceTypeSupport<T> {
+    /// Use a type support pointer the caller has obtained, see
+    /// `MessageTypeSupport::from_raw`.
+    ///
+    /// # Safety
+    ///
+    /// As for `MessageTypeSupport::from_raw`, with `type_name` e.g.
+    /// "example_interfaces/srv/AddTwoInts". The introspection type
+    /// support of the messages is loaded by the package in
+    /// `type_name`.
+    pub unsafe fn from_raw(ts: *const T, type_name: &str) -> Self {
+        InterfaceTypeSupport {
+            ts,
+            type_name: type_name.to_owned(),
+            _library: None,
+        }
+    }
+
+    /// The type support pointer, valid for as long as `self` lives.
+    pub fn as_ptr(&self) -> *const T {
+        self.ts
+    }
+
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    // The messages of the interface, e.g. "Request" and "Response" for
+    // the AddTwoInts_Request and AddTwoInts_Response of a service.
+    pub(crate) fn message_types(&self, suffixes: &[&str]) -> Result<Vec<Arc<DynamicType>>> {
+        let parts = self.type_name.split('/').collect::<Vec<_>>();
+        let (package, subfolder, name) = match parts[..] {
+            [package, subfolder, name] => (package, subfolder, name),
+            _ => {
+                return Err(Error::InvalidMessageType {
+                    msgtype: self.type_name.clone(),
+                })
+            }
+        };
+        let library = load_library(package, "rosidl_typesupport_introspection_c")?;
+        suffixes
+            .iter()
+            .map(|suffix| {
+                let symbol = format!(
+                    "rosidl_typesupport_introspection_c__get_message_type_support_handle__{}__{}__{}_{}\0",
+                    package, subfolder, name, suffix
+                );
+                let ts = get_type_support::<rosidl_message_type_support_t>(&library, &symbol)?;
+                Ok(Arc::new(unsafe { DynamicType::new(ts, library.clone()) }))
+            })
+            .collect()
+    }
+}
+
+impl MessageTypeSupport {
+    /// Use a type support pointer the caller has obtained.
+    ///
+    /// # Safety
+    ///
+    /// `ts` must point to valid message type support for `type_name`
+    /// (e.g. "std_msgs/msg/String"), and the library it comes from
+    /// must stay loaded for as long as any entity created from it
+    /// lives. Unloading it earlier leads to crashes in the middleware.
+    pub unsafe fn from_raw(ts: *const rosidl_message_type_support_t, type_name: &str) -> Self {
+        MessageTypeSupport {
+            ts,
+            type_name: type_name.to_owned(),
+            _library: None,
+        }
+    }
+
+    /// Type support of `type_name` (e.g. "std_msgs/msg/String"), from
+    /// the messages r2r was built with or else loaded with
+    /// `TypeSupportLibrary`.
+    pub fn for_type_name(type_name: &str) -> Result<Self> {
+        if let Ok(msg) = WrappedNativeMsgUntyped::new_from(type_name) {
+            // built in type support is never unloaded.
+            return Ok(MessageTypeSupport {
+                ts: msg.ts,
+                type_name: type_name.to_owned(),
+                _library: None,
+            });
+        }
+        let mut parts = type_name.split('/');
+        match (parts.next(), parts.last()) {
+            (Some(package), Some(name)) => TypeSupportLibrary::open(package)?.message(name),
+            _ => Err(Error::InvalidMessageType {
+                msgtype: type_name.to_owned(),
+            }),
+        }
+    }
+
+    pub fn as_ptr(&self) -> *const rosidl_message_type_support_t {
+        self.ts
+    }
+
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+}
+
+impl TypeSupportLibrary {
+    /// Loads the C type support library of `package`.
+    ///
+    /// The library is looked up like the dynamic linker does, e.g.
+    /// through `LD_LIBRARY_PATH`, and then in the `lib` directories of
+    /// `AMENT_PREFIX_PATH`.
+    pub fn open(package: &str) -> Result<Self> {
+        Ok(TypeSupportLibrary {
+            package: package.to_owned(),
+            library: load_library(package, "rosidl_typesupport_c")?,
+        })
+    }
+
+    pub fn package(&self) -> &str {
+        &self.package
+    }
+
+    /// Type support of the message `name` of this package, e.g.
+    /// "String" or "msg/String".
+    pub fn message(&self, name: &str) -> Result<MessageTypeSupport> {
+        let name = name.trim_start_matches("msg/");
+        let ts = self.get::<rosidl_message_type_support_t>("message", "msg", name)?;
+        Ok(MessageTypeSupport {
+            ts,
+            type_name: format!("{}/msg/{}", self.package, name),
+            _library: Some(self.library.clone()),
+        })
+    }
+
+    /// Type support of the service `name` of this package.
+    pub fn service(
+        &self,
+        name: &str,
+    ) -> Result<InterfaceTypeSupport<rosidl_service_type_support_t>> {
+        let name = name.trim_start_matches("srv/");
+        let ts = self.get::<rosidl_service_type_support_t>("service", "srv", name)?;
+        Ok(InterfaceTypeSupport {
+            ts,
+            type_name: format!("{}/srv/{}", self.package, name),
+            _library: Some(self.library.clone()),
+        })
+    }
+
+    /// Type support of the action `name` of this package.
+    pub fn action(&self, name: &str) -> Result<InterfaceTypeSupport<rosidl_action_type_support_t>> {
+        let name = name.trim_start_matches("action/");
+        let ts = self.get::<rosidl_action_type_support_t>("action", "action", name)?;
+        Ok(InterfaceTypeSupport {
+            ts,
+            type_name: format!("{}/action/{}", self.package, name),
+            _library: Some(self.library.clone()),
+        })
+    }
+
+    // Calls e.g.
+    // rosidl_typesupport_c__get_message_type_support_handle__std_msgs__msg__String
+    fn get<T>(&self, kind: &str, subfolder: &str, name: &str) -> Result<*const T> {
+        let symbol = format!(
+            "rosidl_typesupport_c__get_{}_type_support_handle__{}__{}__{}\0",
+            kind, self.package, subfolder, name
+        );
+        get_type_support(&self.library, &symbol)
+    }
+}
+
+// Loads lib<package>__<typesupport>.so.
+pub(crate) fn load_library(package: &str, typesupport: &str) -> Result<Arc<Library>> {
+    let file_name = format!(
+        "{}{}__{}{}",
+        std::env::consts::DLL_PREFIX,
+        package,
+        typesupport,
+        std::env::consts::DLL_SUFFIX
+    );
+    let prefixes = std::env::var("AMENT_PREFIX_PATH").unwrap_or_default();
+    let candidates = std::iter::once(PathBuf::from(&file_name))
+        .chain(std::env::split_paths(&prefixes).map(|p| p.join("lib").join(&file_name)));
+    let mut last_error = None;
+    for candidate in candidates {
+        // loading runs the initializers of the library, which for
+        // type support libraries only register type support.
+        match unsafe { Library::new(&candidate) } {
+            Ok(library) => return Ok(Arc::new(library)),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(Error::TypeSupportNotLoaded {
+        reason: format!(
+            "{}: {}",
+            file_name,
+            last_error.map(|e| e.to_string()).unwrap_or_default()
+        ),
+    })
+}
+
+// Calls the nul terminated `symbol` of `library`.
+fn get_type_support<T>(library: &Library, symbol: &str) -> Result<*const T> {
+    let ts = unsafe {
+        let get_ts = library
+            .get::<unsafe extern "C" fn() -> *const T>(symbol.as_bytes())
+            .map_err(|e| Error::TypeSupportNotLoaded {
+                reason: e.to_string(),
+            })?;
+        get_ts()
+    };
+    if ts.is_null() {
+        return Err(Error::TypeSupportNotLoaded {
+            reason: format!("{} returned null", symbol.trim_end_matches('\0')),
+        });
+    }
+    Ok(ts)
+}
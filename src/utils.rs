@@ -1,11 +1,81 @@
 use r2r_rcl::*;
 use std::ffi::CString;
 use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 
+use crate::log_sinks::write_to_sinks;
+use crate::msg_types::generated_msgs::{builtin_interfaces, rcl_interfaces};
 use lazy_static::lazy_static;
 
 lazy_static! {
     static ref LOG_GUARD: Mutex<()> = Mutex::new(());
+    static ref LOG_RATE_LIMIT: Mutex<LogRateLimit> = Mutex::new(LogRateLimit::new(None));
+}
+
+/// Limits the number of log messages per second.
+pub(crate) struct LogRateLimit {
+    max_per_second: Option<u32>,
+    window_start: Option<Instant>,
+    count: u32,
+    dropped_in_window: u64,
+    dropped_total: u64,
+}
+
+impl LogRateLimit {
+    pub(crate) fn new(max_per_second: Option<u32>) -> Self {
+        LogRateLimit {
+            max_per_second,
+            window_start: None,
+            count: 0,
+            dropped_in_window: 0,
+            dropped_total: 0,
+        }
+    }
+
+    /// Returns whether a message may be logged at `now`, and the
+    /// number of messages dropped in the previous window if a new
+    /// window was started.
+    pub(crate) fn allow(&mut self, now: Instant) -> (bool, u64) {
+        let max = match self.max_per_second {
+            Some(max) => max,
+            None => return (true, 0),
+        };
+        let mut dropped_before = 0;
+        let new_window = match self.window_start {
+            Some(start) => now.duration_since(start) >= Duration::from_secs(1),
+            None => true,
+        };
+        if new_window {
+            self.window_start = Some(now);
+            self.count = 0;
+            dropped_before = self.dropped_in_window;
+            self.dropped_in_window = 0;
+        }
+        if self.count < max {
+            self.count += 1;
+            (true, dropped_before)
+        } else {
+            self.dropped_in_window += 1;
+            self.dropped_total += 1;
+            (false, dropped_before)
+        }
+    }
+}
+
+/// Limit the number of log messages per second, for all loggers.
+///
+/// Messages above the limit are dropped, which also keeps them from
+/// being published on `/rosout`. When messages have been dropped, a
+/// warning with the number of dropped messages is logged at the start
+/// of the next second. `None` (the default) removes the limit.
+pub fn set_log_rate_limit(max_per_second: Option<u32>) {
+    let mut limit = LOG_RATE_LIMIT.lock().unwrap();
+    *limit = LogRateLimit::new(max_per_second);
+}
+
+/// The total number of log messages dropped by the rate limit.
+pub fn dropped_log_messages() -> u64 {
+    LOG_RATE_LIMIT.lock().unwrap().dropped_total
 }
 
 pub(crate) fn log_guard() -> MutexGuard<'static, ()> {
@@ -14,7 +84,45 @@ pub(crate) fn log_guard() -> MutexGuard<'static, ()> {
 
 /// Don't call this directly, use the logging macros instead.
 #[doc(hidden)]
-pub fn log(msg: &str, logger_name: &str, file: &str, line: u32, severity: LogSeverity) {
+pub fn log(
+    msg: &str,
+    logger_name: &str,
+    file: &str,
+    function: &str,
+    line: u32,
+    severity: LogSeverity,
+) {
+    let (allowed, dropped) = LOG_RATE_LIMIT.lock().unwrap().allow(Instant::now());
+    if dropped > 0 {
+        let msg = format!("dropped {} log messages due to rate limit", dropped);
+        log_to_sinks(&msg, logger_name, file, function, line, LogSeverity::Warn);
+    }
+    if allowed {
+        log_to_sinks(msg, logger_name, file, function, line, severity);
+    }
+}
+
+fn log_to_sinks(
+    msg: &str,
+    logger_name: &str,
+    file: &str,
+    function: &str,
+    line: u32,
+    severity: LogSeverity,
+) {
+    if write_to_sinks(msg, logger_name, severity) {
+        log_native(msg, logger_name, file, function, line, severity);
+    }
+}
+
+fn log_native(
+    msg: &str,
+    logger_name: &str,
+    file: &str,
+    function: &str,
+    line: u32,
+    severity: LogSeverity,
+) {
     let _guard = log_guard();
     let is_init = unsafe { g_rcutils_logging_initialized };
     if !is_init {
@@ -24,9 +132,10 @@ pub fn log(msg: &str, logger_name: &str, file: &str, line: u32, severity: LogSev
             return;
         }
     }
-    // currently not possible to get function name in rust.
+    // currently not possible to get function name in rust, the
+    // logging macros pass the module path instead.
     // see https://github.com/rust-lang/rfcs/pull/2818
-    let function = CString::new("").unwrap();
+    let function = CString::new(function).unwrap();
     let file = CString::new(file).unwrap();
     let location = rcutils_log_location_t {
         function_name: function.as_ptr(),
@@ -49,6 +158,7 @@ pub fn log(msg: &str, logger_name: &str, file: &str, line: u32, severity: LogSev
 }
 
 /// Logging severity
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum LogSeverity {
     Unset,
     Debug,
@@ -70,6 +180,45 @@ impl LogSeverity {
             LogSeverity::Fatal => RCUTILS_LOG_SEVERITY_FATAL,
         }
     }
+
+    /// Converts from the level used in `rcl_interfaces/msg/Log`.
+    pub fn from_log_level(level: u8) -> Self {
+        match level {
+            10 => LogSeverity::Debug,
+            20 => LogSeverity::Info,
+            30 => LogSeverity::Warn,
+            40 => LogSeverity::Error,
+            50 => LogSeverity::Fatal,
+            _ => LogSeverity::Unset,
+        }
+    }
+}
+
+/// A log message received on `/rosout`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RosoutEntry {
+    pub stamp: builtin_interfaces::msg::Time,
+    pub severity: LogSeverity,
+    /// Name of the logger that produced the message.
+    pub logger_name: String,
+    pub msg: String,
+    pub file: String,
+    pub function: String,
+    pub line: u32,
+}
+
+impl From<rcl_interfaces::msg::Log> for RosoutEntry {
+    fn from(log: rcl_interfaces::msg::Log) -> Self {
+        RosoutEntry {
+            stamp: log.stamp,
+            severity: LogSeverity::from_log_level(log.level),
+            logger_name: log.name,
+            msg: log.msg,
+            file: log.file,
+            function: log.function,
+            line: log.line,
+        }
+    }
 }
 
 // A helper macro to log the message.
@@ -81,6 +230,7 @@ macro_rules! __impl_log {
             &std::fmt::format($msg),
             $logger_name,
             $file,
+            module_path!(),
             $line,
             $severity,
         );
@@ -132,6 +282,24 @@ macro_rules! log_fatal {
     }}
 }
 
+#[test]
+fn test_log_rate_limit() {
+    let start = Instant::now();
+    let mut limit = LogRateLimit::new(Some(2));
+    assert_eq!(limit.allow(start), (true, 0));
+    assert_eq!(limit.allow(start), (true, 0));
+    assert_eq!(limit.allow(start), (false, 0));
+    assert_eq!(limit.allow(start + Duration::from_millis(500)), (false, 0));
+    // new window, report what was dropped in the last one.
+    assert_eq!(limit.allow(start + Duration::from_secs(1)), (true, 2));
+    assert_eq!(limit.dropped_total, 2);
+
+    let mut unlimited = LogRateLimit::new(None);
+    for _ in 0..100 {
+        assert_eq!(unlimited.allow(start), (true, 0));
+    }
+}
+
 #[test]
 fn test_log() {
     log_debug!("log_test", "debug msg");
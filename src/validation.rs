@@ -0,0 +1,235 @@
+//! Checks that run on every message a publisher publishes, see
+//! `Publisher::add_validator`.
+
+use std::borrow::Cow;
+
+use crate::error::*;
+use crate::log_handler::log_internal;
+use crate::msg_types::*;
+use crate::utils::LogSeverity;
+
+/// What a publisher does with a message that fails a validator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationPolicy {
+    /// Do not publish the message and return
+    /// `Error::ValidationFailed`. The default.
+    Reject,
+    /// Log the reason and publish the message anyway, e.g. while
+    /// trying out new validators on a running system.
+    LogAndSend,
+}
+
+impl Default for ValidationPolicy {
+    fn default() -> Self {
+        ValidationPolicy::Reject
+    }
+}
+
+type Check<T> = Box<dyn Fn(&T) -> std::result::Result<(), String> + Send>;
+type Correction<T> = Box<dyn Fn(&mut T) -> std::result::Result<(), String> + Send>;
+type NativeCheck<T> = Box<dyn Fn(NativeMsgView<'_, T>) -> std::result::Result<(), String> + Send>;
+
+enum Validator<T>
+where
+    T: WrappedTypesupport,
+{
+    Check(Check<T>),
+    Correction(Correction<T>),
+    Native(NativeCheck<T>),
+}
+
+/// The validators of a publisher, shared by its clones.
+pub(crate) struct Validators<T>
+where
+    T: WrappedTypesupport,
+{
+    validators: Vec<Validator<T>>,
+    policy: ValidationPolicy,
+}
+
+impl<T> Default for Validators<T>
+where
+    T: WrappedTypesupport,
+{
+    fn default() -> Self {
+        Validators {
+            validators: Vec::new(),
+            policy: ValidationPolicy::default(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Validators<T>
+where
+    T: WrappedTypesupport,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Validators")
+            .field("validators", &self.validators.len())
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+enum NativeCandidate<'a, T>
+where
+    T: WrappedTypesupport,
+{
+    Borrowed(&'a WrappedNativeMsg<T>),
+    Owned(WrappedNativeMsg<T>),
+}
+
+/// A message on its way through the validators. It is only converted
+/// between the rust and the native type when a validator needs the
+/// other one, or when it is published.
+pub(crate) struct Candidate<'a, T>
+where
+    T: WrappedTypesupport,
+{
+    msg: Option<Cow<'a, T>>,
+    native: Option<NativeCandidate<'a, T>>,
+}
+
+impl<'a, T> Candidate<'a, T>
+where
+    T: WrappedTypesupport,
+{
+    pub(crate) fn from_msg(msg: &'a T) -> Self {
+        Candidate {
+            msg: Some(Cow::Borrowed(msg)),
+            native: None,
+        }
+    }
+
+    pub(crate) fn from_native(native: &'a WrappedNativeMsg<T>) -> Self {
+        Candidate {
+            msg: None,
+            native: Some(NativeCandidate::Borrowed(native)),
+        }
+    }
+
+    pub(crate) fn msg(&mut self) -> &T {
+        if self.msg.is_none() {
+            self.msg = Some(Cow::Owned(T::from_native(self.native())));
+        }
+        self.msg.as_ref().expect("converted above")
+    }
+
+    fn msg_mut(&mut self) -> &mut T {
+        self.msg();
+        // the native message is converted again when it is needed.
+        self.native = None;
+        self.msg.as_mut().expect("converted above").to_mut()
+    }
+
+    /// The native message to publish.
+    pub(crate) fn native(&mut self) -> &WrappedNativeMsg<T> {
+        if self.native.is_none() {
+            let msg = self.msg.as_ref().expect("message or native message");
+            self.native = Some(NativeCandidate::Owned(WrappedNativeMsg::from(msg.as_ref())));
+        }
+        match self.native.as_ref().expect("converted above") {
+            NativeCandidate::Borrowed(native) => native,
+            NativeCandidate::Owned(native) => native,
+        }
+    }
+}
+
+impl<T> Validators<T>
+where
+    T: WrappedTypesupport,
+{
+    pub(crate) fn add_check(&mut self, check: Check<T>) {
+        self.validators.push(Validator::Check(check));
+    }
+
+    pub(crate) fn add_correction(&mut self, correction: Correction<T>) {
+        self.validators.push(Validator::Correction(correction));
+    }
+
+    pub(crate) fn add_native_check(&mut self, check: NativeCheck<T>) {
+        self.validators.push(Validator::Native(check));
+    }
+
+    pub(crate) fn set_policy(&mut self, policy: ValidationPolicy) {
+        self.policy = policy;
+    }
+
+    /// Runs the validators on `candidate` in the order they were
+    /// added.
+    pub(crate) fn validate(&self, candidate: &mut Candidate<'_, T>) -> Result<()> {
+        for validator in &self.validators {
+            let result = match validator {
+                Validator::Check(check) => check(candidate.msg()),
+                Validator::Correction(correction) => correction(candidate.msg_mut()),
+                Validator::Native(check) => check(NativeMsgView::new(candidate.native())),
+            };
+            if let Err(reason) = result {
+                match self.policy {
+                    ValidationPolicy::Reject => return Err(Error::ValidationFailed { reason }),
+                    ValidationPolicy::LogAndSend => log_internal(
+                        LogSeverity::Warn,
+                        &format!("publishing message that failed validation: {}", reason),
+                    ),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, r2r__std_msgs__msg__String))]
+mod tests {
+    use super::*;
+    use crate::msg_types::generated_msgs::std_msgs;
+
+    fn short(msg: &std_msgs::msg::String) -> std::result::Result<(), String> {
+        if msg.data.len() <= 5 {
+            Ok(())
+        } else {
+            Err(format!("{} is too long", msg.data))
+        }
+    }
+
+    #[test]
+    fn test_validators_in_order() {
+        let mut validators = Validators::<std_msgs::msg::String>::default();
+        validators.add_correction(Box::new(|msg: &mut std_msgs::msg::String| {
+            msg.data.truncate(5);
+            Ok(())
+        }));
+        validators.add_check(Box::new(short));
+        validators.add_native_check(Box::new(|native: NativeMsgView<'_, _>| {
+            if native.to_msg().data == "hello" {
+                Ok(())
+            } else {
+                Err("not hello".into())
+            }
+        }));
+
+        let msg = std_msgs::msg::String {
+            data: "hello world".into(),
+        };
+        let mut candidate = Candidate::from_msg(&msg);
+        validators.validate(&mut candidate).unwrap();
+        assert_eq!(
+            std_msgs::msg::String::from_native(candidate.native()).data,
+            "hello"
+        );
+
+        let native = WrappedNativeMsg::from(&std_msgs::msg::String { data: "hi".into() });
+        let mut candidate = Candidate::from_native(&native);
+        assert!(matches!(
+            validators.validate(&mut candidate),
+            Err(Error::ValidationFailed { .. })
+        ));
+
+        validators.set_policy(ValidationPolicy::LogAndSend);
+        let mut candidate = Candidate::from_native(&native);
+        validators.validate(&mut candidate).unwrap();
+        assert_eq!(
+            std_msgs::msg::String::from_native(candidate.native()).data,
+            "hi"
+        );
+    }
+}
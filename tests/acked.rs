@@ -0,0 +1,137 @@
+use futures::future::FutureExt;
+use r2r;
+use r2r::std_msgs::msg::{Int64, String as StringMsg};
+use r2r::test_support::{collect_n, first_of, spin_while};
+use r2r::{AckedOptions, AckedPublisher, AckedSubscriber};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn options() -> AckedOptions {
+    AckedOptions {
+        retry_timeout: Duration::from_millis(50),
+        max_retry_timeout: Duration::from_millis(200),
+        max_attempts: 50,
+        ..Default::default()
+    }
+}
+
+fn command(data: i64) -> Int64 {
+    Int64 { data }
+}
+
+#[test]
+// Commands sent before the subscriber exists are retried until it
+// shows up, and then delivered once each and in order.
+fn acked_late_subscriber() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_acked_late", "")?;
+    let publisher =
+        AckedPublisher::<Int64>::create_with_options(&mut node, "/r2r_acked_late", options())?;
+    let sent = (0..3)
+        .map(|i| publisher.send(&command(i)).map(|f| f.boxed()))
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(publisher.pending(), 3);
+    node.spin_once(Duration::from_millis(100));
+
+    let mut subscriber =
+        AckedSubscriber::<Int64>::create_with_options(&mut node, "/r2r_acked_late", options())?;
+    let received = collect_n(&mut subscriber, 3, &mut node, TIMEOUT);
+    assert_eq!(received, vec![command(0), command(1), command(2)]);
+    for ack in sent {
+        first_of(vec![ack], &mut node, TIMEOUT)?.1?;
+    }
+    assert_eq!(publisher.pending(), 0);
+
+    // nothing is delivered twice, even if retries were in flight.
+    let received = collect_n(&mut subscriber, 1, &mut node, Duration::from_millis(500));
+    assert!(received.is_empty());
+    Ok(())
+}
+
+#[test]
+// A command that arrives twice is delivered once and acknowledged
+// twice.
+fn acked_duplicate() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_acked_duplicate", "")?;
+    let topic = "/r2r_acked_duplicate";
+    let mut subscriber = AckedSubscriber::<Int64>::create(&mut node, topic)?;
+    let ack_topic = format!("{}/ack", topic);
+    let mut acks = node.subscribe::<StringMsg>(&ack_topic)?;
+    let publisher = node.create_publisher::<StringMsg>(topic)?;
+    let subscribers = || {
+        publisher
+            .get_inter_process_subscription_count()
+            .unwrap_or(0)
+    };
+    spin_while(&mut node, || subscribers() == 0, TIMEOUT)?;
+    while node.get_publishers_info_by_topic(&ack_topic)?.is_empty() {
+        node.spin_once(Duration::from_millis(10));
+    }
+
+    let raw = StringMsg {
+        data: serde_json::json!({
+            "publisher": uuid::Uuid::new_v4().to_string(),
+            "seq": 0,
+            "low": 0,
+            "message": { "data": 7 },
+        })
+        .to_string(),
+    };
+    publisher.publish(&raw)?;
+    publisher.publish(&raw)?;
+    let received = collect_n(&mut subscriber, 2, &mut node, Duration::from_secs(1));
+    assert_eq!(received, vec![command(7)]);
+
+    let statuses = collect_n(&mut acks, 2, &mut node, TIMEOUT)
+        .into_iter()
+        .map(|ack| {
+            let ack: serde_json::Value = serde_json::from_str(&ack.data).unwrap();
+            ack["status"].as_str().unwrap().to_owned()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(statuses, vec!["delivered", "duplicate"]);
+    Ok(())
+}
+
+#[test]
+// Commands fail when nobody acknowledges them, or when the subscriber
+// cannot convert them.
+fn acked_failures() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_acked_failures", "")?;
+    let unheard = AckedPublisher::<Int64>::create_with_options(
+        &mut node,
+        "/r2r_acked_unheard",
+        AckedOptions {
+            max_attempts: 3,
+            ..options()
+        },
+    )?;
+    let ack = unheard.send(&command(1))?.boxed();
+    let result = first_of(vec![ack], &mut node, TIMEOUT)?.1;
+    assert!(matches!(
+        result,
+        Err(r2r::Error::CommandNotAcknowledged { attempts: 3, .. })
+    ));
+    assert_eq!(unheard.pending(), 0);
+
+    let publisher = AckedPublisher::<StringMsg>::create_with_options(
+        &mut node,
+        "/r2r_acked_rejected",
+        options(),
+    )?;
+    let _subscriber = AckedSubscriber::<Int64>::create(&mut node, "/r2r_acked_rejected")?;
+    let ack = publisher
+        .send(&StringMsg {
+            data: "not a number".into(),
+        })?
+        .boxed();
+    let result = first_of(vec![ack], &mut node, TIMEOUT)?.1;
+    assert!(matches!(
+        result,
+        Err(r2r::Error::CommandRejected { seq: 0 })
+    ));
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::test_support::{collect_n, first_of};
+use r2r::GoalStatus;
+use std::time::Duration;
+
+#[test]
+// A goal aborted through the node is aborted for the client and for
+// the goal handle of the server, which can no longer finish it.
+fn action_abort_goal() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_abort_goal", "")?;
+    let action = "/r2r_action_abort_goal";
+    let client = node.create_action_client::<Fibonacci::Action>(action)?;
+    let mut goals = node.create_action_server::<Fibonacci::Action>(action)?;
+    let timeout = Duration::from_secs(5);
+    let available = node.is_available(&client)?;
+    first_of(vec![Box::pin(available)], &mut node, timeout)?.1?;
+
+    let goal = client.send_goal_request(Fibonacci::Goal { order: 1 })?;
+    let request = collect_n(&mut goals, 1, &mut node, timeout)
+        .pop()
+        .expect("no goal request");
+    let (mut server_goal, _cancel_requests) = request.accept()?;
+    let (goal, result, _feedback) = first_of(vec![Box::pin(goal)], &mut node, timeout)?.1?;
+    assert_eq!(
+        node.active_goals(action)?,
+        vec![(goal.uuid, GoalStatus::Executing)]
+    );
+
+    node.abort_goal(action, &goal.uuid)?;
+    assert_eq!(server_goal.status()?, GoalStatus::Aborted);
+    assert!(node.active_goals(action)?.is_empty());
+    let (status, _) = first_of(vec![Box::pin(result)], &mut node, timeout)?.1?;
+    assert_eq!(status, GoalStatus::Aborted);
+
+    assert!(server_goal
+        .succeed(Fibonacci::Result { sequence: vec![1] })
+        .is_err());
+    assert!(node.abort_goal(action, &goal.uuid).is_err());
+    assert!(matches!(
+        node.abort_goal("/r2r_no_such_action", &goal.uuid),
+        Err(r2r::Error::RCL_RET_ACTION_NAME_INVALID)
+    ));
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::{Clock, ClockType, GoalStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task;
+
+#[tokio::test(flavor = "multi_thread")]
+// Goals accepted before a stamp are canceled together, and cancel all
+// takes the rest.
+async fn tokio_cancel_all_goals() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_cancel_all", "")?;
+    let client = node.create_action_client::<Fibonacci::Action>("/r2r_action_cancel_all")?;
+    let server = node
+        .action_server_builder::<Fibonacci::Action>("/r2r_action_cancel_all")
+        .on_execute(|goal| async move {
+            while !goal.is_cancelling()? {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Ok(Fibonacci::Result::default())
+        })
+        .build()?;
+    task::spawn(server);
+    let server_available = node.is_available(&client)?;
+
+    let done = Arc::new(AtomicBool::new(false));
+    let spin_done = done.clone();
+    let spin_handle = std::thread::spawn(move || {
+        while !spin_done.load(Ordering::Relaxed) {
+            node.spin_once(Duration::from_millis(10));
+        }
+    });
+    server_available.await?;
+
+    let (first, first_result, _) = client
+        .send_goal_request(Fibonacci::Goal { order: 1 })?
+        .await?;
+    let (second, second_result, _) = client
+        .send_goal_request(Fibonacci::Goal { order: 2 })?
+        .await?;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let stamp = Clock::to_builtin_time(&Clock::create(ClockType::RosTime)?.get_now()?);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let (third, third_result, _) = client
+        .send_goal_request(Fibonacci::Goal { order: 3 })?
+        .await?;
+
+    let mut canceled = client.cancel_goals_before(stamp)?.await?.goal_ids();
+    canceled.sort();
+    let mut expected = vec![first.uuid, second.uuid];
+    expected.sort();
+    assert_eq!(canceled, expected);
+    let (status, _) = tokio::time::timeout(Duration::from_secs(10), first_result).await??;
+    assert_eq!(status, GoalStatus::Canceled);
+    let (status, _) = tokio::time::timeout(Duration::from_secs(10), second_result).await??;
+    assert_eq!(status, GoalStatus::Canceled);
+
+    let canceled = client.cancel_all_goals()?.await?.goal_ids();
+    assert_eq!(canceled, vec![third.uuid]);
+    let (status, _) = tokio::time::timeout(Duration::from_secs(10), third_result).await??;
+    assert_eq!(status, GoalStatus::Canceled);
+
+    done.store(true, Ordering::Relaxed);
+    spin_handle.join().unwrap();
+    Ok(())
+}
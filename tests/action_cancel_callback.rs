@@ -0,0 +1,155 @@
+use futures::channel::oneshot;
+use futures::future::FutureExt;
+use r2r;
+use r2r::action_msgs::srv::CancelGoal;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::test_support::{collect_n, first_of};
+use r2r::{ActionServerOptions, CancelCallback, CancelDecision, GoalId};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// One cancel request for all goals asks the callback about each goal,
+// and the response is only sent once all decisions are made.
+#[test]
+fn cancel_callback_decides_per_goal() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_cancel_callback", "")?;
+    let timeout = Duration::from_secs(10);
+
+    // goals that may be canceled, decided once the gate opens.
+    let safe: Arc<Mutex<Vec<GoalId>>> = Arc::new(Mutex::new(vec![]));
+    let (open_gate, gate) = oneshot::channel::<()>();
+    let gate = gate.shared();
+    let callback_safe = safe.clone();
+    let options = ActionServerOptions {
+        cancel_callback: Some(CancelCallback::new(move |goal| {
+            let accept = callback_safe.lock().unwrap().contains(&goal);
+            let gate = gate.clone();
+            async move {
+                let _ = gate.await;
+                if accept {
+                    CancelDecision::Accept
+                } else {
+                    CancelDecision::Reject
+                }
+            }
+        })),
+        ..Default::default()
+    };
+    let mut goal_requests = node
+        .create_action_server_with_options::<Fibonacci::Action>("/r2r_cancel_callback", options)?;
+    let client = node.create_action_client::<Fibonacci::Action>("/r2r_cancel_callback")?;
+    let cancel_client =
+        node.create_client::<CancelGoal::Service>("/r2r_cancel_callback/_action/cancel_goal")?;
+    let server_available = node.is_available(&client)?;
+    first_of(vec![Box::pin(server_available)], &mut node, timeout)?.1?;
+
+    let first = client.send_goal_request(Fibonacci::Goal { order: 1 })?;
+    let second = client.send_goal_request(Fibonacci::Goal { order: 2 })?;
+    let requests = collect_n(&mut goal_requests, 2, &mut node, timeout);
+    assert_eq!(requests.len(), 2);
+    let mut goals = vec![];
+    for req in requests {
+        if req.goal.order == 1 {
+            safe.lock().unwrap().push(req.uuid);
+        }
+        let (goal, _cancel_requests) = req.accept()?;
+        goals.push(goal);
+    }
+    first_of(vec![Box::pin(first)], &mut node, timeout)?.1?;
+    first_of(vec![Box::pin(second)], &mut node, timeout)?.1?;
+
+    // a zero goal id and stamp cancels all goals.
+    let mut cancel = Box::pin(cancel_client.request(&CancelGoal::Request::default())?);
+    assert!(first_of(vec![&mut cancel], &mut node, Duration::from_millis(300)).is_err());
+
+    open_gate.send(()).unwrap();
+    let response = first_of(vec![&mut cancel], &mut node, timeout)?.1?;
+    assert_eq!(response.return_code, 0);
+    let canceling = response
+        .goals_canceling
+        .iter()
+        .map(|info| GoalId::from_msg(&info.goal_id))
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(canceling, safe.lock().unwrap().clone());
+
+    for goal in &goals {
+        let expected = safe.lock().unwrap().contains(&goal.uuid);
+        assert_eq!(goal.is_cancelling()?, expected);
+    }
+    Ok(())
+}
+
+// A callback that does not decide in time lets the goal be canceled.
+#[test]
+fn cancel_callback_timeout_accepts() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_cancel_callback_timeout", "")?;
+    let timeout = Duration::from_secs(10);
+
+    let callback = CancelCallback::new(|_| futures::future::pending::<CancelDecision>())
+        .timeout(Duration::from_millis(100));
+    let options = ActionServerOptions {
+        cancel_callback: Some(callback),
+        ..Default::default()
+    };
+    let mut goal_requests = node.create_action_server_with_options::<Fibonacci::Action>(
+        "/r2r_cancel_callback_timeout",
+        options,
+    )?;
+    let client = node.create_action_client::<Fibonacci::Action>("/r2r_cancel_callback_timeout")?;
+    let server_available = node.is_available(&client)?;
+    first_of(vec![Box::pin(server_available)], &mut node, timeout)?.1?;
+
+    let goal = client.send_goal_request(Fibonacci::Goal { order: 1 })?;
+    let req = collect_n(&mut goal_requests, 1, &mut node, timeout)
+        .pop()
+        .expect("no goal request");
+    let (server_goal, _cancel_requests) = req.accept()?;
+    let (goal, _result, _feedback) = first_of(vec![Box::pin(goal)], &mut node, timeout)?.1?;
+
+    first_of(vec![Box::pin(goal.cancel()?)], &mut node, timeout)?.1?;
+    assert!(server_goal.is_cancelling()?);
+    Ok(())
+}
+
+// An immediate callback refusing the cancellation keeps the goal
+// executing, and the client gets the rejection.
+#[test]
+fn cancel_callback_immediate_rejects() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_cancel_callback_immediate", "")?;
+    let timeout = Duration::from_secs(10);
+
+    let asked = Arc::new(Mutex::new(vec![]));
+    let callback_asked = asked.clone();
+    let options = ActionServerOptions {
+        cancel_callback: Some(CancelCallback::immediate(move |goal| {
+            callback_asked.lock().unwrap().push(*goal);
+            CancelDecision::Reject
+        })),
+        ..Default::default()
+    };
+    let mut goal_requests = node.create_action_server_with_options::<Fibonacci::Action>(
+        "/r2r_cancel_callback_immediate",
+        options,
+    )?;
+    let client =
+        node.create_action_client::<Fibonacci::Action>("/r2r_cancel_callback_immediate")?;
+    let server_available = node.is_available(&client)?;
+    first_of(vec![Box::pin(server_available)], &mut node, timeout)?.1?;
+
+    let goal = client.send_goal_request(Fibonacci::Goal { order: 1 })?;
+    let req = collect_n(&mut goal_requests, 1, &mut node, timeout)
+        .pop()
+        .expect("no goal request");
+    let uuid = req.uuid;
+    let (server_goal, _cancel_requests) = req.accept()?;
+    let (goal, _result, _feedback) = first_of(vec![Box::pin(goal)], &mut node, timeout)?.1?;
+
+    let cancel = first_of(vec![Box::pin(goal.cancel()?)], &mut node, timeout)?.1;
+    assert!(matches!(cancel, Err(r2r::Error::GoalCancelRejected)));
+    assert_eq!(*asked.lock().unwrap(), vec![uuid]);
+    assert!(!server_goal.is_cancelling()?);
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use futures::future;
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::{GoalDecision, GoalStatus, QosProfile};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task;
+
+fn fibonacci(order: i32) -> Vec<i32> {
+    let mut sequence = vec![0, 1];
+    for i in 2..order as usize {
+        sequence.push(sequence[i - 1] + sequence[i - 2]);
+    }
+    sequence
+}
+
+#[tokio::test(flavor = "multi_thread")]
+// Clients built with different options against servers that answer
+// right away, never answer the goal request or never finish the goal.
+async fn tokio_action_client_builder() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_client_builder", "")?;
+
+    let server = node
+        .action_server_builder::<Fibonacci::Action>("/r2r_client_builder")
+        .qos(QosProfile::default().keep_last(20))
+        .on_execute(|goal| async move {
+            for _ in 0..3 {
+                goal.publish_feedback(Fibonacci::Feedback { sequence: vec![0] })?;
+            }
+            Ok(Fibonacci::Result {
+                sequence: fibonacci(goal.goal.order),
+            })
+        })
+        .build()?;
+    task::spawn(server);
+    let silent_server = node
+        .action_server_builder::<Fibonacci::Action>("/r2r_client_builder_silent")
+        .on_goal(|_| future::pending())
+        .on_execute(|_| async { Ok(Fibonacci::Result::default()) })
+        .build()?;
+    task::spawn(silent_server);
+    let slow_server = node
+        .action_server_builder::<Fibonacci::Action>("/r2r_client_builder_slow")
+        .on_execute(|_| async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(Fibonacci::Result::default())
+        })
+        .build()?;
+    task::spawn(slow_server);
+
+    let client = node
+        .action_client_builder::<Fibonacci::Action>("/r2r_client_builder")
+        .qos(QosProfile::default().keep_last(20))
+        .feedback_capacity(20)
+        .wait_for_server(Duration::from_secs(10))
+        .build();
+    let lazy_client = node
+        .action_client_builder::<Fibonacci::Action>("/r2r_client_builder")
+        .auto_request_result(false)
+        .feedback_qos(QosProfile::default().best_effort().keep_last(1))
+        .status_qos(QosProfile::default().keep_last(1).transient_local())
+        .wait_for_server(Duration::from_secs(10))
+        .build();
+    let silent_client = node
+        .action_client_builder::<Fibonacci::Action>("/r2r_client_builder_silent")
+        .goal_response_timeout(Duration::from_millis(500))
+        .wait_for_server(Duration::from_secs(10))
+        .build();
+    let slow_client = node
+        .action_client_builder::<Fibonacci::Action>("/r2r_client_builder_slow")
+        .result_timeout(Duration::from_millis(500))
+        .wait_for_server(Duration::from_secs(10))
+        .build();
+    let missing_client = node
+        .action_client_builder::<Fibonacci::Action>("/r2r_client_builder_missing")
+        .wait_for_server(Duration::from_millis(500))
+        .build();
+
+    let done = Arc::new(AtomicBool::new(false));
+    let spin_done = done.clone();
+    let spin_handle = std::thread::spawn(move || {
+        while !spin_done.load(Ordering::Relaxed) {
+            node.spin_once(Duration::from_millis(10));
+        }
+    });
+
+    let client = client.await?;
+    let (_goal, result, feedback) = client
+        .send_goal_request(Fibonacci::Goal { order: 5 })?
+        .await?;
+    let (status, msg) = tokio::time::timeout(Duration::from_secs(10), result).await??;
+    assert_eq!(status, GoalStatus::Succeeded);
+    assert_eq!(msg.sequence, fibonacci(5));
+    drop(feedback);
+
+    // the result is only requested once the future is polled.
+    let lazy_client = lazy_client.await?;
+    let (_goal, result, _feedback) = lazy_client
+        .send_goal_request(Fibonacci::Goal { order: 4 })?
+        .await?;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let (status, msg) = tokio::time::timeout(Duration::from_secs(10), result).await??;
+    assert_eq!(status, GoalStatus::Succeeded);
+    assert_eq!(msg.sequence, fibonacci(4));
+
+    let silent_client = silent_client.await?;
+    let goal = silent_client.send_goal_request(Fibonacci::Goal { order: 4 })?;
+    match tokio::time::timeout(Duration::from_secs(10), goal).await? {
+        Err(r2r::Error::RequestTimedOut { .. }) => (),
+        other => panic!("expected a timeout, got {:?}", other.map(|_| ())),
+    }
+
+    let slow_client = slow_client.await?;
+    let (_goal, result, _feedback) = slow_client
+        .send_goal_request(Fibonacci::Goal { order: 4 })?
+        .await?;
+    match tokio::time::timeout(Duration::from_secs(10), result).await? {
+        Err(r2r::Error::RequestTimedOut { .. }) => (),
+        other => panic!("expected a timeout, got {:?}", other.map(|_| ())),
+    }
+
+    match missing_client.await {
+        Err(r2r::Error::ActionServerUnavailable { action_name, .. }) => {
+            assert_eq!(action_name, "/r2r_client_builder_missing")
+        }
+        other => panic!("expected no server, got {:?}", other.map(|_| ())),
+    }
+
+    done.store(true, Ordering::Relaxed);
+    spin_handle.join().unwrap();
+    Ok(())
+}
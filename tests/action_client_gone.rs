@@ -0,0 +1,131 @@
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::test_support::{collect_n, first_of};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// set in the process that plays the client in
+// action_server_notices_killed_client.
+const CLIENT_PROCESS_VAR: &str = "R2R_TEST_ACTION_CLIENT_PROCESS";
+
+#[test]
+// When the client goes away mid-goal, the server notices it when
+// publishing feedback and can stop early.
+fn action_server_notices_client_gone() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_client_gone_server", "")?;
+    let mut goal_requests =
+        node.create_action_server::<Fibonacci::Action>("/r2r_action_client_gone")?;
+
+    // the client lives in its own context and goes away as soon as its
+    // goal has been accepted, like a client process that is killed.
+    let client_thread = thread::spawn(|| -> r2r::Result<()> {
+        let ctx = r2r::Context::create()?;
+        let mut node = r2r::Node::create(ctx, "testnode_action_client_gone_client", "")?;
+        let client = node.create_action_client::<Fibonacci::Action>("/r2r_action_client_gone")?;
+        let timeout = Duration::from_secs(10);
+        let available = node.is_available(&client)?;
+        first_of(vec![Box::pin(available)], &mut node, timeout)?.1?;
+        let goal = client.send_goal_request(Fibonacci::Goal { order: 10 })?;
+        first_of(vec![Box::pin(goal)], &mut node, timeout)?.1?;
+        Ok(())
+    });
+
+    let requests = collect_n(&mut goal_requests, 1, &mut node, Duration::from_secs(10));
+    let req = requests.into_iter().next().expect("no goal request");
+    let (g, _cancel) = req.accept()?;
+    assert!(g.client_reachable());
+    g.publish_feedback(Fibonacci::Feedback { sequence: vec![0] })?;
+    client_thread.join().unwrap()?;
+
+    let start = Instant::now();
+    let error = loop {
+        node.spin_once(Duration::from_millis(50));
+        if let Err(e) = g.publish_feedback(Fibonacci::Feedback {
+            sequence: vec![0, 1],
+        }) {
+            break e;
+        }
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "client never seen gone"
+        );
+    };
+    assert!(matches!(error, r2r::Error::GoalClientUnreachable { .. }));
+    assert!(!g.client_reachable());
+    Ok(())
+}
+
+#[test]
+// Like above, but the client is a process of its own that is killed
+// while its goal runs, so it never gets to clean up. The server notices
+// when the client disappears from the ROS graph, which takes until the
+// liveliness lease of the killed process expires. Finishing the goal
+// still succeeds.
+fn action_server_notices_killed_client() -> Result<(), Box<dyn std::error::Error>> {
+    if std::env::var(CLIENT_PROCESS_VAR).is_ok() {
+        return run_client_process();
+    }
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_client_killed_server", "")?;
+    let mut goal_requests =
+        node.create_action_server::<Fibonacci::Action>("/r2r_action_client_killed")?;
+
+    // the test binary again, running only this test as the client.
+    let mut client = Command::new(std::env::current_exe()?)
+        .args(&[
+            "action_server_notices_killed_client",
+            "--exact",
+            "--nocapture",
+        ])
+        .env(CLIENT_PROCESS_VAR, "1")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let requests = collect_n(&mut goal_requests, 1, &mut node, Duration::from_secs(30));
+    let req = requests.into_iter().next().expect("no goal request");
+    let (mut g, _cancel) = req.accept()?;
+    g.publish_feedback(Fibonacci::Feedback { sequence: vec![0] })?;
+    client.kill()?;
+    client.wait()?;
+
+    let start = Instant::now();
+    let error = loop {
+        node.spin_once(Duration::from_millis(50));
+        if let Err(e) = g.publish_feedback(Fibonacci::Feedback {
+            sequence: vec![0, 1],
+        }) {
+            break e;
+        }
+        assert!(
+            start.elapsed() < Duration::from_secs(60),
+            "killed client never seen gone"
+        );
+    };
+    assert!(matches!(error, r2r::Error::GoalClientUnreachable { .. }));
+    assert!(!g.client_reachable());
+
+    g.succeed(Fibonacci::Result {
+        sequence: vec![0, 1],
+    })?;
+    assert_eq!(g.status()?, r2r::GoalStatus::Succeeded);
+    Ok(())
+}
+
+// Sends a goal and spins until the process is killed.
+fn run_client_process() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_client_killed_client", "")?;
+    let client = node.create_action_client::<Fibonacci::Action>("/r2r_action_client_killed")?;
+    let timeout = Duration::from_secs(30);
+    let available = node.is_available(&client)?;
+    first_of(vec![Box::pin(available)], &mut node, timeout)?.1?;
+    let goal = client.send_goal_request(Fibonacci::Goal { order: 10 })?;
+    let (_, goal) = first_of(vec![Box::pin(goal)], &mut node, timeout)?;
+    let _goal = goal?;
+    loop {
+        node.spin_once(Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,204 @@
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::test_support::{collect_n, first_of, spin_while};
+use r2r::GoalStatus;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+// A goal given as json runs to completion with feedback, another is canceled.
+fn untyped_client_against_typed_server() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_client_untyped", "")?;
+    let mut goal_requests =
+        node.create_action_server::<Fibonacci::Action>("/r2r_action_client_untyped")?;
+    let client = node.create_action_client_untyped(
+        "/r2r_action_client_untyped",
+        "example_interfaces/action/Fibonacci",
+    )?;
+    let available = node.is_available(&client)?;
+    first_of(vec![Box::pin(available)], &mut node, TIMEOUT)?.1?;
+
+    let goal = client.send_goal_request(serde_json::json!({ "order": 2 }))?;
+    let req = collect_n(&mut goal_requests, 1, &mut node, TIMEOUT)
+        .into_iter()
+        .next()
+        .expect("no goal request");
+    assert_eq!(req.goal.order, 2);
+    let (mut server_goal, _cancel_requests) = req.accept()?;
+    let (_, result, mut feedback) = first_of(vec![Box::pin(goal)], &mut node, TIMEOUT)?.1?;
+    server_goal.publish_feedback(Fibonacci::Feedback {
+        sequence: vec![0, 1],
+    })?;
+    let received = collect_n(&mut feedback, 1, &mut node, TIMEOUT);
+    assert_eq!(received.len(), 1);
+    assert_eq!(
+        received[0].as_ref().unwrap(),
+        &serde_json::json!({ "sequence": [0, 1] })
+    );
+    server_goal.succeed(Fibonacci::Result {
+        sequence: vec![0, 1, 1],
+    })?;
+    let (status, result) = first_of(vec![Box::pin(result)], &mut node, TIMEOUT)?.1?;
+    assert_eq!(status, GoalStatus::Succeeded);
+    assert_eq!(result?, serde_json::json!({ "sequence": [0, 1, 1] }));
+
+    let goal = client.send_goal_request(serde_json::json!({ "order": 5 }))?;
+    let req = collect_n(&mut goal_requests, 1, &mut node, TIMEOUT)
+        .into_iter()
+        .next()
+        .expect("no goal request");
+    let (mut server_goal, mut cancel_requests) = req.accept()?;
+    let (client_goal, result, _) = first_of(vec![Box::pin(goal)], &mut node, TIMEOUT)?.1?;
+    let cancel = client_goal.cancel()?;
+    let cancel_request = collect_n(&mut cancel_requests, 1, &mut node, TIMEOUT)
+        .into_iter()
+        .next()
+        .expect("no cancel request");
+    cancel_request.accept();
+    first_of(vec![Box::pin(cancel)], &mut node, TIMEOUT)?.1?;
+    server_goal.canceled(Fibonacci::Result { sequence: vec![0] })?;
+    let (status, _) = first_of(vec![Box::pin(result)], &mut node, TIMEOUT)?.1?;
+    assert_eq!(status, GoalStatus::Canceled);
+    Ok(())
+}
+
+#[test]
+// A goal that does not fit the goal type is an error, not a panic.
+fn untyped_client_rejects_malformed_goal() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_client_untyped_malformed", "")?;
+    let _goal_requests =
+        node.create_action_server::<Fibonacci::Action>("/r2r_action_client_untyped_malformed")?;
+    let client = node.create_action_client_untyped(
+        "/r2r_action_client_untyped_malformed",
+        "example_interfaces/action/Fibonacci",
+    )?;
+    let available = node.is_available(&client)?;
+    first_of(vec![Box::pin(available)], &mut node, TIMEOUT)?.1?;
+
+    let goal = client.send_goal_request(serde_json::json!({ "order": "five" }));
+    assert!(matches!(goal, Err(r2r::Error::SerdeError { .. })));
+    Ok(())
+}
+
+#[test]
+// Requests still waiting when the client goes away fail with
+// ClientDestroyed, not with a generic error.
+fn untyped_client_destroyed() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_client_untyped_destroyed", "")?;
+    // no server, nothing is ever answered.
+    let client = node.create_action_client_untyped(
+        "/r2r_action_client_untyped_destroyed",
+        "example_interfaces/action/Fibonacci",
+    )?;
+    let goal = client.send_goal_request(serde_json::json!({ "order": 2 }))?;
+    let cancel = client.cancel_all_goals()?;
+    node.spin_once(Duration::from_millis(10));
+    drop(node);
+
+    let goal = futures::executor::block_on(goal);
+    assert!(matches!(goal, Err(r2r::Error::ClientDestroyed)));
+    let cancel = futures::executor::block_on(cancel);
+    assert!(matches!(cancel, Err(r2r::Error::ClientDestroyed)));
+    Ok(())
+}
+
+#[test]
+// Goal, cancel and result requests that are never answered time out.
+fn untyped_client_timeouts() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_client_untyped_timeouts", "")?;
+    let options = || r2r::ActionClientOptions {
+        goal_response_timeout: Some(Duration::from_millis(200)),
+        result_timeout: Some(Duration::from_millis(200)),
+        cancel_response_timeout: Some(Duration::from_millis(200)),
+        ..Default::default()
+    };
+    let unanswered = node.create_action_client_untyped_with_options(
+        "/r2r_action_client_untyped_unanswered",
+        "example_interfaces/action/Fibonacci",
+        options(),
+    )?;
+    let goal = unanswered.send_goal_request(serde_json::json!({ "order": 2 }))?;
+    let goal = first_of(vec![Box::pin(goal)], &mut node, TIMEOUT)?.1;
+    assert!(matches!(goal, Err(r2r::Error::RequestTimedOut { .. })));
+    let cancel = unanswered.cancel_all_goals()?;
+    let cancel = first_of(vec![Box::pin(cancel)], &mut node, TIMEOUT)?.1;
+    assert!(matches!(cancel, Err(r2r::Error::RequestTimedOut { .. })));
+
+    // a server that accepts the goal and never finishes it.
+    let mut goal_requests =
+        node.create_action_server::<Fibonacci::Action>("/r2r_action_client_untyped_timeouts")?;
+    let client = node.create_action_client_untyped_with_options(
+        "/r2r_action_client_untyped_timeouts",
+        "example_interfaces/action/Fibonacci",
+        options(),
+    )?;
+    let available = node.is_available(&client)?;
+    first_of(vec![Box::pin(available)], &mut node, TIMEOUT)?.1?;
+    let goal = client.send_goal_request(serde_json::json!({ "order": 2 }))?;
+    let req = collect_n(&mut goal_requests, 1, &mut node, TIMEOUT)
+        .into_iter()
+        .next()
+        .expect("no goal request");
+    let (_server_goal, _cancel_requests) = req.accept()?;
+    let (_, result, _) = first_of(vec![Box::pin(goal)], &mut node, TIMEOUT)?.1?;
+    let result = first_of(vec![Box::pin(result)], &mut node, TIMEOUT)?.1;
+    assert!(matches!(result, Err(r2r::Error::RequestTimedOut { .. })));
+    Ok(())
+}
+
+#[test]
+// Finished goals keep their status for a while, and a client tracking
+// all goals sees those of other clients.
+fn untyped_client_tracks_goals() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_client_untyped_tracking", "")?;
+    let mut goal_requests =
+        node.create_action_server::<Fibonacci::Action>("/r2r_action_client_untyped_tracking")?;
+    let client = node.create_action_client_untyped(
+        "/r2r_action_client_untyped_tracking",
+        "example_interfaces/action/Fibonacci",
+    )?;
+    let observer = node.create_action_client_untyped_with_options(
+        "/r2r_action_client_untyped_tracking",
+        "example_interfaces/action/Fibonacci",
+        r2r::ActionClientOptions {
+            track_all_goals: true,
+            ..Default::default()
+        },
+    )?;
+    let available = node.is_available(&client)?;
+    first_of(vec![Box::pin(available)], &mut node, TIMEOUT)?.1?;
+
+    let goal = client.send_goal_request(serde_json::json!({ "order": 2 }))?;
+    let req = collect_n(&mut goal_requests, 1, &mut node, TIMEOUT)
+        .into_iter()
+        .next()
+        .expect("no goal request");
+    let (mut server_goal, _cancel_requests) = req.accept()?;
+    let (client_goal, result, _) = first_of(vec![Box::pin(goal)], &mut node, TIMEOUT)?.1?;
+    // known from the goal response on, before any status message.
+    assert_ne!(client_goal.get_status()?, GoalStatus::Unknown);
+    server_goal.succeed(Fibonacci::Result {
+        sequence: vec![0, 1, 1],
+    })?;
+    let (status, _) = first_of(vec![Box::pin(result)], &mut node, TIMEOUT)?.1?;
+    assert_eq!(status, GoalStatus::Succeeded);
+
+    assert_eq!(client_goal.get_status()?, GoalStatus::Succeeded);
+    assert_eq!(
+        client.known_goals()?,
+        vec![(client_goal.uuid, GoalStatus::Succeeded)]
+    );
+    let observed = || observer.known_goals().unwrap_or_default();
+    spin_while(
+        &mut node,
+        || observed() != vec![(client_goal.uuid, GoalStatus::Succeeded)],
+        TIMEOUT,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::test_support::{collect_n, first_of};
+use r2r::{ActionClientOptions, ActionServerOptions};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// The key-value pairs of the first status of a debug message.
+fn values(msg: &serde_json::Value) -> Vec<(String, String)> {
+    msg["status"][0]["values"]
+        .as_array()
+        .map(|values| {
+            values
+                .iter()
+                .map(|kv| {
+                    let s = |v: &serde_json::Value| v.as_str().unwrap_or("").to_owned();
+                    (s(&kv["key"]), s(&kv["value"]))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[test]
+// With a debug period, the client and the server publish what they
+// know about an executing goal on their hidden debug topics.
+fn action_debug_topics() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_debug", "")?;
+    let period = Duration::from_millis(50);
+    let mut goal_requests = node.create_action_server_with_options::<Fibonacci::Action>(
+        "/r2r_action_debug",
+        ActionServerOptions {
+            debug_period: Some(period),
+            ..Default::default()
+        },
+    )?;
+    let client = node.create_action_client_with_options::<Fibonacci::Action>(
+        "/r2r_action_debug",
+        ActionClientOptions {
+            debug_period: Some(period),
+            ..Default::default()
+        },
+    )?;
+    let msg_type = "diagnostic_msgs/msg/DiagnosticArray";
+    let mut client_debug =
+        node.subscribe_untyped("/testnode_action_debug/_r2r/action_client_debug", msg_type)?;
+    let mut server_debug =
+        node.subscribe_untyped("/testnode_action_debug/_r2r/action_server_debug", msg_type)?;
+    let available = node.is_available(&client)?;
+    first_of(vec![Box::pin(available)], &mut node, TIMEOUT)?.1?;
+
+    let goal = client.send_goal_request(Fibonacci::Goal { order: 5 })?;
+    let req = collect_n(&mut goal_requests, 1, &mut node, TIMEOUT)
+        .into_iter()
+        .next()
+        .expect("no goal request");
+    let (_server_goal, _cancel_requests) = req.accept()?;
+    let (client_goal, _result, _feedback) = first_of(vec![Box::pin(goal)], &mut node, TIMEOUT)?.1?;
+    let goal_key = format!("goal {}", client_goal.uuid);
+
+    // the first messages may be from before the goal was accepted.
+    let shows_goal = |msg: &serde_json::Value| {
+        values(msg)
+            .iter()
+            .any(|(key, value)| key == &goal_key && value == "executing")
+    };
+    let mut found = (false, false);
+    for _ in 0..100 {
+        for msg in collect_n(&mut client_debug, 1, &mut node, Duration::from_millis(100)) {
+            let msg = msg?;
+            assert_eq!(
+                msg["status"][0]["name"],
+                "/testnode_action_debug: /r2r_action_debug"
+            );
+            assert!(values(&msg).iter().any(|(key, _)| key == "goal_status"));
+            found.0 |= shows_goal(&msg);
+        }
+        for msg in collect_n(&mut server_debug, 1, &mut node, Duration::from_millis(100)) {
+            found.1 |= shows_goal(&msg?);
+        }
+        if found == (true, true) {
+            break;
+        }
+    }
+    assert_eq!(found, (true, true));
+    Ok(())
+}
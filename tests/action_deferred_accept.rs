@@ -0,0 +1,45 @@
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::test_support::{collect_n, first_of};
+use std::time::Duration;
+
+#[test]
+// Goal requests can be decided on later, the client waits for the
+// decision, and a request that is dropped undecided is rejected.
+fn action_deferred_accept() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_deferred_accept", "")?;
+    let client = node.create_action_client::<Fibonacci::Action>("/r2r_action_deferred_accept")?;
+    let mut goals =
+        node.create_action_server::<Fibonacci::Action>("/r2r_action_deferred_accept")?;
+    let timeout = Duration::from_secs(5);
+    let available = node.is_available(&client)?;
+    first_of(vec![Box::pin(available)], &mut node, timeout)?.1?;
+
+    let mut accepted_goal = Box::pin(client.send_goal_request(Fibonacci::Goal { order: 1 })?);
+    let mut dropped_goal = Box::pin(client.send_goal_request(Fibonacci::Goal { order: 2 })?);
+    let mut requests = collect_n(&mut goals, 2, &mut node, timeout);
+    assert_eq!(requests.len(), 2);
+    requests.sort_by_key(|req| req.goal.order);
+
+    // no response until there is a decision.
+    assert!(matches!(
+        first_of(
+            vec![&mut accepted_goal, &mut dropped_goal],
+            &mut node,
+            Duration::from_millis(200)
+        ),
+        Err(r2r::Error::RCL_RET_TIMEOUT)
+    ));
+
+    let dropped = requests.pop().unwrap();
+    let accepted = requests.pop().unwrap();
+    let (_goal, _cancel_requests) = accepted.accept()?;
+    first_of(vec![accepted_goal], &mut node, timeout)?.1?;
+    drop(dropped);
+    assert!(matches!(
+        first_of(vec![dropped_goal], &mut node, timeout)?.1,
+        Err(r2r::Error::GoalRejected)
+    ));
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::test_support::{collect_n, first_of};
+use r2r::FeedbackThrottle;
+use std::time::Duration;
+
+#[test]
+// Throttled feedback skips messages within the interval, and the last
+// skipped one is published before the goal succeeds.
+fn action_feedback_throttle() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_feedback_throttle", "")?;
+    let client = node.create_action_client::<Fibonacci::Action>("/r2r_action_feedback_throttle")?;
+    let mut goals =
+        node.create_action_server::<Fibonacci::Action>("/r2r_action_feedback_throttle")?;
+    let timeout = Duration::from_secs(5);
+    let available = node.is_available(&client)?;
+    first_of(vec![Box::pin(available)], &mut node, timeout)?.1?;
+
+    let goal = client.send_goal_request(Fibonacci::Goal { order: 5 })?;
+    let request = collect_n(&mut goals, 1, &mut node, timeout)
+        .pop()
+        .expect("no goal request");
+    let (mut server_goal, _cancel_requests) = request.accept()?;
+    let (_goal, result, mut feedback) = first_of(vec![Box::pin(goal)], &mut node, timeout)?.1?;
+
+    server_goal.set_feedback_throttle(Some(FeedbackThrottle::new(Duration::from_secs(60))));
+    let published = (1..=5)
+        .map(|i| server_goal.publish_feedback_throttled(Fibonacci::Feedback { sequence: vec![i] }))
+        .collect::<r2r::Result<Vec<_>>>()?;
+    assert_eq!(published, [true, false, false, false, false]);
+    server_goal.succeed(Fibonacci::Result { sequence: vec![5] })?;
+
+    let received = collect_n(&mut feedback, 2, &mut node, timeout);
+    let received: Vec<_> = received.into_iter().map(|f| f.sequence).collect();
+    assert_eq!(received, [vec![1], vec![5]]);
+    let (status, _) = first_of(vec![Box::pin(result)], &mut node, timeout)?.1?;
+    assert_eq!(status, r2r::GoalStatus::Succeeded);
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use futures::stream::StreamExt;
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::GoalStatus;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task;
+
+#[tokio::test(flavor = "multi_thread")]
+// Two goals in flight at once, one runs to completion and the other
+// is canceled, each through its own handle.
+async fn tokio_client_goal_handles() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_goal_handle", "")?;
+    let client = node.create_action_client::<Fibonacci::Action>("/r2r_action_goal_handle")?;
+    // goals with a negative order run until canceled.
+    let server = node
+        .action_server_builder::<Fibonacci::Action>("/r2r_action_goal_handle")
+        .on_execute(|goal| async move {
+            while goal.goal.order < 0 && !goal.is_cancelling()? {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            for i in 0..goal.goal.order.max(0) {
+                goal.publish_feedback(Fibonacci::Feedback { sequence: vec![i] })?;
+            }
+            Ok(Fibonacci::Result {
+                sequence: vec![goal.goal.order],
+            })
+        })
+        .build()?;
+    task::spawn(server);
+    let server_available = node.is_available(&client)?;
+
+    let done = Arc::new(AtomicBool::new(false));
+    let spin_done = done.clone();
+    let spin_handle = std::thread::spawn(move || {
+        while !spin_done.load(Ordering::Relaxed) {
+            node.spin_once(Duration::from_millis(10));
+        }
+    });
+    server_available.await?;
+
+    let mut endless = client.send_goal(Fibonacci::Goal { order: -1 })?.await?;
+    let mut finite = client.send_goal(Fibonacci::Goal { order: 3 })?.await?;
+    assert_ne!(endless.uuid(), finite.uuid());
+
+    let feedback = finite.feedback().expect("feedback taken");
+    assert!(finite.feedback().is_none());
+    let (status, result) = finite.result().expect("result taken").await?;
+    assert_eq!(status, GoalStatus::Succeeded);
+    assert_eq!(result.sequence, vec![3]);
+    // the final status is kept after the goal has finished.
+    assert_eq!(finite.status()?, GoalStatus::Succeeded);
+    // and its feedback stream ends.
+    let feedback: Vec<_> = feedback.collect().await;
+    assert_eq!(feedback.len(), 3);
+
+    let canceled = endless.cancel()?.await?;
+    assert_eq!(canceled.goal_ids(), vec![endless.uuid()]);
+    let (status, _) = endless.result().expect("result taken").await?;
+    assert_eq!(status, GoalStatus::Canceled);
+
+    done.store(true, Ordering::Relaxed);
+    spin_handle.join().unwrap();
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use futures::channel::oneshot;
+use futures::stream::StreamExt;
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task;
+
+#[tokio::test(flavor = "multi_thread")]
+// With one executing slot and one queue slot, the second goal waits
+// in the queue and the third is rejected. The queued goal starts once
+// the first one succeeds.
+async fn tokio_action_goal_queue() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let node = r2r::Node::create(ctx, "testnode_action_goal_queue", "")?;
+    let node = Arc::new(Mutex::new(node));
+    let options = r2r::ActionServerOptions {
+        goal_queue: Some(r2r::GoalQueue {
+            max_executing: 1,
+            max_queued: 1,
+        }),
+        ..Default::default()
+    };
+    let client = node
+        .lock()
+        .unwrap()
+        .create_action_client::<Fibonacci::Action>("/r2r_action_goal_queue")?;
+    let mut goal_requests = node
+        .lock()
+        .unwrap()
+        .create_action_server_with_options::<Fibonacci::Action>("/r2r_action_goal_queue", options)?;
+    let server_available = node.lock().unwrap().is_available(&client)?;
+
+    let done = Arc::new(AtomicBool::new(false));
+    let spin_done = done.clone();
+    let spin_node = node.clone();
+    let spin_handle = std::thread::spawn(move || {
+        while !spin_done.load(Ordering::Relaxed) {
+            spin_node.lock().unwrap().spin_once(Duration::from_millis(10));
+        }
+    });
+
+    // the first goal runs until released, later goals finish at once.
+    let (release_sender, release_receiver) = oneshot::channel::<()>();
+    let started = Arc::new(Mutex::new(Vec::new()));
+    let task_started = started.clone();
+    task::spawn(async move {
+        let mut release = Some(release_receiver);
+        while let Some(req) = goal_requests.next().await {
+            let order = req.goal.order;
+            task_started.lock().unwrap().push(order);
+            let (mut g, _cancel) = req.accept().expect("could not accept goal");
+            if let Some(release) = release.take() {
+                release.await.expect("release dropped");
+            }
+            g.succeed(Fibonacci::Result {
+                sequence: vec![order],
+            })
+            .expect("could not send result");
+        }
+    });
+
+    server_available.await?;
+
+    let (first, first_result, _) = client
+        .send_goal_request(Fibonacci::Goal { order: 1 })?
+        .await?;
+    let (second, second_result, _) = client
+        .send_goal_request(Fibonacci::Goal { order: 2 })?
+        .await?;
+    assert!(first.accepted_at.sec > 0);
+    let third = client
+        .send_goal_request(Fibonacci::Goal { order: 3 })?
+        .await;
+    assert!(matches!(third, Err(r2r::Error::GoalRejected)));
+
+    let active = node
+        .lock()
+        .unwrap()
+        .active_goals("/r2r_action_goal_queue")?;
+    assert_eq!(
+        active,
+        vec![
+            (first.uuid, r2r::GoalStatus::Executing),
+            (second.uuid, r2r::GoalStatus::Accepted),
+        ]
+    );
+    assert_eq!(*started.lock().unwrap(), vec![1]);
+
+    release_sender.send(()).unwrap();
+    let (status, msg) = tokio::time::timeout(Duration::from_secs(10), first_result).await??;
+    assert_eq!(status, r2r::GoalStatus::Succeeded);
+    assert_eq!(msg.sequence, vec![1]);
+    let (status, msg) = tokio::time::timeout(Duration::from_secs(10), second_result).await??;
+    assert_eq!(status, r2r::GoalStatus::Succeeded);
+    assert_eq!(msg.sequence, vec![2]);
+    assert_eq!(*started.lock().unwrap(), vec![1, 2]);
+
+    done.store(true, Ordering::Relaxed);
+    spin_handle.join().unwrap();
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::test_support::{collect_n, first_of};
+use std::time::Duration;
+
+#[test]
+// A goal sent with our own id keeps it, and the id cannot be reused
+// while the client knows the goal.
+fn action_goal_with_uuid() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_goal_uuid", "")?;
+    let client = node.create_action_client::<Fibonacci::Action>("/r2r_action_goal_uuid")?;
+    let mut goals = node.create_action_server::<Fibonacci::Action>("/r2r_action_goal_uuid")?;
+    let timeout = Duration::from_secs(5);
+    let available = node.is_available(&client)?;
+    first_of(vec![Box::pin(available)], &mut node, timeout)?.1?;
+
+    let id = r2r::GoalId::from(uuid::Uuid::from_u128(0x2a));
+    let send = || {
+        client
+            .goal_request_builder(Fibonacci::Goal { order: 1 })
+            .goal_id(id)
+            .send()
+    };
+    let goal = send()?;
+    assert!(matches!(send(), Err(r2r::Error::GoalIdInUse { .. })));
+
+    let requests = collect_n(&mut goals, 1, &mut node, timeout);
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].uuid, id);
+    let mut accepted = vec![];
+    for req in requests {
+        accepted.push(req.accept()?);
+    }
+    let (goal, _result, _feedback) = first_of(vec![Box::pin(goal)], &mut node, timeout)?.1?;
+    assert_eq!(goal.uuid, id);
+    assert!(matches!(send(), Err(r2r::Error::GoalIdInUse { .. })));
+    Ok(())
+}
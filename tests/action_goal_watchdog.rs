@@ -0,0 +1,47 @@
+use futures::stream::StreamExt;
+use r2r;
+use r2r::builtin_interfaces::msg::Time;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::test_support::{collect_n, first_of};
+use r2r::GoalWatchdog;
+use std::time::Duration;
+
+// A server that accepts goals and then never responds: no feedback,
+// no status and no cancel or result service at all.
+#[test]
+fn watchdog_fails_goal_of_unresponsive_server() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_goal_watchdog", "")?;
+    let timeout = Duration::from_secs(10);
+
+    let mut goals = node
+        .create_service::<Fibonacci::SendGoal::Service>("/r2r_goal_watchdog/_action/send_goal")?;
+    let client = node.create_action_client::<Fibonacci::Action>("/r2r_goal_watchdog")?;
+    let watchdog = GoalWatchdog::new(Duration::from_millis(200), Duration::from_millis(200));
+    // the options combine, e.g. with a goal id of our own.
+    let id = r2r::GoalId::new_random();
+    let goal = client
+        .goal_request_builder(Fibonacci::Goal { order: 5 })
+        .goal_id(id)
+        .watchdog(watchdog)
+        .send()?;
+
+    let requests = collect_n(&mut goals, 1, &mut node, timeout);
+    assert_eq!(requests.len(), 1);
+    for req in requests {
+        req.respond(Fibonacci::SendGoal::Response {
+            accepted: true,
+            stamp: Time::default(),
+        })?;
+    }
+    let (_, goal) = first_of(vec![Box::pin(goal)], &mut node, timeout)?;
+    let (handle, result, mut feedback) = goal?;
+    assert_eq!(handle.uuid, id);
+
+    let (_, result) = first_of(vec![Box::pin(result)], &mut node, timeout)?;
+    assert!(matches!(result, Err(r2r::Error::GoalStuck { .. })));
+    assert_eq!(handle.get_status()?, r2r::GoalStatus::Unknown);
+    let (_, end) = first_of(vec![feedback.next()], &mut node, timeout)?;
+    assert!(end.is_none());
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::test_support::{collect_n, first_of};
+use r2r::{ActionClientOptions, ActionServerOptions, GoalStatus};
+use std::time::{Duration, Instant};
+
+#[test]
+// Results are kept for `result_timeout` after the goal finished, after
+// that the goal expires and is unknown to the server.
+fn action_result_timeout() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_result_timeout", "")?;
+    let client = node.create_action_client_with_options::<Fibonacci::Action>(
+        "/r2r_action_result_timeout",
+        ActionClientOptions {
+            auto_request_result: false,
+            ..Default::default()
+        },
+    )?;
+    let mut goals = node.create_action_server_with_options::<Fibonacci::Action>(
+        "/r2r_action_result_timeout",
+        ActionServerOptions {
+            result_timeout: Some(Duration::from_millis(500)),
+            ..Default::default()
+        },
+    )?;
+    let timeout = Duration::from_secs(5);
+    let available = node.is_available(&client)?;
+    first_of(vec![Box::pin(available)], &mut node, timeout)?.1?;
+
+    let early = client.send_goal_request(Fibonacci::Goal { order: 1 })?;
+    let late = client.send_goal_request(Fibonacci::Goal { order: 2 })?;
+    for req in collect_n(&mut goals, 2, &mut node, timeout) {
+        let order = req.goal.order;
+        let (mut g, _cancel) = req.accept()?;
+        g.succeed(Fibonacci::Result {
+            sequence: vec![order],
+        })?;
+    }
+    let (_, early_result, _) = first_of(vec![Box::pin(early)], &mut node, timeout)?.1?;
+    let (_, late_result, _) = first_of(vec![Box::pin(late)], &mut node, timeout)?.1?;
+
+    let (status, result) = first_of(vec![Box::pin(early_result)], &mut node, timeout)?.1?;
+    assert_eq!(status, GoalStatus::Succeeded);
+    assert_eq!(result.sequence, vec![1]);
+
+    let expired = Instant::now() + Duration::from_secs(1);
+    while Instant::now() < expired {
+        node.spin_once(Duration::from_millis(10));
+    }
+    let (status, result) = first_of(vec![Box::pin(late_result)], &mut node, timeout)?.1?;
+    assert_eq!(status, GoalStatus::Unknown);
+    assert!(result.sequence.is_empty());
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task;
+
+#[tokio::test(flavor = "multi_thread")]
+// The client waits for a server that starts a second after it.
+async fn tokio_wait_for_late_action_server() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx.clone(), "testnode_action_available", "")?;
+    let client = node.create_action_client::<Fibonacci::Action>("/r2r_action_available")?;
+    assert!(!client.is_available(&node)?);
+    let server_available = node.is_available(&client)?;
+
+    let node = Arc::new(Mutex::new(node));
+    let done = Arc::new(AtomicBool::new(false));
+    let spin_node = node.clone();
+    let spin_done = done.clone();
+    let spin_handle = std::thread::spawn(move || {
+        while !spin_done.load(Ordering::Relaxed) {
+            spin_node
+                .lock()
+                .unwrap()
+                .spin_once(Duration::from_millis(10));
+        }
+    });
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let mut server_node = r2r::Node::create(ctx, "testnode_action_available_server", "")?;
+    let _server = server_node.create_action_server::<Fibonacci::Action>("/r2r_action_available")?;
+
+    tokio::time::timeout(Duration::from_secs(10), server_available).await??;
+    assert!(client.is_available(&node.lock().unwrap())?);
+
+    done.store(true, Ordering::Relaxed);
+    spin_handle.join().unwrap();
+    Ok(())
+}
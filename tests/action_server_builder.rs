@@ -0,0 +1,80 @@
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::{GoalDecision, GoalStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task;
+
+#[tokio::test(flavor = "multi_thread")]
+// Goals are accepted or rejected by on_goal, and the result returned
+// by on_execute finishes the goal.
+async fn tokio_action_server_builder() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_server_builder", "")?;
+    let client = node.create_action_client::<Fibonacci::Action>("/r2r_action_server_builder")?;
+
+    // on_execute is required.
+    assert!(node
+        .action_server_builder::<Fibonacci::Action>("/r2r_action_server_builder_incomplete")
+        .on_goal(|_| async { GoalDecision::Accept })
+        .build()
+        .is_err());
+
+    let server = node
+        .action_server_builder::<Fibonacci::Action>("/r2r_action_server_builder")
+        .on_goal(|goal: Fibonacci::Goal| async move {
+            if goal.order <= 10 {
+                GoalDecision::Accept
+            } else {
+                GoalDecision::Reject
+            }
+        })
+        .on_execute(|goal| async move {
+            if goal.goal.order < 0 {
+                return Err(r2r::Error::RCL_RET_INVALID_ARGUMENT);
+            }
+            let mut sequence = vec![0, 1];
+            for i in 2..goal.goal.order as usize {
+                sequence.push(sequence[i - 1] + sequence[i - 2]);
+                goal.publish_feedback(Fibonacci::Feedback {
+                    sequence: sequence.clone(),
+                })?;
+            }
+            Ok(Fibonacci::Result { sequence })
+        })
+        .build()?;
+    task::spawn(server);
+    let server_available = node.is_available(&client)?;
+
+    let done = Arc::new(AtomicBool::new(false));
+    let spin_done = done.clone();
+    let spin_handle = std::thread::spawn(move || {
+        while !spin_done.load(Ordering::Relaxed) {
+            node.spin_once(Duration::from_millis(10));
+        }
+    });
+    server_available.await?;
+
+    let (_goal, result, _feedback) = client
+        .send_goal_request(Fibonacci::Goal { order: 5 })?
+        .await?;
+    let (status, msg) = tokio::time::timeout(Duration::from_secs(10), result).await??;
+    assert_eq!(status, GoalStatus::Succeeded);
+    assert_eq!(msg.sequence, vec![0, 1, 1, 2, 3]);
+
+    let (_goal, result, _feedback) = client
+        .send_goal_request(Fibonacci::Goal { order: -1 })?
+        .await?;
+    let (status, _msg) = tokio::time::timeout(Duration::from_secs(10), result).await??;
+    assert_eq!(status, GoalStatus::Aborted);
+
+    assert!(client
+        .send_goal_request(Fibonacci::Goal { order: 11 })?
+        .await
+        .is_err());
+
+    done.store(true, Ordering::Relaxed);
+    spin_handle.join().unwrap();
+    Ok(())
+}
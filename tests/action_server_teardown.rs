@@ -0,0 +1,50 @@
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::test_support::{collect_n, first_of};
+use r2r::GoalStatus;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+const ACTION: &str = "/r2r_action_server_teardown";
+
+#[test]
+// When the node of a server is dropped mid-goal, the goal is aborted
+// instead of leaving the client waiting for its result forever.
+fn action_server_teardown_aborts_goals() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_server_teardown_client", "")?;
+    let client = node.create_action_client::<Fibonacci::Action>(ACTION)?;
+
+    // the server lives in its own context and is dropped while its goal
+    // is executing, like a server process that shuts down.
+    let (shutdown_sender, shutdown_receiver) = mpsc::channel();
+    let server_thread = thread::spawn(move || -> r2r::Result<()> {
+        let ctx = r2r::Context::create()?;
+        let mut node = r2r::Node::create(ctx, "testnode_action_server_teardown_server", "")?;
+        let mut goal_requests = node.create_action_server::<Fibonacci::Action>(ACTION)?;
+        let requests = collect_n(&mut goal_requests, 1, &mut node, Duration::from_secs(10));
+        let req = requests.into_iter().next().expect("no goal request");
+        let (g, _cancel) = req.accept()?;
+        g.publish_feedback(Fibonacci::Feedback { sequence: vec![0] })?;
+        while shutdown_receiver.try_recv().is_err() {
+            node.spin_once(Duration::from_millis(10));
+        }
+        drop(node);
+        drop(g);
+        Ok(())
+    });
+
+    let timeout = Duration::from_secs(10);
+    let available = node.is_available(&client)?;
+    first_of(vec![Box::pin(available)], &mut node, timeout)?.1?;
+    let goal = client.send_goal_request(Fibonacci::Goal { order: 10 })?;
+    let (_goal, result, mut feedback) = first_of(vec![Box::pin(goal)], &mut node, timeout)?.1?;
+    assert_eq!(collect_n(&mut feedback, 1, &mut node, timeout).len(), 1);
+    shutdown_sender.send(())?;
+
+    let (status, _) = first_of(vec![Box::pin(result)], &mut node, timeout)?.1?;
+    assert_eq!(status, GoalStatus::Aborted);
+    server_thread.join().unwrap()?;
+    Ok(())
+}
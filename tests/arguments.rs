@@ -0,0 +1,23 @@
+use r2r;
+
+#[test]
+// Without remap rules, names are only expanded.
+fn describe_resolution_without_rules() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let args = ctx.global_arguments()?;
+    assert_eq!(args.args, std::env::args().collect::<Vec<_>>());
+    assert!(args.remap_rules.is_empty());
+
+    let node = r2r::Node::create(ctx, "testnode_arguments", "/r2r_ns")?;
+    assert!(node.effective_remap_rules()?.is_empty());
+
+    let trace = node.describe_resolution("chatter")?;
+    assert_eq!(trace.expanded, "/r2r_ns/chatter");
+    assert_eq!(trace.resolved, "/r2r_ns/chatter");
+    assert!(!trace.remapped);
+
+    let trace = node.describe_resolution("~/status")?;
+    assert_eq!(trace.resolved, "/r2r_ns/testnode_arguments/status");
+    assert!(trace.to_string().contains("no rule matched"));
+    Ok(())
+}
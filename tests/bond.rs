@@ -0,0 +1,40 @@
+use r2r;
+use r2r::test_support::collect_n;
+use r2r::{BondBreak, BondEvent, BondOptions, BondState};
+use std::time::Duration;
+
+#[test]
+// Two sides of a bond form it, and the one left behind notices when
+// the other is dropped.
+fn bond_formed_and_broken() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_bond", "")?;
+    let topic = "/r2r_bond";
+    let options = BondOptions {
+        heartbeat_period: Duration::from_millis(50),
+        heartbeat_timeout: Duration::from_millis(500),
+        ..BondOptions::default()
+    };
+    let supervisor = match node.create_bond(topic, "child", options) {
+        Err(r2r::Error::InvalidMessageType { .. }) => {
+            // the bond message package is not installed.
+            return Ok(());
+        }
+        bond => bond?,
+    };
+    let child = node.create_bond(topic, "child", options)?;
+    assert_ne!(supervisor.instance_id(), child.instance_id());
+
+    let mut events = supervisor.events();
+    let received = collect_n(&mut events, 1, &mut node, Duration::from_secs(5));
+    assert_eq!(received, vec![BondEvent::Formed]);
+    let received = collect_n(&mut child.events(), 1, &mut node, Duration::from_secs(5));
+    assert_eq!(received, vec![BondEvent::Formed]);
+    assert_eq!(child.state(), BondState::Active);
+
+    drop(child);
+    let received = collect_n(&mut events, 1, &mut node, Duration::from_secs(5));
+    assert_eq!(received, vec![BondEvent::Broken(BondBreak::PeerBroke)]);
+    assert_eq!(supervisor.state(), BondState::Broken);
+    Ok(())
+}
@@ -0,0 +1,66 @@
+#![cfg(feature = "capi")]
+
+use futures::stream::StreamExt;
+use futures::FutureExt;
+use r2r;
+use r2r::example_interfaces::srv::AddTwoInts;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+// Compiles tests/capi/capi_test.c against the `r2r_capi` example library and
+// runs it, with a service for it to call.
+fn capi_from_c() -> Result<(), Box<dyn std::error::Error>> {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // the test binary is in target/<profile>/deps.
+    let profile_dir = std::env::current_exe()?
+        .parent()
+        .and_then(|deps| deps.parent())
+        .map(|p| p.to_owned())
+        .expect("no target dir");
+    let lib_dir = profile_dir.join("examples");
+    let program = profile_dir.join("r2r_capi_test");
+
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_owned());
+    let status = Command::new(cc)
+        .arg(manifest_dir.join("tests/capi/capi_test.c"))
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg("-lr2r_capi")
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-o")
+        .arg(&program)
+        .status()?;
+    assert!(status.success(), "could not compile the C test program");
+
+    let done = Arc::new(AtomicBool::new(false));
+    let server_done = done.clone();
+    let server = std::thread::spawn(move || -> Result<(), r2r::Error> {
+        let ctx = r2r::Context::create()?;
+        let mut node = r2r::Node::create(ctx, "testnode_capi_server", "")?;
+        let mut requests = node.create_service::<AddTwoInts::Service>("/r2r_capi_add_two_ints")?;
+        while !server_done.load(Ordering::SeqCst) {
+            node.spin_once(Duration::from_millis(10));
+            while let Some(Some(request)) = requests.next().now_or_never() {
+                let sum = request.message.a + request.message.b;
+                request.respond(AddTwoInts::Response { sum })?;
+            }
+        }
+        Ok(())
+    });
+
+    let output = Command::new(&program).output()?;
+    done.store(true, Ordering::SeqCst);
+    server.join().expect("server panicked")?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(())
+}
@@ -0,0 +1,95 @@
+/*
+ * Exercises the C API, run by tests/capi.rs. Expects a server of
+ * example_interfaces/srv/AddTwoInts on /r2r_capi_add_two_ints.
+ */
+#include <stdio.h>
+#include <string.h>
+
+#include "r2r.h"
+
+#define CHECK(cond)                                                  \
+    do {                                                             \
+        if (!(cond)) {                                               \
+            char msg[256];                                           \
+            r2r_last_error(msg, sizeof(msg));                        \
+            fprintf(stderr, "%s:%d: %s (last error: %s)\n", __FILE__, \
+                    __LINE__, #cond, msg);                           \
+            return 1;                                                \
+        }                                                            \
+    } while (0)
+
+/* std_msgs/msg/String { data: "hello" } as little endian CDR. */
+static const uint8_t HELLO[] = {0x00, 0x01, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00,
+                                'h',  'e',  'l',  'l',  'o',  0x00};
+
+int main(void) {
+    R2rContext *context = NULL;
+    R2rNode *node = NULL;
+    R2rPublisher *publisher = NULL;
+    R2rSubscriber *subscriber = NULL;
+    R2rClient *client = NULL;
+    R2rResponse *response = NULL;
+    uint8_t *data = NULL;
+    size_t len = 0;
+    int ret = R2R_ERR_NO_DATA;
+    int i;
+
+    CHECK(r2r_context_create(&context) == R2R_OK);
+    CHECK(r2r_node_create(context, "testnode_capi", "", &node) == R2R_OK);
+    /* the node keeps the context alive. */
+    r2r_context_destroy(context);
+
+    /* errors are reported with their message. */
+    CHECK(r2r_publisher_create(node, "/r2r_capi", "std_msgs/msg/NoSuchType", &publisher) !=
+          R2R_OK);
+    CHECK(r2r_last_error(NULL, 0) > 0);
+    CHECK(r2r_node_create(NULL, "testnode_capi", "", &node) != R2R_OK);
+
+    /* a message gets through. */
+    CHECK(r2r_publisher_create(node, "/r2r_capi", "std_msgs/msg/String", &publisher) == R2R_OK);
+    CHECK(r2r_subscriber_create(node, "/r2r_capi", "std_msgs/msg/String", &subscriber) == R2R_OK);
+    CHECK(r2r_subscriber_take(subscriber, &data, &len) == R2R_ERR_NO_DATA);
+    for (i = 0; i < 100 && ret == R2R_ERR_NO_DATA; i++) {
+        CHECK(r2r_publisher_publish(publisher, HELLO, sizeof(HELLO)) == R2R_OK);
+        CHECK(r2r_node_spin_once(node, 100) == R2R_OK);
+        ret = r2r_subscriber_take(subscriber, &data, &len);
+    }
+    CHECK(ret == R2R_OK);
+    CHECK(len >= sizeof(HELLO));
+    CHECK(memcmp(data, HELLO, sizeof(HELLO)) == 0);
+    r2r_buffer_free(data, len);
+
+    /* a service call. */
+    CHECK(r2r_client_create(node, "/r2r_capi_add_two_ints", "example_interfaces/srv/AddTwoInts",
+                            &client) == R2R_OK);
+    CHECK(r2r_client_wait_for_service(node, client, 10000) == R2R_OK);
+    const char *malformed = "{ \"a\": ";
+    CHECK(r2r_client_request(client, (const uint8_t *)malformed, strlen(malformed), &response) ==
+          R2R_ERR_OTHER);
+    const char *request = "{ \"a\": 40, \"b\": 2 }";
+    CHECK(r2r_client_request(client, (const uint8_t *)request, strlen(request), &response) ==
+          R2R_OK);
+    ret = R2R_ERR_NO_DATA;
+    for (i = 0; i < 100 && ret == R2R_ERR_NO_DATA; i++) {
+        CHECK(r2r_node_spin_once(node, 100) == R2R_OK);
+        ret = r2r_response_take(response, &data, &len);
+    }
+    CHECK(ret == R2R_OK);
+    CHECK(len == strlen("{\"sum\":42}"));
+    CHECK(memcmp(data, "{\"sum\":42}", len) == 0);
+    r2r_buffer_free(data, len);
+    /* a response is taken once. */
+    CHECK(r2r_response_take(response, &data, &len) != R2R_OK);
+    r2r_response_destroy(response);
+
+    /* the handles stop working with their node. */
+    r2r_node_destroy(node);
+    CHECK(r2r_publisher_publish(publisher, HELLO, sizeof(HELLO)) != R2R_OK);
+    CHECK(r2r_subscriber_take(subscriber, &data, &len) != R2R_OK);
+    CHECK(r2r_client_request(client, (const uint8_t *)request, strlen(request), &response) !=
+          R2R_OK);
+    r2r_publisher_destroy(publisher);
+    r2r_subscriber_destroy(subscriber);
+    r2r_client_destroy(client);
+    return 0;
+}
@@ -0,0 +1,91 @@
+use std::thread;
+use std::time::Duration;
+
+use r2r;
+use r2r::std_msgs::msg::String as StringMsg;
+use r2r::test_support::{collect_n, spin_while};
+use r2r::ContextOptions;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn args(args: &[&str]) -> ContextOptions {
+    ContextOptions {
+        args: Some(args.iter().map(|a| a.to_string()).collect()),
+    }
+}
+
+#[test]
+// Contexts can be created and dropped over and over, also while others live.
+fn contexts_create_and_drop() -> Result<(), Box<dyn std::error::Error>> {
+    let long_lived = r2r::Context::create()?;
+    for i in 0..20 {
+        let ctx = r2r::Context::create()?;
+        let node = r2r::Node::create(ctx.clone(), &format!("testnode_contexts_{}", i), "")?;
+        assert!(ctx.is_valid());
+        drop(node);
+        drop(ctx);
+        assert!(long_lived.is_valid());
+    }
+    Ok(())
+}
+
+#[test]
+// Contexts can be created and dropped from several threads at once.
+fn contexts_concurrently() -> Result<(), Box<dyn std::error::Error>> {
+    let threads = (0..8)
+        .map(|i| {
+            thread::spawn(move || -> Result<(), r2r::Error> {
+                for j in 0..5 {
+                    let ctx = r2r::Context::create()?;
+                    let name = format!("testnode_contexts_concurrently_{}_{}", i, j);
+                    let node = r2r::Node::create(ctx.clone(), &name, "")?;
+                    assert_eq!(node.name()?, name);
+                    assert!(ctx.is_valid());
+                }
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+    for t in threads {
+        t.join().expect("thread panicked")?;
+    }
+    Ok(())
+}
+
+#[test]
+// The arguments of one context only apply to its nodes, and dropping
+// it leaves the nodes of the other working.
+fn contexts_are_isolated() -> Result<(), Box<dyn std::error::Error>> {
+    let remapped = r2r::Context::create_with_options(args(&[
+        "test",
+        "--ros-args",
+        "-r",
+        "__ns:=/r2r_contexts",
+    ]))?;
+    let plain = r2r::Context::create_with_options(args(&["test"]))?;
+
+    let remapped_node = r2r::Node::create(remapped.clone(), "testnode_contexts_isolated", "")?;
+    let mut node = r2r::Node::create(plain.clone(), "testnode_contexts_isolated", "")?;
+    assert_eq!(remapped_node.namespace()?, "/r2r_contexts");
+    assert_eq!(node.namespace()?, "/");
+
+    drop(remapped_node);
+    drop(remapped);
+    assert!(plain.is_valid());
+
+    let mut subscription = node.subscribe::<StringMsg>("/r2r_contexts_isolated")?;
+    let publisher = node.create_publisher::<StringMsg>("/r2r_contexts_isolated")?;
+    let subscribers = || {
+        publisher
+            .get_inter_process_subscription_count()
+            .unwrap_or(0)
+    };
+    spin_while(&mut node, || subscribers() == 0, TIMEOUT)?;
+    let msg = StringMsg {
+        data: "still here".into(),
+    };
+    publisher.publish(&msg)?;
+    let received = collect_n(&mut subscription, 1, &mut node, TIMEOUT);
+    assert_eq!(received, vec![msg]);
+    Ok(())
+}
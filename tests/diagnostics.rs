@@ -0,0 +1,44 @@
+use futures::stream::StreamExt;
+use r2r;
+use r2r::test_support::collect_n;
+use r2r::{DiagnosticLevel, DiagnosticUpdater};
+use std::time::Duration;
+
+#[test]
+// The tasks of an updater are published as one status each, named
+// after the node, until the updater is dropped.
+fn diagnostic_updater() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_diagnostics", "/r2r")?;
+    let mut stream = node
+        .subscribe_untyped("/r2r_diagnostics", "diagnostic_msgs/msg/DiagnosticArray")?
+        .filter(|msg| {
+            // the first arrays may be published before the tasks are added.
+            let complete = msg
+                .as_ref()
+                .map(|m| m["status"].as_array().map_or(0, Vec::len) == 2);
+            futures::future::ready(complete.unwrap_or(true))
+        });
+    let updater =
+        DiagnosticUpdater::with_topic(&mut node, "/r2r_diagnostics", Duration::from_millis(50))?;
+    updater.set_hardware_id("robot");
+    updater.add("battery", |status| {
+        status.summary(DiagnosticLevel::Warn, "low");
+        status.add("voltage", 10.5);
+        Ok(())
+    });
+    updater.add("camera", |_| Err(r2r::Error::RCL_RET_TIMEOUT));
+
+    let received = collect_n(&mut stream, 1, &mut node, Duration::from_secs(2));
+    let msg = received.into_iter().next().expect("no diagnostics")?;
+    let battery = &msg["status"][0];
+    assert_eq!(battery["name"], "/r2r/testnode_diagnostics: battery");
+    assert_eq!(battery["hardware_id"], "robot");
+    assert_eq!(battery["level"], 1);
+    assert_eq!(battery["values"][0]["value"], "10.5");
+    assert_eq!(msg["status"][1]["level"], 2);
+
+    assert!(updater.remove("camera"));
+    assert!(!updater.remove("camera"));
+    Ok(())
+}
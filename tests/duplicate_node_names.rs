@@ -0,0 +1,49 @@
+use r2r;
+use r2r::{DuplicateNamePolicy, NodeOptions};
+use std::time::Duration;
+
+#[test]
+fn duplicate_node_names() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let node = r2r::Node::create(ctx.clone(), "r2r_duplicate", "/dup")?;
+
+    let options = |policy| NodeOptions {
+        duplicate_name: Some(policy),
+        duplicate_check_window: Duration::from_secs(2),
+    };
+
+    match r2r::Node::create_with_options(
+        ctx.clone(),
+        "r2r_duplicate",
+        "/dup",
+        options(DuplicateNamePolicy::Fail),
+    ) {
+        Err(r2r::Error::DuplicateNodeName { name }) => assert_eq!(name, "/dup/r2r_duplicate"),
+        r => panic!("expected DuplicateNodeName, got {:?}", r.map(|_| ())),
+    }
+
+    let renamed = r2r::Node::create_with_options(
+        ctx.clone(),
+        "r2r_duplicate",
+        "/dup",
+        options(DuplicateNamePolicy::Rename),
+    )?;
+    assert_eq!(renamed.name()?, "r2r_duplicate_2");
+
+    // a different namespace is a different name.
+    let other = r2r::Node::create_with_options(
+        ctx,
+        "r2r_duplicate",
+        "/other",
+        options(DuplicateNamePolicy::Fail),
+    )?;
+    assert_eq!(other.fully_qualified_name()?, "/other/r2r_duplicate");
+
+    let names = node.get_node_names()?;
+    let count = names
+        .iter()
+        .filter(|n| n.fully_qualified_name() == "/dup/r2r_duplicate")
+        .count();
+    assert_eq!(count, 1);
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use r2r;
+use r2r::EntityKind;
+use std::time::Duration;
+
+fn handle_alive(entities: &[r2r::EntityInfo], name: &str) -> Option<bool> {
+    entities
+        .iter()
+        .find(|e| e.name == name)
+        .map(|e| e.handle_alive)
+}
+
+#[test]
+// Entities whose handles have been dropped are listed as such until
+// they are reaped, the others are left alone.
+fn reap_orphaned_entities() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_entities", "")?;
+    let publisher = node.create_publisher::<r2r::std_msgs::msg::String>("/r2r_entities_dropped")?;
+    let _kept = node.create_publisher::<r2r::std_msgs::msg::String>("/r2r_entities_kept")?;
+    let client =
+        node.create_client::<r2r::example_interfaces::srv::AddTwoInts>("/r2r_entities_client")?;
+    let timer = node.create_wall_timer(Duration::from_secs(60))?;
+
+    let entities = node.list_entities();
+    let dropped = entities
+        .iter()
+        .find(|e| e.name == "/r2r_entities_dropped")
+        .expect("publisher is listed");
+    assert_eq!(dropped.kind, EntityKind::Publisher);
+    assert!(dropped.handle_alive);
+    assert_eq!(handle_alive(&entities, "/r2r_entities_client"), Some(true));
+
+    drop(publisher);
+    drop(client);
+    drop(timer);
+    let entities = node.list_entities();
+    assert_eq!(
+        handle_alive(&entities, "/r2r_entities_dropped"),
+        Some(false)
+    );
+    assert_eq!(handle_alive(&entities, "/r2r_entities_client"), Some(false));
+    assert_eq!(handle_alive(&entities, "/r2r_entities_kept"), Some(true));
+
+    let reaped = node.reap_orphaned_entities();
+    assert_eq!(reaped.len(), 3, "unexpected reaped entities {:?}", reaped);
+    assert!(reaped.iter().any(|e| e.kind == EntityKind::Timer));
+    let entities = node.list_entities();
+    assert_eq!(handle_alive(&entities, "/r2r_entities_dropped"), None);
+    assert_eq!(handle_alive(&entities, "/r2r_entities_client"), None);
+    assert_eq!(handle_alive(&entities, "/r2r_entities_kept"), Some(true));
+    assert!(node.reap_orphaned_entities().is_empty());
+
+    // the node keeps spinning without them.
+    node.spin_once(Duration::from_millis(10));
+    Ok(())
+}
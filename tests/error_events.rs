@@ -0,0 +1,22 @@
+use r2r;
+use r2r::test_support::collect_n;
+use std::time::Duration;
+
+#[test]
+// A timer whose ticks are never awaited cannot deliver them, which
+// should show up as an error event.
+fn error_events_report_missed_timer_ticks() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_error_events", "")?;
+    node.set_error_logging(false);
+    let mut errors = node.error_events();
+    let _timer = node.create_wall_timer(Duration::from_millis(5))?;
+
+    let event = collect_n(&mut errors, 1, &mut node, Duration::from_secs(1))
+        .pop()
+        .expect("no error event");
+    assert_eq!(event.kind, r2r::EntityKind::Timer);
+    assert_eq!(event.operation, r2r::SpinOperation::Deliver);
+    assert!(matches!(event.error, r2r::Error::DeliveryFailed { .. }));
+    Ok(())
+}
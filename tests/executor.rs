@@ -0,0 +1,85 @@
+use futures::stream::StreamExt;
+use r2r;
+use r2r::example_interfaces::srv::AddTwoInts;
+use r2r::Executor;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task;
+
+// Handles entities in the default order but remembers what it saw.
+struct RecordingExecutor {
+    seen: Arc<Mutex<Vec<r2r::EntityKind>>>,
+}
+
+impl r2r::Executor for RecordingExecutor {
+    fn execute(&mut self, node: &mut r2r::Node, ready: Vec<r2r::ReadyEntity>) {
+        for entity in ready {
+            self.seen.lock().unwrap().push(entity.info().kind);
+            node.execute(entity);
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+// Services and clients handled by the worker threads of the multi
+// threaded executor should work like with spin_once.
+async fn multi_threaded_executor_handles_services() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_executor_mt", "")?;
+    let mut service = node.create_service::<AddTwoInts::Service>("/r2r_executor_mt")?;
+    let client = node.create_client::<AddTwoInts::Service>("/r2r_executor_mt")?;
+    let service_available = node.is_available(&client)?;
+
+    let done = Arc::new(AtomicBool::new(false));
+    let spin_done = done.clone();
+    let spin_handle = std::thread::spawn(move || {
+        let mut executor = r2r::MultiThreadedExecutor::new(2);
+        while !spin_done.load(Ordering::Relaxed) {
+            executor.spin_once(&mut node, Duration::from_millis(10));
+        }
+    });
+
+    task::spawn(async move {
+        while let Some(req) = service.next().await {
+            let resp = AddTwoInts::Response {
+                sum: req.message.a + req.message.b,
+            };
+            req.respond(resp).expect("could not send response");
+        }
+    });
+
+    service_available.await?;
+    for i in 0..10 {
+        let resp = client.request(&AddTwoInts::Request { a: i, b: 5 })?.await?;
+        assert_eq!(resp.sum, i + 5);
+    }
+
+    done.store(true, Ordering::Relaxed);
+    spin_handle.join().unwrap();
+    Ok(())
+}
+
+#[test]
+// A custom executor gets the readiness information of the entities.
+fn custom_executor_sees_ready_entities() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_executor_custom", "")?;
+    let publisher = node.create_publisher::<r2r::std_msgs::msg::String>("/r2r_executor_custom")?;
+    let _stream = node.subscribe::<r2r::std_msgs::msg::String>("/r2r_executor_custom")?;
+    let _timer = node.create_wall_timer(Duration::from_millis(5))?;
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let mut executor = RecordingExecutor { seen: seen.clone() };
+    for _ in 0..100 {
+        publisher.publish(&r2r::std_msgs::msg::String {
+            data: "hello".into(),
+        })?;
+        executor.spin_once(&mut node, Duration::from_millis(10));
+    }
+
+    let seen = seen.lock().unwrap();
+    assert!(seen.contains(&r2r::EntityKind::Subscription));
+    assert!(seen.contains(&r2r::EntityKind::Timer));
+    Ok(())
+}
@@ -0,0 +1,32 @@
+use r2r;
+use r2r::test_support::spin_while;
+use std::time::Duration;
+
+#[test]
+// Flushing should report each reliable publisher once, and not fail
+// when there are subscribers that have to acknowledge.
+fn flush_reliable_publishers() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_flush_publishers", "")?;
+    let publisher = node.create_publisher::<r2r::std_msgs::msg::String>("/r2r_flush_publishers")?;
+    let _stream = node.subscribe::<r2r::std_msgs::msg::String>("/r2r_flush_publishers")?;
+
+    spin_while(
+        &mut node,
+        || publisher.get_inter_process_subscription_count().unwrap() != 1,
+        Duration::from_secs(2),
+    )?;
+
+    publisher.publish(&r2r::std_msgs::msg::String {
+        data: "going offline".into(),
+    })?;
+    node.set_flush_grace_period(Duration::from_millis(10));
+    let report = node.flush_publishers(Duration::from_secs(5));
+
+    assert_eq!(report.len(), 1);
+    let (topic, status) = &report[0];
+    assert_eq!(topic, "/r2r_flush_publishers");
+    // some rmw implementations cannot tell.
+    assert!(status == &r2r::FlushStatus::Flushed || status == &r2r::FlushStatus::Unknown);
+    Ok(())
+}
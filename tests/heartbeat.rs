@@ -0,0 +1,36 @@
+use r2r;
+use r2r::test_support::collect_n;
+use r2r::{Heartbeat, HeartbeatEvent, HeartbeatMonitor};
+use std::time::Duration;
+
+#[test]
+// The monitor sees the publisher come up, go away and come back.
+fn heartbeat_missed_and_restarted() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_heartbeat", "")?;
+    let topic = "/r2r_heartbeat";
+    let mut events = HeartbeatMonitor::watch(&mut node, topic, Duration::from_millis(300))?;
+
+    let heartbeat = Heartbeat::publish(&mut node, topic, 20.0)?;
+    let received = collect_n(&mut events, 1, &mut node, Duration::from_secs(5));
+    assert!(matches!(received[..], [HeartbeatEvent::Alive { .. }]));
+    assert!(heartbeat.count() > 0);
+
+    drop(heartbeat);
+    let received = collect_n(&mut events, 1, &mut node, Duration::from_secs(5));
+    match received[..] {
+        [HeartbeatEvent::Missed {
+            gap,
+            missed_beats: 0,
+        }] => {
+            assert!(gap > Duration::from_millis(300))
+        }
+        _ => panic!("expected a timeout, got {:?}", received),
+    }
+
+    // a new publisher starts counting from the beginning.
+    let _heartbeat = Heartbeat::publish(&mut node, topic, 20.0)?;
+    let received = collect_n(&mut events, 1, &mut node, Duration::from_secs(5));
+    assert_eq!(received, vec![HeartbeatEvent::Alive { counter: 1 }]);
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use r2r;
+use r2r::std_msgs::msg::Int32;
+use r2r::test_support::spin_while;
+use r2r::{DeliverResult, LogSeverity, MessageInfo, MessageSink, SubscriptionOptions};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Is always full.
+struct Full;
+
+impl MessageSink<(Int32, MessageInfo)> for Full {
+    fn try_deliver(&self, _: (Int32, MessageInfo)) -> DeliverResult {
+        DeliverResult::DroppedFull
+    }
+}
+
+#[test]
+// What r2r logs for the nodes of a context goes to the handler of that
+// context.
+fn context_log_handler() -> Result<(), Box<dyn std::error::Error>> {
+    let logged = Arc::new(Mutex::new(vec![]));
+    let ctx = r2r::Context::create()?;
+    let handler_logged = logged.clone();
+    ctx.set_log_handler(Some(Arc::new(move |severity, msg: &str| {
+        handler_logged
+            .lock()
+            .unwrap()
+            .push((severity, msg.to_owned()))
+    })));
+    let other_ctx = r2r::Context::create()?;
+    let mut other_node = r2r::Node::create(other_ctx, "testnode_log_handler_other", "")?;
+    let mut node = r2r::Node::create(ctx, "testnode_log_handler", "")?;
+
+    let topic = "/r2r_log_handler";
+    node.subscribe_with_sink::<Int32>(topic, SubscriptionOptions::default(), Full)?;
+    other_node.subscribe_with_sink::<Int32>(topic, SubscriptionOptions::default(), Full)?;
+    let publisher = node.create_publisher::<Int32>(topic)?;
+    let subscribers = || {
+        publisher
+            .get_inter_process_subscription_count()
+            .unwrap_or(0)
+    };
+    spin_while(&mut node, || subscribers() < 2, Duration::from_secs(2))?;
+
+    publisher.publish(&Int32 { data: 1 })?;
+    let count = || logged.lock().unwrap().len();
+    spin_while(&mut node, || count() == 0, Duration::from_secs(2))?;
+    // the other node logs elsewhere.
+    other_node.spin_once(Duration::from_millis(100));
+
+    let logged = logged.lock().unwrap();
+    assert_eq!(logged.len(), 1);
+    assert_eq!(logged[0].0, LogSeverity::Warn);
+    assert!(logged[0].1.contains(topic), "{}", logged[0].1);
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use r2r;
+use r2r::{FileSinkOptions, LogSinkConfig};
+
+// Switching sinks at runtime: lines logged while the ring buffer is
+// enabled can be dumped, and the file sink gets every line.
+#[test]
+fn log_sinks_switch_at_runtime() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join(format!("r2r_log_sinks_{}.log", std::process::id()));
+    let logger = "r2r_log_sinks";
+
+    r2r::log_info!(logger, "before any sink");
+    assert!(r2r::log_ring_buffer().is_none());
+
+    r2r::configure_log_sinks(&LogSinkConfig {
+        stdout: false,
+        file: Some(FileSinkOptions::new(&path)),
+        ring_buffer: Some(3),
+    })?;
+    for i in 0..5 {
+        r2r::log_warn!(logger, "line {}", i);
+    }
+    let dump = r2r::log_ring_buffer().expect("ring buffer enabled").dump();
+    assert_eq!(dump.len(), 3);
+    assert!(dump[0].starts_with("[WARN]"));
+    assert!(dump[0].ends_with("[r2r_log_sinks]: line 2"));
+    assert!(dump[2].ends_with("line 4"));
+
+    // growing the buffer keeps what is in it.
+    r2r::configure_log_sinks(&LogSinkConfig {
+        ring_buffer: Some(10),
+        ..r2r::log_sink_config()
+    })?;
+    r2r::log_error!(logger, "line 5");
+    assert_eq!(r2r::log_ring_buffer().unwrap().dump().len(), 4);
+
+    r2r::configure_log_sinks(&LogSinkConfig::default())?;
+    assert!(r2r::log_ring_buffer().is_none());
+    r2r::log_info!(logger, "after the file sink");
+
+    let written = std::fs::read_to_string(&path)?;
+    assert_eq!(written.lines().count(), 6);
+    assert!(!written.contains("before any sink"));
+    assert!(!written.contains("after the file sink"));
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
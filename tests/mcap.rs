@@ -0,0 +1,108 @@
+#![cfg(feature = "mcap")]
+
+use r2r;
+use r2r::test_support::{collect_n, spin_while};
+use r2r::{McapReader, McapRecorder};
+use std::time::Duration;
+
+// Records three topics of different types from live publishers, then
+// replays the file onto the same topics and checks what arrives.
+#[test]
+fn mcap_record_and_replay() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join(format!("r2r_mcap_{}.mcap", std::process::id()));
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_mcap", "")?;
+
+    let strings = node.create_publisher::<r2r::std_msgs::msg::String>("/r2r_mcap_string")?;
+    let ints = node.create_publisher::<r2r::std_msgs::msg::Int32>("/r2r_mcap_int")?;
+    let points = node.create_publisher::<r2r::geometry_msgs::msg::Point>("/r2r_mcap_point")?;
+
+    let mut recorder = McapRecorder::create(&path)?;
+    recorder.record(&mut node, "/r2r_mcap_string", "std_msgs/msg/String")?;
+    recorder.record(&mut node, "/r2r_mcap_int", "std_msgs/msg/Int32")?;
+    recorder.record(&mut node, "/r2r_mcap_point", "geometry_msgs/msg/Point")?;
+    spin_while(
+        &mut node,
+        || {
+            strings.get_inter_process_subscription_count().unwrap_or(0) == 0
+                || ints.get_inter_process_subscription_count().unwrap_or(0) == 0
+                || points.get_inter_process_subscription_count().unwrap_or(0) == 0
+        },
+        Duration::from_secs(2),
+    )?;
+
+    for i in 0..3 {
+        strings.publish(&r2r::std_msgs::msg::String {
+            data: format!("msg {}", i),
+        })?;
+        ints.publish(&r2r::std_msgs::msg::Int32 { data: i })?;
+        points.publish(&r2r::geometry_msgs::msg::Point {
+            x: i as f64,
+            y: 2.0,
+            z: 3.0,
+        })?;
+    }
+    let mut written = 0;
+    spin_while(
+        &mut node,
+        || {
+            written += recorder.write_pending().unwrap();
+            written < 9
+        },
+        Duration::from_secs(2),
+    )?;
+    recorder.finish()?;
+
+    let reader = McapReader::open(&path)?;
+    assert_eq!(reader.messages().len(), 9);
+    let topics = reader.topics();
+    assert_eq!(topics["/r2r_mcap_string"], "std_msgs/msg/String");
+    assert_eq!(topics["/r2r_mcap_point"], "geometry_msgs/msg/Point");
+    let ints_read = reader.typed::<r2r::std_msgs::msg::Int32>("/r2r_mcap_int")?;
+    assert_eq!(
+        ints_read.iter().map(|m| m.data).collect::<Vec<_>>(),
+        vec![0, 1, 2]
+    );
+
+    // replay onto the live topics.
+    let mut string_sub = node.subscribe::<r2r::std_msgs::msg::String>("/r2r_mcap_string")?;
+    let mut int_sub = node.subscribe::<r2r::std_msgs::msg::Int32>("/r2r_mcap_int")?;
+    let mut point_sub = node.subscribe::<r2r::geometry_msgs::msg::Point>("/r2r_mcap_point")?;
+    let replay = reader.replay(&mut node)?;
+    spin_while(
+        &mut node,
+        || {
+            ["/r2r_mcap_string", "/r2r_mcap_int", "/r2r_mcap_point"]
+                .iter()
+                .any(|t| {
+                    replay
+                        .publisher(t)
+                        .and_then(|p| p.get_inter_process_subscription_count().ok())
+                        .unwrap_or(0)
+                        == 0
+                })
+        },
+        Duration::from_secs(2),
+    )?;
+    assert_eq!(replay.publish_all()?, 9);
+
+    let received = collect_n(&mut string_sub, 3, &mut node, Duration::from_secs(2));
+    assert_eq!(
+        received.iter().map(|m| m.data.as_str()).collect::<Vec<_>>(),
+        vec!["msg 0", "msg 1", "msg 2"]
+    );
+    let received = collect_n(&mut int_sub, 3, &mut node, Duration::from_secs(2));
+    assert_eq!(
+        received.iter().map(|m| m.data).collect::<Vec<_>>(),
+        vec![0, 1, 2]
+    );
+    let received = collect_n(&mut point_sub, 3, &mut node, Duration::from_secs(2));
+    assert_eq!(
+        received.iter().map(|m| m.x).collect::<Vec<_>>(),
+        vec![0.0, 1.0, 2.0]
+    );
+    assert!(received.iter().all(|m| m.y == 2.0 && m.z == 3.0));
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
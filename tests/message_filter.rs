@@ -0,0 +1,122 @@
+use r2r;
+use r2r::test_support::collect_n;
+use r2r::{FilterExpr, FilterMode, MessageFilter, SubscriptionOptions};
+use std::time::Duration;
+
+// Publishes 0..10 on `topic` once the subscriptions are matched.
+fn publish_numbers(node: &mut r2r::Node, topic: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let publisher = node.create_publisher::<r2r::std_msgs::msg::Int32>(topic)?;
+    for _ in 0..10 {
+        node.spin_once(Duration::from_millis(10));
+    }
+    for data in 0..10 {
+        publisher.publish(&r2r::std_msgs::msg::Int32 { data })?;
+    }
+    Ok(())
+}
+
+#[test]
+// Whichever mode is used, only matching messages are received.
+fn filter_expression() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_filter_expression", "")?;
+    let topic = "/r2r_filter_expression";
+    let expr = FilterExpr::field("data")
+        .ge(3)
+        .and(FilterExpr::field("data").lt(6));
+    let mut sub = node.subscribe_filtered::<r2r::std_msgs::msg::Int32>(
+        topic,
+        SubscriptionOptions::default(),
+        expr,
+    )?;
+    match sub.mode() {
+        FilterMode::ContentFilter { expression } => {
+            assert!(!["foxy", "galactic"].contains(&r2r::ros_distro()));
+            assert_eq!(expression, "(data >= %0) AND (data < %1)");
+        }
+        FilterMode::Local { .. } => (),
+    }
+
+    publish_numbers(&mut node, topic)?;
+    let received = collect_n(&mut sub, 3, &mut node, Duration::from_secs(2));
+    assert_eq!(
+        received.iter().map(|m| m.data).collect::<Vec<_>>(),
+        vec![3, 4, 5]
+    );
+    Ok(())
+}
+
+#[test]
+// Closures are always evaluated locally, also on top of an expression.
+fn filter_predicate() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_filter_predicate", "")?;
+    let topic = "/r2r_filter_predicate";
+    let mut odd = node.subscribe_filtered::<r2r::std_msgs::msg::Int32>(
+        topic,
+        SubscriptionOptions::default(),
+        MessageFilter::predicate(|m: &r2r::std_msgs::msg::Int32| m.data % 2 == 1),
+    )?;
+    assert!(matches!(odd.mode(), FilterMode::Local { .. }));
+    let filter = MessageFilter::expression(FilterExpr::field("data").gt(4))
+        .with_predicate(|m: &r2r::std_msgs::msg::Int32| m.data % 2 == 0);
+    let mut large_even = node.subscribe_filtered::<r2r::std_msgs::msg::Int32>(
+        topic,
+        SubscriptionOptions::default(),
+        filter,
+    )?;
+
+    publish_numbers(&mut node, topic)?;
+    let received = collect_n(&mut odd, 5, &mut node, Duration::from_secs(2));
+    assert_eq!(
+        received.iter().map(|m| m.data).collect::<Vec<_>>(),
+        vec![1, 3, 5, 7, 9]
+    );
+    let received = collect_n(&mut large_even, 2, &mut node, Duration::from_secs(2));
+    assert_eq!(
+        received.iter().map(|m| m.data).collect::<Vec<_>>(),
+        vec![6, 8]
+    );
+    Ok(())
+}
+
+#[test]
+// An expression the rmw cannot use falls back to local filtering
+// instead of failing.
+fn filter_downgrade() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_filter_downgrade", "")?;
+    let topic = "/r2r_filter_downgrade";
+    // not a field of Int32.
+    let unknown_field = FilterExpr::field("no_such_field").eq(1);
+    let mut none = node.subscribe_filtered::<r2r::std_msgs::msg::Int32>(
+        topic,
+        SubscriptionOptions::default(),
+        unknown_field.or(FilterExpr::field("data").eq(-1)),
+    )?;
+    assert!(matches!(none.mode(), FilterMode::Local { .. }));
+    // quotes cannot be passed to the rmw at all.
+    let quoted = FilterExpr::field("data")
+        .eq("'")
+        .or(FilterExpr::field("data").eq(7));
+    let mut seven = node.subscribe_filtered::<r2r::std_msgs::msg::Int32>(
+        topic,
+        SubscriptionOptions::default(),
+        quoted,
+    )?;
+    assert!(matches!(seven.mode(), FilterMode::Local { .. }));
+
+    let invalid = node.subscribe_filtered::<r2r::std_msgs::msg::Int32>(
+        topic,
+        SubscriptionOptions::default(),
+        FilterExpr::field("data = 1 OR data").eq(2),
+    );
+    assert!(matches!(invalid, Err(r2r::Error::InvalidFilter { .. })));
+
+    publish_numbers(&mut node, topic)?;
+    let received = collect_n(&mut seven, 1, &mut node, Duration::from_secs(2));
+    assert_eq!(received.iter().map(|m| m.data).collect::<Vec<_>>(), vec![7]);
+    let received = collect_n(&mut none, 1, &mut node, Duration::from_millis(200));
+    assert!(received.is_empty());
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use r2r;
+use r2r::std_msgs::msg::Int32;
+use r2r::test_support::spin_while;
+use r2r::{DeliverResult, MessageInfo, MessageSink, SubscriptionOptions};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Takes three messages, then closes.
+struct ThreeMessages(Arc<Mutex<Vec<i32>>>);
+
+impl MessageSink<(Int32, MessageInfo)> for ThreeMessages {
+    fn try_deliver(&self, (msg, _): (Int32, MessageInfo)) -> DeliverResult {
+        let mut received = self.0.lock().unwrap();
+        if received.len() == 3 {
+            return DeliverResult::Closed;
+        }
+        received.push(msg.data);
+        DeliverResult::Accepted
+    }
+}
+
+#[test]
+// Messages land in the sink of the application, and closing the sink
+// destroys the subscription.
+fn subscription_delivers_to_sink() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_message_sinks", "")?;
+    let topic = "/r2r_message_sinks";
+    let received = Arc::new(Mutex::new(vec![]));
+    node.subscribe_with_sink::<Int32>(
+        topic,
+        SubscriptionOptions::default(),
+        ThreeMessages(received.clone()),
+    )?;
+    let publisher = node.create_publisher::<Int32>(topic)?;
+    let subscribers = || {
+        publisher
+            .get_inter_process_subscription_count()
+            .unwrap_or(0)
+    };
+    spin_while(&mut node, || subscribers() == 0, Duration::from_secs(2))?;
+
+    for data in 0..3 {
+        publisher.publish(&Int32 { data })?;
+        let taken = || received.lock().unwrap().len() as i32;
+        spin_while(&mut node, || taken() <= data, Duration::from_secs(2))?;
+    }
+    // the fourth message closes the sink.
+    publisher.publish(&Int32 { data: 3 })?;
+    spin_while(&mut node, || subscribers() > 0, Duration::from_secs(2))?;
+    assert_eq!(*received.lock().unwrap(), vec![0, 1, 2]);
+    assert_eq!(subscribers(), 0);
+    Ok(())
+}
+
+// Keeps everything it is given.
+struct Collect<T>(Arc<Mutex<Vec<T>>>);
+
+impl<T: Send> MessageSink<T> for Collect<T> {
+    fn try_deliver(&self, item: T) -> DeliverResult {
+        self.0.lock().unwrap().push(item);
+        DeliverResult::Accepted
+    }
+}
+
+#[test]
+// Native, untyped and serialized subscriptions deliver to sinks too.
+fn other_subscriptions_deliver_to_sink() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_message_sinks_other", "")?;
+    let native = Arc::new(Mutex::new(vec![]));
+    let untyped = Arc::new(Mutex::new(vec![]));
+    let serialized = Arc::new(Mutex::new(vec![]));
+    node.subscribe_native_with_sink::<Int32>(
+        "/r2r_message_sinks_native",
+        SubscriptionOptions::default(),
+        Collect(native.clone()),
+    )?;
+    node.subscribe_untyped_with_sink(
+        "/r2r_message_sinks_untyped",
+        "std_msgs/msg/Int32",
+        SubscriptionOptions::default(),
+        Collect(untyped.clone()),
+    )?;
+    node.subscribe_serialized_with_sink(
+        "/r2r_message_sinks_serialized",
+        &r2r::MessageTypeSupport::for_type_name("std_msgs/msg/Int32")?,
+        SubscriptionOptions::default(),
+        Collect(serialized.clone()),
+    )?;
+    let mut publishers = vec![];
+    for kind in &["native", "untyped", "serialized"] {
+        let topic = format!("/r2r_message_sinks_{}", kind);
+        publishers.push(node.create_publisher::<Int32>(&topic)?);
+    }
+    let unmatched = || {
+        publishers
+            .iter()
+            .any(|p| p.get_inter_process_subscription_count().unwrap_or(0) == 0)
+    };
+    spin_while(&mut node, unmatched, Duration::from_secs(2))?;
+
+    for p in &publishers {
+        p.publish(&Int32 { data: 7 })?;
+    }
+    let pending = || {
+        native.lock().unwrap().is_empty()
+            || untyped.lock().unwrap().is_empty()
+            || serialized.lock().unwrap().is_empty()
+    };
+    spin_while(&mut node, pending, Duration::from_secs(2))?;
+    assert_eq!(native.lock().unwrap()[0].data, 7);
+    let value = untyped.lock().unwrap().remove(0)?;
+    assert_eq!(value["data"], 7);
+    assert!(!serialized.lock().unwrap()[0].is_empty());
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use r2r;
+use r2r::test_support::spin_while;
+use r2r::NodeOptions;
+use std::time::Duration;
+
+#[test]
+// A minimal node has the publisher that was created for it and nothing
+// else, in particular no rosout publisher and no services.
+fn minimal_node_graph() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut observer = r2r::Node::create(ctx.clone(), "testnode_minimal_observer", "")?;
+    let mut node = r2r::Node::create_with_options(
+        ctx.clone(),
+        "testnode_minimal",
+        "",
+        NodeOptions::minimal(),
+    )?;
+    let _publisher = node.create_publisher::<r2r::std_msgs::msg::String>("/r2r_minimal_node")?;
+
+    spin_while(
+        &mut observer,
+        || {
+            !node
+                .get_publisher_names_and_types_by_node("testnode_minimal", "/")
+                .map(|p| p.contains_key("/r2r_minimal_node"))
+                .unwrap_or(false)
+        },
+        Duration::from_secs(5),
+    )?;
+    let publishers = observer.get_publisher_names_and_types_by_node("testnode_minimal", "/")?;
+    assert_eq!(
+        publishers.len(),
+        1,
+        "unexpected publishers {:?}",
+        publishers
+    );
+    assert_eq!(
+        publishers["/r2r_minimal_node"],
+        vec!["std_msgs/msg/String".to_owned()]
+    );
+    let services = observer.get_service_names_and_types_by_node("testnode_minimal", "/")?;
+    assert!(services.is_empty(), "unexpected services {:?}", services);
+
+    // a node with default options publishes its log.
+    let _default = r2r::Node::create(ctx, "testnode_minimal_default", "")?;
+    spin_while(
+        &mut observer,
+        || {
+            !node
+                .get_publisher_names_and_types_by_node("testnode_minimal_default", "/")
+                .map(|p| p.contains_key("/rosout"))
+                .unwrap_or(false)
+        },
+        Duration::from_secs(5),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use r2r;
+use r2r::std_msgs::msg::String as StringMsg;
+use r2r::test_support::{collect_n, spin_while};
+use r2r::QosProfile;
+use std::time::Duration;
+
+#[test]
+// Messages are relayed while spinning, transformed or as they are, and
+// counted in the statistics of the mirror.
+fn mirror() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_mirror", "")?;
+    let qos = QosProfile::default().keep_last(100);
+    let mut upper = node.subscribe::<StringMsg>("/r2r_mirror_upper")?;
+    let mut copy = node.subscribe::<StringMsg>("/r2r_mirror_copy")?;
+    let upper_mirror = node.mirror::<StringMsg, StringMsg>(
+        "/r2r_mirror_in",
+        "/r2r_mirror_upper",
+        qos.clone(),
+        qos.clone(),
+        |msg| {
+            if msg.data.is_empty() {
+                None
+            } else {
+                Some(StringMsg {
+                    data: msg.data.to_uppercase(),
+                })
+            }
+        },
+    )?;
+    let copy_mirror = node.mirror_passthrough::<StringMsg>(
+        "/r2r_mirror_in",
+        "/r2r_mirror_copy",
+        qos.clone(),
+        qos.clone(),
+    )?;
+    let publisher = node.create_publisher_with_options::<StringMsg>(
+        "/r2r_mirror_in",
+        r2r::PublisherOptions {
+            qos: Some(qos),
+            ..Default::default()
+        },
+    )?;
+    spin_while(
+        &mut node,
+        || publisher.get_inter_process_subscription_count().unwrap() != 2,
+        Duration::from_secs(2),
+    )?;
+
+    let sent = ["a", "", "b"];
+    for data in &sent {
+        publisher.publish(&StringMsg {
+            data: data.to_string(),
+        })?;
+    }
+    let timeout = Duration::from_secs(2);
+    let copied = collect_n(&mut copy, 3, &mut node, timeout);
+    let uppercased = collect_n(&mut upper, 2, &mut node, timeout);
+    assert_eq!(
+        copied.iter().map(|m| m.data.as_str()).collect::<Vec<_>>(),
+        sent
+    );
+    assert_eq!(
+        uppercased
+            .iter()
+            .map(|m| m.data.as_str())
+            .collect::<Vec<_>>(),
+        ["A", "B"]
+    );
+
+    assert_eq!(upper_mirror.received().messages, 3);
+    assert_eq!(upper_mirror.sent().messages, 2);
+    assert_eq!(upper_mirror.sent().dropped, 1);
+    let (received, sent) = (copy_mirror.received(), copy_mirror.sent());
+    assert_eq!((received.messages, sent.messages), (3, 3));
+    assert_eq!(received.bytes, sent.bytes);
+    let metrics = node.metrics();
+    assert_eq!(metrics.received["/r2r_mirror_in"].messages, 6);
+    assert_eq!(metrics.published["/r2r_mirror_copy"].messages, 3);
+
+    // nothing is relayed after the mirror is dropped.
+    drop(copy_mirror);
+    node.spin_once(Duration::from_millis(10));
+    publisher.publish(&StringMsg { data: "c".into() })?;
+    assert_eq!(collect_n(&mut upper, 1, &mut node, timeout).len(), 1);
+    assert!(collect_n(&mut copy, 1, &mut node, Duration::from_millis(200)).is_empty());
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use r2r;
+use r2r::test_support::{collect_n, spin_while};
+use r2r::{QosProfile, SubscriptionOptions};
+use std::time::Duration;
+
+fn with_qos(qos: QosProfile) -> SubscriptionOptions {
+    SubscriptionOptions {
+        qos: Some(qos),
+        ..Default::default()
+    }
+}
+
+fn data(msgs: &[r2r::std_msgs::msg::String]) -> Vec<&str> {
+    msgs.iter().map(|msg| msg.data.as_str()).collect()
+}
+
+// Three subscriptions with different profiles on one topic of one node
+// each get every message on their own stream.
+#[test]
+fn multiple_subscriptions_same_topic() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_multiple_subscriptions", "")?;
+    let topic = "/r2r_multiple_subscriptions";
+
+    let mut reliable = node.subscribe_with_options::<r2r::std_msgs::msg::String>(
+        topic,
+        with_qos(QosProfile::default().keep_last(10).transient_local()),
+    )?;
+    let mut best_effort = node.subscribe_with_options::<r2r::std_msgs::msg::String>(
+        topic,
+        with_qos(QosProfile::sensor_data()),
+    )?;
+    let mut default = node.subscribe::<r2r::std_msgs::msg::String>(topic)?;
+
+    let publisher = node.create_publisher_with_options::<r2r::std_msgs::msg::String>(
+        topic,
+        r2r::PublisherOptions {
+            qos: Some(QosProfile::default().keep_last(10).transient_local()),
+            ..Default::default()
+        },
+    )?;
+    spin_while(
+        &mut node,
+        || {
+            publisher
+                .get_inter_process_subscription_count()
+                .unwrap_or(0)
+                < 3
+        },
+        Duration::from_secs(2),
+    )?;
+
+    for i in 0..3 {
+        publisher.publish(&r2r::std_msgs::msg::String {
+            data: format!("msg {}", i),
+        })?;
+    }
+    let expected = vec!["msg 0", "msg 1", "msg 2"];
+    let received = collect_n(&mut reliable, 3, &mut node, Duration::from_secs(2));
+    assert_eq!(data(&received), expected);
+    let received = collect_n(&mut default, 3, &mut node, Duration::from_secs(2));
+    assert_eq!(data(&received), expected);
+    // best effort may lose messages, but not within one process.
+    let received = collect_n(&mut best_effort, 3, &mut node, Duration::from_secs(2));
+    assert_eq!(data(&received), expected);
+
+    // dropping one subscription leaves the others alone.
+    drop(best_effort);
+    node.spin_once(Duration::from_millis(10));
+    publisher.publish(&r2r::std_msgs::msg::String {
+        data: "after drop".into(),
+    })?;
+    let received = collect_n(&mut reliable, 1, &mut node, Duration::from_secs(2));
+    assert_eq!(data(&received), vec!["after drop"]);
+    let received = collect_n(&mut default, 1, &mut node, Duration::from_secs(2));
+    assert_eq!(data(&received), vec!["after drop"]);
+
+    // a late transient local subscription gets the history of the
+    // publisher, the existing ones do not get it again.
+    let mut late = node.subscribe_with_options::<r2r::std_msgs::msg::String>(
+        topic,
+        with_qos(QosProfile::default().keep_last(10).transient_local()),
+    )?;
+    let received = collect_n(&mut late, 4, &mut node, Duration::from_secs(2));
+    assert_eq!(
+        data(&received),
+        vec!["msg 0", "msg 1", "msg 2", "after drop"]
+    );
+    let received = collect_n(&mut reliable, 1, &mut node, Duration::from_millis(200));
+    assert!(received.is_empty());
+    Ok(())
+}
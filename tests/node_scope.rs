@@ -0,0 +1,70 @@
+use futures::stream::StreamExt;
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::example_interfaces::srv::AddTwoInts;
+use r2r::std_msgs::msg::String as StringMsg;
+use r2r::test_support::{collect_n, first_of};
+use std::time::Duration;
+
+#[test]
+// Dropping a scope destroys its entities and fails the operations
+// still pending on them, while the rest of the node lives on.
+fn node_scope_cleans_up_on_drop() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_node_scope", "")?;
+    let timeout = Duration::from_secs(10);
+
+    // a service and an action server that never answer.
+    let mut requests = node.create_service::<AddTwoInts::Service>("/r2r_node_scope_service")?;
+    let mut goal_requests = node.create_action_server::<Fibonacci::Action>("/r2r_node_scope")?;
+    let kept = node.create_publisher::<StringMsg>("/r2r_node_scope_kept")?;
+    let outside = node.list_entities().len();
+
+    let (publisher, mut subscription, response, result, _goal, _request) = {
+        let mut scope = node.scope().drop_timeout(Duration::from_secs(1));
+        let publisher = scope.create_publisher::<StringMsg>("/r2r_node_scope_topic")?;
+        let subscription = scope.subscribe::<StringMsg>("/r2r_node_scope_topic")?;
+        let client = scope.create_client::<AddTwoInts::Service>("/r2r_node_scope_service")?;
+        let action_client = scope.create_action_client::<Fibonacci::Action>("/r2r_node_scope")?;
+        let _timer = scope.create_wall_timer(Duration::from_millis(100))?;
+        let available = scope.node().is_available(&client)?;
+        first_of(vec![Box::pin(available)], scope.node(), timeout)?.1?;
+        let available = scope.node().is_available(&action_client)?;
+        first_of(vec![Box::pin(available)], scope.node(), timeout)?.1?;
+        assert_eq!(scope.node().list_entities().len(), outside + 5);
+
+        let response = client.request(&AddTwoInts::Request { a: 1, b: 2 })?;
+        let request = collect_n(&mut requests, 1, scope.node(), timeout);
+        let goal = action_client.send_goal_request(Fibonacci::Goal { order: 5 })?;
+        let req = collect_n(&mut goal_requests, 1, scope.node(), timeout)
+            .into_iter()
+            .next()
+            .expect("no goal request");
+        let (server_goal, _cancel_requests) = req.accept()?;
+        let (_, result, _) = first_of(vec![Box::pin(goal)], scope.node(), timeout)?.1?;
+        (
+            publisher,
+            subscription,
+            response,
+            result,
+            server_goal,
+            request,
+        )
+    };
+
+    let entities = node.list_entities();
+    assert_eq!(entities.len(), outside);
+    assert!(entities
+        .iter()
+        .all(|e| !e.name.contains("/r2r_node_scope_topic")));
+    assert!(publisher.publish(&StringMsg::default()).is_err());
+    kept.publish(&StringMsg::default())?;
+    let (_, next) = first_of(vec![subscription.next()], &mut node, timeout)?;
+    assert!(next.is_none());
+    let (_, response) = first_of(vec![Box::pin(response)], &mut node, timeout)?;
+    assert!(response.is_err());
+    let (_, result) = first_of(vec![Box::pin(result)], &mut node, timeout)?;
+    // told apart from a server that never answered.
+    assert!(matches!(result, Err(r2r::Error::ClientDestroyed)));
+    Ok(())
+}
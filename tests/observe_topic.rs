@@ -0,0 +1,53 @@
+use futures::future::FutureExt;
+use futures::stream::StreamExt;
+use r2r;
+use r2r::test_support::spin_while;
+use std::time::Duration;
+
+#[test]
+// Observing a topic should pick up the type and qos of the publisher
+// and deliver its messages as json.
+fn observe_topic_finds_type_and_qos() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_observe_topic", "")?;
+    let publisher = node.create_publisher::<r2r::std_msgs::msg::String>("/r2r_observe_topic")?;
+
+    let (topic_type, qos, mut stream) =
+        node.observe_topic("/r2r_observe_topic", Duration::from_secs(5))?;
+    assert_eq!(topic_type, "std_msgs/msg/String");
+    assert_eq!(qos.reliability, r2r::ReliabilityPolicy::Reliable);
+    assert_eq!(qos.durability, r2r::DurabilityPolicy::Volatile);
+
+    let mut received = None;
+    spin_while(
+        &mut node,
+        || {
+            if let Some(Some(msg)) = stream.next().now_or_never() {
+                received = Some(msg);
+                return false;
+            }
+            publisher
+                .publish(&r2r::std_msgs::msg::String {
+                    data: "hello".into(),
+                })
+                .unwrap();
+            true
+        },
+        Duration::from_secs(2),
+    )?;
+    assert_eq!(received.transpose()?, Some(serde_json::json!({ "data": "hello" })));
+    Ok(())
+}
+
+#[test]
+fn observe_missing_topic_times_out() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_observe_missing_topic", "")?;
+    match node.observe_topic("/r2r_observe_topic_missing", Duration::from_millis(100)) {
+        Err(r2r::Error::TopicNotFound { topic }) => {
+            assert_eq!(topic, "/r2r_observe_topic_missing");
+        }
+        _ => panic!("expected TopicNotFound"),
+    }
+    Ok(())
+}
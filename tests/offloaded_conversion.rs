@@ -0,0 +1,41 @@
+use r2r;
+use r2r::test_support::collect_n;
+use r2r::SubscriptionOptions;
+use std::time::Duration;
+
+#[test]
+// Messages converted on the conversion thread arrive complete and in
+// the order they were published.
+fn offloaded_conversion_keeps_order() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_offloaded_conversion", "")?;
+    let options = SubscriptionOptions {
+        offload_conversion: true,
+        ..Default::default()
+    };
+    let mut sub = node.subscribe_with_options::<r2r::std_msgs::msg::String>(
+        "/r2r_offloaded_conversion",
+        options,
+    )?;
+    let publisher =
+        node.create_publisher::<r2r::std_msgs::msg::String>("/r2r_offloaded_conversion")?;
+
+    // let the subscription get matched.
+    for _ in 0..10 {
+        node.spin_once(Duration::from_millis(10));
+    }
+
+    let large = "x".repeat(1 << 20);
+    for i in 0..8 {
+        publisher.publish(&r2r::std_msgs::msg::String {
+            data: format!("{}{}", i, large),
+        })?;
+    }
+    let received = collect_n(&mut sub, 8, &mut node, Duration::from_secs(5));
+    assert_eq!(received.len(), 8);
+    for (i, msg) in received.iter().enumerate() {
+        assert_eq!(msg.data, format!("{}{}", i, large));
+    }
+    assert!(sub.is_empty());
+    Ok(())
+}
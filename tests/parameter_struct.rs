@@ -0,0 +1,90 @@
+use futures::{FutureExt, StreamExt};
+use r2r;
+use r2r::ParameterValue;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct PidCfg {
+    p: f64,
+    i: f64,
+    d: f64,
+    limits: Option<Vec<f64>>,
+}
+
+#[test]
+// A struct set on a node can be read back, and only touches the
+// parameters below its prefix.
+fn parameter_struct_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_parameter_struct", "")?;
+    node.params
+        .lock()
+        .unwrap()
+        .insert("controller.rate".into(), ParameterValue::Integer(50));
+
+    let pid = PidCfg {
+        p: 1.0,
+        i: 0.5,
+        d: 0.0,
+        limits: Some(vec![-1.0, 1.0]),
+    };
+    node.set_parameter_struct("controller.pid", &pid)?;
+    {
+        let params = node.params.lock().unwrap();
+        assert_eq!(params["controller.pid.p"], ParameterValue::Double(1.0));
+        assert_eq!(
+            params["controller.pid.limits"],
+            ParameterValue::DoubleArray(vec![-1.0, 1.0])
+        );
+        assert_eq!(params["controller.rate"], ParameterValue::Integer(50));
+    }
+    assert_eq!(node.get_parameter_struct::<PidCfg>("controller.pid")?, pid);
+
+    node.params
+        .lock()
+        .unwrap()
+        .insert("controller.pid.i".into(), ParameterValue::Bool(true));
+    match node.get_parameter_struct::<PidCfg>("controller.pid") {
+        Err(r2r::Error::InvalidParameter { name, .. }) => assert_eq!(name, "controller.pid.i"),
+        r => panic!("unexpected result {:?}", r),
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Sinks {
+    stdout: i64,
+}
+
+#[test]
+// Setting a struct is validated like a set_parameters request, and
+// the changes show up on the parameter event stream.
+fn parameter_struct_events() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_parameter_struct_events", "")?;
+    let (_handler, events) = node.make_parameter_handler()?;
+    let mut events = Box::pin(events);
+
+    let pid = PidCfg {
+        p: 1.0,
+        i: 0.5,
+        d: 0.0,
+        limits: None,
+    };
+    node.set_parameter_struct("pid", &pid)?;
+    let changed: Vec<_> = std::iter::from_fn(|| events.next().now_or_never().flatten()).collect();
+    assert!(changed.contains(&("pid.p".to_owned(), ParameterValue::Double(1.0))));
+    assert!(changed.contains(&("pid.i".to_owned(), ParameterValue::Double(0.5))));
+
+    // the same values again change nothing.
+    node.set_parameter_struct("pid", &pid)?;
+    assert_eq!(events.next().now_or_never(), None);
+
+    // stdout must be a bool.
+    assert!(node
+        .set_parameter_struct("log_sinks", &Sinks { stdout: 1 })
+        .is_err());
+    assert!(!node.params.lock().unwrap().contains_key("log_sinks.stdout"));
+    assert_eq!(events.next().now_or_never(), None);
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use r2r;
+use r2r::std_msgs::msg::String as StringMsg;
+use r2r::test_support::{collect_n, spin_while};
+use r2r::{PlaybackClock, PlaybackItem, PlaybackOptions, PlaybackScheduler, SkippedItems};
+use std::time::{Duration, Instant};
+
+fn items(topic: &str) -> r2r::Result<Vec<PlaybackItem>> {
+    // out of order, they are sorted by stamp.
+    [(11_000, "d"), (10_000, "a"), (10_100, "b"), (10_200, "c")]
+        .iter()
+        .map(|(ms, data)| {
+            PlaybackItem::typed(
+                Duration::from_millis(*ms),
+                topic,
+                &StringMsg {
+                    data: data.to_string(),
+                },
+            )
+        })
+        .collect()
+}
+
+fn data(msgs: Vec<StringMsg>) -> Vec<String> {
+    msgs.into_iter().map(|m| m.data).collect()
+}
+
+#[test]
+// With a manual clock, items are published when the position passes
+// them, seeking forward drops the items in between and seeking back
+// publishes them again.
+fn playback_manual() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_playback_manual", "")?;
+    let topic = "/r2r_playback_manual";
+    let mut sub = node.subscribe::<StringMsg>(topic)?;
+    let playback = PlaybackScheduler::start(
+        &mut node,
+        items(topic)?,
+        PlaybackOptions {
+            clock: PlaybackClock::Manual,
+            start_paused: true,
+            ..Default::default()
+        },
+    )?;
+    let mut progress = playback.progress();
+    let publisher = playback.publisher(topic).unwrap();
+    spin_while(
+        &mut node,
+        || publisher.get_inter_process_subscription_count().unwrap() != 1,
+        Duration::from_secs(2),
+    )?;
+    let timeout = Duration::from_secs(1);
+    let short = Duration::from_millis(100);
+    assert!(collect_n(&mut sub, 1, &mut node, short).is_empty());
+
+    playback.resume();
+    assert_eq!(data(collect_n(&mut sub, 1, &mut node, timeout)), ["a"]);
+    playback.advance(Duration::from_millis(150))?;
+    assert_eq!(data(collect_n(&mut sub, 1, &mut node, timeout)), ["b"]);
+    assert!(collect_n(&mut sub, 1, &mut node, short).is_empty());
+
+    playback.seek(Duration::from_millis(900));
+    assert!(collect_n(&mut sub, 1, &mut node, short).is_empty());
+    playback.advance(Duration::from_millis(100))?;
+    assert_eq!(data(collect_n(&mut sub, 1, &mut node, timeout)), ["d"]);
+    let status = playback.status();
+    assert_eq!((status.published, status.dropped), (3, 1));
+    assert_eq!(status.duration, Duration::from_secs(1));
+    assert!(status.finished);
+
+    playback.seek(Duration::ZERO);
+    assert_eq!(data(collect_n(&mut sub, 1, &mut node, timeout)), ["a"]);
+    assert!(!playback.status().finished);
+
+    let reported = collect_n(&mut progress, 1, &mut node, timeout);
+    assert_eq!(reported.len(), 1);
+    assert_eq!(reported[0].duration, Duration::from_secs(1));
+    Ok(())
+}
+
+#[test]
+// With a scaled clock, items keep their relative timing, sped up.
+fn playback_scaled() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_playback_scaled", "")?;
+    let topic = "/r2r_playback_scaled";
+    let mut sub = node.subscribe::<StringMsg>(topic)?;
+    let playback = PlaybackScheduler::start(
+        &mut node,
+        items(topic)?,
+        PlaybackOptions {
+            clock: PlaybackClock::Scaled(4.0),
+            skipped: SkippedItems::Burst,
+            start_paused: true,
+            ..Default::default()
+        },
+    )?;
+    assert!(playback.advance(Duration::from_millis(1)).is_err());
+    let publisher = playback.publisher(topic).unwrap();
+    spin_while(
+        &mut node,
+        || publisher.get_inter_process_subscription_count().unwrap() != 1,
+        Duration::from_secs(2),
+    )?;
+
+    let started = Instant::now();
+    playback.resume();
+    assert_eq!(
+        data(collect_n(&mut sub, 4, &mut node, Duration::from_secs(2))),
+        ["a", "b", "c", "d"]
+    );
+    // one second recorded, played four times faster.
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(240), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_millis(900), "{:?}", elapsed);
+
+    // bursts the skipped items instead of dropping them.
+    playback.pause();
+    playback.seek(Duration::ZERO);
+    playback.resume();
+    playback.seek(Duration::from_millis(950));
+    assert_eq!(
+        data(collect_n(&mut sub, 3, &mut node, Duration::from_secs(1))),
+        ["a", "b", "c"]
+    );
+    assert_eq!(playback.status().dropped, 0);
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use r2r;
+use r2r::geometry_msgs::msg::PoseStamped;
+use r2r::test_support::collect_n;
+use r2r::{Clock, ClockType, DropReason, PublishOutcome, PublisherOptions};
+use std::time::Duration;
+
+const TOPIC: &str = "/r2r_publisher_gates";
+
+#[test]
+// Messages published too fast or with an old stamp are dropped, and
+// counted in the publisher statistics.
+fn publisher_gates() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_publisher_gates", "")?;
+    let mut stream = node.subscribe::<PoseStamped>(TOPIC)?;
+    let publisher = node
+        .create_publisher_with_options::<PoseStamped>(
+            TOPIC,
+            PublisherOptions {
+                stats: true,
+                ..Default::default()
+            },
+        )?
+        .rate_limited(1.0)?
+        .drop_if_older_than(Duration::from_secs(10))?;
+    publisher.add_validator(|msg: &PoseStamped| {
+        if msg.header.frame_id.is_empty() {
+            Err("no frame".into())
+        } else {
+            Ok(())
+        }
+    });
+    for _ in 0..10 {
+        node.spin_once(Duration::from_millis(10));
+    }
+
+    let mut clock = Clock::create(ClockType::RosTime)?;
+    let mut pose = PoseStamped::default();
+    pose.header.frame_id = "map".into();
+    pose.header.stamp = Clock::to_builtin_time(&clock.get_now()?);
+    let old = PoseStamped {
+        header: r2r::std_msgs::msg::Header {
+            frame_id: "map".into(),
+            stamp: Default::default(),
+        },
+        ..Default::default()
+    };
+
+    assert_eq!(
+        publisher.try_publish(&old)?,
+        PublishOutcome::Dropped(DropReason::TooOld)
+    );
+    assert_eq!(publisher.try_publish(&pose)?, PublishOutcome::Sent);
+    for _ in 0..3 {
+        assert_eq!(
+            publisher.try_publish(&pose)?,
+            PublishOutcome::Dropped(DropReason::RateLimited)
+        );
+    }
+    // dropped messages are not validated.
+    let mut unframed = pose.clone();
+    unframed.header.frame_id.clear();
+    publisher.publish(&unframed)?;
+
+    let received = collect_n(&mut stream, 2, &mut node, Duration::from_millis(500));
+    assert_eq!(received, vec![pose]);
+    let dropped = publisher.dropped();
+    assert_eq!((dropped.too_old, dropped.rate_limited), (1, 4));
+    let stats = publisher.stats().expect("stats enabled");
+    assert_eq!((stats.messages, stats.dropped), (1, 5));
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use r2r;
+use r2r::geometry_msgs::msg::Twist;
+use r2r::test_support::collect_n;
+use r2r::{NativeMsg, NativeMsgView, ValidationPolicy};
+use std::time::Duration;
+
+const MAX_SPEED: f64 = 1.0;
+
+fn twist(x: f64) -> Twist {
+    let mut msg = Twist::default();
+    msg.linear.x = x;
+    msg
+}
+
+#[test]
+// Velocity commands are clamped to the speed limit, commands that are
+// not a number are rejected, on both publish paths.
+fn publisher_validation() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_publisher_validation", "")?;
+    let mut stream = node.subscribe::<Twist>("/r2r_publisher_validation")?;
+    let publisher = node.create_publisher::<Twist>("/r2r_publisher_validation")?;
+    publisher.add_validator(|msg: &Twist| {
+        if msg.linear.x.is_nan() {
+            Err("linear.x is not a number".into())
+        } else {
+            Ok(())
+        }
+    });
+    publisher.add_mutating_validator(|msg: &mut Twist| {
+        msg.linear.x = msg.linear.x.max(-MAX_SPEED).min(MAX_SPEED);
+        Ok(())
+    });
+    // applies to all clones of the publisher.
+    let clone = publisher.clone();
+    clone.add_native_validator(|msg: NativeMsgView<'_, Twist>| {
+        if msg.angular.z == 0.0 {
+            Ok(())
+        } else {
+            Err("turning is not allowed".into())
+        }
+    });
+    for _ in 0..10 {
+        node.spin_once(Duration::from_millis(10));
+    }
+
+    assert!(matches!(
+        publisher.publish(&twist(f64::NAN)),
+        Err(r2r::Error::ValidationFailed { .. })
+    ));
+    let mut turning = twist(0.5);
+    turning.angular.z = 1.0;
+    assert!(matches!(
+        clone.publish(&turning),
+        Err(r2r::Error::ValidationFailed { .. })
+    ));
+    assert!(matches!(
+        publisher.publish_native(&NativeMsg::from(&twist(f64::NAN))),
+        Err(r2r::Error::ValidationFailed { .. })
+    ));
+    publisher.publish(&twist(5.0))?;
+    publisher.publish_native(&NativeMsg::from(&twist(-3.0)))?;
+    clone.publish(&twist(0.5))?;
+
+    // a message that fails is published anyway with this policy.
+    publisher.set_validation_policy(ValidationPolicy::LogAndSend);
+    clone.publish(&turning)?;
+
+    let received = collect_n(&mut stream, 4, &mut node, Duration::from_secs(2));
+    assert_eq!(
+        received.iter().map(|msg| msg.linear.x).collect::<Vec<_>>(),
+        vec![MAX_SPEED, -MAX_SPEED, 0.5, 0.5]
+    );
+    assert_eq!(received[3].angular.z, 1.0);
+    Ok(())
+}
+
+#[test]
+// A retained publisher validates too, and remembers the corrected
+// message rather than the rejected or original one.
+fn retained_publisher_validation() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_retained_publisher_validation", "")?;
+    let topic = "/r2r_retained_publisher_validation";
+    let publisher =
+        node.create_retained_publisher::<Twist>(topic, r2r::QosProfile::default(), 1)?;
+    publisher.add_validator(|msg: &Twist| {
+        if msg.linear.x.is_nan() {
+            Err("linear.x is not a number".into())
+        } else {
+            Ok(())
+        }
+    });
+    publisher.add_mutating_validator(|msg: &mut Twist| {
+        msg.linear.x = msg.linear.x.max(-MAX_SPEED).min(MAX_SPEED);
+        Ok(())
+    });
+
+    publisher.publish(&twist(5.0))?;
+    assert!(matches!(
+        publisher.publish(&twist(f64::NAN)),
+        Err(r2r::Error::ValidationFailed { .. })
+    ));
+    node.spin_once(Duration::from_millis(10));
+
+    let mut stream = node.subscribe::<Twist>(topic)?;
+    let received = collect_n(&mut stream, 1, &mut node, Duration::from_secs(2));
+    assert_eq!(
+        received.iter().map(|msg| msg.linear.x).collect::<Vec<_>>(),
+        vec![MAX_SPEED]
+    );
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use r2r;
+use r2r::{
+    DurabilityPolicy, ParameterValue, PublisherOptions, QosOverridePolicy, QosOverridingOptions,
+    QosProfile, ReliabilityPolicy, SubscriptionOptions,
+};
+
+const PARAMS: &str = r#"
+/testnode_qos_overrides:
+  ros__parameters:
+    qos_overrides:
+      /r2r_qos_overrides:
+        publisher:
+          reliability: best_effort
+          durability: transient_local
+          depth: 3
+        subscription:
+          reliability: best_effort
+"#;
+
+#[test]
+// Overrides from a parameter file replace the profile in code, and the
+// policies that are not overridden are declared with their values.
+fn qos_overrides_from_params_file() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_qos_overrides", "")?;
+    node.restore_parameters(PARAMS)?;
+
+    let _publisher = node.create_publisher_with_options::<r2r::std_msgs::msg::String>(
+        "/r2r_qos_overrides",
+        PublisherOptions {
+            qos: Some(QosProfile::default()),
+            qos_overrides: QosOverridePolicy::Declare(QosOverridingOptions::default()),
+            ..Default::default()
+        },
+    )?;
+    let info = node.get_publishers_info_by_topic("/r2r_qos_overrides")?;
+    assert_eq!(info.len(), 1);
+    let qos = info[0].qos.clone().expect("publisher qos");
+    assert_eq!(qos.reliability, ReliabilityPolicy::BestEffort);
+    assert_eq!(qos.durability, DurabilityPolicy::TransientLocal);
+    {
+        let params = node.params.lock().unwrap();
+        assert_eq!(
+            params["qos_overrides./r2r_qos_overrides.publisher.history"],
+            ParameterValue::String("keep_last".into())
+        );
+        assert_eq!(
+            params["qos_overrides./r2r_qos_overrides.publisher.depth"],
+            ParameterValue::Integer(3)
+        );
+    }
+
+    // without Declare the parameters are ignored.
+    let _plain = node.create_publisher::<r2r::std_msgs::msg::String>("/r2r_qos_overrides_plain")?;
+    assert!(!node
+        .params
+        .lock()
+        .unwrap()
+        .keys()
+        .any(|k| k.starts_with("qos_overrides./r2r_qos_overrides_plain")));
+
+    // the validation callback sees the overridden profile.
+    let strict = QosOverridingOptions::default().with_validation(|qos| {
+        if qos.reliability == ReliabilityPolicy::BestEffort {
+            Err("subscription must be reliable".into())
+        } else {
+            Ok(())
+        }
+    });
+    let result = node.subscribe_with_options::<r2r::std_msgs::msg::String>(
+        "/r2r_qos_overrides",
+        SubscriptionOptions {
+            qos: Some(QosProfile::default()),
+            qos_overrides: QosOverridePolicy::Declare(strict),
+            ..Default::default()
+        },
+    );
+    assert!(matches!(result, Err(r2r::Error::InvalidQosProfile { .. })));
+    Ok(())
+}
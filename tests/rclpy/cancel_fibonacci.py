@@ -0,0 +1,48 @@
+"""Sends a Fibonacci goal to the action server named on the command line,
+cancels it once it is accepted and prints the status and the result of the
+goal as json."""
+
+import json
+import sys
+
+import rclpy
+from example_interfaces.action import Fibonacci
+from rclpy.action import ActionClient
+
+TIMEOUT = 10.0
+
+
+def main():
+    rclpy.init()
+    node = rclpy.create_node("r2r_rclpy_cancel_client")
+    client = ActionClient(node, Fibonacci, sys.argv[1])
+    if not client.wait_for_server(timeout_sec=TIMEOUT):
+        sys.exit("no action server")
+
+    goal = client.send_goal_async(Fibonacci.Goal(order=10))
+    rclpy.spin_until_future_complete(node, goal, timeout_sec=TIMEOUT)
+    handle = goal.result()
+    if handle is None or not handle.accepted:
+        sys.exit("goal not accepted")
+
+    cancel = handle.cancel_goal_async()
+    rclpy.spin_until_future_complete(node, cancel, timeout_sec=TIMEOUT)
+    result = handle.get_result_async()
+    rclpy.spin_until_future_complete(node, result, timeout_sec=TIMEOUT)
+    if result.result() is None:
+        sys.exit("no result")
+
+    print(
+        json.dumps(
+            {
+                "status": result.result().status,
+                "sequence": list(result.result().result.sequence),
+            }
+        )
+    )
+    node.destroy_node()
+    rclpy.shutdown()
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,42 @@
+"""Serves the Fibonacci action named on the command line until one goal has
+finished, publishing the sequence as feedback after each step."""
+
+import sys
+import time
+
+import rclpy
+from example_interfaces.action import Fibonacci
+from rclpy.action import ActionServer
+
+TIMEOUT = 30.0
+
+
+def main():
+    rclpy.init()
+    node = rclpy.create_node("r2r_rclpy_fibonacci_server")
+    finished = []
+
+    def execute(goal_handle):
+        sequence = [0, 1]
+        for _ in range(1, goal_handle.request.order):
+            sequence.append(sequence[-1] + sequence[-2])
+            goal_handle.publish_feedback(Fibonacci.Feedback(sequence=sequence))
+            time.sleep(0.1)
+        goal_handle.succeed()
+        finished.append(goal_handle)
+        return Fibonacci.Result(sequence=sequence)
+
+    ActionServer(node, Fibonacci, sys.argv[1], execute)
+    deadline = time.monotonic() + TIMEOUT
+    while not finished and time.monotonic() < deadline:
+        rclpy.spin_once(node, timeout_sec=0.1)
+    # answer the result request of the client.
+    deadline = time.monotonic() + 1.0
+    while time.monotonic() < deadline:
+        rclpy.spin_once(node, timeout_sec=0.1)
+    node.destroy_node()
+    rclpy.shutdown()
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,81 @@
+use futures::stream::StreamExt;
+use futures::FutureExt;
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// A command running tests/rclpy/<script>, or None when rclpy cannot be
+// imported, e.g. when only the C libraries of ROS are installed.
+fn rclpy_script(script: &str) -> Option<Command> {
+    let python = std::env::var("PYTHON").unwrap_or_else(|_| "python3".to_owned());
+    let available = Command::new(&python)
+        .args(&["-c", "import rclpy"])
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+    if !available {
+        eprintln!("skipped, rclpy is not available");
+        return None;
+    }
+    let mut command = Command::new(python);
+    command.arg(
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/rclpy")
+            .join(script),
+    );
+    Some(command)
+}
+
+#[test]
+// An rclpy client that cancels its goal sees the canceled status
+// together with the partial result the server sent.
+fn rclpy_client_sees_canceled_partial_result() -> Result<(), Box<dyn std::error::Error>> {
+    let mut client = match rclpy_script("cancel_fibonacci.py") {
+        Some(client) => client,
+        None => return Ok(()),
+    };
+
+    let done = Arc::new(AtomicBool::new(false));
+    let server_done = done.clone();
+    let server = std::thread::spawn(move || -> Result<(), r2r::Error> {
+        let ctx = r2r::Context::create()?;
+        let mut node = r2r::Node::create(ctx, "testnode_rclpy_cancel_server", "")?;
+        let mut goal_requests =
+            node.create_action_server::<Fibonacci::Action>("/r2r_rclpy_cancel")?;
+        let mut goal = None;
+        while !server_done.load(Ordering::SeqCst) {
+            node.spin_once(Duration::from_millis(10));
+            if let Some(Some(request)) = goal_requests.next().now_or_never() {
+                goal = Some(request.accept()?);
+            }
+            if let Some((g, cancel)) = goal.as_mut() {
+                if let Some(Some(cancel_request)) = cancel.next().now_or_never() {
+                    cancel_request.accept();
+                    g.canceled(Fibonacci::Result {
+                        sequence: vec![0, 1, 1],
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    });
+
+    let output = client.arg("/r2r_rclpy_cancel").output()?;
+    done.store(true, Ordering::SeqCst);
+    server.join().expect("server panicked")?;
+    assert!(
+        output.status.success(),
+        "the rclpy client failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let reply: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    // action_msgs/msg/GoalStatus STATUS_CANCELED
+    assert_eq!(reply["status"], 5);
+    assert_eq!(reply["sequence"], serde_json::json!([0, 1, 1]));
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use futures::executor::block_on;
+use futures::future::FutureExt;
+use r2r;
+use r2r::example_interfaces::srv::AddTwoInts;
+use r2r::test_support::first_of;
+use r2r::Dependency;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[test]
+// The node becomes ready once the service and the publisher it
+// depends on show up, and reports progress while waiting.
+fn node_ready_when_dependencies_found() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_readiness", "")?;
+    node.declare_dependency(Dependency::Service("/r2r_readiness_service".into()));
+    node.declare_dependency(Dependency::PublisherOn("/r2r_readiness_topic".into()));
+
+    let progress = Arc::new(Mutex::new(Vec::new()));
+    let progress_cb = progress.clone();
+    let mut ready = node.wait_until_ready_with_progress(Duration::from_secs(10), move |missing| {
+        progress_cb.lock().unwrap().push(missing.len());
+    });
+
+    for _ in 0..10 {
+        node.spin_once(Duration::from_millis(10));
+    }
+    assert!((&mut ready).now_or_never().is_none());
+
+    let _service = node.create_service::<AddTwoInts::Service>("/r2r_readiness_service")?;
+    let _publisher = node.create_publisher::<r2r::std_msgs::msg::String>("/r2r_readiness_topic")?;
+
+    let (_, result) = first_of(vec![&mut ready], &mut node, Duration::from_secs(5))?;
+    assert!(matches!(result, Ok(())));
+    assert!(node.missing_dependencies()?.is_empty());
+    assert_eq!(progress.lock().unwrap().first(), Some(&2));
+    Ok(())
+}
+
+#[test]
+fn node_not_ready_times_out() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_readiness_timeout", "")?;
+    node.declare_dependency(Dependency::ActionServer("/r2r_readiness_missing".into()));
+    let ready = node.wait_until_ready(Duration::from_millis(100));
+    for _ in 0..30 {
+        node.spin_once(Duration::from_millis(10));
+    }
+    match block_on(ready) {
+        Err(r2r::Error::DependenciesNotReady { missing }) => {
+            assert_eq!(missing, vec!["action server /r2r_readiness_missing".to_owned()]);
+        }
+        r => panic!("expected DependenciesNotReady, got {:?}", r),
+    }
+    Ok(())
+}
@@ -0,0 +1,168 @@
+#![cfg(feature = "replay")]
+
+use futures::stream::StreamExt;
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::example_interfaces::srv::AddTwoInts;
+use r2r::{ActionRecording, ExactMatch, FieldSubsetMatch, GoalStatus, ServiceRecording};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tokio::task;
+
+const SERVICE: &str = "/r2r_replay_add";
+const ACTION: &str = "/r2r_replay_fibonacci";
+
+fn spin(node: Arc<Mutex<r2r::Node>>, done: Arc<AtomicBool>) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        while !done.load(Ordering::Relaxed) {
+            node.lock().unwrap().spin_once(Duration::from_millis(10));
+        }
+    })
+}
+
+// Records calls to a real server and a real action server.
+async fn record(service_file: &Path, action_file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let node = r2r::Node::create(ctx, "testnode_replay_record", "")?;
+    let node = Arc::new(Mutex::new(node));
+    let (client, action_client, service_available, server_available) = {
+        let mut node = node.lock().unwrap();
+        let mut requests = node.create_service::<AddTwoInts::Service>(SERVICE)?;
+        task::spawn(async move {
+            while let Some(req) = requests.next().await {
+                let sum = req.message.a + req.message.b;
+                req.respond(AddTwoInts::Response { sum })
+                    .expect("could not respond");
+            }
+        });
+        let server = node
+            .action_server_builder::<Fibonacci::Action>(ACTION)
+            .on_execute(|goal| async move {
+                let mut sequence = vec![0, 1];
+                for _ in 0..goal.goal.order {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    sequence.push(sequence[sequence.len() - 1] + sequence[sequence.len() - 2]);
+                    goal.publish_feedback(Fibonacci::Feedback {
+                        sequence: sequence.clone(),
+                    })?;
+                }
+                Ok(Fibonacci::Result { sequence })
+            })
+            .build()?;
+        task::spawn(server);
+        let client = node.create_client::<AddTwoInts::Service>(SERVICE)?;
+        let action_client = node.create_action_client::<Fibonacci::Action>(ACTION)?;
+        let service_available = node.is_available(&client)?;
+        let server_available = node.is_available(&action_client)?;
+        (
+            r2r::RecordingClient::new(client, SERVICE),
+            r2r::RecordingActionClient::new(action_client, ACTION),
+            service_available,
+            server_available,
+        )
+    };
+    let done = Arc::new(AtomicBool::new(false));
+    let spin_handle = spin(node.clone(), done.clone());
+    service_available.await?;
+    server_available.await?;
+
+    let response = client.request(&AddTwoInts::Request { a: 1, b: 2 })?.await?;
+    assert_eq!(response.sum, 3);
+    client
+        .request(&AddTwoInts::Request { a: 10, b: 20 })?
+        .await?;
+    let (status, _) = action_client
+        .send_goal(Fibonacci::Goal { order: 3 })?
+        .await?;
+    assert_eq!(status, GoalStatus::Succeeded);
+
+    let services = client.recording();
+    assert_eq!(services.exchanges().len(), 2);
+    services.save(service_file)?;
+    let actions = action_client.recording();
+    assert_eq!(actions.goals()[0].feedback.len(), 3);
+    actions.save(action_file)?;
+
+    done.store(true, Ordering::Relaxed);
+    spin_handle.join().unwrap();
+    Ok(())
+}
+
+// Answers from the recordings instead.
+async fn replay(service_file: &Path, action_file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let services = ServiceRecording::<AddTwoInts::Service>::load(service_file)?;
+    let actions = ActionRecording::<Fibonacci::Action>::load(action_file)?;
+    let recorded = actions.goals()[0].clone();
+
+    let ctx = r2r::Context::create()?;
+    let node = r2r::Node::create(ctx, "testnode_replay_replay", "")?;
+    let node = Arc::new(Mutex::new(node));
+    let (client, action_client, service_available, server_available) = {
+        let mut node = node.lock().unwrap();
+        // only the first operand has to match.
+        let service =
+            r2r::ReplayServiceServer::create(&mut node, services, FieldSubsetMatch::new(&["a"]))?;
+        task::spawn(service.run());
+        let server = r2r::ReplayActionServer::create(&mut node, actions, ExactMatch)?;
+        task::spawn(server.run());
+        let client = node.create_client::<AddTwoInts::Service>(SERVICE)?;
+        let action_client = node.create_action_client::<Fibonacci::Action>(ACTION)?;
+        let service_available = node.is_available(&client)?;
+        let server_available = node.is_available(&action_client)?;
+        (client, action_client, service_available, server_available)
+    };
+    let done = Arc::new(AtomicBool::new(false));
+    let spin_handle = spin(node.clone(), done.clone());
+    service_available.await?;
+    server_available.await?;
+
+    // the recorded sum, not the real one.
+    let response = client
+        .request(&AddTwoInts::Request { a: 10, b: 0 })?
+        .await?;
+    assert_eq!(response.sum, 30);
+
+    let mut goal = action_client
+        .send_goal(Fibonacci::Goal { order: 3 })?
+        .await?;
+    let feedback: Vec<_> = goal.feedback().expect("feedback taken").collect().await;
+    let (status, result) = goal.result().expect("result taken").await?;
+    assert_eq!(status, recorded.status);
+    assert_eq!(result, recorded.result);
+    assert_eq!(
+        feedback,
+        recorded
+            .feedback
+            .iter()
+            .map(|(_, f)| f.clone())
+            .collect::<Vec<_>>()
+    );
+
+    // goals that were not recorded are rejected.
+    assert!(matches!(
+        action_client.send_goal(Fibonacci::Goal { order: 4 })?.await,
+        Err(r2r::Error::GoalRejected)
+    ));
+
+    done.store(true, Ordering::Relaxed);
+    spin_handle.join().unwrap();
+    Ok(())
+}
+
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("{}_{}", name, std::process::id()))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tokio_record_and_replay() -> Result<(), Box<dyn std::error::Error>> {
+    let service_file = temp_file("r2r_replay_service");
+    let action_file = temp_file("r2r_replay_action");
+    record(&service_file, &action_file).await?;
+    replay(&service_file, &action_file).await?;
+    std::fs::remove_file(service_file)?;
+    std::fs::remove_file(action_file)?;
+    Ok(())
+}
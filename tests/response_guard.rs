@@ -0,0 +1,51 @@
+use futures::future::FutureExt;
+use futures::stream::StreamExt;
+use r2r;
+use r2r::example_interfaces::srv::AddTwoInts;
+use r2r::test_support::first_of;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[test]
+// Two clients of the same service both number their requests from one.
+// Each should get the response to its own request, also when the
+// response to the other client carries the same sequence number.
+fn clients_only_take_their_own_responses() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut server = r2r::Node::create(ctx.clone(), "testnode_response_guard_server", "")?;
+    let mut node = r2r::Node::create(ctx, "testnode_response_guard", "")?;
+    let mut service = server.create_service::<AddTwoInts::Service>("/r2r_response_guard")?;
+    let first = node.create_client::<AddTwoInts::Service>("/r2r_response_guard")?;
+    let second = node.create_client::<AddTwoInts::Service>("/r2r_response_guard")?;
+
+    let done = Arc::new(AtomicBool::new(false));
+    let server_done = done.clone();
+    let server_handle = std::thread::spawn(move || {
+        while !server_done.load(Ordering::Relaxed) {
+            server.spin_once(Duration::from_millis(10));
+            while let Some(Some(request)) = service.next().now_or_never() {
+                let sum = request.message.a + request.message.b;
+                request.respond(AddTwoInts::Response { sum }).unwrap();
+            }
+        }
+    });
+
+    for client in &[&first, &second] {
+        let available = node.is_available(*client)?;
+        first_of(vec![available], &mut node, TIMEOUT)?.1?;
+    }
+
+    let first_response = first.request(&AddTwoInts::Request { a: 1, b: 1 })?;
+    let second_response = second.request(&AddTwoInts::Request { a: 10, b: 10 })?;
+    let (_, first_response) = first_of(vec![first_response], &mut node, TIMEOUT)?;
+    let (_, second_response) = first_of(vec![second_response], &mut node, TIMEOUT)?;
+    assert_eq!(first_response?.sum, 2);
+    assert_eq!(second_response?.sum, 20);
+
+    done.store(true, Ordering::Relaxed);
+    server_handle.join().unwrap();
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use r2r;
+use r2r::test_support::collect_n;
+use r2r::{ResubscribeEvent, ResubscribeOptions};
+use std::time::Duration;
+
+// Publishes until the subscription gets a message.
+fn wait_for_message(
+    node: &mut r2r::Node,
+    publisher: &r2r::Publisher<r2r::std_msgs::msg::String>,
+    subscription: &mut r2r::Subscription<r2r::std_msgs::msg::String>,
+) -> bool {
+    let msg = r2r::std_msgs::msg::String {
+        data: "hello".into(),
+    };
+    (0..50).any(|_| {
+        publisher.publish(&msg).is_ok()
+            && !collect_n(subscription, 1, node, Duration::from_millis(100)).is_empty()
+    })
+}
+
+#[test]
+// A publisher that goes away and comes back is reported as lost and
+// then resubscribed, after which messages arrive again.
+fn resubscribe_after_publisher_restart() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx.clone(), "testnode_resubscribe", "")?;
+    let topic = "/r2r_resubscribe";
+    let mut events = node.enable_resubscribe(ResubscribeOptions {
+        backoff: Duration::from_millis(10),
+        max_attempts: 3,
+    });
+    let mut subscription = node.subscribe::<r2r::std_msgs::msg::String>(topic)?;
+    let timeout = Duration::from_secs(5);
+
+    let mut publisher_node = r2r::Node::create(ctx.clone(), "testnode_resubscribe_pub", "")?;
+    let publisher = publisher_node.create_publisher::<r2r::std_msgs::msg::String>(topic)?;
+    assert!(wait_for_message(&mut node, &publisher, &mut subscription));
+
+    drop(publisher);
+    drop(publisher_node);
+    let received = collect_n(&mut events, 1, &mut node, timeout);
+    assert_eq!(
+        received,
+        vec![ResubscribeEvent::PublishersLost {
+            topic: topic.to_owned()
+        }]
+    );
+
+    let mut publisher_node = r2r::Node::create(ctx, "testnode_resubscribe_pub", "")?;
+    let publisher = publisher_node.create_publisher::<r2r::std_msgs::msg::String>(topic)?;
+    let received = collect_n(&mut events, 1, &mut node, timeout);
+    assert_eq!(
+        received,
+        vec![ResubscribeEvent::Resubscribed {
+            topic: topic.to_owned(),
+            attempt: 1
+        }]
+    );
+    // the existing stream keeps working.
+    assert!(wait_for_message(&mut node, &publisher, &mut subscription));
+    Ok(())
+}
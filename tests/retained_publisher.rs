@@ -0,0 +1,43 @@
+use r2r;
+use r2r::test_support::collect_n;
+use r2r::QosProfile;
+use std::time::Duration;
+
+// Publishes before anyone subscribes and checks that a subscriber
+// created later still gets the message.
+fn late_subscriber_receives(qos: QosProfile, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, name, "")?;
+    let topic = format!("/{}", name);
+    let publisher = node.create_retained_publisher::<r2r::std_msgs::msg::String>(&topic, qos, 1)?;
+
+    publisher.publish(&r2r::std_msgs::msg::String {
+        data: "old".into(),
+    })?;
+    publisher.publish(&r2r::std_msgs::msg::String {
+        data: "latest".into(),
+    })?;
+    node.spin_once(Duration::from_millis(10));
+
+    let mut stream = node.subscribe::<r2r::std_msgs::msg::String>(&topic)?;
+    let received = collect_n(&mut stream, 1, &mut node, Duration::from_secs(2));
+    // only the last message is retained.
+    assert_eq!(
+        received.iter().map(|msg| msg.data.as_str()).collect::<Vec<_>>(),
+        vec!["latest"]
+    );
+    Ok(())
+}
+
+#[test]
+fn retained_publisher_volatile() -> Result<(), Box<dyn std::error::Error>> {
+    late_subscriber_receives(QosProfile::default().volatile(), "r2r_retained_volatile")
+}
+
+#[test]
+fn retained_publisher_transient_local() -> Result<(), Box<dyn std::error::Error>> {
+    late_subscriber_receives(
+        QosProfile::default().transient_local(),
+        "r2r_retained_transient_local",
+    )
+}
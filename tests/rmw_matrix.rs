@@ -0,0 +1,216 @@
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::example_interfaces::srv::AddTwoInts;
+use r2r::std_msgs::msg::String as StringMsg;
+use r2r::test_support::{collect_n, first_of, spin_while, RmwMatrix};
+use r2r::{GoalStatus, MessageTypeSupport, PublisherOptions, QosProfile, SubscriptionOptions};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+// A message gets through with each of the QoS presets on both sides.
+fn rmw_pub_sub_qos_presets() -> Result<(), Box<dyn std::error::Error>> {
+    RmwMatrix::new().run("rmw_pub_sub_qos_presets", |_| {
+        let ctx = r2r::Context::create()?;
+        let mut node = r2r::Node::create(ctx, "testnode_rmw_pub_sub", "")?;
+        let presets = vec![
+            ("default", QosProfile::default()),
+            ("sensor_data", QosProfile::sensor_data()),
+            ("parameters", QosProfile::parameters()),
+            ("services_default", QosProfile::services_default()),
+            ("parameter_events", QosProfile::parameter_events()),
+            ("system_default", QosProfile::system_default()),
+        ];
+        for (name, qos) in presets {
+            let topic = format!("/r2r_rmw_pub_sub_{}", name);
+            let mut subscription = node.subscribe_with_options::<StringMsg>(
+                &topic,
+                SubscriptionOptions {
+                    qos: Some(qos.clone()),
+                    ..Default::default()
+                },
+            )?;
+            let publisher = node.create_publisher_with_options::<StringMsg>(
+                &topic,
+                PublisherOptions {
+                    qos: Some(qos),
+                    ..Default::default()
+                },
+            )?;
+            let subscribers = || {
+                publisher
+                    .get_inter_process_subscription_count()
+                    .unwrap_or(0)
+            };
+            spin_while(&mut node, || subscribers() == 0, TIMEOUT)?;
+            // best effort presets may lose a message.
+            let msg = StringMsg {
+                data: name.to_owned(),
+            };
+            let mut received = vec![];
+            for _ in 0..20 {
+                publisher.publish(&msg)?;
+                received = collect_n(&mut subscription, 1, &mut node, Duration::from_millis(500));
+                if !received.is_empty() {
+                    break;
+                }
+            }
+            assert_eq!(received, vec![msg], "with the {} preset", name);
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+#[test]
+// A request is answered.
+fn rmw_service_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    RmwMatrix::new().run("rmw_service_round_trip", |_| {
+        let ctx = r2r::Context::create()?;
+        let mut node = r2r::Node::create(ctx, "testnode_rmw_service", "")?;
+        let mut requests = node.create_service::<AddTwoInts::Service>("/r2r_rmw_service")?;
+        let client = node.create_client::<AddTwoInts::Service>("/r2r_rmw_service")?;
+        let available = node.is_available(&client)?;
+        first_of(vec![Box::pin(available)], &mut node, TIMEOUT)?.1?;
+
+        let response = client.request(&AddTwoInts::Request { a: 40, b: 2 })?;
+        let request = collect_n(&mut requests, 1, &mut node, TIMEOUT)
+            .into_iter()
+            .next()
+            .expect("no request");
+        let sum = request.message.a + request.message.b;
+        request.respond(AddTwoInts::Response { sum })?;
+        let response = first_of(vec![Box::pin(response)], &mut node, TIMEOUT)?.1?;
+        assert_eq!(response.sum, 42);
+        Ok(())
+    })?;
+    Ok(())
+}
+
+#[test]
+// One goal runs to completion with feedback, another is canceled.
+fn rmw_action_lifecycle() -> Result<(), Box<dyn std::error::Error>> {
+    RmwMatrix::new().run("rmw_action_lifecycle", |_| {
+        let ctx = r2r::Context::create()?;
+        let mut node = r2r::Node::create(ctx, "testnode_rmw_action", "")?;
+        let mut goal_requests =
+            node.create_action_server::<Fibonacci::Action>("/r2r_rmw_action")?;
+        let client = node.create_action_client::<Fibonacci::Action>("/r2r_rmw_action")?;
+        let available = node.is_available(&client)?;
+        first_of(vec![Box::pin(available)], &mut node, TIMEOUT)?.1?;
+
+        let goal = client.send_goal_request(Fibonacci::Goal { order: 2 })?;
+        let req = collect_n(&mut goal_requests, 1, &mut node, TIMEOUT)
+            .into_iter()
+            .next()
+            .expect("no goal request");
+        let (mut server_goal, _cancel_requests) = req.accept()?;
+        let (_, result, mut feedback) = first_of(vec![Box::pin(goal)], &mut node, TIMEOUT)?.1?;
+        server_goal.publish_feedback(Fibonacci::Feedback {
+            sequence: vec![0, 1],
+        })?;
+        let received = collect_n(&mut feedback, 1, &mut node, TIMEOUT);
+        assert_eq!(received.len(), 1);
+        server_goal.succeed(Fibonacci::Result {
+            sequence: vec![0, 1, 1],
+        })?;
+        let (status, result) = first_of(vec![Box::pin(result)], &mut node, TIMEOUT)?.1?;
+        assert_eq!(status, GoalStatus::Succeeded);
+        assert_eq!(result.sequence, vec![0, 1, 1]);
+
+        let goal = client.send_goal_request(Fibonacci::Goal { order: 5 })?;
+        let req = collect_n(&mut goal_requests, 1, &mut node, TIMEOUT)
+            .into_iter()
+            .next()
+            .expect("no goal request");
+        let (mut server_goal, mut cancel_requests) = req.accept()?;
+        let (client_goal, result, _) = first_of(vec![Box::pin(goal)], &mut node, TIMEOUT)?.1?;
+        let cancel = client_goal.cancel()?;
+        let cancel_request = collect_n(&mut cancel_requests, 1, &mut node, TIMEOUT)
+            .into_iter()
+            .next()
+            .expect("no cancel request");
+        cancel_request.accept();
+        first_of(vec![Box::pin(cancel)], &mut node, TIMEOUT)?.1?;
+        server_goal.canceled(Fibonacci::Result { sequence: vec![0] })?;
+        let (status, _) = first_of(vec![Box::pin(result)], &mut node, TIMEOUT)?.1?;
+        assert_eq!(status, GoalStatus::Canceled);
+        Ok(())
+    })?;
+    Ok(())
+}
+
+#[test]
+// A transient local subscription that joins late gets the last message.
+fn rmw_transient_local_late_join() -> Result<(), Box<dyn std::error::Error>> {
+    RmwMatrix::new().run("rmw_transient_local_late_join", |_| {
+        let ctx = r2r::Context::create()?;
+        let mut node = r2r::Node::create(ctx, "testnode_rmw_transient_local", "")?;
+        let topic = "/r2r_rmw_transient_local";
+        let qos = QosProfile::default()
+            .keep_last(1)
+            .reliable()
+            .transient_local();
+        let publisher = node.create_publisher_with_options::<StringMsg>(
+            topic,
+            PublisherOptions {
+                qos: Some(qos.clone()),
+                ..Default::default()
+            },
+        )?;
+        publisher.publish(&StringMsg {
+            data: "first".into(),
+        })?;
+        publisher.publish(&StringMsg {
+            data: "latest".into(),
+        })?;
+
+        let mut subscription = node.subscribe_with_options::<StringMsg>(
+            topic,
+            SubscriptionOptions {
+                qos: Some(qos),
+                ..Default::default()
+            },
+        )?;
+        let received = collect_n(&mut subscription, 1, &mut node, TIMEOUT);
+        assert_eq!(
+            received,
+            vec![StringMsg {
+                data: "latest".into()
+            }]
+        );
+        Ok(())
+    })?;
+    Ok(())
+}
+
+#[test]
+// Serialized messages arrive as they were published.
+fn rmw_serialized_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    RmwMatrix::new().run("rmw_serialized_round_trip", |_| {
+        let ctx = r2r::Context::create()?;
+        let mut node = r2r::Node::create(ctx, "testnode_rmw_serialized", "")?;
+        let topic = "/r2r_rmw_serialized";
+        let type_support = MessageTypeSupport::for_type_name("std_msgs/msg/String")?;
+        let mut subscription = node.subscribe_serialized(topic, &type_support)?;
+        let publisher = node.create_publisher_serialized(topic, &type_support)?;
+        let subscribers = || {
+            publisher
+                .get_inter_process_subscription_count()
+                .unwrap_or(0)
+        };
+        spin_while(&mut node, || subscribers() == 0, TIMEOUT)?;
+
+        let msg = StringMsg {
+            data: "serialized".into(),
+        };
+        let data = r2r::serialize_message(&msg)?;
+        publisher.publish(&data)?;
+        let received = collect_n(&mut subscription, 1, &mut node, TIMEOUT);
+        assert_eq!(received, vec![data]);
+        assert_eq!(r2r::deserialize_message::<StringMsg>(&received[0])?, msg);
+        Ok(())
+    })?;
+    Ok(())
+}
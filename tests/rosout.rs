@@ -0,0 +1,44 @@
+use futures::stream::StreamExt;
+use r2r;
+use r2r::test_support::collect_n;
+use r2r::{LogSeverity, NodeOptions};
+use std::time::Duration;
+
+#[test]
+// Messages logged with the logger of a node reach `/rosout`, those of
+// a node without rosout do not.
+fn rosout_log_macros() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx.clone(), "testnode_rosout", "")?;
+    let quiet =
+        r2r::Node::create_with_options(ctx, "testnode_rosout_quiet", "", NodeOptions::minimal())?;
+    let logger = node.logger().to_owned();
+    let mut entries = node
+        .subscribe_rosout()?
+        .filter(|e| futures::future::ready(e.msg.starts_with("rosout test")));
+
+    // the subscription may not be matched with the rosout publisher yet.
+    let mut received = vec![];
+    for i in 0..20 {
+        r2r::log_info!(quiet.logger(), "rosout test quiet {}", i);
+        r2r::log_warn!(&logger, "rosout test {}", i);
+        received = collect_n(&mut entries, 1, &mut node, Duration::from_millis(500));
+        if !received.is_empty() {
+            break;
+        }
+    }
+    let entry = received.pop().expect("nothing on /rosout");
+    assert_eq!(entry.logger_name, logger);
+    assert_eq!(entry.severity, LogSeverity::Warn);
+    assert!(entry.file.ends_with("rosout.rs"));
+
+    // only the node with rosout publishes.
+    r2r::log_error!(quiet.logger(), "rosout test quiet");
+    r2r::log_error!(&logger, "rosout test loud");
+    // earlier attempts may still be on their way.
+    let received = collect_n(&mut entries, 100, &mut node, Duration::from_secs(1));
+    let msgs = received.into_iter().map(|e| e.msg).collect::<Vec<_>>();
+    assert!(msgs.iter().any(|m| m == "rosout test loud"));
+    assert!(!msgs.iter().any(|m| m.starts_with("rosout test quiet")));
+    Ok(())
+}
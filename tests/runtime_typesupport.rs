@@ -0,0 +1,146 @@
+use r2r;
+use r2r::test_support::{collect_n, first_of, spin_while};
+use r2r::{ActionClientOptions, ClientOptions, GoalStatus, TypeSupportLibrary};
+use serde_json::json;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// Whether `package` was left out of the generated types, which test.sh
+// does for example_interfaces in a second run of these tests.
+fn skipped(package: &str) -> bool {
+    std::env::var("R2R_SKIP_IDL_PACKAGES")
+        .unwrap_or_default()
+        .split(':')
+        .any(|p| p == package)
+}
+
+#[test]
+// Serialized messages published with type support loaded at runtime
+// arrive at a subscriber that uses the generated type, and the other
+// way around.
+fn runtime_typesupport_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+    let library = TypeSupportLibrary::open("std_msgs")?;
+    let string_ts = library.message("msg/String")?;
+    assert_eq!(string_ts.type_name(), "std_msgs/msg/String");
+    assert!(library.message("NoSuchMessage").is_err());
+    assert!(TypeSupportLibrary::open("r2r_no_such_package").is_err());
+
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_runtime_typesupport", "")?;
+    let publisher = node.create_publisher_serialized("/r2r_runtime_typesupport", &string_ts)?;
+    let mut typed = node.subscribe::<r2r::std_msgs::msg::String>("/r2r_runtime_typesupport")?;
+    let mut serialized = node.subscribe_serialized("/r2r_runtime_typesupport", &string_ts)?;
+    // the library is kept loaded by the entities.
+    drop(library);
+
+    spin_while(
+        &mut node,
+        || publisher.get_inter_process_subscription_count().unwrap() < 2,
+        Duration::from_secs(2),
+    )?;
+
+    // CDR: encapsulation header, string length including the nul, data.
+    let cdr = vec![0, 1, 0, 0, 6, 0, 0, 0, b'h', b'e', b'l', b'l', b'o', 0];
+    publisher.publish(&cdr)?;
+
+    let received = collect_n(&mut typed, 1, &mut node, Duration::from_secs(2));
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].data, "hello");
+
+    let received = collect_n(&mut serialized, 1, &mut node, Duration::from_secs(2));
+    assert_eq!(received.len(), 1);
+    assert_eq!(&received[0][..cdr.len() - 1], &cdr[..cdr.len() - 1]);
+    Ok(())
+}
+
+#[test]
+// A service and a client created from type support loaded at runtime
+// exchange json, without the generated types of the package.
+fn runtime_typesupport_service() -> Result<(), Box<dyn std::error::Error>> {
+    let add_two_ints = TypeSupportLibrary::open("example_interfaces")?.service("srv/AddTwoInts")?;
+    assert_eq!(
+        add_two_ints.type_name(),
+        "example_interfaces/srv/AddTwoInts"
+    );
+
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_runtime_typesupport_service", "")?;
+    if skipped("example_interfaces") {
+        let generated = node.create_client_untyped("/r2r_runtime_add", add_two_ints.type_name());
+        assert!(generated.is_err());
+    }
+    let mut requests = node.create_service_with_type_support("/r2r_runtime_add", &add_two_ints)?;
+    let client = node.create_client_with_type_support(
+        "/r2r_runtime_add",
+        &add_two_ints,
+        ClientOptions::default(),
+    )?;
+    let available = node.is_available(&client)?;
+    first_of(vec![Box::pin(available)], &mut node, TIMEOUT)?.1?;
+
+    let response = client.request(json!({ "a": 1, "b": 2 }))?;
+    let request = collect_n(&mut requests, 1, &mut node, TIMEOUT)
+        .into_iter()
+        .next()
+        .expect("no request");
+    assert_eq!(request.message, json!({ "a": 1, "b": 2 }));
+    let sum = request.message["a"].as_i64().unwrap() + request.message["b"].as_i64().unwrap();
+    request.respond(json!({ "sum": sum }))?;
+    let response = first_of(vec![Box::pin(response)], &mut node, TIMEOUT)?.1??;
+    assert_eq!(response, json!({ "sum": 3 }));
+
+    // json that does not fit the type is refused before it is sent.
+    assert!(client.request(json!({ "a": "one" })).is_err());
+    Ok(())
+}
+
+#[test]
+// An action client created from type support loaded at runtime gets
+// the feedback and the result of a goal from an rclpy action server.
+fn runtime_typesupport_action_client() -> Result<(), Box<dyn std::error::Error>> {
+    let python = std::env::var("PYTHON").unwrap_or_else(|_| "python3".to_owned());
+    let rclpy = Command::new(&python)
+        .args(&["-c", "import rclpy"])
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+    if !rclpy {
+        eprintln!("skipped, rclpy is not available");
+        return Ok(());
+    }
+    let mut server = Command::new(python)
+        .arg(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/rclpy/fibonacci_server.py"))
+        .arg("/r2r_runtime_fibonacci")
+        .spawn()?;
+
+    let fibonacci = TypeSupportLibrary::open("example_interfaces")?.action("Fibonacci")?;
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_runtime_typesupport_action", "")?;
+    let client = node.create_action_client_with_type_support(
+        "/r2r_runtime_fibonacci",
+        &fibonacci,
+        ActionClientOptions::default(),
+    )?;
+    let available = node.is_available(&client)?;
+    // python takes a while to start.
+    first_of(vec![Box::pin(available)], &mut node, 3 * TIMEOUT)?.1?;
+
+    assert!(client
+        .send_goal_request(json!({ "order": "three" }))
+        .is_err());
+    let goal = client.send_goal_request(json!({ "order": 3 }))?;
+    let (_goal, result, mut feedback) = first_of(vec![Box::pin(goal)], &mut node, TIMEOUT)?.1?;
+    let received = collect_n(&mut feedback, 1, &mut node, TIMEOUT);
+    assert_eq!(received.len(), 1);
+    assert!(received[0].as_ref().unwrap()["sequence"].is_array());
+    let (status, result) = first_of(vec![Box::pin(result)], &mut node, TIMEOUT)?.1?;
+    assert_eq!(status, GoalStatus::Succeeded);
+    assert_eq!(result?, json!({ "sequence": [0, 1, 1, 2] }));
+
+    server.kill().ok();
+    server.wait()?;
+    Ok(())
+}
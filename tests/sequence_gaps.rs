@@ -0,0 +1,66 @@
+use r2r;
+use r2r::test_support::{collect_n, spin_while};
+use r2r::{QosProfile, SubscriptionOptions};
+use std::time::Duration;
+
+// A subscription that keeps only the last message and is not spun
+// while many are published loses messages, which should show up as an
+// error event with the number of lost messages.
+#[test]
+fn sequence_gaps_are_reported() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_sequence_gaps", "")?;
+    node.set_error_logging(false);
+    let mut errors = node.error_events();
+    let topic = "/r2r_sequence_gaps";
+
+    let mut subscription = node.subscribe_with_options::<r2r::std_msgs::msg::Int32>(
+        topic,
+        SubscriptionOptions {
+            qos: Some(QosProfile::default().keep_last(1)),
+            ..Default::default()
+        },
+    )?;
+    let publisher = node.create_publisher::<r2r::std_msgs::msg::Int32>(topic)?;
+    spin_while(
+        &mut node,
+        || {
+            publisher
+                .get_inter_process_subscription_count()
+                .unwrap_or(0)
+                == 0
+        },
+        Duration::from_secs(2),
+    )?;
+
+    publisher.publish(&r2r::std_msgs::msg::Int32 { data: 0 })?;
+    spin_while(
+        &mut node,
+        || subscription.is_empty(),
+        Duration::from_secs(2),
+    )?;
+    let (_, info) = subscription.drain_with_info().pop().expect("no message");
+    if info.publication_sequence_number.is_none() {
+        // the rmw implementation does not number the messages.
+        return Ok(());
+    }
+
+    // the subscription is not spun, so all but the last are dropped.
+    for i in 1..=20 {
+        publisher.publish(&r2r::std_msgs::msg::Int32 { data: i })?;
+    }
+    std::thread::sleep(Duration::from_millis(200));
+    let event = collect_n(&mut errors, 1, &mut node, Duration::from_secs(2))
+        .pop()
+        .expect("no error event");
+    assert_eq!(event.kind, r2r::EntityKind::Subscription);
+    assert_eq!(event.name, topic);
+    assert_eq!(event.operation, r2r::SpinOperation::Take);
+    match event.error {
+        r2r::Error::MessagesLost { missed, .. } => assert!(missed > 0 && missed < 20),
+        e => panic!("unexpected error {}", e),
+    }
+    let received = subscription.drain();
+    assert_eq!(received.last().map(|m| m.data), Some(20));
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use r2r;
+use r2r::example_interfaces::srv::AddTwoInts;
+use r2r::test_support::spin_while;
+use r2r::{NodeOptions, ServiceIntrospection};
+use std::time::Duration;
+
+#[test]
+// An introspected service publishes its events on a topic of its own,
+// and distributions without introspection refuse to create it.
+fn service_introspection() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let observer = r2r::Node::create_with_options(
+        ctx.clone(),
+        "testnode_service_introspection_observer",
+        "",
+        NodeOptions::minimal(),
+    )?;
+    let mut node = r2r::Node::create(ctx, "testnode_service_introspection", "")?;
+    let options = r2r::ServiceOptions {
+        introspection: ServiceIntrospection::Contents,
+        ..Default::default()
+    };
+    let service = node
+        .create_service_with_options::<AddTwoInts::Service>("/r2r_service_introspection", options);
+    if ["foxy", "galactic", "humble"].contains(&r2r::ros_distro()) {
+        assert!(matches!(service, Err(r2r::Error::NotSupported { .. })));
+        return Ok(());
+    }
+    let _service = service?;
+
+    let has_event_topic = |node: &r2r::Node| {
+        node.get_topic_names_and_types()
+            .map(|t| t.contains_key("/r2r_service_introspection/_service_event"))
+            .unwrap_or(false)
+    };
+    spin_while(
+        &mut node,
+        || !has_event_topic(&observer),
+        Duration::from_secs(5),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use futures::stream::StreamExt;
+use r2r;
+use r2r::rcl_interfaces::srv::GetParameters;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task;
+
+#[tokio::test(flavor = "multi_thread")]
+// Requests breaking the limits get a default response without
+// reaching the service stream.
+async fn service_refuses_oversized_requests() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let node = r2r::Node::create(ctx, "testnode_service_validation", "")?;
+    let node = Arc::new(Mutex::new(node));
+    let options = r2r::ServiceOptions {
+        max_string_len: Some(5),
+        max_sequence_len: Some(2),
+        ..Default::default()
+    };
+    let mut service = node
+        .lock()
+        .unwrap()
+        .create_service_with_options::<GetParameters::Service>("/r2r_service_validation", options)?;
+    let client = node
+        .lock()
+        .unwrap()
+        .create_client::<GetParameters::Service>("/r2r_service_validation")?;
+    let service_available = node.lock().unwrap().is_available(&client)?;
+
+    let done = Arc::new(AtomicBool::new(false));
+    let spin_done = done.clone();
+    let spin_node = node.clone();
+    let spin_handle = std::thread::spawn(move || {
+        while !spin_done.load(Ordering::Relaxed) {
+            spin_node.lock().unwrap().spin_once(Duration::from_millis(10));
+        }
+    });
+
+    // answers with one value per requested name.
+    task::spawn(async move {
+        while let Some(req) = service.next().await {
+            let values = req
+                .message
+                .names
+                .iter()
+                .map(|_| r2r::rcl_interfaces::msg::ParameterValue::default())
+                .collect();
+            req.respond(GetParameters::Response { values })
+                .expect("could not send response");
+        }
+    });
+
+    service_available.await?;
+
+    let ok = GetParameters::Request {
+        names: vec!["a".into(), "b".into()],
+    };
+    assert_eq!(client.request(&ok)?.await?.values.len(), 2);
+
+    let long_string = GetParameters::Request {
+        names: vec!["too long".into()],
+    };
+    assert_eq!(client.request(&long_string)?.await?.values.len(), 0);
+
+    let long_sequence = GetParameters::Request {
+        names: vec!["a".into(), "b".into(), "c".into()],
+    };
+    assert_eq!(client.request(&long_sequence)?.await?.values.len(), 0);
+
+    let metrics = node.lock().unwrap().metrics();
+    assert_eq!(
+        metrics.refused_requests.get("/r2r_service_validation"),
+        Some(&2)
+    );
+
+    done.store(true, Ordering::Relaxed);
+    spin_handle.join().unwrap();
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use r2r;
+use r2r::std_msgs::msg::String as StringMsg;
+use r2r::test_support::{collect_n, spin_while};
+use r2r::{NodeOptions, ShutdownOptions};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[test]
+// The hooks publish a last message, which is flushed and reaches the
+// subscriber. Hooks run once, also when the node is dropped afterwards.
+fn shutdown_runs_hooks_then_flushes() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx.clone(), "testnode_shutdown", "")?;
+    let mut observer = r2r::Node::create_with_options(
+        ctx,
+        "testnode_shutdown_observer",
+        "",
+        NodeOptions::minimal(),
+    )?;
+    let mut status = observer.subscribe::<StringMsg>("/r2r_shutdown_status")?;
+    let publisher = node.create_publisher::<StringMsg>("/r2r_shutdown_status")?;
+    let subscribers = || {
+        publisher
+            .get_inter_process_subscription_count()
+            .unwrap_or(0)
+    };
+    spin_while(&mut observer, || subscribers() == 0, TIMEOUT)?;
+
+    let runs = Arc::new(AtomicUsize::new(0));
+    let hook_runs = runs.clone();
+    node.on_shutdown(move |_| {
+        hook_runs.fetch_add(1, Ordering::SeqCst);
+        publisher.publish(&StringMsg {
+            data: "going offline".into(),
+        })
+    });
+    node.on_shutdown(|_| Err(r2r::Error::RCL_RET_ERROR));
+
+    node.set_flush_grace_period(Duration::from_millis(10));
+    let report = node.shutdown(ShutdownOptions {
+        flush_timeout: Some(TIMEOUT),
+    });
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+    assert_eq!(report.hook_errors.len(), 1);
+    assert_eq!(report.flushed.len(), 1);
+    let (topic, flushed) = &report.flushed[0];
+    assert_eq!(topic, "/r2r_shutdown_status");
+    // some rmw implementations cannot tell.
+    assert!(flushed == &r2r::FlushStatus::Flushed || flushed == &r2r::FlushStatus::Unknown);
+
+    let received = collect_n(&mut status, 1, &mut observer, TIMEOUT);
+    assert_eq!(received[0].data, "going offline");
+
+    let report = node.shutdown(ShutdownOptions::default());
+    assert!(report.hook_errors.is_empty());
+    assert!(report.flushed.is_empty());
+    drop(node);
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+    Ok(())
+}
+
+#[test]
+// Hooks that shutdown never ran still run when the node is dropped.
+fn hooks_run_on_drop() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_shutdown_drop", "")?;
+    let runs = Arc::new(AtomicUsize::new(0));
+    let hook_runs = runs.clone();
+    node.on_shutdown(move |_| {
+        hook_runs.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    });
+    drop(node);
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+    Ok(())
+}
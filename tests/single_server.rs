@@ -0,0 +1,57 @@
+use r2r;
+use r2r::example_interfaces::srv::AddTwoInts;
+use r2r::test_support::first_of;
+use std::time::Duration;
+
+#[test]
+// A client that expects a single server refuses to proceed when two
+// nodes serve the service.
+fn client_detects_multiple_servers() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx.clone(), "testnode_single_server", "")?;
+    let mut other = r2r::Node::create(ctx, "testnode_single_server_stale", "")?;
+    let _service = node.create_service::<AddTwoInts::Service>("/r2r_single_server")?;
+    let _stale = other.create_service::<AddTwoInts::Service>("/r2r_single_server")?;
+
+    let options = r2r::ClientOptions {
+        expect_single_server: true,
+        ..Default::default()
+    };
+    let client =
+        node.create_client_with_options::<AddTwoInts::Service>("/r2r_single_server", options)?;
+
+    // both servers have to be discovered before the client is asked.
+    let wait_for_both = std::time::Instant::now() + Duration::from_secs(5);
+    while std::time::Instant::now() < wait_for_both {
+        let names = node.get_node_names()?;
+        if names
+            .iter()
+            .any(|n| n.fully_qualified_name() == "/testnode_single_server_stale")
+        {
+            break;
+        }
+        node.spin_once(Duration::from_millis(10));
+        other.spin_once(Duration::from_millis(0));
+    }
+
+    let available = node.is_available(&client)?;
+    let (_, result) = first_of(vec![available], &mut node, Duration::from_secs(5))?;
+    match result {
+        Err(r2r::Error::MultipleServiceServers { count, nodes }) => {
+            assert_eq!(count, 2);
+            assert_eq!(
+                nodes,
+                vec![
+                    "/testnode_single_server".to_owned(),
+                    "/testnode_single_server_stale".to_owned()
+                ]
+            );
+        }
+        r => panic!("expected MultipleServiceServers, got {:?}", r),
+    }
+    assert!(matches!(
+        client.request(&AddTwoInts::Request { a: 1, b: 2 }),
+        Err(r2r::Error::MultipleServiceServers { .. })
+    ));
+    Ok(())
+}
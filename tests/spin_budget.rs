@@ -0,0 +1,99 @@
+use futures::future::FutureExt;
+use futures::stream::StreamExt;
+use r2r;
+use r2r::test_support::spin_while;
+use std::time::Duration;
+
+#[test]
+// With a budget of one message, each spin should deliver at most one
+// message, while the rest are handled by later spins.
+fn spin_once_with_budget_limits_work() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_spin_budget", "")?;
+    let publisher = node.create_publisher::<r2r::std_msgs::msg::String>("/r2r_spin_budget")?;
+    let mut streams = Vec::new();
+    for _ in 0..3 {
+        streams.push(node.subscribe::<r2r::std_msgs::msg::String>("/r2r_spin_budget")?);
+    }
+
+    // wait until everything is connected.
+    spin_while(
+        &mut node,
+        || publisher.get_inter_process_subscription_count().unwrap() != 3,
+        Duration::from_secs(2),
+    )?;
+
+    publisher.publish(&r2r::std_msgs::msg::String { data: "hello".into() })?;
+
+    let budget = r2r::SpinBudget {
+        max_messages: 1,
+        max_duration: Duration::from_secs(1),
+    };
+    let mut received = 0;
+    for _ in 0..200 {
+        node.spin_once_with_budget(Duration::from_millis(10), budget.clone());
+        let mut received_now = 0;
+        for s in streams.iter_mut() {
+            while let Some(Some(_)) = s.next().now_or_never() {
+                received_now += 1;
+            }
+        }
+        assert!(received_now <= 1);
+        received += received_now;
+        if received == 3 {
+            break;
+        }
+    }
+    assert_eq!(received, 3);
+    Ok(())
+}
+
+#[test]
+// A service that is still waiting to be handled when the budget runs out
+// can be dropped before the next spin. The next spin should skip it
+// instead of handling a request on the destroyed service.
+fn spin_once_with_budget_skips_dropped_services() -> Result<(), Box<dyn std::error::Error>> {
+    use r2r::example_interfaces::srv::AddTwoInts;
+
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx.clone(), "testnode_spin_budget_drop", "")?;
+    let mut client_node = r2r::Node::create_with_options(
+        ctx,
+        "testnode_spin_budget_drop_client",
+        "",
+        r2r::NodeOptions::minimal(),
+    )?;
+    let mut stream = node.subscribe::<r2r::std_msgs::msg::String>("/r2r_spin_budget_drop")?;
+    let service = node.create_service::<AddTwoInts::Service>("/r2r_spin_budget_drop")?;
+    let publisher =
+        client_node.create_publisher::<r2r::std_msgs::msg::String>("/r2r_spin_budget_drop")?;
+    let client = client_node.create_client::<AddTwoInts::Service>("/r2r_spin_budget_drop")?;
+
+    let mut available = client_node.is_available(&client)?;
+    for _ in 0..200 {
+        client_node.spin_once(Duration::from_millis(10));
+        node.spin_once(Duration::from_millis(0));
+        if publisher.get_inter_process_subscription_count()? == 1
+            && (&mut available).now_or_never().is_some()
+        {
+            break;
+        }
+    }
+
+    // make both the subscription and the service ready for the same wait.
+    publisher.publish(&r2r::std_msgs::msg::String { data: "hello".into() })?;
+    let _response = client.request(&AddTwoInts::Request { a: 1, b: 2 })?;
+    std::thread::sleep(Duration::from_millis(200));
+
+    let budget = r2r::SpinBudget {
+        max_messages: 1,
+        max_duration: Duration::from_secs(1),
+    };
+    node.spin_once_with_budget(Duration::from_millis(100), budget.clone());
+    drop(service);
+    for _ in 0..10 {
+        node.spin_once_with_budget(Duration::from_millis(10), budget.clone());
+    }
+    assert!(matches!(stream.next().now_or_never(), Some(Some(_))));
+    Ok(())
+}
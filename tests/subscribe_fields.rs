@@ -0,0 +1,45 @@
+use r2r;
+use r2r::geometry_msgs::msg::PoseWithCovarianceStamped;
+use r2r::test_support::collect_n;
+use r2r::{DynamicValue, QosProfile};
+use std::time::Duration;
+
+const TOPIC: &str = "/r2r_subscribe_fields";
+
+#[test]
+// Only the selected fields of each message are delivered.
+fn subscribe_fields() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_subscribe_fields", "")?;
+    let mut fields = node.subscribe_fields::<PoseWithCovarianceStamped>(
+        TOPIC,
+        &["header.frame_id", "pose.pose.position.x"],
+        QosProfile::default(),
+    )?;
+    let publisher = node.create_publisher::<PoseWithCovarianceStamped>(TOPIC)?;
+
+    let mut msg = PoseWithCovarianceStamped::default();
+    msg.header.frame_id = "map".into();
+    msg.pose.pose.position.x = 2.5;
+    publisher.publish(&msg)?;
+
+    let received = collect_n(&mut fields, 1, &mut node, Duration::from_secs(2));
+    assert_eq!(
+        received,
+        vec![vec![
+            DynamicValue::String("map".into()),
+            DynamicValue::Float(2.5)
+        ]]
+    );
+
+    // unknown fields are rejected up front.
+    assert!(matches!(
+        node.subscribe_fields::<PoseWithCovarianceStamped>(
+            TOPIC,
+            &["pose.pose.position.w"],
+            QosProfile::default()
+        ),
+        Err(r2r::Error::InvalidFieldPath { .. })
+    ));
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use r2r;
+use r2r::test_support::{collect_n, spin_while};
+use std::time::Duration;
+
+#[test]
+// Messages can be taken synchronously, in order, and the stream
+// keeps working afterwards.
+fn subscription_drain() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_subscription_drain", "")?;
+    let mut sub = node.subscribe::<r2r::std_msgs::msg::Int32>("/r2r_subscription_drain")?;
+    let publisher = node.create_publisher::<r2r::std_msgs::msg::Int32>("/r2r_subscription_drain")?;
+
+    // let the subscription get matched.
+    for _ in 0..10 {
+        node.spin_once(Duration::from_millis(10));
+    }
+    assert!(sub.is_empty());
+    assert!(sub.drain().is_empty());
+
+    for data in 0..5 {
+        publisher.publish(&r2r::std_msgs::msg::Int32 { data })?;
+    }
+    spin_while(&mut node, || sub.len() < 5, Duration::from_secs(1))?;
+    assert_eq!(sub.len(), 5);
+    let msgs = sub.drain_with_info();
+    assert_eq!(
+        msgs.iter().map(|(m, _)| m.data).collect::<Vec<_>>(),
+        vec![0, 1, 2, 3, 4]
+    );
+    // the clocks of publisher and subscriber need not agree, but the
+    // messages of one publisher are stamped in order.
+    let stamps: Vec<i64> = msgs.iter().map(|(_, info)| info.source_timestamp).collect();
+    assert!(stamps.iter().all(|stamp| *stamp != 0));
+    assert!(stamps.windows(2).all(|w| w[0] <= w[1]));
+    assert!(sub.is_empty());
+
+    publisher.publish(&r2r::std_msgs::msg::Int32 { data: 5 })?;
+    let received = collect_n(&mut sub, 1, &mut node, Duration::from_secs(1));
+    assert_eq!(received.iter().map(|msg| msg.data).collect::<Vec<_>>(), vec![5]);
+    assert!(sub.is_empty());
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use r2r;
+use r2r::test_support::spin_while;
+use std::time::Duration;
+
+#[test]
+// Dropping the stream returned by `subscribe` should remove the
+// subscription from the node.
+fn dropped_subscription_is_removed() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_subscription_drop", "")?;
+    let publisher = node.create_publisher::<r2r::std_msgs::msg::String>("/r2r_subscription_drop")?;
+    let stream = node.subscribe::<r2r::std_msgs::msg::String>("/r2r_subscription_drop")?;
+
+    let wait_for_count = |node: &mut r2r::Node, expected: usize| {
+        spin_while(
+            node,
+            || publisher.get_inter_process_subscription_count().unwrap() != expected,
+            Duration::from_secs(2),
+        )
+        .is_ok()
+    };
+
+    assert!(wait_for_count(&mut node, 1));
+
+    // publish at high rate while dropping the stream.
+    for _ in 0..100 {
+        publisher.publish(&r2r::std_msgs::msg::String { data: "hello".into() })?;
+    }
+    drop(stream);
+
+    assert!(wait_for_count(&mut node, 0));
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use r2r;
+use r2r::std_msgs::msg::Int32;
+use r2r::test_support::spin_while;
+use r2r::{DeliverResult, MessageInfo, MessageSink, SubscriptionOptions};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Records the priority of its subscription for each message.
+struct Record(i32, Arc<Mutex<Vec<i32>>>);
+
+impl MessageSink<(Int32, MessageInfo)> for Record {
+    fn try_deliver(&self, _: (Int32, MessageInfo)) -> DeliverResult {
+        self.1.lock().unwrap().push(self.0);
+        DeliverResult::Accepted
+    }
+}
+
+#[test]
+// With messages pending on all of them, one spin handles the
+// subscriptions by decreasing priority, not in creation order.
+fn subscriptions_dispatched_by_priority() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_subscription_priority", "")?;
+    let received = Arc::new(Mutex::new(vec![]));
+    let mut publishers = vec![];
+    for (i, priority) in [-1, 10, 0].iter().enumerate() {
+        let topic = format!("/r2r_subscription_priority_{}", i);
+        node.subscribe_with_sink::<Int32>(
+            &topic,
+            SubscriptionOptions {
+                priority: *priority,
+                ..Default::default()
+            },
+            Record(*priority, received.clone()),
+        )?;
+        publishers.push(node.create_publisher::<Int32>(&topic)?);
+    }
+    let unmatched = || {
+        publishers
+            .iter()
+            .any(|p| p.get_inter_process_subscription_count().unwrap_or(0) == 0)
+    };
+    spin_while(&mut node, unmatched, Duration::from_secs(2))?;
+
+    for p in &publishers {
+        p.publish(&Int32 { data: 1 })?;
+    }
+    // give the middleware time to deliver all three before spinning.
+    std::thread::sleep(Duration::from_millis(500));
+    node.spin_once(Duration::from_secs(1));
+    assert_eq!(*received.lock().unwrap(), vec![10, 0, -1]);
+    Ok(())
+}
@@ -5,4 +5,12 @@ source /opt/ros/foxy/setup.bash
 source /opt/ros/galactic/setup.bash
 
 cd /r2r/
-/root/.cargo/bin/cargo test
\ No newline at end of file
+# e.g. --all-features, passed on from `docker run r2r_test --all-features`
+/root/.cargo/bin/cargo test "$@" || exit 1
+# again without the generated types of example_interfaces, which the
+# runtime type support tests then load at runtime.
+R2R_SKIP_IDL_PACKAGES=example_interfaces /root/.cargo/bin/cargo test --test runtime_typesupport || exit 1
+if [ "$1" == "--all-features" ]; then
+    /root/.cargo/bin/rustup component add clippy || exit 1
+    /root/.cargo/bin/cargo clippy --all-targets --all-features -- -D warnings
+fi
\ No newline at end of file
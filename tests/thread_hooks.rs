@@ -0,0 +1,56 @@
+use r2r;
+use r2r::{MultiThreadedExecutor, SubscriptionOptions, ThreadHooks};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[cfg(target_os = "linux")]
+fn thread_names() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut names = Vec::new();
+    for task in std::fs::read_dir("/proc/self/task")? {
+        let comm = std::fs::read_to_string(task?.path().join("comm"))?;
+        names.push(comm.trim_end().to_owned());
+    }
+    Ok(names)
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+// The threads r2r starts are named after the prefix of the context,
+// and the hooks run on each of them.
+fn thread_hooks_name_and_run_on_threads() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let started = Arc::new(AtomicUsize::new(0));
+    let stopped = Arc::new(AtomicUsize::new(0));
+    let start_count = started.clone();
+    let stop_count = stopped.clone();
+    ctx.set_thread_hooks(
+        ThreadHooks::default()
+            .thread_name_prefix("hooktest-")
+            .on_thread_start(move |_| {
+                start_count.fetch_add(1, Ordering::SeqCst);
+            })
+            .on_thread_stop(move |_| {
+                stop_count.fetch_add(1, Ordering::SeqCst);
+            }),
+    );
+    let mut node = r2r::Node::create(ctx.clone(), "testnode_thread_hooks", "")?;
+    let _sub = node.subscribe_with_options::<r2r::std_msgs::msg::String>(
+        "/r2r_thread_hooks",
+        SubscriptionOptions {
+            offload_conversion: true,
+            ..Default::default()
+        },
+    )?;
+    let executor = MultiThreadedExecutor::with_thread_hooks(2, ctx.thread_hooks());
+
+    let names = thread_names()?;
+    assert!(names.iter().any(|n| n == "hooktest-conver"));
+    assert!(names.iter().any(|n| n == "hooktest-execut"));
+    assert!(!names.iter().any(|n| n.starts_with("r2r-")));
+
+    drop(executor);
+    // the conversion thread may not have run its hook yet.
+    assert!(started.load(Ordering::SeqCst) >= 2);
+    assert_eq!(stopped.load(Ordering::SeqCst), 2);
+    Ok(())
+}
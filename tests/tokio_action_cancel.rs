@@ -0,0 +1,113 @@
+use futures::stream::StreamExt;
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::test_support::{first_of, spin_while};
+use std::time::Duration;
+use tokio::task;
+
+#[tokio::test(flavor = "multi_thread")]
+// A canceled goal should deliver the partial result the server sent
+// together with the canceled status.
+async fn tokio_action_canceled_with_partial_result() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_cancel", "")?;
+    let client = node.create_action_client::<Fibonacci::Action>("/r2r_action_cancel")?;
+    let mut goal_requests = node.create_action_server::<Fibonacci::Action>("/r2r_action_cancel")?;
+    let server_available = node.is_available(&client)?;
+
+    // the server waits for a cancel request and replies with what it has so far.
+    task::spawn(async move {
+        let req = goal_requests.next().await.expect("no goal request");
+        let (mut g, mut cancel) = req.accept().expect("could not accept goal");
+        let cancel_request = cancel.next().await.expect("no cancel request");
+        cancel_request.accept();
+        g.canceled(Fibonacci::Result {
+            sequence: vec![0, 1, 1],
+        })
+        .expect("could not send result");
+    });
+
+    let timeout = Duration::from_secs(10);
+    first_of(vec![Box::pin(server_available)], &mut node, timeout)?.1?;
+
+    let goal = client.send_goal_request(Fibonacci::Goal { order: 10 })?;
+    let (goal, result, _feedback) = first_of(vec![Box::pin(goal)], &mut node, timeout)?.1?;
+    first_of(vec![Box::pin(goal.cancel()?)], &mut node, timeout)?.1?;
+
+    let (status, msg) = first_of(vec![Box::pin(result)], &mut node, timeout)?.1?;
+    assert_eq!(status, r2r::GoalStatus::Canceled);
+    assert_eq!(msg.sequence, vec![0, 1, 1]);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+// An aborted goal should deliver the result the server populated,
+// e.g. the progress made before giving up.
+async fn tokio_action_aborted_with_partial_result() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_abort", "")?;
+    let client = node.create_action_client::<Fibonacci::Action>("/r2r_action_abort")?;
+    let mut goal_requests = node.create_action_server::<Fibonacci::Action>("/r2r_action_abort")?;
+    let server_available = node.is_available(&client)?;
+
+    task::spawn(async move {
+        let req = goal_requests.next().await.expect("no goal request");
+        let (mut g, _cancel) = req.accept().expect("could not accept goal");
+        g.abort(Fibonacci::Result {
+            sequence: vec![0, 1, 1, 2],
+        })
+        .expect("could not send result");
+    });
+
+    let timeout = Duration::from_secs(10);
+    first_of(vec![Box::pin(server_available)], &mut node, timeout)?.1?;
+
+    let goal = client.send_goal_request(Fibonacci::Goal { order: 10 })?;
+    let (_goal, result, _feedback) = first_of(vec![Box::pin(goal)], &mut node, timeout)?.1?;
+    let (status, msg) = first_of(vec![Box::pin(result)], &mut node, timeout)?.1?;
+    assert_eq!(status, r2r::GoalStatus::Aborted);
+    assert_eq!(msg.sequence, vec![0, 1, 1, 2]);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+// A result requested only after the goal was canceled is answered from
+// the stored result, with the canceled status.
+async fn tokio_action_result_requested_after_cancel() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_cancel_late_result", "")?;
+    let client = node.create_action_client_with_options::<Fibonacci::Action>(
+        "/r2r_action_cancel_late_result",
+        r2r::ActionClientOptions {
+            auto_request_result: false,
+            ..Default::default()
+        },
+    )?;
+    let mut goal_requests =
+        node.create_action_server::<Fibonacci::Action>("/r2r_action_cancel_late_result")?;
+    let server_available = node.is_available(&client)?;
+
+    task::spawn(async move {
+        let req = goal_requests.next().await.expect("no goal request");
+        let (mut g, mut cancel) = req.accept().expect("could not accept goal");
+        let cancel_request = cancel.next().await.expect("no cancel request");
+        cancel_request.accept();
+        g.canceled(Fibonacci::Result { sequence: vec![0] })
+            .expect("could not send result");
+    });
+
+    let timeout = Duration::from_secs(10);
+    first_of(vec![Box::pin(server_available)], &mut node, timeout)?.1?;
+
+    let goal = client.send_goal_request(Fibonacci::Goal { order: 10 })?;
+    let (goal, result, _feedback) = first_of(vec![Box::pin(goal)], &mut node, timeout)?.1?;
+    first_of(vec![Box::pin(goal.cancel()?)], &mut node, timeout)?.1?;
+    let canceled = || goal.get_status().ok() == Some(r2r::GoalStatus::Canceled);
+    spin_while(&mut node, || !canceled(), timeout)?;
+
+    // the result is only requested now.
+    let (status, msg) = first_of(vec![Box::pin(result)], &mut node, timeout)?.1?;
+    assert_eq!(status, r2r::GoalStatus::Canceled);
+    assert_eq!(msg.sequence, vec![0]);
+    Ok(())
+}
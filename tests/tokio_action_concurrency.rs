@@ -0,0 +1,73 @@
+use futures::stream::StreamExt;
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::task;
+
+const GOALS_PER_TASK: i32 = 1000;
+
+#[tokio::test(flavor = "multi_thread")]
+// Two tasks share one action client and send goals as fast as they can.
+// Every goal must get its own response and result back.
+async fn tokio_action_client_shared_between_tasks() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_concurrency", "")?;
+    let client = node.create_action_client::<Fibonacci::Action>("/r2r_action_concurrency")?;
+    let mut goal_requests =
+        node.create_action_server::<Fibonacci::Action>("/r2r_action_concurrency")?;
+    let server_available = node.is_available(&client)?;
+
+    let done = Arc::new(AtomicBool::new(false));
+    let spin_done = done.clone();
+    let spin_handle = std::thread::spawn(move || {
+        while !spin_done.load(Ordering::Relaxed) {
+            node.spin_once(std::time::Duration::from_millis(10));
+        }
+    });
+
+    // the server echoes the goal back in the result.
+    task::spawn(async move {
+        while let Some(req) = goal_requests.next().await {
+            let (mut g, _cancel) = req.accept().expect("could not accept goal");
+            let order = g.goal.order;
+            g.succeed(Fibonacci::Result {
+                sequence: vec![order],
+            })
+            .expect("could not send result");
+        }
+    });
+
+    server_available.await?;
+
+    let mut tasks = Vec::new();
+    for t in 0..2 {
+        let client = client.clone();
+        tasks.push(task::spawn(async move {
+            let mut results = 0;
+            for i in 0..GOALS_PER_TASK {
+                let order = t * GOALS_PER_TASK + i;
+                let (_goal, result, _feedback) = client
+                    .send_goal_request(Fibonacci::Goal { order })
+                    .expect("could not send goal request")
+                    .await
+                    .expect("goal rejected by server");
+                let (status, msg) = result.await.expect("no result");
+                assert_eq!(status, r2r::GoalStatus::Succeeded);
+                assert_eq!(msg.sequence, vec![order]);
+                results += 1;
+            }
+            results
+        }));
+    }
+
+    let all = futures::future::join_all(tasks);
+    let results = tokio::time::timeout(std::time::Duration::from_secs(120), all).await?;
+    for r in results {
+        assert_eq!(r?, GOALS_PER_TASK);
+    }
+
+    done.store(true, Ordering::Relaxed);
+    spin_handle.join().unwrap();
+    Ok(())
+}
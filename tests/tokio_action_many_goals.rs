@@ -0,0 +1,157 @@
+use futures::future;
+use futures::stream::StreamExt;
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use r2r::{ActionClientOptions, GoalStatus, QosProfile};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task;
+
+const GOALS: i32 = 300;
+const FEEDBACK: usize = 5;
+
+#[tokio::test(flavor = "multi_thread")]
+// Many goals in flight at once, each with feedback, all get their own
+// feedback and result.
+async fn tokio_action_many_goals() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let node = r2r::Node::create(ctx, "testnode_action_many_goals", "")?;
+    let node = Arc::new(Mutex::new(node));
+
+    let (client, available) = {
+        let mut node = node.lock().unwrap();
+        let server = node
+            .action_server_builder::<Fibonacci::Action>("/r2r_action_many_goals")
+            .qos(QosProfile::default().keep_last(1000))
+            .on_execute(|goal| async move {
+                for _ in 0..FEEDBACK {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    goal.publish_feedback(Fibonacci::Feedback {
+                        sequence: vec![goal.goal.order],
+                    })?;
+                }
+                Ok(Fibonacci::Result {
+                    sequence: vec![goal.goal.order],
+                })
+            })
+            .build()?;
+        task::spawn(server);
+        let client = node.create_action_client_with_options::<Fibonacci::Action>(
+            "/r2r_action_many_goals",
+            ActionClientOptions {
+                qos: Some(QosProfile::default().keep_last(1000)),
+                feedback_capacity: FEEDBACK,
+                ..Default::default()
+            },
+        )?;
+        let available = node.is_available(&client)?;
+        (client, available)
+    };
+
+    let done = Arc::new(AtomicBool::new(false));
+    let spin_node = node.clone();
+    let spin_done = done.clone();
+    let spin_handle = std::thread::spawn(move || {
+        while !spin_done.load(Ordering::Relaxed) {
+            spin_node
+                .lock()
+                .unwrap()
+                .spin_once(Duration::from_millis(10));
+        }
+    });
+    available.await?;
+
+    let goals = (0..GOALS).map(|order| {
+        let goal = client.send_goal_request(Fibonacci::Goal { order });
+        async move {
+            let (_goal, result, feedback) = goal?.await?;
+            let feedback: Vec<_> = feedback.collect().await;
+            let (status, msg) = result.await?;
+            Ok::<_, r2r::Error>((order, status, msg, feedback))
+        }
+    });
+    let outcomes = tokio::time::timeout(Duration::from_secs(60), future::join_all(goals)).await?;
+    for outcome in outcomes {
+        let (order, status, msg, feedback) = outcome?;
+        assert_eq!(status, GoalStatus::Succeeded);
+        assert_eq!(msg.sequence, vec![order]);
+        assert!(!feedback.is_empty());
+        assert!(feedback.iter().all(|f| f.sequence == vec![order]));
+    }
+
+    done.store(true, Ordering::Relaxed);
+    spin_handle.join().unwrap();
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+// The same with the untyped client.
+async fn tokio_action_many_goals_untyped() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let node = r2r::Node::create(ctx, "testnode_action_many_goals_untyped", "")?;
+    let node = Arc::new(Mutex::new(node));
+
+    let (client, available) = {
+        let mut node = node.lock().unwrap();
+        let server = node
+            .action_server_builder::<Fibonacci::Action>("/r2r_action_many_goals_untyped")
+            .qos(QosProfile::default().keep_last(1000))
+            .on_execute(|goal| async move {
+                for _ in 0..FEEDBACK {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    goal.publish_feedback(Fibonacci::Feedback {
+                        sequence: vec![goal.goal.order],
+                    })?;
+                }
+                Ok(Fibonacci::Result {
+                    sequence: vec![goal.goal.order],
+                })
+            })
+            .build()?;
+        task::spawn(server);
+        let client = node.create_action_client_untyped(
+            "/r2r_action_many_goals_untyped",
+            "example_interfaces/action/Fibonacci",
+        )?;
+        let available = node.is_available(&client)?;
+        (client, available)
+    };
+
+    let done = Arc::new(AtomicBool::new(false));
+    let spin_node = node.clone();
+    let spin_done = done.clone();
+    let spin_handle = std::thread::spawn(move || {
+        while !spin_done.load(Ordering::Relaxed) {
+            spin_node
+                .lock()
+                .unwrap()
+                .spin_once(Duration::from_millis(10));
+        }
+    });
+    available.await?;
+
+    let goals = (0..GOALS).map(|order| {
+        let goal = client.send_goal_request(serde_json::json!({ "order": order }));
+        async move {
+            let (_goal, result, feedback) = goal?.await?;
+            let feedback: Vec<_> = feedback.collect().await;
+            let (status, msg) = result.await?;
+            Ok::<_, r2r::Error>((order, status, msg?, feedback))
+        }
+    });
+    let outcomes = tokio::time::timeout(Duration::from_secs(60), future::join_all(goals)).await?;
+    for outcome in outcomes {
+        let (order, status, msg, feedback) = outcome?;
+        assert_eq!(status, GoalStatus::Succeeded);
+        assert_eq!(msg, serde_json::json!({ "sequence": [order] }));
+        assert!(!feedback.is_empty());
+        for f in feedback {
+            assert_eq!(f?, serde_json::json!({ "sequence": [order] }));
+        }
+    }
+
+    done.store(true, Ordering::Relaxed);
+    spin_handle.join().unwrap();
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use futures::stream::StreamExt;
+use r2r;
+use r2r::example_interfaces::action::Fibonacci;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task;
+
+#[tokio::test(flavor = "multi_thread")]
+// Goals sent with metadata should expose it on the server goal
+// handle, goals sent without should not.
+async fn tokio_action_goal_metadata() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_action_metadata", "")?;
+    let client = node.create_action_client_with_options::<Fibonacci::Action>(
+        "/r2r_action_metadata",
+        r2r::ActionClientOptions {
+            goal_metadata: true,
+            ..Default::default()
+        },
+    )?;
+    let mut goal_requests = node.create_action_server_with_options::<Fibonacci::Action>(
+        "/r2r_action_metadata",
+        r2r::ActionServerOptions {
+            goal_metadata: true,
+            ..Default::default()
+        },
+    )?;
+    let server_available = node.is_available(&client)?;
+
+    let done = Arc::new(AtomicBool::new(false));
+    let spin_done = done.clone();
+    let spin_handle = std::thread::spawn(move || {
+        while !spin_done.load(Ordering::Relaxed) {
+            node.spin_once(Duration::from_millis(10));
+        }
+    });
+
+    // the server replies with the priority it got, or -1 if there was
+    // no metadata.
+    task::spawn(async move {
+        while let Some(req) = goal_requests.next().await {
+            let (mut g, _cancel) = req.accept().expect("could not accept goal");
+            let mut priority = -1;
+            if g.goal.order == 1 {
+                // metadata is sent on its own topic and can arrive after the goal.
+                for _ in 0..100 {
+                    if let Some(m) = g.metadata() {
+                        assert_eq!(m.goal_id, g.uuid);
+                        assert_eq!(m.deadline.unwrap().sec, 42);
+                        priority = m.priority;
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            } else {
+                assert!(g.metadata().is_none());
+            }
+            g.succeed(Fibonacci::Result {
+                sequence: vec![priority],
+            })
+            .expect("could not send result");
+        }
+    });
+
+    server_available.await?;
+
+    let deadline = r2r::builtin_interfaces::msg::Time {
+        sec: 42,
+        nanosec: 0,
+    };
+    let (_goal, result, _feedback) = client
+        .goal_request_builder(Fibonacci::Goal { order: 1 })
+        .metadata(Some(deadline), 7)
+        .send()?
+        .await?;
+    let (_status, msg) = tokio::time::timeout(Duration::from_secs(10), result).await??;
+    assert_eq!(msg.sequence, vec![7]);
+
+    let (_goal, result, _feedback) = client
+        .send_goal_request(Fibonacci::Goal { order: 2 })?
+        .await?;
+    let (_status, msg) = tokio::time::timeout(Duration::from_secs(10), result).await??;
+    assert_eq!(msg.sequence, vec![-1]);
+
+    done.store(true, Ordering::Relaxed);
+    spin_handle.join().unwrap();
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use r2r;
+use r2r::test_support::spin_while;
+use r2r::NodeOptions;
+use std::time::Duration;
+
+#[test]
+// A node without publishers or subscriptions of its own sees the topics
+// of other nodes, demangled or as the middleware names them.
+fn topic_names_and_types() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let observer = r2r::Node::create_with_options(
+        ctx.clone(),
+        "testnode_topic_names_observer",
+        "",
+        NodeOptions::minimal(),
+    )?;
+    let mut node = r2r::Node::create(ctx, "testnode_topic_names", "")?;
+    let _publisher = node.create_publisher::<r2r::std_msgs::msg::String>("/r2r_topic_names")?;
+
+    let has_topic = |node: &r2r::Node| {
+        node.get_topic_names_and_types()
+            .map(|t| t.contains_key("/r2r_topic_names"))
+            .unwrap_or(false)
+    };
+    spin_while(&mut node, || !has_topic(&observer), Duration::from_secs(5))?;
+    let topics = observer.get_topic_names_and_types()?;
+    assert_eq!(
+        topics["/r2r_topic_names"],
+        vec!["std_msgs/msg/String".to_owned()]
+    );
+
+    // e.g. rt/r2r_topic_names with DDS.
+    let raw = observer.get_topic_names_and_types_no_demangle()?;
+    assert!(
+        raw.keys().any(|name| name.ends_with("r2r_topic_names")),
+        "no middleware name in {:?}",
+        raw
+    );
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use futures::stream::StreamExt;
+use r2r;
+use r2r::std_msgs::msg::{Int64, String as StringMsg};
+use r2r::{TopicRpcClient, TopicRpcServer};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task;
+
+#[tokio::test(flavor = "multi_thread")]
+// Responses that are sent in another order than the requests were made
+// still reach the right request, and unanswered requests time out.
+async fn tokio_topic_rpc() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_topic_rpc", "")?;
+    let mut server = TopicRpcServer::<Int64, StringMsg>::create(&mut node, "/r2r_topic_rpc")?;
+    let client = TopicRpcClient::<Int64, StringMsg>::create(&mut node, "/r2r_topic_rpc")?;
+    let unanswered = TopicRpcClient::<Int64, StringMsg>::create(&mut node, "/r2r_topic_rpc_none")?;
+
+    let done = Arc::new(AtomicBool::new(false));
+    let spin_done = done.clone();
+    let spin_handle = std::thread::spawn(move || {
+        while !spin_done.load(Ordering::Relaxed) {
+            node.spin_once(Duration::from_millis(10));
+        }
+    });
+    while client.server_count()? == 0 || server.client_count()? == 0 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    task::spawn(async move {
+        // answer in reverse order.
+        let first = server.next().await.expect("no request");
+        let second = server.next().await.expect("no request");
+        for request in vec![second, first] {
+            let response = StringMsg {
+                data: format!("response to {}", request.message.data),
+            };
+            request.respond(&response).expect("could not respond");
+        }
+    });
+
+    let first = client.request(&Int64 { data: 1 })?;
+    let second = client.request(&Int64 { data: 2 })?;
+    assert_eq!(client.pending_requests(), 2);
+    let (first, second) =
+        tokio::time::timeout(Duration::from_secs(5), futures::future::join(first, second)).await?;
+    assert_eq!(first?.data, "response to 1");
+    assert_eq!(second?.data, "response to 2");
+    assert_eq!(client.pending_requests(), 0);
+
+    let request =
+        unanswered.request_with_timeout(&Int64 { data: 3 }, Duration::from_millis(100))?;
+    let result = tokio::time::timeout(Duration::from_secs(5), request).await?;
+    assert!(matches!(result, Err(r2r::Error::RequestTimedOut { .. })));
+    assert_eq!(unanswered.pending_requests(), 0);
+
+    done.store(true, Ordering::Relaxed);
+    spin_handle.join().unwrap();
+    Ok(())
+}
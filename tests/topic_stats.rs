@@ -0,0 +1,117 @@
+use r2r;
+use r2r::test_support::{collect_n, spin_while};
+use std::time::Duration;
+
+#[test]
+// Publisher and subscription count the same messages, and the node
+// sums them per topic.
+fn topic_stats() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_topic_stats", "")?;
+    let options = r2r::SubscriptionOptions {
+        stats: true,
+        ..Default::default()
+    };
+    let mut sub = node
+        .subscribe_with_options::<r2r::std_msgs::msg::String>("/r2r_topic_stats", options)?;
+    let publisher = node.create_publisher_with_options::<r2r::std_msgs::msg::String>(
+        "/r2r_topic_stats",
+        r2r::PublisherOptions {
+            stats: true,
+            ..Default::default()
+        },
+    )?;
+    let plain = node.create_publisher::<r2r::std_msgs::msg::String>("/r2r_topic_stats_plain")?;
+    assert!(plain.stats().is_none());
+
+    spin_while(
+        &mut node,
+        || publisher.get_inter_process_subscription_count().unwrap() != 1,
+        Duration::from_secs(2),
+    )?;
+
+    for _ in 0..10 {
+        publisher.publish(&r2r::std_msgs::msg::String {
+            data: "hello".into(),
+        })?;
+        node.spin_once(Duration::from_millis(10));
+    }
+    spin_while(&mut node, || sub.len() < 10, Duration::from_secs(1))?;
+    assert_eq!(sub.drain().len(), 10);
+
+    let published = publisher.stats().unwrap();
+    let received = sub.stats().unwrap();
+    assert_eq!(published.messages, 10);
+    assert_eq!(received.messages, 10);
+    assert!(published.bytes >= 10 * "hello".len() as u64);
+    assert_eq!(published.bytes, received.bytes);
+    assert!(published.frequency > 0.0);
+
+    let metrics = node.metrics();
+    assert_eq!(metrics.published["/r2r_topic_stats"].messages, 10);
+    assert_eq!(metrics.published["/r2r_topic_stats"].bytes, published.bytes);
+    assert_eq!(metrics.received["/r2r_topic_stats"].messages, 10);
+    assert!(!metrics.published.contains_key("/r2r_topic_stats_plain"));
+    Ok(())
+}
+
+#[test]
+// Native, untyped and serialized subscriptions keep statistics too.
+fn topic_stats_other_subscriptions() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_topic_stats_other", "")?;
+    let options = || r2r::SubscriptionOptions {
+        stats: true,
+        ..Default::default()
+    };
+    let type_support = r2r::MessageTypeSupport::for_type_name("std_msgs/msg/String")?;
+    let mut native = node.subscribe_native_with_options::<r2r::std_msgs::msg::String>(
+        "/r2r_topic_stats_native",
+        options(),
+    )?;
+    let mut untyped = node.subscribe_untyped_with_options(
+        "/r2r_topic_stats_untyped",
+        "std_msgs/msg/String",
+        options(),
+    )?;
+    let mut serialized = node.subscribe_serialized_with_options(
+        "/r2r_topic_stats_serialized",
+        &type_support,
+        options(),
+    )?;
+    let topics = [
+        "/r2r_topic_stats_native",
+        "/r2r_topic_stats_untyped",
+        "/r2r_topic_stats_serialized",
+    ];
+    let mut publishers = vec![];
+    for topic in &topics {
+        publishers.push(node.create_publisher::<r2r::std_msgs::msg::String>(topic)?);
+    }
+    spin_while(
+        &mut node,
+        || {
+            publishers
+                .iter()
+                .any(|p| p.get_inter_process_subscription_count().unwrap() != 1)
+        },
+        Duration::from_secs(2),
+    )?;
+
+    for p in &publishers {
+        p.publish(&r2r::std_msgs::msg::String {
+            data: "hello".into(),
+        })?;
+    }
+    let timeout = Duration::from_secs(2);
+    assert_eq!(collect_n(&mut native, 1, &mut node, timeout).len(), 1);
+    assert_eq!(collect_n(&mut untyped, 1, &mut node, timeout).len(), 1);
+    assert_eq!(collect_n(&mut serialized, 1, &mut node, timeout).len(), 1);
+
+    let metrics = node.metrics();
+    for topic in &topics {
+        assert_eq!(metrics.received[*topic].messages, 1);
+        assert!(metrics.received[*topic].bytes >= "hello".len() as u64);
+    }
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use r2r;
+use r2r::test_support::collect_n;
+use r2r::SubscriptionOptions;
+use std::time::Duration;
+
+#[test]
+// A strict subscription reports a publisher of another type on its
+// topic, once.
+fn strict_type_check_reports_other_types() -> Result<(), Box<dyn std::error::Error>> {
+    let ctx = r2r::Context::create()?;
+    let mut node = r2r::Node::create(ctx, "testnode_type_check", "")?;
+    node.set_error_logging(false);
+    let mut errors = node.error_events();
+    let _sub = node.subscribe_with_options::<r2r::std_msgs::msg::Int32>(
+        "/r2r_type_check",
+        SubscriptionOptions {
+            strict_type_check: true,
+            ..Default::default()
+        },
+    )?;
+    let _lenient = node.subscribe::<r2r::std_msgs::msg::Int32>("/r2r_type_check")?;
+    let _publisher = node.create_publisher::<r2r::std_msgs::msg::String>("/r2r_type_check")?;
+
+    let events = collect_n(&mut errors, 2, &mut node, Duration::from_secs(2));
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event.kind, r2r::EntityKind::Subscription);
+    assert_eq!(
+        event.error.to_string(),
+        "Topic /r2r_type_check has type std_msgs/msg/String, not std_msgs/msg/Int32"
+    );
+    match &event.error {
+        r2r::Error::TopicTypeMismatch {
+            topic,
+            expected,
+            found,
+        } => {
+            assert_eq!(topic, "/r2r_type_check");
+            assert_eq!(expected, "std_msgs/msg/Int32");
+            assert_eq!(found, "std_msgs/msg/String");
+        }
+        e => panic!("unexpected error {}", e),
+    }
+    Ok(())
+}